@@ -0,0 +1,109 @@
+//! Search throughput, with criterion: `cargo bench --bench search [filter]`.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use muzero_rs::{
+    games::{connect_four::ConnectFour, gomoku::Gomoku, tic_tac_toe::TicTacToe},
+    mcts::{Mcts, MctsConfig},
+    Game,
+};
+
+fn config(num_simulations: usize) -> MctsConfig {
+    MctsConfig {
+        num_simulations,
+        // The same tree every run.
+        seed: Some(0),
+        ..Default::default()
+    }
+}
+
+/// The number of moves of a random game from `game`, on a clone of it: the state updates,
+/// move generation and win checks of a playout without the tree around them.
+fn playout<G: Game>(game: &G, rng: &mut StdRng, buffer: &mut Vec<G::Action>) -> u64 {
+    let mut game = game.clone();
+    let mut moves = 0;
+    while !game.done() {
+        game.get_available_moves_into(buffer);
+        game.step(buffer.choose(rng).unwrap().clone()).unwrap();
+        black_box(game.state_hash());
+        moves += 1;
+    }
+    moves
+}
+
+/// Moves per second of random games from `game`.
+fn playouts<G: Game>(c: &mut Criterion, name: &str, game: &G) {
+    // Random games vary in length, so count the moves of a fixed sequence of them.
+    let games = 100;
+    let moves = {
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..games)
+            .map(|_| playout(game, &mut rng, &mut vec![]))
+            .sum()
+    };
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Elements(moves));
+    group.bench_function("playouts", |b| {
+        let mut buffer = vec![];
+        b.iter(|| {
+            let mut rng = StdRng::seed_from_u64(0);
+            for _ in 0..games {
+                playout(game, &mut rng, &mut buffer);
+            }
+        })
+    });
+    group.finish();
+}
+
+/// Simulations per second of whole searches from `game`, at several tree sizes.
+fn search<G: Game>(c: &mut Criterion, name: &str, game: &G) {
+    let mut group = c.benchmark_group(format!("{}/search", name));
+    for simulations in [100, 1000, 10000] {
+        let mcts = Mcts::with_config(config(simulations));
+        group.throughput(Throughput::Elements(simulations as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(simulations), game, |b, game| {
+            b.iter(|| mcts.search(game))
+        });
+    }
+    group.finish();
+}
+
+fn games(c: &mut Criterion) {
+    search(c, "tictactoe", &TicTacToe::new());
+    search(c, "connect4", &ConnectFour::new());
+    search(c, "gomoku9", &Gomoku::new(9));
+    playouts(c, "tictactoe", &TicTacToe::new());
+    playouts(c, "connect4", &ConnectFour::new());
+    playouts(c, "gomoku9", &Gomoku::new(9));
+}
+
+fn tree(c: &mut Criterion) {
+    // Without playouts, a simulation is mostly the expansion of a new node.
+    let mcts = Mcts::with_config(MctsConfig {
+        max_rollout_depth: Some(0),
+        ..config(10000)
+    });
+    let game = Gomoku::new(9);
+    let mut group = c.benchmark_group("gomoku9");
+    group.throughput(Throughput::Elements(mcts.search(&game).nodes as u64));
+    group.bench_function("node-allocation", |b| b.iter(|| mcts.search(&game)));
+    group.finish();
+
+    // Three empty squares: the tree is fully expanded within a few simulations, so the rest
+    // are selection and backup through terminal states.
+    let mut game = TicTacToe::new();
+    for action in [(0, 0), (1, 1), (0, 1), (0, 2), (2, 0), (1, 0)] {
+        game.step(action).unwrap();
+    }
+    let mcts = Mcts::with_config(config(10000));
+    let mut group = c.benchmark_group("tictactoe");
+    group.throughput(Throughput::Elements(10000));
+    group.bench_function("selection", |b| b.iter(|| mcts.search(&game)));
+    group.finish();
+}
+
+criterion_group!(benches, games, tree);
+criterion_main!(benches);
@@ -0,0 +1,13 @@
+//! Generates the gRPC service of distributed self-play from proto/distributed.proto, with a
+//! vendored protoc so that building doesn't need one installed.
+
+fn main() -> std::io::Result<()> {
+    let mut config = tonic_prost_build::Config::new();
+    config
+        .protoc_executable(protoc_bin_vendored::protoc_bin_path().map_err(std::io::Error::other)?);
+    tonic_prost_build::configure().compile_with_config(
+        config,
+        &["proto/distributed.proto"],
+        &["proto"],
+    )
+}
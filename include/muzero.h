@@ -0,0 +1,46 @@
+/*
+ * The C interface of muzero-rs, for embedding the search in other programs. Link against the
+ * cdylib built by `cargo build --release --features ffi` (libmuzero_rs.so, .dylib or
+ * muzero_rs.dll).
+ *
+ * Positions are JSON arrays of the action indices played from the start, chance outcomes
+ * included, e.g. "[4, 0, 8]". Failing calls return -1 or NULL and leave a message for
+ * muzero_last_error().
+ */
+
+#ifndef MUZERO_H
+#define MUZERO_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct MuzeroEngine MuzeroEngine;
+
+/* A new engine playing `game`, a name like "tictactoe" or "gomoku:9", with `simulations`
+ * simulations per move, or NULL. Free it with muzero_engine_free(). */
+MuzeroEngine *muzero_engine_new(const char *game, size_t simulations);
+
+/* Set the position to the one after the actions of `state`. Returns 0, or -1 if the state
+ * isn't valid, keeping the position. */
+int32_t muzero_engine_set_position(MuzeroEngine *engine, const char *state);
+
+/* The index of the best action for the player to move, or -1 if the game is over or a chance
+ * event is pending. */
+int64_t muzero_engine_best_move(MuzeroEngine *engine);
+
+/* Free an engine; NULL is ignored. */
+void muzero_engine_free(MuzeroEngine *engine);
+
+/* The message of the last failed call on this thread, or NULL. It stays valid until the next
+ * failing call on the thread. */
+const char *muzero_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,136 @@
+//! Players that choose actions in a [`Game`], so that matches can pit any of them against each
+//! other.
+
+use rand::seq::SliceRandom;
+
+use crate::{
+    error::{Result, SearchError},
+    game::Game,
+    mcts::{sample_outcome, Mcts},
+    muzero::{run_mcts, MuZeroConfig},
+    network::Network,
+    random,
+};
+
+pub trait Agent<G: Game> {
+    /// The action to play in `game`, which isn't over and has a player to move.
+    fn select_action(&mut self, game: &G) -> Result<G::Action>;
+}
+
+impl<G: Game> Agent<G> for Mcts<G> {
+    fn select_action(&mut self, game: &G) -> Result<G::Action> {
+        Ok(self.try_search(game)?.action)
+    }
+}
+
+/// Plays a legal action uniformly at random.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomAgent;
+
+impl<G: Game> Agent<G> for RandomAgent {
+    fn select_action(&mut self, game: &G) -> Result<G::Action> {
+        Ok(game
+            .get_available_moves()
+            .choose(&mut random::rng())
+            .cloned()
+            .ok_or(SearchError::NoLegalMoves)?)
+    }
+}
+
+/// Plays the most visited action of a MuZero search with `network`, e.g. a trained model in
+/// a [`crate::model::ModelNetwork`], on the observations of the game.
+pub struct MuZeroAgent<N> {
+    pub network: N,
+    pub config: MuZeroConfig,
+}
+
+impl<G: Game, N: Network> Agent<G> for MuZeroAgent<N> {
+    fn select_action(&mut self, game: &G) -> Result<G::Action> {
+        let legal_actions: Vec<_> = game
+            .get_available_moves()
+            .iter()
+            .map(|action| game.action_to_index(action))
+            .collect();
+        if legal_actions.is_empty() {
+            return Err(SearchError::NoLegalMoves.into());
+        }
+        let to_play = game.player_index(&game.current_player());
+        let stats = run_mcts(
+            &self.config,
+            &self.network,
+            &game.observation(),
+            &legal_actions,
+            to_play,
+        );
+        Ok(game.index_to_action(stats.select_action(0.)))
+    }
+}
+
+/// Play `game` to its end, the player with index `i` choosing actions with `agents[i]`, and
+/// chance events sampled from their probabilities. Returns the outcome for every player:
+/// [`Game::returns`], or the sum of the rewards in single-player games.
+pub fn play<G: Game>(game: &mut G, agents: &mut [&mut dyn Agent<G>]) -> Result<Vec<f32>> {
+    let mut total_reward = 0.;
+    while !game.done() {
+        let outcomes = game.chance_outcomes();
+        let action = if outcomes.is_empty() {
+            let player = game.player_index(&game.current_player());
+            agents[player].select_action(game)?
+        } else {
+            sample_outcome(&outcomes, &mut random::rng())
+        };
+        total_reward += game.step(action)?;
+    }
+    if game.num_players() == 1 {
+        Ok(vec![total_reward])
+    } else {
+        Ok(game.returns())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games::{
+            gridworld::{Gridworld, GridworldConfig},
+            tic_tac_toe::TicTacToe,
+        },
+        network::UniformNetwork,
+    };
+
+    #[test]
+    fn test_play() {
+        let mut game = TicTacToe::new();
+        let mut mcts = Mcts::new(200);
+        let returns = play(&mut game, &mut [&mut mcts, &mut RandomAgent]).unwrap();
+        assert!(game.done());
+        assert_eq!(returns.len(), 2);
+        assert!(returns[0] >= 0., "{:?}\n{}", returns, game);
+
+        let config = GridworldConfig {
+            slip_probability: 0.2,
+            ..Default::default()
+        };
+        let mut game = Gridworld::four_by_four(config);
+        let returns = play(&mut game, &mut [&mut RandomAgent]).unwrap();
+        assert_eq!(returns.len(), 1);
+    }
+
+    #[test]
+    fn test_muzero_agent() {
+        let mut game = TicTacToe::new();
+        let mut agent = MuZeroAgent {
+            network: UniformNetwork {
+                action_space_size: 9,
+            },
+            config: MuZeroConfig {
+                num_simulations: 20,
+                ..MuZeroConfig::board_game(9, 0.3)
+            },
+        };
+        let returns = play(&mut game, &mut [&mut agent, &mut RandomAgent]).unwrap();
+        assert!(game.done());
+        assert_eq!(returns.len(), 2);
+    }
+}
@@ -0,0 +1,519 @@
+//! Matches between two agents in a two-player game, reported as an Elo difference, with
+//! optional early stopping by a sequential probability ratio test (SPRT), and round-robin
+//! tournaments between many agents.
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::fmt;
+
+use crate::{
+    agent::{self, Agent},
+    error::{ConfigError, Result},
+    game::Game,
+};
+
+/// Stop a match once it's clear whether the agent is `elo0` or `elo1` stronger than its
+/// opponent, wrongly accepting `elo1` with probability `alpha` and `elo0` with `beta`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    /// A test of `elo0` against `elo1` with 5% error rates.
+    pub fn new(elo0: f64, elo1: f64) -> Self {
+        Self {
+            elo0,
+            elo1,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+
+    /// The bounds of the log-likelihood ratio for accepting `elo0` and `elo1`.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1. - self.alpha)).ln(),
+            ((1. - self.beta) / self.alpha).ln(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SprtResult {
+    /// The agent is `elo0` stronger or less.
+    AcceptElo0,
+    /// The agent is `elo1` stronger or more.
+    AcceptElo1,
+}
+
+/// The results of the agent.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct MatchResult {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+impl MatchResult {
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The mean score of a game, counting draws as half a win.
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + 0.5 * self.draws as f64) / self.games() as f64
+    }
+
+    /// The variance of the score of a game.
+    fn variance(&self) -> f64 {
+        let score = self.score();
+        (self.wins as f64 * (1. - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / self.games() as f64
+    }
+
+    /// The Elo difference between the agent and its opponent, with the bounds of its 95%
+    /// confidence interval. They're infinite when the agent always or never won.
+    pub fn elo(&self) -> (f64, f64, f64) {
+        let score = self.score();
+        let margin = 1.96 * (self.variance() / self.games() as f64).sqrt();
+        (
+            elo(score),
+            elo((score - margin).max(0.)),
+            elo((score + margin).min(1.)),
+        )
+    }
+
+    /// The log-likelihood ratio of `sprt`'s `elo1` against `elo0`, approximating the game
+    /// scores as normally distributed.
+    pub fn llr(&self, sprt: &Sprt) -> f64 {
+        if self.games() == 0 {
+            return 0.;
+        }
+        // Estimated with a win and a loss more, so that one-sided results, which have no
+        // variance, don't look certain after a single game.
+        let variance = MatchResult {
+            wins: self.wins + 1,
+            losses: self.losses + 1,
+            ..*self
+        }
+        .variance();
+        let (s0, s1) = (expected_score(sprt.elo0), expected_score(sprt.elo1));
+        self.games() as f64 * (s1 - s0) * (2. * self.score() - s0 - s1) / (2. * variance)
+    }
+
+    /// The conclusion of `sprt`, if any yet.
+    pub fn sprt(&self, sprt: &Sprt) -> Option<SprtResult> {
+        let llr = self.llr(sprt);
+        let (lower, upper) = sprt.bounds();
+        if llr <= lower {
+            Some(SprtResult::AcceptElo0)
+        } else if llr >= upper {
+            Some(SprtResult::AcceptElo1)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for MatchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (elo, lower, upper) = self.elo();
+        write!(
+            f,
+            "wins {} draws {} losses {}, Elo {:+.1} [{:+.1}, {:+.1}]",
+            self.wins, self.draws, self.losses, elo, lower, upper
+        )
+    }
+}
+
+/// The expected score against an opponent `elo` weaker.
+fn expected_score(elo: f64) -> f64 {
+    1. / (1. + 10f64.powf(-elo / 400.))
+}
+
+/// The Elo difference with an expected score of `score`.
+fn elo(score: f64) -> f64 {
+    // Not -400 * log10(1 / score - 1), which is -0 for even matches.
+    400. * (score / (1. - score)).log10()
+}
+
+/// Play up to `games` games of `new_game` between `agent` and `opponent`, the agent moving
+/// first in every other game, and stopping early once `sprt` concludes. `on_game` sees the
+/// results after every game.
+pub fn run<G: Game>(
+    new_game: impl Fn() -> G,
+    agent: &mut dyn Agent<G>,
+    opponent: &mut dyn Agent<G>,
+    games: usize,
+    sprt: Option<&Sprt>,
+    mut on_game: impl FnMut(&MatchResult),
+) -> Result<MatchResult> {
+    let mut result = MatchResult::default();
+    for i in 0..games {
+        let mut game = new_game();
+        if game.num_players() != 2 {
+            return Err(ConfigError::Invalid("matches need a two-player game".to_string()).into());
+        }
+        let seat = i % 2;
+        let returns = if seat == 0 {
+            agent::play(&mut game, &mut [&mut *agent, &mut *opponent])?
+        } else {
+            agent::play(&mut game, &mut [&mut *opponent, &mut *agent])?
+        };
+        match returns[seat] {
+            r if r > 0. => result.wins += 1,
+            r if r < 0. => result.losses += 1,
+            _ => result.draws += 1,
+        }
+        on_game(&result);
+        if sprt.is_some_and(|sprt| result.sprt(sprt).is_some()) {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+/// The results of a round-robin tournament, `results[i][j]` being those of agent `i` against
+/// agent `j`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tournament {
+    pub names: Vec<String>,
+    pub results: Vec<Vec<MatchResult>>,
+}
+
+impl Tournament {
+    /// The total results of agent `i`.
+    pub fn total(&self, i: usize) -> MatchResult {
+        self.results[i]
+            .iter()
+            .fold(MatchResult::default(), |total, result| MatchResult {
+                wins: total.wins + result.wins,
+                draws: total.draws + result.draws,
+                losses: total.losses + result.losses,
+            })
+    }
+
+    /// Elo ratings fitting every result, averaging 0. Every pairing counts a draw more, so
+    /// that agents who never lost or never won still get finite ratings.
+    pub fn ratings(&self) -> Vec<f64> {
+        let n = self.names.len();
+        if n < 2 {
+            return vec![0.; n];
+        }
+        // Bradley-Terry strengths, fitted by minorization-maximization.
+        let mut strengths = vec![1f64; n];
+        for _ in 0..1000 {
+            let mut next: Vec<f64> = (0..n)
+                .map(|i| {
+                    let (mut points, mut denominator) = (0., 0.);
+                    for j in (0..n).filter(|&j| j != i) {
+                        let result = &self.results[i][j];
+                        points += result.wins as f64 + 0.5 * result.draws as f64 + 0.5;
+                        denominator += (result.games() + 1) as f64 / (strengths[i] + strengths[j]);
+                    }
+                    points / denominator
+                })
+                .collect();
+            let mean = next.iter().map(|s| s.ln()).sum::<f64>() / n as f64;
+            next.iter_mut().for_each(|s| *s /= mean.exp());
+            strengths = next;
+        }
+        strengths.iter().map(|s| 400. * s.log10()).collect()
+    }
+
+    /// The indices of the agents from the highest of `ratings` down.
+    pub fn ladder(&self, ratings: &[f64]) -> Vec<usize> {
+        let mut ladder: Vec<usize> = (0..self.names.len()).collect();
+        ladder.sort_by(|&a, &b| ratings[b].total_cmp(&ratings[a]));
+        ladder
+    }
+
+    /// The ladder, one agent per row with its rating, total score and results as
+    /// `wins-draws-losses` against every agent in the same order.
+    pub fn to_csv(&self) -> String {
+        let ratings = self.ratings();
+        let ladder = self.ladder(&ratings);
+        let mut csv = "rank,agent,rating,score".to_string();
+        for &i in &ladder {
+            csv += &format!(",{}", csv_field(&self.names[i]));
+        }
+        csv.push('\n');
+        for (rank, &i) in ladder.iter().enumerate() {
+            csv += &format!(
+                "{},{},{:.1},{:.3}",
+                rank + 1,
+                csv_field(&self.names[i]),
+                ratings[i],
+                self.total(i).score()
+            );
+            for &j in &ladder {
+                let result = &self.results[i][j];
+                if i == j {
+                    csv.push(',');
+                } else {
+                    csv += &format!(",{}-{}-{}", result.wins, result.draws, result.losses);
+                }
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// `field`, quoted if it contains a separator or a quote.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+struct LadderEntry<'a> {
+    agent: &'a str,
+    rating: f64,
+    #[serde(flatten)]
+    result: MatchResult,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+struct Pairing<'a> {
+    agent: &'a str,
+    opponent: &'a str,
+    #[serde(flatten)]
+    result: MatchResult,
+}
+
+/// The ladder with the total results of every agent, and the results of every pairing.
+#[cfg(feature = "serde")]
+impl Serialize for Tournament {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Report<'a> {
+            ladder: Vec<LadderEntry<'a>>,
+            results: Vec<Pairing<'a>>,
+        }
+        let ratings = self.ratings();
+        let ladder = self
+            .ladder(&ratings)
+            .into_iter()
+            .map(|i| LadderEntry {
+                agent: &self.names[i],
+                rating: ratings[i],
+                result: self.total(i),
+            })
+            .collect();
+        let mut results = vec![];
+        for i in 0..self.names.len() {
+            for j in i + 1..self.names.len() {
+                results.push(Pairing {
+                    agent: &self.names[i],
+                    opponent: &self.names[j],
+                    result: self.results[i][j],
+                });
+            }
+        }
+        Report { ladder, results }.serialize(serializer)
+    }
+}
+
+/// Play `games` games between every two of the named `agents`, the first of a pairing moving
+/// first in every other game. `on_match` sees the names and results of every pairing.
+pub fn round_robin<G: Game>(
+    new_game: impl Fn() -> G,
+    agents: &mut [(String, Box<dyn Agent<G>>)],
+    games: usize,
+    mut on_match: impl FnMut(&str, &str, &MatchResult),
+) -> Result<Tournament> {
+    let mut pairings = vec![];
+    for j in 1..agents.len() {
+        let (first, second) = agents.split_at_mut(j);
+        let (opponent_name, opponent) = &mut second[0];
+        for (i, (name, agent)) in first.iter_mut().enumerate() {
+            let result = run(
+                &new_game,
+                agent.as_mut(),
+                opponent.as_mut(),
+                games,
+                None,
+                |_| {},
+            )?;
+            on_match(name, opponent_name, &result);
+            pairings.push((i, j, result));
+        }
+    }
+    let mut results = vec![vec![MatchResult::default(); agents.len()]; agents.len()];
+    for (i, j, result) in pairings {
+        results[i][j] = result;
+        results[j][i] = MatchResult {
+            wins: result.losses,
+            draws: result.draws,
+            losses: result.wins,
+        };
+    }
+    Ok(Tournament {
+        names: agents.iter().map(|(name, _)| name.clone()).collect(),
+        results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agent::RandomAgent,
+        games::{nim::Nim, tic_tac_toe::TicTacToe},
+        mcts::Mcts,
+    };
+
+    #[test]
+    fn test_elo() {
+        let result = MatchResult {
+            wins: 30,
+            draws: 40,
+            losses: 30,
+        };
+        let (elo, lower, upper) = result.elo();
+        assert_eq!(elo, 0.);
+        assert_eq!(format!("{:+.1}", elo), "+0.0");
+        assert!(lower < 0. && upper > 0. && (upper + lower).abs() < 1e-9);
+
+        let result = MatchResult {
+            wins: 75,
+            draws: 0,
+            losses: 25,
+        };
+        let (elo, lower, upper) = result.elo();
+        assert!((elo - 190.8).abs() < 0.1, "{}", elo);
+        assert!(lower < elo && elo < upper);
+
+        let result = MatchResult {
+            wins: 10,
+            ..Default::default()
+        };
+        assert_eq!(result.elo().0, f64::INFINITY);
+    }
+
+    #[test]
+    fn test_sprt() {
+        let sprt = Sprt::new(0., 50.);
+        let even = MatchResult {
+            wins: 400,
+            draws: 200,
+            losses: 400,
+        };
+        assert_eq!(even.sprt(&sprt), Some(SprtResult::AcceptElo0));
+        let strong = MatchResult {
+            wins: 60,
+            draws: 20,
+            losses: 20,
+        };
+        assert_eq!(strong.sprt(&sprt), Some(SprtResult::AcceptElo1));
+        let early = MatchResult {
+            wins: 3,
+            draws: 1,
+            losses: 2,
+        };
+        assert_eq!(early.sprt(&sprt), None);
+        assert_eq!(MatchResult::default().llr(&sprt), 0.);
+    }
+
+    #[test]
+    fn test_run() {
+        let mut mcts = Mcts::new(200);
+        let mut reported = 0;
+        let sprt = Sprt::new(0., 200.);
+        let result = run(
+            TicTacToe::new,
+            &mut mcts,
+            &mut RandomAgent,
+            100,
+            Some(&sprt),
+            |result| reported = result.games(),
+        )
+        .unwrap();
+        assert_eq!(reported, result.games());
+        assert!(result.games() < 100, "{:?}", result);
+        assert_eq!(result.sprt(&sprt), Some(SprtResult::AcceptElo1));
+
+        let nim = || Nim::new(vec![1]);
+        let result = run(nim, &mut RandomAgent, &mut RandomAgent, 4, None, |_| {}).unwrap();
+        // Whoever moves first takes the last object.
+        assert_eq!(result.wins, 2);
+        assert_eq!(result.losses, 2);
+    }
+
+    #[test]
+    fn test_tournament() {
+        let result = |wins, draws, losses| MatchResult {
+            wins,
+            draws,
+            losses,
+        };
+        let tournament = Tournament {
+            names: vec![
+                "weak".to_string(),
+                "strong".to_string(),
+                "mid, v2".to_string(),
+            ],
+            results: vec![
+                vec![result(0, 0, 0), result(0, 0, 10), result(2, 2, 6)],
+                vec![result(10, 0, 0), result(0, 0, 0), result(6, 2, 2)],
+                vec![result(6, 2, 2), result(2, 2, 6), result(0, 0, 0)],
+            ],
+        };
+        let ratings = tournament.ratings();
+        assert!(
+            ratings[1] > ratings[2] && ratings[2] > ratings[0],
+            "{:?}",
+            ratings
+        );
+        assert!(ratings.iter().sum::<f64>().abs() < 1e-6);
+        assert!(ratings.iter().all(|r| r.is_finite()));
+
+        let csv = tournament.to_csv();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], "rank,agent,rating,score,strong,\"mid, v2\",weak");
+        assert!(lines[1].starts_with("1,strong,"), "{}", csv);
+        assert!(lines[1].ends_with(",,6-2-2,10-0-0"), "{}", csv);
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_value(&tournament).unwrap();
+            assert_eq!(json["ladder"][0]["agent"], "strong");
+            assert_eq!(json["ladder"][0]["wins"], 16);
+            assert_eq!(json["results"].as_array().unwrap().len(), 3);
+        }
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let mut agents: Vec<(String, Box<dyn Agent<TicTacToe>>)> = vec![
+            ("random".to_string(), Box::new(RandomAgent)),
+            ("mcts".to_string(), Box::new(Mcts::new(200))),
+            ("random 2".to_string(), Box::new(RandomAgent)),
+        ];
+        let mut matches = 0;
+        let tournament = round_robin(TicTacToe::new, &mut agents, 4, |_, _, result| {
+            assert_eq!(result.games(), 4);
+            matches += 1;
+        })
+        .unwrap();
+        assert_eq!(matches, 3);
+        assert_eq!(
+            tournament.results[0][1].wins,
+            tournament.results[1][0].losses
+        );
+        assert_eq!(tournament.total(1).games(), 8);
+    }
+}
@@ -0,0 +1,187 @@
+//! Opening books: known replies to positions, played without searching.
+//!
+//! A book file lists one line of play per line: the indices of the actions from the start of
+//! the game, then the index of the book reply to the position they reach. Blank lines and
+//! lines starting with `#` are skipped:
+//!
+//! ```text
+//! # TicTacToe: take the center, and answer a corner with the opposite corner.
+//! 4
+//! 4 0 8
+//! ```
+
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::{
+    agent::Agent,
+    error::{Context, IoError, ParseError, Result},
+    game::Game,
+};
+
+/// Book replies by [`Game::state_hash`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpeningBook {
+    moves: HashMap<u64, usize>,
+}
+
+impl OpeningBook {
+    pub fn load<G: Game>(path: &Path, new_game: impl Fn() -> G) -> Result<Self> {
+        let text = fs::read_to_string(path).map_err(IoError::path("read", path))?;
+        Self::parse(&text, new_game).with_context(|| format!("in {}", path.display()))
+    }
+
+    pub fn parse<G: Game>(text: &str, new_game: impl Fn() -> G) -> Result<Self> {
+        let mut book = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let actions = line
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<Vec<usize>, _>>()
+                .with_context(|| format!("on line {}", i + 1))?;
+            let (&reply, moves) = actions.split_last().expect("the line isn't empty");
+            let mut game = new_game();
+            for &action in moves {
+                if action >= game.action_space_size() {
+                    return Err(ParseError::Invalid(format!(
+                        "on line {}: invalid action {}",
+                        i + 1,
+                        action
+                    ))
+                    .into());
+                }
+                game.step(game.index_to_action(action))
+                    .with_context(|| format!("on line {}", i + 1))?;
+            }
+            if !game
+                .legal_action_mask()
+                .get(reply)
+                .copied()
+                .unwrap_or(false)
+            {
+                return Err(ParseError::Invalid(format!(
+                    "on line {}: the reply {} isn't legal",
+                    i + 1,
+                    reply
+                ))
+                .into());
+            }
+            book.insert(&game, reply);
+        }
+        Ok(book)
+    }
+
+    /// Reply with the action with index `action` in `game`.
+    pub fn insert<G: Game>(&mut self, game: &G, action: usize) {
+        self.moves.insert(game.state_hash(), action);
+    }
+
+    /// The book reply in `game`, if it has one and it's legal.
+    pub fn get<G: Game>(&self, game: &G) -> Option<G::Action> {
+        let &action = self.moves.get(&game.state_hash())?;
+        // Guard against hash collisions with positions outside the book.
+        let legal = game
+            .legal_action_mask()
+            .get(action)
+            .copied()
+            .unwrap_or(false);
+        legal.then(|| game.index_to_action(action))
+    }
+
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+}
+
+/// Plays book replies and forced moves right away, and asks `agent` otherwise.
+pub struct BookAgent<A> {
+    pub book: Option<OpeningBook>,
+    pub agent: A,
+}
+
+impl<G: Game, A: Agent<G>> Agent<G> for BookAgent<A> {
+    fn select_action(&mut self, game: &G) -> Result<G::Action> {
+        let mut moves = game.get_available_moves();
+        if moves.len() == 1 {
+            return Ok(moves.remove(0));
+        }
+        match self.book.as_ref().and_then(|book| book.get(game)) {
+            Some(action) => Ok(action),
+            None => self.agent.select_action(game),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::tic_tac_toe::TicTacToe;
+
+    /// Fails the test if it is ever asked.
+    struct Unreachable;
+
+    impl<G: Game> Agent<G> for Unreachable {
+        fn select_action(&mut self, _game: &G) -> Result<G::Action> {
+            Err(ParseError::Invalid("the agent was asked".to_string()).into())
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let book = OpeningBook::parse("# center\n4\n\n4 0 8\n", TicTacToe::new).unwrap();
+        assert_eq!(book.len(), 2);
+        let mut game = TicTacToe::new();
+        assert_eq!(book.get(&game), Some((1, 1)));
+        game.step((1, 1)).unwrap();
+        assert_eq!(book.get(&game), None);
+        game.step((0, 0)).unwrap();
+        assert_eq!(book.get(&game), Some((2, 2)));
+
+        assert!(OpeningBook::parse("4 4", TicTacToe::new).is_err());
+        assert!(OpeningBook::parse("4 x", TicTacToe::new).is_err());
+        assert!(OpeningBook::parse("9", TicTacToe::new).is_err());
+    }
+
+    #[test]
+    fn test_book_agent() {
+        let mut agent = BookAgent {
+            book: Some(OpeningBook::parse("4", TicTacToe::new).unwrap()),
+            agent: Unreachable,
+        };
+        assert_eq!(agent.select_action(&TicTacToe::new()).unwrap(), (1, 1));
+        let mut game = TicTacToe::new();
+        game.step((1, 1)).unwrap();
+        assert!(agent.select_action(&game).is_err());
+    }
+
+    #[test]
+    fn test_forced_move() {
+        let mut game = TicTacToe::new();
+        // Eight moves without a winner: only the bottom right is left.
+        for action in [
+            (0, 0),
+            (0, 1),
+            (0, 2),
+            (1, 1),
+            (1, 0),
+            (1, 2),
+            (2, 1),
+            (2, 0),
+        ] {
+            game.step(action).unwrap();
+        }
+        assert!(!game.done(), "{}", game);
+        let mut agent = BookAgent {
+            book: None,
+            agent: Unreachable,
+        };
+        assert_eq!(agent.select_action(&game).unwrap(), (2, 2));
+    }
+}
@@ -0,0 +1,285 @@
+//! A [`Model`] on burn, behind the `burn` feature, in pure Rust: ndarray on the CPU, and any
+//! GPU through wgpu with the `burn-wgpu` feature.
+//!
+//! The model is the same small fully connected network as the [libtorch](crate::torch) one:
+//! the representation and the dynamics are a linear layer and a ReLU each, the dynamics
+//! taking the hidden state and the action one-hot, and the reward, policy and value are
+//! linear heads. It evaluates positions and saves and loads its weights; training it with
+//! burn's autodiff isn't implemented yet.
+
+use burn::{
+    module::{Module, ModuleMapper, ModuleVisitor, Param},
+    nn::{Linear, LinearConfig},
+    tensor::{activation::relu, backend::Backend, Tensor, TensorData},
+};
+use std::collections::HashMap;
+
+use crate::{
+    checkpoint::{self, Tensors},
+    error::{ConfigError, Result},
+    model::{Device, Model},
+};
+
+/// The backends the model runs on, and the devices each of them has.
+pub trait BurnBackend: Backend {
+    /// The device of the backend for `device`, if it can run there.
+    fn device(device: Device) -> Option<Self::Device>;
+}
+
+impl BurnBackend for burn::backend::NdArray {
+    fn device(device: Device) -> Option<Self::Device> {
+        match device {
+            Device::Cpu => Some(Default::default()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "burn-wgpu")]
+impl BurnBackend for burn::backend::Wgpu {
+    fn device(device: Device) -> Option<Self::Device> {
+        use burn::backend::wgpu::WgpuDevice;
+        match device {
+            Device::Cpu => Some(WgpuDevice::Cpu),
+            Device::Wgpu => Some(WgpuDevice::DefaultDevice),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Module, Debug)]
+struct Net<B: Backend> {
+    representation: Linear<B>,
+    dynamics: Linear<B>,
+    reward: Linear<B>,
+    policy: Linear<B>,
+    value: Linear<B>,
+}
+
+pub struct BurnModel<B: BurnBackend> {
+    net: Net<B>,
+    action_space_size: usize,
+    device: Device,
+    backend_device: B::Device,
+}
+
+impl<B: BurnBackend> BurnModel<B> {
+    /// A freshly initialized model on the CPU.
+    pub fn new(observation_size: usize, hidden_size: usize, action_space_size: usize) -> Self {
+        let backend_device = B::device(Device::Cpu).expect("every backend runs on the CPU");
+        let linear = |inputs: usize, outputs: usize| {
+            LinearConfig::new(inputs, outputs).init(&backend_device)
+        };
+        Self {
+            net: Net {
+                representation: linear(observation_size, hidden_size),
+                dynamics: linear(hidden_size + action_space_size, hidden_size),
+                reward: linear(hidden_size, 1),
+                policy: linear(hidden_size, action_space_size),
+                value: linear(hidden_size, 1),
+            },
+            action_space_size,
+            device: Device::Cpu,
+            backend_device,
+        }
+    }
+
+    /// `data` as a batch of one on the device of the model.
+    fn input(&self, data: &[f32]) -> Tensor<B, 2> {
+        Tensor::from_data(
+            TensorData::new(data.to_vec(), [1, data.len()]),
+            &self.backend_device,
+        )
+    }
+}
+
+/// The values of `tensor`, flattened, as f32.
+fn output<B: Backend, const D: usize>(tensor: Tensor<B, D>) -> Vec<f32> {
+    tensor
+        .into_data()
+        .convert::<f32>()
+        .to_vec()
+        .expect("the data is f32")
+}
+
+/// Collects the weights under the dotted names of the fields that hold them.
+struct Collector {
+    path: Vec<String>,
+    tensors: Tensors,
+}
+
+impl<B: Backend> ModuleVisitor<B> for Collector {
+    fn enter_module(&mut self, name: &str, _container_type: &str) {
+        self.path.push(name.to_string());
+    }
+
+    fn exit_module(&mut self, _name: &str, _container_type: &str) {
+        self.path.pop();
+    }
+
+    fn visit_float<const D: usize>(&mut self, param: &Param<Tensor<B, D>>) {
+        let tensor = param.val();
+        let shape = tensor.dims().to_vec();
+        let tensor =
+            checkpoint::Tensor::new(shape, output(tensor)).expect("the values fill the shape");
+        self.tensors.push((self.path.join("."), tensor));
+    }
+}
+
+/// Replaces the weights with the tensors of the same names, which `load_tensors` checked.
+struct Loader<'a> {
+    path: Vec<String>,
+    tensors: HashMap<&'a str, &'a checkpoint::Tensor>,
+}
+
+impl<B: Backend> ModuleMapper<B> for Loader<'_> {
+    fn enter_module(&mut self, name: &str, _container_type: &str) {
+        self.path.push(name.to_string());
+    }
+
+    fn exit_module(&mut self, _name: &str, _container_type: &str) {
+        self.path.pop();
+    }
+
+    fn map_float<const D: usize>(&mut self, param: Param<Tensor<B, D>>) -> Param<Tensor<B, D>> {
+        let tensor = self.tensors[self.path.join(".").as_str()];
+        param.map(|old| {
+            let data = TensorData::new(tensor.data.clone(), tensor.shape.clone());
+            Tensor::from_data(data, &old.device())
+        })
+    }
+}
+
+impl<B: BurnBackend> Model for BurnModel<B> {
+    fn forward_representation(&self, observation: &[f32]) -> Vec<f32> {
+        output(relu(
+            self.net.representation.forward(self.input(observation)),
+        ))
+    }
+
+    fn forward_dynamics(&self, hidden_state: &[f32], action: usize) -> (Vec<f32>, f32) {
+        let mut one_hot = vec![0.; self.action_space_size];
+        one_hot[action] = 1.;
+        let input = Tensor::cat(vec![self.input(hidden_state), self.input(&one_hot)], 1);
+        let next = relu(self.net.dynamics.forward(input));
+        let reward = output(self.net.reward.forward(next.clone()))[0];
+        (output(next), reward)
+    }
+
+    fn forward_prediction(&self, hidden_state: &[f32]) -> (Vec<f32>, f32) {
+        let hidden_state = self.input(hidden_state);
+        (
+            output(self.net.policy.forward(hidden_state.clone())),
+            output(self.net.value.forward(hidden_state))[0],
+        )
+    }
+
+    fn tensors(&self) -> Tensors {
+        let mut collector = Collector {
+            path: vec![],
+            tensors: vec![],
+        };
+        self.net.visit(&mut collector);
+        collector.tensors.sort_by(|(a, _), (b, _)| a.cmp(b));
+        collector.tensors
+    }
+
+    fn load_tensors(&mut self, tensors: &Tensors) -> Result<()> {
+        let shapes: HashMap<_, _> = self
+            .tensors()
+            .into_iter()
+            .map(|(name, tensor)| (name, tensor.shape))
+            .collect();
+        if tensors.len() != shapes.len() {
+            return Err(ConfigError::Invalid(format!(
+                "expected {} tensors, got {}",
+                shapes.len(),
+                tensors.len()
+            ))
+            .into());
+        }
+        for (name, tensor) in tensors {
+            let shape = shapes.get(name).ok_or_else(|| {
+                ConfigError::Invalid(format!("the model has no tensor `{}`", name))
+            })?;
+            if &tensor.shape != shape {
+                return Err(ConfigError::Invalid(format!(
+                    "tensor `{}` is {:?}, expected {:?}",
+                    name, tensor.shape, shape
+                ))
+                .into());
+            }
+        }
+        let mut loader = Loader {
+            path: vec![],
+            tensors: tensors
+                .iter()
+                .map(|(name, tensor)| (name.as_str(), tensor))
+                .collect(),
+        };
+        self.net = self.net.clone().map(&mut loader);
+        Ok(())
+    }
+
+    fn device(&self) -> Device {
+        self.device
+    }
+
+    fn to_device(&mut self, device: Device) -> Result<()> {
+        let Some(backend_device) = B::device(device) else {
+            return Err(ConfigError::Unsupported(format!(
+                "the burn backend can't run on {}",
+                device
+            ))
+            .into());
+        };
+        self.net = self.net.clone().to_device(&backend_device);
+        self.backend_device = backend_device;
+        self.device = device;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        model::{ModelNetwork, Precision},
+        network::Network,
+    };
+    use burn::backend::NdArray;
+
+    #[test]
+    fn test_model() {
+        let mut model = BurnModel::<NdArray>::new(4, 8, 3);
+        let tensors = model.tensors();
+        let names: Vec<_> = tensors.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names[..2], ["dynamics.bias", "dynamics.weight"]);
+        assert_eq!(tensors[1].1.shape, [11, 8]);
+        let zeros: Tensors = tensors
+            .into_iter()
+            .map(|(name, tensor)| {
+                let zeros = vec![0.; tensor.data.len()];
+                (name, checkpoint::Tensor::new(tensor.shape, zeros).unwrap())
+            })
+            .collect();
+        model.load_tensors(&zeros).unwrap();
+        assert_eq!(model.tensors(), zeros);
+        let network = ModelNetwork(model);
+        let output = network.initial_inference(&[1., 2., 3., 4.]);
+        assert_eq!(output.hidden_state, [0.; 8]);
+        assert_eq!(output.policy_logits, [0.; 3]);
+        let output = network.recurrent_inference(&output.hidden_state, 2);
+        assert_eq!(output.reward, 0.);
+
+        let mut model = network.0;
+        assert!(model.load_tensors(&zeros[1..].to_vec()).is_err());
+        let mut transposed = zeros.clone();
+        transposed[1].1 = checkpoint::Tensor::new(vec![8, 11], vec![0.; 88]).unwrap();
+        assert!(model.load_tensors(&transposed).is_err());
+        assert!(model.to_device(Device::Wgpu).is_err());
+        assert!(model.set_precision(Precision::F16).is_err());
+        model.to_device(Device::Cpu).unwrap();
+        assert_eq!(model.forward_representation(&[1., 2., 3., 4.]).len(), 8);
+    }
+}
@@ -0,0 +1,338 @@
+//! A cache of network evaluations, so that positions searched before, like the root of a
+//! position searched again or an opening every self-play game goes through, don't run the
+//! network again.
+//!
+//! Evaluations are keyed by a hash of exactly what the network sees: the observation for an
+//! initial inference, the hidden state and the action for a recurrent one. [`Game::state_hash`]
+//! can't be the key, as the search only knows it at the root and the observation may hold
+//! more than the state, e.g. stacked past frames. A repeated root gives the same hidden state,
+//! so the evaluations below it are found again too.
+//!
+//! [`Game::state_hash`]: crate::Game::state_hash
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use crate::{
+    network::{Network, NetworkOutput},
+    zobrist::fnv1a,
+};
+
+/// The lookups of a [`CachedNetwork`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        self.hits as f64 / (self.hits + self.misses).max(1) as f64
+    }
+
+    /// The lookups since `earlier`, counts of the same cache.
+    pub fn since(&self, earlier: &CacheStats) -> CacheStats {
+        CacheStats {
+            hits: self.hits - earlier.hits,
+            misses: self.misses - earlier.misses,
+        }
+    }
+}
+
+/// A map from hashes to values, of at most `capacity` entries, which drops the least recently
+/// used one to make room.
+pub struct LruCache<V> {
+    capacity: usize,
+    /// Every value with the time it was last used.
+    entries: HashMap<u64, (V, u64)>,
+    /// The keys by the time they were last used.
+    by_use: BTreeMap<u64, u64>,
+    time: u64,
+}
+
+impl<V: Clone> LruCache<V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            by_use: BTreeMap::new(),
+            time: 0,
+        }
+    }
+
+    pub fn get(&mut self, key: u64) -> Option<V> {
+        let (value, used) = self.entries.get_mut(&key)?;
+        self.by_use.remove(used);
+        self.time += 1;
+        *used = self.time;
+        self.by_use.insert(self.time, key);
+        Some(value.clone())
+    }
+
+    pub fn insert(&mut self, key: u64, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.time += 1;
+        if let Some((_, used)) = self.entries.insert(key, (value, self.time)) {
+            self.by_use.remove(&used);
+        } else if self.entries.len() > self.capacity {
+            let (_, oldest) = self.by_use.pop_first().expect("the cache isn't empty");
+            self.entries.remove(&oldest);
+        }
+        self.by_use.insert(self.time, key);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+struct Cache {
+    outputs: LruCache<NetworkOutput>,
+    stats: CacheStats,
+}
+
+/// Remembers the outputs of the wrapped network in an [`LruCache`], e.g. for one self-play
+/// actor across its searches and games. Only deterministic networks can be cached.
+pub struct CachedNetwork<N> {
+    network: N,
+    cache: Mutex<Cache>,
+}
+
+impl<N: Network> CachedNetwork<N> {
+    /// Cache at most `capacity` outputs of `network`.
+    pub fn new(network: N, capacity: usize) -> Self {
+        Self {
+            network,
+            cache: Mutex::new(Cache {
+                outputs: LruCache::new(capacity),
+                stats: CacheStats::default(),
+            }),
+        }
+    }
+
+    /// The lookups so far.
+    pub fn stats(&self) -> CacheStats {
+        self.cache.lock().unwrap().stats
+    }
+
+    /// The number of outputs cached.
+    pub fn len(&self) -> usize {
+        self.cache.lock().unwrap().outputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The cached outputs for `keys`, with those missing computed by `evaluate` from their
+    /// indices and cached from then on. The lock isn't held while evaluating, so other threads
+    /// can use the cache meanwhile.
+    fn lookup(
+        &self,
+        keys: &[u64],
+        evaluate: impl FnOnce(&[usize]) -> Vec<NetworkOutput>,
+    ) -> Vec<NetworkOutput> {
+        let mut outputs: Vec<_> = {
+            let mut cache = self.cache.lock().unwrap();
+            keys.iter()
+                .map(|&key| {
+                    let cached = cache.outputs.get(key);
+                    match cached {
+                        Some(_) => cache.stats.hits += 1,
+                        None => cache.stats.misses += 1,
+                    }
+                    cached
+                })
+                .collect()
+        };
+        let misses: Vec<_> = (0..keys.len()).filter(|&i| outputs[i].is_none()).collect();
+        if !misses.is_empty() {
+            let evaluated = evaluate(&misses);
+            let mut cache = self.cache.lock().unwrap();
+            for (i, output) in misses.into_iter().zip(evaluated) {
+                cache.outputs.insert(keys[i], output.clone());
+                outputs[i] = Some(output);
+            }
+        }
+        outputs
+            .into_iter()
+            .map(|output| output.expect("one output per input"))
+            .collect()
+    }
+}
+
+/// The key of an input: its values, tagged with the action of recurrent inferences so that the
+/// two kinds don't mix.
+fn key(values: &[f32], action: Option<usize>) -> u64 {
+    let tag = action.map_or(0, |action| action as u64 + 1);
+    fnv1a(
+        values
+            .iter()
+            .flat_map(|value| value.to_bits().to_le_bytes())
+            .chain(tag.to_le_bytes()),
+    )
+}
+
+impl<N: Network> Network for CachedNetwork<N> {
+    fn initial_inference(&self, observation: &[f32]) -> NetworkOutput {
+        self.initial_inference_batch(&[observation]).remove(0)
+    }
+
+    fn recurrent_inference(&self, hidden_state: &[f32], action: usize) -> NetworkOutput {
+        self.recurrent_inference_batch(&[(hidden_state, action)])
+            .remove(0)
+    }
+
+    fn is_absorbing(&self, hidden_state: &[f32]) -> bool {
+        self.network.is_absorbing(hidden_state)
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        Some(self.stats())
+    }
+
+    /// Evaluates the observations missing from the cache in one batch.
+    fn initial_inference_batch(&self, observations: &[&[f32]]) -> Vec<NetworkOutput> {
+        let keys: Vec<_> = observations
+            .iter()
+            .map(|observation| key(observation, None))
+            .collect();
+        self.lookup(&keys, |misses| {
+            let inputs: Vec<_> = misses.iter().map(|&i| observations[i]).collect();
+            self.network.initial_inference_batch(&inputs)
+        })
+    }
+
+    fn recurrent_inference_batch(&self, inputs: &[(&[f32], usize)]) -> Vec<NetworkOutput> {
+        let keys: Vec<_> = inputs
+            .iter()
+            .map(|&(hidden_state, action)| key(hidden_state, Some(action)))
+            .collect();
+        self.lookup(&keys, |misses| {
+            let inputs: Vec<_> = misses.iter().map(|&i| inputs[i]).collect();
+            self.network.recurrent_inference_batch(&inputs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::muzero::{run_mcts, MuZeroConfig};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_lru_cache() {
+        let mut cache = LruCache::new(2);
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        assert_eq!(cache.get(1), Some("a"));
+        // 2 is the least recently used now.
+        cache.insert(3, "c");
+        assert_eq!(cache.get(2), None);
+        assert_eq!(cache.get(1), Some("a"));
+        assert_eq!(cache.get(3), Some("c"));
+        cache.insert(3, "d");
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(3), Some("d"));
+
+        let mut cache = LruCache::new(0);
+        cache.insert(1, "a");
+        assert!(cache.is_empty());
+    }
+
+    /// Counts its evaluations, and predicts the action sum of the path as the value.
+    struct CountingNetwork {
+        evaluations: AtomicUsize,
+    }
+
+    impl CountingNetwork {
+        fn output(&self, hidden_state: Vec<f32>) -> NetworkOutput {
+            self.evaluations.fetch_add(1, Ordering::Relaxed);
+            NetworkOutput {
+                value: hidden_state.iter().sum::<f32>() / 10.,
+                reward: 0.,
+                policy_logits: vec![0.; 3],
+                hidden_state,
+            }
+        }
+    }
+
+    impl Network for CountingNetwork {
+        fn initial_inference(&self, observation: &[f32]) -> NetworkOutput {
+            self.output(observation.to_vec())
+        }
+
+        fn recurrent_inference(&self, hidden_state: &[f32], action: usize) -> NetworkOutput {
+            let mut state = hidden_state.to_vec();
+            state.push(action as f32);
+            self.output(state)
+        }
+    }
+
+    #[test]
+    fn test_cached_network() {
+        let network = CachedNetwork::new(
+            CountingNetwork {
+                evaluations: AtomicUsize::new(0),
+            },
+            1000,
+        );
+        let a = network.initial_inference(&[1., 2.]);
+        let b = network.initial_inference(&[1., 2.]);
+        assert_eq!(a.hidden_state, b.hidden_state);
+        assert_eq!(network.stats(), CacheStats { hits: 1, misses: 1 });
+        // An initial inference of the hidden state isn't a recurrent inference from it.
+        network.recurrent_inference(&[1.], 2);
+        network.initial_inference(&[1., 2., 0.]);
+        assert_eq!(network.stats().misses, 3);
+        assert_eq!(network.network.evaluations.load(Ordering::Relaxed), 3);
+
+        // Only the misses of a batch are evaluated.
+        let outputs = network.initial_inference_batch(&[&[1., 2.], &[5.], &[5.]]);
+        assert_eq!(outputs[1].hidden_state, [5.]);
+        assert_eq!(outputs[2].hidden_state, [5.]);
+        assert_eq!(network.network.evaluations.load(Ordering::Relaxed), 5);
+    }
+
+    #[test]
+    fn test_repeated_search() {
+        let network = CachedNetwork::new(
+            CountingNetwork {
+                evaluations: AtomicUsize::new(0),
+            },
+            1000,
+        );
+        let mut config = MuZeroConfig::board_game(3, 0.3);
+        config.num_simulations = 30;
+        // Without noise at the root, so that both searches go the same way.
+        config.root_exploration_fraction = 0.;
+        let first = run_mcts(&config, &network, &[0.], &[0, 1, 2], 0);
+        assert_eq!(
+            first.cache,
+            Some(CacheStats {
+                hits: 0,
+                misses: 31
+            })
+        );
+        // The same position: the search only reaches the states it found before the first
+        // time it goes deeper.
+        let second = run_mcts(&config, &network, &[0.], &[0, 1, 2], 0);
+        let cache = second.cache.unwrap();
+        assert_eq!(cache.hits + cache.misses, 31);
+        assert!(cache.hits > 20, "{:?}", cache);
+        assert_eq!(network.stats().misses, 31 + cache.misses);
+        assert_eq!(
+            network.network.evaluations.load(Ordering::Relaxed),
+            network.stats().misses
+        );
+    }
+}
@@ -0,0 +1,342 @@
+//! Training checkpoints: a directory holding the network weights and the optimizer state as
+//! safetensors files, and a JSON manifest with the training step, what the replay buffer
+//! held and the rest of the state of the run. Saving and loading them needs the `serde`
+//! feature.
+
+#[cfg(feature = "serde")]
+use safetensors::{tensor::TensorView, Dtype, SafeTensorError, SafeTensors};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
+use serde_json::Value;
+#[cfg(feature = "serde")]
+use std::collections::HashMap;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "serde")]
+use crate::error::{Context, Error};
+use crate::error::{IoError, ParseError, Result};
+
+const MANIFEST: &str = "manifest.json";
+#[cfg(feature = "serde")]
+const WEIGHTS: &str = "weights.safetensors";
+#[cfg(feature = "serde")]
+const OPTIMIZER: &str = "optimizer.safetensors";
+/// The metadata key of the order of the tensors in a safetensors file.
+#[cfg(feature = "serde")]
+const ORDER: &str = "order";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tensor {
+    pub shape: Vec<usize>,
+    /// The values in row-major order.
+    pub data: Vec<f32>,
+}
+
+impl Tensor {
+    pub fn new(shape: Vec<usize>, data: Vec<f32>) -> Result<Self, ParseError> {
+        let size = shape
+            .iter()
+            .try_fold(1usize, |size, &dim| size.checked_mul(dim));
+        if size != Some(data.len()) {
+            return Err(ParseError::Invalid(format!(
+                "{} values don't fill a tensor of shape {:?}",
+                data.len(),
+                shape
+            )));
+        }
+        Ok(Self { shape, data })
+    }
+}
+
+/// Tensors by name, in order.
+pub type Tensors = Vec<(String, Tensor)>;
+
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    /// The number of training steps taken.
+    pub step: usize,
+    pub weights: Tensors,
+    /// E.g. the moment estimates of Adam.
+    pub optimizer: Tensors,
+    /// What the replay buffer held, e.g. its number of games, to resume it from the data.
+    pub replay_buffer: Value,
+    /// The rest of the state of the run, like [`RunState`](crate::training::RunState).
+    pub run: Value,
+}
+
+/// The `manifest.json` of a checkpoint, naming the files of the tensors.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    step: usize,
+    weights: String,
+    optimizer: String,
+    replay_buffer: Value,
+    /// Checkpoints from before runs had a state have none.
+    #[serde(default)]
+    run: Value,
+}
+
+#[cfg(feature = "serde")]
+impl Checkpoint {
+    /// Write the checkpoint into `dir`, creating it. The manifest is written last, so a
+    /// directory with a manifest holds a complete checkpoint.
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir).map_err(IoError::path("create", dir))?;
+        write_safetensors(&dir.join(WEIGHTS), &self.weights)?;
+        write_safetensors(&dir.join(OPTIMIZER), &self.optimizer)?;
+        let manifest = Manifest {
+            step: self.step,
+            weights: WEIGHTS.to_string(),
+            optimizer: OPTIMIZER.to_string(),
+            replay_buffer: self.replay_buffer.clone(),
+            run: self.run.clone(),
+        };
+        let path = dir.join(MANIFEST);
+        fs::write(&path, serde_json::to_string(&manifest)? + "\n")
+            .map_err(IoError::path("write", path))?;
+        Ok(())
+    }
+
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST);
+        let text = fs::read_to_string(&path).map_err(IoError::path("read", &path))?;
+        let manifest: Manifest =
+            serde_json::from_str(&text).with_context(|| format!("in {}", path.display()))?;
+        Ok(Self {
+            step: manifest.step,
+            weights: read_safetensors(&dir.join(manifest.weights))?,
+            optimizer: read_safetensors(&dir.join(manifest.optimizer))?,
+            replay_buffer: manifest.replay_buffer,
+            run: manifest.run,
+        })
+    }
+}
+
+/// The directory of the checkpoint at `step` in `root`, e.g. `root/step-00001000`.
+pub fn step_dir(root: &Path, step: usize) -> PathBuf {
+    root.join(format!("step-{:08}", step))
+}
+
+/// The directory of the complete checkpoint with the most steps in `root`, if any.
+pub fn latest(root: &Path) -> Result<Option<PathBuf>> {
+    if !root.exists() {
+        return Ok(None);
+    }
+    let mut latest = None;
+    let entries = fs::read_dir(root).map_err(IoError::path("read", root))?;
+    for entry in entries {
+        let path = entry.map_err(IoError::path("read", root))?.path();
+        let step = path
+            .file_name()
+            .and_then(|name| name.to_str()?.strip_prefix("step-")?.parse::<usize>().ok());
+        if let Some(step) = step {
+            if path.join(MANIFEST).exists() && latest.as_ref().is_none_or(|(s, _)| step > *s) {
+                latest = Some((step, path));
+            }
+        }
+    }
+    Ok(latest.map(|(_, path)| path))
+}
+
+/// Write `tensors` as 32-bit floats in the safetensors format.
+#[cfg(feature = "serde")]
+pub fn write_safetensors(path: &Path, tensors: &[(String, Tensor)]) -> Result<()> {
+    fs::write(path, encode_safetensors(tensors)?).map_err(IoError::path("write", path))?;
+    Ok(())
+}
+
+/// Encode `tensors` as 32-bit floats in the safetensors format. The format orders tensors by
+/// name, so their order is kept as a JSON list under `order` in the metadata.
+#[cfg(feature = "serde")]
+pub fn encode_safetensors(tensors: &[(String, Tensor)]) -> Result<Vec<u8>> {
+    for (i, (name, _)) in tensors.iter().enumerate() {
+        if tensors[..i].iter().any(|(other, _)| other == name) {
+            return Err(ParseError::Invalid(format!("two tensors are named `{}`", name)).into());
+        }
+    }
+    let data: Vec<Vec<u8>> = tensors
+        .iter()
+        .map(|(_, tensor)| tensor.data.iter().flat_map(|x| x.to_le_bytes()).collect())
+        .collect();
+    let views = tensors
+        .iter()
+        .zip(&data)
+        .map(|((name, tensor), bytes)| {
+            Ok((
+                name,
+                TensorView::new(Dtype::F32, tensor.shape.clone(), bytes)?,
+            ))
+        })
+        .collect::<Result<Vec<_>, SafeTensorError>>()
+        .map_err(Error::backend)?;
+    let names: Vec<_> = tensors.iter().map(|(name, _)| name).collect();
+    let metadata = HashMap::from([(ORDER.to_string(), serde_json::to_string(&names)?)]);
+    safetensors::serialize(views, Some(metadata)).map_err(Error::backend)
+}
+
+/// Read the 32-bit float tensors of a safetensors file, in the order of its metadata if it
+/// has one and else of their data.
+#[cfg(feature = "serde")]
+pub fn read_safetensors(path: &Path) -> Result<Tensors> {
+    let bytes = fs::read(path).map_err(IoError::path("read", path))?;
+    parse_safetensors(&bytes).with_context(|| format!("in {}", path.display()))
+}
+
+/// Decode the tensors of safetensors bytes, like those of `encode_safetensors`.
+#[cfg(feature = "serde")]
+pub fn parse_safetensors(bytes: &[u8]) -> Result<Tensors> {
+    let invalid = |e: SafeTensorError| ParseError::Invalid(e.to_string());
+    let (_, metadata) = SafeTensors::read_metadata(bytes).map_err(invalid)?;
+    let file = SafeTensors::deserialize(bytes).map_err(invalid)?;
+    let mut names = metadata.offset_keys();
+    if let Some(order) = metadata.metadata().as_ref().and_then(|m| m.get(ORDER)) {
+        let order: Vec<String> = serde_json::from_str(order).context("invalid tensor order")?;
+        let (mut sorted, mut expected) = (order.clone(), names);
+        sorted.sort();
+        expected.sort();
+        if sorted != expected {
+            return Err(ParseError::Invalid(
+                "the tensor order doesn't list every tensor once".to_string(),
+            )
+            .into());
+        }
+        names = order;
+    }
+    names
+        .into_iter()
+        .map(|name| {
+            let view = file.tensor(&name).map_err(invalid)?;
+            if view.dtype() != Dtype::F32 {
+                return Err(ParseError::Invalid(format!(
+                    "tensor `{}` is {}, only F32 is supported",
+                    name,
+                    view.dtype()
+                ))
+                .into());
+            }
+            let values = view
+                .data()
+                .chunks_exact(4)
+                .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+                .collect();
+            let tensor = Tensor::new(view.shape().to_vec(), values)
+                .with_context(|| format!("in `{}`", name))?;
+            Ok((name, tensor))
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("muzero-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn checkpoint(step: usize) -> Checkpoint {
+        let tensor = |shape: Vec<usize>| {
+            let len = shape.iter().product();
+            Tensor::new(shape, (0..len).map(|i| i as f32 * 0.5 - 1.).collect()).unwrap()
+        };
+        Checkpoint {
+            step,
+            weights: vec![
+                ("dense.weight".to_string(), tensor(vec![3, 2])),
+                ("dense.bias".to_string(), tensor(vec![3])),
+            ],
+            optimizer: vec![("dense.weight.m".to_string(), tensor(vec![3, 2]))],
+            replay_buffer: serde_json::json!({"games": 12}),
+            run: serde_json::json!({"seed": "7"}),
+        }
+    }
+
+    #[test]
+    fn test_save_load() {
+        let root = temp_dir("checkpoint");
+        assert_eq!(latest(&root).unwrap(), None);
+        for step in [100, 2000, 300] {
+            checkpoint(step).save(&step_dir(&root, step)).unwrap();
+        }
+        // Incomplete, without a manifest.
+        fs::create_dir_all(step_dir(&root, 5000)).unwrap();
+        let dir = latest(&root).unwrap().unwrap();
+        assert_eq!(dir, root.join("step-00002000"));
+        assert_eq!(Checkpoint::load(&dir).unwrap(), checkpoint(2000));
+        assert!(Checkpoint::load(&step_dir(&root, 5000)).is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_safetensors() {
+        let dir = temp_dir("safetensors");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("t.safetensors");
+        let tensors = checkpoint(0).weights;
+        write_safetensors(&path, &tensors).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        let length = u64::from_le_bytes(bytes[..8].try_into().unwrap()) as usize;
+        assert_eq!(length % 8, 0);
+        assert_eq!(bytes.len(), 8 + length + 4 * 9);
+        let header: Value = serde_json::from_slice(&bytes[8..8 + length]).unwrap();
+        // The data is in the order of the names, the metadata keeps the order of the tensors.
+        assert_eq!(
+            header["dense.weight"],
+            serde_json::json!({"dtype": "F32", "shape": [3, 2], "data_offsets": [12, 36]})
+        );
+        assert_eq!(
+            header["__metadata__"]["order"],
+            r#"["dense.weight","dense.bias"]"#
+        );
+        assert_eq!(read_safetensors(&path).unwrap(), tensors);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let file = |header: &str, data: &[u8]| {
+            let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+            bytes.extend(header.as_bytes());
+            bytes.extend(data);
+            bytes
+        };
+        let metadata = file(
+            r#"{"__metadata__":{"format":"pt"},"x":{"dtype":"F32","shape":[],"data_offsets":[0,4]}}"#,
+            &2f32.to_le_bytes(),
+        );
+        assert_eq!(
+            parse_safetensors(&metadata).unwrap(),
+            vec![("x".to_string(), Tensor::new(vec![], vec![2.]).unwrap())]
+        );
+        let mut twice = tensors.clone();
+        twice.push(tensors[0].clone());
+        assert!(encode_safetensors(&twice).is_err());
+        for invalid in [
+            vec![1, 2, 3],
+            file(
+                r#"{"x":{"dtype":"F16","shape":[1],"data_offsets":[0,2]}}"#,
+                &[0; 2],
+            ),
+            file(
+                r#"{"x":{"dtype":"F32","shape":[2],"data_offsets":[0,8]}}"#,
+                &[0; 4],
+            ),
+            file(
+                r#"{"x":{"dtype":"F32","shape":[2],"data_offsets":[0,4]}}"#,
+                &[0; 4],
+            ),
+            file("[]", &[]),
+            file(
+                r#"{"__metadata__":{"order":"[\"y\"]"},"x":{"dtype":"F32","shape":[],"data_offsets":[0,4]}}"#,
+                &[0; 4],
+            ),
+        ] {
+            assert!(parse_safetensors(&invalid).is_err());
+        }
+    }
+}
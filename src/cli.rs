@@ -0,0 +1,1669 @@
+//! Command line arguments, parsed with clap: `muzero <command> [--option value]...`, with
+//! the defaults of the options read from a config file.
+
+use anyhow::{anyhow, bail, Context};
+use clap::{
+    parser::ValueSource, ArgAction, ArgMatches, Args, CommandFactory, FromArgMatches, Parser,
+    Subcommand, ValueEnum,
+};
+use muzero_rs::{
+    arena::Sprt,
+    config::ConfigFile,
+    curriculum::Curriculum,
+    gating::{EarlyStopping, GatingConfig},
+    joint::{JointGames, Networks},
+    mcts::{FirstPlayUrgency, FullTree, MctsConfig, ProgressiveWidening, Rave},
+    metrics::{MetricsConfig, MetricsFormat},
+    model::Device,
+    optimizer::{Decay, LrSchedule, OptimizerConfig, OptimizerKind},
+    replay::ReplayConfig,
+    resign::ResignConfig,
+    rollout::Rollout,
+    scoring::ScoreConfig,
+    strength::Strength,
+};
+use std::{fmt, iter, net::SocketAddr, path::PathBuf, str::FromStr};
+
+const CONFIG_HELP: &str = "\
+Every option can be set in the config file instead: `game` at the top, the search options in
+[mcts] and the others in the section of their command, e.g. `simulations = 800` in [mcts]
+or `games = 100` in [eval]. Flags override the file.";
+
+/// MuZero and Monte Carlo tree search for board games.
+#[derive(Debug, Parser)]
+#[command(name = "muzero", arg_required_else_help = true, after_help = CONFIG_HELP)]
+struct Cli {
+    #[command(subcommand)]
+    command: CliCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum CliCommand {
+    /// Play against the agent.
+    #[command(after_help = CONFIG_HELP)]
+    Play(PlayOptions),
+    /// Search one position and print the best moves, the expected line and the value.
+    #[command(after_help = CONFIG_HELP)]
+    Analyze(AnalyzeOptions),
+    /// Search every position of a file of puzzles and report how many the agent solves.
+    #[command(name = "testsuite", after_help = CONFIG_HELP)]
+    TestSuite(TestSuiteOptions),
+    /// Generate training data by letting the agent play itself.
+    #[command(name = "selfplay", after_help = CONFIG_HELP)]
+    SelfPlay(SelfPlayOptions),
+    /// Train on self-play data.
+    #[command(after_help = CONFIG_HELP)]
+    Train(TrainOptions),
+    /// Play a match between two agents.
+    #[command(after_help = CONFIG_HELP)]
+    Eval(EvalOptions),
+    /// Play a round-robin tournament between many agents.
+    #[command(after_help = CONFIG_HELP)]
+    Tournament(TournamentOptions),
+    /// Step through a recorded game.
+    #[command(after_help = CONFIG_HELP)]
+    Replay(ReplayOptions),
+    /// Convert game records between JSON lines and SGF.
+    #[command(after_help = CONFIG_HELP)]
+    Convert(ConvertOptions),
+    /// Play Go over the Go Text Protocol on stdin and stdout, e.g. in Sabaki or GoGui.
+    #[command(after_help = CONFIG_HELP)]
+    Gtp(EngineOptions),
+    /// Play chess over the Universal Chess Interface on stdin and stdout, e.g. in
+    /// cutechess-cli.
+    #[command(after_help = CONFIG_HELP)]
+    Uci(EngineOptions),
+    /// Serve a WebSocket API for web pages to play against the agent, and an example one.
+    #[command(after_help = CONFIG_HELP)]
+    Serve(ServeOptions),
+    /// Serve weights to distributed selfplay actors and collect their games.
+    #[command(after_help = CONFIG_HELP)]
+    Learner(LearnerOptions),
+    /// Play every new checkpoint of a training run against the best one so far, and make it
+    /// the best if it wins.
+    #[command(after_help = CONFIG_HELP)]
+    Evaluator(EvaluatorOptions),
+    /// Summarize a file of self-play data.
+    InspectData {
+        #[arg(value_name = "path")]
+        path: PathBuf,
+    },
+    /// Run a command for every combination of hyperparameters and compare their final
+    /// metrics; see src/sweep.rs for the config.
+    Sweep(SweepArgs),
+    /// List the games.
+    Games,
+}
+
+/// The options of every command reading a config file.
+#[derive(Debug, Args)]
+#[group(id = "common")]
+struct CommonOptions {
+    /// Read option defaults from a TOML file, see configs/.
+    #[arg(long = "config", value_name = "path")]
+    config_file: Option<PathBuf>,
+    /// The game, with an optional parameter like gomoku:9.
+    #[arg(long, value_name = "name", default_value = "tictactoe")]
+    game: String,
+    /// Draw every random number from the seed, so that two runs with the same seed and
+    /// options play and write exactly the same.
+    #[arg(long, value_name = "seed")]
+    deterministic: Option<u64>,
+}
+
+/// The options of the search, in `[mcts]` in a config file.
+#[derive(Debug, Args)]
+#[group(id = "mcts")]
+#[command(next_help_heading = "Search options")]
+struct MctsOptions {
+    /// MCTS simulations per move.
+    #[arg(long, value_name = "n", default_value_t = 1000)]
+    simulations: usize,
+    /// The weight of exploration in UCT.
+    #[arg(long, value_name = "c", default_value_t = MctsConfig::default().exploration)]
+    exploration: f32,
+    /// Cut random playouts off after this many moves.
+    #[arg(long, value_name = "n")]
+    max_rollout_depth: Option<usize>,
+    /// Average n playouts from every expanded node.
+    #[arg(long, value_name = "n", default_value_t = 1, value_parser = positive)]
+    rollouts_per_leaf: usize,
+    /// uniform random playouts, or tactical ones which take wins and block losses in sight.
+    #[arg(long, value_name = "policy", default_value = "uniform")]
+    rollout: Rollout,
+    /// Play proven moves instead of searching once the game can be solved exactly by
+    /// visiting at most n states.
+    #[arg(long, value_name = "n")]
+    solver_budget: Option<usize>,
+    /// Back proven wins and losses up the tree.
+    #[arg(long, value_name = "bool", default_value_t = false, action = ArgAction::Set)]
+    mcts_solver: bool,
+    /// Blend in RAVE values, weighted equally with k visits.
+    #[arg(long, value_name = "k")]
+    rave_equivalence: Option<f32>,
+    /// Progressive widening: at most c * visits^alpha children.
+    #[arg(long, value_name = "c")]
+    widening_c: Option<f32>,
+    /// The alpha of progressive widening.
+    #[arg(long, value_name = "alpha")]
+    widening_alpha: Option<f32>,
+    /// Seed the search, so that it plays the same moves in the same positions.
+    #[arg(long, value_name = "n")]
+    seed: Option<u64>,
+    /// Limit the search tree to n nodes.
+    #[arg(long, value_name = "n")]
+    max_nodes: Option<usize>,
+    /// Limit the search tree to about n bytes.
+    #[arg(long, value_name = "n")]
+    max_memory_bytes: Option<usize>,
+    /// stop or recycle the least visited subtrees once the tree is full.
+    #[arg(long, value_name = "what", default_value = "stop")]
+    full_tree: FullTree,
+    /// The score of unvisited moves against the UCT scores of visited ones: infinite to try
+    /// every move once first, lowest, parent or parent:<reduction> for the node's value
+    /// minus the reduction, or value:<v>.
+    #[arg(long, value_name = "u", default_value = "infinite")]
+    first_play_urgency: FirstPlayUrgency,
+    /// Value playouts by their margin of victory, e.g. the score in Go, with this weight
+    /// against the outcome.
+    #[arg(long, value_name = "w", default_value_t = ScoreConfig::default().margin_weight)]
+    margin_weight: f32,
+    /// The margin worth a full win.
+    #[arg(long, value_name = "s", default_value_t = ScoreConfig::default().margin_scale)]
+    margin_scale: f32,
+    /// A draw is worth v to the first player and -v to the others.
+    #[arg(
+        long,
+        value_name = "v",
+        default_value_t = ScoreConfig::default().draw_value,
+        allow_hyphen_values = true
+    )]
+    draw_value: f32,
+}
+
+/// Where and how to log metrics, in `[metrics]` in a config file.
+#[derive(Debug, Args)]
+#[group(id = "metrics")]
+#[command(next_help_heading = "Metrics options")]
+struct MetricsOptions {
+    /// Log metrics like game lengths, search depths and Elo there.
+    #[arg(long, value_name = "path")]
+    metrics_dir: Option<PathBuf>,
+    /// tensorboard or csv.
+    #[arg(long, value_name = "fmt", default_value = "tensorboard")]
+    metrics_format: MetricsFormat,
+}
+
+#[derive(Debug, Args)]
+struct PlayOptions {
+    #[command(flatten)]
+    common: CommonOptions,
+    /// text, or tui for a full-screen board.
+    #[arg(long, value_name = "ui", value_enum, default_value_t = Ui::Text)]
+    ui: Ui,
+    /// The players the human plays: numbers from 0, x/o, black/white or first/second,
+    /// separated by commas, or none or all [default: 0].
+    #[arg(long, value_name = "players")]
+    human_side: Option<HumanSide>,
+    /// human or agent, in two-player games instead of --human-side.
+    #[arg(long, value_name = "who", value_enum)]
+    first_move: Option<FirstMove>,
+    /// Write the search tree after every agent move in the text UI, as JSON if the path ends
+    /// in .json and as Graphviz DOT otherwise.
+    #[arg(long, value_name = "path")]
+    dump_tree: Option<PathBuf>,
+    /// Append a record of the game to the file, for replay.
+    #[arg(long, value_name = "path")]
+    record: Option<PathBuf>,
+    /// Handicap the agent: beginner, easy, medium or hard searches with fewer simulations
+    /// and plays looser moves, and sometimes blunders.
+    #[arg(long, value_name = "level")]
+    strength: Option<Strength>,
+    /// Keep searching for up to n simulations while the human thinks in the text UI, and
+    /// reuse the tree below the move they play.
+    #[arg(long, value_name = "n")]
+    ponder: Option<usize>,
+    #[command(flatten)]
+    mcts: MctsOptions,
+}
+
+#[derive(Debug, Args)]
+struct AnalyzeOptions {
+    #[command(flatten)]
+    common: CommonOptions,
+    /// The position, e.g. "X1O/1X1/3 O" in tictactoe: the rows from the top, runs of empty
+    /// cells as numbers, then the player to move [default: the start].
+    #[arg(long, value_name = "notation")]
+    position: Option<String>,
+    /// How many of the best moves to print.
+    #[arg(long, value_name = "n", default_value_t = 5)]
+    top: usize,
+    /// Search on from the tree saved there, if there is one, and save the tree there.
+    #[arg(long, value_name = "path")]
+    tree: Option<PathBuf>,
+    #[command(flatten)]
+    mcts: MctsOptions,
+}
+
+#[derive(Debug, Args)]
+struct TestSuiteOptions {
+    #[command(flatten)]
+    common: CommonOptions,
+    /// The puzzles, one per line: a position in the notation of --position of analyze, then
+    /// `; bm` and the indices of the best moves, `; am` and those of moves to avoid and
+    /// `; id` and a name, e.g. suites/tictactoe.txt.
+    #[arg(long, value_name = "path")]
+    suite: Option<PathBuf>,
+    #[command(flatten)]
+    mcts: MctsOptions,
+}
+
+#[derive(Debug, Args)]
+struct SelfPlayOptions {
+    #[command(flatten)]
+    common: CommonOptions,
+    /// Number of games.
+    #[arg(long, value_name = "n", default_value_t = 10)]
+    games: usize,
+    /// Sample moves from the visit counts raised to 1/t.
+    #[arg(long, value_name = "t", default_value_t = 1.)]
+    temperature: f32,
+    /// Where to write the games.
+    #[arg(long, value_name = "path", default_value = "selfplay.traj")]
+    output: PathBuf,
+    /// Act for the learner at addr: fetch its weights before every game and push the games
+    /// to it instead of writing --output.
+    #[arg(long, value_name = "addr")]
+    learner: Option<SocketAddr>,
+    /// Serve metrics for Prometheus at http://<addr>/metrics, e.g. 0.0.0.0:9184; needs the
+    /// prometheus feature.
+    #[arg(long, value_name = "addr")]
+    prometheus_addr: Option<SocketAddr>,
+    /// Also append records of the games to the file, for replay.
+    #[arg(long, value_name = "path")]
+    record: Option<PathBuf>,
+    /// Resign once the root value of the player to move stays below v, e.g. -0.9
+    /// [default: never resign].
+    #[arg(long, value_name = "v", allow_hyphen_values = true)]
+    resign_threshold: Option<f32>,
+    /// ...for n of its moves in a row.
+    #[arg(long, value_name = "n", default_value_t = ResignConfig::default().moves)]
+    resign_moves: usize,
+    /// Play this fraction of the games which would resign to the end, to measure how many of
+    /// them weren't lost.
+    #[arg(long, value_name = "f", default_value_t = ResignConfig::default().playthrough_fraction)]
+    resign_playthrough: f32,
+    /// Grow the board of gomoku, hex or go from stage to stage, e.g. 9:100,13:100,15 for 100
+    /// games on 9x9, 100 on 13x13 and the rest on 15x15; the games are padded to the largest
+    /// board, the one to train on.
+    #[arg(long, value_name = "stages")]
+    curriculum: Option<Curriculum>,
+    /// Play several games in turn, --games of each, and write each to its own file:
+    /// game=path pairs separated by spaces, e.g.
+    /// "tictactoe=tictactoe.traj connect4=connect4.traj".
+    #[arg(long, value_name = "games")]
+    joint: Option<JointGames>,
+    #[command(flatten)]
+    mcts: MctsOptions,
+    #[command(flatten)]
+    metrics: MetricsOptions,
+}
+
+#[derive(Debug, Args)]
+struct TrainOptions {
+    #[command(flatten)]
+    common: CommonOptions,
+    /// Self-play data written by selfplay.
+    #[arg(long, value_name = "path", default_value = "selfplay.traj")]
+    data: PathBuf,
+    /// Training steps.
+    #[arg(long, value_name = "n", default_value_t = 1000)]
+    steps: usize,
+    /// Save checkpoints there, and resume from the latest one.
+    #[arg(long, value_name = "path")]
+    checkpoint_dir: Option<PathBuf>,
+    /// Training steps between checkpoints, which are also saved after the last step.
+    #[arg(long, value_name = "n", default_value_t = 100, value_parser = positive)]
+    checkpoint_interval: usize,
+    /// Feed the network the last k observations and the actions leading to them.
+    #[arg(long, value_name = "k")]
+    stacked_frames: Option<usize>,
+    /// Actions the model unrolls from every position.
+    #[arg(long, value_name = "k", default_value_t = ReplayConfig::default().num_unroll_steps)]
+    unroll_steps: usize,
+    /// Positions of every training batch.
+    #[arg(long, value_name = "n", default_value_t = 128, value_parser = positive)]
+    batch_size: usize,
+    /// The size of the hidden state of the model.
+    #[arg(long, value_name = "n", default_value_t = 64, value_parser = positive)]
+    hidden_size: usize,
+    /// cpu, cuda, cuda:<n> or metal; other than cpu needs the tch feature.
+    #[arg(long, value_name = "device", default_value = "cpu")]
+    device: Device,
+    /// sgd with momentum, adam, or adamw.
+    #[arg(long, value_name = "name", default_value = "sgd")]
+    optimizer: OptimizerKind,
+    /// The initial learning rate.
+    #[arg(
+        long,
+        value_name = "lr",
+        default_value_t = OptimizerConfig::default().schedule.learning_rate
+    )]
+    lr: f32,
+    /// constant, cosine:<steps>, step:<steps>:<rate> or exponential:<steps>:<rate>, the last
+    /// as in the MuZero paper.
+    #[arg(long, value_name = "decay", default_value = "exponential:350000:0.1")]
+    lr_schedule: Decay,
+    /// Raise the learning rate linearly before the decay.
+    #[arg(
+        long,
+        value_name = "n",
+        default_value_t = OptimizerConfig::default().schedule.warmup_steps
+    )]
+    warmup_steps: usize,
+    /// The momentum of sgd.
+    #[arg(long, value_name = "m", default_value_t = OptimizerConfig::default().momentum)]
+    momentum: f32,
+    /// L2 penalty, decoupled from the gradients by adamw.
+    #[arg(long, value_name = "d", default_value_t = OptimizerConfig::default().weight_decay)]
+    weight_decay: f32,
+    /// Scale gradients down to at most this global L2 norm.
+    #[arg(long, value_name = "norm")]
+    clip_grad_norm: Option<f32>,
+    /// The seed of the run, kept by its checkpoints [default: random].
+    #[arg(long, value_name = "n")]
+    seed: Option<u64>,
+    /// The curriculum of selfplay that played the data, to train one network on every
+    /// stage, sized for the largest board.
+    #[arg(long, value_name = "stages")]
+    curriculum: Option<Curriculum>,
+    /// Train on several games at once, each from its own data, written like the --joint of
+    /// selfplay, instead of --game and --data.
+    #[arg(long, value_name = "games")]
+    joint: Option<JointGames>,
+    /// shared, for one network taking the observations and actions of every game, or
+    /// per-game.
+    #[arg(long, value_name = "which", default_value = "shared")]
+    networks: Networks,
+    /// Batches of each game in a row before the next game's.
+    #[arg(long, value_name = "n", default_value_t = 1, value_parser = positive)]
+    interleave: usize,
+    #[command(flatten)]
+    metrics: MetricsOptions,
+}
+
+#[derive(Debug, Args)]
+struct EvalOptions {
+    #[command(flatten)]
+    common: CommonOptions,
+    /// The agent to evaluate: random, mcts or mcts:<simulations>.
+    #[arg(long, value_name = "agent", default_value = "mcts")]
+    agent: AgentSpec,
+    /// Its opponent.
+    #[arg(long, value_name = "agent", default_value = "random")]
+    opponent: AgentSpec,
+    /// Number of games, alternating who moves first.
+    #[arg(long, value_name = "n", default_value_t = 10, value_parser = positive)]
+    games: usize,
+    /// Stop early once a sequential probability ratio test tells whether the agent is elo0
+    /// or elo1 stronger, with 5% error rates.
+    #[arg(long, value_name = "elo", allow_hyphen_values = true)]
+    sprt_elo0: Option<f64>,
+    /// The elo1 of the test, above elo0.
+    #[arg(long, value_name = "elo", allow_hyphen_values = true)]
+    sprt_elo1: Option<f64>,
+    /// An opening book for MCTS agents, which also play forced moves without searching.
+    #[arg(long, value_name = "path")]
+    book: Option<PathBuf>,
+    #[command(flatten)]
+    mcts: MctsOptions,
+    #[command(flatten)]
+    metrics: MetricsOptions,
+}
+
+#[derive(Debug, Args)]
+struct TournamentOptions {
+    #[command(flatten)]
+    common: CommonOptions,
+    /// The agents, separated by commas.
+    #[arg(
+        long,
+        value_name = "agents",
+        value_delimiter = ',',
+        default_value = "random,mcts"
+    )]
+    agents: Vec<AgentSpec>,
+    /// Games per pairing, alternating who moves first.
+    #[arg(long, value_name = "n", default_value_t = 10, value_parser = positive)]
+    games: usize,
+    /// Also write the crosstable and Elo ladder, as JSON if the path ends in .json and as
+    /// CSV otherwise.
+    #[arg(long, value_name = "path")]
+    output: Option<PathBuf>,
+    /// An opening book for MCTS agents, which also play forced moves without searching.
+    #[arg(long, value_name = "path")]
+    book: Option<PathBuf>,
+    #[command(flatten)]
+    mcts: MctsOptions,
+}
+
+#[derive(Debug, Args)]
+struct ReplayOptions {
+    #[command(flatten)]
+    common: CommonOptions,
+    /// A file of game records written by play or selfplay, or an SGF file if the path ends
+    /// in .sgf.
+    #[arg(long, value_name = "path", default_value = "games.jsonl")]
+    record: PathBuf,
+    /// Which game of the file, counting from 1.
+    #[arg(long, value_name = "n", default_value_t = 1)]
+    index: usize,
+}
+
+#[derive(Debug, Args)]
+struct ConvertOptions {
+    #[command(flatten)]
+    common: CommonOptions,
+    /// Game records, as SGF if the path ends in .sgf and as JSON lines otherwise.
+    #[arg(long, value_name = "path")]
+    input: Option<PathBuf>,
+    /// Where to write them, in the format of its extension; only Go, Gomoku and Othello can
+    /// be written as SGF.
+    #[arg(long, value_name = "path")]
+    output: Option<PathBuf>,
+}
+
+/// The options of the engines speaking GTP and UCI, which take their game from the protocol.
+#[derive(Debug, Args)]
+struct EngineOptions {
+    #[command(flatten)]
+    common: CommonOptions,
+    #[command(flatten)]
+    mcts: MctsOptions,
+}
+
+#[derive(Debug, Args)]
+struct ServeOptions {
+    #[command(flatten)]
+    common: CommonOptions,
+    /// Where to serve the example page, at /, and the API, at /ws.
+    #[arg(long, value_name = "addr", default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+    #[command(flatten)]
+    mcts: MctsOptions,
+}
+
+#[derive(Debug, Args)]
+struct LearnerOptions {
+    #[command(flatten)]
+    common: CommonOptions,
+    /// Where actors connect.
+    #[arg(long, value_name = "addr", default_value = "0.0.0.0:9185")]
+    listen: SocketAddr,
+    /// Games to queue before actors wait for the learner.
+    #[arg(long, value_name = "n", default_value_t = 64)]
+    queue: usize,
+    /// Where to append the games.
+    #[arg(long, value_name = "path", default_value = "selfplay.traj")]
+    output: PathBuf,
+    /// Serve the weights of the latest checkpoint there.
+    #[arg(long, value_name = "path")]
+    checkpoint_dir: Option<PathBuf>,
+    /// Serve the best checkpoint chosen by the evaluator instead of the latest.
+    #[arg(long, value_name = "bool", default_value_t = false, action = ArgAction::Set)]
+    gated: bool,
+    /// Stop after receiving this many games.
+    #[arg(long, value_name = "n")]
+    games: Option<usize>,
+}
+
+#[derive(Debug, Args)]
+struct EvaluatorOptions {
+    #[command(flatten)]
+    common: CommonOptions,
+    /// The checkpoints of the training run.
+    #[arg(long, value_name = "path")]
+    checkpoint_dir: Option<PathBuf>,
+    /// Games of every match, alternating who moves first.
+    #[arg(long, value_name = "n", default_value_t = 40)]
+    games: usize,
+    /// The score a checkpoint needs to become the best.
+    #[arg(long, value_name = "score", default_value_t = 0.55)]
+    threshold: f64,
+    /// Play every checkpoint against this agent instead, e.g. mcts:800 for pure rollout
+    /// MCTS.
+    #[arg(long, value_name = "agent")]
+    baseline: Option<AgentSpec>,
+    /// How often to look for new checkpoints, in seconds.
+    #[arg(long, value_name = "seconds", default_value_t = 60)]
+    interval: u64,
+    /// Stop after this many evaluations.
+    #[arg(long, value_name = "n")]
+    evaluations: Option<usize>,
+    /// Stop training, and the evaluator, once this many evaluations in a row found no
+    /// stronger checkpoint.
+    #[arg(long, value_name = "n")]
+    patience: Option<usize>,
+    /// The Elo by which a checkpoint must be stronger.
+    #[arg(
+        long,
+        value_name = "elo",
+        default_value_t = 0.,
+        allow_hyphen_values = true
+    )]
+    min_delta: f64,
+    #[command(flatten)]
+    mcts: MctsOptions,
+    #[command(flatten)]
+    metrics: MetricsOptions,
+}
+
+/// A count of at least 1.
+fn positive(s: &str) -> anyhow::Result<usize> {
+    match s.parse()? {
+        0 => bail!("must be at least 1"),
+        n => Ok(n),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Command {
+    /// Run the command with every random number drawn from the seed, see
+    /// [`muzero_rs::random`].
+    Deterministic {
+        seed: u64,
+        command: Box<Command>,
+    },
+    Play(PlayArgs),
+    Analyze(AnalyzeArgs),
+    TestSuite(TestSuiteArgs),
+    SelfPlay(SelfPlayArgs),
+    Train(TrainArgs),
+    Eval(EvalArgs),
+    Tournament(TournamentArgs),
+    Learner(LearnerArgs),
+    Evaluator(EvaluatorArgs),
+    Replay(ReplayArgs),
+    Convert(ConvertArgs),
+    Gtp(GtpArgs),
+    Uci(UciArgs),
+    Serve(ServeArgs),
+    Sweep(SweepArgs),
+    /// Summarize the self-play data at the path.
+    InspectData(PathBuf),
+    Games,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PlayArgs {
+    pub(crate) game: String,
+    pub(crate) mcts: MctsConfig,
+    pub(crate) ui: Ui,
+    pub(crate) human_side: HumanSide,
+    /// Write the search tree of every agent move there.
+    pub(crate) dump_tree: Option<PathBuf>,
+    /// Search on the human's time for at most this many simulations.
+    pub(crate) ponder: Option<usize>,
+    pub(crate) strength: Option<Strength>,
+    /// Append a record of the game there.
+    pub(crate) record: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct AnalyzeArgs {
+    pub(crate) game: String,
+    pub(crate) mcts: MctsConfig,
+    /// In the notation of [`muzero_rs::Game::to_notation`]; the start of the game if `None`.
+    pub(crate) position: Option<String>,
+    pub(crate) top: usize,
+    pub(crate) tree: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TestSuiteArgs {
+    pub(crate) game: String,
+    pub(crate) mcts: MctsConfig,
+    pub(crate) suite: PathBuf,
+}
+
+/// Who the human plays; agents play the others.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum HumanSide {
+    /// Players by index.
+    Players(Vec<usize>),
+    All,
+}
+
+impl HumanSide {
+    /// Whether the human plays each of `num_players` players.
+    pub(crate) fn humans(&self, num_players: usize) -> anyhow::Result<Vec<bool>> {
+        let mut humans = vec![matches!(self, HumanSide::All); num_players];
+        if let HumanSide::Players(players) = self {
+            for &player in players {
+                if player >= num_players {
+                    bail!(
+                        "there's no player {} in a {}-player game",
+                        player,
+                        num_players
+                    );
+                }
+                humans[player] = true;
+            }
+        }
+        Ok(humans)
+    }
+}
+
+impl FromStr for HumanSide {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "none" => return Ok(HumanSide::Players(vec![])),
+            "all" => return Ok(HumanSide::All),
+            _ => {}
+        }
+        let players = s
+            .split(',')
+            .map(|player| match player.to_lowercase().as_str() {
+                "x" | "black" | "first" => Ok(0),
+                "o" | "white" | "second" => Ok(1),
+                player => player
+                    .parse()
+                    .map_err(|_| anyhow!("unknown player `{}`", player)),
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(HumanSide::Players(players))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub(crate) enum FirstMove {
+    Human,
+    Agent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub(crate) enum Ui {
+    /// Moves are typed as numbers.
+    Text,
+    /// Moves are picked with the cursor keys, see [`crate::tui`].
+    Tui,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SelfPlayArgs {
+    pub(crate) game: String,
+    pub(crate) mcts: MctsConfig,
+    pub(crate) games: usize,
+    pub(crate) temperature: f32,
+    pub(crate) output: PathBuf,
+    pub(crate) metrics: Option<MetricsConfig>,
+    /// The learner to act for, instead of writing the games to `output`.
+    pub(crate) learner: Option<SocketAddr>,
+    /// Where to serve Prometheus metrics, with the `prometheus` feature.
+    pub(crate) prometheus_addr: Option<SocketAddr>,
+    /// Append records of the games there.
+    pub(crate) record: Option<PathBuf>,
+    pub(crate) resign: Option<ResignConfig>,
+    /// Play on growing boards, with `game` naming the game without its size.
+    pub(crate) curriculum: Option<Curriculum>,
+    /// Play these games in turn instead of `game`, each written to its own data.
+    pub(crate) joint: Option<JointGames>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TrainArgs {
+    pub(crate) game: String,
+    pub(crate) data: PathBuf,
+    pub(crate) steps: usize,
+    pub(crate) checkpoint_dir: Option<PathBuf>,
+    pub(crate) checkpoint_interval: usize,
+    /// Stack this many observations and actions into the input of the network.
+    pub(crate) stacked_frames: Option<usize>,
+    /// Actions the model unrolls from every sampled position.
+    pub(crate) unroll_steps: usize,
+    pub(crate) metrics: Option<MetricsConfig>,
+    pub(crate) optimizer: OptimizerConfig,
+    /// The seed of a new run; resumed runs keep the seed of their checkpoint.
+    pub(crate) seed: Option<u64>,
+    /// The curriculum the data was played with, whose games are padded to its largest board.
+    pub(crate) curriculum: Option<Curriculum>,
+    /// Train on these games instead of `game` and `data`.
+    pub(crate) joint: Option<JointGames>,
+    pub(crate) networks: Networks,
+    /// The batches of a joint run taken from each game in a row.
+    pub(crate) interleave: usize,
+    pub(crate) batch_size: usize,
+    pub(crate) hidden_size: usize,
+    pub(crate) device: Device,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct EvalArgs {
+    pub(crate) game: String,
+    /// The search of MCTS agents, unless they set their own number of simulations.
+    pub(crate) mcts: MctsConfig,
+    pub(crate) agent: AgentSpec,
+    pub(crate) opponent: AgentSpec,
+    pub(crate) games: usize,
+    pub(crate) sprt: Option<Sprt>,
+    pub(crate) metrics: Option<MetricsConfig>,
+    /// An opening book for the MCTS agents.
+    pub(crate) book: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TournamentArgs {
+    pub(crate) game: String,
+    pub(crate) mcts: MctsConfig,
+    pub(crate) agents: Vec<AgentSpec>,
+    pub(crate) games: usize,
+    pub(crate) output: Option<PathBuf>,
+    pub(crate) book: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ReplayArgs {
+    pub(crate) record: PathBuf,
+    /// The game of the file to show, counting from 1.
+    pub(crate) index: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ConvertArgs {
+    pub(crate) input: PathBuf,
+    pub(crate) output: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct GtpArgs {
+    pub(crate) mcts: MctsConfig,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct UciArgs {
+    pub(crate) mcts: MctsConfig,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ServeArgs {
+    pub(crate) game: String,
+    pub(crate) mcts: MctsConfig,
+    pub(crate) listen: SocketAddr,
+}
+
+#[derive(Debug, Clone, PartialEq, Args)]
+pub(crate) struct SweepArgs {
+    /// The sweep, see src/sweep.rs.
+    #[arg(value_name = "config")]
+    pub(crate) config: PathBuf,
+    /// Write the runs and summary.csv there.
+    #[arg(long, value_name = "path", default_value = "sweep")]
+    pub(crate) output: PathBuf,
+    /// Runs at a time, instead of `parallel` in the config.
+    #[arg(long, value_name = "n", value_parser = positive)]
+    pub(crate) parallel: Option<usize>,
+    /// Order the runs by a final metric, highest first, e.g. eval/elo, or lowest first after
+    /// a minus, e.g. -train/loss.
+    #[arg(long, value_name = "metric", allow_hyphen_values = true)]
+    pub(crate) sort: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LearnerArgs {
+    pub(crate) listen: SocketAddr,
+    pub(crate) queue: usize,
+    pub(crate) output: PathBuf,
+    pub(crate) checkpoint_dir: Option<PathBuf>,
+    /// Serve the best checkpoint instead of the latest.
+    pub(crate) gated: bool,
+    /// Stop after this many games instead of serving forever.
+    pub(crate) games: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct EvaluatorArgs {
+    pub(crate) game: String,
+    /// The search of the checkpoints and of MCTS baselines.
+    pub(crate) mcts: MctsConfig,
+    pub(crate) checkpoint_dir: PathBuf,
+    pub(crate) gating: GatingConfig,
+    /// The fixed opponent, instead of the best checkpoint.
+    pub(crate) baseline: Option<AgentSpec>,
+    /// Seconds between looks for new checkpoints.
+    pub(crate) interval: u64,
+    /// Stop after this many evaluations instead of running forever.
+    pub(crate) evaluations: Option<usize>,
+    /// Ask training to stop once the strength plateaus.
+    pub(crate) early_stopping: Option<EarlyStopping>,
+    pub(crate) metrics: Option<MetricsConfig>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum AgentSpec {
+    Random,
+    Mcts { simulations: Option<usize> },
+}
+
+impl FromStr for AgentSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.split_once(':') {
+            None if s == "random" => Ok(AgentSpec::Random),
+            None if s == "mcts" => Ok(AgentSpec::Mcts { simulations: None }),
+            Some(("mcts", simulations)) => Ok(AgentSpec::Mcts {
+                simulations: Some(simulations.parse()?),
+            }),
+            _ => bail!("expected random, mcts or mcts:<simulations>"),
+        }
+    }
+}
+
+impl fmt::Display for AgentSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AgentSpec::Random => write!(f, "random"),
+            AgentSpec::Mcts { simulations: None } => write!(f, "mcts"),
+            AgentSpec::Mcts {
+                simulations: Some(simulations),
+            } => write!(f, "mcts:{}", simulations),
+        }
+    }
+}
+
+/// The flags setting what `config` sets for the command of `matches`, but which isn't given
+/// on the command line: `game` and `deterministic` at the top, the options of the search and
+/// of the metrics in their sections, and the others in the section of their command.
+fn config_flags(matches: &ArgMatches, config: &ConfigFile) -> Vec<String> {
+    let Some((name, matches)) = matches.subcommand() else {
+        return vec![];
+    };
+    let cli = Cli::command();
+    let command = cli
+        .find_subcommand(name)
+        .expect("the command was just parsed");
+    let mut flags = vec![];
+    for option in &config.options {
+        let Some(arg) = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(option.flag))
+        else {
+            continue;
+        };
+        // The groups of the options are named after their sections.
+        let section = option.section().unwrap_or("common");
+        let in_section = section == name
+            || command.get_groups().any(|group| {
+                group.get_id() == section && group.get_args().any(|id| id == arg.get_id())
+            });
+        if in_section
+            && matches.value_source(arg.get_id().as_str()) != Some(ValueSource::CommandLine)
+        {
+            flags.push(format!("--{}={}", option.flag, option.value));
+        }
+    }
+    flags
+}
+
+/// Parse the arguments following the program name. Help, and mistakes in the flags, are
+/// [`clap::Error`]s, for [`clap::Error::exit`].
+pub(crate) fn parse(args: &[String]) -> anyhow::Result<Command> {
+    let matches = Cli::command().try_get_matches_from(argv(args))?;
+    let config = matches
+        .subcommand()
+        .and_then(|(_, matches)| matches.try_get_one::<PathBuf>("config_file").ok().flatten());
+    let Some(path) = config else {
+        return Cli::from_arg_matches(&matches)?.command.resolve();
+    };
+    parse_with_config(args, &ConfigFile::load(path)?)
+        .with_context(|| format!("in {}", path.display()))
+}
+
+/// Parse the arguments with the options they don't give taken from `config`.
+fn parse_with_config(args: &[String], config: &ConfigFile) -> anyhow::Result<Command> {
+    let matches = Cli::command().try_get_matches_from(argv(args))?;
+    let flags = config_flags(&matches, config);
+    // The flags are checked already, so a failure here is one of the config's values.
+    let matches = Cli::command()
+        .try_get_matches_from(argv(args).chain(flags))
+        .map_err(|error| {
+            let message = error.to_string();
+            let line = message.lines().next().unwrap_or_default();
+            anyhow!("{}", line.trim_start_matches("error: "))
+        })?;
+    Cli::from_arg_matches(&matches)?.command.resolve()
+}
+
+/// `args` after the program name, as clap expects them.
+fn argv(args: &[String]) -> impl Iterator<Item = String> + '_ {
+    iter::once("muzero".to_string()).chain(args.iter().cloned())
+}
+
+impl CliCommand {
+    fn resolve(self) -> anyhow::Result<Command> {
+        let (common, command) = match self {
+            CliCommand::Play(options) => {
+                let PlayOptions { common, mcts, .. } = options;
+                let human_side = match (options.human_side, options.first_move) {
+                    (Some(_), Some(_)) => {
+                        bail!("--human-side and --first-move can't be used together")
+                    }
+                    (Some(side), None) => side,
+                    (None, Some(FirstMove::Agent)) => HumanSide::Players(vec![1]),
+                    (None, Some(FirstMove::Human) | None) => HumanSide::Players(vec![0]),
+                };
+                if common.deterministic.is_some() && options.ponder.is_some() {
+                    bail!(
+                        "--ponder searches for as long as the human thinks, so it isn't \
+                         deterministic"
+                    );
+                }
+                let args = PlayArgs {
+                    game: common.game.clone(),
+                    mcts: mcts.config()?,
+                    ui: options.ui,
+                    human_side,
+                    dump_tree: options.dump_tree,
+                    ponder: options.ponder,
+                    strength: options.strength,
+                    record: options.record,
+                };
+                (common, Command::Play(args))
+            }
+            CliCommand::Analyze(options) => {
+                let args = AnalyzeArgs {
+                    game: options.common.game.clone(),
+                    mcts: options.mcts.config()?,
+                    position: options.position,
+                    top: options.top,
+                    tree: options.tree,
+                };
+                (options.common, Command::Analyze(args))
+            }
+            CliCommand::TestSuite(options) => {
+                let args = TestSuiteArgs {
+                    game: options.common.game.clone(),
+                    mcts: options.mcts.config()?,
+                    suite: required(options.suite, "suite")?,
+                };
+                (options.common, Command::TestSuite(args))
+            }
+            CliCommand::SelfPlay(options) => {
+                let args = SelfPlayArgs {
+                    game: options.common.game.clone(),
+                    mcts: options.mcts.config()?,
+                    games: options.games,
+                    temperature: options.temperature,
+                    output: options.output,
+                    metrics: options.metrics.config(),
+                    learner: options.learner,
+                    prometheus_addr: options.prometheus_addr,
+                    record: options.record,
+                    resign: options.resign_threshold.map(|threshold| ResignConfig {
+                        threshold,
+                        moves: options.resign_moves,
+                        playthrough_fraction: options.resign_playthrough,
+                    }),
+                    curriculum: options.curriculum,
+                    joint: options.joint,
+                };
+                (options.common, Command::SelfPlay(args))
+            }
+            CliCommand::Train(options) => {
+                let args = TrainArgs {
+                    game: options.common.game.clone(),
+                    data: options.data,
+                    steps: options.steps,
+                    checkpoint_dir: options.checkpoint_dir,
+                    checkpoint_interval: options.checkpoint_interval,
+                    stacked_frames: options.stacked_frames,
+                    unroll_steps: options.unroll_steps,
+                    metrics: options.metrics.config(),
+                    optimizer: OptimizerConfig {
+                        kind: options.optimizer,
+                        schedule: LrSchedule {
+                            learning_rate: options.lr,
+                            warmup_steps: options.warmup_steps,
+                            decay: options.lr_schedule,
+                        },
+                        momentum: options.momentum,
+                        weight_decay: options.weight_decay,
+                        clip_grad_norm: options.clip_grad_norm,
+                        ..OptimizerConfig::default()
+                    },
+                    seed: options.seed,
+                    curriculum: options.curriculum,
+                    joint: options.joint,
+                    networks: options.networks,
+                    interleave: options.interleave,
+                    batch_size: options.batch_size,
+                    hidden_size: options.hidden_size,
+                    device: options.device,
+                };
+                (options.common, Command::Train(args))
+            }
+            CliCommand::Eval(options) => {
+                let sprt = match (options.sprt_elo0, options.sprt_elo1) {
+                    (Some(elo0), Some(elo1)) if elo0 < elo1 => Some(Sprt::new(elo0, elo1)),
+                    (None, None) => None,
+                    _ => bail!("the SPRT needs an elo0 below elo1"),
+                };
+                let args = EvalArgs {
+                    game: options.common.game.clone(),
+                    mcts: options.mcts.config()?,
+                    agent: options.agent,
+                    opponent: options.opponent,
+                    games: options.games,
+                    sprt,
+                    metrics: options.metrics.config(),
+                    book: options.book,
+                };
+                (options.common, Command::Eval(args))
+            }
+            CliCommand::Tournament(options) => {
+                if options.agents.len() < 2 {
+                    bail!("a tournament needs two agents or more");
+                }
+                let args = TournamentArgs {
+                    game: options.common.game.clone(),
+                    mcts: options.mcts.config()?,
+                    agents: options.agents,
+                    games: options.games,
+                    output: options.output,
+                    book: options.book,
+                };
+                (options.common, Command::Tournament(args))
+            }
+            CliCommand::Replay(options) => {
+                let args = ReplayArgs {
+                    record: options.record,
+                    index: options.index,
+                };
+                (options.common, Command::Replay(args))
+            }
+            CliCommand::Convert(options) => {
+                let args = ConvertArgs {
+                    input: required(options.input, "input")?,
+                    output: required(options.output, "output")?,
+                };
+                (options.common, Command::Convert(args))
+            }
+            CliCommand::Gtp(options) => {
+                let mcts = options.mcts.config()?;
+                (options.common, Command::Gtp(GtpArgs { mcts }))
+            }
+            CliCommand::Uci(options) => {
+                let mcts = options.mcts.config()?;
+                (options.common, Command::Uci(UciArgs { mcts }))
+            }
+            CliCommand::Serve(options) => {
+                let args = ServeArgs {
+                    game: options.common.game.clone(),
+                    mcts: options.mcts.config()?,
+                    listen: options.listen,
+                };
+                (options.common, Command::Serve(args))
+            }
+            CliCommand::Learner(options) => {
+                let args = LearnerArgs {
+                    listen: options.listen,
+                    queue: options.queue,
+                    output: options.output,
+                    checkpoint_dir: options.checkpoint_dir,
+                    gated: options.gated,
+                    games: options.games,
+                };
+                (options.common, Command::Learner(args))
+            }
+            CliCommand::Evaluator(options) => {
+                let args = EvaluatorArgs {
+                    game: options.common.game.clone(),
+                    mcts: options.mcts.config()?,
+                    checkpoint_dir: required(options.checkpoint_dir, "checkpoint-dir")?,
+                    gating: GatingConfig {
+                        games: options.games,
+                        threshold: options.threshold,
+                    },
+                    baseline: options.baseline,
+                    interval: options.interval,
+                    evaluations: options.evaluations,
+                    early_stopping: options
+                        .patience
+                        .map(|patience| EarlyStopping::new(patience, options.min_delta)),
+                    metrics: options.metrics.config(),
+                };
+                (options.common, Command::Evaluator(args))
+            }
+            CliCommand::InspectData { path } => return Ok(Command::InspectData(path)),
+            CliCommand::Sweep(args) => return Ok(Command::Sweep(args)),
+            CliCommand::Games => return Ok(Command::Games),
+        };
+        Ok(match common.deterministic {
+            Some(seed) => Command::Deterministic {
+                seed,
+                command: Box::new(command),
+            },
+            None => command,
+        })
+    }
+}
+
+/// The value of an option without a default, which either the flag or the config sets.
+fn required<T>(value: Option<T>, name: &str) -> anyhow::Result<T> {
+    value.ok_or_else(|| anyhow!("--{} is required", name))
+}
+
+impl MctsOptions {
+    fn config(self) -> anyhow::Result<MctsConfig> {
+        let progressive_widening = match (self.widening_c, self.widening_alpha) {
+            (Some(c), Some(alpha)) => Some(ProgressiveWidening { c, alpha }),
+            (None, None) => None,
+            _ => bail!("progressive widening needs both c and alpha"),
+        };
+        if !(0. ..=1.).contains(&self.margin_weight) {
+            bail!("--margin-weight must be between 0 and 1");
+        }
+        if self.margin_scale <= 0. {
+            bail!("--margin-scale must be positive");
+        }
+        Ok(MctsConfig {
+            num_simulations: self.simulations,
+            exploration: self.exploration,
+            rollouts_per_leaf: self.rollouts_per_leaf,
+            max_rollout_depth: self.max_rollout_depth,
+            rave: self
+                .rave_equivalence
+                .map(|equivalence| Rave { equivalence }),
+            progressive_widening,
+            solver_budget: self.solver_budget,
+            mcts_solver: self.mcts_solver,
+            seed: self.seed,
+            max_nodes: self.max_nodes,
+            max_memory_bytes: self.max_memory_bytes,
+            full_tree: self.full_tree,
+            first_play_urgency: self.first_play_urgency,
+            scoring: ScoreConfig {
+                margin_weight: self.margin_weight,
+                margin_scale: self.margin_scale,
+                draw_value: self.draw_value,
+            },
+            rollout: self.rollout,
+        })
+    }
+}
+
+impl MetricsOptions {
+    fn config(self) -> Option<MetricsConfig> {
+        let format = self.metrics_format;
+        self.metrics_dir.map(|dir| MetricsConfig { dir, format })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::error::ErrorKind;
+    use muzero_rs::sweep::SweepConfig;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn parse_line(line: &str) -> anyhow::Result<Command> {
+        parse(&args(line))
+    }
+
+    fn parse_with_config(line: &str, config: &str) -> anyhow::Result<Command> {
+        super::parse_with_config(&args(line), &ConfigFile::parse(config)?)
+    }
+
+    #[test]
+    fn test_parse() {
+        for line in [
+            "",
+            "--help",
+            "help eval",
+            "eval --help",
+            "eval --games 4 -h",
+        ] {
+            let error = parse_line(line).unwrap_err();
+            let kind = error.downcast_ref::<clap::Error>().unwrap().kind();
+            assert!(
+                matches!(
+                    kind,
+                    ErrorKind::DisplayHelp | ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+                ),
+                "{}",
+                line
+            );
+        }
+        assert!(parse_line("fly --help").is_err());
+        assert!(parse_line("help fly").is_err());
+        assert_eq!(
+            parse_line("inspect-data games.traj").unwrap(),
+            Command::InspectData(PathBuf::from("games.traj"))
+        );
+        assert!(parse_line("inspect-data").is_err());
+        let Command::Deterministic { seed, command } =
+            parse_line("selfplay --deterministic 7 --games 2").unwrap()
+        else {
+            panic!("expected a deterministic command");
+        };
+        assert_eq!(seed, 7);
+        assert!(matches!(
+            *command,
+            Command::SelfPlay(SelfPlayArgs { games: 2, .. })
+        ));
+        assert!(parse_line("play --deterministic 7 --ponder 1000").is_err());
+        assert_eq!(
+            parse_line("sweep configs/sweep.toml --parallel 4").unwrap(),
+            Command::Sweep(SweepArgs {
+                config: PathBuf::from("configs/sweep.toml"),
+                output: PathBuf::from("sweep"),
+                parallel: Some(4),
+                sort: None,
+            })
+        );
+        assert!(parse_line("sweep --parallel 4").is_err());
+        let line = [
+            "analyze",
+            "--game",
+            "connect4",
+            "--position",
+            "7/7/7/7/7/3X3 O",
+        ];
+        let Command::Analyze(args) = parse(&line.map(String::from)).unwrap() else {
+            panic!("expected analyze");
+        };
+        assert_eq!(args.game, "connect4");
+        assert_eq!(args.position.as_deref(), Some("7/7/7/7/7/3X3 O"));
+        assert_eq!((args.top, args.tree), (5, None));
+        assert_eq!(
+            parse_line("testsuite --game connect4 --suite suites/connect4.txt --simulations 200")
+                .unwrap(),
+            Command::TestSuite(TestSuiteArgs {
+                game: "connect4".to_string(),
+                mcts: MctsConfig {
+                    num_simulations: 200,
+                    ..Default::default()
+                },
+                suite: PathBuf::from("suites/connect4.txt"),
+            })
+        );
+        assert!(parse_line("testsuite").is_err());
+        assert!(parse_line("sweep sweep.toml --games 4").is_err());
+        assert_eq!(
+            parse_line("play --simulations 50").unwrap(),
+            Command::Play(PlayArgs {
+                game: "tictactoe".to_string(),
+                mcts: MctsConfig {
+                    num_simulations: 50,
+                    ..Default::default()
+                },
+                ui: Ui::Text,
+                human_side: HumanSide::Players(vec![0]),
+                dump_tree: None,
+                ponder: None,
+                strength: None,
+                record: None,
+            })
+        );
+        match parse_line("play --strength beginner --ponder 5000").unwrap() {
+            Command::Play(args) => {
+                assert_eq!(args.strength, Some(Strength::Beginner));
+                assert_eq!(args.ponder, Some(5000));
+            }
+            command => panic!("{:?}", command),
+        }
+        assert!(parse_line("play --strength grandmaster").is_err());
+        let Command::Gtp(args) = parse_line("gtp --seed 3").unwrap() else {
+            panic!("expected gtp");
+        };
+        assert_eq!(args.mcts.seed, Some(3));
+        let Command::Uci(args) = parse_line("uci --simulations 400").unwrap() else {
+            panic!("expected uci");
+        };
+        assert_eq!(args.mcts.num_simulations, 400);
+        assert_eq!(
+            parse_line("convert --input games.jsonl --output games.sgf").unwrap(),
+            Command::Convert(ConvertArgs {
+                input: PathBuf::from("games.jsonl"),
+                output: PathBuf::from("games.sgf"),
+            })
+        );
+        assert_eq!(
+            parse_line("convert --input games.jsonl")
+                .unwrap_err()
+                .to_string(),
+            "--output is required"
+        );
+        assert_eq!(
+            parse_line("eval --game=hex --opponent mcts:10 --games 4").unwrap(),
+            Command::Eval(EvalArgs {
+                game: "hex".to_string(),
+                mcts: MctsConfig {
+                    num_simulations: 1000,
+                    ..Default::default()
+                },
+                agent: AgentSpec::Mcts { simulations: None },
+                opponent: AgentSpec::Mcts {
+                    simulations: Some(10)
+                },
+                games: 4,
+                sprt: None,
+                book: None,
+                metrics: None,
+            })
+        );
+        let Command::SelfPlay(args) = parse_line("selfplay --output games.jsonl").unwrap() else {
+            panic!("expected selfplay");
+        };
+        assert_eq!(args.output, PathBuf::from("games.jsonl"));
+        assert_eq!(args.temperature, 1.);
+        assert_eq!(args.metrics, None);
+        assert_eq!(args.prometheus_addr, None);
+        assert_eq!(args.learner, None);
+        assert_eq!(args.resign, None);
+        assert_eq!(args.curriculum, None);
+        let Command::SelfPlay(args) =
+            parse_line("selfplay --game gomoku --curriculum 9:100,15").unwrap()
+        else {
+            panic!("expected selfplay");
+        };
+        assert_eq!(args.curriculum.unwrap().max_size(), 15);
+        assert!(parse_line("selfplay --curriculum 15:100,9").is_err());
+        let Command::SelfPlay(args) =
+            parse_line("selfplay --resign-threshold -0.95 --resign-moves 3").unwrap()
+        else {
+            panic!("expected selfplay");
+        };
+        assert_eq!(
+            args.resign,
+            Some(ResignConfig {
+                threshold: -0.95,
+                moves: 3,
+                playthrough_fraction: 0.1,
+            })
+        );
+        assert_eq!(
+            parse_line("learner --queue 8 --games 100").unwrap(),
+            Command::Learner(LearnerArgs {
+                listen: ([0, 0, 0, 0], 9185).into(),
+                queue: 8,
+                output: PathBuf::from("selfplay.traj"),
+                checkpoint_dir: None,
+                gated: false,
+                games: Some(100),
+            })
+        );
+        assert_eq!(
+            parse_line("evaluator --checkpoint-dir ckpt --baseline mcts:800 --games 20").unwrap(),
+            Command::Evaluator(EvaluatorArgs {
+                game: "tictactoe".to_string(),
+                mcts: MctsConfig {
+                    num_simulations: 1000,
+                    ..Default::default()
+                },
+                checkpoint_dir: PathBuf::from("ckpt"),
+                gating: GatingConfig {
+                    games: 20,
+                    threshold: 0.55,
+                },
+                baseline: Some(AgentSpec::Mcts {
+                    simulations: Some(800),
+                }),
+                interval: 60,
+                evaluations: None,
+                early_stopping: None,
+                metrics: None,
+            })
+        );
+        assert!(parse_line("evaluator").is_err());
+        let Command::Evaluator(args) =
+            parse_line("evaluator --checkpoint-dir ckpt --patience 5 --min-delta 20").unwrap()
+        else {
+            panic!("expected evaluator");
+        };
+        assert_eq!(args.early_stopping, Some(EarlyStopping::new(5, 20.)));
+        let Command::Serve(args) = parse_line("serve --listen 0.0.0.0:80").unwrap() else {
+            panic!("expected serve");
+        };
+        assert_eq!(args.listen, ([0, 0, 0, 0], 80).into());
+        let Command::SelfPlay(args) =
+            parse_line("selfplay --prometheus-addr 0.0.0.0:9184").unwrap()
+        else {
+            panic!("expected selfplay");
+        };
+        assert_eq!(args.prometheus_addr, Some(([0, 0, 0, 0], 9184).into()));
+        let Command::Train(args) =
+            parse_line("train --metrics-dir runs/a --metrics-format csv --stacked-frames 8")
+                .unwrap()
+        else {
+            panic!("expected train");
+        };
+        assert_eq!(args.stacked_frames, Some(8));
+        assert_eq!(args.unroll_steps, 5);
+        assert_eq!(
+            args.metrics,
+            Some(MetricsConfig {
+                dir: PathBuf::from("runs/a"),
+                format: MetricsFormat::Csv,
+            })
+        );
+        assert_eq!(args.optimizer, OptimizerConfig::default());
+        assert_eq!(args.seed, None);
+        assert_eq!((args.batch_size, args.hidden_size), (128, 64));
+        assert_eq!(args.device, Device::Cpu);
+        let Command::Train(args) = parse_line(
+            "train --optimizer adamw --lr 0.001 --lr-schedule cosine:1000 --warmup-steps 100 \
+             --clip-grad-norm 5 --seed 7",
+        )
+        .unwrap() else {
+            panic!("expected train");
+        };
+        assert_eq!(args.optimizer.kind, OptimizerKind::AdamW);
+        assert_eq!(
+            args.optimizer.schedule,
+            LrSchedule {
+                learning_rate: 0.001,
+                warmup_steps: 100,
+                decay: Decay::Cosine { steps: 1000 },
+            }
+        );
+        assert_eq!(args.optimizer.clip_grad_norm, Some(5.));
+        assert_eq!(args.seed, Some(7));
+        assert_eq!(args.joint, None);
+        assert_eq!(args.networks, Networks::Shared);
+        assert!(parse_line("train --lr-schedule linear").is_err());
+        assert!(parse_line("train --interleave 0").is_err());
+        assert!(parse_line("train --batch-size 0").is_err());
+        assert!(parse_line("train --checkpoint-interval 0").is_err());
+        let Command::Train(args) = parse_line("train --game hex --curriculum 5:10,7").unwrap()
+        else {
+            panic!("expected train");
+        };
+        assert_eq!(args.curriculum.unwrap().max_size(), 7);
+        assert!(parse_line("train --device gpu").is_err());
+        let Command::Train(args) = parse_with_config(
+            "train --networks per-game",
+            "[train]\njoint = \"go:9=go.traj hex=hex.traj\"\ninterleave = 2",
+        )
+        .unwrap() else {
+            panic!("expected train");
+        };
+        assert_eq!(args.joint.unwrap().games()[1].game, "hex");
+        assert_eq!(args.networks, Networks::PerGame);
+        assert_eq!(args.interleave, 2);
+        let Command::Tournament(args) =
+            parse_line("tournament --agents random,mcts:50,mcts --output t.csv").unwrap()
+        else {
+            panic!("expected tournament");
+        };
+        let agents: Vec<_> = args.agents.iter().map(ToString::to_string).collect();
+        assert_eq!(agents, ["random", "mcts:50", "mcts"]);
+        assert_eq!(args.output, Some(PathBuf::from("t.csv")));
+        let Command::Play(args) = parse_line("play --ui tui").unwrap() else {
+            panic!("expected play");
+        };
+        assert_eq!(args.ui, Ui::Tui);
+        for (line, side) in [
+            ("play --human-side O", HumanSide::Players(vec![1])),
+            ("play --human-side 0,white", HumanSide::Players(vec![0, 1])),
+            ("play --human-side none", HumanSide::Players(vec![])),
+            ("play --human-side all", HumanSide::All),
+            ("play --first-move agent", HumanSide::Players(vec![1])),
+        ] {
+            let Command::Play(args) = parse_line(line).unwrap() else {
+                panic!("expected play");
+            };
+            assert_eq!(args.human_side, side, "{}", line);
+        }
+        assert_eq!(
+            HumanSide::Players(vec![1]).humans(3).unwrap(),
+            [false, true, false]
+        );
+        assert_eq!(HumanSide::All.humans(2).unwrap(), [true, true]);
+        assert!(HumanSide::Players(vec![2]).humans(2).is_err());
+    }
+
+    #[test]
+    fn test_help() {
+        Cli::command().debug_assert();
+        let help = Cli::command()
+            .find_subcommand_mut("eval")
+            .unwrap()
+            .render_help()
+            .to_string();
+        assert!(help.starts_with("Play a match between two agents\n"));
+        for flag in ["--config", "--simulations", "--metrics-dir", "--sprt-elo0"] {
+            assert!(help.contains(flag), "{}", flag);
+        }
+        assert!(!help.contains("--ui"));
+        assert!(!help.contains("--threshold"));
+        assert!(help.ends_with("Flags override the file.\n"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        for line in [
+            "fly",
+            "play --simulations",
+            "play --simulations many",
+            "play --simulations 1 --simulations 2",
+            "play --games 3",
+            "play extra",
+            "play --widening-c 2",
+            "play --rollouts-per-leaf 0",
+            "play --ui gui",
+            "play --metrics-dir runs",
+            "eval --metrics-format json",
+            "selfplay --prometheus-addr localhost",
+            "learner --simulations 10",
+            "play --human-side red",
+            "play --first-move nobody",
+            "play --human-side 1 --first-move agent",
+            "eval --agent mcts:",
+            "eval --sprt-elo0 0",
+            "eval --games 0",
+            "tournament --games 0",
+            "tournament --agents mcts",
+            "tournament --agents random,,mcts",
+            "eval --sprt-elo0 10 --sprt-elo1 0",
+            "train --config /nonexistent/config.toml",
+        ] {
+            assert!(parse_line(line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_config() {
+        let config = r#"
+game = "gomoku"
+
+[mcts]
+simulations = 200
+exploration = 0.5
+max_rollout_depth = 50
+rollouts_per_leaf = 4
+rollout = "tactical"
+solver_budget = 10000
+max_nodes = 100000
+full_tree = "recycle"
+first_play_urgency = "parent:0.2"
+
+[mcts.progressive_widening]
+c = 2
+alpha = 0.5
+
+[mcts.scoring]
+margin_weight = 0.5
+margin_scale = 81
+
+[selfplay]
+games = 3
+"#;
+        let Command::SelfPlay(args) =
+            parse_with_config("selfplay --simulations 400 --output out.jsonl", config).unwrap()
+        else {
+            panic!("expected selfplay");
+        };
+        assert_eq!(args.game, "gomoku");
+        // Flags win over the file.
+        assert_eq!(
+            args.mcts,
+            MctsConfig {
+                num_simulations: 400,
+                exploration: 0.5,
+                rollouts_per_leaf: 4,
+                max_rollout_depth: Some(50),
+                progressive_widening: Some(ProgressiveWidening { c: 2., alpha: 0.5 }),
+                rave: None,
+                solver_budget: Some(10000),
+                mcts_solver: false,
+                seed: None,
+                max_nodes: Some(100000),
+                max_memory_bytes: None,
+                full_tree: FullTree::Recycle,
+                first_play_urgency: FirstPlayUrgency::Parent { reduction: 0.2 },
+                scoring: ScoreConfig {
+                    margin_weight: 0.5,
+                    margin_scale: 81.,
+                    draw_value: 0.,
+                },
+                rollout: Rollout::Tactical,
+            }
+        );
+        assert_eq!(args.games, 3);
+        assert_eq!(args.output, PathBuf::from("out.jsonl"));
+
+        assert!(parse_with_config("play", "[mcts]\nsimulation = 1").is_err());
+        assert!(parse_with_config("play", "[mcts]\nsimulations = -1").is_err());
+        assert!(parse_with_config("play", "game = [\"go\"]").is_err());
+    }
+
+    #[test]
+    fn test_shipped_configs() {
+        for config in [
+            include_str!("../configs/tictactoe.toml"),
+            include_str!("../configs/gomoku.toml"),
+            include_str!("../configs/go9.toml"),
+            include_str!("../configs/joint.toml"),
+            include_str!("../configs/gomoku-curriculum.toml"),
+        ] {
+            for command in ["play", "selfplay", "train", "eval", "tournament"] {
+                parse_with_config(command, config).unwrap();
+            }
+        }
+        for sweep in [
+            include_str!("../configs/sweep.toml"),
+            include_str!("../configs/sweep-train.toml"),
+        ] {
+            let sweep = SweepConfig::parse(sweep).unwrap();
+            for run in sweep.runs() {
+                parse_with_config(&sweep.command, &sweep.config(&run)).unwrap();
+            }
+        }
+    }
+}
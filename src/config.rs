@@ -0,0 +1,218 @@
+//! The config files of the command line, which set the defaults of its options: `game` at the
+//! top, the options of the search in `[mcts]`, those of the metrics in `[metrics]` and the
+//! others in the section of their command, e.g. `games = 100` in `[eval]`. Flags override
+//! the file.
+
+use std::{fs, path::Path};
+
+use toml::{Table, Value};
+
+use crate::error::{Context, IoError, ParseError, Result};
+
+/// Every key a config file may set, and the flag of the option it sets.
+pub const KEYS: [(&str, &str); 94] = [
+    ("game", "game"),
+    ("deterministic", "deterministic"),
+    ("mcts.simulations", "simulations"),
+    ("mcts.exploration", "exploration"),
+    ("mcts.max_rollout_depth", "max-rollout-depth"),
+    ("mcts.rollouts_per_leaf", "rollouts-per-leaf"),
+    ("mcts.rollout", "rollout"),
+    ("mcts.solver_budget", "solver-budget"),
+    ("mcts.mcts_solver", "mcts-solver"),
+    ("mcts.seed", "seed"),
+    ("mcts.max_nodes", "max-nodes"),
+    ("mcts.max_memory_bytes", "max-memory-bytes"),
+    ("mcts.full_tree", "full-tree"),
+    ("mcts.first_play_urgency", "first-play-urgency"),
+    ("mcts.scoring.margin_weight", "margin-weight"),
+    ("mcts.scoring.margin_scale", "margin-scale"),
+    ("mcts.scoring.draw_value", "draw-value"),
+    ("mcts.rave.equivalence", "rave-equivalence"),
+    ("mcts.progressive_widening.c", "widening-c"),
+    ("mcts.progressive_widening.alpha", "widening-alpha"),
+    ("metrics.dir", "metrics-dir"),
+    ("metrics.format", "metrics-format"),
+    ("play.ui", "ui"),
+    ("play.human_side", "human-side"),
+    ("play.first_move", "first-move"),
+    ("play.dump_tree", "dump-tree"),
+    ("play.ponder", "ponder"),
+    ("play.strength", "strength"),
+    ("play.record", "record"),
+    ("analyze.position", "position"),
+    ("analyze.top", "top"),
+    ("analyze.tree", "tree"),
+    ("testsuite.suite", "suite"),
+    ("selfplay.games", "games"),
+    ("selfplay.temperature", "temperature"),
+    ("selfplay.output", "output"),
+    ("selfplay.learner", "learner"),
+    ("selfplay.prometheus_addr", "prometheus-addr"),
+    ("selfplay.record", "record"),
+    ("selfplay.resign_threshold", "resign-threshold"),
+    ("selfplay.resign_moves", "resign-moves"),
+    ("selfplay.resign_playthrough", "resign-playthrough"),
+    ("selfplay.curriculum", "curriculum"),
+    ("selfplay.joint", "joint"),
+    ("replay.record", "record"),
+    ("replay.index", "index"),
+    ("serve.listen", "listen"),
+    ("convert.input", "input"),
+    ("convert.output", "output"),
+    ("train.data", "data"),
+    ("train.steps", "steps"),
+    ("train.checkpoint_dir", "checkpoint-dir"),
+    ("train.stacked_frames", "stacked-frames"),
+    ("train.unroll_steps", "unroll-steps"),
+    ("train.optimizer", "optimizer"),
+    ("train.lr", "lr"),
+    ("train.lr_schedule", "lr-schedule"),
+    ("train.warmup_steps", "warmup-steps"),
+    ("train.momentum", "momentum"),
+    ("train.weight_decay", "weight-decay"),
+    ("train.clip_grad_norm", "clip-grad-norm"),
+    ("eval.agent", "agent"),
+    ("eval.opponent", "opponent"),
+    ("eval.games", "games"),
+    ("eval.sprt.elo0", "sprt-elo0"),
+    ("eval.sprt.elo1", "sprt-elo1"),
+    ("eval.book", "book"),
+    ("tournament.agents", "agents"),
+    ("tournament.games", "games"),
+    ("tournament.output", "output"),
+    ("tournament.book", "book"),
+    ("learner.listen", "listen"),
+    ("learner.queue", "queue"),
+    ("learner.output", "output"),
+    ("learner.checkpoint_dir", "checkpoint-dir"),
+    ("learner.gated", "gated"),
+    ("learner.games", "games"),
+    ("evaluator.checkpoint_dir", "checkpoint-dir"),
+    ("evaluator.games", "games"),
+    ("evaluator.threshold", "threshold"),
+    ("evaluator.baseline", "baseline"),
+    ("evaluator.interval", "interval"),
+    ("evaluator.evaluations", "evaluations"),
+    ("evaluator.patience", "patience"),
+    ("evaluator.min_delta", "min-delta"),
+    ("train.seed", "seed"),
+    ("train.joint", "joint"),
+    ("train.networks", "networks"),
+    ("train.interleave", "interleave"),
+    ("train.batch_size", "batch-size"),
+    ("train.hidden_size", "hidden-size"),
+    ("train.device", "device"),
+    ("train.checkpoint_interval", "checkpoint-interval"),
+    ("train.curriculum", "curriculum"),
+];
+
+/// An option a config file sets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigOption {
+    /// The dotted key, e.g. `mcts.rave.equivalence`.
+    pub key: String,
+    /// The long flag of the option, without the dashes, e.g. `rave-equivalence`.
+    pub flag: &'static str,
+    /// The value, as it would be written after the flag.
+    pub value: String,
+}
+
+impl ConfigOption {
+    /// The section of the key, like `mcts` or `eval`; `None` at the top.
+    pub fn section(&self) -> Option<&str> {
+        self.key.split_once('.').map(|(section, _)| section)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigFile {
+    pub options: Vec<ConfigOption>,
+}
+
+impl ConfigFile {
+    pub fn parse(text: &str) -> Result<Self> {
+        let table: Table = toml::from_str(text).map_err(|e| ParseError::Invalid(e.to_string()))?;
+        let mut values = vec![];
+        collect(&table, "", &mut values);
+        let options = values
+            .into_iter()
+            .map(|(key, value)| {
+                let Some(&(_, flag)) = KEYS.iter().find(|(k, _)| *k == key) else {
+                    return Err(ParseError::unknown("config key", key).into());
+                };
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    Value::Integer(_) | Value::Float(_) | Value::Boolean(_) => value.to_string(),
+                    value => {
+                        return Err(ParseError::Invalid(format!(
+                            "invalid value {} for `{}`",
+                            value, key
+                        ))
+                        .into())
+                    }
+                };
+                Ok(ConfigOption { key, flag, value })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { options })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).map_err(IoError::path("read", path))?;
+        Self::parse(&text).with_context(|| format!("failed to parse {}", path.display()))
+    }
+}
+
+/// Collect the dotted keys of the values in `table`, and the values.
+fn collect<'a>(table: &'a Table, prefix: &str, values: &mut Vec<(String, &'a Value)>) {
+    for (key, value) in table {
+        let path = format!("{}{}", prefix, key);
+        match value {
+            Value::Table(table) => collect(table, &format!("{}.", path), values),
+            _ => values.push((path, value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = ConfigFile::parse(
+            "game = \"go:9\"\n[mcts]\nsimulations = 800\nrave.equivalence = 0.5\n\
+             [learner]\ngated = true\n",
+        )
+        .unwrap();
+        let options: Vec<_> = config
+            .options
+            .iter()
+            .map(|option| (option.section(), option.flag, option.value.as_str()))
+            .collect();
+        assert_eq!(
+            options,
+            [
+                (None, "game", "go:9"),
+                (Some("mcts"), "simulations", "800"),
+                (Some("mcts"), "rave-equivalence", "0.5"),
+                (Some("learner"), "gated", "true"),
+            ]
+        );
+        for text in [
+            "[mcts]\nsimulation = 1",
+            "game = [\"go\"]",
+            "games = 3",
+            "[eval\ngames = 3",
+        ] {
+            assert!(ConfigFile::parse(text).is_err(), "{}", text);
+        }
+        assert_eq!(
+            ConfigFile::parse("[mcts]\nsimulation = 1")
+                .unwrap_err()
+                .to_string(),
+            "unknown config key `mcts.simulation`"
+        );
+    }
+}
@@ -0,0 +1,334 @@
+//! Self-play curricula over board sizes: the first games on small boards, where random play
+//! ends quickly and the search sees to the end, the later ones on larger boards. The histories
+//! of every stage are padded to the largest board, so that one network trains on all of them.
+
+use std::{fmt, str::FromStr};
+
+use crate::{
+    error::{ConfigError, Context, Error, Result},
+    history::GameHistory,
+};
+
+/// A board size and how many games to play on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stage {
+    pub size: usize,
+    /// `None` for every game left, in the last stage.
+    pub games: Option<usize>,
+}
+
+/// Stages of growing board sizes, written like `9:100,13:100,15`: 100 games on 9x9, 100 on
+/// 13x13, then 15x15 for the rest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Curriculum {
+    stages: Vec<Stage>,
+}
+
+impl Curriculum {
+    pub fn new(stages: Vec<Stage>) -> Result<Self> {
+        let Some((last, rest)) = stages.split_last() else {
+            return Err(ConfigError::Invalid("a curriculum needs a stage".to_string()).into());
+        };
+        for stage in rest {
+            if stage.games.is_none_or(|games| games == 0) {
+                return Err(ConfigError::Invalid(
+                    "only the last stage can leave out its number of games".to_string(),
+                )
+                .into());
+            }
+        }
+        if last.games == Some(0) {
+            return Err(ConfigError::Invalid("stages need at least one game".to_string()).into());
+        }
+        for pair in stages.windows(2) {
+            if pair[0].size >= pair[1].size {
+                return Err(ConfigError::Invalid(
+                    "the boards must grow from stage to stage".to_string(),
+                )
+                .into());
+            }
+        }
+        Ok(Self { stages })
+    }
+
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    /// The size of the largest board, the last.
+    pub fn max_size(&self) -> usize {
+        self.stages.last().expect("a curriculum has stages").size
+    }
+
+    /// The index of the stage of the game with index `game`, counting from 0. The last stage
+    /// continues after its games.
+    pub fn stage_index(&self, game: usize) -> usize {
+        let mut start = 0;
+        for (i, stage) in self.stages.iter().enumerate() {
+            match stage.games {
+                Some(games) if game >= start + games => start += games,
+                _ => return i,
+            }
+        }
+        self.stages.len() - 1
+    }
+
+    pub fn stage(&self, game: usize) -> &Stage {
+        &self.stages[self.stage_index(game)]
+    }
+}
+
+impl fmt::Display for Curriculum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, stage) in self.stages.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", stage.size)?;
+            if let Some(games) = stage.games {
+                write!(f, ":{}", games)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Curriculum {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let stages = s
+            .split(',')
+            .map(|stage| {
+                let stage = stage.trim();
+                let (size, games) = match stage.split_once(':') {
+                    Some((size, games)) => (size, Some(games)),
+                    None => (stage, None),
+                };
+                Ok(Stage {
+                    size: size
+                        .parse()
+                        .with_context(|| format!("invalid board size `{}`", size))?,
+                    games: games
+                        .map(|games| {
+                            games
+                                .parse()
+                                .with_context(|| format!("invalid number of games `{}`", games))
+                        })
+                        .transpose()?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Self::new(stages)
+    }
+}
+
+/// Maps the observations and actions of a board game to those of the same game on a larger
+/// board, for games whose observations are planes of the board and whose actions index its
+/// points row by row, followed by any others like passing in Go. The smaller board sits in the
+/// top left corner, and the points around it are zeros in every plane: empty, but never legal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoardPadding {
+    planes: usize,
+    /// The height and width of the board, and of the padded one.
+    from: (usize, usize),
+    to: (usize, usize),
+    /// The actions which aren't points of the board.
+    other_actions: usize,
+}
+
+impl BoardPadding {
+    /// Padding from a game with `observation_shape` and `action_space_size` to one with
+    /// `max_observation_shape` and `max_action_space_size`.
+    pub fn new(
+        observation_shape: &[usize],
+        action_space_size: usize,
+        max_observation_shape: &[usize],
+        max_action_space_size: usize,
+    ) -> Result<Self> {
+        let (&[planes, height, width], &[max_planes, max_height, max_width]) =
+            (observation_shape, max_observation_shape)
+        else {
+            return Err(ConfigError::Invalid(
+                "only observations of planes of the board can be padded".to_string(),
+            )
+            .into());
+        };
+        if planes != max_planes || height > max_height || width > max_width {
+            return Err(ConfigError::Invalid(format!(
+                "observations of shape {:?} don't fit into {:?}",
+                observation_shape, max_observation_shape
+            ))
+            .into());
+        }
+        let other_actions = action_space_size.checked_sub(height * width);
+        if !(other_actions.is_some()
+            && max_action_space_size.checked_sub(max_height * max_width) == other_actions)
+        {
+            return Err(ConfigError::Invalid(
+                "the actions aren't the points of the board and the same others on both boards"
+                    .to_string(),
+            )
+            .into());
+        }
+        Ok(Self {
+            planes,
+            from: (height, width),
+            to: (max_height, max_width),
+            other_actions: other_actions.unwrap(),
+        })
+    }
+
+    pub fn observation(&self, observation: &[f32]) -> Vec<f32> {
+        let (height, width) = self.from;
+        let (max_height, max_width) = self.to;
+        let mut padded = vec![0.; self.planes * max_height * max_width];
+        for (plane, padded) in observation
+            .chunks(height * width)
+            .zip(padded.chunks_mut(max_height * max_width))
+        {
+            for (row, padded) in plane.chunks(width).zip(padded.chunks_mut(max_width)) {
+                padded[..width].copy_from_slice(row);
+            }
+        }
+        padded
+    }
+
+    /// The index on the larger board of the action with `index`.
+    pub fn action(&self, index: usize) -> usize {
+        let (height, width) = self.from;
+        let (max_height, max_width) = self.to;
+        if index < height * width {
+            index / width * max_width + index % width
+        } else {
+            index - height * width + max_height * max_width
+        }
+    }
+
+    pub fn action_space_size(&self) -> usize {
+        self.to.0 * self.to.1 + self.other_actions
+    }
+
+    /// A distribution over the actions, like the visits of a search, on the larger board.
+    pub fn policy(&self, policy: &[f32]) -> Vec<f32> {
+        let mut padded = vec![0.; self.action_space_size()];
+        for (index, &p) in policy.iter().enumerate() {
+            padded[self.action(index)] = p;
+        }
+        padded
+    }
+
+    /// `history` as if it had been played on the larger board.
+    pub fn history(&self, history: &GameHistory) -> GameHistory {
+        let actions = |indices: &[usize]| indices.iter().map(|&i| self.action(i)).collect();
+        GameHistory {
+            observations: history
+                .observations
+                .iter()
+                .map(|observation| self.observation(observation))
+                .collect(),
+            actions: actions(&history.actions),
+            child_visits: history
+                .child_visits
+                .iter()
+                .map(|policy| self.policy(policy))
+                .collect(),
+            legal_actions: history
+                .legal_actions
+                .iter()
+                .map(|legal| actions(legal))
+                .collect(),
+            ..history.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::Game,
+        games::{go::Go, gomoku::Gomoku},
+        muzero::SearchStatistics,
+    };
+
+    #[test]
+    fn test_curriculum() {
+        let curriculum: Curriculum = "9:2,13:1,15".parse().unwrap();
+        assert_eq!(curriculum.to_string(), "9:2,13:1,15");
+        assert_eq!(curriculum.max_size(), 15);
+        let sizes: Vec<_> = (0..5).map(|game| curriculum.stage(game).size).collect();
+        assert_eq!(sizes, [9, 9, 13, 15, 15]);
+        assert_eq!(curriculum.stage_index(2), 1);
+        // A last stage with a number of games still plays every game left.
+        let curriculum: Curriculum = "7:1,9:1".parse().unwrap();
+        assert_eq!(curriculum.stage(5).size, 9);
+
+        for curriculum in [
+            "",
+            "9,13",
+            "13:5,9",
+            "9:0,13",
+            "9:5,9",
+            "nine:5,13",
+            "9:x,13",
+        ] {
+            assert!(curriculum.parse::<Curriculum>().is_err(), "{}", curriculum);
+        }
+    }
+
+    #[test]
+    fn test_padding() {
+        let small = Go::new(5);
+        let large = Go::new(9);
+        let padding = BoardPadding::new(
+            &small.observation_shape(),
+            small.action_space_size(),
+            &large.observation_shape(),
+            large.action_space_size(),
+        )
+        .unwrap();
+        assert_eq!(padding.action_space_size(), large.action_space_size());
+        // The same points, and passing, on both boards.
+        for index in [0, 4, 7, 24, 25] {
+            assert_eq!(
+                large.index_to_action(padding.action(index)),
+                small.index_to_action(index)
+            );
+        }
+
+        let mut game = Go::new(5);
+        let mut history = GameHistory::default();
+        let stats = |index: usize| SearchStatistics {
+            root_value: 0.,
+            visit_counts: (0..26).map(|i| usize::from(i == index)).collect(),
+            depth: 1,
+            nodes: 2,
+            cache: None,
+        };
+        for index in [6, 18] {
+            let action = game.index_to_action(index);
+            history.apply(&mut game, action, &stats(index)).unwrap();
+        }
+        let mut expected = Go::new(9);
+        expected
+            .step(expected.index_to_action(padding.action(6)))
+            .unwrap();
+        let padded = padding.history(&history);
+        assert_eq!(padded.actions, [10, 30]);
+        assert_eq!(padded.observations[1], expected.observation());
+        assert_eq!(padded.child_visits[1][30], 1.);
+        assert_eq!(padded.legal_actions[0].len(), 26);
+        assert_eq!(padded.rewards, history.rewards);
+
+        let gomoku = Gomoku::new(9);
+        assert!(BoardPadding::new(
+            &large.observation_shape(),
+            large.action_space_size(),
+            &gomoku.observation_shape(),
+            gomoku.action_space_size(),
+        )
+        .is_err());
+        assert!(BoardPadding::new(&[3, 9, 9], 81, &[3, 5, 5], 25).is_err());
+    }
+}
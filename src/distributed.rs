@@ -0,0 +1,432 @@
+//! Distributed self-play: actor processes play games with the latest weights of a learner and
+//! push the finished games back to it.
+//!
+//! Actors call the `Learner` gRPC service of `proto/distributed.proto`:
+//! - `Weights` asks for weights newer than the actor has. The learner answers with the version
+//!   of its latest weights, and their bytes as safetensors when the version is newer.
+//! - `PushGame` pushes a game, answered once the learner queued it. The queue is bounded, so
+//!   actors wait while the learner falls behind.
+
+use std::{
+    future::Future,
+    net::{SocketAddr, TcpListener, ToSocketAddrs},
+    sync::{
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+use tokio::runtime::{self, Runtime};
+use tonic::{
+    transport::{server::TcpIncoming, Channel, Server},
+    Request, Response, Status,
+};
+
+use crate::{
+    checkpoint::{self, Tensors},
+    error::{Context, Error, Result},
+    history::{GameHistory, UncheckedHistory},
+};
+
+/// The messages and service generated from `proto/distributed.proto`.
+mod proto {
+    tonic::include_proto!("muzero.distributed");
+}
+
+use proto::{
+    learner_client::LearnerClient as GrpcClient,
+    learner_server::{self, LearnerServer},
+    Ack, Floats, Game, Indices, WeightsReply, WeightsRequest,
+};
+
+/// The longest wait between attempts to reach the learner.
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Weights {
+    /// Bumped every time the learner publishes weights.
+    pub version: u64,
+    pub tensors: Tensors,
+}
+
+impl From<&GameHistory> for Game {
+    fn from(history: &GameHistory) -> Self {
+        let indices = |values: &[usize]| values.iter().map(|&value| value as u64).collect();
+        let floats = |rows: &[Vec<f32>]| {
+            rows.iter()
+                .map(|values| Floats {
+                    values: values.clone(),
+                })
+                .collect()
+        };
+        Game {
+            observations: floats(&history.observations),
+            to_play: indices(&history.to_play),
+            actions: indices(&history.actions),
+            rewards: history.rewards.clone(),
+            child_visits: floats(&history.child_visits),
+            root_values: history.root_values.clone(),
+            legal_actions: history
+                .legal_actions
+                .iter()
+                .map(|actions| Indices {
+                    values: indices(actions),
+                })
+                .collect(),
+            final_value: history.final_value,
+        }
+    }
+}
+
+impl TryFrom<Game> for GameHistory {
+    type Error = String;
+
+    fn try_from(game: Game) -> Result<Self, String> {
+        let indices = |values: Vec<u64>| {
+            values
+                .into_iter()
+                .map(usize::try_from)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())
+        };
+        let floats = |rows: Vec<Floats>| rows.into_iter().map(|row| row.values).collect();
+        UncheckedHistory {
+            observations: floats(game.observations),
+            to_play: indices(game.to_play)?,
+            actions: indices(game.actions)?,
+            rewards: game.rewards,
+            child_visits: floats(game.child_visits),
+            root_values: game.root_values,
+            legal_actions: game
+                .legal_actions
+                .into_iter()
+                .map(|actions| indices(actions.values))
+                .collect::<Result<_, _>>()?,
+            final_value: game.final_value,
+        }
+        .try_into()
+    }
+}
+
+/// The weights last published, encoded once for every actor.
+struct Published {
+    version: u64,
+    bytes: Vec<u8>,
+}
+
+type SharedWeights = Arc<Mutex<Option<Arc<Published>>>>;
+
+/// The gRPC service of the learner.
+struct Service {
+    games: SyncSender<GameHistory>,
+    weights: SharedWeights,
+}
+
+#[tonic::async_trait]
+impl learner_server::Learner for Service {
+    async fn weights(
+        &self,
+        request: Request<WeightsRequest>,
+    ) -> Result<Response<WeightsReply>, Status> {
+        let have = request.into_inner().version;
+        let published = self.weights.lock().unwrap().clone();
+        let safetensors = match &published {
+            Some(published) if have.is_none_or(|have| have < published.version) => {
+                published.bytes.clone()
+            }
+            _ => vec![],
+        };
+        Ok(Response::new(WeightsReply {
+            version: published.map(|published| published.version),
+            safetensors,
+        }))
+    }
+
+    async fn push_game(&self, request: Request<Game>) -> Result<Response<Ack>, Status> {
+        let game = GameHistory::try_from(request.into_inner()).map_err(Status::invalid_argument)?;
+        let games = self.games.clone();
+        // Blocks while the queue is full, which holds the actor back.
+        let queued = tokio::task::spawn_blocking(move || games.send(game).is_ok())
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        if !queued {
+            return Err(Status::unavailable("the learner stopped"));
+        }
+        Ok(Response::new(Ack {}))
+    }
+}
+
+/// Serves weights to actors and queues the games they push.
+pub struct Learner {
+    addr: SocketAddr,
+    // Dropped before the runtime, whose shutdown waits for the pushes blocked on a full queue.
+    games: Receiver<GameHistory>,
+    weights: SharedWeights,
+    /// Runs the server until dropped.
+    _runtime: Runtime,
+}
+
+impl Learner {
+    /// Listen on `addr`, queuing up to `queue` games before actors have to wait.
+    pub fn bind(addr: impl ToSocketAddrs, queue: usize) -> Result<Self> {
+        let listener = TcpListener::bind(addr).context("failed to bind the learner")?;
+        let addr = listener.local_addr()?;
+        listener.set_nonblocking(true)?;
+        let runtime = Runtime::new()?;
+        let incoming = {
+            let _runtime = runtime.enter();
+            TcpIncoming::from(tokio::net::TcpListener::from_std(listener)?)
+        };
+        let (sender, games) = mpsc::sync_channel(queue);
+        let weights = SharedWeights::default();
+        let service = LearnerServer::new(Service {
+            games: sender,
+            weights: weights.clone(),
+        })
+        .max_decoding_message_size(usize::MAX)
+        .max_encoding_message_size(usize::MAX);
+        runtime.spawn(async move {
+            let server = Server::builder().add_service(service);
+            if let Err(e) = server.serve_with_incoming(incoming).await {
+                tracing::warn!("the learner stopped serving: {}", e);
+            }
+        });
+        Ok(Self {
+            addr,
+            games,
+            weights,
+            _runtime: runtime,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Serve `tensors` to actors from now on. Returns their version.
+    pub fn publish(&self, tensors: &Tensors) -> Result<u64> {
+        let bytes = checkpoint::encode_safetensors(tensors)?;
+        let mut weights = self.weights.lock().unwrap();
+        let version = weights
+            .as_ref()
+            .map_or(1, |published| published.version + 1);
+        *weights = Some(Arc::new(Published { version, bytes }));
+        Ok(version)
+    }
+
+    /// The games pushed by actors, in the order they arrived.
+    pub fn games(&self) -> &Receiver<GameHistory> {
+        &self.games
+    }
+}
+
+async fn connect(addr: SocketAddr) -> Result<GrpcClient<Channel>> {
+    let channel = Channel::from_shared(format!("http://{}", addr))
+        .map_err(Error::backend)?
+        .connect()
+        .await
+        .map_err(Error::backend)?;
+    Ok(GrpcClient::new(channel)
+        .max_decoding_message_size(usize::MAX)
+        .max_encoding_message_size(usize::MAX))
+}
+
+/// An actor's connection to the learner, reconnecting when it drops.
+pub struct LearnerClient {
+    addr: SocketAddr,
+    /// Runs the calls, which block.
+    runtime: Runtime,
+    client: Option<GrpcClient<Channel>>,
+    /// The version of the weights received last.
+    version: Option<u64>,
+    /// How many times to retry a request, waiting twice as long every time.
+    pub retries: usize,
+    pub backoff: Duration,
+}
+
+impl LearnerClient {
+    /// A client of the learner at `addr`, which connects on its first request.
+    pub fn new(addr: SocketAddr) -> Result<Self> {
+        Ok(Self {
+            addr,
+            runtime: runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?,
+            client: None,
+            version: None,
+            retries: 10,
+            backoff: Duration::from_millis(100),
+        })
+    }
+
+    /// The weights the learner published since the last call, if any.
+    pub fn weights(&mut self) -> Result<Option<Weights>> {
+        let version = self.version;
+        let reply = self.request(|mut client| async move {
+            Ok(client
+                .weights(WeightsRequest { version })
+                .await
+                .map_err(Error::backend)?
+                .into_inner())
+        })?;
+        let weights = match reply.version {
+            Some(version) if !reply.safetensors.is_empty() => Weights {
+                version,
+                tensors: checkpoint::parse_safetensors(&reply.safetensors)?,
+            },
+            _ => return Ok(None),
+        };
+        self.version = Some(weights.version);
+        Ok(Some(weights))
+    }
+
+    /// Push a finished game, waiting while the learner's queue is full. A game whose
+    /// acknowledgement got lost is pushed again, so the learner may receive it twice.
+    pub fn push(&mut self, history: &GameHistory) -> Result<()> {
+        let game = Game::from(history);
+        self.request(|mut client| {
+            let game = game.clone();
+            async move {
+                client.push_game(game).await.map_err(Error::backend)?;
+                Ok(())
+            }
+        })
+    }
+
+    /// Run `call` on a client, reconnecting and retrying it when it fails.
+    fn request<T, F>(&mut self, mut call: impl FnMut(GrpcClient<Channel>) -> F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let mut backoff = self.backoff;
+        let mut attempt = 0;
+        loop {
+            let client = match &self.client {
+                Some(client) => Ok(client.clone()),
+                None => self.runtime.block_on(connect(self.addr)),
+            };
+            let result = client.and_then(|client| {
+                self.client = Some(client.clone());
+                self.runtime.block_on(call(client))
+            });
+            match result {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < self.retries => {
+                    tracing::warn!(
+                        "learner at {}: {:#}; retrying in {:?}",
+                        self.addr,
+                        e,
+                        backoff
+                    );
+                    self.client = None;
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    attempt += 1;
+                }
+                Err(e) => {
+                    self.client = None;
+                    return Err(e).context(format!("failed to reach the learner at {}", self.addr));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        checkpoint::Tensor, games::tic_tac_toe::TicTacToe, muzero::SearchStatistics, Game,
+    };
+
+    fn history(moves: &[usize]) -> GameHistory {
+        let mut game = TicTacToe::new();
+        let mut history = GameHistory::default();
+        for &action in moves {
+            let stats = SearchStatistics {
+                root_value: 0.,
+                visit_counts: vec![1; 9],
+                depth: 1,
+                nodes: 2,
+                cache: None,
+            };
+            let action = game.index_to_action(action);
+            history.apply(&mut game, action, &stats).unwrap();
+        }
+        history
+    }
+
+    #[test]
+    fn test_weights() {
+        let learner = Learner::bind("127.0.0.1:0", 4).unwrap();
+        let mut client = LearnerClient::new(learner.local_addr()).unwrap();
+        assert_eq!(client.weights().unwrap(), None);
+        let tensors = vec![(
+            "bias".to_string(),
+            Tensor::new(vec![2], vec![0.5, -1.]).unwrap(),
+        )];
+        assert_eq!(learner.publish(&tensors).unwrap(), 1);
+        assert_eq!(
+            client.weights().unwrap(),
+            Some(Weights {
+                version: 1,
+                tensors: tensors.clone(),
+            })
+        );
+        // Up to date.
+        assert_eq!(client.weights().unwrap(), None);
+        assert_eq!(learner.publish(&tensors).unwrap(), 2);
+        assert_eq!(client.weights().unwrap().unwrap().version, 2);
+    }
+
+    #[test]
+    fn test_backpressure() {
+        let learner = Learner::bind("127.0.0.1:0", 1).unwrap();
+        let addr = learner.local_addr();
+        let actor = thread::spawn(move || {
+            let mut client = LearnerClient::new(addr).unwrap();
+            for moves in [&[4][..], &[4, 0], &[4, 0, 8]] {
+                client.push(&history(moves)).unwrap();
+            }
+        });
+        // The first game fills the queue and the second waits in the learner, so the actor
+        // can't push the third.
+        thread::sleep(Duration::from_millis(200));
+        assert!(!actor.is_finished());
+        let lengths: Vec<_> = (0..3)
+            .map(|_| learner.games().recv().unwrap().len())
+            .collect();
+        assert_eq!(lengths, [1, 2, 3]);
+        actor.join().unwrap();
+
+        let game = proto::Game {
+            actions: vec![4],
+            ..Default::default()
+        };
+        assert!(GameHistory::try_from(game).is_err());
+    }
+
+    #[test]
+    fn test_reconnect() {
+        // Reserve a port, then start the learner on it only after the actor tried it.
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let actor = thread::spawn(move || {
+            let mut client = LearnerClient::new(addr).unwrap();
+            client.backoff = Duration::from_millis(50);
+            client.push(&history(&[0]))
+        });
+        thread::sleep(Duration::from_millis(100));
+        let learner = Learner::bind(addr, 1).unwrap();
+        assert_eq!(learner.games().recv().unwrap(), history(&[0]));
+        actor.join().unwrap().unwrap();
+
+        let mut client = LearnerClient::new(addr).unwrap();
+        client.retries = 0;
+        drop(learner);
+        assert!(client.push(&history(&[0])).is_err());
+    }
+}
@@ -0,0 +1,294 @@
+//! An object-safe view of [`Game`], so that games can be chosen at runtime and stored as
+//! `Box<dyn DynGame>`, made with [`boxed`]. Actions and players are plain indices.
+//!
+//! `Box<dyn DynGame>` implements [`Game`] itself, so searches and agents run on boxed games
+//! unchanged.
+
+use std::fmt;
+
+use crate::{
+    error::{GameError, ParseError},
+    game::Game,
+};
+
+pub trait DynGame: fmt::Display + Send {
+    /// Play the action with index `action`.
+    fn step(&mut self, action: usize) -> Result<f32, GameError>;
+
+    /// The indices of the legal actions.
+    fn legal_actions(&self) -> Vec<usize>;
+
+    fn action_space_size(&self) -> usize;
+
+    /// The action with index `action`, as the game names it.
+    fn action_name(&self, action: usize) -> String;
+
+    /// The index of the player to move.
+    fn to_play(&self) -> usize;
+
+    fn num_players(&self) -> usize;
+
+    fn observation_shape(&self) -> Vec<usize>;
+
+    fn observation(&self) -> Vec<f32>;
+
+    fn canonical_observation(&self) -> Vec<f32>;
+
+    fn state_hash(&self) -> u64;
+
+    fn terminated(&self) -> bool;
+
+    fn truncated(&self) -> bool;
+
+    /// The index of the winner.
+    fn winner(&self) -> Option<usize>;
+
+    fn returns(&self) -> Vec<f32>;
+
+    fn scores(&self) -> Vec<f32>;
+
+    /// The indices of the moves which would win at once, see [`Game::winning_moves`].
+    fn winning_moves(&self, opponent: bool) -> Vec<usize>;
+
+    fn to_notation(&self) -> Option<String>;
+
+    /// The position `notation` describes in a game of the same type, see
+    /// [`Game::from_notation`].
+    fn parse_notation(&self, notation: &str) -> Result<Box<dyn DynGame>, ParseError>;
+
+    /// The indices and probabilities of the outcomes of a pending chance event.
+    fn chance_outcomes(&self) -> Vec<(usize, f32)>;
+
+    fn clone_box(&self) -> Box<dyn DynGame>;
+}
+
+/// Box `game` as a [`DynGame`].
+pub fn boxed<G: Game + Send + 'static>(game: G) -> Box<dyn DynGame> {
+    Box::new(Boxed(game))
+}
+
+#[derive(Clone)]
+struct Boxed<G>(G);
+
+impl<G: Game> fmt::Display for Boxed<G> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<G: Game + Send + 'static> DynGame for Boxed<G> {
+    fn step(&mut self, action: usize) -> Result<f32, GameError> {
+        if action >= self.0.action_space_size() {
+            return Err(GameError::illegal_move(&action, "out of the action space"));
+        }
+        self.0.step(self.0.index_to_action(action))
+    }
+
+    fn legal_actions(&self) -> Vec<usize> {
+        self.0
+            .get_available_moves()
+            .iter()
+            .map(|action| self.0.action_to_index(action))
+            .collect()
+    }
+
+    fn action_space_size(&self) -> usize {
+        self.0.action_space_size()
+    }
+
+    fn action_name(&self, action: usize) -> String {
+        format!("{:?}", self.0.index_to_action(action))
+    }
+
+    fn to_play(&self) -> usize {
+        self.0.player_index(&self.0.current_player())
+    }
+
+    fn num_players(&self) -> usize {
+        self.0.num_players()
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        self.0.observation_shape()
+    }
+
+    fn observation(&self) -> Vec<f32> {
+        self.0.observation()
+    }
+
+    fn canonical_observation(&self) -> Vec<f32> {
+        self.0.canonical_observation()
+    }
+
+    fn state_hash(&self) -> u64 {
+        self.0.state_hash()
+    }
+
+    fn terminated(&self) -> bool {
+        self.0.terminated()
+    }
+
+    fn truncated(&self) -> bool {
+        self.0.truncated()
+    }
+
+    fn winner(&self) -> Option<usize> {
+        self.0
+            .check_winner()
+            .map(|winner| self.0.player_index(&winner))
+    }
+
+    fn returns(&self) -> Vec<f32> {
+        self.0.returns()
+    }
+
+    fn scores(&self) -> Vec<f32> {
+        self.0.scores()
+    }
+
+    fn winning_moves(&self, opponent: bool) -> Vec<usize> {
+        self.0
+            .winning_moves(opponent)
+            .iter()
+            .map(|action| self.0.action_to_index(action))
+            .collect()
+    }
+
+    fn to_notation(&self) -> Option<String> {
+        self.0.to_notation()
+    }
+
+    fn parse_notation(&self, notation: &str) -> Result<Box<dyn DynGame>, ParseError> {
+        Ok(boxed(G::from_notation(notation)?))
+    }
+
+    fn chance_outcomes(&self) -> Vec<(usize, f32)> {
+        self.0
+            .chance_outcomes()
+            .iter()
+            .map(|(action, probability)| (self.0.action_to_index(action), *probability))
+            .collect()
+    }
+
+    fn clone_box(&self) -> Box<dyn DynGame> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn DynGame> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+impl Game for Box<dyn DynGame> {
+    type Action = usize;
+
+    type Player = usize;
+
+    fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+        self.as_mut().step(action)
+    }
+
+    fn get_available_moves(&self) -> Vec<Self::Action> {
+        self.as_ref().legal_actions()
+    }
+
+    fn action_space_size(&self) -> usize {
+        self.as_ref().action_space_size()
+    }
+
+    fn action_to_index(&self, action: &Self::Action) -> usize {
+        *action
+    }
+
+    fn index_to_action(&self, index: usize) -> Self::Action {
+        index
+    }
+
+    fn current_player(&self) -> Self::Player {
+        self.as_ref().to_play()
+    }
+
+    fn player_index(&self, player: &Self::Player) -> usize {
+        *player
+    }
+
+    fn num_players(&self) -> usize {
+        self.as_ref().num_players()
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        self.as_ref().observation_shape()
+    }
+
+    fn observation(&self) -> Vec<f32> {
+        self.as_ref().observation()
+    }
+
+    fn canonical_observation(&self) -> Vec<f32> {
+        self.as_ref().canonical_observation()
+    }
+
+    fn state_hash(&self) -> u64 {
+        self.as_ref().state_hash()
+    }
+
+    fn terminated(&self) -> bool {
+        self.as_ref().terminated()
+    }
+
+    fn truncated(&self) -> bool {
+        self.as_ref().truncated()
+    }
+
+    fn check_winner(&self) -> Option<Self::Player> {
+        self.as_ref().winner()
+    }
+
+    fn returns(&self) -> Vec<f32> {
+        self.as_ref().returns()
+    }
+
+    fn scores(&self) -> Vec<f32> {
+        self.as_ref().scores()
+    }
+
+    fn winning_moves(&self, opponent: bool) -> Vec<Self::Action> {
+        self.as_ref().winning_moves(opponent)
+    }
+
+    fn to_notation(&self) -> Option<String> {
+        self.as_ref().to_notation()
+    }
+
+    fn chance_outcomes(&self) -> Vec<(Self::Action, f32)> {
+        self.as_ref().chance_outcomes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{games::tic_tac_toe::TicTacToe, mcts::Mcts};
+
+    #[test]
+    fn test_boxed_game() {
+        let mut game = boxed(TicTacToe::new());
+        assert_eq!(game.legal_actions().len(), 9);
+        assert_eq!(game.action_name(5), "(1, 2)");
+        for action in [0, 3, 1, 4] {
+            game.step(action).unwrap();
+        }
+        let clone = game.clone();
+        assert!(game.step(9).is_err());
+
+        // X completes the top row.
+        let mcts = Mcts::<Box<dyn DynGame>>::new(500);
+        assert_eq!(mcts.search(&game).action, 2);
+        game.step(2).unwrap();
+        assert_eq!(game.check_winner(), Some(0));
+        assert_eq!(game.returns(), vec![1., -1.]);
+        assert!(!clone.done());
+    }
+}
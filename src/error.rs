@@ -0,0 +1,332 @@
+//! The errors of the crate, as types that callers can match on to handle an illegal move
+//! differently from a broken environment, or a typo in a config from a missing file.
+//! [`Error`] holds any of them, and [`Context`] adds what was being done when one happened.
+
+use std::{
+    fmt, io,
+    num::{ParseFloatError, ParseIntError},
+    path::PathBuf,
+};
+
+/// A [`std::result::Result`] failing with an [`Error`] unless told otherwise.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Why [`crate::Game::step`] failed.
+#[derive(Debug, thiserror::Error)]
+pub enum GameError {
+    /// `action` can't be played in the current state.
+    #[error("illegal move {action}: {reason}")]
+    IllegalMove { action: String, reason: String },
+    /// The episode is over, so nothing can be played.
+    #[error("the game is over")]
+    GameAlreadyOver,
+    /// An environment outside the crate, like a Python process, failed.
+    #[error(transparent)]
+    Environment(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl GameError {
+    /// An [`GameError::IllegalMove`] of `action`, shown with its `Debug` representation.
+    pub fn illegal_move(action: &impl fmt::Debug, reason: impl Into<String>) -> Self {
+        Self::IllegalMove {
+            action: format!("{:?}", action),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Why [`crate::mcts::Mcts::try_search`] can't choose a move.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum SearchError {
+    /// The game is over.
+    #[error("the game is over")]
+    GameOver,
+    /// A chance event is next rather than a player's move.
+    #[error("a chance event is pending")]
+    ChancePending,
+    /// The player to move has no legal action.
+    #[error("no legal action")]
+    NoLegalMoves,
+    /// The search was configured with no simulations.
+    #[error("the search has no simulations")]
+    NoSimulations,
+    /// [`crate::mcts::MctsConfig::max_nodes`] or `max_memory_bytes` leave no room for a
+    /// child of the root.
+    #[error("the tree limits leave no room to search a move")]
+    TreeTooSmall,
+}
+
+/// Why text, like a move, a position, a protocol command or a file, couldn't be read.
+#[derive(Debug, thiserror::Error)]
+pub enum ParseError {
+    /// The text isn't valid, for the reason given.
+    #[error("{0}")]
+    Invalid(String),
+    /// The text doesn't name anything known, like a game or a device.
+    #[error("unknown {kind} `{name}`")]
+    Unknown { kind: &'static str, name: String },
+    #[error(transparent)]
+    Int(#[from] ParseIntError),
+    #[error(transparent)]
+    Float(#[from] ParseFloatError),
+    #[cfg(feature = "serde")]
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+impl ParseError {
+    /// A [`ParseError::Unknown`] `kind` named `name`.
+    pub fn unknown(kind: &'static str, name: impl Into<String>) -> Self {
+        Self::Unknown {
+            kind,
+            name: name.into(),
+        }
+    }
+}
+
+/// Why settings can't be used, like a search without simulations or a backend on a device
+/// it doesn't run on.
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    /// A setting is out of its range or doesn't fit the others, for the reason given.
+    #[error("{0}")]
+    Invalid(String),
+    /// What was asked for isn't supported here, like half precision on the CPU.
+    #[error("{0}")]
+    Unsupported(String),
+    /// `what` needs a cargo feature the crate was built without.
+    #[error("{what} needs muzero built with the {feature} feature")]
+    MissingFeature { what: String, feature: &'static str },
+}
+
+/// Why reading or writing failed.
+#[derive(Debug, thiserror::Error)]
+pub enum IoError {
+    /// Doing `action`, like `read` or `create`, on the file or directory at `path`.
+    #[error("failed to {action} {}", path.display())]
+    Path {
+        action: &'static str,
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// Any other input or output, like a pipe or a socket.
+    #[error(transparent)]
+    Other(#[from] io::Error),
+}
+
+impl IoError {
+    /// A [`IoError::Path`] of `action` on `path`, for `map_err`.
+    pub fn path(action: &'static str, path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.into();
+        move |source| Self::Path {
+            action,
+            path,
+            source,
+        }
+    }
+}
+
+/// Any error of the crate. `{:#}` shows the causes after it, separated by colons.
+#[derive(Debug)]
+pub enum Error {
+    Game(GameError),
+    Search(SearchError),
+    Parse(ParseError),
+    Config(ConfigError),
+    Io(IoError),
+    /// A library or a process the crate drives failed, like a deep learning backend, a
+    /// Python environment or a peer on the network.
+    Backend(Box<dyn std::error::Error + Send + Sync>),
+    /// `source` happened while doing `context`.
+    Context {
+        context: String,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// A [`Error::Backend`] from the error of a library, or a message.
+    pub fn backend(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Self::Backend(error.into())
+    }
+
+    /// The error without the context around it.
+    pub fn root(&self) -> &Error {
+        match self {
+            Self::Context { source, .. } => source.root(),
+            error => error,
+        }
+    }
+
+    /// The error the variant wraps, whose message and causes are this error's.
+    fn inner(&self) -> &(dyn std::error::Error + 'static) {
+        match self {
+            Self::Game(error) => error,
+            Self::Search(error) => error,
+            Self::Parse(error) => error,
+            Self::Config(error) => error,
+            Self::Io(error) => error,
+            Self::Backend(error) => error.as_ref(),
+            Self::Context { source, .. } => source.as_ref(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Context { context, .. } => write!(f, "{}", context)?,
+            error => write!(f, "{}", error.inner())?,
+        }
+        if f.alternate() {
+            let mut source = std::error::Error::source(self);
+            while let Some(error) = source {
+                write!(f, ": {}", error)?;
+                source = error.source();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Context { source, .. } => Some(source.as_ref()),
+            error => error.inner().source(),
+        }
+    }
+}
+
+impl From<GameError> for Error {
+    fn from(error: GameError) -> Self {
+        Self::Game(error)
+    }
+}
+
+impl From<SearchError> for Error {
+    fn from(error: SearchError) -> Self {
+        Self::Search(error)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Self::Parse(error)
+    }
+}
+
+impl From<ConfigError> for Error {
+    fn from(error: ConfigError) -> Self {
+        Self::Config(error)
+    }
+}
+
+impl From<IoError> for Error {
+    fn from(error: IoError) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error.into())
+    }
+}
+
+impl From<ParseIntError> for Error {
+    fn from(error: ParseIntError) -> Self {
+        Self::Parse(error.into())
+    }
+}
+
+impl From<ParseFloatError> for Error {
+    fn from(error: ParseFloatError) -> Self {
+        Self::Parse(error.into())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Parse(error.into())
+    }
+}
+
+#[cfg(any(feature = "gym", feature = "atari"))]
+impl From<pyo3::PyErr> for Error {
+    fn from(error: pyo3::PyErr) -> Self {
+        Self::Backend(error.into())
+    }
+}
+
+/// Adds what was being done to the error of a result, shown before it.
+pub trait Context<T> {
+    fn context(self, context: impl fmt::Display) -> Result<T>;
+
+    fn with_context<C: fmt::Display>(self, context: impl FnOnce() -> C) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: impl fmt::Display) -> Result<T> {
+        self.with_context(|| context)
+    }
+
+    fn with_context<C: fmt::Display>(self, context: impl FnOnce() -> C) -> Result<T> {
+        self.map_err(|error| Error::Context {
+            context: context().to_string(),
+            source: Box::new(error.into()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn test_display() {
+        let error = GameError::illegal_move(&(1, 2), "the spot is taken");
+        assert_eq!(error.to_string(), "illegal move (1, 2): the spot is taken");
+        assert!(matches!(
+            error,
+            GameError::IllegalMove { ref action, .. } if action == "(1, 2)"
+        ));
+        let error = GameError::Environment("the environment exited".into());
+        assert_eq!(error.to_string(), "the environment exited");
+        assert_eq!(SearchError::GameOver.to_string(), "the game is over");
+        assert_eq!(
+            ParseError::unknown("game", "chekers").to_string(),
+            "unknown game `chekers`"
+        );
+    }
+
+    #[test]
+    fn test_context() {
+        let error = Err::<(), _>(GameError::GameAlreadyOver)
+            .context("move 3")
+            .context("in game.txt")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "in game.txt");
+        assert_eq!(
+            format!("{:#}", error),
+            "in game.txt: move 3: the game is over"
+        );
+        assert_eq!(error.source().unwrap().to_string(), "move 3");
+        assert!(matches!(
+            error.root(),
+            Error::Game(GameError::GameAlreadyOver)
+        ));
+        let error: Error = io::Error::from(io::ErrorKind::NotFound).into();
+        assert!(matches!(error, Error::Io(IoError::Other(_))));
+        let error: Error =
+            IoError::path("read", "config.toml")(io::ErrorKind::NotFound.into()).into();
+        assert_eq!(error.to_string(), "failed to read config.toml");
+        assert_eq!(
+            format!("{:#}", error),
+            "failed to read config.toml: entity not found"
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! A C interface for embedding the search in other programs, declared in `include/muzero.h`.
+//! The library is also built as a `cdylib` for this, with the `ffi` feature.
+//!
+//! An engine plays one game from the registry. Positions are given as the JSON array of the
+//! action indices played from the start, chance outcomes included, e.g. `[4, 0, 8]`. Failing
+//! calls return -1 or null and leave a message for [`muzero_last_error`].
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use crate::{
+    dyn_game::DynGame,
+    error::{ConfigError, ParseError, Result},
+    mcts::{Mcts, MctsConfig},
+    registry::Registry,
+    Game,
+};
+
+pub struct MuzeroEngine {
+    start: Box<dyn DynGame>,
+    game: Box<dyn DynGame>,
+    mcts: Mcts<Box<dyn DynGame>>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// The result of `f`, or `failed` after recording its error or panic for
+/// [`muzero_last_error`], since neither may cross into C.
+fn guard<T>(failed: T, f: impl FnOnce() -> Result<T>) -> T {
+    let error = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => return value,
+        Ok(Err(e)) => format!("{:#}", e),
+        Err(_) => "the engine panicked".to_string(),
+    };
+    LAST_ERROR.with(|last| {
+        *last.borrow_mut() = Some(CString::new(error.replace('\0', " ")).unwrap());
+    });
+    failed
+}
+
+/// The string at `s`, which must be null or a NUL-terminated string.
+unsafe fn string<'a>(s: *const c_char) -> Result<&'a str> {
+    if s.is_null() {
+        return Err(ConfigError::Invalid("null string".to_string()).into());
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|e| ParseError::Invalid(e.to_string()).into())
+}
+
+/// The engine at `engine`, which must be null or made by [`muzero_engine_new`].
+unsafe fn engine<'a>(engine: *mut MuzeroEngine) -> Result<&'a mut MuzeroEngine> {
+    engine
+        .as_mut()
+        .ok_or_else(|| ConfigError::Invalid("null engine".to_string()).into())
+}
+
+/// A new engine playing `game`, a name like `tictactoe` or `gomoku:9`, with `simulations`
+/// simulations per move, or null. Free it with [`muzero_engine_free`].
+///
+/// # Safety
+///
+/// `game` must be null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn muzero_engine_new(
+    game: *const c_char,
+    simulations: usize,
+) -> *mut MuzeroEngine {
+    guard(ptr::null_mut(), || {
+        let start = Registry::default().create(string(game)?)?;
+        let engine = MuzeroEngine {
+            game: start.clone(),
+            start,
+            mcts: Mcts::with_config(MctsConfig {
+                num_simulations: simulations,
+                ..Default::default()
+            }),
+        };
+        Ok(Box::into_raw(Box::new(engine)))
+    })
+}
+
+/// Set the position to the one after the actions of `state`, a JSON array of action indices.
+/// Returns 0, or -1 if the state isn't valid, keeping the position.
+///
+/// # Safety
+///
+/// `engine` must be null or made by [`muzero_engine_new`] and not freed, and `state` null or
+/// a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn muzero_engine_set_position(
+    engine: *mut MuzeroEngine,
+    state: *const c_char,
+) -> i32 {
+    guard(-1, || {
+        let engine = self::engine(engine)?;
+        let actions = serde_json::from_str::<Vec<usize>>(string(state)?)?;
+        let mut game = engine.start.clone();
+        for (i, &action) in actions.iter().enumerate() {
+            let legal = if game.chance_outcomes().is_empty() {
+                game.legal_actions()
+            } else {
+                game.chance_outcomes()
+                    .iter()
+                    .map(|&(action, _)| action)
+                    .collect()
+            };
+            if !legal.contains(&action) {
+                return Err(ParseError::Invalid(format!(
+                    "action {}: {} isn't legal",
+                    i + 1,
+                    action
+                ))
+                .into());
+            }
+            game.step(action)?;
+        }
+        engine.game = game;
+        Ok(0)
+    })
+}
+
+/// The index of the best action for the player to move, or -1 if the game is over or a chance
+/// event is pending.
+///
+/// # Safety
+///
+/// `engine` must be null or made by [`muzero_engine_new`] and not freed.
+#[no_mangle]
+pub unsafe extern "C" fn muzero_engine_best_move(engine: *mut MuzeroEngine) -> i64 {
+    guard(-1, || {
+        let engine = self::engine(engine)?;
+        Ok(engine.mcts.try_search(&engine.game)?.action as i64)
+    })
+}
+
+/// Free an engine made by [`muzero_engine_new`]; null is ignored.
+///
+/// # Safety
+///
+/// `engine` must be null or made by [`muzero_engine_new`] and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn muzero_engine_free(engine: *mut MuzeroEngine) {
+    if !engine.is_null() {
+        drop(Box::from_raw(engine));
+    }
+}
+
+/// The message of the last failed call on this thread, or null. It stays valid until the next
+/// failing call on the thread.
+#[no_mangle]
+pub extern "C" fn muzero_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(muzero_last_error()) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_engine() {
+        unsafe {
+            let engine = muzero_engine_new(c"tictactoe".as_ptr(), 500);
+            assert!(!engine.is_null());
+            assert_eq!(
+                muzero_engine_set_position(engine, c"[0, 3, 1, 4]".as_ptr()),
+                0
+            );
+            // X completes the top row.
+            assert_eq!(muzero_engine_best_move(engine), 2);
+
+            assert_eq!(muzero_engine_set_position(engine, c"[0, 0]".as_ptr()), -1);
+            assert_eq!(last_error(), "action 2: 0 isn't legal");
+            // The position is kept.
+            assert_eq!(muzero_engine_best_move(engine), 2);
+            assert_eq!(muzero_engine_set_position(engine, c"[0, 3".as_ptr()), -1);
+            assert_eq!(
+                muzero_engine_set_position(engine, c"[0, 3, 1, 4, 2]".as_ptr()),
+                0
+            );
+            assert_eq!(muzero_engine_best_move(engine), -1);
+            assert_eq!(last_error(), "the game is over");
+            muzero_engine_free(engine);
+
+            assert!(muzero_engine_new(c"shogi".as_ptr(), 10).is_null());
+            assert!(last_error().starts_with("unknown game"));
+            assert!(muzero_engine_new(ptr::null(), 10).is_null());
+            assert_eq!(muzero_engine_best_move(ptr::null_mut()), -1);
+            assert_eq!(last_error(), "null engine");
+            muzero_engine_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_header() {
+        let header = include_str!("../include/muzero.h");
+        for function in [
+            "muzero_engine_new(",
+            "muzero_engine_set_position(",
+            "muzero_engine_best_move(",
+            "muzero_engine_free(",
+            "muzero_last_error(",
+        ] {
+            assert!(header.contains(function), "{}", function);
+        }
+    }
+}
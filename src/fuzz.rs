@@ -0,0 +1,270 @@
+//! Entry points of the fuzz targets in `fuzz/`, one per parser of untrusted input. Each takes
+//! arbitrary bytes and may return errors, but must never panic, hang or allocate without
+//! bound. The tests below run them on mutations of valid inputs, so that the common cases
+//! are covered without `cargo fuzz`.
+//!
+//! With cargo-fuzz and a nightly toolchain, run a target with e.g. `cargo +nightly fuzz run sgf`.
+//! The module is only built for it, which sets `--cfg fuzzing`, and for the tests.
+
+use std::io::Cursor;
+
+use crate::{
+    checkpoint,
+    config::ConfigFile,
+    games::external::{
+        self, LegalActionsReply, ObservationReply, ResetReply, SaveReply, Spec, StepReply,
+    },
+    gtp::GtpEngine,
+    mcts::MctsConfig,
+    record::GameRecord,
+    registry::Registry,
+    sgf,
+    sweep::SweepConfig,
+    trajectory::{self, TrajectoryReader},
+};
+
+/// A config file of the command line, or a sweep and the config files of its runs.
+pub fn toml(data: &[u8]) {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let _ = ConfigFile::parse(text);
+    if let Ok(sweep) = SweepConfig::parse(text) {
+        for run in sweep.runs() {
+            let _ = ConfigFile::parse(&sweep.config(&run));
+        }
+    }
+}
+
+/// An SGF collection, replayed like `muzero replay` does.
+pub fn sgf(data: &[u8]) {
+    if let Ok(records) = std::str::from_utf8(data).map(sgf::from_sgf) {
+        for record in records.into_iter().flatten() {
+            replay(&record);
+        }
+    }
+}
+
+/// A file of JSON lines game records, replayed like `muzero replay` does.
+pub fn game_records(data: &[u8]) {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    for line in text.lines() {
+        if let Ok(record) = serde_json::from_str::<GameRecord>(line) {
+            replay(&record);
+        }
+    }
+}
+
+fn replay(record: &GameRecord) {
+    // Gym and Atari games start Python.
+    if record.game.starts_with("gym") || record.game.starts_with("atari") {
+        return;
+    }
+    if let Ok(game) = Registry::default().create(&record.game) {
+        let _ = record.positions(game);
+    }
+}
+
+/// A file of self-play data, both whole and as a single record.
+pub fn trajectory(data: &[u8]) {
+    let _ = trajectory::decode(data);
+    if let Ok(reader) = TrajectoryReader::new(Cursor::new(data)) {
+        for history in reader {
+            if history.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// The weights of a checkpoint.
+pub fn safetensors(data: &[u8]) {
+    let _ = checkpoint::parse_safetensors(data);
+}
+
+/// Lines written by an external environment: its spec, then replies.
+pub fn external_protocol(data: &[u8]) {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let mut lines = text.lines();
+    if let Some(line) = lines.next() {
+        let _ = external::parse_reply::<Spec>(line);
+    }
+    for line in lines {
+        let _ = external::parse_reply::<ResetReply>(line);
+        let _ = external::parse_reply::<StepReply>(line);
+        let _ = external::parse_reply::<ObservationReply>(line);
+        let _ = external::parse_reply::<LegalActionsReply>(line);
+        let _ = external::parse_reply::<SaveReply>(line);
+    }
+}
+
+/// Commands from a GTP controller, on a small board so that searches stay fast.
+pub fn gtp(data: &[u8]) {
+    let mut engine = GtpEngine::new(MctsConfig {
+        num_simulations: 2,
+        ..Default::default()
+    });
+    let _ = engine.run(&mut Cursor::new("boardsize 5\n"), &mut std::io::sink());
+    let _ = engine.run(&mut Cursor::new(data), &mut std::io::sink());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        history::GameHistory,
+        record::{MoveRecord, MoveSearch},
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// Run `target` on `iterations` random mutations of every input of `corpus`: flipped,
+    /// inserted and deleted bytes, and splices of two inputs.
+    fn fuzz(target: fn(&[u8]), corpus: &[Vec<u8>], iterations: usize) {
+        let mut rng = StdRng::seed_from_u64(0);
+        for input in corpus {
+            target(input);
+        }
+        for _ in 0..iterations {
+            let mut data = corpus[rng.gen_range(0..corpus.len())].clone();
+            for _ in 0..rng.gen_range(1..=4) {
+                let i = rng.gen_range(0..=data.len());
+                match rng.gen_range(0..4) {
+                    0 if i < data.len() => data[i] ^= 1 << rng.gen_range(0..8),
+                    1 => data.insert(i, rng.gen()),
+                    2 if i < data.len() => {
+                        data.remove(i);
+                    }
+                    _ => {
+                        let other = &corpus[rng.gen_range(0..corpus.len())];
+                        let j = rng.gen_range(0..=other.len());
+                        data.truncate(i);
+                        data.extend_from_slice(&other[j..]);
+                    }
+                }
+            }
+            target(&data);
+        }
+    }
+
+    fn corpus(inputs: &[&str]) -> Vec<Vec<u8>> {
+        inputs
+            .iter()
+            .map(|input| input.as_bytes().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn test_text_formats() {
+        fuzz(
+            toml,
+            &corpus(&[
+                "game = \"go\"\n[mcts]\nsimulations = 10\nrave.equivalence = 2.5\n",
+                include_str!("../configs/sweep.toml"),
+            ]),
+            2000,
+        );
+        fuzz(
+            sgf,
+            &corpus(&["(;GM[1]SZ[9];B[ee];W[]) (;GM[2]SZ[11];B[aa];W[kk])"]),
+            1000,
+        );
+        fuzz(
+            external_protocol,
+            &corpus(&[
+                "{\"num_actions\": 3, \"observation_shape\": [1, 2], \"num_players\": 2}\n\
+                 {\"reward\": 1, \"terminated\": false, \"to_play\": 1}\n{\"error\": \"no\"}",
+            ]),
+            2000,
+        );
+    }
+
+    #[test]
+    fn test_game_records() {
+        let mut record = GameRecord::new("tictactoe");
+        record.push(
+            4,
+            Some(0),
+            Some(MoveSearch {
+                value: 0.5,
+                visits: vec![(4, 10), (0, 2)],
+            }),
+        );
+        record.push(0, Some(1), None);
+        let go = GameRecord {
+            game: "go:5".to_string(),
+            moves: vec![MoveRecord {
+                action: 25,
+                player: None,
+                timestamp: 0,
+                search: None,
+            }],
+        };
+        let records = format!(
+            "{}\n{}\n",
+            serde_json::to_string(&record).unwrap(),
+            serde_json::to_string(&go).unwrap()
+        );
+        fuzz(game_records, &corpus(&[&records]), 2000);
+    }
+
+    #[test]
+    fn test_binary_formats() {
+        let history = GameHistory {
+            observations: vec![vec![0.5; 3]; 2],
+            actions: vec![1],
+            rewards: vec![1.],
+            ..Default::default()
+        };
+        let mut data = trajectory::header().to_vec();
+        data.extend(trajectory::record(&history).unwrap());
+        fuzz(trajectory, &[data], 2000);
+        let tensors = [(
+            "w".to_string(),
+            checkpoint::Tensor::new(vec![2], vec![1., 2.]).unwrap(),
+        )];
+        fuzz(
+            safetensors,
+            &[checkpoint::encode_safetensors(&tensors).unwrap()],
+            2000,
+        );
+    }
+
+    /// Inputs that crashed, hung or exhausted the memory before their parsers were fixed.
+    #[test]
+    fn test_regressions() {
+        toml(format!("a = {}", "[".repeat(100_000)).as_bytes());
+        toml(b"command = \"eval\"\n[range]\nmcts.simulations = [1, 2, 9223372036854775807]");
+        toml(b"command = \"eval\"\n[log_range]\na = [1, 9, 999]\nb = [1, 9, 999]\nc = [1, 9, 999]");
+        sgf("(".repeat(100_000).as_bytes());
+        for game in [
+            "go:100000",
+            "gomoku:4294967296",
+            "hex:65536",
+            "nim:1,99999999999",
+        ] {
+            game_records(format!(r#"{{"game": "{}", "moves": []}}"#, game).as_bytes());
+        }
+        let mut data = trajectory::header().to_vec();
+        data.extend(u32::MAX.to_le_bytes());
+        trajectory(&data);
+        let header =
+            r#"{"w": {"dtype": "F32", "shape": [4294967296, 4294967296], "data_offsets": [0, 0]}}"#;
+        let mut data = (header.len() as u64).to_le_bytes().to_vec();
+        data.extend(header.as_bytes());
+        safetensors(&data);
+        external_protocol(br#"{"num_actions": 3, "observation_shape": [4294967296, 4294967296], "num_players": 2}"#);
+    }
+
+    #[test]
+    fn test_gtp() {
+        fuzz(
+            gtp,
+            &corpus(&["clear_board\nkomi 6.5\nplay b c3\ngenmove w\nundo\nshowboard\nquit\n"]),
+            300,
+        );
+    }
+}
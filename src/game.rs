@@ -1,16 +1,189 @@
+//! The interface between games and the search.
+
 use std::hash::Hash;
 
-pub(crate) trait Game: Clone + std::fmt::Display {
+use crate::{
+    error::{GameError, ParseError},
+    symmetry::Transform,
+    zobrist::fnv1a,
+};
+
+/// A game or environment the search can play: players take turns applying actions to a
+/// state until the episode is over.
+pub trait Game: Clone + std::fmt::Display {
     type Action: std::fmt::Debug + Hash + PartialEq + Eq + Clone;
     type Player: PartialEq + std::fmt::Debug + Clone;
 
-    fn step(&mut self, action: Self::Action) -> anyhow::Result<f32>;
+    /// Apply `action` for the player to move, returning that player's reward. Illegal
+    /// actions are an error, and so is any action once the episode is over.
+    fn step(&mut self, action: Self::Action) -> Result<f32, GameError>;
 
+    /// The legal actions of the player to move, none once the episode is over or while
+    /// chance is to move.
     fn get_available_moves(&self) -> Vec<Self::Action>;
 
+    /// Like [`Game::get_available_moves`], but into `moves` after clearing it, so that the
+    /// inner loops of a search can reuse one buffer. Small games override it to not allocate.
+    fn get_available_moves_into(&self, moves: &mut Vec<Self::Action>) {
+        moves.clear();
+        moves.extend(self.get_available_moves());
+    }
+
+    /// The number of actions in the game's fixed action space, e.g. the length of a
+    /// network's policy output.
+    fn action_space_size(&self) -> usize;
+
+    /// Maps an action to its index in `0..action_space_size()`.
+    fn action_to_index(&self, action: &Self::Action) -> usize;
+
+    /// The inverse of [`Game::action_to_index`].
+    fn index_to_action(&self, index: usize) -> Self::Action;
+
+    /// Which indices of the action space are legal in the current state.
+    fn legal_action_mask(&self) -> Vec<bool> {
+        let mut mask = vec![false; self.action_space_size()];
+        for action in self.get_available_moves() {
+            mask[self.action_to_index(&action)] = true;
+        }
+        mask
+    }
+
+    /// The player to move.
     fn current_player(&self) -> Self::Player;
 
-    fn done(&self) -> bool;
+    /// The shape of [`Game::observation`], e.g. `[planes, rows, cols]` for board games.
+    fn observation_shape(&self) -> Vec<usize>;
 
+    /// A tensor encoding of the current state in row-major order, used as the input
+    /// of evaluators and networks.
+    fn observation(&self) -> Vec<f32>;
+
+    /// A hash of the current state; equal states must have equal hashes.
+    ///
+    /// Defaults to hashing [`Game::observation`] and the player to play. Games that can
+    /// update a hash incrementally (see [`crate::zobrist`]) should override it.
+    fn state_hash(&self) -> u64 {
+        let player = self.player_index(&self.current_player()) as u64;
+        let observation = self.observation();
+        fnv1a(
+            observation
+                .iter()
+                .flat_map(|x| x.to_le_bytes())
+                .chain(player.to_le_bytes()),
+        )
+    }
+
+    /// The observation from the perspective of the player to play, so that one evaluator can
+    /// play every side. Has the same shape as [`Game::observation`], which it defaults to.
+    fn canonical_observation(&self) -> Vec<f32> {
+        self.observation()
+    }
+
+    /// Whether the game reached a natural end (a win, a draw, a crashed pole) and no
+    /// reward can follow.
+    fn terminated(&self) -> bool;
+
+    /// Whether the episode was cut off, e.g. by a step limit, before reaching a natural end.
+    /// Values of truncated states should be bootstrapped rather than taken as 0.
+    fn truncated(&self) -> bool {
+        false
+    }
+
+    /// Whether the episode is over, for either reason.
+    fn done(&self) -> bool {
+        self.terminated() || self.truncated()
+    }
+
+    /// The winner of a finished game, `None` on a draw, while playing or in single-player
+    /// games.
     fn check_winner(&self) -> Option<Self::Player>;
+
+    /// How many players take turns in the game.
+    ///
+    /// Single-player games (puzzles, control tasks) have no winner: they are scored by the sum
+    /// of the rewards returned by [`Game::step`] until the episode ends, and [`Game::returns`]
+    /// isn't used.
+    fn num_players(&self) -> usize {
+        2
+    }
+
+    /// Maps a player to an index in `0..num_players()`.
+    fn player_index(&self, player: &Self::Player) -> usize;
+
+    /// The outcome of a finished game for every player, indexed by [`Game::player_index`].
+    ///
+    /// Defaults to 1 for the winner and -1 for everyone else, or 0 for everyone on a draw.
+    /// Games scoring more than a winner, like Hearts, should override it; values are expected
+    /// to lie in [-1, 1].
+    fn returns(&self) -> Vec<f32> {
+        let winner = self.check_winner().map(|winner| self.player_index(&winner));
+        (0..self.num_players())
+            .map(|player| match winner {
+                Some(winner) if winner == player => 1.,
+                Some(_) => -1.,
+                None => 0.,
+            })
+            .collect()
+    }
+
+    /// The final score of every player, indexed by [`Game::player_index`], in the game's own
+    /// units: above 0 for a win, 0 for a draw, and larger for larger wins. Searches can value
+    /// the margin of victory with it, see [`crate::scoring::ScoreConfig`].
+    ///
+    /// Defaults to [`Game::returns`]; games with a score, like the area after komi in Go,
+    /// override it.
+    fn scores(&self) -> Vec<f32> {
+        self.returns()
+    }
+
+    /// The moves which would win the game at once for the player to move, or with `opponent`,
+    /// for the other player of a two-player game if it were their turn. Heuristic playouts
+    /// use them to take wins and block losses, see [`crate::rollout::Rollout::Tactical`].
+    ///
+    /// Defaults to none; games which can tell cheaply, like the n-in-a-row games, override
+    /// it.
+    fn winning_moves(&self, _opponent: bool) -> Vec<Self::Action> {
+        vec![]
+    }
+
+    /// The position in a compact notation, like the FEN-like one of [`crate::notation`], e.g.
+    /// for bug reports and puzzles; `None` for games without one.
+    fn to_notation(&self) -> Option<String> {
+        None
+    }
+
+    /// The position `notation` describes, as [`Game::to_notation`] writes it.
+    fn from_notation(_notation: &str) -> Result<Self, ParseError> {
+        Err(ParseError::Invalid(
+            "the game has no position notation".to_string(),
+        ))
+    }
+
+    /// The possible outcomes of a chance event (a dice roll, a tile spawn) and their
+    /// probabilities, if the next step is decided by chance rather than by a player.
+    /// Outcomes are applied with [`Game::step`] like any other action.
+    fn chance_outcomes(&self) -> Vec<(Self::Action, f32)> {
+        vec![]
+    }
+}
+
+/// Games that can take back a move, so that a search can walk down and back up a single game
+/// instance instead of cloning the root state for every simulation.
+pub trait Undo: Game {
+    /// What [`Undo::undo`] needs to restore the previous state, e.g. captured pieces.
+    type UndoToken;
+
+    /// Like [`Game::step`], also returning the token to undo the move with.
+    fn step_with_undo(&mut self, action: Self::Action)
+        -> Result<(f32, Self::UndoToken), GameError>;
+
+    /// Take back `action`, which must be the last move played.
+    fn undo(&mut self, action: Self::Action, token: Self::UndoToken);
+}
+
+/// Games whose rules are the same under some transforms of the board, like its rotations and
+/// reflections, which training and search can take advantage of.
+pub trait Symmetry: Game {
+    /// The transforms of the board, the identity first.
+    fn symmetries(&self) -> Vec<Transform>;
 }
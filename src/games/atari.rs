@@ -0,0 +1,622 @@
+//! Atari games through the Arcade Learning Environment, behind the `atari` feature, with the
+//! preprocessing of the MuZero and DQN papers: every action is repeated for several frames,
+//! the screen is max-pooled over the last two of them to undo flickering, downsampled and
+//! stacked with the previous observations, and losing a life can end the episode during
+//! training.
+//!
+//! The emulator is abstracted by [`Emulator`]. [`Ale`] implements it with `ale_py`, the
+//! Python binding of the ALE, in the Python interpreter embedded with pyo3.
+
+use pyo3::{
+    prelude::*,
+    types::{PyBytes, PyString},
+};
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex, Weak},
+};
+
+use crate::{
+    error::{Error, GameError, ParseError, Result},
+    game::Game,
+};
+
+/// What [`Atari`] needs from the emulator, following the ALE interface. Cloning has to
+/// snapshot the emulator state, e.g. with `cloneState`/`restoreState`.
+pub trait Emulator: Clone {
+    /// The actions that do something in the loaded game, as ALE action ids.
+    fn minimal_action_set(&self) -> Vec<u8>;
+
+    /// Emulate one frame with `action` held, returning the reward.
+    fn act(&mut self, action: u8) -> Result<i32, GameError>;
+
+    /// The screen in grayscale, row by row.
+    fn screen_grayscale(&self) -> Vec<u8>;
+
+    /// The height and width of the screen.
+    fn screen_dimensions(&self) -> (usize, usize);
+
+    fn lives(&self) -> u32;
+
+    fn game_over(&self) -> bool;
+
+    fn reset_game(&mut self) -> Result<(), GameError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct AleConfig {
+    /// The ROM, as the name of one that `ale_py` ships, like `breakout`, or the path of a
+    /// `.bin` file.
+    pub rom: String,
+    /// The seed of the emulator.
+    pub seed: u64,
+    /// How likely the emulator is to repeat the previous action instead of the one given,
+    /// the "sticky actions" of the ALE.
+    pub repeat_action_probability: f32,
+}
+
+impl Default for AleConfig {
+    fn default() -> Self {
+        Self {
+            rom: "breakout".to_string(),
+            seed: 0,
+            repeat_action_probability: 0.,
+        }
+    }
+}
+
+/// An `ale_py.ALEInterface`, and the snapshot it is in.
+struct Interface {
+    ale: Py<PyAny>,
+    /// `ale_py.Action`, to make actions from their ids.
+    action: Py<PyAny>,
+    /// The snapshot of the emulator whose state the interface is in.
+    loaded: Weak<Py<PyAny>>,
+}
+
+/// The ALE of `ale_py`. Clones share the interface, and each of them holds a snapshot of its
+/// state, restored before it acts if another clone acted since.
+#[derive(Clone)]
+pub struct Ale {
+    interface: Arc<Mutex<Interface>>,
+    /// An `ale_py.ALEState` of the emulator's state.
+    state: Arc<Py<PyAny>>,
+    actions: Vec<u8>,
+    screen: Vec<u8>,
+    dimensions: (usize, usize),
+    lives: u32,
+    game_over: bool,
+}
+
+impl Ale {
+    /// Load the ROM into a new interface.
+    pub fn new(config: &AleConfig) -> Result<Self> {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = py.import("ale_py")?;
+            let ale = module.getattr("ALEInterface")?.call0()?;
+            ale.call_method1("setInt", ("random_seed", config.seed as i64))?;
+            ale.call_method1(
+                "setFloat",
+                (
+                    "repeat_action_probability",
+                    config.repeat_action_probability,
+                ),
+            )?;
+            let rom = if config.rom.ends_with(".bin") {
+                PyString::new(py, &config.rom).into_any()
+            } else {
+                module
+                    .getattr("roms")?
+                    .call_method1("get_rom_path", (&config.rom,))?
+            };
+            if rom.is_none() {
+                return Err(ParseError::unknown("ROM", &config.rom).into());
+            }
+            ale.call_method1("loadROM", (rom,))?;
+            let actions = ale
+                .call_method0("getMinimalActionSet")?
+                .try_iter()?
+                .map(|action| action?.call_method0("__int__")?.extract())
+                .collect::<PyResult<_>>()?;
+            let state = Arc::new(ale.call_method0("cloneState")?.unbind());
+            let mut emulator = Self {
+                interface: Arc::new(Mutex::new(Interface {
+                    ale: ale.clone().unbind(),
+                    action: module.getattr("Action")?.unbind(),
+                    loaded: Arc::downgrade(&state),
+                })),
+                state,
+                actions,
+                screen: vec![],
+                dimensions: (0, 0),
+                lives: 0,
+                game_over: false,
+            };
+            emulator.read(&ale)?;
+            Ok(emulator)
+        })
+    }
+
+    /// Run `f` on the interface in the state of this emulator, then snapshot the new state
+    /// and read the screen, lives and game over from it.
+    fn update<T>(&mut self, f: impl FnOnce(&Bound<PyAny>, &Interface) -> PyResult<T>) -> Result<T> {
+        let interface = self.interface.clone();
+        let mut interface = interface
+            .lock()
+            .map_err(|_| Error::backend("the emulator failed on another thread"))?;
+        Python::attach(|py| {
+            let ale = interface.ale.bind(py).clone();
+            if !Weak::ptr_eq(&interface.loaded, &Arc::downgrade(&self.state)) {
+                ale.call_method1("restoreState", (self.state.bind(py),))?;
+            }
+            let value = f(&ale, &interface)?;
+            self.state = Arc::new(ale.call_method0("cloneState")?.unbind());
+            interface.loaded = Arc::downgrade(&self.state);
+            self.read(&ale)?;
+            Ok(value)
+        })
+    }
+
+    fn read(&mut self, ale: &Bound<PyAny>) -> Result<()> {
+        let screen = ale
+            .call_method0("getScreenGrayscale")?
+            .call_method0("tobytes")?;
+        self.screen = screen
+            .cast_into::<PyBytes>()
+            .map_err(PyErr::from)?
+            .as_bytes()
+            .to_vec();
+        self.dimensions = ale.call_method0("getScreenDims")?.extract()?;
+        let (height, width) = self.dimensions;
+        if height.checked_mul(width) != Some(self.screen.len()) || self.screen.is_empty() {
+            return Err(ParseError::Invalid(format!(
+                "the screen has {} pixels, not {}x{}",
+                self.screen.len(),
+                height,
+                width
+            ))
+            .into());
+        }
+        self.lives = ale.call_method0("lives")?.extract()?;
+        self.game_over = ale.call_method0("game_over")?.is_truthy()?;
+        Ok(())
+    }
+}
+
+impl Emulator for Ale {
+    fn minimal_action_set(&self) -> Vec<u8> {
+        self.actions.clone()
+    }
+
+    fn act(&mut self, action: u8) -> Result<i32, GameError> {
+        self.update(|ale, interface| {
+            let action = interface.action.bind(ale.py()).call1((action,))?;
+            ale.call_method1("act", (action,))?.extract()
+        })
+        .map_err(|e| GameError::Environment(e.into()))
+    }
+
+    fn screen_grayscale(&self) -> Vec<u8> {
+        self.screen.clone()
+    }
+
+    fn screen_dimensions(&self) -> (usize, usize) {
+        self.dimensions
+    }
+
+    fn lives(&self) -> u32 {
+        self.lives
+    }
+
+    fn game_over(&self) -> bool {
+        self.game_over
+    }
+
+    fn reset_game(&mut self) -> Result<(), GameError> {
+        self.update(|ale, _| ale.call_method0("reset_game").map(drop))
+            .map_err(|e| GameError::Environment(e.into()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AtariConfig {
+    /// How many frames every action is repeated for.
+    pub frame_skip: usize,
+    /// How many past observations are stacked into one.
+    pub frame_stack: usize,
+    /// The height and width the screen is downsampled to.
+    pub observation_size: (usize, usize),
+    /// End the episode when a life is lost, as commonly done during training.
+    pub terminal_on_life_loss: bool,
+    /// Episodes are truncated after this many emulated frames (30 minutes at 60 fps).
+    pub max_frames: usize,
+}
+
+impl Default for AtariConfig {
+    fn default() -> Self {
+        Self {
+            frame_skip: 4,
+            frame_stack: 4,
+            observation_size: (96, 96),
+            terminal_on_life_loss: false,
+            max_frames: 108_000,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Atari<E: Emulator> {
+    emulator: E,
+    config: AtariConfig,
+    actions: Vec<u8>,
+    /// The most recent observations, oldest first.
+    frames: VecDeque<Vec<f32>>,
+    num_frames: usize,
+    lives: u32,
+    life_lost: bool,
+}
+
+impl<E: Emulator> Atari<E> {
+    /// Start a new episode on `emulator`.
+    pub fn new(mut emulator: E, config: AtariConfig) -> Result<Self, GameError> {
+        emulator.reset_game()?;
+        let actions = emulator.minimal_action_set();
+        let lives = emulator.lives();
+        let mut game = Self {
+            emulator,
+            config,
+            actions,
+            frames: VecDeque::new(),
+            num_frames: 0,
+            lives,
+            life_lost: false,
+        };
+        let frame = game.downsample(&game.emulator.screen_grayscale());
+        game.frames = std::iter::repeat_n(frame, game.config.frame_stack).collect();
+        Ok(game)
+    }
+
+    pub fn lives(&self) -> u32 {
+        self.lives
+    }
+
+    /// Average `screen` down to the observation size, scaled to `[0, 1]`.
+    fn downsample(&self, screen: &[u8]) -> Vec<f32> {
+        let (height, width) = self.emulator.screen_dimensions();
+        let (out_height, out_width) = self.config.observation_size;
+        // The input pixels covered by output pixel `i`, at least one.
+        let span = |i: usize, input: usize, output: usize| {
+            let start = i * input / output;
+            start..((i + 1) * input / output).max(start + 1)
+        };
+        let mut frame = vec![0.; out_height * out_width];
+        for (i, pixel) in frame.iter_mut().enumerate() {
+            let rows = span(i / out_width, height, out_height);
+            let cols = span(i % out_width, width, out_width);
+            let mut sum = 0.;
+            for r in rows.clone() {
+                for c in cols.clone() {
+                    sum += screen[r * width + c] as f32;
+                }
+            }
+            *pixel = sum / (rows.len() * cols.len()) as f32 / 255.;
+        }
+        frame
+    }
+}
+
+impl<E: Emulator> Game for Atari<E> {
+    /// An index into the game's minimal action set.
+    type Action = usize;
+
+    type Player = ();
+
+    fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+        if self.done() {
+            return Err(GameError::GameAlreadyOver);
+        }
+        let Some(&ale_action) = self.actions.get(action) else {
+            return Err(GameError::illegal_move(&action, "not in the action set"));
+        };
+        let mut reward = 0;
+        let mut last_screens = VecDeque::with_capacity(2);
+        for _ in 0..self.config.frame_skip {
+            reward += self.emulator.act(ale_action)?;
+            self.num_frames += 1;
+            if last_screens.len() == 2 {
+                last_screens.pop_front();
+            }
+            last_screens.push_back(self.emulator.screen_grayscale());
+            if self.emulator.game_over() {
+                break;
+            }
+        }
+        let screen: Vec<u8> = match (last_screens.front(), last_screens.back()) {
+            (Some(a), Some(b)) => a.iter().zip(b).map(|(&a, &b)| a.max(b)).collect(),
+            _ => self.emulator.screen_grayscale(),
+        };
+        let frame = self.downsample(&screen);
+        self.frames.pop_front();
+        self.frames.push_back(frame);
+
+        let lives = self.emulator.lives();
+        self.life_lost = lives < self.lives;
+        self.lives = lives;
+        Ok(reward as f32)
+    }
+
+    fn get_available_moves(&self) -> Vec<Self::Action> {
+        if self.done() {
+            return vec![];
+        }
+        (0..self.actions.len()).collect()
+    }
+
+    fn action_space_size(&self) -> usize {
+        self.actions.len()
+    }
+
+    fn action_to_index(&self, action: &Self::Action) -> usize {
+        *action
+    }
+
+    fn index_to_action(&self, index: usize) -> Self::Action {
+        index
+    }
+
+    fn current_player(&self) -> Self::Player {}
+
+    fn player_index(&self, _player: &Self::Player) -> usize {
+        0
+    }
+
+    fn num_players(&self) -> usize {
+        1
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        let (height, width) = self.config.observation_size;
+        vec![self.config.frame_stack, height, width]
+    }
+
+    /// The last `frame_stack` downsampled grayscale frames, oldest first.
+    fn observation(&self) -> Vec<f32> {
+        self.frames.iter().flatten().copied().collect()
+    }
+
+    fn terminated(&self) -> bool {
+        self.emulator.game_over() || (self.config.terminal_on_life_loss && self.life_lost)
+    }
+
+    fn truncated(&self) -> bool {
+        !self.terminated() && self.num_frames >= self.config.max_frames
+    }
+
+    fn check_winner(&self) -> Option<Self::Player> {
+        None
+    }
+}
+
+impl<E: Emulator> fmt::Display for Atari<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "frame {} lives {}", self.num_frames, self.lives)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 4x4 screen whose pixels all show the frame number, flickering to black on odd
+    /// frames. Action 1 scores a point, and a life is lost every 8 frames.
+    #[derive(Clone, Default)]
+    struct FakeEmulator {
+        frame: u32,
+    }
+
+    impl Emulator for FakeEmulator {
+        fn minimal_action_set(&self) -> Vec<u8> {
+            vec![0, 1]
+        }
+
+        fn act(&mut self, action: u8) -> Result<i32, GameError> {
+            self.frame += 1;
+            Ok(action as i32)
+        }
+
+        fn screen_grayscale(&self) -> Vec<u8> {
+            let value = if self.frame % 2 == 1 {
+                0
+            } else {
+                self.frame as u8
+            };
+            vec![value; 16]
+        }
+
+        fn screen_dimensions(&self) -> (usize, usize) {
+            (4, 4)
+        }
+
+        fn lives(&self) -> u32 {
+            3 - self.frame / 8
+        }
+
+        fn game_over(&self) -> bool {
+            self.frame >= 24
+        }
+
+        fn reset_game(&mut self) -> Result<(), GameError> {
+            self.frame = 0;
+            Ok(())
+        }
+    }
+
+    fn config() -> AtariConfig {
+        AtariConfig {
+            frame_skip: 4,
+            frame_stack: 2,
+            observation_size: (2, 2),
+            terminal_on_life_loss: false,
+            max_frames: 1000,
+        }
+    }
+
+    #[test]
+    fn test_step() {
+        let mut game = Atari::new(FakeEmulator::default(), config()).unwrap();
+        assert_eq!(game.action_space_size(), 2);
+        assert_eq!(game.observation_shape(), vec![2, 2, 2]);
+        assert_eq!(game.observation(), vec![0.; 8]);
+
+        // The action is repeated, and the flickering frame 3 is hidden by frame 4.
+        assert_eq!(game.step(1).unwrap(), 4.);
+        assert_eq!(game.observation()[..4], [0.; 4]);
+        assert_eq!(game.observation()[4..], [4. / 255.; 4]);
+        assert!(game.step(2).is_err());
+
+        while !game.done() {
+            game.step(0).unwrap();
+        }
+        assert!(game.terminated());
+        assert_eq!(game.lives(), 0);
+    }
+
+    #[test]
+    fn test_life_loss() {
+        let config = AtariConfig {
+            terminal_on_life_loss: true,
+            ..config()
+        };
+        let mut game = Atari::new(FakeEmulator::default(), config).unwrap();
+        game.step(0).unwrap();
+        assert!(!game.done());
+        game.step(0).unwrap();
+        assert!(game.terminated());
+        assert_eq!(game.lives(), 2);
+    }
+
+    #[test]
+    fn test_truncation() {
+        let config = AtariConfig {
+            max_frames: 8,
+            ..config()
+        };
+        let mut game = Atari::new(FakeEmulator::default(), config).unwrap();
+        game.step(0).unwrap();
+        game.step(0).unwrap();
+        assert!(game.truncated());
+        assert!(game.get_available_moves().is_empty());
+    }
+
+    #[test]
+    fn test_downsample() {
+        let game = Atari::new(FakeEmulator::default(), config()).unwrap();
+        let screen: Vec<u8> = (0..16).map(|i| (i % 4 * 10) as u8).collect();
+        // Each output pixel averages a 2x2 block.
+        assert_eq!(
+            game.downsample(&screen),
+            vec![5. / 255., 25. / 255., 5. / 255., 25. / 255.]
+        );
+    }
+
+    /// A stand-in for `ale_py` whose screen shows the frame number, with two actions that
+    /// score 0 and 1 and the lives and game over of [`FakeEmulator`].
+    const FAKE_ALE_PY: &std::ffi::CStr = cr#"
+class Action(int):
+    pass
+
+class ALEState:
+    def __init__(self, frame):
+        self.frame = frame
+
+class Roms:
+    def get_rom_path(self, name):
+        return "/roms/pong.bin" if name == "pong" else None
+
+roms = Roms()
+
+class ALEInterface:
+    def __init__(self):
+        self.frame = 0
+
+    def setInt(self, key, value):
+        pass
+
+    def setFloat(self, key, value):
+        pass
+
+    def loadROM(self, path):
+        assert path == "/roms/pong.bin"
+
+    def getMinimalActionSet(self):
+        return [Action(0), Action(3)]
+
+    def act(self, action):
+        assert isinstance(action, Action)
+        self.frame += 1
+        return action // 3
+
+    def getScreenGrayscale(self):
+        return memoryview(bytes([self.frame] * 16))
+
+    def getScreenDims(self):
+        return (4, 4)
+
+    def lives(self):
+        return 3 - self.frame // 8
+
+    def game_over(self):
+        return self.frame >= 24
+
+    def reset_game(self):
+        self.frame = 0
+
+    def cloneState(self):
+        return ALEState(self.frame)
+
+    def restoreState(self, state):
+        self.frame = state.frame
+"#;
+
+    /// An ALE config of the fake `ale_py`, which replaces any installed one.
+    fn fake_ale_config(rom: &str) -> AleConfig {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = PyModule::from_code(py, FAKE_ALE_PY, c"ale_py.py", c"ale_py").unwrap();
+            py.import("sys")
+                .unwrap()
+                .getattr("modules")
+                .unwrap()
+                .set_item("ale_py", module)
+                .unwrap();
+        });
+        AleConfig {
+            rom: rom.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_ale() {
+        let ale = Ale::new(&fake_ale_config("pong")).unwrap();
+        let mut game = Atari::new(ale, config()).unwrap();
+        assert_eq!(game.action_space_size(), 2);
+        assert_eq!(game.step(1).unwrap(), 4.);
+        let mut clone = game.clone();
+
+        game.step(1).unwrap();
+        assert_eq!(game.lives(), 2);
+        assert_eq!(game.observation()[4..], [8. / 255.; 4]);
+        // The clone goes on from frame 4, and then the game from frame 8.
+        assert_eq!(clone.step(0).unwrap(), 0.);
+        assert_eq!(clone.observation()[..4], [4. / 255.; 4]);
+        assert_eq!(clone.observation()[4..], [8. / 255.; 4]);
+        game.step(0).unwrap();
+        assert_eq!(game.observation()[4..], [12. / 255.; 4]);
+        assert_eq!(game.lives(), 2);
+
+        assert!(Ale::new(&fake_ale_config("tetris")).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+//! CartPole as in Gymnasium's `CartPole-v1`: push a cart left or right to keep the pole on it
+//! upright. Every step is rewarded with 1; the episode terminates when the pole falls past 12
+//! degrees or the cart leaves the track, and is truncated after 500 steps.
+//!
+//! Unlike the board games, the observation is four continuous values.
+
+use rand::Rng;
+use std::fmt;
+
+use crate::{error::GameError, game::Game, random};
+
+const GRAVITY: f64 = 9.8;
+const CART_MASS: f64 = 1.;
+const POLE_MASS: f64 = 0.1;
+const TOTAL_MASS: f64 = CART_MASS + POLE_MASS;
+/// Half the pole's length.
+const POLE_HALF_LENGTH: f64 = 0.5;
+const FORCE: f64 = 10.;
+/// Seconds between steps.
+const TAU: f64 = 0.02;
+
+const ANGLE_LIMIT: f64 = 12. * 2. * std::f64::consts::PI / 360.;
+const POSITION_LIMIT: f64 = 2.4;
+
+pub const DEFAULT_MAX_STEPS: usize = 500;
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Push {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone)]
+pub struct CartPole {
+    position: f64,
+    velocity: f64,
+    /// The pole's angle from upright in radians, positive to the right.
+    angle: f64,
+    angular_velocity: f64,
+    steps: usize,
+    max_steps: usize,
+}
+
+impl CartPole {
+    /// A new episode with every state variable drawn uniformly from `[-0.05, 0.05]`.
+    pub fn new() -> Self {
+        let mut rng = random::rng();
+        let mut sample = || rng.gen_range(-0.05..0.05);
+        Self::from_state([sample(), sample(), sample(), sample()])
+    }
+
+    /// An episode starting from position, velocity, angle and angular velocity.
+    pub fn from_state(state: [f64; 4]) -> Self {
+        let [position, velocity, angle, angular_velocity] = state;
+        Self {
+            position,
+            velocity,
+            angle,
+            angular_velocity,
+            steps: 0,
+            max_steps: DEFAULT_MAX_STEPS,
+        }
+    }
+
+    pub fn with_max_steps(self, max_steps: usize) -> Self {
+        Self { max_steps, ..self }
+    }
+
+    pub fn state(&self) -> [f64; 4] {
+        [
+            self.position,
+            self.velocity,
+            self.angle,
+            self.angular_velocity,
+        ]
+    }
+}
+
+impl Default for CartPole {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Game for CartPole {
+    type Action = Push;
+
+    type Player = ();
+
+    /// Integrates the dynamics over one time step with the Euler method, as Gymnasium does.
+    fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+        if self.done() {
+            return Err(GameError::GameAlreadyOver);
+        }
+        let force = match action {
+            Push::Left => -FORCE,
+            Push::Right => FORCE,
+        };
+        let (sin, cos) = self.angle.sin_cos();
+        let temp = (force + POLE_MASS * POLE_HALF_LENGTH * self.angular_velocity.powi(2) * sin)
+            / TOTAL_MASS;
+        let angular_acceleration = (GRAVITY * sin - cos * temp)
+            / (POLE_HALF_LENGTH * (4. / 3. - POLE_MASS * cos.powi(2) / TOTAL_MASS));
+        let acceleration =
+            temp - POLE_MASS * POLE_HALF_LENGTH * angular_acceleration * cos / TOTAL_MASS;
+
+        self.position += TAU * self.velocity;
+        self.velocity += TAU * acceleration;
+        self.angle += TAU * self.angular_velocity;
+        self.angular_velocity += TAU * angular_acceleration;
+        self.steps += 1;
+        Ok(1.)
+    }
+
+    fn get_available_moves(&self) -> Vec<Self::Action> {
+        if self.done() {
+            return vec![];
+        }
+        vec![Push::Left, Push::Right]
+    }
+
+    fn action_space_size(&self) -> usize {
+        2
+    }
+
+    fn action_to_index(&self, action: &Self::Action) -> usize {
+        *action as usize
+    }
+
+    fn index_to_action(&self, index: usize) -> Self::Action {
+        [Push::Left, Push::Right][index]
+    }
+
+    fn current_player(&self) -> Self::Player {}
+
+    fn player_index(&self, _player: &Self::Player) -> usize {
+        0
+    }
+
+    fn num_players(&self) -> usize {
+        1
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        vec![4]
+    }
+
+    /// Position, velocity, angle and angular velocity.
+    fn observation(&self) -> Vec<f32> {
+        self.state().iter().map(|&x| x as f32).collect()
+    }
+
+    fn terminated(&self) -> bool {
+        self.angle.abs() > ANGLE_LIMIT || self.position.abs() > POSITION_LIMIT
+    }
+
+    fn truncated(&self) -> bool {
+        !self.terminated() && self.steps >= self.max_steps
+    }
+
+    fn check_winner(&self) -> Option<Self::Player> {
+        None
+    }
+}
+
+impl fmt::Display for CartPole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "x: {:+.3} v: {:+.3} angle: {:+.3} angular velocity: {:+.3}",
+            self.position, self.velocity, self.angle, self.angular_velocity
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::{Mcts, MctsConfig};
+
+    #[test]
+    fn test_dynamics() {
+        let mut game = CartPole::from_state([0.; 4]);
+        assert_eq!(game.step(Push::Right).unwrap(), 1.);
+        let [position, velocity, angle, angular_velocity] = game.state();
+        // Positions and angles only change from the next step, after the velocities.
+        assert_eq!((position, angle), (0., 0.));
+        assert!((velocity - 0.195122).abs() < 1e-6, "{}", velocity);
+        assert!(
+            (angular_velocity + 0.292683).abs() < 1e-6,
+            "{}",
+            angular_velocity
+        );
+        assert_eq!(
+            game.observation().len(),
+            game.observation_shape().iter().product::<usize>()
+        );
+    }
+
+    #[test]
+    fn test_episode_end() {
+        // Pushing the same way knocks the pole over.
+        let mut game = CartPole::from_state([0.; 4]);
+        let mut steps = 0;
+        while !game.done() {
+            game.step(Push::Left).unwrap();
+            steps += 1;
+        }
+        assert!(game.terminated());
+        assert!(steps < 20, "{}", steps);
+        assert!(game.step(Push::Left).is_err());
+
+        let mut game = CartPole::from_state([0.; 4]).with_max_steps(4);
+        for push in [Push::Left, Push::Right, Push::Right, Push::Left] {
+            game.step(push).unwrap();
+        }
+        assert!(game.truncated());
+        assert!(game.get_available_moves().is_empty());
+    }
+
+    #[test]
+    fn test_search_balances() {
+        let mcts = Mcts::<CartPole>::with_config(MctsConfig {
+            num_simulations: 50,
+            max_rollout_depth: Some(30),
+            seed: Some(1),
+            ..Default::default()
+        });
+        let mut game = CartPole::from_state([0.01, 0., -0.02, 0.01]).with_max_steps(100);
+        while !game.done() {
+            let action = mcts.search(&game).action;
+            game.step(action).unwrap();
+        }
+        assert!(game.truncated(), "{}", game);
+    }
+}
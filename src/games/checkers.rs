@@ -0,0 +1,552 @@
+//! English draughts on an 8x8 board. Men move diagonally forward and kings in every
+//! direction, one square at a time; capturing is mandatory and a capture sequence has to be
+//! jumped to the end, so a whole multi-jump is a single action. A man reaching the far row is
+//! crowned, which ends its move. A player who can't move loses, and the game is drawn after
+//! 40 moves by each player without a capture or a man moving.
+
+use std::fmt;
+
+use crate::{error::GameError, game::Game};
+
+const SIZE: usize = 8;
+
+/// Only the dark squares are played on.
+const NUM_SQUARES: usize = SIZE * SIZE / 2;
+
+/// Plies without a capture or a man moving after which the game is drawn.
+const DRAW_PLIES: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Player {
+    /// Starts on rows 5 to 7, moves first and moves towards row 0.
+    Black,
+    /// Starts on rows 0 to 2.
+    White,
+}
+
+impl Player {
+    fn opponent(self) -> Self {
+        match self {
+            Player::Black => Player::White,
+            Player::White => Player::Black,
+        }
+    }
+
+    /// The row direction the men of the player move in.
+    fn forward(self) -> isize {
+        match self {
+            Player::Black => -1,
+            Player::White => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Piece {
+    pub player: Player,
+    pub king: bool,
+}
+
+/// The squares a piece passes through, from its start to where it ends: two squares for a
+/// simple move, one more for every further jump of a capture sequence.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Move(pub Vec<(usize, usize)>);
+
+impl Move {
+    fn from(&self) -> (usize, usize) {
+        self.0[0]
+    }
+
+    fn to(&self) -> (usize, usize) {
+        self.0[self.0.len() - 1]
+    }
+
+    fn is_capture(&self) -> bool {
+        let ((r0, _), (r1, _)) = (self.0[0], self.0[1]);
+        r0.abs_diff(r1) == 2
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Checkers {
+    board: [[Option<Piece>; SIZE]; SIZE],
+    current_player: Player,
+    /// Plies since the last capture or man move, for the draw rule.
+    quiet_plies: usize,
+}
+
+/// The index of a dark square in `0..NUM_SQUARES`.
+fn square_index((row, col): (usize, usize)) -> usize {
+    row * SIZE / 2 + col / 2
+}
+
+fn square_at(index: usize) -> (usize, usize) {
+    let row = index / (SIZE / 2);
+    let col = index % (SIZE / 2) * 2 + (row + 1) % 2;
+    (row, col)
+}
+
+fn offset((row, col): (usize, usize), dr: isize, dc: isize) -> Option<(usize, usize)> {
+    let (row, col) = (row as isize + dr, col as isize + dc);
+    if (0..SIZE as isize).contains(&row) && (0..SIZE as isize).contains(&col) {
+        Some((row as usize, col as usize))
+    } else {
+        None
+    }
+}
+
+impl Checkers {
+    pub fn new() -> Self {
+        let mut board = [[None; SIZE]; SIZE];
+        for index in 0..NUM_SQUARES {
+            let (row, col) = square_at(index);
+            let player = match row {
+                0..=2 => Player::White,
+                5..=7 => Player::Black,
+                _ => continue,
+            };
+            board[row][col] = Some(Piece {
+                player,
+                king: false,
+            });
+        }
+        Self {
+            board,
+            current_player: Player::Black,
+            quiet_plies: 0,
+        }
+    }
+
+    pub fn piece(&self, row: usize, col: usize) -> Option<Piece> {
+        self.board[row][col]
+    }
+
+    fn directions(piece: Piece) -> Vec<(isize, isize)> {
+        let forward = piece.player.forward();
+        if piece.king {
+            vec![(-1, -1), (-1, 1), (1, -1), (1, 1)]
+        } else {
+            vec![(forward, -1), (forward, 1)]
+        }
+    }
+
+    fn is_king_row(player: Player, row: usize) -> bool {
+        match player {
+            Player::Black => row == 0,
+            Player::White => row == SIZE - 1,
+        }
+    }
+
+    /// Extend the capture sequence `path` of `piece` as far as it goes, pushing every
+    /// complete sequence into `moves`. The piece has been lifted off the board, and captured
+    /// pieces stay on it until the move is over, so that they can't be jumped twice.
+    fn extend_captures(&self, piece: Piece, path: &mut Vec<(usize, usize)>, moves: &mut Vec<Move>) {
+        let at = path[path.len() - 1];
+        let mut extended = false;
+        for (dr, dc) in Self::directions(piece) {
+            let (Some(over), Some(to)) = (offset(at, dr, dc), offset(at, 2 * dr, 2 * dc)) else {
+                continue;
+            };
+            let captures_opponent = matches!(
+                self.board[over.0][over.1],
+                Some(Piece { player, .. }) if player != piece.player
+            );
+            let already_captured = path.windows(2).any(|jump| {
+                let ((r0, c0), (r1, c1)) = (jump[0], jump[1]);
+                ((r0 + r1) / 2, (c0 + c1) / 2) == over
+            });
+            let lands_empty = self.board[to.0][to.1].is_none() || to == path[0];
+            if captures_opponent && !already_captured && lands_empty {
+                extended = true;
+                path.push(to);
+                if !piece.king && Self::is_king_row(piece.player, to.0) {
+                    // Being crowned ends the move.
+                    moves.push(Move(path.clone()));
+                } else {
+                    self.extend_captures(piece, path, moves);
+                }
+                path.pop();
+            }
+        }
+        if !extended && path.len() > 1 {
+            moves.push(Move(path.clone()));
+        }
+    }
+
+    /// Every legal move, including several paths between the same two squares.
+    fn legal_moves(&self) -> Vec<Move> {
+        let mut lifted = self.clone();
+        let mut captures = Vec::new();
+        let mut simple_moves = Vec::new();
+        for index in 0..NUM_SQUARES {
+            let from = square_at(index);
+            let Some(piece) = self.board[from.0][from.1] else {
+                continue;
+            };
+            if piece.player != self.current_player {
+                continue;
+            }
+            lifted.board[from.0][from.1] = None;
+            lifted.extend_captures(piece, &mut vec![from], &mut captures);
+            lifted.board[from.0][from.1] = Some(piece);
+            for (dr, dc) in Self::directions(piece) {
+                if let Some(to) = offset(from, dr, dc) {
+                    if self.board[to.0][to.1].is_none() {
+                        simple_moves.push(Move(vec![from, to]));
+                    }
+                }
+            }
+        }
+        if captures.is_empty() {
+            simple_moves
+        } else {
+            captures
+        }
+    }
+}
+
+impl Default for Checkers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Game for Checkers {
+    type Action = Move;
+
+    type Player = Player;
+
+    fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+        if self.terminated() {
+            return Err(GameError::GameAlreadyOver);
+        }
+        // Paths between the same squares can differ in the pieces they capture, so compare
+        // whole paths.
+        if !self.legal_moves().contains(&action) {
+            return Err(GameError::illegal_move(&action.0, "not a legal move"));
+        }
+        let (from, to) = (action.from(), action.to());
+        let mut piece = self.board[from.0][from.1].take().unwrap();
+        if action.is_capture() {
+            for jump in action.0.windows(2) {
+                let ((r0, c0), (r1, c1)) = (jump[0], jump[1]);
+                self.board[(r0 + r1) / 2][(c0 + c1) / 2] = None;
+            }
+        }
+        if action.is_capture() || !piece.king {
+            self.quiet_plies = 0;
+        } else {
+            self.quiet_plies += 1;
+        }
+        if Self::is_king_row(piece.player, to.0) {
+            piece.king = true;
+        }
+        self.board[to.0][to.1] = Some(piece);
+        self.current_player = self.current_player.opponent();
+
+        let reward = if self.check_winner() == Some(piece.player) {
+            1.
+        } else {
+            0.
+        };
+        Ok(reward)
+    }
+
+    /// When several capture sequences share their start and end squares, only the first is
+    /// available, so that every move has its own index in the action space.
+    fn get_available_moves(&self) -> Vec<Self::Action> {
+        if self.quiet_plies >= DRAW_PLIES {
+            return vec![];
+        }
+        let mut moves = self.legal_moves();
+        let mut seen = [false; NUM_SQUARES * NUM_SQUARES];
+        moves.retain(|action| !std::mem::replace(&mut seen[self.action_to_index(action)], true));
+        moves
+    }
+
+    /// A move is indexed by its start and end squares.
+    fn action_space_size(&self) -> usize {
+        NUM_SQUARES * NUM_SQUARES
+    }
+
+    fn action_to_index(&self, action: &Self::Action) -> usize {
+        square_index(action.from()) * NUM_SQUARES + square_index(action.to())
+    }
+
+    /// The available move with the start and end squares of `index`, or a simple move between
+    /// them if there's none.
+    fn index_to_action(&self, index: usize) -> Self::Action {
+        self.get_available_moves()
+            .into_iter()
+            .find(|action| self.action_to_index(action) == index)
+            .unwrap_or_else(|| {
+                Move(vec![
+                    square_at(index / NUM_SQUARES),
+                    square_at(index % NUM_SQUARES),
+                ])
+            })
+    }
+
+    fn current_player(&self) -> Self::Player {
+        self.current_player
+    }
+
+    fn player_index(&self, player: &Self::Player) -> usize {
+        *player as usize
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        vec![5, SIZE, SIZE]
+    }
+
+    /// Five planes: Black's men, Black's kings, White's men, White's kings, and a plane of
+    /// ones if Black is to play.
+    fn observation(&self) -> Vec<f32> {
+        let area = SIZE * SIZE;
+        let mut observation = vec![0.; 5 * area];
+        for (row, pieces) in self.board.iter().enumerate() {
+            for (col, piece) in pieces.iter().enumerate() {
+                if let Some(piece) = piece {
+                    let plane = 2 * piece.player as usize + piece.king as usize;
+                    observation[plane * area + row * SIZE + col] = 1.;
+                }
+            }
+        }
+        if self.current_player == Player::Black {
+            observation[4 * area..].fill(1.);
+        }
+        observation
+    }
+
+    /// The board as seen by the player to play, moving towards row 0: when White is to play,
+    /// the board is rotated by 180 degrees and the colors swapped.
+    fn canonical_observation(&self) -> Vec<f32> {
+        let observation = self.observation();
+        if self.current_player == Player::Black {
+            return observation;
+        }
+        let area = SIZE * SIZE;
+        let mut canonical = vec![0.; 5 * area];
+        for (plane, swapped) in [(0, 2), (1, 3), (2, 0), (3, 1)] {
+            for square in 0..area {
+                canonical[swapped * area + area - 1 - square] = observation[plane * area + square];
+            }
+        }
+        canonical
+    }
+
+    fn terminated(&self) -> bool {
+        self.quiet_plies >= DRAW_PLIES || self.legal_moves().is_empty()
+    }
+
+    /// The player to play loses when they can't move.
+    fn check_winner(&self) -> Option<Self::Player> {
+        if self.quiet_plies < DRAW_PLIES && self.legal_moves().is_empty() {
+            Some(self.current_player.opponent())
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Checkers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in &self.board {
+            for piece in row {
+                let symbol = match piece {
+                    None => ".",
+                    Some(Piece {
+                        player: Player::Black,
+                        king: false,
+                    }) => "b",
+                    Some(Piece {
+                        player: Player::Black,
+                        king: true,
+                    }) => "B",
+                    Some(Piece {
+                        player: Player::White,
+                        king: false,
+                    }) => "w",
+                    Some(Piece {
+                        player: Player::White,
+                        king: true,
+                    }) => "W",
+                };
+                write!(f, "{} ", symbol)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::seq::SliceRandom;
+
+    /// A position from rows in the notation of [`fmt::Display`], without the spaces.
+    fn from_rows(rows: [&str; SIZE], current_player: Player) -> Checkers {
+        let mut game = Checkers::new();
+        for (row, line) in rows.iter().enumerate() {
+            for (col, c) in line.chars().enumerate() {
+                game.board[row][col] = match c {
+                    'b' | 'B' => Some(Piece {
+                        player: Player::Black,
+                        king: c == 'B',
+                    }),
+                    'w' | 'W' => Some(Piece {
+                        player: Player::White,
+                        king: c == 'W',
+                    }),
+                    _ => None,
+                };
+            }
+        }
+        game.current_player = current_player;
+        game
+    }
+
+    #[test]
+    fn test_squares() {
+        for index in 0..NUM_SQUARES {
+            let (row, col) = square_at(index);
+            assert_eq!((row + col) % 2, 1);
+            assert_eq!(square_index((row, col)), index);
+        }
+    }
+
+    #[test]
+    fn test_opening_moves() {
+        let game = Checkers::new();
+        assert_eq!(game.get_available_moves().len(), 7);
+        for action in game.get_available_moves() {
+            assert!(!action.is_capture());
+            let index = game.action_to_index(&action);
+            assert!(game.legal_action_mask()[index]);
+            assert_eq!(game.index_to_action(index), action);
+        }
+        let mut game = game.clone();
+        assert!(game.step(Move(vec![(5, 0), (3, 2)])).is_err());
+        game.step(Move(vec![(5, 0), (4, 1)])).unwrap();
+        assert_eq!(game.current_player(), Player::White);
+    }
+
+    #[test]
+    fn test_forced_multi_jump() {
+        let mut game = from_rows(
+            [
+                "........", "........", "...w....", "........", ".w......", "b.......", ".....b..",
+                "......w.",
+            ],
+            Player::Black,
+        );
+        // The double jump is the only legal move: the capture is forced and can't stop halfway.
+        let double_jump = Move(vec![(5, 0), (3, 2), (1, 4)]);
+        assert_eq!(game.get_available_moves(), vec![double_jump.clone()]);
+        assert!(game.step(Move(vec![(5, 0), (3, 2)])).is_err());
+        assert!(game.step(Move(vec![(6, 5), (5, 4)])).is_err());
+        game.step(double_jump).unwrap();
+        assert_eq!(game.piece(4, 1), None);
+        assert_eq!(game.piece(2, 3), None);
+        assert_eq!(game.quiet_plies, 0);
+    }
+
+    #[test]
+    fn test_kinging_ends_the_move() {
+        let mut game = from_rows(
+            [
+                "........", "..w.w...", ".b......", "........", "........", "........", "........",
+                "w.......",
+            ],
+            Player::Black,
+        );
+        // Jumping into the king row crowns the man, which can't jump on backwards.
+        assert_eq!(game.get_available_moves(), vec![Move(vec![(2, 1), (0, 3)])]);
+        game.step(Move(vec![(2, 1), (0, 3)])).unwrap();
+        assert_eq!(
+            game.piece(0, 3),
+            Some(Piece {
+                player: Player::Black,
+                king: true
+            })
+        );
+        assert_eq!(
+            game.piece(1, 4).map(|piece| piece.player),
+            Some(Player::White)
+        );
+    }
+
+    #[test]
+    fn test_no_moves_loses() {
+        let mut game = from_rows(
+            [
+                "........", "........", "........", "........", "........", "..w.....", ".b......",
+                "........",
+            ],
+            Player::Black,
+        );
+        assert_eq!(game.step(Move(vec![(6, 1), (4, 3)])).unwrap(), 1.);
+        assert!(game.done());
+        assert_eq!(game.check_winner(), Some(Player::Black));
+        assert_eq!(game.returns(), vec![1., -1.]);
+    }
+
+    #[test]
+    fn test_draw_rule() {
+        let mut game = from_rows(
+            [
+                ".B......", "........", "........", "........", "........", "........", "........",
+                "......W.",
+            ],
+            Player::Black,
+        );
+        let shuffle = [(0, 1), (1, 2)];
+        let shuffle_white = [(7, 6), (6, 5)];
+        for ply in 0..DRAW_PLIES {
+            assert!(!game.done());
+            let i = ply / 2 % 2;
+            let (from, to) = if ply % 2 == 0 {
+                (shuffle[i], shuffle[1 - i])
+            } else {
+                (shuffle_white[i], shuffle_white[1 - i])
+            };
+            game.step(Move(vec![from, to])).unwrap();
+        }
+        assert!(game.done());
+        assert_eq!(game.check_winner(), None);
+        assert!(game.get_available_moves().is_empty());
+    }
+
+    #[test]
+    fn test_canonical_observation() {
+        let mut game = Checkers::new();
+        let black_view = game.canonical_observation();
+        game.current_player = Player::White;
+        // The opening position is symmetric.
+        let mut white_view = game.canonical_observation();
+        white_view[4 * 64..].fill(1.);
+        assert_eq!(white_view, black_view);
+        assert_eq!(
+            black_view.len(),
+            game.observation_shape().iter().product::<usize>()
+        );
+    }
+
+    #[test]
+    fn test_random_games() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let mut game = Checkers::new();
+            while !game.done() {
+                let action = game.get_available_moves().choose(&mut rng).unwrap().clone();
+                let pieces_before = game.board.iter().flatten().flatten().count();
+                let captures = action.0.len() - 1;
+                game.step(action.clone()).unwrap();
+                let pieces_after = game.board.iter().flatten().flatten().count();
+                let expected = if action.is_capture() { captures } else { 0 };
+                assert_eq!(pieces_before - pieces_after, expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,635 @@
+//! Chess with the encodings of AlphaZero: a fixed action space of 4672 moves and a
+//! plane-based observation, both from the point of view of the side to move.
+//!
+//! A move is encoded by its from-square and one of 73 move types: 56 queen-like moves (8
+//! directions, 1 to 7 squares), 8 knight moves and 9 underpromotions (3 directions, to a
+//! knight, bishop or rook). Promotions to a queen use the queen-like moves.
+//!
+//! The rules come from `shakmaty`, behind the `chess` feature: [`Chess`] translates its
+//! positions into a [`Board`] and its moves through [`encode_move`] and [`decode_move`]. The
+//! game is drawn by stalemate, insufficient material, the fifty-move rule and threefold
+//! repetition.
+
+#[cfg(feature = "chess")]
+use shakmaty::{
+    fen::Fen, san::SanPlus, uci::UciMove, zobrist::Zobrist64, CastlingMode, CastlingSide,
+    EnPassantMode, Position,
+};
+#[cfg(feature = "chess")]
+use std::fmt;
+
+#[cfg(feature = "chess")]
+use crate::{
+    error::{GameError, ParseError},
+    game::Game,
+};
+
+/// 64 from-squares, 73 move types each.
+pub const NUM_ACTIONS: usize = 64 * NUM_MOVE_TYPES;
+
+const NUM_MOVE_TYPES: usize = 73;
+
+/// North, north-east, east, south-east, south, south-west, west, north-west.
+const QUEEN_DIRECTIONS: [(i8, i8); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+
+/// As `(file, rank)` offsets.
+const KNIGHT_MOVES: [(i8, i8); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+
+const UNDERPROMOTIONS: [Role; 3] = [Role::Knight, Role::Bishop, Role::Rook];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+    White,
+    Black,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    King,
+}
+
+/// A square index `rank * 8 + file`, with a1 = 0 and h8 = 63.
+pub type Square = u8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChessMove {
+    pub from: Square,
+    pub to: Square,
+    pub promotion: Option<Role>,
+}
+
+/// Mirror the ranks, so that Black's moves look like White's.
+fn orient(square: Square, turn: Color) -> Square {
+    match turn {
+        Color::White => square,
+        Color::Black => square ^ 56,
+    }
+}
+
+fn file_rank(square: Square) -> (i8, i8) {
+    ((square % 8) as i8, (square / 8) as i8)
+}
+
+/// The index of `mv`, played by `turn`, in `0..NUM_ACTIONS`, or `None` if no piece could
+/// make the move.
+pub fn encode_move(mv: ChessMove, turn: Color) -> Option<usize> {
+    let (from, to) = (orient(mv.from, turn), orient(mv.to, turn));
+    let ((from_file, from_rank), (to_file, to_rank)) = (file_rank(from), file_rank(to));
+    let (df, dr) = (to_file - from_file, to_rank - from_rank);
+
+    let move_type = if let Some(piece) = mv
+        .promotion
+        .and_then(|role| UNDERPROMOTIONS.iter().position(|&r| r == role))
+    {
+        if dr != 1 || df.abs() > 1 {
+            return None;
+        }
+        64 + (df + 1) as usize * 3 + piece
+    } else if let Some(knight) = KNIGHT_MOVES.iter().position(|&d| d == (df, dr)) {
+        56 + knight
+    } else {
+        let distance = df.abs().max(dr.abs());
+        if distance == 0 || distance > 7 || (df != 0 && dr != 0 && df.abs() != dr.abs()) {
+            return None;
+        }
+        let direction = QUEEN_DIRECTIONS
+            .iter()
+            .position(|&d| d == (df.signum(), dr.signum()))?;
+        direction * 7 + distance as usize - 1
+    };
+    Some(from as usize * NUM_MOVE_TYPES + move_type)
+}
+
+/// The inverse of [`encode_move`]. Queen-like moves decode without a promotion; the adapter
+/// promotes pawns reaching the last rank to a queen.
+pub fn decode_move(index: usize, turn: Color) -> Option<ChessMove> {
+    if index >= NUM_ACTIONS {
+        return None;
+    }
+    let (from, move_type) = ((index / NUM_MOVE_TYPES) as Square, index % NUM_MOVE_TYPES);
+    let (file, rank) = file_rank(from);
+    let (df, dr, promotion) = match move_type {
+        0..=55 => {
+            let (df, dr) = QUEEN_DIRECTIONS[move_type / 7];
+            let distance = (move_type % 7 + 1) as i8;
+            (df * distance, dr * distance, None)
+        }
+        56..=63 => {
+            let (df, dr) = KNIGHT_MOVES[move_type - 56];
+            (df, dr, None)
+        }
+        _ => {
+            let underpromotion = move_type - 64;
+            let df = (underpromotion / 3) as i8 - 1;
+            (df, 1, Some(UNDERPROMOTIONS[underpromotion % 3]))
+        }
+    };
+    let (to_file, to_rank) = (file + df, rank + dr);
+    if !(0..8).contains(&to_file) || !(0..8).contains(&to_rank) {
+        return None;
+    }
+    Some(ChessMove {
+        from: orient(from, turn),
+        to: orient((to_rank * 8 + to_file) as Square, turn),
+        promotion,
+    })
+}
+
+/// What the observation is built from, as read from the adapted position.
+#[derive(Debug, Clone)]
+pub struct Board {
+    pub pieces: [Option<(Color, Role)>; 64],
+    pub turn: Color,
+    /// White king side, White queen side, Black king side, Black queen side.
+    pub castling: [bool; 4],
+    /// Plies since the last capture or pawn move, for the fifty-move rule.
+    pub halfmove_clock: u32,
+}
+
+impl Board {
+    pub const OBSERVATION_SHAPE: [usize; 3] = [17, 8, 8];
+
+    /// Seventeen 8x8 planes, from the point of view of the side to move with its pieces
+    /// moving up the board: the six piece types of the side to move and then of the
+    /// opponent, its own king and queen side castling rights and the opponent's, and the
+    /// halfmove clock scaled to `[0, 1]`.
+    pub fn observation(&self) -> Vec<f32> {
+        let mut observation = vec![0.; 17 * 64];
+        for (square, piece) in self.pieces.iter().enumerate() {
+            if let Some((color, role)) = *piece {
+                let side = if color == self.turn { 0 } else { 6 };
+                let plane = side + role as usize;
+                let square = orient(square as Square, self.turn) as usize;
+                observation[plane * 64 + square] = 1.;
+            }
+        }
+        let castling = match self.turn {
+            Color::White => self.castling,
+            Color::Black => [
+                self.castling[2],
+                self.castling[3],
+                self.castling[0],
+                self.castling[1],
+            ],
+        };
+        for (i, &allowed) in castling.iter().enumerate() {
+            if allowed {
+                observation[(12 + i) * 64..][..64].fill(1.);
+            }
+        }
+        observation[16 * 64..].fill((self.halfmove_clock as f32 / 100.).min(1.));
+        observation
+    }
+}
+
+/// Plies without a capture or a pawn move after which the game is drawn.
+#[cfg(feature = "chess")]
+const DRAW_PLIES: u32 = 100;
+
+#[cfg(feature = "chess")]
+fn color(color: shakmaty::Color) -> Color {
+    match color {
+        shakmaty::Color::White => Color::White,
+        shakmaty::Color::Black => Color::Black,
+    }
+}
+
+#[cfg(feature = "chess")]
+fn role(role: shakmaty::Role) -> Role {
+    match role {
+        shakmaty::Role::Pawn => Role::Pawn,
+        shakmaty::Role::Knight => Role::Knight,
+        shakmaty::Role::Bishop => Role::Bishop,
+        shakmaty::Role::Rook => Role::Rook,
+        shakmaty::Role::Queen => Role::Queen,
+        shakmaty::Role::King => Role::King,
+    }
+}
+
+#[derive(Clone)]
+#[cfg(feature = "chess")]
+pub struct Chess {
+    position: shakmaty::Chess,
+    /// The hashes of the positions since the last capture or pawn move, the current one
+    /// last, for the repetition rule.
+    hashes: Vec<u64>,
+}
+
+#[cfg(feature = "chess")]
+impl Chess {
+    pub fn new() -> Self {
+        Self::from_position(shakmaty::Chess::default())
+    }
+
+    fn from_position(position: shakmaty::Chess) -> Self {
+        let hash = position.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0;
+        Self {
+            position,
+            hashes: vec![hash],
+        }
+    }
+
+    /// The position as shakmaty has it.
+    pub fn position(&self) -> &shakmaty::Chess {
+        &self.position
+    }
+
+    /// The legal moves with castling written as the king moving two squares, like UCI does.
+    fn moves(&self) -> Vec<(ChessMove, shakmaty::Move)> {
+        self.position
+            .legal_moves()
+            .into_iter()
+            .filter_map(|m| match m.to_uci(CastlingMode::Standard) {
+                UciMove::Normal {
+                    from,
+                    to,
+                    promotion,
+                } => Some((
+                    ChessMove {
+                        from: from as Square,
+                        to: to as Square,
+                        promotion: promotion.map(role),
+                    },
+                    m,
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// `action` as a legal shakmaty move, if it's one.
+    fn legal_move(&self, action: ChessMove) -> Option<shakmaty::Move> {
+        if action.from >= 64 || action.to >= 64 {
+            return None;
+        }
+        let uci = UciMove::Normal {
+            from: shakmaty::Square::new(action.from as u32),
+            to: shakmaty::Square::new(action.to as u32),
+            promotion: action.promotion.map(|role| match role {
+                Role::Pawn => shakmaty::Role::Pawn,
+                Role::Knight => shakmaty::Role::Knight,
+                Role::Bishop => shakmaty::Role::Bishop,
+                Role::Rook => shakmaty::Role::Rook,
+                Role::Queen => shakmaty::Role::Queen,
+                Role::King => shakmaty::Role::King,
+            }),
+        };
+        let m = uci.to_move(&self.position).ok()?;
+        // shakmaty also takes the king moving onto its rook as castling.
+        (m.to_uci(CastlingMode::Standard) == uci).then_some(m)
+    }
+
+    /// `action` in Standard Algebraic Notation with a check or mate suffix, e.g. `Qh4#`, if
+    /// it's legal.
+    pub fn san(&self, action: ChessMove) -> Option<String> {
+        let m = self.legal_move(action)?;
+        Some(SanPlus::from_move(self.position.clone(), m).to_string())
+    }
+
+    fn repetitions(&self) -> usize {
+        let hash = self.hashes[self.hashes.len() - 1];
+        self.hashes.iter().filter(|&&h| h == hash).count()
+    }
+
+    pub fn board(&self) -> Board {
+        let board = self.position.board();
+        let mut pieces = [None; 64];
+        for (square, piece) in board {
+            pieces[square as usize] = Some((color(piece.color), role(piece.role)));
+        }
+        let castles = self.position.castles();
+        let mut castling = [false; 4];
+        for (i, (side_color, side)) in [
+            (shakmaty::Color::White, CastlingSide::KingSide),
+            (shakmaty::Color::White, CastlingSide::QueenSide),
+            (shakmaty::Color::Black, CastlingSide::KingSide),
+            (shakmaty::Color::Black, CastlingSide::QueenSide),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            castling[i] = castles.has(side_color, side);
+        }
+        Board {
+            pieces,
+            turn: color(self.position.turn()),
+            castling,
+            halfmove_clock: self.position.halfmoves(),
+        }
+    }
+}
+
+#[cfg(feature = "chess")]
+impl Default for Chess {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "chess")]
+impl Game for Chess {
+    type Action = ChessMove;
+
+    type Player = Color;
+
+    fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+        if self.done() {
+            return Err(GameError::GameAlreadyOver);
+        }
+        let Some(m) = self.legal_move(action) else {
+            return Err(GameError::illegal_move(&action, "not a legal move"));
+        };
+        if m.is_zeroing() {
+            self.hashes.clear();
+        }
+        self.position.play_unchecked(m);
+        self.hashes.push(
+            self.position
+                .zobrist_hash::<Zobrist64>(EnPassantMode::Legal)
+                .0,
+        );
+        Ok(if self.position.is_checkmate() { 1. } else { 0. })
+    }
+
+    fn get_available_moves(&self) -> Vec<Self::Action> {
+        if self.done() {
+            return vec![];
+        }
+        self.moves().into_iter().map(|(mv, _)| mv).collect()
+    }
+
+    fn action_space_size(&self) -> usize {
+        NUM_ACTIONS
+    }
+
+    fn action_to_index(&self, action: &Self::Action) -> usize {
+        encode_move(*action, color(self.position.turn())).expect("no piece makes the move")
+    }
+
+    /// Indices off the board decode to a move to the from-square, which is never legal.
+    fn index_to_action(&self, index: usize) -> Self::Action {
+        let turn = color(self.position.turn());
+        let Some(mut mv) = decode_move(index, turn) else {
+            let from = (index / NUM_MOVE_TYPES) as Square;
+            return ChessMove {
+                from: orient(from, turn),
+                to: orient(from, turn),
+                promotion: None,
+            };
+        };
+        let pawn = self
+            .position
+            .board()
+            .role_at(shakmaty::Square::new(mv.from as u32))
+            == Some(shakmaty::Role::Pawn);
+        if pawn && mv.promotion.is_none() && matches!(mv.to / 8, 0 | 7) {
+            mv.promotion = Some(Role::Queen);
+        }
+        mv
+    }
+
+    fn current_player(&self) -> Self::Player {
+        color(self.position.turn())
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        Board::OBSERVATION_SHAPE.to_vec()
+    }
+
+    fn observation(&self) -> Vec<f32> {
+        self.board().observation()
+    }
+
+    fn state_hash(&self) -> u64 {
+        self.hashes[self.hashes.len() - 1]
+    }
+
+    fn terminated(&self) -> bool {
+        self.position.is_game_over()
+            || self.position.halfmoves() >= DRAW_PLIES
+            || self.repetitions() >= 3
+    }
+
+    fn check_winner(&self) -> Option<Self::Player> {
+        self.position.outcome().winner().map(color)
+    }
+
+    fn player_index(&self, player: &Self::Player) -> usize {
+        *player as usize
+    }
+
+    /// FEN, e.g. `rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1`.
+    fn to_notation(&self) -> Option<String> {
+        Some(Fen::from_position(&self.position, EnPassantMode::Legal).to_string())
+    }
+
+    fn from_notation(notation: &str) -> Result<Self, ParseError> {
+        let invalid = |e: &dyn fmt::Display| ParseError::Invalid(e.to_string());
+        let fen: Fen = notation.parse().map_err(|e| invalid(&e))?;
+        let position = fen
+            .into_position(CastlingMode::Standard)
+            .map_err(|e| invalid(&e))?;
+        Ok(Self::from_position(position))
+    }
+}
+
+/// The board from White's side, White's pieces in capitals, and the side to move.
+#[cfg(feature = "chess")]
+impl fmt::Display for Chess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let board = self.position.board();
+        for rank in (0..8).rev() {
+            for file in 0..8 {
+                let symbol = board
+                    .piece_at(shakmaty::Square::new(rank * 8 + file))
+                    .map_or('.', |piece| piece.char());
+                write!(f, "{} ", symbol)?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "{:?} to move", color(self.position.turn()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(name: &str) -> Square {
+        let bytes = name.as_bytes();
+        (bytes[1] - b'1') * 8 + (bytes[0] - b'a')
+    }
+
+    fn mv(from: &str, to: &str) -> ChessMove {
+        ChessMove {
+            from: square(from),
+            to: square(to),
+            promotion: None,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut valid = 0;
+        for turn in [Color::White, Color::Black] {
+            for index in 0..NUM_ACTIONS {
+                if let Some(mv) = decode_move(index, turn) {
+                    assert_eq!(encode_move(mv, turn), Some(index), "{:?}", mv);
+                    valid += 1;
+                }
+            }
+        }
+        // Moves running off the board from their square have no encoding.
+        assert!(valid < 2 * NUM_ACTIONS);
+        assert_eq!(decode_move(NUM_ACTIONS, Color::White), None);
+    }
+
+    #[test]
+    fn test_encoding() {
+        // The same move mirrored for either side has the same index.
+        assert_eq!(
+            encode_move(mv("e2", "e4"), Color::White),
+            encode_move(mv("e7", "e5"), Color::Black)
+        );
+        assert_eq!(
+            encode_move(mv("e2", "e4"), Color::White),
+            Some(square("e2") as usize * NUM_MOVE_TYPES + 1)
+        );
+        assert_eq!(
+            encode_move(mv("g1", "f3"), Color::White),
+            Some(square("g1") as usize * NUM_MOVE_TYPES + 56 + 7)
+        );
+        assert_eq!(encode_move(mv("a1", "b4"), Color::White), None);
+
+        let underpromotion = ChessMove {
+            promotion: Some(Role::Knight),
+            ..mv("b7", "a8")
+        };
+        let index = encode_move(underpromotion, Color::White).unwrap();
+        assert_eq!(index % NUM_MOVE_TYPES, 64);
+        assert_eq!(decode_move(index, Color::White), Some(underpromotion));
+        // Queen promotions are plain queen-like moves.
+        let queening = ChessMove {
+            promotion: Some(Role::Queen),
+            ..mv("b7", "b8")
+        };
+        assert_eq!(
+            encode_move(queening, Color::White),
+            encode_move(mv("b7", "b8"), Color::White)
+        );
+    }
+
+    #[test]
+    fn test_observation() {
+        let mut pieces = [None; 64];
+        pieces[square("e1") as usize] = Some((Color::White, Role::King));
+        pieces[square("e8") as usize] = Some((Color::Black, Role::King));
+        pieces[square("d7") as usize] = Some((Color::Black, Role::Pawn));
+        let board = Board {
+            pieces,
+            turn: Color::Black,
+            castling: [false, false, true, false],
+            halfmove_clock: 50,
+        };
+        let observation = board.observation();
+        assert_eq!(
+            observation.len(),
+            Board::OBSERVATION_SHAPE.iter().product::<usize>()
+        );
+        // Black to play sees its pawn on d2 and its king on e1.
+        assert_eq!(
+            observation[Role::Pawn as usize * 64 + square("d2") as usize],
+            1.
+        );
+        assert_eq!(
+            observation[Role::King as usize * 64 + square("e1") as usize],
+            1.
+        );
+        assert_eq!(
+            observation[(6 + Role::King as usize) * 64 + square("e8") as usize],
+            1.
+        );
+        assert_eq!(observation[12 * 64..13 * 64], [1.; 64]);
+        assert_eq!(observation[13 * 64..16 * 64], [0.; 3 * 64]);
+        assert_eq!(observation[16 * 64], 0.5);
+    }
+
+    #[cfg(feature = "chess")]
+    #[test]
+    fn test_chess() {
+        let mut game = Chess::new();
+        assert_eq!(game.get_available_moves().len(), 20);
+        assert_eq!(game.action_space_size(), NUM_ACTIONS);
+        // Fool's mate.
+        for (from, to) in [("f2", "f3"), ("e7", "e5"), ("g2", "g4")] {
+            assert_eq!(game.step(mv(from, to)).unwrap(), 0.);
+        }
+        assert!(game.step(mv("d8", "d4")).is_err());
+        assert_eq!(game.step(mv("d8", "h4")).unwrap(), 1.);
+        assert!(game.terminated());
+        assert_eq!(game.check_winner(), Some(Color::Black));
+        assert_eq!(game.returns(), [-1., 1.]);
+    }
+
+    #[cfg(feature = "chess")]
+    #[test]
+    fn test_special_moves() {
+        // Castling is the king moving two squares.
+        let game = Chess::from_notation("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let moves = game.get_available_moves();
+        assert!(moves.contains(&mv("e1", "g1")));
+        assert!(moves.contains(&mv("e1", "c1")));
+        assert!(!moves.contains(&mv("e1", "h1")));
+        let mut castled = game.clone();
+        castled.step(mv("e1", "g1")).unwrap();
+        assert_eq!(
+            castled.to_notation().unwrap(),
+            "r3k2r/8/8/8/8/8/8/R4RK1 b kq - 1 1"
+        );
+
+        // A pawn reaching the last rank by a queen-like move becomes a queen.
+        let game = Chess::from_notation("8/1P5k/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let queening = ChessMove {
+            promotion: Some(Role::Queen),
+            ..mv("b7", "b8")
+        };
+        let index = game.action_to_index(&queening);
+        assert_eq!(game.index_to_action(index), queening);
+        assert!(game.get_available_moves().contains(&queening));
+        assert!(game.clone().step(queening).is_ok());
+
+        // Shuffling knights repeats the position a third time.
+        let mut game = Chess::new();
+        for _ in 0..2 {
+            for (from, to) in [("g1", "f3"), ("g8", "f6"), ("f3", "g1"), ("f6", "g8")] {
+                assert!(!game.done());
+                game.step(mv(from, to)).unwrap();
+            }
+        }
+        assert!(game.terminated());
+        assert_eq!(game.check_winner(), None);
+    }
+}
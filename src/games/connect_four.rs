@@ -0,0 +1,521 @@
+//! Connect Four: players take turns dropping discs into the columns of a grid 7 wide and 6
+//! high, and the first to get four in a row, horizontally, vertically or diagonally, wins.
+
+use std::{fmt, sync::OnceLock};
+
+use crate::{
+    error::{GameError, ParseError},
+    game::{Game, Symmetry, Undo},
+    notation,
+    symmetry::Transform,
+    zobrist::ZobristTable,
+};
+
+pub const WIDTH: usize = 7;
+pub const HEIGHT: usize = 6;
+
+/// Bits per column of a bitboard: one more than the rows, always empty, so that no line runs
+/// from the top of a column into the bottom of the next.
+const STRIDE: usize = HEIGHT + 1;
+
+/// The discs of a player, bit `col * STRIDE + row` for every one, counting rows from the bottom.
+type Bitboard = u64;
+
+/// Whether `board` has four in a row. Shifting by 1 follows a column, by `STRIDE` a row, and
+/// by one less or more a diagonal.
+fn has_four(board: Bitboard) -> bool {
+    [1, STRIDE, STRIDE - 1, STRIDE + 1].iter().any(|&shift| {
+        let pairs = board & board >> shift;
+        pairs & pairs >> (2 * shift) != 0
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Player {
+    Red,
+    Yellow,
+}
+
+impl Player {
+    fn opponent(self) -> Self {
+        match self {
+            Player::Red => Player::Yellow,
+            Player::Yellow => Player::Red,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConnectFour {
+    /// The discs of Red and of Yellow.
+    boards: [Bitboard; 2],
+    /// The number of discs in every column.
+    heights: [u8; WIDTH],
+    current_player: Player,
+    /// Set by the move that completes a row, so that it is never looked up again.
+    winner: Option<Player>,
+    /// Zobrist hash of the boards and `current_player`, updated by `step`.
+    hash: u64,
+}
+
+fn zobrist() -> &'static ZobristTable {
+    static TABLE: OnceLock<ZobristTable> = OnceLock::new();
+    TABLE.get_or_init(|| ZobristTable::new(WIDTH * STRIDE, 2, 2, 0xc4))
+}
+
+impl ConnectFour {
+    pub fn new() -> Self {
+        Self {
+            boards: [0; 2],
+            heights: [0; WIDTH],
+            current_player: Player::Red,
+            winner: None,
+            hash: zobrist().player(Player::Red as usize),
+        }
+    }
+
+    /// The disc at `row`, counted from the top like in [`fmt::Display`], and `col`.
+    pub fn disc(&self, row: usize, col: usize) -> Option<Player> {
+        let bit = 1 << (col * STRIDE + HEIGHT - 1 - row);
+        if self.boards[Player::Red as usize] & bit != 0 {
+            Some(Player::Red)
+        } else if self.boards[Player::Yellow as usize] & bit != 0 {
+            Some(Player::Yellow)
+        } else {
+            None
+        }
+    }
+
+    fn num_discs(&self) -> usize {
+        (self.boards[0] | self.boards[1]).count_ones() as usize
+    }
+
+    /// Pass the turn without playing, in the hash too.
+    fn toggle_player(&mut self) {
+        let table = zobrist();
+        self.hash ^= table.player(Player::Red as usize) ^ table.player(Player::Yellow as usize);
+        self.current_player = self.current_player.opponent();
+    }
+
+    /// Add or remove the disc of `player` at `square` and pass the turn, in the hash.
+    fn toggle(&mut self, square: usize, player: Player) {
+        let table = zobrist();
+        self.boards[player as usize] ^= 1 << square;
+        self.hash ^= table.piece(square, player as usize)
+            ^ table.player(Player::Red as usize)
+            ^ table.player(Player::Yellow as usize);
+    }
+}
+
+impl Default for ConnectFour {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Game for ConnectFour {
+    /// The column to drop a disc into.
+    type Action = usize;
+
+    type Player = Player;
+
+    fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+        if action >= WIDTH {
+            return Err(GameError::illegal_move(&action, "off the board"));
+        }
+        if self.terminated() {
+            return Err(GameError::GameAlreadyOver);
+        }
+        let height = self.heights[action] as usize;
+        if height == HEIGHT {
+            return Err(GameError::illegal_move(&action, "the column is full"));
+        }
+        let player = self.current_player;
+        self.toggle(action * STRIDE + height, player);
+        self.heights[action] += 1;
+        self.current_player = player.opponent();
+        // Only the mover's discs can have made a line.
+        if has_four(self.boards[player as usize]) {
+            self.winner = Some(player);
+            Ok(1.)
+        } else {
+            Ok(0.)
+        }
+    }
+
+    fn get_available_moves(&self) -> Vec<Self::Action> {
+        let mut moves = vec![];
+        self.get_available_moves_into(&mut moves);
+        moves
+    }
+
+    fn get_available_moves_into(&self, moves: &mut Vec<Self::Action>) {
+        moves.clear();
+        if self.winner.is_none() {
+            moves.extend((0..WIDTH).filter(|&col| (self.heights[col] as usize) < HEIGHT));
+        }
+    }
+
+    fn to_notation(&self) -> Option<String> {
+        let cells: Vec<char> = (0..HEIGHT * WIDTH)
+            .map(|i| symbol(self.disc(i / WIDTH, i % WIDTH)))
+            .collect();
+        Some(notation::write(
+            &cells,
+            WIDTH,
+            symbol(Some(self.current_player)),
+        ))
+    }
+
+    fn from_notation(notation: &str) -> Result<Self, ParseError> {
+        let (cells, to_play) = notation::parse(notation, HEIGHT, WIDTH)?;
+        let player = |symbol: char| match symbol {
+            '.' => Ok(None),
+            'X' => Ok(Some(Player::Red)),
+            'O' => Ok(Some(Player::Yellow)),
+            _ => Err(ParseError::Invalid(format!("invalid disc `{}`", symbol))),
+        };
+        let mut game = ConnectFour::new();
+        // From the bottom up, so that every disc lands on the one below.
+        for row in (0..HEIGHT).rev() {
+            for col in 0..WIDTH {
+                let Some(disc) = player(cells[row * WIDTH + col])? else {
+                    continue;
+                };
+                let height = game.heights[col] as usize;
+                if height != HEIGHT - 1 - row {
+                    return Err(ParseError::Invalid(format!(
+                        "the disc in row {} of column {} floats",
+                        row, col
+                    )));
+                }
+                game.toggle(col * STRIDE + height, disc);
+                game.heights[col] += 1;
+            }
+        }
+        let Some(to_play) = player(to_play)? else {
+            return Err(ParseError::Invalid("no player to move".to_string()));
+        };
+        if game.num_discs() % 2 == 1 {
+            // Every disc toggled the player to move in the hash, which starts with Red.
+            game.current_player = Player::Yellow;
+        }
+        if to_play != game.current_player {
+            game.toggle_player();
+        }
+        let winners = [Player::Red, Player::Yellow]
+            .into_iter()
+            .filter(|&player| has_four(game.boards[player as usize]));
+        game.winner = match winners.collect::<Vec<_>>()[..] {
+            [] => None,
+            [winner] => Some(winner),
+            _ => {
+                return Err(ParseError::Invalid(
+                    "both players have four in a row".to_string(),
+                ))
+            }
+        };
+        Ok(game)
+    }
+
+    fn winning_moves(&self, opponent: bool) -> Vec<Self::Action> {
+        let player = match opponent {
+            false => self.current_player,
+            true => self.current_player.opponent(),
+        };
+        let board = self.boards[player as usize];
+        self.get_available_moves()
+            .into_iter()
+            .filter(|&col| has_four(board | 1 << (col * STRIDE + self.heights[col] as usize)))
+            .collect()
+    }
+
+    fn action_space_size(&self) -> usize {
+        WIDTH
+    }
+
+    fn action_to_index(&self, action: &Self::Action) -> usize {
+        *action
+    }
+
+    fn index_to_action(&self, index: usize) -> Self::Action {
+        index
+    }
+
+    fn current_player(&self) -> Self::Player {
+        self.current_player
+    }
+
+    fn player_index(&self, player: &Self::Player) -> usize {
+        *player as usize
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        vec![3, HEIGHT, WIDTH]
+    }
+
+    fn state_hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Three planes with the top row first: Red's discs, Yellow's discs, and a plane of ones
+    /// if Red is to play.
+    fn observation(&self) -> Vec<f32> {
+        let area = WIDTH * HEIGHT;
+        let mut observation = vec![0.; 3 * area];
+        for row in 0..HEIGHT {
+            for col in 0..WIDTH {
+                if let Some(player) = self.disc(row, col) {
+                    observation[player as usize * area + row * WIDTH + col] = 1.;
+                }
+            }
+        }
+        if self.current_player == Player::Red {
+            observation[2 * area..].fill(1.);
+        }
+        observation
+    }
+
+    /// Like [`Game::observation`], with the planes of Red and Yellow swapped when Yellow is
+    /// to play.
+    fn canonical_observation(&self) -> Vec<f32> {
+        let area = WIDTH * HEIGHT;
+        let mut observation = self.observation();
+        if self.current_player == Player::Yellow {
+            let (red, yellow) = observation.split_at_mut(area);
+            red.swap_with_slice(&mut yellow[..area]);
+        }
+        observation
+    }
+
+    fn terminated(&self) -> bool {
+        self.winner.is_some() || self.num_discs() == WIDTH * HEIGHT
+    }
+
+    fn check_winner(&self) -> Option<Self::Player> {
+        self.winner
+    }
+}
+
+impl Undo for ConnectFour {
+    type UndoToken = ();
+
+    fn step_with_undo(&mut self, action: Self::Action) -> Result<(f32, ()), GameError> {
+        self.step(action).map(|reward| (reward, ()))
+    }
+
+    fn undo(&mut self, action: Self::Action, _token: ()) {
+        let Some(height) = self.heights[action].checked_sub(1) else {
+            return;
+        };
+        let square = action * STRIDE + height as usize;
+        let player = if self.boards[Player::Red as usize] & 1 << square != 0 {
+            Player::Red
+        } else {
+            Player::Yellow
+        };
+        self.toggle(square, player);
+        self.heights[action] = height;
+        // No move can follow a win, so the taken back move is the only one that can have won.
+        self.winner = None;
+        self.current_player = player;
+    }
+}
+
+/// The board and its mirror image, left to right.
+impl Symmetry for ConnectFour {
+    fn symmetries(&self) -> Vec<Transform> {
+        let identity = Transform::new((0..WIDTH * HEIGHT).collect(), (0..WIDTH).collect());
+        let mirror = Transform::new(
+            (0..WIDTH * HEIGHT)
+                .map(|i| i - i % WIDTH + WIDTH - 1 - i % WIDTH)
+                .collect(),
+            (0..WIDTH).rev().collect(),
+        );
+        vec![identity, mirror]
+    }
+}
+
+/// The symbol of a disc, or of the empty cell.
+fn symbol(disc: Option<Player>) -> char {
+    match disc {
+        None => '.',
+        Some(Player::Red) => 'X',
+        Some(Player::Yellow) => 'O',
+    }
+}
+
+impl fmt::Display for ConnectFour {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in 0..HEIGHT {
+            for col in 0..WIDTH {
+                write!(f, "{} ", symbol(self.disc(row, col)))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::{Mcts, MctsConfig};
+
+    /// Play `moves`, alternating from Red.
+    fn play(game: &mut ConnectFour, moves: &[usize]) {
+        for &action in moves {
+            game.step(action).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_step() {
+        let mut game = ConnectFour::new();
+        assert_eq!(game.get_available_moves(), (0..WIDTH).collect::<Vec<_>>());
+        play(&mut game, &[3, 3, 3, 3, 3, 3]);
+        assert_eq!(game.disc(HEIGHT - 1, 3), Some(Player::Red));
+        assert_eq!(game.disc(0, 3), Some(Player::Yellow));
+        assert!(matches!(
+            game.step(3),
+            Err(GameError::IllegalMove { reason, .. }) if reason == "the column is full"
+        ));
+        assert!(game.step(WIDTH).is_err());
+        assert_eq!(game.current_player(), Player::Red);
+        assert_eq!(game.get_available_moves(), vec![0, 1, 2, 4, 5, 6]);
+        assert_eq!(game.to_string().lines().next().unwrap(), ". . . O . . . ");
+    }
+
+    #[test]
+    fn test_check_winner() {
+        let lines: [&[usize]; 4] = [
+            // Across the bottom.
+            &[0, 0, 1, 1, 2, 2, 3],
+            // Up a column.
+            &[4, 5, 4, 5, 4, 5, 4],
+            // Up and to the right.
+            &[0, 1, 1, 2, 2, 3, 2, 3, 3, 6, 3],
+            // Up and to the left.
+            &[6, 5, 5, 4, 4, 3, 4, 3, 3, 0, 3],
+        ];
+        for moves in lines {
+            let mut game = ConnectFour::new();
+            let (last, moves) = moves.split_last().unwrap();
+            play(&mut game, moves);
+            assert_eq!(game.check_winner(), None);
+            assert_eq!(game.step(*last).unwrap(), 1.);
+            assert_eq!(game.check_winner(), Some(Player::Red));
+            assert!(game.done());
+            assert!(game.get_available_moves().is_empty());
+            assert!(matches!(game.step(0), Err(GameError::GameAlreadyOver)));
+        }
+    }
+
+    #[test]
+    fn test_no_wrap_around() {
+        assert!(has_four(0b1111 << 2));
+        // The top rows of a column and the bottom rows of the next are only apart by the
+        // empty bit between them.
+        assert!(!has_four(0b11 << 4 | 0b11 << STRIDE));
+        assert!(!has_four(0b111 << 3 | 1 << STRIDE));
+    }
+
+    #[test]
+    fn test_draw() {
+        // Columns filled in pairs, three discs at a time, never line up four.
+        let mut game = ConnectFour::new();
+        for pair in [0, 2, 4] {
+            for _ in 0..3 {
+                play(&mut game, &[pair, pair + 1]);
+            }
+            for _ in 0..3 {
+                play(&mut game, &[pair + 1, pair]);
+            }
+        }
+        play(&mut game, &[6; HEIGHT]);
+        assert!(game.done());
+        assert_eq!(game.check_winner(), None);
+        assert_eq!(game.returns(), vec![0., 0.]);
+    }
+
+    #[test]
+    fn test_notation() {
+        let mut game = ConnectFour::new();
+        play(&mut game, &[3, 3, 4]);
+        let notation = game.to_notation().unwrap();
+        assert_eq!(notation, "7/7/7/7/3O3/3XX2 O");
+        let parsed = ConnectFour::from_notation(&notation).unwrap();
+        assert_eq!(parsed.to_string(), game.to_string());
+        assert_eq!(parsed.state_hash(), game.state_hash());
+        assert_eq!(parsed.get_available_moves(), game.get_available_moves());
+
+        play(&mut game, &[0, 5, 0, 6]);
+        let parsed = ConnectFour::from_notation(&game.to_notation().unwrap()).unwrap();
+        assert_eq!(parsed.check_winner(), Some(Player::Red));
+        // A disc with an empty cell below it.
+        assert!(ConnectFour::from_notation("7/7/7/7/3O3/4X2 X").is_err());
+    }
+
+    #[test]
+    fn test_observation() {
+        let mut game = ConnectFour::new();
+        play(&mut game, &[0, 6]);
+        let observation = game.observation();
+        let area = WIDTH * HEIGHT;
+        assert_eq!(observation[(HEIGHT - 1) * WIDTH], 1.);
+        assert_eq!(observation[area + area - 1], 1.);
+        assert_eq!(observation[2 * area..], [1.; WIDTH * HEIGHT]);
+
+        game.step(3).unwrap();
+        let canonical = game.canonical_observation();
+        assert_eq!(canonical[area - 1], 1.);
+        assert_eq!(canonical[area + (HEIGHT - 1) * WIDTH + 3], 1.);
+        assert_eq!(canonical[2 * area..], [0.; WIDTH * HEIGHT]);
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut game = ConnectFour::new();
+        play(&mut game, &[0, 1, 0, 1, 0, 1]);
+        let before = game.clone();
+        let (reward, token) = game.step_with_undo(0).unwrap();
+        assert_eq!(reward, 1.);
+        game.undo(0, token);
+        assert_eq!(game.boards, before.boards);
+        assert_eq!(game.heights, before.heights);
+        assert_eq!(game.check_winner(), None);
+        assert_eq!(game.current_player(), Player::Red);
+        assert_eq!(game.state_hash(), before.state_hash());
+    }
+
+    #[test]
+    fn test_symmetries() {
+        let moves = [0, 3, 5, 5];
+        let mut game = ConnectFour::new();
+        play(&mut game, &moves);
+        for transform in game.symmetries() {
+            let mut transformed = ConnectFour::new();
+            for &action in &moves {
+                transformed.step(transform.action(action)).unwrap();
+            }
+            assert_eq!(
+                transformed.observation(),
+                transform.observation(&game.observation())
+            );
+        }
+    }
+
+    #[test]
+    fn test_search_blocks() {
+        // Yellow must stop three in a row on the bottom from becoming four.
+        let mut game = ConnectFour::new();
+        play(&mut game, &[0, 0, 1, 1, 2]);
+        let mcts = Mcts::with_config(MctsConfig {
+            num_simulations: 2000,
+            seed: Some(0),
+            ..Default::default()
+        });
+        let action = mcts.search(&game).action;
+        assert_eq!(action, 3);
+    }
+}
@@ -0,0 +1,545 @@
+//! Environments written in any language, run as a child process that speaks JSON lines over
+//! its stdin and stdout: every request line gets exactly one reply line.
+//!
+//! On start the environment writes its spec,
+//! `{"num_actions": n, "observation_shape": [...], "num_players": p}`, with `num_players`
+//! defaulting to 1. The requests are then:
+//!
+//! - `{"cmd": "reset", "seed": s}`, replied with `{"to_play": i}`;
+//! - `{"cmd": "step", "action": a}`, replied with
+//!   `{"reward": r, "terminated": b, "truncated": b, "to_play": i}`, the reward going to the
+//!   player who took the action;
+//! - `{"cmd": "observation"}`, replied with `{"observation": [...]}`, flattened in row-major
+//!   order;
+//! - `{"cmd": "legal_actions"}`, replied with `{"legal_actions": [...]}`;
+//! - `{"cmd": "save"}`, replied with `{"state": s}`, where `s` is any JSON value standing for
+//!   the current state, like the state itself or the key of a copy the environment keeps;
+//! - `{"cmd": "load", "state": s}`, replied with `{}`, to return to a saved state;
+//! - `{"cmd": "release", "state": s}`, which isn't replied to, once no game needs a saved
+//!   state anymore;
+//! - `{"cmd": "close"}`, which isn't replied to.
+//!
+//! `to_play` is the index of the player to move and can be left out in single-player games.
+//! Any request can be replied with `{"error": message}` instead.
+//!
+//! Saving and loading states is what lets a search explore an environment: every clone of an
+//! [`ExternalGame`] shares the process and holds the state it saved after its last step,
+//! which is loaded back before the clone steps again.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fmt,
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    error::{Context, Error, GameError, ParseError, Result},
+    game::Game,
+};
+
+/// How to start the environment's process.
+#[derive(Debug, Clone)]
+pub struct ExternalCommand {
+    program: String,
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+}
+
+impl ExternalCommand {
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.to_string(),
+            args: vec![],
+            env: vec![],
+        }
+    }
+
+    pub fn arg(mut self, arg: &str) -> Self {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    /// Set an environment variable of the process.
+    pub fn env(mut self, key: &str, value: &str) -> Self {
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// The environment's process, killed when dropped.
+struct Connection {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    /// The saved state the environment is in, `Null` after a step.
+    loaded: Value,
+}
+
+impl Connection {
+    fn spawn(command: &ExternalCommand) -> Result<Self> {
+        let mut child = Command::new(&command.program)
+            .args(&command.args)
+            .envs(command.env.iter().map(|(key, value)| (key, value)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to run `{}`", command.program))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+            loaded: Value::Null,
+        })
+    }
+
+    fn receive<T: DeserializeOwned>(&mut self) -> Result<T> {
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            return Err(Error::backend("the environment exited"));
+        }
+        parse_reply(&line)
+    }
+
+    fn send(&mut self, request: &Request) -> Result<()> {
+        writeln!(self.stdin, "{}", serde_json::to_string(request)?)?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn request<T: DeserializeOwned>(&mut self, request: &Request) -> Result<T> {
+        self.send(request)?;
+        self.receive()
+    }
+
+    fn save(&mut self) -> Result<Value> {
+        let reply: SaveReply = self.request(&Request::Save)?;
+        self.loaded = reply.state.clone();
+        Ok(reply.state)
+    }
+
+    /// Return to `state`, unless the environment is in it already.
+    fn load(&mut self, state: &Value) -> Result<()> {
+        if self.loaded != *state {
+            self.loaded = Value::Null;
+            self.request::<Value>(&Request::Load {
+                state: state.clone(),
+            })?;
+            self.loaded = state.clone();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.send(&Request::Close);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum Request {
+    Reset { seed: u64 },
+    Step { action: usize },
+    Observation,
+    LegalActions,
+    Save,
+    Load { state: Value },
+    Release { state: Value },
+    Close,
+}
+
+/// A line written by the environment, an error if it's an `{"error": message}` reply.
+pub fn parse_reply<T: DeserializeOwned>(line: &str) -> Result<T> {
+    let reply: Value = serde_json::from_str(line)?;
+    match reply.get("error") {
+        Some(Value::String(error)) => {
+            Err(Error::backend(format!("the environment failed: {}", error)))
+        }
+        Some(error) => Err(Error::backend(format!("the environment failed: {}", error))),
+        None => Ok(serde_json::from_value(reply)?),
+    }
+}
+
+/// What the environment writes on start.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "UncheckedSpec")]
+pub struct Spec {
+    pub num_actions: usize,
+    pub observation_shape: Vec<usize>,
+    pub num_players: usize,
+}
+
+#[derive(Deserialize)]
+struct UncheckedSpec {
+    num_actions: usize,
+    observation_shape: Vec<usize>,
+    num_players: Option<usize>,
+}
+
+impl TryFrom<UncheckedSpec> for Spec {
+    type Error = String;
+
+    fn try_from(spec: UncheckedSpec) -> Result<Self, String> {
+        let spec = Self {
+            num_actions: spec.num_actions,
+            observation_shape: spec.observation_shape,
+            num_players: spec.num_players.unwrap_or(1),
+        };
+        if spec.num_players == 0 {
+            return Err("an environment needs a player".to_string());
+        }
+        if spec
+            .observation_shape
+            .iter()
+            .try_fold(1usize, |size, &dim| size.checked_mul(dim))
+            .is_none()
+        {
+            return Err(format!(
+                "observation shape {:?} is too large",
+                spec.observation_shape
+            ));
+        }
+        Ok(spec)
+    }
+}
+
+/// The reply to `reset`.
+#[derive(Debug, Deserialize)]
+pub struct ResetReply {
+    pub to_play: Option<usize>,
+}
+
+/// The reply to `step`.
+#[derive(Debug, Deserialize)]
+pub struct StepReply {
+    pub reward: f32,
+    pub terminated: bool,
+    pub truncated: bool,
+    pub to_play: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ObservationReply {
+    pub observation: Vec<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LegalActionsReply {
+    pub legal_actions: Vec<usize>,
+}
+
+/// The reply to `save`.
+#[derive(Debug, Deserialize)]
+pub struct SaveReply {
+    pub state: Value,
+}
+
+/// A state the environment saved, released once no clone holds it.
+struct SavedState {
+    state: Value,
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl Drop for SavedState {
+    fn drop(&mut self) {
+        if let Ok(mut connection) = self.connection.lock() {
+            let _ = connection.send(&Request::Release {
+                state: self.state.take(),
+            });
+        }
+    }
+}
+
+/// Cheap to clone: clones share the environment's process.
+#[derive(Clone)]
+pub struct ExternalGame {
+    program: String,
+    connection: Arc<Mutex<Connection>>,
+    state: Arc<SavedState>,
+    num_actions: usize,
+    observation_shape: Vec<usize>,
+    num_players: usize,
+    observation: Vec<f32>,
+    legal_actions: Vec<usize>,
+    to_play: usize,
+    /// The number of steps since the reset.
+    steps: usize,
+    terminated: bool,
+    truncated: bool,
+}
+
+impl ExternalGame {
+    /// Start the environment and reset it with `seed`.
+    pub fn new(command: ExternalCommand, seed: u64) -> Result<Self> {
+        let mut connection = Connection::spawn(&command)?;
+        let spec: Spec = connection
+            .receive()
+            .context("failed to read the environment's spec")?;
+        let reply: ResetReply = connection.request(&Request::Reset { seed })?;
+        let to_play = read_to_play(reply.to_play, spec.num_players)?;
+        let (observation, legal_actions) = refresh(&mut connection, &spec)?;
+        let state = connection.save()?;
+        let connection = Arc::new(Mutex::new(connection));
+        Ok(Self {
+            program: command.program,
+            state: Arc::new(SavedState {
+                state,
+                connection: connection.clone(),
+            }),
+            connection,
+            num_actions: spec.num_actions,
+            observation_shape: spec.observation_shape,
+            num_players: spec.num_players,
+            observation,
+            legal_actions,
+            to_play,
+            steps: 0,
+            terminated: false,
+            truncated: false,
+        })
+    }
+
+    /// Play `action` in the environment from the game's state and read back the new state.
+    fn send_step(&mut self, action: usize) -> Result<f32> {
+        let spec = Spec {
+            num_actions: self.num_actions,
+            observation_shape: self.observation_shape.clone(),
+            num_players: self.num_players,
+        };
+        let connection = self.connection.clone();
+        let mut connection = connection
+            .lock()
+            .map_err(|_| Error::backend("the environment failed on another thread"))?;
+        connection.load(&self.state.state)?;
+        connection.loaded = Value::Null;
+        let reply: StepReply = connection.request(&Request::Step { action })?;
+        let to_play = read_to_play(reply.to_play, self.num_players)?;
+        let (observation, legal_actions) = refresh(&mut connection, &spec)?;
+        let state = connection.save()?;
+        // Replacing the state releases the old one, which needs the lock.
+        drop(connection);
+        self.state = Arc::new(SavedState {
+            state,
+            connection: self.connection.clone(),
+        });
+        self.terminated = reply.terminated;
+        self.truncated = reply.truncated;
+        self.to_play = to_play;
+        self.observation = observation;
+        self.legal_actions = legal_actions;
+        self.steps += 1;
+        Ok(reply.reward)
+    }
+}
+
+fn read_to_play(to_play: Option<usize>, num_players: usize) -> Result<usize> {
+    let to_play = to_play.unwrap_or(0);
+    if to_play >= num_players {
+        return Err(ParseError::Invalid(format!("player {} doesn't exist", to_play)).into());
+    }
+    Ok(to_play)
+}
+
+/// Fetch the observation and legal actions of the environment's state.
+fn refresh(connection: &mut Connection, spec: &Spec) -> Result<(Vec<f32>, Vec<usize>)> {
+    let ObservationReply { observation } = connection.request(&Request::Observation)?;
+    let LegalActionsReply { legal_actions } = connection.request(&Request::LegalActions)?;
+    if let Some(&action) = legal_actions.iter().find(|&&a| a >= spec.num_actions) {
+        return Err(ParseError::Invalid(format!(
+            "legal action {} is out of the action space",
+            action
+        ))
+        .into());
+    }
+    let size: usize = spec.observation_shape.iter().product();
+    if observation.len() != size {
+        return Err(ParseError::Invalid(format!(
+            "the observation has {} values, not {}",
+            observation.len(),
+            size
+        ))
+        .into());
+    }
+    Ok((observation, legal_actions))
+}
+
+impl Game for ExternalGame {
+    type Action = usize;
+
+    type Player = usize;
+
+    fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+        if self.done() {
+            return Err(GameError::GameAlreadyOver);
+        }
+        if !self.legal_actions.contains(&action) {
+            return Err(GameError::illegal_move(&action, "not a legal action"));
+        }
+        self.send_step(action)
+            .map_err(|e| GameError::Environment(e.into()))
+    }
+
+    fn get_available_moves(&self) -> Vec<Self::Action> {
+        if self.done() {
+            return vec![];
+        }
+        self.legal_actions.clone()
+    }
+
+    fn action_space_size(&self) -> usize {
+        self.num_actions
+    }
+
+    fn action_to_index(&self, action: &Self::Action) -> usize {
+        *action
+    }
+
+    fn index_to_action(&self, index: usize) -> Self::Action {
+        index
+    }
+
+    fn current_player(&self) -> Self::Player {
+        self.to_play
+    }
+
+    fn player_index(&self, player: &Self::Player) -> usize {
+        *player
+    }
+
+    fn num_players(&self) -> usize {
+        self.num_players
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        self.observation_shape.clone()
+    }
+
+    fn observation(&self) -> Vec<f32> {
+        self.observation.clone()
+    }
+
+    fn terminated(&self) -> bool {
+        self.terminated
+    }
+
+    fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn check_winner(&self) -> Option<Self::Player> {
+        None
+    }
+}
+
+impl fmt::Display for ExternalGame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} step {}: {:?}",
+            self.program, self.steps, self.observation
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two players take turns adding 1 or 2 to a total starting at the seed; whoever reaches
+    /// 5 wins.
+    const RACE_TO_FIVE: &str = r#"
+import json, sys
+
+def reply(message):
+    print(json.dumps(message), flush=True)
+
+reply({"num_actions": 3, "observation_shape": [1], "num_players": 2})
+total, to_play = 0, 0
+for line in sys.stdin:
+    request = json.loads(line)
+    if request["cmd"] == "reset":
+        total, to_play = request["seed"], 0
+        reply({"to_play": to_play})
+    elif request["cmd"] == "step":
+        total += request["action"]
+        done = total >= 5
+        to_play = 1 - to_play
+        reply({"reward": 1.0 if done else 0.0, "terminated": done, "truncated": False,
+               "to_play": to_play})
+    elif request["cmd"] == "observation":
+        reply({"observation": [total]})
+    elif request["cmd"] == "legal_actions":
+        reply({"legal_actions": [1, 2]})
+    elif request["cmd"] == "save":
+        reply({"state": [total, to_play]})
+    elif request["cmd"] == "load":
+        total, to_play = request["state"]
+        reply({})
+    elif request["cmd"] == "release":
+        pass
+    elif request["cmd"] == "close":
+        break
+    else:
+        reply({"error": "unknown command"})
+"#;
+
+    /// The race to five, or `None` without a Python interpreter.
+    fn race_to_five() -> Option<ExternalCommand> {
+        Command::new("python3").arg("--version").output().ok()?;
+        Some(ExternalCommand::new("python3").arg("-c").arg(RACE_TO_FIVE))
+    }
+
+    #[test]
+    fn test_protocol() {
+        let Some(command) = race_to_five() else {
+            return;
+        };
+        let mut game = ExternalGame::new(command, 1).unwrap();
+        assert_eq!(game.num_players(), 2);
+        assert_eq!(game.observation(), vec![1.]);
+        assert_eq!(game.get_available_moves(), vec![1, 2]);
+        assert!(game.step(0).is_err());
+
+        assert_eq!(game.step(2).unwrap(), 0.);
+        assert_eq!(game.current_player(), 1);
+        let mut clone = game.clone();
+        assert_eq!(clone.observation(), vec![3.]);
+        assert_eq!(clone.current_player(), 1);
+
+        assert_eq!(game.step(2).unwrap(), 1.);
+        assert!(game.terminated());
+        assert!(game.get_available_moves().is_empty());
+        assert!(!clone.done());
+        // The clone steps from its own state, which the environment goes back to.
+        assert_eq!(clone.step(1).unwrap(), 0.);
+        assert_eq!(clone.observation(), vec![4.]);
+        assert_eq!(clone.current_player(), 0);
+    }
+
+    #[test]
+    fn test_search() {
+        let Some(command) = race_to_five() else {
+            return;
+        };
+        let mut game = ExternalGame::new(command, 0).unwrap();
+        let result = crate::mcts::Mcts::<ExternalGame>::new(100).search(&game);
+        let visits: usize = result.children.iter().map(|child| child.visits).sum();
+        assert_eq!(visits, 100);
+        // The simulations stepped clones, so the game goes on from where it was.
+        assert_eq!(game.step(2).unwrap(), 0.);
+        assert_eq!(game.observation(), vec![2.]);
+    }
+
+    #[test]
+    fn test_missing_program() {
+        assert!(ExternalGame::new(ExternalCommand::new("/nonexistent/environment"), 0).is_err());
+    }
+}
@@ -0,0 +1,473 @@
+//! Go under Tromp-Taylor rules: suicide is illegal, no move may recreate an earlier board
+//! position (positional superko), the game ends after two passes in a row, and it is scored
+//! by area: a player's stones plus the empty regions reaching only their stones, with komi
+//! added for White.
+//!
+//! The standard board sizes are 9x9, 13x13 and 19x19.
+
+use std::{collections::HashSet, fmt, sync::Arc};
+
+use crate::{error::GameError, game::Game, zobrist::ZobristTable};
+
+/// Komi for area scoring; the half point rules out draws.
+pub const DEFAULT_KOMI: f32 = 7.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Player {
+    Black,
+    White,
+}
+
+impl Player {
+    fn opponent(self) -> Self {
+        match self {
+            Player::Black => Player::White,
+            Player::White => Player::Black,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Move {
+    Place(usize, usize),
+    Pass,
+}
+
+#[derive(Debug, Clone)]
+pub struct Go {
+    size: usize,
+    /// The stones in row-major order.
+    board: Vec<Option<Player>>,
+    current_player: Player,
+    komi: f32,
+    /// Consecutive passes; two end the game.
+    passes: usize,
+    num_moves: usize,
+    /// Games are truncated after this many moves, since random play can go on for very long.
+    max_moves: usize,
+    table: Arc<ZobristTable>,
+    /// Zobrist hash of `board`, without the player to play as superko ignores it.
+    hash: u64,
+    /// The hashes of every position so far, for superko.
+    seen: HashSet<u64>,
+}
+
+impl Go {
+    pub fn new(size: usize) -> Self {
+        Self::with_komi(size, DEFAULT_KOMI)
+    }
+
+    pub fn with_komi(size: usize, komi: f32) -> Self {
+        assert!(size > 0, "the board must have at least one point");
+        let area = size * size;
+        let table = Arc::new(ZobristTable::new(area, 2, 2, 0x60 + size as u64));
+        Self {
+            size,
+            board: vec![None; area],
+            current_player: Player::Black,
+            komi,
+            passes: 0,
+            num_moves: 0,
+            max_moves: 3 * area,
+            table,
+            hash: 0,
+            seen: HashSet::from([0]),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn komi(&self) -> f32 {
+        self.komi
+    }
+
+    pub fn set_komi(&mut self, komi: f32) {
+        self.komi = komi;
+    }
+
+    /// Give the move to `player`, e.g. for handicap stones or a move out of turn sent by a
+    /// GUI.
+    pub fn set_current_player(&mut self, player: Player) {
+        self.current_player = player;
+    }
+
+    fn neighbors(&self, point: usize) -> impl Iterator<Item = usize> {
+        let size = self.size;
+        let (row, col) = (point / size, point % size);
+        [
+            (row > 0).then(|| point - size),
+            (row + 1 < size).then(|| point + size),
+            (col > 0).then(|| point - 1),
+            (col + 1 < size).then(|| point + 1),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// The points of the group of stones at `point`, and whether it has a liberty.
+    fn group(&self, board: &[Option<Player>], point: usize) -> (Vec<usize>, bool) {
+        let color = board[point];
+        let mut group = vec![point];
+        let mut visited = vec![false; board.len()];
+        visited[point] = true;
+        let mut has_liberty = false;
+        let mut i = 0;
+        while i < group.len() {
+            for neighbor in self.neighbors(group[i]) {
+                if board[neighbor].is_none() {
+                    has_liberty = true;
+                } else if board[neighbor] == color && !visited[neighbor] {
+                    visited[neighbor] = true;
+                    group.push(neighbor);
+                }
+            }
+            i += 1;
+        }
+        (group, has_liberty)
+    }
+
+    /// The board and its hash after the player to play places a stone on `point`, or why the
+    /// stone can't go there.
+    fn place(&self, point: usize) -> Result<(Vec<Option<Player>>, u64), &'static str> {
+        if self.board[point].is_some() {
+            return Err("the spot is already filled");
+        }
+        let player = self.current_player;
+        let mut board = self.board.clone();
+        let mut hash = self.hash ^ self.table.piece(point, player as usize);
+        board[point] = Some(player);
+        for neighbor in self.neighbors(point) {
+            if board[neighbor] != Some(player.opponent()) {
+                continue;
+            }
+            let (group, has_liberty) = self.group(&board, neighbor);
+            if !has_liberty {
+                for stone in group {
+                    board[stone] = None;
+                    hash ^= self.table.piece(stone, player.opponent() as usize);
+                }
+            }
+        }
+        if !self.group(&board, point).1 {
+            return Err("suicide is illegal");
+        }
+        if self.seen.contains(&hash) {
+            return Err("the move repeats an earlier position");
+        }
+        Ok((board, hash))
+    }
+
+    /// The area score of Black and White, including komi.
+    pub fn score(&self) -> (f32, f32) {
+        let mut scores = [0., self.komi];
+        let mut visited = vec![false; self.board.len()];
+        for point in 0..self.board.len() {
+            if let Some(player) = self.board[point] {
+                scores[player as usize] += 1.;
+                continue;
+            }
+            if visited[point] {
+                continue;
+            }
+            // Flood fill the empty region and see whose stones it reaches.
+            let mut region = vec![point];
+            visited[point] = true;
+            let mut reaches = [false; 2];
+            let mut i = 0;
+            while i < region.len() {
+                for neighbor in self.neighbors(region[i]) {
+                    match self.board[neighbor] {
+                        Some(player) => reaches[player as usize] = true,
+                        None if !visited[neighbor] => {
+                            visited[neighbor] = true;
+                            region.push(neighbor);
+                        }
+                        None => {}
+                    }
+                }
+                i += 1;
+            }
+            match reaches {
+                [true, false] => scores[0] += region.len() as f32,
+                [false, true] => scores[1] += region.len() as f32,
+                _ => {}
+            }
+        }
+        (scores[0], scores[1])
+    }
+}
+
+impl Game for Go {
+    type Action = Move;
+
+    type Player = Player;
+
+    /// The move ending the game is rewarded with 1 if its player wins, -1 if they lose.
+    fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+        if self.done() {
+            return Err(GameError::GameAlreadyOver);
+        }
+        let player = self.current_player;
+        match action {
+            Move::Place(row, col) => {
+                if row >= self.size || col >= self.size {
+                    return Err(GameError::illegal_move(&action, "off the board"));
+                }
+                let (board, hash) = self
+                    .place(row * self.size + col)
+                    .map_err(|reason| GameError::illegal_move(&action, reason))?;
+                self.board = board;
+                self.hash = hash;
+                self.seen.insert(hash);
+                self.passes = 0;
+            }
+            Move::Pass => self.passes += 1,
+        }
+        self.current_player = player.opponent();
+        self.num_moves += 1;
+        let reward = match self.check_winner() {
+            Some(winner) if winner == player => 1.,
+            Some(_) => -1.,
+            None => 0.,
+        };
+        Ok(reward)
+    }
+
+    /// Passing is always legal.
+    fn get_available_moves(&self) -> Vec<Self::Action> {
+        if self.done() {
+            return vec![];
+        }
+        let mut moves: Vec<_> = (0..self.board.len())
+            .filter(|&point| self.place(point).is_ok())
+            .map(|point| self.index_to_action(point))
+            .collect();
+        moves.push(Move::Pass);
+        moves
+    }
+
+    /// Every point, and a pass.
+    fn action_space_size(&self) -> usize {
+        self.board.len() + 1
+    }
+
+    fn action_to_index(&self, action: &Self::Action) -> usize {
+        match *action {
+            Move::Place(row, col) => row * self.size + col,
+            Move::Pass => self.board.len(),
+        }
+    }
+
+    fn index_to_action(&self, index: usize) -> Self::Action {
+        if index == self.board.len() {
+            Move::Pass
+        } else {
+            Move::Place(index / self.size, index % self.size)
+        }
+    }
+
+    fn current_player(&self) -> Self::Player {
+        self.current_player
+    }
+
+    fn player_index(&self, player: &Self::Player) -> usize {
+        *player as usize
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        vec![3, self.size, self.size]
+    }
+
+    /// Three planes: Black's stones, White's stones, and a plane of ones if Black is to play.
+    fn observation(&self) -> Vec<f32> {
+        let area = self.board.len();
+        let mut observation = vec![0.; 3 * area];
+        for (i, stone) in self.board.iter().enumerate() {
+            if let Some(player) = stone {
+                observation[*player as usize * area + i] = 1.;
+            }
+        }
+        if self.current_player == Player::Black {
+            observation[2 * area..].fill(1.);
+        }
+        observation
+    }
+
+    /// Like [`Game::observation`], with the planes of Black and White swapped when White is
+    /// to play.
+    fn canonical_observation(&self) -> Vec<f32> {
+        let area = self.board.len();
+        let mut observation = self.observation();
+        if self.current_player == Player::White {
+            let (black, white) = observation.split_at_mut(area);
+            black.swap_with_slice(&mut white[..area]);
+        }
+        observation
+    }
+
+    fn state_hash(&self) -> u64 {
+        self.hash ^ self.table.player(self.current_player as usize)
+    }
+
+    fn terminated(&self) -> bool {
+        self.passes >= 2
+    }
+
+    fn truncated(&self) -> bool {
+        !self.terminated() && self.num_moves >= self.max_moves
+    }
+
+    /// The player with the higher area score, once the game is over.
+    fn check_winner(&self) -> Option<Self::Player> {
+        if !self.done() {
+            return None;
+        }
+        let (black, white) = self.score();
+        if black > white {
+            Some(Player::Black)
+        } else if white > black {
+            Some(Player::White)
+        } else {
+            None
+        }
+    }
+
+    /// The area margins, after komi.
+    fn scores(&self) -> Vec<f32> {
+        let (black, white) = self.score();
+        vec![black - white, white - black]
+    }
+}
+
+impl fmt::Display for Go {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.board.chunks(self.size) {
+            for stone in row {
+                let symbol = match stone {
+                    None => ".",
+                    Some(Player::Black) => "X",
+                    Some(Player::White) => "O",
+                };
+                write!(f, "{} ", symbol)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::seq::SliceRandom;
+
+    /// Play `moves` as `(row, col)` points, alternating from Black, passing for `None`.
+    fn play(game: &mut Go, moves: &[Option<(usize, usize)>]) {
+        for action in moves {
+            let action = match *action {
+                Some((row, col)) => Move::Place(row, col),
+                None => Move::Pass,
+            };
+            game.step(action).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_board_sizes() {
+        for size in [9, 13, 19] {
+            let game = Go::new(size);
+            assert_eq!(game.action_space_size(), size * size + 1);
+            assert_eq!(game.get_available_moves().len(), size * size + 1);
+        }
+    }
+
+    #[test]
+    fn test_capture() {
+        let mut game = Go::new(9);
+        // White's stone in the corner is captured by two black stones.
+        play(&mut game, &[Some((0, 1)), Some((0, 0)), Some((1, 0))]);
+        assert_eq!(game.board[0], None);
+        assert!(game.step(Move::Place(0, 1)).is_err());
+    }
+
+    #[test]
+    fn test_suicide() {
+        let mut game = Go::new(9);
+        play(&mut game, &[Some((0, 1)), None, Some((1, 0))]);
+        assert!(game.step(Move::Place(0, 0)).is_err());
+        assert!(!game.get_available_moves().contains(&Move::Place(0, 0)));
+    }
+
+    #[test]
+    fn test_superko() {
+        let mut game = Go::new(9);
+        // A ko: Black captures at (1, 1), and White may not retake at (1, 2) right away.
+        play(
+            &mut game,
+            &[
+                Some((0, 1)),
+                Some((0, 2)),
+                Some((1, 0)),
+                Some((1, 3)),
+                Some((2, 1)),
+                Some((2, 2)),
+                None,
+                Some((1, 1)),
+                Some((1, 2)),
+            ],
+        );
+        assert_eq!(game.board[10], None);
+        assert!(game.step(Move::Place(1, 1)).is_err());
+        assert!(!game.get_available_moves().contains(&Move::Place(1, 1)));
+        // After a move elsewhere the position is new, and the retake is allowed.
+        play(&mut game, &[Some((5, 5)), Some((6, 6)), Some((1, 1))]);
+        assert_eq!(game.board[11], None);
+    }
+
+    #[test]
+    fn test_scoring() {
+        let mut game = Go::with_komi(5, 0.5);
+        // Black walls off the left two columns, White the right two.
+        for row in 0..5 {
+            play(&mut game, &[Some((row, 1)), Some((row, 3))]);
+        }
+        assert_eq!(game.score(), (10., 10.5));
+        assert!(!game.done());
+        play(&mut game, &[None]);
+        assert_eq!(game.step(Move::Pass).unwrap(), 1.);
+        assert!(game.terminated());
+        assert_eq!(game.check_winner(), Some(Player::White));
+        assert!(game.get_available_moves().is_empty());
+    }
+
+    #[test]
+    fn test_truncation() {
+        let mut game = Go::new(2);
+        let mut rng = rand::thread_rng();
+        while !game.done() {
+            let moves: Vec<_> = game
+                .get_available_moves()
+                .into_iter()
+                .filter(|&action| action != Move::Pass)
+                .collect();
+            let action = *moves.choose(&mut rng).unwrap_or(&Move::Pass);
+            game.step(action).unwrap();
+        }
+        assert!(game.num_moves <= game.max_moves);
+        assert!(game.terminated() != game.truncated());
+    }
+
+    #[test]
+    fn test_state_hash() {
+        let mut game = Go::new(9);
+        play(&mut game, &[Some((0, 1)), Some((0, 0)), Some((1, 0))]);
+        let mut replayed = Go::new(9);
+        play(&mut replayed, &[Some((1, 0)), None, Some((0, 1))]);
+        assert_eq!(game.board, replayed.board);
+        assert_eq!(game.state_hash(), replayed.state_hash());
+        play(&mut replayed, &[None]);
+        assert_ne!(game.state_hash(), replayed.state_hash());
+    }
+}
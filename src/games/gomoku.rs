@@ -0,0 +1,469 @@
+//! Gomoku: players take turns placing stones on an NxN board, and the first to get five or
+//! more in a row, horizontally, vertically or diagonally, wins.
+
+use std::fmt;
+
+use crate::{
+    error::{GameError, ParseError},
+    game::{Game, Symmetry, Undo},
+    notation,
+    symmetry::Transform,
+};
+
+/// The standard board is 15x15.
+pub const DEFAULT_SIZE: usize = 15;
+
+/// How many stones in a row win the game.
+const WIN_LENGTH: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Player {
+    Black,
+    White,
+}
+
+impl Player {
+    fn opponent(self) -> Self {
+        match self {
+            Player::Black => Player::White,
+            Player::White => Player::Black,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Gomoku {
+    size: usize,
+    /// The stones in row-major order.
+    board: Vec<Option<Player>>,
+    current_player: Player,
+    /// Set by the move that completes a row, so that it is never looked up again.
+    winner: Option<Player>,
+    num_stones: usize,
+}
+
+impl Gomoku {
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "the board must have at least one square");
+        Self {
+            size,
+            board: vec![None; size * size],
+            current_player: Player::Black,
+            winner: None,
+            num_stones: 0,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn stone(&self, row: usize, col: usize) -> Option<Player> {
+        self.board[row * self.size + col]
+    }
+
+    /// Whether a stone of `player` at `(row, col)` is, or would be, part of a row of at least
+    /// five, looking only along the four lines through it.
+    fn completes_row(&self, row: usize, col: usize, player: Player) -> bool {
+        let count = |dr: isize, dc: isize| {
+            let (mut r, mut c) = (row as isize, col as isize);
+            let mut count = 0;
+            loop {
+                r += dr;
+                c += dc;
+                if r < 0 || c < 0 || r >= self.size as isize || c >= self.size as isize {
+                    return count;
+                }
+                if self.stone(r as usize, c as usize) != Some(player) {
+                    return count;
+                }
+                count += 1;
+            }
+        };
+        [(0, 1), (1, 0), (1, 1), (1, -1)]
+            .iter()
+            .any(|&(dr, dc)| 1 + count(dr, dc) + count(-dr, -dc) >= WIN_LENGTH)
+    }
+}
+
+impl Default for Gomoku {
+    fn default() -> Self {
+        Self::new(DEFAULT_SIZE)
+    }
+}
+
+impl Game for Gomoku {
+    type Action = (usize, usize);
+
+    type Player = Player;
+
+    fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+        let (row, col) = action;
+        if row >= self.size || col >= self.size {
+            return Err(GameError::illegal_move(&action, "off the board"));
+        }
+        if self.terminated() {
+            return Err(GameError::GameAlreadyOver);
+        }
+        if self.stone(row, col).is_some() {
+            return Err(GameError::illegal_move(
+                &action,
+                "the spot is already filled",
+            ));
+        }
+        self.board[row * self.size + col] = Some(self.current_player);
+        self.num_stones += 1;
+        let reward = if self.completes_row(row, col, self.current_player) {
+            self.winner = Some(self.current_player);
+            1.
+        } else {
+            0.
+        };
+        self.current_player = self.current_player.opponent();
+        Ok(reward)
+    }
+
+    fn get_available_moves(&self) -> Vec<Self::Action> {
+        let mut moves = vec![];
+        self.get_available_moves_into(&mut moves);
+        moves
+    }
+
+    fn get_available_moves_into(&self, moves: &mut Vec<Self::Action>) {
+        moves.clear();
+        if self.winner.is_none() {
+            moves.extend(
+                (0..self.board.len())
+                    .filter(|&i| self.board[i].is_none())
+                    .map(|i| self.index_to_action(i)),
+            );
+        }
+    }
+
+    fn to_notation(&self) -> Option<String> {
+        let cells: Vec<char> = self.board.iter().map(|&stone| symbol(stone)).collect();
+        let to_play = symbol(Some(self.current_player));
+        Some(notation::write(&cells, self.size, to_play))
+    }
+
+    fn from_notation(notation: &str) -> Result<Self, ParseError> {
+        let size = notation.split('/').count();
+        let (cells, to_play) = notation::parse(notation, size, size)?;
+        let player = |symbol: char| match symbol {
+            '.' => Ok(None),
+            'X' => Ok(Some(Player::Black)),
+            'O' => Ok(Some(Player::White)),
+            _ => Err(ParseError::Invalid(format!("invalid stone `{}`", symbol))),
+        };
+        let mut game = Gomoku::new(size);
+        for (i, &cell) in cells.iter().enumerate() {
+            game.board[i] = player(cell)?;
+        }
+        game.num_stones = game.board.iter().flatten().count();
+        let Some(to_play) = player(to_play)? else {
+            return Err(ParseError::Invalid("no player to move".to_string()));
+        };
+        game.current_player = to_play;
+        for i in 0..game.board.len() {
+            let (row, col) = (i / size, i % size);
+            let Some(stone) = game.board[i] else {
+                continue;
+            };
+            if game.completes_row(row, col, stone) {
+                if game.winner.is_some_and(|winner| winner != stone) {
+                    return Err(ParseError::Invalid(
+                        "both players have five in a row".to_string(),
+                    ));
+                }
+                game.winner = Some(stone);
+            }
+        }
+        Ok(game)
+    }
+
+    fn winning_moves(&self, opponent: bool) -> Vec<Self::Action> {
+        let player = match opponent {
+            false => self.current_player,
+            true => self.current_player.opponent(),
+        };
+        self.get_available_moves()
+            .into_iter()
+            .filter(|&(row, col)| self.completes_row(row, col, player))
+            .collect()
+    }
+
+    fn action_space_size(&self) -> usize {
+        self.size * self.size
+    }
+
+    fn action_to_index(&self, action: &Self::Action) -> usize {
+        let (row, col) = *action;
+        row * self.size + col
+    }
+
+    fn index_to_action(&self, index: usize) -> Self::Action {
+        (index / self.size, index % self.size)
+    }
+
+    fn current_player(&self) -> Self::Player {
+        self.current_player
+    }
+
+    fn player_index(&self, player: &Self::Player) -> usize {
+        *player as usize
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        vec![3, self.size, self.size]
+    }
+
+    /// Three planes: Black's stones, White's stones, and a plane of ones if Black is to play.
+    fn observation(&self) -> Vec<f32> {
+        let area = self.board.len();
+        let mut observation = vec![0.; 3 * area];
+        for (i, stone) in self.board.iter().enumerate() {
+            if let Some(player) = stone {
+                observation[*player as usize * area + i] = 1.;
+            }
+        }
+        if self.current_player == Player::Black {
+            observation[2 * area..].fill(1.);
+        }
+        observation
+    }
+
+    /// Like [`Game::observation`], with the planes of Black and White swapped when White is
+    /// to play.
+    fn canonical_observation(&self) -> Vec<f32> {
+        let area = self.board.len();
+        let mut observation = self.observation();
+        if self.current_player == Player::White {
+            let (black, white) = observation.split_at_mut(area);
+            black.swap_with_slice(&mut white[..area]);
+        }
+        observation
+    }
+
+    fn terminated(&self) -> bool {
+        self.winner.is_some() || self.num_stones == self.board.len()
+    }
+
+    fn check_winner(&self) -> Option<Self::Player> {
+        self.winner
+    }
+}
+
+impl Undo for Gomoku {
+    type UndoToken = ();
+
+    fn step_with_undo(&mut self, action: Self::Action) -> Result<(f32, ()), GameError> {
+        self.step(action).map(|reward| (reward, ()))
+    }
+
+    fn undo(&mut self, action: Self::Action, _token: ()) {
+        let index = self.action_to_index(&action);
+        if let Some(player) = self.board[index].take() {
+            self.num_stones -= 1;
+            // No move can follow a win, so the taken back move is the only one that can
+            // have won.
+            self.winner = None;
+            self.current_player = player;
+        }
+    }
+}
+
+impl Symmetry for Gomoku {
+    fn symmetries(&self) -> Vec<Transform> {
+        Transform::dihedral(self.size, self.action_space_size())
+    }
+}
+
+/// The symbol of a stone, or of the empty cell.
+fn symbol(stone: Option<Player>) -> char {
+    match stone {
+        None => '.',
+        Some(Player::Black) => 'X',
+        Some(Player::White) => 'O',
+    }
+}
+
+impl fmt::Display for Gomoku {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.board.chunks(self.size) {
+            for &stone in row {
+                write!(f, "{} ", symbol(stone))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::Mcts;
+
+    /// Play `moves`, alternating from Black.
+    fn play(game: &mut Gomoku, moves: &[(usize, usize)]) {
+        for &action in moves {
+            game.step(action).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_step() {
+        let mut game = Gomoku::default();
+        assert_eq!(game.action_space_size(), 225);
+        assert_eq!(game.get_available_moves().len(), 225);
+        assert!(game.step((7, 7)).is_ok());
+        assert_eq!(game.current_player(), Player::White);
+        assert!(game.step((7, 7)).is_err());
+        assert!(game.step((15, 0)).is_err());
+        assert_eq!(game.current_player(), Player::White);
+        assert_eq!(game.get_available_moves().len(), 224);
+    }
+
+    #[test]
+    fn test_check_winner() {
+        let lines: [[(usize, usize); 5]; 4] = [
+            [(3, 0), (3, 1), (3, 2), (3, 3), (3, 4)],
+            [(0, 6), (1, 6), (2, 6), (3, 6), (4, 6)],
+            [(2, 2), (3, 3), (4, 4), (5, 5), (6, 6)],
+            [(0, 8), (1, 7), (2, 6), (3, 5), (4, 4)],
+        ];
+        for line in lines {
+            // Complete the row in the middle, so that both directions are counted.
+            let mut order = line.to_vec();
+            order.swap(2, 4);
+            let mut game = Gomoku::new(9);
+            for (i, &action) in order.iter().enumerate() {
+                assert_eq!(game.check_winner(), None);
+                let reward = game.step(action).unwrap();
+                assert_eq!(reward, if i == 4 { 1. } else { 0. });
+                if i < 4 {
+                    // White answers far from the line.
+                    game.step((8, i)).unwrap();
+                }
+            }
+            assert_eq!(game.check_winner(), Some(Player::Black));
+            assert!(game.done());
+            assert!(game.get_available_moves().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_no_wrap_around() {
+        let mut game = Gomoku::new(9);
+        // Black at the end of row 0 and the start of row 1, adjacent in row-major order.
+        play(
+            &mut game,
+            &[
+                (0, 6),
+                (5, 0),
+                (0, 7),
+                (5, 2),
+                (0, 8),
+                (5, 4),
+                (1, 0),
+                (5, 6),
+            ],
+        );
+        game.step((1, 1)).unwrap();
+        assert_eq!(game.check_winner(), None);
+    }
+
+    #[test]
+    fn test_draw() {
+        // Too small a board for five in a row.
+        let mut game = Gomoku::new(2);
+        play(&mut game, &[(0, 0), (0, 1), (1, 0), (1, 1)]);
+        assert!(game.done());
+        assert_eq!(game.check_winner(), None);
+        assert_eq!(game.returns(), vec![0., 0.]);
+    }
+
+    #[test]
+    fn test_notation() {
+        let mut game = Gomoku::new(9);
+        play(&mut game, &[(4, 4), (0, 8)]);
+        let notation = game.to_notation().unwrap();
+        assert_eq!(notation, "8O/9/9/9/4X4/9/9/9/9 X");
+        let parsed = Gomoku::from_notation(&notation).unwrap();
+        assert_eq!(parsed.to_string(), game.to_string());
+        assert_eq!(parsed.get_available_moves().len(), 79);
+
+        let parsed = Gomoku::from_notation("XXXXX4/9/9/9/9/9/9/9/OOOO5 O").unwrap();
+        assert_eq!(parsed.check_winner(), Some(Player::Black));
+        assert!(Gomoku::from_notation("XXXXX4/9/9/9/9/9/9/9/OOOOO4 O").is_err());
+        // The board size comes from the number of rows.
+        assert_eq!(Gomoku::from_notation("5/5/5/5/5 X").unwrap().size(), 5);
+        assert!(Gomoku::from_notation("5/5/5/5 X").is_err());
+    }
+
+    #[test]
+    fn test_observation() {
+        let mut game = Gomoku::new(5);
+        play(&mut game, &[(0, 1), (4, 4)]);
+        let observation = game.observation();
+        assert_eq!(
+            observation.len(),
+            game.observation_shape().iter().product::<usize>()
+        );
+        assert_eq!(observation[1], 1.);
+        assert_eq!(observation[25 + 24], 1.);
+        assert_eq!(observation[50..], [1.; 25]);
+
+        game.step((2, 2)).unwrap();
+        let canonical = game.canonical_observation();
+        assert_eq!(canonical[24], 1.);
+        assert_eq!(canonical[25 + 1], 1.);
+        assert_eq!(canonical[50..], [0.; 25]);
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut game = Gomoku::new(9);
+        play(
+            &mut game,
+            &[
+                (4, 0),
+                (0, 0),
+                (4, 1),
+                (0, 1),
+                (4, 2),
+                (0, 2),
+                (4, 3),
+                (0, 3),
+            ],
+        );
+        let before = game.clone();
+        let (reward, token) = game.step_with_undo((4, 4)).unwrap();
+        assert_eq!(reward, 1.);
+        game.undo((4, 4), token);
+        assert_eq!(game.board, before.board);
+        assert_eq!(game.check_winner(), None);
+        assert_eq!(game.current_player(), Player::Black);
+        assert_eq!(game.state_hash(), before.state_hash());
+    }
+
+    #[test]
+    fn test_search_completes_five() {
+        let mut game = Gomoku::new(7);
+        play(
+            &mut game,
+            &[
+                (3, 1),
+                (0, 0),
+                (3, 2),
+                (0, 6),
+                (3, 3),
+                (6, 0),
+                (3, 4),
+                (6, 6),
+            ],
+        );
+        let action = Mcts::<Gomoku>::new(2000).search(&game).action;
+        assert!(action == (3, 0) || action == (3, 5), "{:?}", action);
+    }
+}
@@ -0,0 +1,324 @@
+//! A small gridworld in the style of FrozenLake: the agent walks from its start to a goal
+//! around walls, paying a penalty for every step. On slippery ground a move may go sideways
+//! instead, which is played as a chance outcome.
+
+use std::fmt;
+
+use crate::{
+    error::{GameError, ParseError},
+    game::Game,
+};
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+];
+
+impl Direction {
+    fn perpendicular(self) -> [Direction; 2] {
+        match self {
+            Direction::Up | Direction::Down => [Direction::Left, Direction::Right],
+            Direction::Left | Direction::Right => [Direction::Up, Direction::Down],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GridworldConfig {
+    /// The reward of every step, usually negative.
+    pub step_penalty: f32,
+    pub goal_reward: f32,
+    /// The probability that a move goes to either side of the intended direction instead.
+    pub slip_probability: f32,
+    /// The episode is truncated after this many steps.
+    pub max_steps: usize,
+}
+
+impl Default for GridworldConfig {
+    fn default() -> Self {
+        Self {
+            step_penalty: -0.01,
+            goal_reward: 1.,
+            slip_probability: 0.,
+            max_steps: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Gridworld {
+    config: GridworldConfig,
+    width: usize,
+    height: usize,
+    walls: Vec<bool>,
+    goal: usize,
+    position: usize,
+    steps: usize,
+    /// The direction chosen by the agent, waiting for chance to decide where it goes.
+    intended: Option<Direction>,
+}
+
+impl Gridworld {
+    /// A gridworld from rows of `S` (start), `G` (goal), `#` (wall) and `.` (floor).
+    pub fn from_map(map: &[&str], config: GridworldConfig) -> Result<Self, ParseError> {
+        let height = map.len();
+        let width = map.first().map_or(0, |row| row.chars().count());
+        let (mut start, mut goal) = (None, None);
+        let mut walls = Vec::with_capacity(width * height);
+        for row in map {
+            if row.chars().count() != width {
+                return Err(ParseError::Invalid(format!(
+                    "row `{}` doesn't have {} cells",
+                    row, width
+                )));
+            }
+            for c in row.chars() {
+                let cell = walls.len();
+                match c {
+                    'S' => start = Some(cell),
+                    'G' => goal = Some(cell),
+                    '#' | '.' => {}
+                    _ => return Err(ParseError::Invalid(format!("invalid cell `{}`", c))),
+                }
+                walls.push(c == '#');
+            }
+        }
+        let (Some(start), Some(goal)) = (start, goal) else {
+            return Err(ParseError::Invalid(
+                "the map needs a start and a goal".to_string(),
+            ));
+        };
+        Ok(Self {
+            config,
+            width,
+            height,
+            walls,
+            goal,
+            position: start,
+            steps: 0,
+            intended: None,
+        })
+    }
+
+    /// The 4x4 map of FrozenLake, with walls for holes.
+    pub fn four_by_four(config: GridworldConfig) -> Self {
+        Self::from_map(&["S...", ".#.#", "...#", "#..G"], config).unwrap()
+    }
+
+    pub fn position(&self) -> (usize, usize) {
+        (self.position / self.width, self.position % self.width)
+    }
+
+    /// Walk one cell in `direction`, staying put at walls and edges.
+    fn walk(&mut self, direction: Direction) -> f32 {
+        let (row, col) = self.position();
+        let target = match direction {
+            Direction::Up if row > 0 => Some(self.position - self.width),
+            Direction::Down if row + 1 < self.height => Some(self.position + self.width),
+            Direction::Left if col > 0 => Some(self.position - 1),
+            Direction::Right if col + 1 < self.width => Some(self.position + 1),
+            _ => None,
+        };
+        if let Some(target) = target.filter(|&target| !self.walls[target]) {
+            self.position = target;
+        }
+        self.steps += 1;
+        if self.position == self.goal {
+            self.config.step_penalty + self.config.goal_reward
+        } else {
+            self.config.step_penalty
+        }
+    }
+}
+
+impl Game for Gridworld {
+    type Action = Direction;
+
+    type Player = ();
+
+    /// Directions chosen on slippery ground are walked once chance decides where they go.
+    fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+        if self.done() {
+            return Err(GameError::GameAlreadyOver);
+        }
+        if self.intended.take().is_some() || self.config.slip_probability == 0. {
+            Ok(self.walk(action))
+        } else {
+            self.intended = Some(action);
+            Ok(0.)
+        }
+    }
+
+    fn get_available_moves(&self) -> Vec<Self::Action> {
+        if self.done() || self.intended.is_some() {
+            return vec![];
+        }
+        DIRECTIONS.to_vec()
+    }
+
+    fn action_space_size(&self) -> usize {
+        DIRECTIONS.len()
+    }
+
+    fn action_to_index(&self, action: &Self::Action) -> usize {
+        *action as usize
+    }
+
+    fn index_to_action(&self, index: usize) -> Self::Action {
+        DIRECTIONS[index]
+    }
+
+    fn current_player(&self) -> Self::Player {}
+
+    fn player_index(&self, _player: &Self::Player) -> usize {
+        0
+    }
+
+    fn num_players(&self) -> usize {
+        1
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        vec![3, self.height, self.width]
+    }
+
+    /// Three planes: the agent, the walls and the goal.
+    fn observation(&self) -> Vec<f32> {
+        let area = self.walls.len();
+        let mut observation = vec![0.; 3 * area];
+        observation[self.position] = 1.;
+        for (cell, &wall) in self.walls.iter().enumerate() {
+            if wall {
+                observation[area + cell] = 1.;
+            }
+        }
+        observation[2 * area + self.goal] = 1.;
+        observation
+    }
+
+    fn terminated(&self) -> bool {
+        self.position == self.goal
+    }
+
+    fn truncated(&self) -> bool {
+        !self.terminated() && self.steps >= self.config.max_steps
+    }
+
+    fn check_winner(&self) -> Option<Self::Player> {
+        None
+    }
+
+    fn chance_outcomes(&self) -> Vec<(Self::Action, f32)> {
+        let Some(intended) = self.intended else {
+            return vec![];
+        };
+        let slip = self.config.slip_probability;
+        let [left, right] = intended.perpendicular();
+        vec![(intended, 1. - slip), (left, slip / 2.), (right, slip / 2.)]
+    }
+}
+
+impl fmt::Display for Gridworld {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let cell = row * self.width + col;
+                let symbol = if cell == self.position {
+                    "A"
+                } else if cell == self.goal {
+                    "G"
+                } else if self.walls[cell] {
+                    "#"
+                } else {
+                    "."
+                };
+                write!(f, "{} ", symbol)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::Mcts;
+
+    #[test]
+    fn test_walk() {
+        let mut game = Gridworld::from_map(&["S#G", "..."], GridworldConfig::default()).unwrap();
+        assert_eq!(game.step(Direction::Right).unwrap(), -0.01);
+        assert_eq!(game.position(), (0, 0));
+        game.step(Direction::Up).unwrap();
+        assert_eq!(game.position(), (0, 0));
+        for direction in [Direction::Down, Direction::Right, Direction::Right] {
+            assert!(!game.done());
+            game.step(direction).unwrap();
+        }
+        assert!((game.step(Direction::Up).unwrap() - 0.99).abs() < 1e-6);
+        assert!(game.terminated());
+        assert!(game.step(Direction::Up).is_err());
+
+        assert!(Gridworld::from_map(&["S.", "."], GridworldConfig::default()).is_err());
+        assert!(Gridworld::from_map(&["S.."], GridworldConfig::default()).is_err());
+    }
+
+    #[test]
+    fn test_truncation() {
+        let config = GridworldConfig {
+            max_steps: 3,
+            ..Default::default()
+        };
+        let mut game = Gridworld::four_by_four(config);
+        for _ in 0..3 {
+            assert!(!game.done());
+            game.step(Direction::Up).unwrap();
+        }
+        assert!(game.truncated());
+        assert!(!game.terminated());
+        assert!(game.get_available_moves().is_empty());
+    }
+
+    #[test]
+    fn test_slippery() {
+        let config = GridworldConfig {
+            slip_probability: 0.4,
+            ..Default::default()
+        };
+        let mut game = Gridworld::four_by_four(config);
+        assert_eq!(game.step(Direction::Down).unwrap(), 0.);
+        assert!(game.get_available_moves().is_empty());
+        let outcomes = game.chance_outcomes();
+        assert_eq!(outcomes.len(), 3);
+        assert!((outcomes.iter().map(|(_, p)| p).sum::<f32>() - 1.).abs() < 1e-6);
+        // Chance sends the agent right instead.
+        game.step(Direction::Right).unwrap();
+        assert_eq!(game.position(), (0, 1));
+        assert!(game.chance_outcomes().is_empty());
+    }
+
+    #[test]
+    fn test_search_finds_goal() {
+        let game = Gridworld::from_map(
+            &["S..G"],
+            GridworldConfig {
+                max_steps: 4,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mcts = Mcts::<Gridworld>::new(1000);
+        assert_eq!(mcts.search(&game).action, Direction::Right);
+    }
+}
@@ -0,0 +1,325 @@
+//! Gymnasium environments with a discrete action space, behind the `gym` feature, run in the
+//! Python interpreter that pyo3 embeds, which needs `gymnasium` installed. Observations are
+//! flattened to floats in row-major order, and actions are counted from the action space's
+//! `start`. Clones share the environment until one of them steps, which first copies it with
+//! `copy.deepcopy`, so searching needs an environment that can be deep-copied.
+
+use pyo3::{
+    prelude::*,
+    types::{IntoPyDict, PyList, PyTuple},
+};
+use std::{fmt, sync::Arc};
+
+use crate::{
+    error::{GameError, ParseError, Result},
+    game::Game,
+};
+
+#[derive(Debug, Clone)]
+pub struct GymConfig {
+    /// The Gymnasium id of the environment, as given to `gymnasium.make`.
+    pub env_id: String,
+    /// The seed the environment is reset with.
+    pub seed: u64,
+}
+
+impl Default for GymConfig {
+    fn default() -> Self {
+        Self {
+            env_id: "CartPole-v1".to_string(),
+            seed: 0,
+        }
+    }
+}
+
+/// Cheap to clone: clones share the environment until one of them steps.
+#[derive(Clone)]
+pub struct GymGame {
+    env_id: String,
+    /// The environment in the state of the game.
+    env: Arc<Py<PyAny>>,
+    num_actions: usize,
+    /// The action of the environment that index 0 stands for.
+    start: i64,
+    observation_shape: Vec<usize>,
+    observation: Vec<f32>,
+    /// The number of steps since the reset.
+    steps: usize,
+    terminated: bool,
+    truncated: bool,
+}
+
+/// Make the environment and reset it with the configured seed.
+pub fn make(config: &GymConfig) -> Result<GymGame> {
+    Python::initialize();
+    Python::attach(|py| {
+        let env = py
+            .import("gymnasium")?
+            .call_method1("make", (&config.env_id,))?;
+        let space = env.getattr("action_space")?;
+        if !space.hasattr("n")? {
+            return Err(ParseError::Invalid(format!(
+                "only discrete action spaces are supported, found {}",
+                space
+            ))
+            .into());
+        }
+        let num_actions = space.getattr("n")?.extract()?;
+        let start = match space.getattr("start") {
+            Ok(start) => start.extract()?,
+            Err(_) => 0,
+        };
+        let observation_shape: Vec<usize> = env
+            .getattr("observation_space")?
+            .getattr("shape")?
+            .extract()?;
+        let (observation, _info): (Bound<PyAny>, Bound<PyAny>) = env
+            .call_method(
+                "reset",
+                (),
+                Some(&[("seed", config.seed)].into_py_dict(py)?),
+            )?
+            .extract()?;
+        let observation = read_observation(&observation, &observation_shape)?;
+        Ok(GymGame {
+            env_id: config.env_id.clone(),
+            env: Arc::new(env.unbind()),
+            num_actions,
+            start,
+            observation_shape,
+            observation,
+            steps: 0,
+            terminated: false,
+            truncated: false,
+        })
+    })
+}
+
+/// `observation` flattened, checked to fill `shape`.
+fn read_observation(observation: &Bound<PyAny>, shape: &[usize]) -> Result<Vec<f32>> {
+    let mut values = vec![];
+    flatten(observation, &mut values)?;
+    let size = shape
+        .iter()
+        .try_fold(1usize, |size, &dim| size.checked_mul(dim));
+    if size != Some(values.len()) {
+        return Err(ParseError::Invalid(format!(
+            "the observation has {} values, not the {:?} of the observation space",
+            values.len(),
+            shape
+        ))
+        .into());
+    }
+    Ok(values)
+}
+
+/// Push the numbers of a (possibly nested) observation, like an array or a list, to `values`.
+fn flatten(value: &Bound<PyAny>, values: &mut Vec<f32>) -> PyResult<()> {
+    if value.hasattr("tolist")? {
+        return flatten(&value.call_method0("tolist")?, values);
+    }
+    if value.is_instance_of::<PyList>() || value.is_instance_of::<PyTuple>() {
+        for item in value.try_iter()? {
+            flatten(&item?, values)?;
+        }
+        return Ok(());
+    }
+    values.push(value.extract::<f64>()? as f32);
+    Ok(())
+}
+
+impl GymGame {
+    /// Play `action` in the environment, copied first if a clone shares it.
+    fn send_step(&mut self, action: usize) -> Result<f32> {
+        Python::attach(|py| {
+            let env = if Arc::strong_count(&self.env) == 1 {
+                self.env.bind(py).clone()
+            } else {
+                let env = py
+                    .import("copy")?
+                    .call_method1("deepcopy", (self.env.bind(py),))?;
+                self.env = Arc::new(env.clone().unbind());
+                env
+            };
+            let (observation, reward, terminated, truncated, _info): (
+                Bound<PyAny>,
+                f32,
+                Bound<PyAny>,
+                Bound<PyAny>,
+                Bound<PyAny>,
+            ) = env
+                .call_method1("step", (self.start + action as i64,))?
+                .extract()?;
+            self.observation = read_observation(&observation, &self.observation_shape)?;
+            self.terminated = terminated.is_truthy()?;
+            self.truncated = truncated.is_truthy()?;
+            self.steps += 1;
+            Ok(reward)
+        })
+    }
+}
+
+impl Game for GymGame {
+    type Action = usize;
+
+    type Player = usize;
+
+    fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+        if self.done() {
+            return Err(GameError::GameAlreadyOver);
+        }
+        if action >= self.num_actions {
+            return Err(GameError::illegal_move(&action, "out of the action space"));
+        }
+        self.send_step(action)
+            .map_err(|e| GameError::Environment(e.into()))
+    }
+
+    fn get_available_moves(&self) -> Vec<Self::Action> {
+        if self.done() {
+            return vec![];
+        }
+        (0..self.num_actions).collect()
+    }
+
+    fn action_space_size(&self) -> usize {
+        self.num_actions
+    }
+
+    fn action_to_index(&self, action: &Self::Action) -> usize {
+        *action
+    }
+
+    fn index_to_action(&self, index: usize) -> Self::Action {
+        index
+    }
+
+    fn current_player(&self) -> Self::Player {
+        0
+    }
+
+    fn player_index(&self, player: &Self::Player) -> usize {
+        *player
+    }
+
+    fn num_players(&self) -> usize {
+        1
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        self.observation_shape.clone()
+    }
+
+    fn observation(&self) -> Vec<f32> {
+        self.observation.clone()
+    }
+
+    fn terminated(&self) -> bool {
+        self.terminated
+    }
+
+    fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn check_winner(&self) -> Option<Self::Player> {
+        None
+    }
+}
+
+impl fmt::Display for GymGame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} step {}: {:?}",
+            self.env_id, self.steps, self.observation
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A stand-in for the `gymnasium` package: a counter that action 1 increments, rewarded
+    /// with the action and terminated at 3.
+    const FAKE_GYMNASIUM: &std::ffi::CStr = cr#"
+class Discrete:
+    def __init__(self, n, start=0):
+        self.n = n
+        self.start = start
+
+class Box:
+    def __init__(self, shape):
+        self.shape = shape
+
+class Counter:
+    action_space = Discrete(2, start=10)
+    observation_space = Box((1, 2))
+
+    def reset(self, seed=None):
+        self.count = seed
+        return [[self.count, -self.count]], {}
+
+    def step(self, action):
+        if action not in (10, 11):
+            raise ValueError("bad action %d" % action)
+        self.count += action - 10
+        return [[self.count, -self.count]], action - 10, self.count >= 3, False, {}
+
+    def close(self):
+        pass
+
+def make(env_id):
+    if env_id != "Counter-v0":
+        raise KeyError(env_id)
+    return Counter()
+"#;
+
+    /// A config of the fake `gymnasium`, which replaces any installed one.
+    fn fake_config(env_id: &str) -> GymConfig {
+        Python::initialize();
+        Python::attach(|py| {
+            let module =
+                PyModule::from_code(py, FAKE_GYMNASIUM, c"gymnasium.py", c"gymnasium").unwrap();
+            py.import("sys")
+                .unwrap()
+                .getattr("modules")
+                .unwrap()
+                .set_item("gymnasium", module)
+                .unwrap();
+        });
+        GymConfig {
+            env_id: env_id.to_string(),
+            seed: 1,
+        }
+    }
+
+    #[test]
+    fn test_episode() {
+        let mut game = make(&fake_config("Counter-v0")).unwrap();
+        assert_eq!(game.action_space_size(), 2);
+        assert_eq!(game.observation_shape(), vec![1, 2]);
+        assert_eq!(game.observation(), vec![1., -1.]);
+
+        assert_eq!(game.step(1).unwrap(), 1.);
+        assert_eq!(game.step(0).unwrap(), 0.);
+        assert!(game.step(2).is_err());
+        let mut clone = game.clone();
+        assert_eq!(clone.observation(), vec![2., -2.]);
+
+        game.step(1).unwrap();
+        assert!(game.terminated());
+        assert!(game.get_available_moves().is_empty());
+        assert!(!clone.done());
+        assert_eq!(clone.step(0).unwrap(), 0.);
+        assert_eq!(clone.observation(), vec![2., -2.]);
+        assert_eq!(clone.step(1).unwrap(), 1.);
+        assert!(clone.terminated());
+    }
+
+    #[test]
+    fn test_unknown_env() {
+        assert!(make(&fake_config("Missing-v0")).is_err());
+    }
+}
@@ -0,0 +1,339 @@
+//! Hex on an NxN rhombus of hexagons. Black tries to connect the top and bottom edges, White
+//! the left and right edges. A full board always has exactly one winner, so there are no
+//! draws.
+
+use std::fmt;
+
+use crate::{error::GameError, game::Game};
+
+/// The standard board is 11x11.
+pub const DEFAULT_SIZE: usize = 11;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Player {
+    Black,
+    White,
+}
+
+impl Player {
+    fn opponent(self) -> Self {
+        match self {
+            Player::Black => Player::White,
+            Player::White => Player::Black,
+        }
+    }
+}
+
+/// Union-find over the cells and four virtual cells, one per edge, so that a player has won
+/// when their two edges are in the same set.
+#[derive(Debug, Clone)]
+struct DisjointSets {
+    parent: Vec<usize>,
+}
+
+impl DisjointSets {
+    fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+        }
+    }
+
+    fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            // Path halving.
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parent[a] = b;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Hex {
+    size: usize,
+    /// The stones in row-major order.
+    board: Vec<Option<Player>>,
+    current_player: Player,
+    sets: DisjointSets,
+    winner: Option<Player>,
+}
+
+impl Hex {
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "the board must have at least one cell");
+        let area = size * size;
+        Self {
+            size,
+            board: vec![None; area],
+            current_player: Player::Black,
+            sets: DisjointSets::new(area + 4),
+            winner: None,
+        }
+    }
+
+    /// The virtual cells of Black's top and bottom edges and White's left and right edges.
+    fn edges(&self, player: Player) -> (usize, usize) {
+        let area = self.board.len();
+        match player {
+            Player::Black => (area, area + 1),
+            Player::White => (area + 2, area + 3),
+        }
+    }
+
+    fn neighbors(&self, row: usize, col: usize) -> impl Iterator<Item = (usize, usize)> {
+        let size = self.size as isize;
+        let (row, col) = (row as isize, col as isize);
+        [(-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0)]
+            .into_iter()
+            .map(move |(dr, dc)| (row + dr, col + dc))
+            .filter(move |&(r, c)| (0..size).contains(&r) && (0..size).contains(&c))
+            .map(|(r, c)| (r as usize, c as usize))
+    }
+}
+
+impl Default for Hex {
+    fn default() -> Self {
+        Self::new(DEFAULT_SIZE)
+    }
+}
+
+impl Game for Hex {
+    type Action = (usize, usize);
+
+    type Player = Player;
+
+    fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+        let (row, col) = action;
+        if row >= self.size || col >= self.size {
+            return Err(GameError::illegal_move(&action, "off the board"));
+        }
+        if self.winner.is_some() {
+            return Err(GameError::GameAlreadyOver);
+        }
+        let cell = row * self.size + col;
+        if self.board[cell].is_some() {
+            return Err(GameError::illegal_move(
+                &action,
+                "the spot is already filled",
+            ));
+        }
+        let player = self.current_player;
+        self.board[cell] = Some(player);
+
+        let neighbors: Vec<_> = self.neighbors(row, col).collect();
+        for (r, c) in neighbors {
+            let neighbor = r * self.size + c;
+            if self.board[neighbor] == Some(player) {
+                self.sets.union(cell, neighbor);
+            }
+        }
+        let (first, second) = self.edges(player);
+        let line = match player {
+            Player::Black => row,
+            Player::White => col,
+        };
+        if line == 0 {
+            self.sets.union(cell, first);
+        }
+        if line == self.size - 1 {
+            self.sets.union(cell, second);
+        }
+
+        self.current_player = player.opponent();
+        if self.sets.find(first) == self.sets.find(second) {
+            self.winner = Some(player);
+            Ok(1.)
+        } else {
+            Ok(0.)
+        }
+    }
+
+    fn get_available_moves(&self) -> Vec<Self::Action> {
+        let mut moves = vec![];
+        self.get_available_moves_into(&mut moves);
+        moves
+    }
+
+    fn get_available_moves_into(&self, moves: &mut Vec<Self::Action>) {
+        moves.clear();
+        if self.winner.is_none() {
+            moves.extend(
+                (0..self.board.len())
+                    .filter(|&i| self.board[i].is_none())
+                    .map(|i| self.index_to_action(i)),
+            );
+        }
+    }
+
+    fn action_space_size(&self) -> usize {
+        self.size * self.size
+    }
+
+    fn action_to_index(&self, action: &Self::Action) -> usize {
+        let (row, col) = *action;
+        row * self.size + col
+    }
+
+    fn index_to_action(&self, index: usize) -> Self::Action {
+        (index / self.size, index % self.size)
+    }
+
+    fn current_player(&self) -> Self::Player {
+        self.current_player
+    }
+
+    fn player_index(&self, player: &Self::Player) -> usize {
+        *player as usize
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        vec![3, self.size, self.size]
+    }
+
+    /// Three planes: Black's stones, White's stones, and a plane of ones if Black is to play.
+    fn observation(&self) -> Vec<f32> {
+        let area = self.board.len();
+        let mut observation = vec![0.; 3 * area];
+        for (i, stone) in self.board.iter().enumerate() {
+            if let Some(player) = stone {
+                observation[*player as usize * area + i] = 1.;
+            }
+        }
+        if self.current_player == Player::Black {
+            observation[2 * area..].fill(1.);
+        }
+        observation
+    }
+
+    /// The board from the point of view of the player to play connecting top and bottom:
+    /// when White is to play, the board is transposed and the colors swapped.
+    fn canonical_observation(&self) -> Vec<f32> {
+        let observation = self.observation();
+        if self.current_player == Player::Black {
+            return observation;
+        }
+        let area = self.board.len();
+        let mut canonical = vec![0.; 3 * area];
+        for row in 0..self.size {
+            for col in 0..self.size {
+                let (cell, transposed) = (row * self.size + col, col * self.size + row);
+                canonical[transposed] = observation[area + cell];
+                canonical[area + transposed] = observation[cell];
+            }
+        }
+        canonical
+    }
+
+    /// A full board always has a winner.
+    fn terminated(&self) -> bool {
+        self.winner.is_some()
+    }
+
+    fn check_winner(&self) -> Option<Self::Player> {
+        self.winner
+    }
+}
+
+impl fmt::Display for Hex {
+    /// Each row is shifted right by one more space, so that the board is drawn as a rhombus.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, row) in self.board.chunks(self.size).enumerate() {
+            write!(f, "{}", " ".repeat(i))?;
+            for stone in row {
+                let symbol = match stone {
+                    None => ".",
+                    Some(Player::Black) => "X",
+                    Some(Player::White) => "O",
+                };
+                write!(f, "{} ", symbol)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::Mcts;
+    use rand::seq::SliceRandom;
+
+    #[test]
+    fn test_connection() {
+        let mut game = Hex::new(3);
+        // Black zigzags from top to bottom along the hexagonal neighbors (r, c) - (r+1, c-1).
+        for (black, white) in [((0, 2), (0, 0)), ((1, 1), (1, 0))] {
+            assert_eq!(game.step(black).unwrap(), 0.);
+            assert_eq!(game.step(white).unwrap(), 0.);
+        }
+        assert_eq!(game.check_winner(), None);
+        assert_eq!(game.step((2, 0)).unwrap(), 1.);
+        assert_eq!(game.check_winner(), Some(Player::Black));
+        assert!(game.done());
+        assert!(game.get_available_moves().is_empty());
+        assert!(game.step((2, 2)).is_err());
+    }
+
+    #[test]
+    fn test_no_connection_through_non_neighbors() {
+        let mut game = Hex::new(3);
+        // (0, 0) and (1, 1) touch only at a corner, which doesn't connect in Hex.
+        for action in [(0, 0), (1, 0), (1, 1), (0, 1), (2, 1), (2, 2)] {
+            game.step(action).unwrap();
+        }
+        assert_eq!(game.check_winner(), None);
+    }
+
+    #[test]
+    fn test_no_draws() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let mut game = Hex::new(5);
+            let mut moves = game.get_available_moves();
+            moves.shuffle(&mut rng);
+            for action in moves {
+                if game.done() {
+                    break;
+                }
+                game.step(action).unwrap();
+            }
+            assert!(game.check_winner().is_some());
+            assert_eq!(game.returns().iter().sum::<f32>(), 0.);
+        }
+    }
+
+    #[test]
+    fn test_canonical_observation() {
+        let mut game = Hex::new(3);
+        assert_eq!(game.canonical_observation(), game.observation());
+        game.step((0, 1)).unwrap();
+        let observation = game.canonical_observation();
+        assert_eq!(
+            observation.len(),
+            game.observation_shape().iter().product::<usize>()
+        );
+        // White to play: Black's stone is transposed into the opponent's plane.
+        assert_eq!(observation[..9], [0.; 9]);
+        assert_eq!(observation[9 + 3], 1.);
+        assert_eq!(observation[18..], [0.; 9]);
+    }
+
+    #[test]
+    fn test_search_completes_connection() {
+        let mut game = Hex::new(4);
+        for action in [(0, 1), (0, 0), (1, 1), (1, 0), (2, 1), (2, 0)] {
+            game.step(action).unwrap();
+        }
+        let mcts = Mcts::<Hex>::new(1000);
+        let action = mcts.search(&game).action;
+        let mut next = game.clone();
+        next.step(action).unwrap();
+        assert_eq!(next.check_winner(), Some(Player::Black), "{:?}", action);
+    }
+}
@@ -0,0 +1,230 @@
+//! Implementations of [`crate::game::Game`].
+
+#[cfg(feature = "atari")]
+pub mod atari;
+pub mod cart_pole;
+pub mod checkers;
+pub mod chess;
+pub mod connect_four;
+#[cfg(feature = "serde")]
+pub mod external;
+pub mod go;
+pub mod gomoku;
+pub mod gridworld;
+#[cfg(feature = "gym")]
+pub mod gym;
+pub mod hex;
+pub mod nim;
+pub mod othello;
+pub mod tic_tac_toe;
+pub mod twenty_forty_eight;
+
+/// Properties every game must have, checked with proptest along games whose moves and chance
+/// outcomes are picked by a list of choices, which shrinks to the shortest list that still
+/// fails. The Atari, Gym and external games are left out, since they need emulators and
+/// processes.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use proptest::{
+        collection::vec,
+        prelude::any,
+        test_runner::{Config, RngSeed, TestRunner},
+    };
+    use std::{cell::RefCell, collections::HashMap};
+
+    /// Play `cases` games from `new_game`, checking every state and step.
+    fn check_properties<G: Game>(new_game: impl Fn() -> G, cases: u32) {
+        let mut runner = TestRunner::new(Config {
+            cases,
+            failure_persistence: None,
+            // The same games every run.
+            rng_seed: RngSeed::Fixed(0),
+            ..Config::default()
+        });
+        // Equal states, by their display, observation and player to move, and their hashes.
+        let hashes = RefCell::new(HashMap::new());
+        // Past the end of the choices, the first move is played.
+        let choices = vec(any::<usize>(), 0..200);
+        let result = runner.run(&choices, |choices| {
+            let mut game = new_game();
+            let mut actions = vec![];
+            loop {
+                let context = format!("after {:?}", actions);
+                check_state(&game, &mut hashes.borrow_mut(), &context);
+                if game.done() {
+                    break;
+                }
+                assert!(actions.len() < 100_000, "{}: the game doesn't end", context);
+                let choice = choices.get(actions.len()).copied().unwrap_or(0);
+                let outcomes = game.chance_outcomes();
+                let action = if outcomes.is_empty() {
+                    let moves = game.get_available_moves();
+                    moves[choice % moves.len()].clone()
+                } else {
+                    outcomes[choice % outcomes.len()].0.clone()
+                };
+                // A clone steps exactly like the original.
+                let mut copy = game.clone();
+                let reward = game.step(action.clone()).unwrap();
+                assert_eq!(copy.step(action.clone()).unwrap(), reward, "{}", context);
+                assert_eq!(copy.to_string(), game.to_string(), "{}", context);
+                assert_eq!(copy.observation(), game.observation(), "{}", context);
+                assert_eq!(copy.state_hash(), game.state_hash(), "{}", context);
+                assert_eq!(copy.done(), game.done(), "{}", context);
+                actions.push(action);
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            panic!("{}", e);
+        }
+    }
+
+    fn check_state<G: Game>(
+        game: &G,
+        hashes: &mut HashMap<(String, Vec<u32>, usize), u64>,
+        context: &str,
+    ) {
+        let moves = game.get_available_moves();
+        // A buffer holding anything gets the same moves.
+        let mut buffer = moves.iter().rev().cloned().collect();
+        game.get_available_moves_into(&mut buffer);
+        assert_eq!(buffer, moves, "{}", context);
+        let size = game.action_space_size();
+        let shape = game.observation_shape();
+        let observation = game.observation();
+        assert_eq!(
+            observation.len(),
+            shape.iter().product::<usize>(),
+            "{}",
+            context
+        );
+        assert_eq!(
+            game.canonical_observation().len(),
+            observation.len(),
+            "{}",
+            context
+        );
+        if game.check_winner().is_some() {
+            assert!(game.done(), "{}: a winner but not over", context);
+        }
+        if game.done() {
+            assert!(moves.is_empty(), "{}: moves after the end", context);
+            assert_eq!(game.returns().len(), game.num_players(), "{}", context);
+        } else if game.chance_outcomes().is_empty() {
+            assert!(!moves.is_empty(), "{}: no moves before the end", context);
+        }
+        let mask = game.legal_action_mask();
+        assert_eq!(mask.len(), size, "{}", context);
+        assert_eq!(
+            mask.iter().filter(|&&legal| legal).count(),
+            moves.len(),
+            "{}",
+            context
+        );
+        for action in &moves {
+            let index = game.action_to_index(action);
+            assert!(
+                index < size,
+                "{}: {:?} out of the action space",
+                context,
+                action
+            );
+            assert_eq!(&game.index_to_action(index), action, "{}", context);
+        }
+        // Stepping with any illegal action is an error, also once the game is over.
+        if game.chance_outcomes().is_empty() {
+            for index in (0..size).filter(|&index| !mask[index]) {
+                let action = game.index_to_action(index);
+                assert!(
+                    game.clone().step(action.clone()).is_err(),
+                    "{}: illegal {:?} was played",
+                    context,
+                    action
+                );
+            }
+        }
+        let player = game.player_index(&game.current_player());
+        let key = (
+            game.to_string(),
+            observation.iter().map(|x| x.to_bits()).collect(),
+            player,
+        );
+        let hash = *hashes.entry(key).or_insert(game.state_hash());
+        assert_eq!(
+            game.state_hash(),
+            hash,
+            "{}: equal states hash differently",
+            context
+        );
+    }
+
+    #[test]
+    fn test_tic_tac_toe() {
+        check_properties(tic_tac_toe::TicTacToe::new, 50);
+    }
+
+    #[test]
+    fn test_connect_four() {
+        check_properties(connect_four::ConnectFour::new, 30);
+    }
+
+    #[test]
+    fn test_gomoku() {
+        check_properties(|| gomoku::Gomoku::new(7), 10);
+    }
+
+    #[test]
+    fn test_othello() {
+        check_properties(othello::Othello::new, 10);
+    }
+
+    #[test]
+    fn test_hex() {
+        check_properties(|| hex::Hex::new(5), 20);
+    }
+
+    #[test]
+    fn test_checkers() {
+        // Every one of the 1024 actions is tried in every state, so few games.
+        check_properties(checkers::Checkers::new, 3);
+    }
+
+    #[cfg(feature = "chess")]
+    #[test]
+    fn test_chess() {
+        // Every one of the 4672 actions is tried in every state, so a single game.
+        check_properties(chess::Chess::new, 1);
+    }
+
+    #[test]
+    fn test_go() {
+        check_properties(|| go::Go::new(5), 10);
+    }
+
+    #[test]
+    fn test_nim() {
+        check_properties(|| nim::Nim::new(vec![3, 4, 5]), 50);
+    }
+
+    #[test]
+    fn test_twenty_forty_eight() {
+        check_properties(twenty_forty_eight::TwentyFortyEight::new, 3);
+    }
+
+    #[test]
+    fn test_cart_pole() {
+        check_properties(cart_pole::CartPole::new, 20);
+    }
+
+    #[test]
+    fn test_gridworld() {
+        let slippery = gridworld::GridworldConfig {
+            slip_probability: 0.2,
+            ..Default::default()
+        };
+        check_properties(|| gridworld::Gridworld::four_by_four(slippery.clone()), 20);
+    }
+}
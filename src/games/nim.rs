@@ -0,0 +1,254 @@
+//! Nim: players take turns removing any number of objects from a single heap. In normal play
+//! the player taking the last object wins, in misère play they lose.
+//!
+//! Nim is solved, with [`Nim::is_winning`] telling in closed form whether the player to play
+//! wins with perfect play, which makes it an objective check of search strength.
+
+use std::fmt;
+
+use crate::{error::GameError, game::Game};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Player {
+    First,
+    Second,
+}
+
+#[derive(Debug, Clone)]
+pub struct Nim {
+    heaps: Vec<usize>,
+    /// The size of the largest starting heap, which bounds the action space.
+    max_heap: usize,
+    misere: bool,
+    current_player: Player,
+}
+
+impl Nim {
+    pub fn new(heaps: Vec<usize>) -> Self {
+        let max_heap = heaps.iter().copied().max().unwrap_or(0);
+        Self {
+            heaps,
+            max_heap,
+            misere: false,
+            current_player: Player::First,
+        }
+    }
+
+    /// The misère variant, where whoever takes the last object loses.
+    pub fn misere(heaps: Vec<usize>) -> Self {
+        Self {
+            misere: true,
+            ..Self::new(heaps)
+        }
+    }
+
+    pub fn heaps(&self) -> &[usize] {
+        &self.heaps
+    }
+
+    /// Whether the player to play wins with perfect play, by Bouton's theorem: in normal play
+    /// exactly when the heap sizes XOR to a non-zero value. Misère play is the same unless
+    /// every heap has at most one object, in which case the parity is reversed.
+    pub fn is_winning(&self) -> bool {
+        let nim_sum = self.heaps.iter().fold(0, |sum, heap| sum ^ heap);
+        if self.misere && self.heaps.iter().all(|&heap| heap <= 1) {
+            nim_sum == 0
+        } else {
+            nim_sum != 0
+        }
+    }
+}
+
+impl Game for Nim {
+    /// The heap and how many objects to take from it.
+    type Action = (usize, usize);
+
+    type Player = Player;
+
+    fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+        let (heap, count) = action;
+        if self.terminated() {
+            return Err(GameError::GameAlreadyOver);
+        }
+        if heap >= self.heaps.len() {
+            return Err(GameError::illegal_move(
+                &action,
+                format!("there is no heap {}", heap),
+            ));
+        }
+        if count == 0 || count > self.heaps[heap] {
+            return Err(GameError::illegal_move(
+                &action,
+                format!("can't take {} from a heap of {}", count, self.heaps[heap]),
+            ));
+        }
+        let player = self.current_player;
+        self.heaps[heap] -= count;
+        self.current_player = match player {
+            Player::First => Player::Second,
+            Player::Second => Player::First,
+        };
+        let reward = if self.check_winner() == Some(player) {
+            1.
+        } else {
+            0.
+        };
+        Ok(reward)
+    }
+
+    fn get_available_moves(&self) -> Vec<Self::Action> {
+        let mut moves = vec![];
+        self.get_available_moves_into(&mut moves);
+        moves
+    }
+
+    fn get_available_moves_into(&self, moves: &mut Vec<Self::Action>) {
+        moves.clear();
+        moves.extend(
+            self.heaps
+                .iter()
+                .enumerate()
+                .flat_map(|(heap, &size)| (1..=size).map(move |count| (heap, count))),
+        );
+    }
+
+    fn action_space_size(&self) -> usize {
+        self.heaps.len() * self.max_heap
+    }
+
+    fn action_to_index(&self, action: &Self::Action) -> usize {
+        let (heap, count) = *action;
+        heap * self.max_heap + count - 1
+    }
+
+    fn index_to_action(&self, index: usize) -> Self::Action {
+        (index / self.max_heap, index % self.max_heap + 1)
+    }
+
+    fn current_player(&self) -> Self::Player {
+        self.current_player
+    }
+
+    fn player_index(&self, player: &Self::Player) -> usize {
+        *player as usize
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        vec![self.heaps.len(), self.max_heap]
+    }
+
+    /// A row per heap with a one for each object left. Both players face the same position,
+    /// so it's also the canonical observation.
+    fn observation(&self) -> Vec<f32> {
+        let mut observation = vec![0.; self.heaps.len() * self.max_heap];
+        for (heap, &size) in self.heaps.iter().enumerate() {
+            observation[heap * self.max_heap..][..size].fill(1.);
+        }
+        observation
+    }
+
+    fn terminated(&self) -> bool {
+        self.heaps.iter().all(|&heap| heap == 0)
+    }
+
+    fn check_winner(&self) -> Option<Self::Player> {
+        if !self.terminated() {
+            return None;
+        }
+        let last_mover = match self.current_player {
+            Player::First => Player::Second,
+            Player::Second => Player::First,
+        };
+        if self.misere {
+            Some(self.current_player)
+        } else {
+            Some(last_mover)
+        }
+    }
+}
+
+impl fmt::Display for Nim {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (heap, &size) in self.heaps.iter().enumerate() {
+            writeln!(f, "{}: {}", heap, "|".repeat(size))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::Mcts;
+
+    /// Whether the player to play wins, by searching the whole game tree.
+    fn solve(game: &Nim) -> bool {
+        if game.terminated() {
+            return game.check_winner() == Some(game.current_player);
+        }
+        game.get_available_moves().into_iter().any(|action| {
+            let mut next = game.clone();
+            next.step(action).unwrap();
+            !solve(&next)
+        })
+    }
+
+    fn positions(max_heap: usize) -> impl Iterator<Item = Vec<usize>> {
+        (0..=max_heap).flat_map(move |a| {
+            (0..=max_heap).flat_map(move |b| (0..=max_heap).map(move |c| vec![a, b, c]))
+        })
+    }
+
+    #[test]
+    fn test_closed_form() {
+        for heaps in positions(3) {
+            let game = Nim::new(heaps.clone());
+            assert_eq!(game.is_winning(), solve(&game), "{:?}", heaps);
+            let game = Nim::misere(heaps.clone());
+            assert_eq!(game.is_winning(), solve(&game), "misère {:?}", heaps);
+        }
+    }
+
+    #[test]
+    fn test_step() {
+        let mut game = Nim::new(vec![1, 3]);
+        assert_eq!(game.action_space_size(), 6);
+        assert_eq!(game.get_available_moves().len(), 4);
+        assert!(game.step((0, 2)).is_err());
+        assert!(game.step((2, 1)).is_err());
+        assert_eq!(game.step((1, 3)).unwrap(), 0.);
+        assert_eq!(game.observation(), vec![1., 0., 0., 0., 0., 0.]);
+        assert_eq!(game.step((0, 1)).unwrap(), 1.);
+        assert_eq!(game.check_winner(), Some(Player::Second));
+
+        let mut game = Nim::misere(vec![1]);
+        assert_eq!(game.step((0, 1)).unwrap(), 0.);
+        assert_eq!(game.check_winner(), Some(Player::Second));
+    }
+
+    /// From every winning position with a few small heaps, plain MCTS must move to a
+    /// position that is lost for the opponent.
+    fn assert_plays_perfectly(new: fn(Vec<usize>) -> Nim) {
+        let mcts = Mcts::<Nim>::new(3000);
+        for heaps in positions(3) {
+            let game = new(heaps.clone());
+            if game.terminated() || !game.is_winning() {
+                continue;
+            }
+            let action = mcts.search(&game).action;
+            let mut next = game.clone();
+            next.step(action).unwrap();
+            assert!(!next.is_winning(), "{:?}: {:?}", heaps, action);
+        }
+    }
+
+    #[test]
+    fn test_mcts_plays_perfectly() {
+        assert_plays_perfectly(Nim::new);
+    }
+
+    #[test]
+    fn test_mcts_plays_perfectly_misere() {
+        assert_plays_perfectly(Nim::misere);
+    }
+}
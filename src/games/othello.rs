@@ -0,0 +1,465 @@
+//! Othello (Reversi) on the standard 8x8 board. A disc must be placed so that it flanks at
+//! least one line of the opponent's discs, which are flipped; a player without such a move
+//! has to pass, and the game ends when neither player can move. The player with the most
+//! discs wins.
+
+use std::fmt;
+
+use crate::{
+    error::{GameError, ParseError},
+    game::{Game, Symmetry, Undo},
+    notation,
+    symmetry::Transform,
+};
+
+const SIZE: usize = 8;
+const AREA: usize = SIZE * SIZE;
+
+const DIRECTIONS: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Player {
+    Black,
+    White,
+}
+
+impl Player {
+    fn opponent(self) -> Self {
+        match self {
+            Player::Black => Player::White,
+            Player::White => Player::Black,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Move {
+    Place(usize, usize),
+    /// Only legal when no disc can be placed.
+    Pass,
+}
+
+#[derive(Debug, Clone)]
+pub struct Othello {
+    /// The discs in row-major order.
+    board: [Option<Player>; AREA],
+    current_player: Player,
+}
+
+impl Othello {
+    pub fn new() -> Self {
+        let mut board = [None; AREA];
+        board[3 * SIZE + 3] = Some(Player::White);
+        board[4 * SIZE + 4] = Some(Player::White);
+        board[3 * SIZE + 4] = Some(Player::Black);
+        board[4 * SIZE + 3] = Some(Player::Black);
+        Self {
+            board,
+            current_player: Player::Black,
+        }
+    }
+
+    /// The number of discs of Black and White.
+    pub fn disc_counts(&self) -> (usize, usize) {
+        let count = |player| self.board.iter().filter(|&&d| d == Some(player)).count();
+        (count(Player::Black), count(Player::White))
+    }
+
+    /// The squares `player` would flip by placing a disc on `square`.
+    fn flips(&self, square: usize, player: Player) -> Vec<usize> {
+        let mut flips = Vec::new();
+        if self.board[square].is_some() {
+            return flips;
+        }
+        let (row, col) = ((square / SIZE) as isize, (square % SIZE) as isize);
+        for (dr, dc) in DIRECTIONS {
+            let start = flips.len();
+            let (mut r, mut c) = (row + dr, col + dc);
+            while (0..SIZE as isize).contains(&r) && (0..SIZE as isize).contains(&c) {
+                let index = r as usize * SIZE + c as usize;
+                match self.board[index] {
+                    Some(disc) if disc == player.opponent() => flips.push(index),
+                    Some(_) => break,
+                    None => {
+                        flips.truncate(start);
+                        break;
+                    }
+                }
+                r += dr;
+                c += dc;
+            }
+            // Running off the board doesn't flank the line either.
+            if !(0..SIZE as isize).contains(&r) || !(0..SIZE as isize).contains(&c) {
+                flips.truncate(start);
+            }
+        }
+        flips
+    }
+
+    fn can_place(&self, player: Player) -> bool {
+        (0..AREA).any(|square| !self.flips(square, player).is_empty())
+    }
+
+    fn play(&mut self, action: Move) -> Result<Vec<usize>, GameError> {
+        let flips = match action {
+            Move::Place(row, col) => {
+                if row >= SIZE || col >= SIZE {
+                    return Err(GameError::illegal_move(&action, "off the board"));
+                }
+                let square = row * SIZE + col;
+                let flips = self.flips(square, self.current_player);
+                if flips.is_empty() {
+                    return Err(GameError::illegal_move(&action, "doesn't flip any disc"));
+                }
+                self.board[square] = Some(self.current_player);
+                for &index in &flips {
+                    self.board[index] = Some(self.current_player);
+                }
+                flips
+            }
+            Move::Pass => {
+                if self.can_place(self.current_player) {
+                    return Err(GameError::illegal_move(&action, "a disc can be placed"));
+                }
+                if self.terminated() {
+                    return Err(GameError::GameAlreadyOver);
+                }
+                vec![]
+            }
+        };
+        self.current_player = self.current_player.opponent();
+        Ok(flips)
+    }
+}
+
+impl Default for Othello {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Game for Othello {
+    type Action = Move;
+
+    type Player = Player;
+
+    /// The move ending the game is rewarded with 1 if its player wins, -1 if they lose.
+    fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+        self.step_with_undo(action).map(|(reward, _)| reward)
+    }
+
+    fn get_available_moves(&self) -> Vec<Self::Action> {
+        let moves: Vec<_> = (0..AREA)
+            .filter(|&square| !self.flips(square, self.current_player).is_empty())
+            .map(|square| self.index_to_action(square))
+            .collect();
+        if !moves.is_empty() {
+            moves
+        } else if self.can_place(self.current_player.opponent()) {
+            vec![Move::Pass]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Every square, and a pass.
+    fn action_space_size(&self) -> usize {
+        AREA + 1
+    }
+
+    fn action_to_index(&self, action: &Self::Action) -> usize {
+        match *action {
+            Move::Place(row, col) => row * SIZE + col,
+            Move::Pass => AREA,
+        }
+    }
+
+    fn index_to_action(&self, index: usize) -> Self::Action {
+        if index == AREA {
+            Move::Pass
+        } else {
+            Move::Place(index / SIZE, index % SIZE)
+        }
+    }
+
+    fn current_player(&self) -> Self::Player {
+        self.current_player
+    }
+
+    fn player_index(&self, player: &Self::Player) -> usize {
+        *player as usize
+    }
+
+    fn to_notation(&self) -> Option<String> {
+        let cells: Vec<char> = self.board.iter().map(|&disc| symbol(disc)).collect();
+        let to_play = symbol(Some(self.current_player));
+        Some(notation::write(&cells, SIZE, to_play))
+    }
+
+    fn from_notation(notation: &str) -> Result<Self, ParseError> {
+        let (cells, to_play) = notation::parse(notation, SIZE, SIZE)?;
+        let player = |symbol: char| match symbol {
+            '.' => Ok(None),
+            'X' => Ok(Some(Player::Black)),
+            'O' => Ok(Some(Player::White)),
+            _ => Err(ParseError::Invalid(format!("invalid disc `{}`", symbol))),
+        };
+        let mut game = Othello::new();
+        for (i, &cell) in cells.iter().enumerate() {
+            game.board[i] = player(cell)?;
+        }
+        let Some(to_play) = player(to_play)? else {
+            return Err(ParseError::Invalid("no player to move".to_string()));
+        };
+        game.current_player = to_play;
+        Ok(game)
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        vec![3, SIZE, SIZE]
+    }
+
+    /// Three planes: Black's discs, White's discs, and a plane of ones if Black is to play.
+    fn observation(&self) -> Vec<f32> {
+        let mut observation = vec![0.; 3 * AREA];
+        for (i, disc) in self.board.iter().enumerate() {
+            if let Some(player) = disc {
+                observation[*player as usize * AREA + i] = 1.;
+            }
+        }
+        if self.current_player == Player::Black {
+            observation[2 * AREA..].fill(1.);
+        }
+        observation
+    }
+
+    /// Like [`Game::observation`], with the planes of Black and White swapped when White is
+    /// to play.
+    fn canonical_observation(&self) -> Vec<f32> {
+        let mut observation = self.observation();
+        if self.current_player == Player::White {
+            let (black, white) = observation.split_at_mut(AREA);
+            black.swap_with_slice(&mut white[..AREA]);
+        }
+        observation
+    }
+
+    fn terminated(&self) -> bool {
+        !self.can_place(Player::Black) && !self.can_place(Player::White)
+    }
+
+    fn check_winner(&self) -> Option<Self::Player> {
+        if !self.terminated() {
+            return None;
+        }
+        let (black, white) = self.disc_counts();
+        match black.cmp(&white) {
+            std::cmp::Ordering::Greater => Some(Player::Black),
+            std::cmp::Ordering::Less => Some(Player::White),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    /// The differences in discs.
+    fn scores(&self) -> Vec<f32> {
+        let (black, white) = self.disc_counts();
+        let margin = black as f32 - white as f32;
+        vec![margin, -margin]
+    }
+}
+
+impl Undo for Othello {
+    /// The flipped squares.
+    type UndoToken = Vec<usize>;
+
+    fn step_with_undo(&mut self, action: Self::Action) -> Result<(f32, Vec<usize>), GameError> {
+        let player = self.current_player;
+        let flips = self.play(action)?;
+        let reward = match self.check_winner() {
+            Some(winner) if winner == player => 1.,
+            Some(_) => -1.,
+            None => 0.,
+        };
+        Ok((reward, flips))
+    }
+
+    fn undo(&mut self, action: Self::Action, flips: Vec<usize>) {
+        let player = self.current_player.opponent();
+        if let Move::Place(row, col) = action {
+            self.board[row * SIZE + col] = None;
+            for index in flips {
+                self.board[index] = Some(player.opponent());
+            }
+        }
+        self.current_player = player;
+    }
+}
+
+/// The rules are symmetric under all 8 transforms, even though the starting position is only
+/// symmetric under 4 of them.
+impl Symmetry for Othello {
+    fn symmetries(&self) -> Vec<Transform> {
+        Transform::dihedral(SIZE, AREA + 1)
+    }
+}
+
+/// The symbol of a disc, or of the empty cell.
+fn symbol(disc: Option<Player>) -> char {
+    match disc {
+        None => '.',
+        Some(Player::Black) => 'X',
+        Some(Player::White) => 'O',
+    }
+}
+
+impl fmt::Display for Othello {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.board.chunks(SIZE) {
+            for &disc in row {
+                write!(f, "{} ", symbol(disc))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::seq::SliceRandom;
+
+    /// A position from rows like `"XO......"`.
+    fn from_rows(rows: [&str; SIZE], current_player: Player) -> Othello {
+        let mut game = Othello::new();
+        for (i, c) in rows.concat().chars().enumerate() {
+            game.board[i] = match c {
+                'X' => Some(Player::Black),
+                'O' => Some(Player::White),
+                _ => None,
+            };
+        }
+        game.current_player = current_player;
+        game
+    }
+
+    #[test]
+    fn test_opening_moves() {
+        let game = Othello::new();
+        let mut moves = game.get_available_moves();
+        moves.sort_by_key(|action| game.action_to_index(action));
+        assert_eq!(
+            moves,
+            vec![
+                Move::Place(2, 3),
+                Move::Place(3, 2),
+                Move::Place(4, 5),
+                Move::Place(5, 4)
+            ]
+        );
+        assert!(game.clone().step(Move::Place(0, 0)).is_err());
+        assert!(game.clone().step(Move::Pass).is_err());
+    }
+
+    #[test]
+    fn test_flips() {
+        let mut game = Othello::new();
+        game.step(Move::Place(2, 3)).unwrap();
+        assert_eq!(game.board[3 * SIZE + 3], Some(Player::Black));
+        assert_eq!(game.disc_counts(), (4, 1));
+        assert_eq!(game.current_player(), Player::White);
+
+        // One move flipping along two lines at once.
+        let mut game = from_rows(
+            [
+                "..X.....", "OO......", "X.X.....", "........", "........", "........", "........",
+                "........",
+            ],
+            Player::Black,
+        );
+        game.step(Move::Place(0, 0)).unwrap();
+        assert_eq!(game.disc_counts(), (6, 0));
+    }
+
+    #[test]
+    fn test_pass() {
+        // White can't flank the lone black disc, but Black can capture.
+        let mut game = from_rows(
+            [
+                "XO......", "........", "........", "........", "........", "........", "........",
+                "........",
+            ],
+            Player::White,
+        );
+        assert!(!game.terminated());
+        assert_eq!(game.get_available_moves(), vec![Move::Pass]);
+        assert_eq!(game.legal_action_mask().iter().filter(|&&l| l).count(), 1);
+        assert_eq!(game.step(Move::Pass).unwrap(), 0.);
+        assert_eq!(game.current_player(), Player::Black);
+
+        // Black's capture wipes White out and ends the game.
+        assert_eq!(game.step(Move::Place(0, 2)).unwrap(), 1.);
+        assert!(game.done());
+        assert!(game.get_available_moves().is_empty());
+        assert_eq!(game.check_winner(), Some(Player::Black));
+        assert!(game.step(Move::Pass).is_err());
+    }
+
+    #[test]
+    fn test_notation() {
+        let mut game = Othello::new();
+        game.step(Move::Place(2, 3)).unwrap();
+        let notation = game.to_notation().unwrap();
+        assert_eq!(notation, "8/8/3X4/3XX3/3XO3/8/8/8 O");
+        let parsed = Othello::from_notation(&notation).unwrap();
+        assert_eq!(parsed.to_string(), game.to_string());
+        assert_eq!(parsed.get_available_moves(), game.get_available_moves());
+    }
+
+    #[test]
+    fn test_observation() {
+        let mut game = Othello::new();
+        assert_eq!(
+            game.observation().len(),
+            game.observation_shape().iter().product::<usize>()
+        );
+        game.step(Move::Place(2, 3)).unwrap();
+        let observation = game.canonical_observation();
+        // White to play: its own plane holds its one disc.
+        assert_eq!(observation[..AREA].iter().sum::<f32>(), 1.);
+        assert_eq!(observation[AREA..2 * AREA].iter().sum::<f32>(), 4.);
+        assert_eq!(observation[2 * AREA..], [0.; AREA]);
+    }
+
+    #[test]
+    fn test_random_games() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let mut game = Othello::new();
+            let mut history = vec![];
+            while !game.done() {
+                let action = *game.get_available_moves().choose(&mut rng).unwrap();
+                let before = game.clone();
+                let (_, token) = game.step_with_undo(action).unwrap();
+                history.push(before);
+                let after = game.clone();
+                game.undo(action, token.clone());
+                assert_eq!(game.board, history.last().unwrap().board);
+                assert_eq!(game.current_player, history.last().unwrap().current_player);
+                game = after;
+            }
+            let (black, white) = game.disc_counts();
+            assert!(black + white <= AREA);
+            assert_eq!(game.returns().iter().sum::<f32>(), 0.);
+        }
+    }
+}
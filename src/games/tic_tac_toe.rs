@@ -0,0 +1,575 @@
+//! Tic-tac-toe on a 3x3 board, X moving first.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::{fmt, sync::OnceLock};
+
+use crate::{
+    error::{GameError, ParseError},
+    game::{Game, Symmetry, Undo},
+    notation,
+    symmetry::Transform,
+    zobrist::ZobristTable,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Player {
+    X,
+    O,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Spot {
+    Empty,
+    Filled(Player),
+}
+
+/// The squares of a board, bit `row * 3 + col` for every filled one.
+type Bitboard = u16;
+
+/// Every square.
+const FULL: Bitboard = 0x1ff;
+
+/// The eight lines of three: rows, columns and diagonals.
+const LINES: [Bitboard; 8] = [
+    0b000_000_111,
+    0b000_111_000,
+    0b111_000_000,
+    0b001_001_001,
+    0b010_010_010,
+    0b100_100_100,
+    0b100_010_001,
+    0b001_010_100,
+];
+
+fn has_line(board: Bitboard) -> bool {
+    LINES.iter().any(|&line| line & !board == 0)
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(into = "State", try_from = "State"))]
+pub struct TicTacToe {
+    /// The squares of X and of O.
+    boards: [Bitboard; 2],
+    pub current_player: Player,
+    /// Zobrist hash of the boards and `current_player`, updated by `step`.
+    hash: u64,
+}
+
+fn zobrist() -> &'static ZobristTable {
+    static TABLE: OnceLock<ZobristTable> = OnceLock::new();
+    TABLE.get_or_init(|| ZobristTable::new(9, 2, 2, 0x7ac7ac))
+}
+
+impl Game for TicTacToe {
+    type Action = (usize, usize);
+
+    type Player = Player;
+
+    fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+        if self.check_winner().is_some() {
+            return Err(GameError::GameAlreadyOver);
+        }
+        let (row, col) = action;
+        if row >= 3 || col >= 3 {
+            return Err(GameError::illegal_move(&action, "off the board"));
+        }
+        let square = row * 3 + col;
+        if self.filled() & 1 << square != 0 {
+            return Err(GameError::illegal_move(
+                &action,
+                "the spot is already filled",
+            ));
+        }
+        let player = self.current_player;
+        self.boards[player as usize] |= 1 << square;
+        let next_player = player.opponent();
+        let table = zobrist();
+        self.hash ^= table.piece(square, player as usize)
+            ^ table.player(player as usize)
+            ^ table.player(next_player as usize);
+        self.current_player = next_player;
+        // Only the mover's marks can have made a line.
+        Ok(if has_line(self.boards[player as usize]) {
+            1.
+        } else {
+            0.
+        })
+    }
+
+    fn get_available_moves(&self) -> Vec<Self::Action> {
+        let mut moves = vec![];
+        self.get_available_moves_into(&mut moves);
+        moves
+    }
+
+    fn get_available_moves_into(&self, moves: &mut Vec<Self::Action>) {
+        moves.clear();
+        if self.check_winner().is_some() {
+            return;
+        }
+        let mut empty = FULL & !self.filled();
+        while empty != 0 {
+            moves.push(self.index_to_action(empty.trailing_zeros() as usize));
+            empty &= empty - 1;
+        }
+    }
+
+    fn action_space_size(&self) -> usize {
+        9
+    }
+
+    fn action_to_index(&self, action: &Self::Action) -> usize {
+        let (row, col) = *action;
+        row * 3 + col
+    }
+
+    fn index_to_action(&self, index: usize) -> Self::Action {
+        (index / 3, index % 3)
+    }
+
+    fn current_player(&self) -> Self::Player {
+        self.current_player
+    }
+
+    fn player_index(&self, player: &Self::Player) -> usize {
+        *player as usize
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        vec![3, 3, 3]
+    }
+
+    fn state_hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Three planes: X's spots, O's spots, and a plane of ones if X is to play.
+    fn observation(&self) -> Vec<f32> {
+        let mut observation = vec![0.; 27];
+        for square in 0..9 {
+            for (plane, board) in self.boards.iter().enumerate() {
+                if board & 1 << square != 0 {
+                    observation[plane * 9 + square] = 1.;
+                }
+            }
+        }
+        if self.current_player == Player::X {
+            observation[18..].fill(1.);
+        }
+        observation
+    }
+
+    fn to_notation(&self) -> Option<String> {
+        let cells: Vec<char> = (0..9)
+            .map(|square| symbol(self.spot(square / 3, square % 3)))
+            .collect();
+        let to_play = symbol(Spot::Filled(self.current_player));
+        Some(notation::write(&cells, 3, to_play))
+    }
+
+    fn from_notation(notation: &str) -> Result<Self, ParseError> {
+        let (cells, to_play) = notation::parse(notation, 3, 3)?;
+        let mut game = TicTacToe::new();
+        for (square, cell) in cells.into_iter().enumerate() {
+            match cell {
+                '.' => {}
+                'X' => game.boards[Player::X as usize] |= 1 << square,
+                'O' => game.boards[Player::O as usize] |= 1 << square,
+                _ => return Err(ParseError::Invalid(format!("invalid spot `{}`", cell))),
+            }
+        }
+        game.current_player = match to_play {
+            'X' => Player::X,
+            'O' => Player::O,
+            _ => return Err(ParseError::Invalid(format!("invalid player `{}`", to_play))),
+        };
+        game.hash = game.compute_hash();
+        Ok(game)
+    }
+
+    fn winning_moves(&self, opponent: bool) -> Vec<Self::Action> {
+        if self.terminated() {
+            return vec![];
+        }
+        let player = match opponent {
+            false => self.current_player,
+            true => self.current_player.opponent(),
+        };
+        let board = self.boards[player as usize];
+        (0..9)
+            .filter(|&square| self.filled() & 1 << square == 0 && has_line(board | 1 << square))
+            .map(|square| (square / 3, square % 3))
+            .collect()
+    }
+
+    /// Like [`Game::observation`], with the planes of X and O swapped when O is to play.
+    fn canonical_observation(&self) -> Vec<f32> {
+        let mut observation = self.observation();
+        if self.current_player == Player::O {
+            let (x, o) = observation.split_at_mut(9);
+            x.swap_with_slice(&mut o[..9]);
+        }
+        observation
+    }
+
+    fn terminated(&self) -> bool {
+        self.filled() == FULL || self.check_winner().is_some()
+    }
+
+    fn check_winner(&self) -> Option<Self::Player> {
+        if has_line(self.boards[Player::X as usize]) {
+            Some(Player::X)
+        } else if has_line(self.boards[Player::O as usize]) {
+            Some(Player::O)
+        } else {
+            None
+        }
+    }
+}
+
+impl Undo for TicTacToe {
+    type UndoToken = ();
+
+    fn step_with_undo(&mut self, action: Self::Action) -> Result<(f32, ()), GameError> {
+        self.step(action).map(|reward| (reward, ()))
+    }
+
+    fn undo(&mut self, action: Self::Action, _token: ()) {
+        let (row, col) = action;
+        if let Spot::Filled(player) = self.spot(row, col) {
+            let square = row * 3 + col;
+            let table = zobrist();
+            self.hash ^= table.piece(square, player as usize)
+                ^ table.player(self.current_player as usize)
+                ^ table.player(player as usize);
+            self.boards[player as usize] &= !(1 << square);
+            self.current_player = player;
+        }
+    }
+}
+
+impl Symmetry for TicTacToe {
+    fn symmetries(&self) -> Vec<Transform> {
+        Transform::dihedral(3, 9)
+    }
+}
+
+impl Player {
+    fn opponent(self) -> Self {
+        match self {
+            Player::X => Player::O,
+            Player::O => Player::X,
+        }
+    }
+}
+
+impl TicTacToe {
+    pub fn new() -> Self {
+        Self {
+            boards: [0; 2],
+            current_player: Player::X,
+            hash: zobrist().player(Player::X as usize),
+        }
+    }
+
+    /// The spot at `(row, col)`.
+    pub fn spot(&self, row: usize, col: usize) -> Spot {
+        let square = 1 << (row * 3 + col);
+        if self.boards[Player::X as usize] & square != 0 {
+            Spot::Filled(Player::X)
+        } else if self.boards[Player::O as usize] & square != 0 {
+            Spot::Filled(Player::O)
+        } else {
+            Spot::Empty
+        }
+    }
+
+    fn filled(&self) -> Bitboard {
+        self.boards[0] | self.boards[1]
+    }
+
+    fn compute_hash(&self) -> u64 {
+        let table = zobrist();
+        let mut hash = table.player(self.current_player as usize);
+        for square in 0..9 {
+            if let Spot::Filled(player) = self.spot(square / 3, square % 3) {
+                hash ^= table.piece(square, player as usize);
+            }
+        }
+        hash
+    }
+}
+
+impl Default for TicTacToe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn symbol(spot: Spot) -> char {
+    match spot {
+        Spot::Empty => '.',
+        Spot::Filled(Player::X) => 'X',
+        Spot::Filled(Player::O) => 'O',
+    }
+}
+
+/// The board is stored as three rows like `"X.O"`, in the same notation as [`fmt::Display`].
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct State {
+    spots: Vec<String>,
+    current_player: Player,
+}
+
+#[cfg(feature = "serde")]
+impl From<TicTacToe> for State {
+    fn from(game: TicTacToe) -> Self {
+        Self {
+            spots: (0..3)
+                .map(|row| (0..3).map(|col| symbol(game.spot(row, col))).collect())
+                .collect(),
+            current_player: game.current_player,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<State> for TicTacToe {
+    type Error = ParseError;
+
+    fn try_from(state: State) -> Result<Self, ParseError> {
+        if state.spots.len() != 3 {
+            return Err(ParseError::Invalid(format!(
+                "expected 3 rows, found {}",
+                state.spots.len()
+            )));
+        }
+        let mut game = TicTacToe::new();
+        for (i, row) in state.spots.iter().enumerate() {
+            if row.chars().count() != 3 {
+                return Err(ParseError::Invalid(format!(
+                    "expected 3 spots in row `{}`",
+                    row
+                )));
+            }
+            for (j, c) in row.chars().enumerate() {
+                match c {
+                    '.' => {}
+                    'X' => game.boards[Player::X as usize] |= 1 << (i * 3 + j),
+                    'O' => game.boards[Player::O as usize] |= 1 << (i * 3 + j),
+                    _ => return Err(ParseError::Invalid(format!("invalid spot `{}`", c))),
+                }
+            }
+        }
+        game.current_player = state.current_player;
+        game.hash = game.compute_hash();
+        Ok(game)
+    }
+}
+
+impl fmt::Display for TicTacToe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in 0..3 {
+            for col in 0..3 {
+                write!(f, "{} ", symbol(self.spot(row, col)))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        let game = TicTacToe::new();
+        assert_eq!(game.boards, [0; 2]);
+        assert_eq!(game.current_player, Player::X);
+    }
+
+    #[test]
+    fn test_step() {
+        let mut game = TicTacToe::new();
+        assert!(game.step((0, 0)).is_ok());
+        assert_eq!(game.spot(0, 0), Spot::Filled(Player::X));
+        assert_eq!(game.current_player, Player::O);
+
+        assert!(matches!(
+            game.step((0, 0)),
+            Err(GameError::IllegalMove { action, .. }) if action == "(0, 0)"
+        ));
+        assert!(game.step((3, 0)).is_err());
+        assert_eq!(game.spot(0, 0), Spot::Filled(Player::X));
+        assert_eq!(game.current_player, Player::O);
+
+        assert!(game.step((0, 1)).is_ok());
+        assert_eq!(game.spot(0, 1), Spot::Filled(Player::O));
+        assert_eq!(game.current_player, Player::X);
+
+        for action in [(1, 0), (1, 1), (2, 0)] {
+            game.step(action).unwrap();
+        }
+        assert!(matches!(game.step((2, 2)), Err(GameError::GameAlreadyOver)));
+    }
+
+    #[test]
+    fn test_check_winner() {
+        let mut game = TicTacToe::new();
+        assert_eq!(game.check_winner(), None);
+
+        game.boards = [0b100_010_001, 0];
+        assert_eq!(game.check_winner(), Some(Player::X));
+
+        game.boards = [0, 0b100_010_001];
+        assert_eq!(game.check_winner(), Some(Player::O));
+
+        // X O . / . X O / O . .
+        game.boards = [0b000_010_001, 0b001_100_010];
+        assert_eq!(game.check_winner(), None);
+        game.boards[Player::X as usize] |= 1 << 8;
+        assert_eq!(game.check_winner(), Some(Player::X));
+
+        // Three in a row across the end of one row and the start of the next isn't a line.
+        game.boards = [0b000_011_100, 0];
+        assert_eq!(game.check_winner(), None);
+    }
+
+    #[test]
+    fn test_legal_action_mask() {
+        let mut game = TicTacToe::new();
+        assert_eq!(game.legal_action_mask(), vec![true; 9]);
+        game.step((1, 2)).unwrap();
+        let mask = game.legal_action_mask();
+        assert!(!mask[5]);
+        assert_eq!(mask.iter().filter(|&&legal| legal).count(), 8);
+        for index in 0..game.action_space_size() {
+            assert_eq!(game.action_to_index(&game.index_to_action(index)), index);
+        }
+    }
+
+    #[test]
+    fn test_notation() {
+        let mut game = TicTacToe::new();
+        for action in [(0, 0), (1, 1), (0, 1)] {
+            game.step(action).unwrap();
+        }
+        assert_eq!(game.to_notation().unwrap(), "XX1/1O1/3 O");
+        let parsed = TicTacToe::from_notation("XX1/1O1/3 O").unwrap();
+        assert_eq!(parsed.to_string(), game.to_string());
+        assert_eq!(parsed.state_hash(), game.state_hash());
+        assert!(TicTacToe::from_notation("XX1/1Q1/3 O").is_err());
+        assert!(TicTacToe::from_notation("XX1/1O1/3 -").is_err());
+    }
+
+    #[test]
+    fn test_observation() {
+        let mut game = TicTacToe::new();
+        game.step((0, 1)).unwrap();
+        game.step((2, 2)).unwrap();
+        let observation = game.observation();
+        let shape = game.observation_shape();
+        assert_eq!(observation.len(), shape.iter().product::<usize>());
+        assert_eq!(observation[1], 1.);
+        assert_eq!(observation[9 + 8], 1.);
+        assert_eq!(observation[..18].iter().sum::<f32>(), 2.);
+        assert_eq!(observation[18..], [1.; 9]);
+    }
+
+    #[test]
+    fn test_canonical_observation() {
+        let mut game = TicTacToe::new();
+        assert_eq!(game.canonical_observation(), game.observation());
+
+        game.step((0, 0)).unwrap();
+        let observation = game.canonical_observation();
+        // O to play: its own plane is empty, the opponent's plane holds X's stone.
+        assert_eq!(observation[..9], [0.; 9]);
+        assert_eq!(observation[9], 1.);
+        assert_eq!(observation[18..], [0.; 9]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json() {
+        let mut game = TicTacToe::new();
+        game.step((0, 2)).unwrap();
+        game.step((1, 1)).unwrap();
+        game.step((2, 0)).unwrap();
+        let json = serde_json::to_string(&game).unwrap();
+        assert_eq!(
+            json,
+            r#"{"spots":["..X",".O.","X.."],"current_player":"O"}"#
+        );
+        let restored: TicTacToe = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.boards, game.boards);
+        assert_eq!(restored.current_player, game.current_player);
+
+        let invalid = r#"{"spots":["..X",".Z.","X.."],"current_player":"O"}"#;
+        assert!(serde_json::from_str::<TicTacToe>(invalid).is_err());
+    }
+
+    #[test]
+    fn test_state_hash() {
+        // Enumerate every reachable position and check that the incremental hash matches the
+        // hash computed from scratch, and that no two distinct positions collide.
+        let mut hashes = std::collections::HashMap::new();
+        let mut stack = vec![TicTacToe::new()];
+        while let Some(game) = stack.pop() {
+            assert_eq!(game.state_hash(), game.compute_hash());
+            let position = format!("{}{:?}", game, game.current_player);
+            if let Some(other) = hashes.insert(game.state_hash(), position.clone()) {
+                assert_eq!(other, position);
+                continue;
+            }
+            if game.done() {
+                continue;
+            }
+            for action in game.get_available_moves() {
+                let mut next = game.clone();
+                next.step(action).unwrap();
+                stack.push(next);
+            }
+        }
+        assert_eq!(hashes.len(), 5478);
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut game = TicTacToe::new();
+        game.step((1, 1)).unwrap();
+        let before = game.clone();
+        let (_, token) = game.step_with_undo((0, 2)).unwrap();
+        game.undo((0, 2), token);
+        assert_eq!(game.boards, before.boards);
+        assert_eq!(game.current_player, before.current_player);
+        assert_eq!(game.state_hash(), before.state_hash());
+    }
+
+    #[test]
+    fn test_symmetries() {
+        let moves = [0, 4, 5];
+        let mut game = TicTacToe::new();
+        for &action in &moves {
+            game.step(game.index_to_action(action)).unwrap();
+        }
+        for transform in game.symmetries() {
+            // Playing the transformed moves reaches the transformed position.
+            let mut transformed = TicTacToe::new();
+            for &action in &moves {
+                let action = transformed.index_to_action(transform.action(action));
+                transformed.step(action).unwrap();
+            }
+            assert_eq!(
+                transformed.observation(),
+                transform.observation(&game.observation())
+            );
+        }
+    }
+}
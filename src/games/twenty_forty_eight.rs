@@ -0,0 +1,462 @@
+//! 2048: sliding the 4x4 grid merges equal tiles and scores their sum, and after every slide
+//! chance spawns a 2 (probability 0.9) or a 4 on an empty cell. The game ends when no slide
+//! moves any tile.
+//!
+//! The grid is packed into a `u64` of sixteen 4-bit exponents, one per cell, and slides look
+//! up each row in a precomputed table.
+
+use rand::Rng;
+use std::{fmt, sync::OnceLock};
+
+use crate::{error::GameError, game::Game, random};
+
+const NUM_CELLS: usize = 16;
+
+/// The probability that a spawned tile is a 2 rather than a 4.
+const SPAWN_TWO_PROBABILITY: f32 = 0.9;
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Action {
+    Slide(Direction),
+    /// A chance outcome: a tile with value `2^exponent` appears on `cell`.
+    Spawn {
+        cell: usize,
+        exponent: u8,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct TwentyFortyEight {
+    /// The exponent of every cell in row-major order, 4 bits each from the lowest bits up,
+    /// 0 for an empty cell.
+    board: u64,
+    /// Whether chance has to spawn a tile before the next slide.
+    spawn_pending: bool,
+}
+
+/// A slide of every possible row to the left: the new row and the score of its merges.
+fn left_slides() -> &'static [(u16, u32)] {
+    static TABLE: OnceLock<Vec<(u16, u32)>> = OnceLock::new();
+    TABLE.get_or_init(|| (0..=u16::MAX).map(slide_row_left).collect())
+}
+
+fn slide_row_left(row: u16) -> (u16, u32) {
+    let tiles: Vec<u16> = (0..4)
+        .map(|i| (row >> (4 * i)) & 0xf)
+        .filter(|&tile| tile != 0)
+        .collect();
+    let mut merged = Vec::with_capacity(4);
+    let mut score = 0;
+    let mut i = 0;
+    while i < tiles.len() {
+        if i + 1 < tiles.len() && tiles[i] == tiles[i + 1] && tiles[i] < 0xf {
+            merged.push(tiles[i] + 1);
+            score += 1 << (tiles[i] + 1);
+            i += 2;
+        } else {
+            merged.push(tiles[i]);
+            i += 1;
+        }
+    }
+    let row = merged
+        .iter()
+        .enumerate()
+        .fold(0, |row, (i, &tile)| row | tile << (4 * i));
+    (row, score)
+}
+
+fn reverse_row(row: u16) -> u16 {
+    (row >> 12) | ((row >> 4) & 0x00f0) | ((row << 4) & 0x0f00) | (row << 12)
+}
+
+fn transpose(board: u64) -> u64 {
+    let mut transposed = 0;
+    for row in 0..4 {
+        for col in 0..4 {
+            let tile = (board >> (4 * (row * 4 + col))) & 0xf;
+            transposed |= tile << (4 * (col * 4 + row));
+        }
+    }
+    transposed
+}
+
+/// Slide every row of `board` to the left, or to the right if `reverse`.
+fn slide_rows(board: u64, reverse: bool) -> (u64, u32) {
+    let table = left_slides();
+    let mut slid = 0;
+    let mut score = 0;
+    for r in 0..4 {
+        let mut row = (board >> (16 * r)) as u16;
+        if reverse {
+            row = reverse_row(row);
+        }
+        let (mut new_row, row_score) = table[row as usize];
+        if reverse {
+            new_row = reverse_row(new_row);
+        }
+        slid |= (new_row as u64) << (16 * r);
+        score += row_score;
+    }
+    (slid, score)
+}
+
+fn slide(board: u64, direction: Direction) -> (u64, u32) {
+    match direction {
+        Direction::Left => slide_rows(board, false),
+        Direction::Right => slide_rows(board, true),
+        Direction::Up => {
+            let (slid, score) = slide_rows(transpose(board), false);
+            (transpose(slid), score)
+        }
+        Direction::Down => {
+            let (slid, score) = slide_rows(transpose(board), true);
+            (transpose(slid), score)
+        }
+    }
+}
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+];
+
+impl TwentyFortyEight {
+    /// A new game with two random tiles.
+    pub fn new() -> Self {
+        let mut rng = random::rng();
+        let mut game = Self::from_board(0);
+        for _ in 0..2 {
+            let empty = game.empty_cells();
+            let cell = empty[rng.gen_range(0..empty.len())];
+            let exponent = if rng.gen::<f32>() < SPAWN_TWO_PROBABILITY {
+                1
+            } else {
+                2
+            };
+            game.set_exponent(cell, exponent);
+        }
+        game
+    }
+
+    /// A game with the cells of `board` (see [`TwentyFortyEight::exponent`]) and the player
+    /// to slide.
+    pub fn from_board(board: u64) -> Self {
+        Self {
+            board,
+            spawn_pending: false,
+        }
+    }
+
+    /// The tile on `cell` is `2^exponent`, or the cell is empty if the exponent is 0.
+    pub fn exponent(&self, cell: usize) -> u8 {
+        ((self.board >> (4 * cell)) & 0xf) as u8
+    }
+
+    fn set_exponent(&mut self, cell: usize, exponent: u8) {
+        self.board = self.board & !(0xf << (4 * cell)) | (exponent as u64) << (4 * cell);
+    }
+
+    fn empty_cells(&self) -> Vec<usize> {
+        (0..NUM_CELLS)
+            .filter(|&cell| self.exponent(cell) == 0)
+            .collect()
+    }
+
+    pub fn max_tile(&self) -> u32 {
+        let exponent = (0..NUM_CELLS)
+            .map(|cell| self.exponent(cell))
+            .max()
+            .unwrap();
+        if exponent == 0 {
+            0
+        } else {
+            1 << exponent
+        }
+    }
+}
+
+impl Default for TwentyFortyEight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Game for TwentyFortyEight {
+    type Action = Action;
+
+    type Player = ();
+
+    /// Slides are rewarded with the sum of the merged tiles.
+    fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+        match action {
+            Action::Slide(direction) => {
+                if self.spawn_pending {
+                    return Err(GameError::illegal_move(
+                        &action,
+                        "a tile has to spawn first",
+                    ));
+                }
+                let (board, score) = slide(self.board, direction);
+                if board == self.board {
+                    return Err(GameError::illegal_move(&action, "doesn't move any tile"));
+                }
+                self.board = board;
+                self.spawn_pending = true;
+                Ok(score as f32)
+            }
+            Action::Spawn { cell, exponent } => {
+                if !self.spawn_pending {
+                    return Err(GameError::illegal_move(
+                        &action,
+                        "no tile spawns before a slide",
+                    ));
+                }
+                if cell >= NUM_CELLS || self.exponent(cell) != 0 {
+                    return Err(GameError::illegal_move(&action, "the cell isn't empty"));
+                }
+                if !(1..=2).contains(&exponent) {
+                    return Err(GameError::illegal_move(&action, "only 2s and 4s spawn"));
+                }
+                self.set_exponent(cell, exponent);
+                self.spawn_pending = false;
+                Ok(0.)
+            }
+        }
+    }
+
+    fn get_available_moves(&self) -> Vec<Self::Action> {
+        if self.spawn_pending {
+            return vec![];
+        }
+        DIRECTIONS
+            .into_iter()
+            .filter(|&direction| slide(self.board, direction).0 != self.board)
+            .map(Action::Slide)
+            .collect()
+    }
+
+    /// The four slides, followed by the 32 spawns, a 2 or a 4 on each cell, that only chance
+    /// plays.
+    fn action_space_size(&self) -> usize {
+        DIRECTIONS.len() + 2 * NUM_CELLS
+    }
+
+    fn action_to_index(&self, action: &Self::Action) -> usize {
+        match *action {
+            Action::Slide(direction) => direction as usize,
+            Action::Spawn { cell, exponent } => DIRECTIONS.len() + 2 * cell + exponent as usize - 1,
+        }
+    }
+
+    fn index_to_action(&self, index: usize) -> Self::Action {
+        match index.checked_sub(DIRECTIONS.len()) {
+            None => Action::Slide(DIRECTIONS[index]),
+            Some(spawn) => Action::Spawn {
+                cell: spawn / 2,
+                exponent: (spawn % 2 + 1) as u8,
+            },
+        }
+    }
+
+    fn current_player(&self) -> Self::Player {}
+
+    fn player_index(&self, _player: &Self::Player) -> usize {
+        0
+    }
+
+    fn num_players(&self) -> usize {
+        1
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        vec![16, 4, 4]
+    }
+
+    /// A one-hot plane per exponent: plane `e` marks the cells holding `2^e`, and plane 0 the
+    /// empty cells.
+    fn observation(&self) -> Vec<f32> {
+        let mut observation = vec![0.; 16 * NUM_CELLS];
+        for cell in 0..NUM_CELLS {
+            observation[self.exponent(cell) as usize * NUM_CELLS + cell] = 1.;
+        }
+        observation
+    }
+
+    fn terminated(&self) -> bool {
+        !self.spawn_pending && self.get_available_moves().is_empty()
+    }
+
+    fn check_winner(&self) -> Option<Self::Player> {
+        None
+    }
+
+    fn chance_outcomes(&self) -> Vec<(Self::Action, f32)> {
+        if !self.spawn_pending {
+            return vec![];
+        }
+        let empty = self.empty_cells();
+        let share = 1. / empty.len() as f32;
+        empty
+            .into_iter()
+            .flat_map(|cell| {
+                [
+                    (
+                        Action::Spawn { cell, exponent: 1 },
+                        share * SPAWN_TWO_PROBABILITY,
+                    ),
+                    (
+                        Action::Spawn { cell, exponent: 2 },
+                        share * (1. - SPAWN_TWO_PROBABILITY),
+                    ),
+                ]
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for TwentyFortyEight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in 0..4 {
+            for col in 0..4 {
+                match self.exponent(row * 4 + col) {
+                    0 => write!(f, "{:>5}", ".")?,
+                    exponent => write!(f, "{:>5}", 1u32 << exponent)?,
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::Mcts;
+
+    /// A board from rows of exponents.
+    fn board(rows: [[u64; 4]; 4]) -> u64 {
+        rows.iter()
+            .flatten()
+            .enumerate()
+            .fold(0, |board, (cell, &exponent)| board | exponent << (4 * cell))
+    }
+
+    #[test]
+    fn test_slide_row() {
+        let row = |tiles: [u16; 4]| {
+            tiles
+                .iter()
+                .enumerate()
+                .fold(0, |row, (i, &tile)| row | tile << (4 * i))
+        };
+        assert_eq!(slide_row_left(row([1, 1, 2, 0])), (row([2, 2, 0, 0]), 4));
+        // A merged tile doesn't merge again in the same slide.
+        assert_eq!(slide_row_left(row([1, 1, 2, 2])), (row([2, 3, 0, 0]), 12));
+        assert_eq!(slide_row_left(row([0, 3, 0, 3])), (row([4, 0, 0, 0]), 16));
+        assert_eq!(slide_row_left(row([1, 2, 1, 2])), (row([1, 2, 1, 2]), 0));
+        assert_eq!(reverse_row(row([1, 2, 3, 4])), row([4, 3, 2, 1]));
+    }
+
+    #[test]
+    fn test_slides() {
+        let start = board([[1, 0, 0, 1], [0, 0, 0, 0], [0, 0, 0, 0], [2, 0, 0, 0]]);
+        assert_eq!(transpose(transpose(start)), start);
+        let mut game = TwentyFortyEight::from_board(start);
+        assert_eq!(game.step(Action::Slide(Direction::Left)).unwrap(), 4.);
+        assert_eq!(
+            game.board,
+            board([[2, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0], [2, 0, 0, 0]])
+        );
+        assert!(game.step(Action::Slide(Direction::Down)).is_err());
+        assert!(game.chance_outcomes().iter().all(|(action, _)| {
+            matches!(action, Action::Spawn { cell, .. } if ![0, 12].contains(cell))
+        }));
+        game.step(Action::Spawn {
+            cell: 1,
+            exponent: 1,
+        })
+        .unwrap();
+        assert_eq!(game.step(Action::Slide(Direction::Down)).unwrap(), 8.);
+        assert_eq!(game.exponent(12), 3);
+        assert_eq!(game.exponent(13), 1);
+    }
+
+    #[test]
+    fn test_chance_outcomes() {
+        let mut game = TwentyFortyEight::from_board(board([[1, 0, 0, 0]; 4]));
+        assert!(game.chance_outcomes().is_empty());
+        assert!(game
+            .step(Action::Spawn {
+                cell: 1,
+                exponent: 1
+            })
+            .is_err());
+        game.step(Action::Slide(Direction::Right)).unwrap();
+        assert!(game.get_available_moves().is_empty());
+        assert!(!game.done());
+        let outcomes = game.chance_outcomes();
+        assert_eq!(outcomes.len(), 2 * 12);
+        assert!((outcomes.iter().map(|(_, p)| p).sum::<f32>() - 1.).abs() < 1e-5);
+        for (action, _) in outcomes {
+            let index = game.action_to_index(&action);
+            assert!(index >= 4 && index < game.action_space_size());
+            assert_eq!(game.index_to_action(index), action);
+        }
+    }
+
+    #[test]
+    fn test_game_over() {
+        let full = TwentyFortyEight::from_board(board([
+            [1, 2, 1, 2],
+            [2, 1, 2, 1],
+            [1, 2, 1, 2],
+            [2, 1, 2, 1],
+        ]));
+        assert!(full.done());
+        let mergeable = TwentyFortyEight::from_board(board([
+            [1, 1, 1, 2],
+            [2, 1, 2, 1],
+            [1, 2, 1, 2],
+            [2, 1, 2, 1],
+        ]));
+        assert!(!mergeable.done());
+        assert_eq!(
+            mergeable.get_available_moves(),
+            vec![
+                Action::Slide(Direction::Up),
+                Action::Slide(Direction::Down),
+                Action::Slide(Direction::Left),
+                Action::Slide(Direction::Right)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_search() {
+        let game = TwentyFortyEight::from_board(board([
+            [3, 0, 0, 0],
+            [1, 0, 0, 0],
+            [1, 0, 0, 0],
+            [4, 0, 0, 0],
+        ]));
+        let mcts = Mcts::<TwentyFortyEight>::new(200);
+        let action = mcts.search(&game).action;
+        assert!(game.get_available_moves().contains(&action));
+        assert_eq!(
+            game.observation().len(),
+            game.observation_shape().iter().product::<usize>()
+        );
+        assert!(TwentyFortyEight::new().max_tile() <= 4);
+    }
+}
@@ -0,0 +1,361 @@
+//! Evaluation during training: every new checkpoint plays a match against the best checkpoint
+//! so far, or against a fixed baseline, and takes its place as the best if it scores enough,
+//! like the evaluator of AlphaGo Zero. The best checkpoint is named in a `best` file next to
+//! the checkpoints, which self-play can follow instead of the latest one. Once the strength
+//! stops improving, a `stop` file asks training to stop.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    agent::Agent,
+    arena::{self, MatchResult},
+    checkpoint::{self, Checkpoint},
+    error::{ConfigError, IoError, Result},
+    game::Game,
+};
+
+const BEST: &str = "best";
+const STOP: &str = "stop";
+
+/// The best checkpoint in `root`, if one was chosen.
+pub fn best(root: &Path) -> Result<Option<PathBuf>> {
+    let path = root.join(BEST);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let name = fs::read_to_string(&path).map_err(IoError::path("read", path))?;
+    Ok(Some(root.join(name.trim())))
+}
+
+/// Make `dir`, a checkpoint in `root`, the best one.
+pub fn set_best(root: &Path, dir: &Path) -> Result<()> {
+    let name = dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| {
+            ConfigError::Invalid(format!("{} isn't a checkpoint directory", dir.display()))
+        })?;
+    // Replace the file at once, so that readers never see half a name.
+    let path = root.join(BEST);
+    let temporary = root.join(format!("{}.tmp", BEST));
+    fs::write(&temporary, format!("{}\n", name))
+        .and_then(|()| fs::rename(&temporary, &path))
+        .map_err(IoError::path("write", path))?;
+    Ok(())
+}
+
+/// Ask the training run of the checkpoints in `root` to stop, saying why.
+pub fn request_stop(root: &Path, reason: &str) -> Result<()> {
+    let path = root.join(STOP);
+    fs::write(&path, format!("{}\n", reason)).map_err(IoError::path("write", path))?;
+    Ok(())
+}
+
+/// Why the training run of the checkpoints in `root` was asked to stop, if it was.
+pub fn stop_requested(root: &Path) -> Result<Option<String>> {
+    let path = root.join(STOP);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let reason = fs::read_to_string(&path).map_err(IoError::path("read", path))?;
+    Ok(Some(reason.trim().to_string()))
+}
+
+/// The Elo difference of `result`, finite by counting a clean sweep as half a game short of
+/// one.
+fn elo(result: &MatchResult) -> f64 {
+    let half_game = 0.5 / result.games() as f64;
+    let score = result.score().clamp(half_game, 1. - half_game);
+    -400. * (1. / score - 1.).log10()
+}
+
+/// Stops a run once its strength hasn't risen by more than `min_delta` for `patience`
+/// evaluations in a row.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EarlyStopping {
+    pub patience: usize,
+    pub min_delta: f64,
+    best: Option<f64>,
+    /// Evaluations since the best one.
+    stale: usize,
+}
+
+impl EarlyStopping {
+    pub fn new(patience: usize, min_delta: f64) -> Self {
+        Self {
+            patience,
+            min_delta,
+            best: None,
+            stale: 0,
+        }
+    }
+
+    /// Record the strength of an evaluation, returning whether to stop.
+    pub fn update(&mut self, strength: f64) -> bool {
+        if self
+            .best
+            .is_none_or(|best| strength > best + self.min_delta)
+        {
+            self.best = Some(strength);
+            self.stale = 0;
+        } else {
+            self.stale += 1;
+        }
+        self.stale >= self.patience
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GatingConfig {
+    /// Games of every match, alternating who moves first.
+    pub games: usize,
+    /// The score a checkpoint needs against its opponent to become the best, 0.55 in
+    /// AlphaGo Zero.
+    pub threshold: f64,
+}
+
+impl Default for GatingConfig {
+    fn default() -> Self {
+        Self {
+            games: 40,
+            threshold: 0.55,
+        }
+    }
+}
+
+/// The match of a checkpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    pub checkpoint: PathBuf,
+    pub step: usize,
+    /// The results of the checkpoint, or `None` if it became the best without a match as the
+    /// first one.
+    pub result: Option<MatchResult>,
+    /// Whether it's the best checkpoint now.
+    pub promoted: bool,
+    /// Its Elo above the first best checkpoint, measured through the chain of best ones, or
+    /// above the baseline.
+    pub strength: f64,
+    /// Whether its evaluation asked training to stop.
+    pub stop: bool,
+}
+
+/// Makes the agent that plays with the weights of a checkpoint.
+pub type AgentFactory<G> = Box<dyn FnMut(&Checkpoint) -> Result<Box<dyn Agent<G>>>>;
+
+/// Evaluates the checkpoints of a training run as they appear.
+pub struct Evaluator<G: Game> {
+    root: PathBuf,
+    config: GatingConfig,
+    new_agent: AgentFactory<G>,
+    /// The fixed opponent, instead of the best checkpoint.
+    baseline: Option<Box<dyn Agent<G>>>,
+    /// The last checkpoint evaluated.
+    evaluated: Option<PathBuf>,
+    /// The strength of the best checkpoint.
+    best_strength: f64,
+    early_stopping: Option<EarlyStopping>,
+}
+
+impl<G: Game> Evaluator<G> {
+    pub fn new(
+        root: &Path,
+        config: GatingConfig,
+        new_agent: impl FnMut(&Checkpoint) -> Result<Box<dyn Agent<G>>> + 'static,
+    ) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            config,
+            new_agent: Box::new(new_agent),
+            baseline: None,
+            evaluated: None,
+            best_strength: 0.,
+            early_stopping: None,
+        }
+    }
+
+    /// Ask training to stop once the strength plateaus.
+    pub fn with_early_stopping(mut self, early_stopping: EarlyStopping) -> Self {
+        self.early_stopping = Some(early_stopping);
+        self
+    }
+
+    /// Play every checkpoint against `baseline` instead of the best one.
+    pub fn with_baseline(mut self, baseline: Box<dyn Agent<G>>) -> Self {
+        self.baseline = Some(baseline);
+        self
+    }
+
+    /// Evaluate the latest checkpoint, unless it was evaluated before. The first checkpoint
+    /// becomes the best without a match when playing against the best.
+    pub fn evaluate_latest(&mut self, new_game: impl Fn() -> G) -> Result<Option<Evaluation>> {
+        let Some(latest) = checkpoint::latest(&self.root)? else {
+            return Ok(None);
+        };
+        if self.evaluated.as_ref() == Some(&latest) {
+            return Ok(None);
+        }
+        let best = best(&self.root)?;
+        if self.baseline.is_none() && best.as_ref() == Some(&latest) {
+            self.evaluated = Some(latest);
+            return Ok(None);
+        }
+        let checkpoint = Checkpoint::load(&latest)?;
+        let mut agent = (self.new_agent)(&checkpoint)?;
+        let games = self.config.games;
+        let result = match (&mut self.baseline, &best) {
+            (Some(baseline), _) => Some(arena::run(
+                new_game,
+                agent.as_mut(),
+                baseline.as_mut(),
+                games,
+                None,
+                |_| {},
+            )?),
+            (None, Some(best)) => {
+                let mut opponent = (self.new_agent)(&Checkpoint::load(best)?)?;
+                Some(arena::run(
+                    new_game,
+                    agent.as_mut(),
+                    opponent.as_mut(),
+                    games,
+                    None,
+                    |_| {},
+                )?)
+            }
+            (None, None) => None,
+        };
+        let promoted = result.is_none_or(|result| result.score() >= self.config.threshold);
+        let strength = match (&self.baseline, &result) {
+            (Some(_), Some(result)) => elo(result),
+            (_, Some(result)) => self.best_strength + elo(result),
+            (_, None) => self.best_strength,
+        };
+        if promoted {
+            set_best(&self.root, &latest)?;
+            if self.baseline.is_none() {
+                self.best_strength = strength;
+            }
+        }
+        let stop = match &mut self.early_stopping {
+            Some(early_stopping) => early_stopping.update(strength),
+            None => false,
+        };
+        if stop {
+            let patience = self.early_stopping.unwrap().patience;
+            let reason = format!("no stronger checkpoint in {} evaluations", patience);
+            request_stop(&self.root, &reason)?;
+        }
+        self.evaluated = Some(latest.clone());
+        Ok(Some(Evaluation {
+            checkpoint: latest,
+            step: checkpoint.step,
+            result,
+            promoted,
+            strength,
+            stop,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agent::RandomAgent,
+        checkpoint::Tensor,
+        games::tic_tac_toe::TicTacToe,
+        mcts::{Mcts, MctsConfig},
+    };
+
+    /// A checkpoint whose single weight says whether it plays well.
+    fn save(root: &Path, step: usize, strong: bool) {
+        let weight = Tensor::new(vec![1], vec![strong as u8 as f32]).unwrap();
+        Checkpoint {
+            step,
+            weights: vec![("strong".to_string(), weight)],
+            optimizer: vec![],
+            replay_buffer: serde_json::Value::Null,
+            run: serde_json::Value::Null,
+        }
+        .save(&checkpoint::step_dir(root, step))
+        .unwrap();
+    }
+
+    fn new_agent(checkpoint: &Checkpoint) -> Result<Box<dyn Agent<TicTacToe>>> {
+        Ok(match checkpoint.weights[0].1.data[0] {
+            0. => Box::new(RandomAgent),
+            _ => Box::new(Mcts::with_config(MctsConfig {
+                num_simulations: 300,
+                ..Default::default()
+            })),
+        })
+    }
+
+    #[test]
+    fn test_gating() {
+        let root = std::env::temp_dir().join(format!("muzero-gating-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let config = GatingConfig {
+            games: 10,
+            threshold: 0.55,
+        };
+        let mut evaluator = Evaluator::new(&root, config, new_agent);
+        assert_eq!(evaluator.evaluate_latest(TicTacToe::new).unwrap(), None);
+
+        save(&root, 100, false);
+        let evaluation = evaluator.evaluate_latest(TicTacToe::new).unwrap().unwrap();
+        assert_eq!((evaluation.result, evaluation.promoted), (None, true));
+        assert_eq!(evaluator.evaluate_latest(TicTacToe::new).unwrap(), None);
+
+        save(&root, 200, true);
+        let evaluation = evaluator.evaluate_latest(TicTacToe::new).unwrap().unwrap();
+        assert_eq!(evaluation.step, 200);
+        assert!(evaluation.promoted, "{:?}", evaluation.result);
+        assert_eq!(evaluation.result.unwrap().games(), 10);
+        let best_strength = evaluation.strength;
+        assert!(best_strength > 0., "{:?}", evaluation);
+
+        save(&root, 300, false);
+        let evaluation = evaluator.evaluate_latest(TicTacToe::new).unwrap().unwrap();
+        assert!(!evaluation.promoted, "{:?}", evaluation.result);
+        assert_eq!(best(&root).unwrap(), Some(checkpoint::step_dir(&root, 200)));
+
+        assert!(evaluation.strength < best_strength, "{:?}", evaluation);
+        assert_eq!(stop_requested(&root).unwrap(), None);
+
+        // Against a fixed baseline, every checkpoint plays, the best one included.
+        let mut evaluator =
+            Evaluator::new(&root, config, new_agent).with_baseline(Box::new(RandomAgent));
+        let evaluation = evaluator.evaluate_latest(TicTacToe::new).unwrap().unwrap();
+        assert_eq!(evaluation.step, 300);
+        assert!(evaluation.result.is_some());
+
+        // An evaluation without improvement asks training to stop.
+        let mut evaluator = Evaluator::new(&root, config, new_agent)
+            .with_baseline(Box::new(RandomAgent))
+            .with_early_stopping(EarlyStopping::new(1, 1000.));
+        save(&root, 400, false);
+        let evaluation = evaluator.evaluate_latest(TicTacToe::new).unwrap().unwrap();
+        assert!(!evaluation.stop, "{:?}", evaluation);
+        save(&root, 500, false);
+        let evaluation = evaluator.evaluate_latest(TicTacToe::new).unwrap().unwrap();
+        assert!(evaluation.stop, "{:?}", evaluation);
+        assert!(stop_requested(&root).unwrap().is_some());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_early_stopping() {
+        let mut early_stopping = EarlyStopping::new(2, 10.);
+        assert!(!early_stopping.update(0.));
+        assert!(!early_stopping.update(50.));
+        assert!(!early_stopping.update(55.));
+        assert!(!early_stopping.update(70.));
+        assert!(!early_stopping.update(-20.));
+        assert!(early_stopping.update(75.));
+    }
+}
@@ -0,0 +1,282 @@
+//! The Go Text Protocol, so that GUIs like Sabaki or GoGui, and other engines like GNU Go or
+//! KataGo, can play against the search on [`Go`].
+//!
+//! Points are named by a column letter, skipping I, and a row number counted from the
+//! bottom, e.g. `D4`; boards are 1x1 to 25x25.
+
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    error::{ParseError, Result},
+    game::Game,
+    games::go::{Go, Move, Player},
+    mcts::{Mcts, MctsConfig},
+};
+
+const COMMANDS: [&str; 14] = [
+    "boardsize",
+    "clear_board",
+    "final_score",
+    "genmove",
+    "known_command",
+    "komi",
+    "list_commands",
+    "name",
+    "play",
+    "protocol_version",
+    "quit",
+    "showboard",
+    "undo",
+    "version",
+];
+
+const COLUMNS: &[u8] = b"ABCDEFGHJKLMNOPQRSTUVWXYZ";
+
+/// A GTP engine: the game so far and the search that generates moves.
+pub struct GtpEngine {
+    game: Go,
+    /// The game before every move, for `undo`.
+    history: Vec<Go>,
+    mcts: Mcts<Go>,
+}
+
+fn parse_color(color: &str) -> Result<Player> {
+    match color.to_lowercase().as_str() {
+        "b" | "black" => Ok(Player::Black),
+        "w" | "white" => Ok(Player::White),
+        _ => Err(ParseError::Invalid("invalid color".to_string()).into()),
+    }
+}
+
+/// The move at the GTP vertex `vertex` on a `size` by `size` board.
+pub fn parse_vertex(vertex: &str, size: usize) -> Result<Move, ParseError> {
+    let vertex = vertex.to_uppercase();
+    if vertex == "PASS" {
+        return Ok(Move::Pass);
+    }
+    let invalid = || ParseError::Invalid("invalid coordinate".to_string());
+    let (&column, number) = vertex.as_bytes().split_first().ok_or_else(invalid)?;
+    let col = COLUMNS
+        .iter()
+        .position(|&c| c == column)
+        .ok_or_else(invalid)?;
+    let number: usize = std::str::from_utf8(number)
+        .ok()
+        .and_then(|number| number.parse().ok())
+        .ok_or_else(invalid)?;
+    if col >= size || number == 0 || number > size {
+        return Err(invalid());
+    }
+    Ok(Move::Place(size - number, col))
+}
+
+pub fn format_vertex(action: Move, size: usize) -> String {
+    match action {
+        Move::Place(row, col) => format!("{}{}", COLUMNS[col] as char, size - row),
+        Move::Pass => "pass".to_string(),
+    }
+}
+
+impl GtpEngine {
+    pub fn new(config: MctsConfig) -> Self {
+        Self {
+            game: Go::new(19),
+            history: vec![],
+            mcts: Mcts::with_config(config),
+        }
+    }
+
+    /// The response to the command `command` with its `args`, `Err` for a failure response.
+    fn execute(&mut self, command: &str, args: &[&str]) -> Result<String> {
+        let size = self.game.size();
+        match command {
+            "protocol_version" => Ok("2".to_string()),
+            "name" => Ok("muzero-rs".to_string()),
+            "version" => Ok(env!("CARGO_PKG_VERSION").to_string()),
+            "known_command" => Ok(COMMANDS
+                .contains(&args.first().copied().unwrap_or(""))
+                .to_string()),
+            "list_commands" => Ok(COMMANDS.join("\n")),
+            "quit" => Ok(String::new()),
+            "boardsize" => {
+                let size: usize = args
+                    .first()
+                    .and_then(|size| size.parse().ok())
+                    .ok_or_else(|| ParseError::Invalid("boardsize not an integer".to_string()))?;
+                if !(1..=COLUMNS.len()).contains(&size) {
+                    return Err(ParseError::Invalid("unacceptable size".to_string()).into());
+                }
+                self.game = Go::with_komi(size, self.game.komi());
+                self.history.clear();
+                Ok(String::new())
+            }
+            "clear_board" => {
+                self.game = Go::with_komi(size, self.game.komi());
+                self.history.clear();
+                Ok(String::new())
+            }
+            "komi" => {
+                let komi: f32 = args
+                    .first()
+                    .and_then(|komi| komi.parse().ok())
+                    .ok_or_else(|| ParseError::Invalid("komi not a float".to_string()))?;
+                // Komi only counts at the end, so the moves so far are kept.
+                for game in self.history.iter_mut().chain([&mut self.game]) {
+                    game.set_komi(komi);
+                }
+                Ok(String::new())
+            }
+            "play" => {
+                let [color, vertex] = args else {
+                    return Err(ParseError::Invalid("syntax error".to_string()).into());
+                };
+                let player = parse_color(color)?;
+                let action = parse_vertex(vertex, size)?;
+                self.play(player, action)
+                    .map_err(|_| ParseError::Invalid("illegal move".to_string()))?;
+                Ok(String::new())
+            }
+            "genmove" => {
+                let [color] = args else {
+                    return Err(ParseError::Invalid("syntax error".to_string()).into());
+                };
+                let player = parse_color(color)?;
+                let mut game = self.game.clone();
+                game.set_current_player(player);
+                if game.done() {
+                    return Ok(format_vertex(Move::Pass, size));
+                }
+                let action = self.mcts.search(&game).action;
+                self.play(player, action)?;
+                Ok(format_vertex(action, size))
+            }
+            "undo" => {
+                self.game = self
+                    .history
+                    .pop()
+                    .ok_or_else(|| ParseError::Invalid("cannot undo".to_string()))?;
+                Ok(String::new())
+            }
+            "showboard" => Ok(format!("\n{}", self.game.to_string().trim_end())),
+            "final_score" => {
+                let (black, white) = self.game.score();
+                Ok(match black - white {
+                    margin if margin > 0. => format!("B+{}", margin),
+                    margin if margin < 0. => format!("W+{}", -margin),
+                    _ => "0".to_string(),
+                })
+            }
+            _ => Err(ParseError::Invalid("unknown command".to_string()).into()),
+        }
+    }
+
+    fn play(&mut self, player: Player, action: Move) -> Result<()> {
+        let mut game = self.game.clone();
+        game.set_current_player(player);
+        game.step(action)?;
+        self.history.push(std::mem::replace(&mut self.game, game));
+        Ok(())
+    }
+
+    /// Answer the commands of `input` on `output` until `quit` or the end of the input.
+    pub fn run(&mut self, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<()> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let line: String = line
+                .split('#')
+                .next()
+                .unwrap_or_default()
+                .chars()
+                .filter(|c| !c.is_control() || *c == '\t')
+                .map(|c| if c == '\t' { ' ' } else { c })
+                .collect();
+            let mut words = line.split_whitespace();
+            let Some(first) = words.next() else {
+                continue;
+            };
+            let (id, command) = match first.parse::<u32>() {
+                Ok(id) => (Some(id), words.next().unwrap_or_default()),
+                Err(_) => (None, first),
+            };
+            let args: Vec<_> = words.collect();
+            let id = id.map(|id| id.to_string()).unwrap_or_default();
+            match self.execute(command, &args) {
+                Ok(response) => write!(output, "={} {}\n\n", id, response)?,
+                Err(e) => write!(output, "?{} {}\n\n", id, e)?,
+            }
+            output.flush()?;
+            if command == "quit" {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vertex() {
+        assert_eq!(parse_vertex("A1", 9).unwrap(), Move::Place(8, 0));
+        assert_eq!(parse_vertex("j9", 9).unwrap(), Move::Place(0, 8));
+        assert_eq!(parse_vertex("PASS", 9).unwrap(), Move::Pass);
+        for vertex in ["I1", "A0", "A10", "K1", "", "A"] {
+            assert!(parse_vertex(vertex, 9).is_err(), "{}", vertex);
+        }
+        assert_eq!(format_vertex(Move::Place(0, 8), 9), "J9");
+        assert_eq!(format_vertex(Move::Place(18, 3), 19), "D1");
+    }
+
+    fn session(engine: &mut GtpEngine, commands: &str) -> Vec<String> {
+        let mut output = vec![];
+        engine.run(&mut commands.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.ends_with("\n\n"));
+        output
+            .trim_end()
+            .split("\n\n")
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_session() {
+        let mut engine = GtpEngine::new(MctsConfig {
+            num_simulations: 50,
+            ..Default::default()
+        });
+        let responses = session(
+            &mut engine,
+            "1 protocol_version\n\
+             # a comment\n\
+             boardsize 5\n\
+             komi 0.5\n\
+             play black C3\n\
+             play white C3\n\
+             2 known_command genmove\n\
+             genmove w\n\
+             frobnicate\n",
+        );
+        assert_eq!(responses[..5], ["=1 2", "= ", "= ", "= ", "? illegal move"]);
+        assert_eq!(responses[5], "=2 true");
+        let vertex = responses[6].strip_prefix("= ").unwrap();
+        assert!(parse_vertex(vertex, 5).is_ok(), "{}", vertex);
+        assert_eq!(responses[7], "? unknown command");
+
+        let responses = session(
+            &mut engine,
+            "undo\nundo\nundo\nshowboard\nfinal_score\nquit\nname\n",
+        );
+        assert_eq!(responses[..3], ["= ", "= ", "? cannot undo"]);
+        assert!(responses[3].contains(". . . . ."));
+        // The board is empty, so White wins by komi.
+        assert_eq!(responses[4], "= W+0.5");
+        // Nothing is answered after quit.
+        assert_eq!(responses.len(), 6);
+    }
+}
@@ -0,0 +1,240 @@
+//! Records of played games, the training data of MuZero.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{error::GameError, game::Game, muzero::SearchStatistics};
+
+/// A game played by the agent together with the search statistics of every move, as stored
+/// for training. This is the `Game` class of the MuZero pseudocode, without the environment.
+///
+/// Actions are stored as indices into the game's action space so that histories of every
+/// game can share a replay buffer.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "UncheckedHistory"))]
+pub struct GameHistory {
+    /// The observation before each move.
+    pub observations: Vec<Vec<f32>>,
+    /// The index of the player to play before each move.
+    pub to_play: Vec<usize>,
+    pub actions: Vec<usize>,
+    /// The reward returned by each move.
+    pub rewards: Vec<f32>,
+    /// The root visit distribution over the action space for each move.
+    pub child_visits: Vec<Vec<f32>>,
+    /// The root value of the search for each move.
+    pub root_values: Vec<f32>,
+    /// The legal action indices before each move, which reanalyzing searches again. Empty
+    /// for games recorded without them.
+    pub legal_actions: Vec<Vec<usize>>,
+    /// If the episode was truncated rather than terminated, the value of the state after the
+    /// last move from the perspective of the player who made it, to bootstrap from.
+    pub final_value: Option<f32>,
+}
+
+impl GameHistory {
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// Record the current state of `game` and the search that chose `action`, then play it.
+    pub fn apply<T: Game>(
+        &mut self,
+        game: &mut T,
+        action: T::Action,
+        stats: &SearchStatistics,
+    ) -> Result<(), GameError> {
+        let observation = game.observation();
+        let to_play = game.player_index(&game.current_player());
+        let action_index = game.action_to_index(&action);
+        let legal_actions = game
+            .get_available_moves()
+            .iter()
+            .map(|action| game.action_to_index(action))
+            .collect();
+        let reward = game.step(action)?;
+        self.observations.push(observation);
+        self.to_play.push(to_play);
+        self.actions.push(action_index);
+        self.rewards.push(reward);
+        self.child_visits.push(stats.policy());
+        self.root_values.push(stats.root_value);
+        self.legal_actions.push(legal_actions);
+        Ok(())
+    }
+
+    /// Mark the episode as cut off by a step limit in a state worth `value` to the player
+    /// who made the last move.
+    pub fn truncate(&mut self, value: f32) {
+        self.final_value = Some(value);
+    }
+
+    /// End the episode with `player` resigning before its move, in a two-player zero-sum game:
+    /// the state after the last move is lost for it.
+    pub fn resign(&mut self, player: usize) {
+        let last_mover = self.to_play.last() == Some(&player);
+        self.truncate(if last_mover { -1. } else { 1. });
+    }
+
+    /// The n-step return target for the value at `index`, from the perspective of the player
+    /// to play there: the discounted rewards of the next `td_steps` moves plus the discounted
+    /// root value `td_steps` moves later. Past the end of the episode, the bootstrap value is
+    /// 0 if it terminated and [`GameHistory::final_value`] if it was truncated.
+    pub fn value_target(&self, index: usize, td_steps: usize, discount: f32) -> f32 {
+        let player = self.to_play[index];
+        let sign = |other: usize| if other == player { 1. } else { -1. };
+        let bootstrap_index = index + td_steps;
+        let mut value = if bootstrap_index < self.len() {
+            sign(self.to_play[bootstrap_index])
+                * self.root_values[bootstrap_index]
+                * discount.powi(td_steps as i32)
+        } else {
+            match (self.final_value, self.to_play.last()) {
+                (Some(final_value), Some(&last)) => {
+                    sign(last) * final_value * discount.powi((self.len() - index) as i32)
+                }
+                _ => 0.,
+            }
+        };
+        let end = bootstrap_index.min(self.len());
+        for i in index..end {
+            value += sign(self.to_play[i]) * self.rewards[i] * discount.powi((i - index) as i32);
+        }
+        value
+    }
+}
+
+/// A deserialized [`GameHistory`] before its fields are checked to have a value per move.
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub(crate) struct UncheckedHistory {
+    pub(crate) observations: Vec<Vec<f32>>,
+    pub(crate) to_play: Vec<usize>,
+    pub(crate) actions: Vec<usize>,
+    pub(crate) rewards: Vec<f32>,
+    pub(crate) child_visits: Vec<Vec<f32>>,
+    pub(crate) root_values: Vec<f32>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) legal_actions: Vec<Vec<usize>>,
+    pub(crate) final_value: Option<f32>,
+}
+
+impl TryFrom<UncheckedHistory> for GameHistory {
+    type Error = String;
+
+    fn try_from(history: UncheckedHistory) -> Result<Self, String> {
+        let len = history.actions.len();
+        if [
+            history.observations.len(),
+            history.to_play.len(),
+            history.rewards.len(),
+            history.child_visits.len(),
+            history.root_values.len(),
+        ]
+        .iter()
+        .any(|&other| other != len)
+            || !history.legal_actions.is_empty() && history.legal_actions.len() != len
+        {
+            return Err("game history fields have different lengths".to_string());
+        }
+        Ok(GameHistory {
+            observations: history.observations,
+            to_play: history.to_play,
+            actions: history.actions,
+            rewards: history.rewards,
+            child_visits: history.child_visits,
+            root_values: history.root_values,
+            legal_actions: history.legal_actions,
+            final_value: history.final_value,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_round_trip() {
+        use crate::games::tic_tac_toe::TicTacToe;
+
+        let mut game = TicTacToe::new();
+        let mut history = GameHistory::default();
+        let mut visit_counts = vec![1; 9];
+        visit_counts[4] = 10;
+        let stats = SearchStatistics {
+            root_value: 0.25,
+            visit_counts,
+            depth: 1,
+            nodes: 2,
+            cache: None,
+        };
+        history.apply(&mut game, (1, 1), &stats).unwrap();
+        history.apply(&mut game, (0, 0), &stats).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.actions, vec![4, 0]);
+        assert_eq!(history.to_play, vec![0, 1]);
+        assert_eq!(history.legal_actions[1], [0, 1, 2, 3, 5, 6, 7, 8]);
+
+        let text = serde_json::to_string(&history).unwrap();
+        let restored: GameHistory = serde_json::from_str(&text).unwrap();
+        assert_eq!(restored, history);
+
+        let mut truncated = serde_json::to_value(&history).unwrap();
+        truncated["actions"] = serde_json::json!([]);
+        assert!(serde_json::from_value::<GameHistory>(truncated).is_err());
+
+        // Recorded before legal actions were.
+        let mut old = serde_json::to_value(&history).unwrap();
+        old.as_object_mut().unwrap().remove("legal_actions");
+        let restored: GameHistory = serde_json::from_value(old).unwrap();
+        assert!(restored.legal_actions.is_empty());
+    }
+
+    fn single_player_history(rewards: &[f32]) -> GameHistory {
+        let len = rewards.len();
+        GameHistory {
+            observations: vec![vec![]; len],
+            to_play: vec![0; len],
+            actions: vec![0; len],
+            rewards: rewards.to_vec(),
+            child_visits: vec![vec![]; len],
+            root_values: (0..len).map(|i| 10. * i as f32).collect(),
+            final_value: None,
+            legal_actions: vec![],
+        }
+    }
+
+    #[test]
+    fn test_value_target() {
+        let mut history = single_player_history(&[1., 2., 3.]);
+        assert_eq!(history.value_target(0, 2, 0.5), 1. + 2. * 0.5 + 20. * 0.25);
+        // The episode terminated, so nothing follows the last reward.
+        assert_eq!(history.value_target(1, 5, 0.5), 2. + 3. * 0.5);
+        // A truncated episode bootstraps from the value of the state it stopped in.
+        history.truncate(8.);
+        assert_eq!(history.value_target(1, 5, 0.5), 2. + 3. * 0.5 + 8. * 0.25);
+
+        let history = GameHistory {
+            to_play: vec![0, 1, 0],
+            ..single_player_history(&[0., 0., 1.])
+        };
+        // The last move's reward goes to player 0 and counts against player 1.
+        assert_eq!(history.value_target(1, 5, 1.), -1.);
+        assert_eq!(history.value_target(0, 1, 1.), -10.);
+
+        // Player 1 resigns instead of playing on.
+        let mut history = GameHistory {
+            to_play: vec![0, 1, 0],
+            ..single_player_history(&[0., 0., 0.])
+        };
+        history.resign(1);
+        assert_eq!(history.value_target(1, 5, 1.), -1.);
+        assert_eq!(history.value_target(2, 5, 1.), 1.);
+    }
+}
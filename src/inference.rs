@@ -0,0 +1,369 @@
+//! An inference server batching the network evaluations of many searches.
+//!
+//! Every search thread evaluates leaves through its own [`InferenceClient`], which is a
+//! [`Network`] sending each request to the server and waiting for the answer. The server
+//! gathers requests until a batch is full or the oldest request waited long enough, then
+//! evaluates them with one call to the batch methods of the network.
+//!
+//! A client sends all the requests of a batch before waiting for any of them, and
+//! [`Network::submit_recurrent_batch`] returns without waiting at all, so a search can descend
+//! to its next leaves while the server evaluates the last ones, see
+//! [`MuZeroConfig::overlap_evaluation`].
+//!
+//! [`MuZeroConfig::overlap_evaluation`]: crate::muzero::MuZeroConfig::overlap_evaluation
+
+use std::{
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::network::{Network, NetworkOutput, Pending};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchConfig {
+    /// Evaluate a batch as soon as it has this many requests.
+    pub max_batch_size: usize,
+    /// Evaluate a batch once its first request waited this long, even if it isn't full.
+    pub max_wait: Duration,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_batch_size: 64,
+            max_wait: Duration::from_millis(2),
+        }
+    }
+}
+
+/// How batching went so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct InferenceStats {
+    pub batches: usize,
+    pub evaluations: usize,
+    pub max_batch_size: usize,
+}
+
+impl InferenceStats {
+    pub fn mean_batch_size(&self) -> f64 {
+        self.evaluations as f64 / self.batches.max(1) as f64
+    }
+}
+
+enum Input {
+    Initial(Vec<f32>),
+    Recurrent(Vec<f32>, usize),
+}
+
+struct Request {
+    input: Input,
+    answer: Sender<NetworkOutput>,
+}
+
+/// Owns the network on a thread of its own, which runs until the server and all its
+/// clients are dropped.
+pub struct InferenceServer {
+    requests: Sender<Request>,
+    stats: Arc<Mutex<InferenceStats>>,
+}
+
+impl InferenceServer {
+    pub fn spawn<N: Network + Send + 'static>(network: N, config: BatchConfig) -> Self {
+        let (requests, receiver) = mpsc::channel();
+        let stats = Arc::new(Mutex::new(InferenceStats::default()));
+        let shared = stats.clone();
+        thread::spawn(move || {
+            while let Some(batch) = next_batch(&receiver, &config) {
+                evaluate(&network, batch, &shared);
+            }
+        });
+        Self { requests, stats }
+    }
+
+    /// A network for one search thread.
+    pub fn client(&self) -> InferenceClient {
+        InferenceClient {
+            requests: self.requests.clone(),
+        }
+    }
+
+    pub fn stats(&self) -> InferenceStats {
+        *self.stats.lock().unwrap()
+    }
+}
+
+/// Wait for a request, then gather more until the batch is full or `max_wait` passed.
+/// Returns `None` once every sender is gone.
+fn next_batch(receiver: &Receiver<Request>, config: &BatchConfig) -> Option<Vec<Request>> {
+    let first = receiver.recv().ok()?;
+    let deadline = Instant::now() + config.max_wait;
+    let mut batch = vec![first];
+    while batch.len() < config.max_batch_size {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(timeout) {
+            Ok(request) => batch.push(request),
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    Some(batch)
+}
+
+fn evaluate(network: &impl Network, batch: Vec<Request>, stats: &Mutex<InferenceStats>) {
+    let mut initial = (vec![], vec![]);
+    let mut recurrent = (vec![], vec![]);
+    for request in &batch {
+        match &request.input {
+            Input::Initial(observation) => {
+                initial.0.push(&observation[..]);
+                initial.1.push(&request.answer);
+            }
+            Input::Recurrent(hidden_state, action) => {
+                recurrent.0.push((&hidden_state[..], *action));
+                recurrent.1.push(&request.answer);
+            }
+        }
+    }
+    let mut outputs = vec![];
+    if !initial.0.is_empty() {
+        outputs.extend(
+            network
+                .initial_inference_batch(&initial.0)
+                .into_iter()
+                .zip(initial.1),
+        );
+    }
+    if !recurrent.0.is_empty() {
+        outputs.extend(
+            network
+                .recurrent_inference_batch(&recurrent.0)
+                .into_iter()
+                .zip(recurrent.1),
+        );
+    }
+    {
+        let mut stats = stats.lock().unwrap();
+        stats.batches += 1;
+        stats.evaluations += batch.len();
+        stats.max_batch_size = stats.max_batch_size.max(batch.len());
+    }
+    for (output, answer) in outputs {
+        // The client may have given up waiting.
+        let _ = answer.send(output);
+    }
+}
+
+/// Evaluates through an [`InferenceServer`], blocking until its batch is done.
+#[derive(Clone)]
+pub struct InferenceClient {
+    requests: Sender<Request>,
+}
+
+impl InferenceClient {
+    fn send(&self, input: Input) -> Pending {
+        let (answer, receiver) = mpsc::channel();
+        self.requests
+            .send(Request { input, answer })
+            .expect("the inference server stopped");
+        Pending::Receiving(receiver)
+    }
+}
+
+impl Network for InferenceClient {
+    fn initial_inference(&self, observation: &[f32]) -> NetworkOutput {
+        self.send(Input::Initial(observation.to_vec())).wait()
+    }
+
+    fn recurrent_inference(&self, hidden_state: &[f32], action: usize) -> NetworkOutput {
+        self.send(Input::Recurrent(hidden_state.to_vec(), action))
+            .wait()
+    }
+
+    fn initial_inference_batch(&self, observations: &[&[f32]]) -> Vec<NetworkOutput> {
+        let pending: Vec<_> = observations
+            .iter()
+            .map(|observation| self.send(Input::Initial(observation.to_vec())))
+            .collect();
+        pending.into_iter().map(Pending::wait).collect()
+    }
+
+    fn recurrent_inference_batch(&self, inputs: &[(&[f32], usize)]) -> Vec<NetworkOutput> {
+        self.submit_recurrent_batch(inputs)
+            .into_iter()
+            .map(Pending::wait)
+            .collect()
+    }
+
+    fn submit_recurrent_batch(&self, inputs: &[(&[f32], usize)]) -> Vec<Pending> {
+        inputs
+            .iter()
+            .map(|&(hidden_state, action)| {
+                self.send(Input::Recurrent(hidden_state.to_vec(), action))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::muzero::{run_mcts, MuZeroConfig};
+    use std::sync::Barrier;
+
+    /// Echoes its input back in the hidden state, and records the size of every batch.
+    struct EchoNetwork {
+        batches: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl EchoNetwork {
+        fn output(hidden_state: Vec<f32>) -> NetworkOutput {
+            NetworkOutput {
+                value: 0.,
+                reward: 0.,
+                policy_logits: vec![0.; 3],
+                hidden_state,
+            }
+        }
+    }
+
+    impl Network for EchoNetwork {
+        fn initial_inference(&self, observation: &[f32]) -> NetworkOutput {
+            Self::output(observation.to_vec())
+        }
+
+        fn recurrent_inference(&self, hidden_state: &[f32], action: usize) -> NetworkOutput {
+            let mut state = hidden_state.to_vec();
+            state.push(action as f32);
+            Self::output(state)
+        }
+
+        fn initial_inference_batch(&self, observations: &[&[f32]]) -> Vec<NetworkOutput> {
+            self.batches.lock().unwrap().push(observations.len());
+            observations
+                .iter()
+                .map(|observation| self.initial_inference(observation))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_batching() {
+        let batches = Arc::new(Mutex::new(vec![]));
+        let network = EchoNetwork {
+            batches: batches.clone(),
+        };
+        let config = BatchConfig {
+            max_batch_size: 4,
+            max_wait: Duration::from_secs(10),
+        };
+        let server = InferenceServer::spawn(network, config);
+        let barrier = Arc::new(Barrier::new(8));
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let client = server.client();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    barrier.wait();
+                    client.initial_inference(&[i as f32]).hidden_state
+                })
+            })
+            .collect();
+        for (i, thread) in threads.into_iter().enumerate() {
+            assert_eq!(thread.join().unwrap(), [i as f32]);
+        }
+        // Full batches don't wait for `max_wait`.
+        assert_eq!(*batches.lock().unwrap(), [4, 4]);
+        assert_eq!(
+            server.stats(),
+            InferenceStats {
+                batches: 2,
+                evaluations: 8,
+                max_batch_size: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn test_max_wait() {
+        let network = EchoNetwork {
+            batches: Arc::new(Mutex::new(vec![])),
+        };
+        let config = BatchConfig {
+            max_batch_size: 64,
+            max_wait: Duration::from_millis(1),
+        };
+        let server = InferenceServer::spawn(network, config);
+        let client = server.client();
+        assert_eq!(client.recurrent_inference(&[1.], 2).hidden_state, [1., 2.]);
+        assert_eq!(client.initial_inference(&[3.]).hidden_state, [3.]);
+        assert_eq!(server.stats().batches, 2);
+    }
+
+    #[test]
+    fn test_submit() {
+        let network = EchoNetwork {
+            batches: Arc::new(Mutex::new(vec![])),
+        };
+        let config = BatchConfig {
+            max_batch_size: 3,
+            max_wait: Duration::from_secs(10),
+        };
+        let server = InferenceServer::spawn(network, config);
+        let client = server.client();
+        let pending = client.submit_recurrent_batch(&[(&[1.], 0), (&[2.], 1), (&[3.], 2)]);
+        let outputs: Vec<_> = pending
+            .into_iter()
+            .map(|pending| pending.wait().hidden_state)
+            .collect();
+        assert_eq!(outputs, [[1., 0.], [2., 1.], [3., 2.]]);
+        assert_eq!(server.stats().batches, 1);
+        assert_eq!(server.stats().max_batch_size, 3);
+    }
+
+    #[test]
+    fn test_search() {
+        let server = InferenceServer::spawn(
+            EchoNetwork {
+                batches: Arc::new(Mutex::new(vec![])),
+            },
+            BatchConfig::default(),
+        );
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let client = server.client();
+                thread::spawn(move || {
+                    let mut config = MuZeroConfig::board_game(3, 0.3);
+                    config.num_simulations = 20;
+                    run_mcts(&config, &client, &[0.], &[0, 1, 2], 0)
+                })
+            })
+            .collect();
+        for thread in threads {
+            let stats = thread.join().unwrap();
+            assert_eq!(stats.visit_counts.iter().sum::<usize>(), 20);
+        }
+        assert_eq!(server.stats().evaluations, 4 * 21);
+
+        // Overlapping, every search has leaves being evaluated while it descends.
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let client = server.client();
+                thread::spawn(move || {
+                    let mut config = MuZeroConfig::board_game(3, 0.3);
+                    config.num_simulations = 20;
+                    config.leaf_batch_size = 4;
+                    config.overlap_evaluation = true;
+                    run_mcts(&config, &client, &[0.], &[0, 1, 2], 0)
+                })
+            })
+            .collect();
+        for thread in threads {
+            let stats = thread.join().unwrap();
+            assert_eq!(stats.visit_counts.iter().sum::<usize>(), 20);
+        }
+        assert!(server.stats().max_batch_size > 1);
+    }
+}
@@ -0,0 +1,133 @@
+//! Moves typed on stdin, asked for again until the input makes sense.
+
+use anyhow::bail;
+use std::io::{self, BufRead, Write};
+
+use muzero_rs::dyn_game::DynGame;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Input {
+    Move(usize),
+    /// Show the best moves for the human.
+    Hint,
+    /// Show the agent's evaluation of the position.
+    Analyze,
+    Undo,
+    Quit,
+}
+
+/// Parse a line: a move number from the list, a move as the game names it with any
+/// punctuation left out, e.g. `1 2` for `(1, 2)`, `h` for a hint, `a` to analyze, `u` to undo
+/// or `q` to quit.
+fn parse(game: &dyn DynGame, line: &str) -> anyhow::Result<Input> {
+    let line = line.trim();
+    match line {
+        "" => bail!("type a move, h for a hint, a to analyze, u to undo or q to quit"),
+        "q" | "quit" => return Ok(Input::Quit),
+        "h" | "hint" => return Ok(Input::Hint),
+        "a" | "analyze" => return Ok(Input::Analyze),
+        "u" | "undo" => return Ok(Input::Undo),
+        _ => {}
+    }
+    let moves = game.legal_actions();
+    if let Ok(action) = line.parse::<usize>() {
+        if moves.contains(&action) {
+            return Ok(Input::Move(action));
+        }
+        if action < game.action_space_size() {
+            bail!("{} isn't a legal move", game.action_name(action));
+        }
+        bail!("there's no move {}", action);
+    }
+    let typed = words(line);
+    let named = |action: &usize| words(&game.action_name(*action)) == typed;
+    if let Some(&action) = moves.iter().find(|action| named(action)) {
+        return Ok(Input::Move(action));
+    }
+    if (0..game.action_space_size()).any(|action| named(&action)) {
+        bail!("{} isn't a legal move", line);
+    }
+    bail!("unknown move `{}`", line)
+}
+
+/// The lowercase words and numbers of `s`.
+fn words(s: &str) -> Vec<String> {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Show `game` and its moves on `output`, then read lines from `input` until one is valid.
+/// The end of the input quits.
+pub(crate) fn prompt(
+    game: &dyn DynGame,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<Input> {
+    writeln!(output, "{}", game)?;
+    for action in game.legal_actions() {
+        writeln!(output, "{:>4}: {}", action, game.action_name(action))?;
+    }
+    loop {
+        write!(output, "Your move: ")?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(Input::Quit);
+        }
+        match parse(game, &line) {
+            Ok(input) => return Ok(input),
+            Err(e) => writeln!(output, "{}", e)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use muzero_rs::{dyn_game::boxed, games::tic_tac_toe::TicTacToe};
+
+    #[test]
+    fn test_parse() {
+        let mut game = boxed(TicTacToe::new());
+        game.step(4).unwrap();
+        let game = game.as_ref();
+        assert_eq!(parse(game, "0\n").unwrap(), Input::Move(0));
+        assert_eq!(parse(game, " 2 1").unwrap(), Input::Move(7));
+        assert_eq!(parse(game, "(0, 2)").unwrap(), Input::Move(2));
+        assert_eq!(parse(game, "u").unwrap(), Input::Undo);
+        assert_eq!(parse(game, "q").unwrap(), Input::Quit);
+        assert_eq!(parse(game, "hint").unwrap(), Input::Hint);
+        assert_eq!(parse(game, "a").unwrap(), Input::Analyze);
+        for (line, error) in [
+            (
+                "",
+                "type a move, h for a hint, a to analyze, u to undo or q to quit",
+            ),
+            ("4", "(1, 1) isn't a legal move"),
+            ("1 1", "1 1 isn't a legal move"),
+            ("9", "there's no move 9"),
+            ("a b", "unknown move `a b`"),
+            ("-1", "unknown move `-1`"),
+        ] {
+            assert_eq!(parse(game, line).unwrap_err().to_string(), error);
+        }
+    }
+
+    #[test]
+    fn test_prompt() {
+        let game = boxed(TicTacToe::new());
+        let mut input: &[u8] = b"\nx\n9\n1 1\n";
+        let mut output = vec![];
+        let result = prompt(game.as_ref(), &mut input, &mut output).unwrap();
+        assert_eq!(result, Input::Move(4));
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("Your move: ").count(), 4);
+        assert!(output.contains("there's no move 9"));
+        assert_eq!(
+            prompt(game.as_ref(), &mut input, &mut vec![]).unwrap(),
+            Input::Quit
+        );
+    }
+}
@@ -0,0 +1,359 @@
+//! Training one run on several games at once, to check that the same algorithm learns all of
+//! them and to compare how many samples each needs. The games are played and trained on in
+//! turn, with a network per game or one shared by all of them, whose input and policy cover
+//! those of every game.
+
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use crate::{
+    checkpoint::Tensors,
+    error::{ConfigError, Error, ParseError, Result},
+    history::GameHistory,
+};
+
+/// A game of a joint run and the file of its self-play data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JointGame {
+    /// The name of the game in the registry, like `gomoku:9`.
+    pub game: String,
+    pub data: PathBuf,
+}
+
+/// The games of a joint run, written like `tictactoe=tictactoe.traj connect4=connect4.traj`:
+/// separated by spaces, as commas may be part of the name of a game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JointGames {
+    games: Vec<JointGame>,
+}
+
+impl JointGames {
+    pub fn new(games: Vec<JointGame>) -> Result<Self> {
+        if games.is_empty() {
+            return Err(ConfigError::Invalid("a joint run needs a game".to_string()).into());
+        }
+        for (i, game) in games.iter().enumerate() {
+            if games[..i].iter().any(|other| other.game == game.game) {
+                return Err(
+                    ConfigError::Invalid(format!("`{}` is listed twice", game.game)).into(),
+                );
+            }
+            if games[..i].iter().any(|other| other.data == game.data) {
+                return Err(ConfigError::Invalid(format!(
+                    "two games share {}",
+                    game.data.display()
+                ))
+                .into());
+            }
+        }
+        Ok(Self { games })
+    }
+
+    pub fn games(&self) -> &[JointGame] {
+        &self.games
+    }
+
+    pub fn len(&self) -> usize {
+        self.games.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty()
+    }
+
+    /// The index of the game whose turn is `round`, counting from 0, when every game gets
+    /// `interleave` turns in a row.
+    pub fn turn(&self, round: usize, interleave: usize) -> usize {
+        round / interleave.max(1) % self.games.len()
+    }
+
+    /// How many of the first `rounds` rounds are the turns of game `game`.
+    pub fn turns(&self, game: usize, rounds: usize, interleave: usize) -> usize {
+        let interleave = interleave.max(1);
+        let cycle = interleave * self.games.len();
+        let rest = (rounds % cycle).saturating_sub(game * interleave);
+        rounds / cycle * interleave + rest.min(interleave)
+    }
+}
+
+/// The tensors of the network of `game`, named as in a checkpoint of per-game networks:
+/// `<game>/<name>`.
+pub fn game_tensors(game: &str, tensors: Tensors) -> Tensors {
+    tensors
+        .into_iter()
+        .map(|(name, tensor)| (format!("{}/{}", game, name), tensor))
+        .collect()
+}
+
+/// The tensors of the network of `game` in a checkpoint of per-game networks, under their
+/// own names.
+pub fn split_game_tensors(game: &str, tensors: &Tensors) -> Tensors {
+    let prefix = format!("{}/", game);
+    tensors
+        .iter()
+        .filter_map(|(name, tensor)| {
+            Some((name.strip_prefix(&prefix)?.to_string(), tensor.clone()))
+        })
+        .collect()
+}
+
+impl fmt::Display for JointGames {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, game) in self.games.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}={}", game.game, game.data.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for JointGames {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let games = s
+            .split_whitespace()
+            .map(|entry| {
+                let (game, data) = entry
+                    .split_once('=')
+                    .filter(|(game, data)| !game.is_empty() && !data.is_empty())
+                    .ok_or_else(|| {
+                        ParseError::Invalid(format!("expected <game>=<data>, got `{}`", entry))
+                    })?;
+                Ok(JointGame {
+                    game: game.to_string(),
+                    data: PathBuf::from(data),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Self::new(games)
+    }
+}
+
+/// Whether the games of a joint run train one network or a network each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Networks {
+    Shared,
+    PerGame,
+}
+
+impl fmt::Display for Networks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Networks::Shared => write!(f, "shared"),
+            Networks::PerGame => write!(f, "per-game"),
+        }
+    }
+}
+
+impl FromStr for Networks {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        match s {
+            "shared" => Ok(Networks::Shared),
+            "per-game" => Ok(Networks::PerGame),
+            _ => Err(ParseError::Invalid(format!(
+                "expected shared or per-game, got `{}`",
+                s
+            ))),
+        }
+    }
+}
+
+/// The input and policy of a network shared by games with differently shaped observations
+/// and action spaces: the largest of every dimension and action space. An observation sits in
+/// the first entries of every dimension with zeros around it, and an action keeps its index,
+/// the actions past those of its game never being legal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedInput {
+    observation_shape: Vec<usize>,
+    action_space_size: usize,
+}
+
+impl SharedInput {
+    /// The input covering games with these observation shapes and action space sizes.
+    pub fn new<'a>(games: impl IntoIterator<Item = (&'a [usize], usize)>) -> Result<Self> {
+        let mut input: Option<Self> = None;
+        for (shape, action_space_size) in games {
+            if shape.is_empty() {
+                return Err(
+                    ConfigError::Invalid("observations must have a dimension".to_string()).into(),
+                );
+            }
+            match &mut input {
+                None => {
+                    input = Some(Self {
+                        observation_shape: shape.to_vec(),
+                        action_space_size,
+                    })
+                }
+                Some(input) => {
+                    if input.observation_shape.len() != shape.len() {
+                        return Err(ConfigError::Invalid(format!(
+                            "one network can't take observations of shapes {:?} and {:?}",
+                            input.observation_shape, shape
+                        ))
+                        .into());
+                    }
+                    for (max, &dim) in input.observation_shape.iter_mut().zip(shape) {
+                        *max = (*max).max(dim);
+                    }
+                    input.action_space_size = input.action_space_size.max(action_space_size);
+                }
+            }
+        }
+        Ok(input
+            .ok_or_else(|| ConfigError::Invalid("a shared network needs a game".to_string()))?)
+    }
+
+    pub fn observation_shape(&self) -> &[usize] {
+        &self.observation_shape
+    }
+
+    pub fn action_space_size(&self) -> usize {
+        self.action_space_size
+    }
+
+    /// `observation`, of a game with observations of `shape`, in the shared input.
+    pub fn observation(&self, shape: &[usize], observation: &[f32]) -> Vec<f32> {
+        let mut padded = vec![0.; self.observation_shape.iter().product()];
+        let width = shape[shape.len() - 1];
+        for (i, row) in observation.chunks(width).enumerate() {
+            // Where the row starts in the padded observation, from its index in every
+            // dimension but the last.
+            let mut offset = 0;
+            let mut stride = self.observation_shape[shape.len() - 1];
+            let mut rest = i;
+            for (&dim, &max) in shape.iter().zip(&self.observation_shape).rev().skip(1) {
+                offset += rest % dim * stride;
+                rest /= dim;
+                stride *= max;
+            }
+            padded[offset..offset + width].copy_from_slice(row);
+        }
+        padded
+    }
+
+    /// A distribution over the actions of a game, like the visits of a search, over the
+    /// shared action space.
+    pub fn policy(&self, policy: &[f32]) -> Vec<f32> {
+        let mut padded = policy.to_vec();
+        padded.resize(self.action_space_size, 0.);
+        padded
+    }
+
+    /// `history`, of a game with observations of `shape`, in the shared input.
+    pub fn history(&self, shape: &[usize], history: &GameHistory) -> GameHistory {
+        GameHistory {
+            observations: history
+                .observations
+                .iter()
+                .map(|observation| self.observation(shape, observation))
+                .collect(),
+            child_visits: history
+                .child_visits
+                .iter()
+                .map(|policy| self.policy(policy))
+                .collect(),
+            ..history.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::Game,
+        games::{connect_four::ConnectFour, tic_tac_toe::TicTacToe},
+        muzero::SearchStatistics,
+    };
+
+    #[test]
+    fn test_joint_games() {
+        let joint: JointGames = "tictactoe=ttt.traj  nim:1,2=nim.traj".parse().unwrap();
+        assert_eq!(joint.to_string(), "tictactoe=ttt.traj nim:1,2=nim.traj");
+        assert_eq!(joint.games()[1].game, "nim:1,2");
+        assert_eq!(joint.games()[1].data, PathBuf::from("nim.traj"));
+        let turns: Vec<_> = (0..6).map(|round| joint.turn(round, 2)).collect();
+        assert_eq!(turns, [0, 0, 1, 1, 0, 0]);
+        for rounds in 0..7 {
+            for game in 0..2 {
+                let turns = (0..rounds)
+                    .filter(|&round| joint.turn(round, 2) == game)
+                    .count();
+                assert_eq!(joint.turns(game, rounds, 2), turns);
+            }
+        }
+
+        for joint in [
+            "",
+            "tictactoe",
+            "=a.traj",
+            "tictactoe=",
+            "go=a go=b",
+            "go=a hex=a",
+        ] {
+            assert!(joint.parse::<JointGames>().is_err(), "{}", joint);
+        }
+        assert_eq!("per-game".parse::<Networks>().unwrap(), Networks::PerGame);
+        assert!("both".parse::<Networks>().is_err());
+    }
+
+    #[test]
+    fn test_game_tensors() {
+        let tensor = |value| crate::checkpoint::Tensor::new(vec![1], vec![value]).unwrap();
+        let mut tensors = game_tensors("nim:1,2", vec![("value.bias".to_string(), tensor(1.))]);
+        tensors.extend(game_tensors(
+            "nim",
+            vec![("value.bias".to_string(), tensor(2.))],
+        ));
+        assert_eq!(tensors[0].0, "nim:1,2/value.bias");
+        assert_eq!(
+            split_game_tensors("nim", &tensors),
+            [("value.bias".to_string(), tensor(2.))]
+        );
+        assert!(split_game_tensors("hex", &tensors).is_empty());
+    }
+
+    #[test]
+    fn test_shared_input() {
+        let tic_tac_toe = TicTacToe::new();
+        let connect_four = ConnectFour::new();
+        let shape = tic_tac_toe.observation_shape();
+        let input = SharedInput::new([
+            (shape.as_slice(), tic_tac_toe.action_space_size()),
+            (
+                connect_four.observation_shape().as_slice(),
+                connect_four.action_space_size(),
+            ),
+        ])
+        .unwrap();
+        assert_eq!(input.observation_shape(), [3, 6, 7]);
+        assert_eq!(input.action_space_size(), 9);
+
+        let mut game = TicTacToe::new();
+        let mut history = GameHistory::default();
+        let stats = SearchStatistics {
+            root_value: 0.,
+            visit_counts: vec![1; 9],
+            depth: 1,
+            nodes: 2,
+            cache: None,
+        };
+        history.apply(&mut game, (1, 2), &stats).unwrap();
+        history.apply(&mut game, (0, 0), &stats).unwrap();
+        let padded = input.history(&shape, &history);
+        assert_eq!(padded.observations[1].len(), 3 * 6 * 7);
+        // X's stone at (1, 2) in the first plane, rows of 7 points.
+        assert_eq!(padded.observations[1][7 + 2], 1.);
+        assert_eq!(padded.observations[1].iter().sum::<f32>(), 1.);
+        assert_eq!(padded.child_visits[0].len(), 9);
+        assert_eq!(padded.actions, history.actions);
+
+        assert!(SharedInput::new([(&[4][..], 2), (&[3, 3, 3][..], 9)]).is_err());
+        assert!(SharedInput::new([]).is_err());
+    }
+}
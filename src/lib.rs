@@ -0,0 +1,90 @@
+//! MuZero and Monte Carlo tree search for games implementing [`Game`].
+//!
+//! [`Mcts`] searches the real game with random playouts and needs nothing but the rules:
+//!
+//! ```
+//! use muzero_rs::{games::tic_tac_toe::TicTacToe, Game, Mcts};
+//!
+//! let mut game = TicTacToe::new();
+//! let mcts = Mcts::<TicTacToe>::new(100);
+//! while !game.done() {
+//!     let action = mcts.search(&game).action;
+//!     game.step(action).unwrap();
+//! }
+//! ```
+//!
+//! [`muzero`] searches with a learned model instead, behind the [`network::Network`] trait,
+//! and [`history::GameHistory`] records the games it plays for training.
+
+pub mod agent;
+pub mod arena;
+pub mod book;
+#[cfg(feature = "burn")]
+pub mod burn_model;
+pub mod cache;
+pub mod checkpoint;
+pub mod config;
+pub mod curriculum;
+#[cfg(all(not(target_arch = "wasm32"), feature = "serde"))]
+pub mod distributed;
+pub mod dyn_game;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(all(any(fuzzing, test), feature = "serde"))]
+pub mod fuzz;
+pub mod game;
+pub mod games;
+#[cfg(feature = "serde")]
+pub mod gating;
+pub mod gtp;
+pub mod history;
+pub mod inference;
+pub mod joint;
+pub mod loss;
+pub mod mcts;
+pub mod metrics;
+pub mod mlp;
+pub mod model;
+pub mod muzero;
+pub mod network;
+pub mod notation;
+pub mod observation;
+#[cfg(feature = "onnx")]
+pub mod onnx;
+pub mod optimizer;
+pub mod pgn;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod random;
+pub mod record;
+pub mod registry;
+pub mod replay;
+pub mod resign;
+pub mod rollout;
+pub mod scoring;
+#[cfg(feature = "serde")]
+pub mod server;
+pub mod sgf;
+pub mod solver;
+#[cfg(unix)]
+pub mod storage;
+pub mod strength;
+#[cfg(feature = "serde")]
+pub mod sweep;
+pub mod symmetry;
+pub mod testsuite;
+#[cfg(feature = "tch")]
+pub mod torch;
+pub mod training;
+pub mod trajectory;
+pub mod uci;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod zobrist;
+
+pub use error::{ConfigError, Error, GameError, IoError, ParseError, SearchError};
+pub use game::{Game, Symmetry, Undo};
+pub use mcts::{Mcts, MctsConfig};
@@ -0,0 +1,112 @@
+//! Losses of training, computed on network outputs and replay targets.
+
+use crate::network::ConsistencyNetwork;
+
+/// The terms of the MuZero loss of a batch: the squared error of the value and of the reward,
+/// and the cross-entropy of the policy against its target. Each is summed over the steps of
+/// an unroll, divided by their number, scaled by the sample's importance-sampling weight and
+/// averaged over the batch.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Losses {
+    pub value: f32,
+    pub reward: f32,
+    pub policy: f32,
+}
+
+impl Losses {
+    pub fn total(&self) -> f32 {
+        self.value + self.reward + self.policy
+    }
+}
+
+/// `-cos(a, b)`, 0 if either vector is zero.
+pub fn negative_cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0. {
+        0.
+    } else {
+        -dot / norms
+    }
+}
+
+/// The consistency loss of EfficientZero, averaged over the unroll: the negative cosine
+/// similarity between the predicted projection of each hidden state the dynamics predicted
+/// and the projection of the representation of the observation that followed.
+///
+/// The second projection is the target: a trainer must not propagate gradients through it.
+/// Steps past the end of `next_observations` are skipped.
+pub fn consistency_loss<N: ConsistencyNetwork>(
+    network: &N,
+    predicted_states: &[Vec<f32>],
+    next_observations: &[Vec<f32>],
+) -> f32 {
+    let losses: Vec<_> = predicted_states
+        .iter()
+        .zip(next_observations)
+        .map(|(state, observation)| {
+            let prediction = network.predict_projection(&network.project(state));
+            let target = network.project(&network.initial_inference(observation).hidden_state);
+            negative_cosine_similarity(&prediction, &target)
+        })
+        .collect();
+    if losses.is_empty() {
+        0.
+    } else {
+        losses.iter().sum::<f32>() / losses.len() as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{Network, NetworkOutput};
+
+    /// Represents observations as themselves and projects with the identity.
+    struct IdentityNetwork;
+
+    impl Network for IdentityNetwork {
+        fn initial_inference(&self, observation: &[f32]) -> NetworkOutput {
+            NetworkOutput {
+                value: 0.,
+                reward: 0.,
+                policy_logits: vec![],
+                hidden_state: observation.to_vec(),
+            }
+        }
+
+        fn recurrent_inference(&self, hidden_state: &[f32], _action: usize) -> NetworkOutput {
+            self.initial_inference(hidden_state)
+        }
+    }
+
+    impl ConsistencyNetwork for IdentityNetwork {
+        fn project(&self, hidden_state: &[f32]) -> Vec<f32> {
+            hidden_state.to_vec()
+        }
+
+        fn predict_projection(&self, projection: &[f32]) -> Vec<f32> {
+            projection.to_vec()
+        }
+    }
+
+    #[test]
+    fn test_negative_cosine_similarity() {
+        assert_eq!(negative_cosine_similarity(&[1., 0.], &[2., 0.]), -1.);
+        assert_eq!(negative_cosine_similarity(&[1., 0.], &[0., 3.]), 0.);
+        assert!((negative_cosine_similarity(&[1., 1.], &[-1., -1.]) - 1.).abs() < 1e-6);
+        assert_eq!(negative_cosine_similarity(&[0., 0.], &[1., 1.]), 0.);
+    }
+
+    #[test]
+    fn test_consistency_loss() {
+        let network = IdentityNetwork;
+        let predicted = [vec![1., 0.], vec![0., 1.], vec![1., 1.]];
+        // The last prediction has no observation to compare with.
+        let observed = [vec![3., 0.], vec![0., -1.]];
+        assert_eq!(consistency_loss(&network, &predicted, &observed), 0.);
+        assert_eq!(consistency_loss(&network, &predicted[..1], &observed), -1.);
+        assert_eq!(consistency_loss(&network, &predicted, &[]), 0.);
+    }
+}
@@ -1,52 +1,1454 @@
-mod game;
-mod mcts;
-mod tic_tac_toe;
+mod cli;
+mod input;
+mod tui;
+mod viewer;
 
-use std::io;
+use anyhow::{bail, Context};
+use rand::Rng;
+#[cfg(not(feature = "tch"))]
+use rand::{rngs::StdRng, SeedableRng};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, IsTerminal},
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::RecvTimeoutError,
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
 
-use mcts::Mcts;
-use tic_tac_toe::TicTacToe;
+use cli::{
+    AgentSpec, AnalyzeArgs, Command, ConvertArgs, EvalArgs, EvaluatorArgs, LearnerArgs, PlayArgs,
+    ReplayArgs, SelfPlayArgs, SweepArgs, TestSuiteArgs, TournamentArgs, TrainArgs, Ui,
+};
+use input::Input;
+#[cfg(not(feature = "tch"))]
+use muzero_rs::model::Device;
+#[cfg(feature = "prometheus")]
+use muzero_rs::prometheus::{self, SelfPlayMetrics};
+use muzero_rs::{
+    agent::{self, Agent, MuZeroAgent, RandomAgent},
+    arena::{self, SprtResult},
+    book::{BookAgent, OpeningBook},
+    checkpoint::{self, Checkpoint},
+    curriculum::{BoardPadding, Curriculum},
+    distributed::{Learner, LearnerClient},
+    dyn_game::DynGame,
+    error::ConfigError,
+    gating::{self, Evaluator},
+    gtp::GtpEngine,
+    history::GameHistory,
+    joint::{self, JointGames, Networks, SharedInput},
+    mcts::{sample_outcome, MctsConfig, SearchResult, SearchTree, TreeFormat},
+    metrics::MetricsConfig,
+    mlp::MlpModel,
+    model::{ModelNetwork, Trainable},
+    muzero::MuZeroConfig,
+    observation::FrameStacking,
+    random,
+    record::{GameRecord, MoveSearch},
+    registry::Registry,
+    replay::{ReplayBuffer, ReplayConfig, Snapshot},
+    resign::{ResignStats, Resignation},
+    server, sgf,
+    strength::Strength,
+    sweep::{self, Comparison, Outcome, SweepConfig},
+    testsuite,
+    training::{RunState, Trainer},
+    trajectory::{Summary, TrajectoryReader, TrajectoryWriter},
+    Game, Mcts,
+};
+#[cfg(feature = "chess")]
+use muzero_rs::{games::chess::Chess, uci::UciEngine};
+#[cfg(feature = "tch")]
+use muzero_rs::{model::Model, torch::TchModel};
+#[cfg(feature = "prometheus")]
+use std::sync::Arc;
 
-use crate::game::Game;
-use crate::tic_tac_toe::Player;
+type BoxedGame = Box<dyn DynGame>;
 
-fn main() -> anyhow::Result<()> {
-    env_logger::init();
+/// Training steps between the losses `train` prints.
+const LOG_INTERVAL: usize = 100;
+
+/// Makes new games named `spec` in the registry, which must be valid.
+fn game_factory(spec: &str) -> anyhow::Result<impl Fn() -> BoxedGame> {
+    let registry = Registry::default();
+    registry.create(spec)?;
+    let spec = spec.to_string();
+    Ok(move || registry.create(&spec).expect("the game was made before"))
+}
+
+/// The games of every stage of `curriculum`, `game` with the board size of the stage, and how
+/// to pad their histories to the largest board.
+fn curriculum_stages(
+    game: &str,
+    curriculum: &Curriculum,
+) -> anyhow::Result<Vec<(String, BoardPadding)>> {
+    if game.contains(':') {
+        bail!("--curriculum sets the board size of `{}`", game);
+    }
+    let registry = Registry::default();
+    let largest = registry.create(&format!("{}:{}", game, curriculum.max_size()))?;
+    curriculum
+        .stages()
+        .iter()
+        .map(|stage| {
+            let spec = format!("{}:{}", game, stage.size);
+            let small = registry.create(&spec)?;
+            let padding = BoardPadding::new(
+                &small.observation_shape(),
+                small.action_space_size(),
+                &largest.observation_shape(),
+                largest.action_space_size(),
+            )
+            .with_context(|| format!("`{}` has no curriculum over board sizes", game))?;
+            Ok((spec, padding))
+        })
+        .collect()
+}
 
-    let mut game = TicTacToe::new();
-    let mcts = Mcts::<TicTacToe>::new(100);
+/// Makes the agent `spec`. MCTS agents play forced moves and the replies of `book` without
+/// searching.
+fn new_agent(
+    spec: AgentSpec,
+    mcts: &MctsConfig,
+    book: Option<&OpeningBook>,
+) -> Box<dyn Agent<BoxedGame>> {
+    match spec {
+        AgentSpec::Random => Box::new(RandomAgent),
+        AgentSpec::Mcts { simulations } => Box::new(BookAgent {
+            book: book.cloned(),
+            agent: Mcts::with_config(MctsConfig {
+                num_simulations: simulations.unwrap_or(mcts.num_simulations),
+                ..mcts.clone()
+            })
+            .with_parallel_playouts(),
+        }),
+    }
+}
+
+fn load_book(
+    path: Option<&Path>,
+    new_game: impl Fn() -> BoxedGame,
+) -> anyhow::Result<Option<OpeningBook>> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let book = OpeningBook::load(path, new_game)?;
+    println!(
+        "loaded {} book positions from {}",
+        book.len(),
+        path.display()
+    );
+    Ok(Some(book))
+}
 
-    loop {
-        println!("{}", game);
+/// How `game` ended, for the humans playing the players in `humans`.
+fn outcome(game: &BoxedGame, humans: &[bool], score: f32) -> String {
+    let one_human = humans.iter().filter(|&&human| human).count() == 1;
+    match game.check_winner() {
+        Some(winner) if one_human && humans[winner] => "You win!".to_string(),
+        Some(_) if one_human => "The agent wins!".to_string(),
+        Some(winner) => format!("Player {} wins!", winner),
+        None if game.num_players() == 1 => format!("Score: {}", score),
+        None => "Draw!".to_string(),
+    }
+}
 
+/// A search value, for returns between -1 and 1, as a win rate, and in single-player games
+/// as the expected score.
+fn describe_value(game: &BoxedGame, value: f32) -> String {
+    if game.num_players() == 1 {
+        format!("expected score {:.2}", value)
+    } else {
+        format!("{:.0}% win rate", 50. * (value + 1.))
+    }
+}
+
+/// The `n` most visited moves of a search from `game`, one per line.
+fn hint(game: &BoxedGame, result: &SearchResult<usize>, n: usize) -> String {
+    result
+        .children
+        .iter()
+        .take(n)
+        .enumerate()
+        .map(|(i, child)| {
+            format!(
+                "{}. {}: {}, {} visits\n",
+                i + 1,
+                game.action_name(child.action),
+                describe_value(game, child.q),
+                child.visits
+            )
+        })
+        .collect()
+}
+
+/// The value of `game` for the player to move and the line the search expects.
+fn analysis(game: &BoxedGame, result: &SearchResult<usize>) -> String {
+    let line: Vec<_> = result
+        .principal_variation
+        .iter()
+        .map(|&action| game.action_name(action))
+        .collect();
+    let mut analysis = format!(
+        "Player {}: {}, expecting {}",
+        game.to_play(),
+        describe_value(game, result.value()),
+        line.join(" ")
+    );
+    if let Some(proof) = result.proof {
+        analysis += &format!(" ({})", proof);
+    }
+    analysis
+}
+
+impl PlayArgs {
+    /// The human plays the players of `human_side`, the agent every other player.
+    fn run(self, new_game: impl Fn() -> BoxedGame) -> anyhow::Result<()> {
+        let mut game = new_game();
+        let humans = self.human_side.humans(game.num_players())?;
+        let handicap = self.strength.map(Strength::handicap);
+        let config = match &handicap {
+            Some(handicap) => handicap.config(self.mcts.clone()),
+            None => self.mcts.clone(),
+        };
+        if self.ui == Ui::Tui {
+            return tui::run(game, config, humans, handicap);
+        }
+        let mcts = Mcts::<BoxedGame>::with_config(config).with_parallel_playouts();
+        // Hints come from a search at full strength.
+        let analyst = Mcts::<BoxedGame>::with_config(self.mcts).with_parallel_playouts();
+        let mut score = 0.;
+        let mut record = GameRecord::new(&self.game);
+        // The game, score and length of the record before each move of the human.
+        let mut history = vec![];
+        let mut stdin = io::stdin().lock();
+        // The tree searched while the human thought, advanced by the moves played since.
+        let mut pondered = None;
+        while !game.done() {
+            let outcomes = game.chance_outcomes();
+            if !outcomes.is_empty() {
+                let outcome = sample_outcome(&outcomes, &mut random::rng());
+                pondered = pondered.and_then(|tree: SearchTree<_>| tree.advance(&outcome));
+                record.push(outcome, None, None);
+                score += game.step(outcome)?;
+                continue;
+            }
+            let player = game.current_player();
+            if !humans[player] {
+                if !humans.contains(&true) {
+                    println!("{}", game);
+                }
+                let tree = pondered.take();
+                let result = match &self.dump_tree {
+                    Some(path) => {
+                        let format = if path
+                            .extension()
+                            .is_some_and(|extension| extension == "json")
+                        {
+                            TreeFormat::Json
+                        } else {
+                            TreeFormat::Dot
+                        };
+                        let (result, tree) = mcts.export_tree(&game, format);
+                        fs::write(path, tree)
+                            .with_context(|| format!("failed to write {}", path.display()))?;
+                        result
+                    }
+                    None => {
+                        if let Some(tree) = &tree {
+                            tracing::info!("reusing {} simulations from pondering", tree.visits());
+                        }
+                        mcts.search_from(&game, tree)
+                    }
+                };
+                let pv: Vec<_> = result
+                    .principal_variation
+                    .iter()
+                    .map(|&action| game.action_name(action))
+                    .collect();
+                tracing::info!(
+                    "pv {}, depth {} (mean {:.1}), {} nodes",
+                    pv.join(" "),
+                    result.max_depth,
+                    result.mean_depth,
+                    result.nodes
+                );
+                let action = match &handicap {
+                    Some(handicap) => {
+                        let visits: Vec<_> = result
+                            .children
+                            .iter()
+                            .map(|child| (child.action, child.visits))
+                            .collect();
+                        handicap.select(&visits, &game.get_available_moves())
+                    }
+                    None => result.action,
+                };
+                match result.proof.filter(|_| action == result.action) {
+                    Some(proof) => println!(
+                        "Player {} plays {} ({})",
+                        player,
+                        game.action_name(action),
+                        proof
+                    ),
+                    None => println!("Player {} plays {}", player, game.action_name(action)),
+                }
+                record.push(action, Some(player), Some(MoveSearch::from_result(&result)));
+                score += game.step(action)?;
+                continue;
+            }
+            let ponder = self
+                .ponder
+                .map(|simulations| mcts.ponder(&game, simulations));
+            pondered = None;
+            match input::prompt(game.as_ref(), &mut stdin, &mut io::stdout())? {
+                Input::Move(action) => {
+                    pondered = ponder.and_then(|ponder| ponder.stop().advance(&action));
+                    history.push((game.clone(), score, record.moves.len()));
+                    record.push(action, Some(player), None);
+                    score += game.step(action)?;
+                }
+                Input::Hint => print!("{}", hint(&game, &analyst.search(&game), 3)),
+                Input::Analyze => println!("{}", analysis(&game, &mcts.search(&game))),
+                Input::Undo => match history.pop() {
+                    Some((previous, previous_score, moves)) => {
+                        (game, score) = (previous, previous_score);
+                        record.moves.truncate(moves);
+                    }
+                    None => println!("Nothing to undo"),
+                },
+                Input::Quit => break,
+            }
+        }
+        if let Some(path) = &self.record {
+            record.append(path)?;
+        }
         if game.done() {
-            if let Some(winner) = game.check_winner() {
-                println!("Player {:?} wins!", winner);
-                break;
+            println!("{}", game);
+            println!("{}", outcome(&game, &humans, score));
+        }
+        Ok(())
+    }
+}
+
+impl SelfPlayArgs {
+    fn run(self, new_game: impl Fn() -> BoxedGame) -> anyhow::Result<()> {
+        #[cfg(feature = "prometheus")]
+        let prometheus = match self.prometheus_addr {
+            Some(addr) => {
+                let metrics = Arc::new(SelfPlayMetrics::default());
+                let addr = prometheus::serve(addr, metrics.clone())?;
+                println!("serving metrics at http://{}/metrics", addr);
+                Some(metrics)
+            }
+            None => None,
+        };
+        #[cfg(not(feature = "prometheus"))]
+        if self.prometheus_addr.is_some() {
+            bail!("--prometheus-addr needs muzero built with the prometheus feature");
+        }
+        if self.joint.is_some() && (self.curriculum.is_some() || self.learner.is_some()) {
+            bail!("--joint can't be combined with --curriculum or --learner");
+        }
+        let mcts = Mcts::<BoxedGame>::with_config(self.mcts).with_parallel_playouts();
+        let mut metrics = self.metrics.as_ref().map(MetricsConfig::open).transpose()?;
+        // An actor pushes its games to the learner instead of writing them, and a joint run
+        // writes the games of each of its games to their own data.
+        let mut learner = self.learner.map(LearnerClient::new).transpose()?;
+        let mut outputs = match (&learner, &self.joint) {
+            (Some(_), _) => vec![],
+            (None, Some(joint)) => joint
+                .games()
+                .iter()
+                .map(|game| TrajectoryWriter::create(&game.data))
+                .collect::<muzero_rs::error::Result<_>>()?,
+            (None, None) => vec![TrajectoryWriter::create(&self.output)?],
+        };
+        let mut resign_stats = ResignStats::default();
+        let stages = match &self.curriculum {
+            Some(curriculum) => curriculum_stages(&self.game, curriculum)?,
+            None => vec![],
+        };
+        let registry = Registry::default();
+        let mut last_stage = None;
+        let rounds = self.games * self.joint.as_ref().map_or(1, JointGames::len);
+        for i in 0..rounds {
+            if let Some(learner) = &mut learner {
+                // Nothing plays with the weights until the crate has a network.
+                if let Some(weights) = learner.weights()? {
+                    println!("received weights version {}", weights.version);
+                }
+            }
+            let stage = self
+                .curriculum
+                .as_ref()
+                .map(|curriculum| curriculum.stage_index(i));
+            let turn = self.joint.as_ref().map_or(0, |joint| joint.turn(i, 1));
+            let (mut game, spec) = match (stage, &self.joint) {
+                (Some(stage), _) => {
+                    let spec = &stages[stage].0;
+                    if last_stage != Some(stage) {
+                        println!("game {}: moving on to {}", i + 1, spec);
+                        last_stage = Some(stage);
+                    }
+                    (registry.create(spec)?, spec.as_str())
+                }
+                (None, Some(joint)) => {
+                    let spec = &joint.games()[turn].game;
+                    (registry.create(spec)?, spec.as_str())
+                }
+                (None, None) => (new_game(), self.game.as_str()),
+            };
+            let mut history = GameHistory::default();
+            let mut record = GameRecord::new(spec);
+            let mut depths = vec![];
+            let mut resignation = self
+                .resign
+                .map(|config| Resignation::new(config, game.num_players(), &mut random::rng()));
+            let mut resigned = None;
+            while !game.done() {
+                // Chance events aren't decisions of the agent, so only their effect on the
+                // next observation is recorded.
+                let outcomes = game.chance_outcomes();
+                if !outcomes.is_empty() {
+                    let outcome = sample_outcome(&outcomes, &mut random::rng());
+                    record.push(outcome, None, None);
+                    game.step(outcome)?;
+                    continue;
+                }
+                #[cfg(feature = "prometheus")]
+                let start = std::time::Instant::now();
+                let stats = mcts.search_statistics(&game);
+                #[cfg(feature = "prometheus")]
+                if let Some(prometheus) = &prometheus {
+                    prometheus
+                        .move_seconds
+                        .observe(start.elapsed().as_secs_f64());
+                    prometheus.moves.inc_by(1);
+                    prometheus
+                        .simulations
+                        .inc_by(stats.visit_counts.iter().sum::<usize>() as u64);
+                    prometheus.nodes.inc_by(stats.nodes as u64);
+                }
+                depths.push(stats.depth);
+                let player = game.to_play();
+                if let Some(resignation) = &mut resignation {
+                    if resignation.resigns(player, stats.root_value) {
+                        history.resign(player);
+                        resigned = Some(player);
+                        break;
+                    }
+                }
+                let action = game.index_to_action(stats.select_action(self.temperature));
+                let search = MoveSearch::from_statistics(&stats);
+                record.push(action, Some(game.to_play()), Some(search));
+                history.apply(&mut game, action, &stats)?;
+            }
+            if let Some(resignation) = &resignation {
+                resign_stats.add(resignation, &game.returns());
+            }
+            if let Some(path) = &self.record {
+                record.append(path)?;
+            }
+            if let Some(stage) = stage {
+                history = stages[stage].1.history(&history);
+            }
+            if let Some(learner) = &mut learner {
+                learner.push(&history)?;
+            } else if let Some(output) = outputs.get_mut(turn) {
+                output.write(&history)?;
+            }
+            #[cfg(feature = "prometheus")]
+            if let Some(prometheus) = &prometheus {
+                prometheus.games.inc_by(1);
+                prometheus.game_length.observe(history.len() as f64);
+            }
+            if let Some(metrics) = &mut metrics {
+                let step = i as u64 + 1;
+                let mean_depth = depths.iter().sum::<usize>() as f64 / depths.len().max(1) as f64;
+                let max_depth = depths.iter().max().copied().unwrap_or(0);
+                let total_reward: f32 = history.rewards.iter().sum();
+                // The games of a joint run are told apart by their name.
+                let tag = |name: &str| match &self.joint {
+                    Some(_) => format!("selfplay/{}/{}", spec, name),
+                    None => format!("selfplay/{}", name),
+                };
+                metrics.scalar(&tag("game_length"), step, history.len() as f64)?;
+                metrics.scalar(&tag("total_reward"), step, total_reward as f64)?;
+                metrics.scalar("search/mean_depth", step, mean_depth)?;
+                metrics.scalar("search/max_depth", step, max_depth as f64)?;
+                if resign_stats.played_through > 0 {
+                    metrics.scalar(
+                        "selfplay/resign_false_positive_rate",
+                        step,
+                        resign_stats.false_positive_rate(),
+                    )?;
+                }
+                metrics.flush()?;
+            }
+            println!(
+                "game {}{}: {} moves, rewards {}{}",
+                i + 1,
+                match &self.joint {
+                    Some(_) => format!(" of {}", spec),
+                    None => String::new(),
+                },
+                history.len(),
+                history.rewards.iter().sum::<f32>(),
+                match resigned {
+                    Some(player) => format!(", player {} resigned", player),
+                    None => String::new(),
+                }
+            );
+        }
+        if self.resign.is_some() {
+            println!(
+                "resigned {} of {} games; {} of {} played through weren't lost ({:.1}%)",
+                resign_stats.resigned,
+                resign_stats.games,
+                resign_stats.false_positives,
+                resign_stats.played_through,
+                100. * resign_stats.false_positive_rate()
+            );
+        }
+        for output in &mut outputs {
+            output.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl LearnerArgs {
+    fn run(self) -> anyhow::Result<()> {
+        let learner = Learner::bind(self.listen, self.queue)?;
+        println!("waiting for actors on {}", learner.local_addr());
+        let mut output = TrajectoryWriter::append(&self.output)?;
+        let mut published = None;
+        let mut received = 0;
+        while self.games.is_none_or(|games| received < games) {
+            if let Some(dir) = &self.checkpoint_dir {
+                let latest = match self.gated {
+                    true => gating::best(dir)?,
+                    false => checkpoint::latest(dir)?,
+                };
+                if let Some(dir) = latest.filter(|latest| published.as_ref() != Some(latest)) {
+                    let checkpoint = Checkpoint::load(&dir)?;
+                    let version = learner.publish(&checkpoint.weights)?;
+                    println!("serving {} as version {}", dir.display(), version);
+                    published = Some(dir);
+                }
+            }
+            let history = match learner.games().recv_timeout(Duration::from_secs(1)) {
+                Ok(history) => history,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => bail!("the learner stopped listening"),
+            };
+            output.write(&history)?;
+            output.flush()?;
+            received += 1;
+            println!("game {}: {} moves", received, history.len());
+        }
+        Ok(())
+    }
+}
+
+impl EvaluatorArgs {
+    fn run(self, new_game: impl Fn() -> BoxedGame) -> anyhow::Result<()> {
+        // Checkpoints play with their network, searching as many simulations as MCTS agents.
+        let game = new_game();
+        let observation_size = game.observation().len();
+        let config = MuZeroConfig {
+            num_players: game.num_players(),
+            num_simulations: self.mcts.num_simulations,
+            known_bounds: None,
+            ..MuZeroConfig::board_game(game.action_space_size(), 0.3)
+        };
+        let mut evaluator = Evaluator::new(&self.checkpoint_dir, self.gating, move |checkpoint| {
+            let model = MlpModel::from_tensors(&checkpoint.weights)?;
+            if model.observation_size() != observation_size {
+                return Err(ConfigError::Invalid(format!(
+                    "the network takes observations of {} values, the game's have {}",
+                    model.observation_size(),
+                    observation_size
+                ))
+                .into());
+            }
+            Ok(Box::new(MuZeroAgent {
+                network: ModelNetwork(model),
+                config: config.clone(),
+            }))
+        });
+        if let Some(baseline) = self.baseline {
+            evaluator = evaluator.with_baseline(new_agent(baseline, &self.mcts, None));
+        }
+        if let Some(early_stopping) = self.early_stopping {
+            evaluator = evaluator.with_early_stopping(early_stopping);
+        }
+        let mut metrics = self.metrics.as_ref().map(MetricsConfig::open).transpose()?;
+        let mut evaluations = 0;
+        while self.evaluations.is_none_or(|n| evaluations < n) {
+            let Some(evaluation) = evaluator.evaluate_latest(&new_game)? else {
+                thread::sleep(Duration::from_secs(self.interval));
+                continue;
+            };
+            evaluations += 1;
+            let dir = evaluation.checkpoint.display();
+            let Some(result) = evaluation.result else {
+                println!("{}: the first checkpoint, now the best", dir);
+                continue;
+            };
+            let promoted = if evaluation.promoted {
+                ", now the best"
             } else {
-                println!("Draw!");
+                ""
+            };
+            println!("{}: {}{}", dir, result, promoted);
+            if let Some(metrics) = &mut metrics {
+                let step = evaluation.step as u64;
+                metrics.scalar("evaluator/score", step, result.score())?;
+                metrics.scalar("evaluator/elo", step, result.elo().0)?;
+                metrics.scalar("evaluator/promoted", step, evaluation.promoted as u8 as f64)?;
+                metrics.scalar("evaluator/strength", step, evaluation.strength)?;
+                metrics.flush()?;
+            }
+            if evaluation.stop {
+                println!(
+                    "the strength plateaued at {:.0} Elo, asked training to stop",
+                    evaluation.strength
+                );
                 break;
             }
         }
+        Ok(())
+    }
+}
+
+/// The games of `data` in a replay buffer, each passed through `prepare`. A resumed run gets
+/// the buffer of its checkpoint back from `snapshot` by adding the same games and restoring
+/// their priorities, before the games played since.
+fn load_replay_buffer(
+    data: &Path,
+    config: ReplayConfig,
+    snapshot: Option<&Value>,
+    prepare: impl Fn(GameHistory) -> GameHistory,
+) -> anyhow::Result<ReplayBuffer> {
+    let mut buffer = ReplayBuffer::new(config);
+    let snapshot = match snapshot {
+        Some(snapshot) if !snapshot.is_null() => Some(Snapshot::deserialize(snapshot)?),
+        _ => None,
+    };
+    let mut restore_after = snapshot.as_ref().map(|snapshot| snapshot.games);
+    let mut games = 0;
+    for history in TrajectoryReader::open(data)? {
+        if restore_after == Some(games) {
+            buffer.restore(snapshot.as_ref().unwrap())?;
+            restore_after = None;
+        }
+        buffer.save_game(prepare(
+            history.with_context(|| format!("in {}", data.display()))?,
+        ));
+        games += 1;
+    }
+    match restore_after {
+        Some(n) if n == games => buffer.restore(snapshot.as_ref().unwrap())?,
+        Some(n) => bail!(
+            "the checkpoint is of {} games, {} has {}",
+            n,
+            data.display(),
+            games
+        ),
+        None => {}
+    }
+    Ok(buffer)
+}
+
+impl TrainArgs {
+    /// Train the libtorch model when muzero is built with it, on any device, and the Rust
+    /// model on the CPU otherwise.
+    fn run(self, new_game: impl Fn() -> BoxedGame) -> anyhow::Result<()> {
+        let (hidden_size, device) = (self.hidden_size, self.device);
+        #[cfg(feature = "tch")]
+        return self.train(new_game, |observation_size, action_space_size, run| {
+            tch::manual_seed(run.seed as i64);
+            let mut model = TchModel::new(observation_size, hidden_size, action_space_size);
+            model.to_device(device)?;
+            Ok(model)
+        });
+        #[cfg(not(feature = "tch"))]
+        {
+            if device != Device::Cpu {
+                bail!(
+                    "training on {} needs muzero built with the tch feature",
+                    device
+                );
+            }
+            self.train(new_game, |observation_size, action_space_size, run| {
+                let rng = &mut StdRng::seed_from_u64(run.seed);
+                Ok(MlpModel::new(
+                    observation_size,
+                    hidden_size,
+                    action_space_size,
+                    rng,
+                ))
+            })
+        }
+    }
+
+    /// Train a model made by `new_model` from the size of its observations, its number of
+    /// actions and the state of the run.
+    fn train<M: Trainable>(
+        self,
+        new_game: impl Fn() -> BoxedGame,
+        new_model: impl Fn(usize, usize, &RunState) -> muzero_rs::error::Result<M>,
+    ) -> anyhow::Result<()> {
+        if self.stacked_frames == Some(0) {
+            bail!("--stacked-frames must be at least 1");
+        }
+        if self.joint.is_some() && self.curriculum.is_some() {
+            bail!("--joint can't be combined with --curriculum");
+        }
+        let mut resumed = None;
+        if let Some(dir) = &self.checkpoint_dir {
+            if let Some(reason) = gating::stop_requested(dir)? {
+                println!("training was asked to stop: {}", reason);
+                return Ok(());
+            }
+            if let Some(latest) = checkpoint::latest(dir)? {
+                let checkpoint = Checkpoint::load(&latest)?;
+                println!(
+                    "resuming from {} at step {}",
+                    latest.display(),
+                    checkpoint.step
+                );
+                resumed = Some(checkpoint);
+            }
+        }
+        // The name, data, observation shape and action space size of every game trained on.
+        let games: Vec<(String, PathBuf, Vec<usize>, usize)> = match &self.joint {
+            Some(joint) => {
+                let registry = Registry::default();
+                joint
+                    .games()
+                    .iter()
+                    .map(|game| {
+                        let new = registry.create(&game.game)?;
+                        Ok((
+                            game.game.clone(),
+                            game.data.clone(),
+                            new.observation_shape(),
+                            new.action_space_size(),
+                        ))
+                    })
+                    .collect::<anyhow::Result<_>>()?
+            }
+            // The data of a curriculum is padded to its largest board.
+            None => {
+                let game = match &self.curriculum {
+                    Some(curriculum) => {
+                        let (spec, _) = curriculum_stages(&self.game, curriculum)?
+                            .pop()
+                            .expect("a curriculum has stages");
+                        Registry::default().create(&spec)?
+                    }
+                    None => new_game(),
+                };
+                vec![(
+                    self.game.clone(),
+                    self.data.clone(),
+                    game.observation_shape(),
+                    game.action_space_size(),
+                )]
+            }
+        };
+        // One network for the games of a joint run takes the observations of all of them.
+        let shared = match (&self.joint, self.networks) {
+            (Some(_), Networks::Shared) => Some(
+                SharedInput::new(
+                    games
+                        .iter()
+                        .map(|(_, _, shape, actions)| (shape.as_slice(), *actions)),
+                )
+                .context("the games can't share a network, try --networks per-game")?,
+            ),
+            _ => None,
+        };
+        let snapshot = resumed.as_ref().map(|checkpoint| &checkpoint.replay_buffer);
+        // The input shape of every network, and the replay buffer of every game.
+        let mut input_shapes = vec![];
+        let mut buffers = vec![];
+        for (name, data, shape, _) in &games {
+            let observation_shape = shared
+                .as_ref()
+                .map_or(shape.as_slice(), SharedInput::observation_shape);
+            let frame_stacking = self.stacked_frames.map(|frames| FrameStacking {
+                frames,
+                planes: observation_shape[0],
+            });
+            // A joint run keeps the snapshot of every game's buffer under the game's name.
+            let snapshot = match (&self.joint, snapshot) {
+                (Some(_), Some(snapshot)) if !snapshot.is_null() => Some(
+                    snapshot
+                        .get(name)
+                        .with_context(|| format!("the checkpoint has no buffer of {}", name))?,
+                ),
+                _ => snapshot,
+            };
+            let buffer = load_replay_buffer(
+                data,
+                ReplayConfig {
+                    batch_size: self.batch_size,
+                    num_unroll_steps: self.unroll_steps,
+                    frame_stacking,
+                    ..ReplayConfig::default()
+                },
+                snapshot,
+                |history| match &shared {
+                    Some(shared) => shared.history(shape, &history),
+                    None => history,
+                },
+            )?;
+            match &self.joint {
+                Some(_) => println!(
+                    "loaded {} games, {} positions of {}",
+                    buffer.len(),
+                    buffer.num_positions(),
+                    name
+                ),
+                None => println!(
+                    "loaded {} games, {} positions",
+                    buffer.len(),
+                    buffer.num_positions()
+                ),
+            }
+            let input_shape = match frame_stacking {
+                Some(stacking) => stacking.stacked_shape(observation_shape),
+                None => observation_shape.to_vec(),
+            };
+            if shared.is_none() || input_shapes.is_empty() {
+                input_shapes.push(input_shape);
+            }
+            buffers.push(buffer);
+        }
+        match (&self.joint, &shared) {
+            (Some(_), None) => {
+                for ((name, ..), input_shape) in games.iter().zip(&input_shapes) {
+                    println!("network input shape of {}: {:?}", name, input_shape);
+                }
+            }
+            _ => println!("network input shape: {:?}", input_shapes[0]),
+        }
+        if let Some(shared) = &shared {
+            println!("shared action space size: {}", shared.action_space_size());
+        }
+        let run = match &resumed {
+            Some(checkpoint) if !checkpoint.run.is_null() => {
+                RunState::deserialize(&checkpoint.run)?
+            }
+            _ => RunState {
+                seed: self.seed.unwrap_or_else(|| random::rng().gen()),
+            },
+        };
+        println!("seed: {}", run.seed);
+        // A trainer per game with per-game networks, otherwise one for all of them.
+        let per_game = self.joint.is_some() && shared.is_none();
+        let mut trainers = vec![];
+        for (input_shape, (_, _, _, actions)) in input_shapes.iter().zip(&games) {
+            let action_space_size = shared
+                .as_ref()
+                .map_or(*actions, SharedInput::action_space_size);
+            let model = new_model(input_shape.iter().product(), action_space_size, &run)?;
+            trainers.push(Trainer::new(model, self.optimizer));
+        }
+        // The steps each trainer took in the first `step` steps of the run.
+        let trainer_steps = |trainer: usize, step: usize| match &self.joint {
+            Some(joint) if per_game => joint.turns(trainer, step, self.interleave),
+            _ => step,
+        };
+        let mut step = 0;
+        if let Some(checkpoint) = &resumed {
+            step = checkpoint.step;
+            if per_game {
+                for (i, (trainer, (name, ..))) in trainers.iter_mut().zip(&games).enumerate() {
+                    trainer
+                        .load(
+                            &joint::split_game_tensors(name, &checkpoint.weights),
+                            &joint::split_game_tensors(name, &checkpoint.optimizer),
+                            trainer_steps(i, step),
+                        )
+                        .with_context(|| format!("loading the network of {}", name))?;
+                }
+            } else {
+                trainers[0].load(&checkpoint.weights, &checkpoint.optimizer, step)?;
+            }
+        }
+        let turn = |step: usize| {
+            self.joint
+                .as_ref()
+                .map_or(0, |joint| joint.turn(step, self.interleave))
+        };
+        println!(
+            "optimizer: {}, learning rate {} at step {}",
+            self.optimizer.kind,
+            trainers[if per_game { turn(step) } else { 0 }]
+                .optimizer
+                .learning_rate(),
+            step
+        );
+        if let Some(joint) = &self.joint {
+            println!(
+                "interleaving {} batches of each game, the batch of step {} from {}",
+                self.interleave,
+                step,
+                joint.games()[turn(step)].game
+            );
+        }
+        let mut metrics = self.metrics.as_ref().map(MetricsConfig::open).transpose()?;
+        if let Some(metrics) = &mut metrics {
+            for ((name, ..), buffer) in games.iter().zip(&buffers) {
+                // The games of a joint run are told apart by their name.
+                let tag = |metric: &str| match &self.joint {
+                    Some(_) => format!("replay_buffer/{}/{}", name, metric),
+                    None => format!("replay_buffer/{}", metric),
+                };
+                metrics.scalar(&tag("games"), step as u64, buffer.len() as f64)?;
+                let positions = buffer.num_positions() as f64;
+                metrics.scalar(&tag("positions"), step as u64, positions)?;
+            }
+            metrics.flush()?;
+        }
+        while step < self.steps {
+            let turn = turn(step);
+            let trainer = &mut trainers[if per_game { turn } else { 0 }];
+            let (losses, learning_rate) = trainer
+                .train(&run, step, &buffers[turn])
+                .with_context(|| format!("in training step {} on {}", step, games[turn].0))?;
+            step += 1;
+            if let Some(dir) = &self.checkpoint_dir {
+                if step % self.checkpoint_interval == 0 || step == self.steps {
+                    // A joint run keeps the snapshot of every game's buffer under its name.
+                    let snapshot = match &self.joint {
+                        Some(_) => Value::Object(
+                            games
+                                .iter()
+                                .zip(&buffers)
+                                .map(|((name, ..), buffer)| {
+                                    Ok((name.clone(), serde_json::to_value(buffer.snapshot())?))
+                                })
+                                .collect::<serde_json::Result<_>>()?,
+                        ),
+                        None => serde_json::to_value(buffers[0].snapshot())?,
+                    };
+                    // Per-game networks keep their weights and optimizer state under the
+                    // names of their games.
+                    let checkpoint = if per_game {
+                        Checkpoint {
+                            step,
+                            weights: games
+                                .iter()
+                                .zip(&trainers)
+                                .flat_map(|((name, ..), trainer)| {
+                                    joint::game_tensors(name, trainer.model.tensors())
+                                })
+                                .collect(),
+                            optimizer: games
+                                .iter()
+                                .zip(&trainers)
+                                .flat_map(|((name, ..), trainer)| {
+                                    joint::game_tensors(name, trainer.optimizer.state())
+                                })
+                                .collect(),
+                            replay_buffer: snapshot,
+                            run: serde_json::to_value(run)?,
+                        }
+                    } else {
+                        trainers[0].checkpoint(&run, step, snapshot)?
+                    };
+                    let path = checkpoint::step_dir(dir, step);
+                    checkpoint.save(&path)?;
+                    println!("saved {}", path.display());
+                    // E.g. by the evaluator, once the strength of the checkpoints plateaus.
+                    if let Some(reason) = gating::stop_requested(dir)? {
+                        println!("training was asked to stop: {}", reason);
+                        break;
+                    }
+                }
+            }
+            if let Some(metrics) = &mut metrics {
+                let tag = |metric: &str| match &self.joint {
+                    Some(_) => format!("train/{}/{}", games[turn].0, metric),
+                    None => format!("train/{}", metric),
+                };
+                metrics.scalar(&tag("loss"), step as u64, losses.total() as f64)?;
+                metrics.scalar(&tag("value_loss"), step as u64, losses.value as f64)?;
+                metrics.scalar(&tag("reward_loss"), step as u64, losses.reward as f64)?;
+                metrics.scalar(&tag("policy_loss"), step as u64, losses.policy as f64)?;
+                // Per-game networks have an optimizer each.
+                let learning_rate_tag = if per_game {
+                    tag("learning_rate")
+                } else {
+                    "train/learning_rate".to_string()
+                };
+                metrics.scalar(&learning_rate_tag, step as u64, learning_rate as f64)?;
+            }
+            if step % LOG_INTERVAL == 0 || step == self.steps {
+                println!(
+                    "step {}{}: loss {:.4} (value {:.4}, reward {:.4}, policy {:.4}), \
+                     learning rate {}",
+                    step,
+                    match &self.joint {
+                        Some(_) => format!(" on {}", games[turn].0),
+                        None => String::new(),
+                    },
+                    losses.total(),
+                    losses.value,
+                    losses.reward,
+                    losses.policy,
+                    learning_rate
+                );
+                if let Some(metrics) = &mut metrics {
+                    metrics.flush()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
 
-        let (row, col) = match game.current_player {
-            Player::X => {
-                // Ask the user for their move
-                let mut input = String::new();
-                io::stdin().read_line(&mut input)?;
-                let mut parts = input.split_whitespace();
-                let row: usize = parts.next().unwrap().parse()?;
-                let col: usize = parts.next().unwrap().parse()?;
-                (row, col)
+impl EvalArgs {
+    fn run(self, new_game: impl Fn() -> BoxedGame) -> anyhow::Result<()> {
+        let book = load_book(self.book.as_deref(), &new_game)?;
+        let mut agent = new_agent(self.agent, &self.mcts, book.as_ref());
+        let mut opponent = new_agent(self.opponent, &self.mcts, book.as_ref());
+        if new_game().num_players() == 1 {
+            // Nothing to play against: compare the scores of separate episodes.
+            for (name, agent) in [("agent", &mut agent), ("opponent", &mut opponent)] {
+                let mut total = 0.;
+                for _ in 0..self.games {
+                    total += agent::play(&mut new_game(), &mut [agent.as_mut()])?[0];
+                }
+                println!("{} mean score: {}", name, total / self.games as f32);
             }
-            Player::O => {
-                // Use MCTS to select the best move
-                mcts.search(&game)
+            return Ok(());
+        }
+        let mut metrics = self.metrics.as_ref().map(MetricsConfig::open).transpose()?;
+        let mut logged = Ok(());
+        let result = arena::run(
+            new_game,
+            agent.as_mut(),
+            opponent.as_mut(),
+            self.games,
+            self.sprt.as_ref(),
+            |result| {
+                println!("{}", result);
+                if let (Some(metrics), Ok(())) = (&mut metrics, &logged) {
+                    let step = result.games() as u64;
+                    logged = metrics
+                        .scalar("eval/elo", step, result.elo().0)
+                        .and_then(|()| metrics.scalar("eval/score", step, result.score()))
+                        .and_then(|()| metrics.flush());
+                }
+            },
+        )?;
+        logged?;
+        if let Some(sprt) = &self.sprt {
+            match result.sprt(sprt) {
+                Some(SprtResult::AcceptElo0) => println!("SPRT: elo0 accepted"),
+                Some(SprtResult::AcceptElo1) => println!("SPRT: elo1 accepted"),
+                None => println!("SPRT: inconclusive, LLR {:.2}", result.llr(sprt)),
             }
+        }
+        Ok(())
+    }
+}
+
+impl TournamentArgs {
+    fn run(self, new_game: impl Fn() -> BoxedGame) -> anyhow::Result<()> {
+        let book = load_book(self.book.as_deref(), &new_game)?;
+        let mut agents: Vec<_> = self
+            .agents
+            .iter()
+            .map(|&spec| (spec.to_string(), new_agent(spec, &self.mcts, book.as_ref())))
+            .collect();
+        let tournament = arena::round_robin(new_game, &mut agents, self.games, |a, b, result| {
+            println!("{} vs {}: {}", a, b, result);
+        })?;
+        let ratings = tournament.ratings();
+        for (rank, i) in tournament.ladder(&ratings).into_iter().enumerate() {
+            println!(
+                "{:>3}. {:<16} {:+7.1}",
+                rank + 1,
+                tournament.names[i],
+                ratings[i]
+            );
+        }
+        if let Some(output) = &self.output {
+            let text = if output
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                serde_json::to_string(&tournament)? + "\n"
+            } else {
+                tournament.to_csv()
+            };
+            fs::write(output, text)
+                .with_context(|| format!("failed to write {}", output.display()))?;
+        }
+        Ok(())
+    }
+}
+
+impl AnalyzeArgs {
+    fn run(self, new_game: impl Fn() -> BoxedGame) -> anyhow::Result<()> {
+        let mut game = new_game();
+        if let Some(position) = &self.position {
+            game = game
+                .parse_notation(position)
+                .with_context(|| format!("invalid position `{}`", position))?;
+        }
+        print!("{}", game);
+        if let Some(notation) = game.to_notation() {
+            println!("{}", notation);
+        }
+        if game.done() {
+            bail!("the game is over");
+        }
+        if !game.chance_outcomes().is_empty() {
+            bail!("a chance event is pending, not a move");
+        }
+        let mut tree = match &self.tree {
+            Some(path) if path.exists() => SearchTree::load(path, &game)?,
+            _ => SearchTree::new(&game),
         };
+        let mcts = Mcts::<BoxedGame>::with_config(self.mcts).with_parallel_playouts();
+        let result = mcts.grow_tree(&game, &mut tree);
+        println!("{}", analysis(&game, &result));
+        print!("{}", hint(&game, &result, self.top));
+        println!("{} simulations, {} nodes", tree.visits(), tree.nodes());
+        if let Some(path) = &self.tree {
+            tree.save(&game, path)?;
+        }
+        Ok(())
+    }
+}
 
-        game.step((row, col))?;
+impl TestSuiteArgs {
+    fn run(self, new_game: impl Fn() -> BoxedGame) -> anyhow::Result<()> {
+        let puzzles = testsuite::load(&self.suite, |position| new_game().parse_notation(position))?;
+        if puzzles.is_empty() {
+            bail!("{} has no puzzles", self.suite.display());
+        }
+        let mcts = Mcts::<BoxedGame>::with_config(self.mcts).with_parallel_playouts();
+        let solved = testsuite::run(&puzzles, &mcts, |puzzle, result, ok| {
+            let game = &puzzle.game;
+            let moves = |indices: &[usize]| {
+                let names: Vec<_> = indices.iter().map(|&i| game.action_name(i)).collect();
+                names.join(", ")
+            };
+            let mut expected = String::new();
+            if !puzzle.best_moves.is_empty() {
+                expected += &format!("bm {}", moves(&puzzle.best_moves));
+            }
+            if !puzzle.avoid_moves.is_empty() {
+                if !expected.is_empty() {
+                    expected += "; ";
+                }
+                expected += &format!("am {}", moves(&puzzle.avoid_moves));
+            }
+            println!(
+                "{:4} {}: played {} ({}), {}",
+                if ok { "ok" } else { "FAIL" },
+                puzzle.id,
+                game.action_name(result.action),
+                expected,
+                describe_value(game, result.value())
+            );
+        });
+        println!(
+            "solved {}/{} ({:.1}%)",
+            solved,
+            puzzles.len(),
+            100. * solved as f32 / puzzles.len() as f32
+        );
+        Ok(())
     }
+}
 
-    Ok(())
+impl ReplayArgs {
+    fn run(self) -> anyhow::Result<()> {
+        let records = GameRecord::load_all(&self.record)?;
+        let Some(record) = self.index.checked_sub(1).and_then(|i| records.get(i)) else {
+            bail!(
+                "{} has {} games, not a game {}",
+                self.record.display(),
+                records.len(),
+                self.index
+            );
+        };
+        let new_game = game_factory(&record.game)?;
+        let positions = record
+            .positions(new_game())
+            .with_context(|| format!("game {} of {}", self.index, self.record.display()))?;
+        viewer::view(
+            record,
+            &positions,
+            &mut io::stdin().lock(),
+            &mut io::stdout(),
+        )?;
+        Ok(())
+    }
+}
+
+impl SweepArgs {
+    fn run(self) -> anyhow::Result<()> {
+        let config = SweepConfig::load(&self.config)?;
+        if !["selfplay", "train", "eval", "evaluator"].contains(&config.command.as_str()) {
+            bail!("`{}` logs no metrics to compare", config.command);
+        }
+        let runs = config.runs();
+        // Write and check the config of every run before starting any.
+        for run in &runs {
+            let dir = self.output.join(&run.name);
+            fs::create_dir_all(&dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+            let path = dir.join("config.toml");
+            fs::write(&path, config.config(run))
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            cli::parse(&sweep_run_args(&config.command, &dir))
+                .with_context(|| format!("invalid config for {}", run.name))?;
+        }
+        let parallel = self.parallel.unwrap_or(config.parallel);
+        println!(
+            "{} runs of {}, {} at a time, in {}",
+            runs.len(),
+            config.command,
+            parallel,
+            self.output.display()
+        );
+        let exe = std::env::current_exe().context("failed to find the muzero executable")?;
+        let next = AtomicUsize::new(0);
+        let outcomes = Mutex::new(vec![None; runs.len()]);
+        thread::scope(|scope| {
+            for _ in 0..parallel.min(runs.len()) {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(run) = runs.get(i) else {
+                        break;
+                    };
+                    let dir = self.output.join(&run.name);
+                    let outcome = match run_sweep_run(&exe, &config.command, &dir) {
+                        Ok(metrics) => Outcome::Finished(metrics),
+                        Err(error) => Outcome::Failed(format!("{:#}", error)),
+                    };
+                    match &outcome {
+                        Outcome::Finished(_) => println!("{}: finished", run.name),
+                        Outcome::Failed(error) => println!("{}: {}", run.name, error),
+                    }
+                    outcomes.lock().unwrap()[i] = Some(outcome);
+                });
+            }
+        });
+        let outcomes = outcomes
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(Option::unwrap);
+        let mut comparison = Comparison {
+            parameters: config.parameters.iter().map(|p| p.key.clone()).collect(),
+            runs: runs.into_iter().zip(outcomes).collect(),
+        };
+        if let Some(tag) = &self.sort {
+            // Losses are better the lower they are.
+            match tag.strip_prefix('-') {
+                Some(tag) => comparison.sort_by(tag, true),
+                None => comparison.sort_by(tag, false),
+            }
+        }
+        print!("{}", comparison);
+        let path = self.output.join("summary.csv");
+        fs::write(&path, comparison.to_csv())
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        println!("wrote {}", path.display());
+        Ok(())
+    }
+}
+
+/// The arguments of a run of a sweep, whose config and metrics are in `dir`.
+fn sweep_run_args(command: &str, dir: &Path) -> Vec<String> {
+    let mut args = vec![
+        command.to_string(),
+        "--config".to_string(),
+        dir.join("config.toml").display().to_string(),
+        "--metrics-dir".to_string(),
+        dir.display().to_string(),
+        "--metrics-format".to_string(),
+        "csv".to_string(),
+    ];
+    // Runs at the same time mustn't append to the same data or save the same checkpoints.
+    match command {
+        "selfplay" => {
+            args.push("--output".to_string());
+            args.push(dir.join("selfplay.traj").display().to_string());
+        }
+        "train" => {
+            args.push("--checkpoint-dir".to_string());
+            args.push(dir.join("checkpoints").display().to_string());
+        }
+        _ => {}
+    }
+    args
+}
+
+/// Run a run of a sweep in a process of its own, logging to `dir`, and read its final
+/// metrics.
+fn run_sweep_run(exe: &Path, command: &str, dir: &Path) -> anyhow::Result<BTreeMap<String, f64>> {
+    let log_path = dir.join("log.txt");
+    let log = fs::File::create(&log_path)
+        .with_context(|| format!("failed to create {}", log_path.display()))?;
+    let status = process::Command::new(exe)
+        .args(sweep_run_args(command, dir))
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .status()
+        .with_context(|| format!("failed to run {}", exe.display()))?;
+    if !status.success() {
+        bail!("{}, see {}", status, log_path.display());
+    }
+    Ok(sweep::final_metrics(&dir.join("metrics.csv"))?)
+}
+
+impl ConvertArgs {
+    fn run(self) -> anyhow::Result<()> {
+        let records = GameRecord::load_all(&self.input)?;
+        let mut text = String::new();
+        if self
+            .output
+            .extension()
+            .is_some_and(|extension| extension == "sgf")
+        {
+            for (i, record) in records.iter().enumerate() {
+                let game = game_factory(&record.game)?();
+                text += &sgf::to_sgf(record, game.action_space_size())
+                    .with_context(|| format!("game {}", i + 1))?;
+            }
+        } else {
+            for record in &records {
+                text += &format!("{}\n", serde_json::to_string(record)?);
+            }
+        }
+        fs::write(&self.output, text)
+            .with_context(|| format!("failed to write {}", self.output.display()))?;
+        println!(
+            "converted {} games to {}",
+            records.len(),
+            self.output.display()
+        );
+        Ok(())
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    // Logs go to stderr, since GTP and UCI speak on stdout.
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match cli::parse(&args) {
+        Ok(command) => run(command),
+        // Help, and mistakes in the flags, as clap prints them.
+        Err(error) => match error.downcast::<clap::Error>() {
+            Ok(error) => error.exit(),
+            Err(error) => Err(error),
+        },
+    }
+}
+
+fn run(command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Deterministic { seed, command } => {
+            random::set_seed(seed);
+            run(*command)
+        }
+        Command::Play(args) => {
+            let new_game = game_factory(&args.game)?;
+            args.run(new_game)
+        }
+        Command::Analyze(args) => {
+            let new_game = game_factory(&args.game)?;
+            args.run(new_game)
+        }
+        Command::TestSuite(args) => {
+            let new_game = game_factory(&args.game)?;
+            args.run(new_game)
+        }
+        Command::SelfPlay(args) => {
+            let new_game = game_factory(&args.game)?;
+            args.run(new_game)
+        }
+        Command::Train(args) => {
+            let new_game = game_factory(&args.game)?;
+            args.run(new_game)
+        }
+        Command::Learner(args) => args.run(),
+        Command::Evaluator(args) => {
+            let new_game = game_factory(&args.game)?;
+            args.run(new_game)
+        }
+        Command::Replay(args) => args.run(),
+        Command::Convert(args) => args.run(),
+        Command::Sweep(args) => args.run(),
+        Command::Gtp(args) => {
+            let mut engine = GtpEngine::new(args.mcts);
+            engine.run(&mut io::stdin().lock(), &mut io::stdout())?;
+            Ok(())
+        }
+        #[cfg(feature = "chess")]
+        Command::Uci(args) => {
+            let mut engine = UciEngine::<Chess>::new(args.mcts);
+            engine.run(&mut io::stdin().lock(), &mut io::stdout())?;
+            Ok(())
+        }
+        #[cfg(not(feature = "chess"))]
+        Command::Uci(_) => bail!("uci needs muzero built with the chess feature"),
+        Command::Serve(args) => {
+            let listener = TcpListener::bind(args.listen)
+                .with_context(|| format!("failed to listen on {}", args.listen))?;
+            println!("serving {} on http://{}", args.game, listener.local_addr()?);
+            Ok(server::serve(listener, &args.game, args.mcts)?)
+        }
+        Command::Eval(args) => {
+            let new_game = game_factory(&args.game)?;
+            args.run(new_game)
+        }
+        Command::Tournament(args) => {
+            let new_game = game_factory(&args.game)?;
+            args.run(new_game)
+        }
+        Command::InspectData(path) => {
+            let mut summary = Summary::default();
+            for history in TrajectoryReader::open(&path)? {
+                summary.add(&history.with_context(|| format!("in {}", path.display()))?);
+            }
+            println!("{}", summary);
+            Ok(())
+        }
+        Command::Games => {
+            for (name, description) in Registry::default().games() {
+                println!("{:<12}{}", name, description);
+            }
+            Ok(())
+        }
+    }
 }
@@ -1,236 +1,3114 @@
-use std::{collections::HashMap, hash::Hash, sync::atomic::AtomicUsize};
+//! Monte Carlo tree search with random playouts, which needs no trained network.
+//!
+//! The search itself uses no threads or clocks, so it also runs on `wasm32-unknown-unknown`
+//! with a [`MctsConfig::seed`]; only [`Mcts::ponder`] and [`Mcts::search_for`] are native.
+//!
+//! Every search is a `search` span of [`tracing`] with a summary event at info level. At trace
+//! level, the phases of every simulation are spans of their own with an event each, e.g.
+//! `RUST_LOG=muzero_rs::mcts[backpropagation]=trace`, and the tree is dumped in a `tree` span.
 
-use log::debug;
-use rand::seq::IteratorRandom;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    fmt,
+    hash::{BuildHasherDefault, Hash},
+    mem,
+    str::FromStr,
+    sync::{atomic::AtomicBool, Arc},
+    time::Instant,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    thread::{self, JoinHandle},
+    time::Duration,
+};
 
-use crate::game::Game;
+#[cfg(feature = "serde")]
+use std::{fs, iter::Peekable, path::Path, slice};
 
-pub(crate) struct Mcts<T: Game> {
-    _phantom: std::marker::PhantomData<T>,
-    num_simulations: usize,
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    Rng, SeedableRng,
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
+use serde_json::Value;
+use tracing::{info, info_span, trace, trace_span, Level};
+
+#[cfg(feature = "serde")]
+use crate::error::{Context, Error, IoError};
+use crate::{
+    error::{ParseError, Result, SearchError},
+    game::{Game, Undo},
+    muzero::SearchStatistics,
+    random,
+    rollout::{Rollout, RolloutPolicy},
+    scoring::ScoreConfig,
+    solver::{self, Solution},
+};
+
+/// When a search started, where there's a clock.
+#[cfg(not(target_arch = "wasm32"))]
+fn now() -> Option<Instant> {
+    Some(Instant::now())
 }
 
-static NODE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+#[cfg(target_arch = "wasm32")]
+fn now() -> Option<Instant> {
+    None
+}
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
-struct NodeId(usize);
+/// Search hyperparameters for [`Mcts`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MctsConfig {
+    pub num_simulations: usize,
+    /// The weight `c` of the exploration term of UCT, `c * sqrt(ln N / n)`; the theory of
+    /// UCB1 suggests `sqrt(2)`.
+    pub exploration: f32,
+    /// Limit how many children a node may expand based on its visit count.
+    /// `None` expands every legal action before descending further.
+    pub progressive_widening: Option<ProgressiveWidening>,
+    /// Blend all-moves-as-first statistics into the child values (RAVE).
+    pub rave: Option<Rave>,
+    /// Play this many playouts from every expanded node and back their average up the tree,
+    /// for less noisy values in games of chance or long playouts; the tree grows as it would
+    /// with one. [`Mcts::with_parallel_playouts`] plays them on several threads.
+    pub rollouts_per_leaf: usize,
+    /// Cut random playouts off after this many moves, like a step limit truncating the
+    /// episode. A truncated multi-player playout is scored by [`Game::returns`] of the state
+    /// it stopped in, a single-player one by the rewards collected so far.
+    pub max_rollout_depth: Option<usize>,
+    /// Before searching, try to solve the game exactly by visiting at most this many states,
+    /// and play the proven best move if that succeeds.
+    pub solver_budget: Option<usize>,
+    /// MCTS-Solver (Winands et al., 2008) in two-player games: back terminal results up the
+    /// tree as proven values, stop searching below proven nodes and skip proven losses.
+    pub mcts_solver: bool,
+    /// Seed the random choices of every search, so that searching the same position gives
+    /// the same result; `None` seeds each search from [`random::rng`].
+    pub seed: Option<u64>,
+    /// The most nodes the tree may have, see [`MctsConfig::full_tree`].
+    pub max_nodes: Option<usize>,
+    /// The most memory the tree may take, by an estimate of the size of its nodes and their
+    /// lists of moves; see [`MctsConfig::full_tree`].
+    pub max_memory_bytes: Option<usize>,
+    /// What a search does once its tree reaches `max_nodes` or `max_memory_bytes`.
+    pub full_tree: FullTree,
+    /// When to expand another move of a node rather than descend into a visited one.
+    pub first_play_urgency: FirstPlayUrgency,
+    /// How the end of a multi-player playout is valued. Proofs only know wins, losses and
+    /// draws, and don't use it.
+    pub scoring: ScoreConfig,
+    /// How playouts choose their moves, unless [`Mcts::with_rollout_policy`] sets a policy
+    /// of the game's own.
+    pub rollout: Rollout,
+}
 
-impl NodeId {
-    fn new() -> Self {
-        NodeId(NODE_COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst))
+impl Default for MctsConfig {
+    fn default() -> Self {
+        Self {
+            num_simulations: 100,
+            exploration: std::f32::consts::SQRT_2,
+            progressive_widening: None,
+            rave: None,
+            rollouts_per_leaf: 1,
+            max_rollout_depth: None,
+            solver_budget: None,
+            mcts_solver: false,
+            seed: None,
+            max_nodes: None,
+            max_memory_bytes: None,
+            full_tree: FullTree::Stop,
+            first_play_urgency: FirstPlayUrgency::Infinite,
+            scoring: ScoreConfig::default(),
+            rollout: Rollout::Uniform,
+        }
+    }
+}
+
+/// What a search does with a full tree, see [`MctsConfig::max_nodes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FullTree {
+    /// Stop, choosing a move from the simulations so far.
+    Stop,
+    /// Drop the least visited subtrees below the children of the root, down to three
+    /// quarters of the limits, and go on. Their moves can be expanded again later.
+    Recycle,
+}
+
+impl fmt::Display for FullTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FullTree::Stop => write!(f, "stop"),
+            FullTree::Recycle => write!(f, "recycle"),
+        }
+    }
+}
+
+impl FromStr for FullTree {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        match s {
+            "stop" => Ok(FullTree::Stop),
+            "recycle" => Ok(FullTree::Recycle),
+            _ => Err(ParseError::Invalid("expected stop or recycle".to_string())),
+        }
+    }
+}
+
+/// The value a search gives the children of a node it hasn't visited yet, when choosing
+/// where to descend.
+///
+/// In UCT, as [`Mcts`] searches, it is the whole score of an unvisited move, which is
+/// expanded only once no visited child scores higher with its exploration bonus. In PUCT, as
+/// the MuZero search does, it takes the place of the value of an unvisited child, whose prior
+/// still adds to it: the lower it is, the more the search trusts the priors and the deeper it
+/// goes, the higher, the wider.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FirstPlayUrgency {
+    /// Higher than any visited child: every move is visited once before any is visited
+    /// twice, UCT's usual choice.
+    Infinite,
+    /// The lowest value, the choice of the MuZero pseudocode. In UCT, a node then only grows
+    /// a first child.
+    Lowest,
+    /// The value of the parent so far, lowered by `reduction` (first-play urgency reduction,
+    /// as in Leela Zero). In UCT, a reduction of 0 or more can leave a good move unvisited for
+    /// the whole search once the exploration bonuses of the visited children shrink.
+    Parent { reduction: f32 },
+    /// A fixed value, e.g. the highest possible return for an optimistic search.
+    Value(f32),
+}
+
+impl fmt::Display for FirstPlayUrgency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FirstPlayUrgency::Infinite => write!(f, "infinite"),
+            FirstPlayUrgency::Lowest => write!(f, "lowest"),
+            FirstPlayUrgency::Parent { reduction } => write!(f, "parent:{}", reduction),
+            FirstPlayUrgency::Value(value) => write!(f, "value:{}", value),
+        }
+    }
+}
+
+impl FromStr for FirstPlayUrgency {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        let number = |value: &str| -> Result<f32, ParseError> {
+            value
+                .parse()
+                .map_err(|_| ParseError::Invalid(format!("expected a number, got `{}`", value)))
+        };
+        match s.split_once(':') {
+            None if s == "infinite" => Ok(FirstPlayUrgency::Infinite),
+            None if s == "lowest" => Ok(FirstPlayUrgency::Lowest),
+            None if s == "parent" => Ok(FirstPlayUrgency::Parent { reduction: 0. }),
+            Some(("parent", reduction)) => Ok(FirstPlayUrgency::Parent {
+                reduction: number(reduction)?,
+            }),
+            Some(("value", value)) => Ok(FirstPlayUrgency::Value(number(value)?)),
+            _ => Err(ParseError::Invalid(
+                "expected infinite, lowest, parent[:<reduction>] or value:<v>".to_string(),
+            )),
+        }
+    }
+}
+
+/// Progressive widening: a node with `n` visits may have at most `ceil(c * n^alpha)` children.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressiveWidening {
+    pub c: f32,
+    pub alpha: f32,
+}
+
+impl ProgressiveWidening {
+    fn max_children(&self, visits: usize) -> usize {
+        // Always allow at least one child, otherwise an unvisited node could never be expanded.
+        ((self.c * (visits as f32).powf(self.alpha)).ceil() as usize).max(1)
+    }
+}
+
+/// Rapid Action Value Estimation.
+///
+/// The AMAF value of a child is weighted by `beta = sqrt(k / (3n + k))`, where `n` is the
+/// visit count of the parent and `k` is the `equivalence` parameter: the number of visits
+/// at which the tree and AMAF estimates are given equal weight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rave {
+    pub equivalence: f32,
+}
+
+impl Rave {
+    fn beta(&self, visits: usize) -> f32 {
+        (self.equivalence / (3. * visits as f32 + self.equivalence)).sqrt()
+    }
+}
+
+/// What a search found: the action it chose and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult<A> {
+    pub action: A,
+    /// The statistics of the children of the root, the most visited first.
+    pub children: Vec<ChildStatistics<A>>,
+    /// The number of moves on the longest path of the search tree.
+    pub max_depth: usize,
+    /// The mean depth of the nodes of the tree below the root.
+    pub mean_depth: f32,
+    /// The number of nodes of the tree, including the root.
+    pub nodes: usize,
+    /// The nodes the search added to the tree, including any it dropped to stay within
+    /// [`MctsConfig::max_nodes`]. A search continuing a tree counts only its own.
+    pub allocated_nodes: usize,
+    /// The line of play the search expects, starting with `action`.
+    pub principal_variation: Vec<A>,
+    /// The proven outcome of the root, found with [`MctsConfig::solver_budget`] or
+    /// [`MctsConfig::mcts_solver`].
+    pub proof: Option<Proof>,
+}
+
+impl<A> SearchResult<A> {
+    /// The value of the root for the player to move: the proven one, or the mean over the
+    /// simulations.
+    pub fn value(&self) -> f32 {
+        if let Some(proof) = self.proof {
+            return proof.value;
+        }
+        let visits: usize = self.children.iter().map(|child| child.visits).sum();
+        let value_sum: f32 = self
+            .children
+            .iter()
+            .map(|child| child.q * child.visits as f32)
+            .sum();
+        value_sum / visits.max(1) as f32
+    }
+}
+
+/// The statistics of one move of the root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChildStatistics<A> {
+    pub action: A,
+    pub visits: usize,
+    /// The mean value of the move for the player to move at the root.
+    pub q: f32,
+    /// Uniform over the moves, or the probability of a chance outcome.
+    pub prior: f32,
+}
+
+/// The formats [`Mcts::export_tree`] writes the search tree in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeFormat {
+    /// A Graphviz digraph.
+    Dot,
+    /// Nested objects, one per node.
+    #[cfg(feature = "serde")]
+    Json,
+}
+
+/// The exact value of a node proven by MCTS-Solver.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Proof {
+    /// The outcome with perfect play, from the perspective of the player to move.
+    pub value: f32,
+    /// The number of moves until the end along the best line.
+    pub moves: usize,
+}
+
+impl Proof {
+    /// The proof from the perspective of the player to move at `parent`, in a two-player game.
+    fn for_parent<T: Game>(&self, parent: &Node<T>, child: &Node<T>) -> Self {
+        let value = if parent.to_play_index == child.to_play_index {
+            self.value
+        } else {
+            // Not `-self.value`, which would make a draw -0.
+            0. - self.value
+        };
+        Proof {
+            value,
+            moves: self.moves,
+        }
+    }
+}
+
+impl fmt::Display for Proof {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.value >= 1. {
+            write!(f, "forced win in {}", self.moves)
+        } else if self.value <= -1. {
+            write!(f, "forced loss in {}", self.moves)
+        } else if self.value == 0. {
+            write!(f, "proven draw in {}", self.moves)
+        } else {
+            write!(f, "proven value {} in {}", self.value, self.moves)
+        }
+    }
+}
+
+/// A Monte Carlo tree search over the real game, picking the action with the highest mean
+/// value after [`MctsConfig::num_simulations`] simulations.
+pub struct Mcts<T: Game> {
+    // Only a marker, so that the search can be shared between threads whatever `T` is.
+    _phantom: std::marker::PhantomData<fn() -> T>,
+    config: MctsConfig,
+    rollout_policy: Option<Arc<dyn RolloutPolicy<T>>>,
+    /// Plays the extra playouts of [`MctsConfig::rollouts_per_leaf`], see
+    /// [`Mcts::with_parallel_playouts`].
+    extra_playouts: fn(&Self, &mut [ExtraPlayout<T>]),
+}
+
+// Not derived, which would need `T: Clone` for the `PhantomData`.
+impl<T: Game> Clone for Mcts<T> {
+    fn clone(&self) -> Self {
+        Self {
+            _phantom: std::marker::PhantomData,
+            config: self.config.clone(),
+            rollout_policy: self.rollout_policy.clone(),
+            extra_playouts: self.extra_playouts,
+        }
     }
 }
 
+/// A node of one tree, numbered by the tree in the order the nodes were added.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+struct NodeId(usize);
+
 struct Node<T: Game> {
     visits: usize,
-    wins: i32,
+    /// Sum of the returns of the player who made the move leading to this node.
+    value_sum: f32,
     to_play: T::Player,
+    /// [`Game::player_index`] of `to_play`.
+    to_play_index: usize,
     parent: Option<NodeId>,
-    children: HashMap<T::Action, NodeId>,
-    unvisited_actions: Vec<T::Action>,
+    children: Children<T::Action>,
+    /// The moves not expanded yet, listed when the node is first expanded: most nodes are
+    /// leaves that never are, and keep no list.
+    unvisited_actions: Option<Vec<T::Action>>,
+    /// Outcomes and their probabilities if this node is a chance node, empty otherwise.
+    chance_outcomes: Vec<(T::Action, f32)>,
     done: bool,
+    /// All-moves-as-first statistics of the actions available to `to_play`, only filled in with RAVE.
+    amaf: HashMap<T::Action, AmafStats>,
+    /// The exact value of the node, only proven with MCTS-Solver.
+    proven: Option<Proof>,
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+struct AmafStats {
+    visits: usize,
+    value_sum: f32,
 }
 
 impl<T: Game> Node<T> {
-    fn new(db: &mut NodeMap<T>, game: &T, parent: Option<NodeId>) -> NodeId {
-        let available_moves = game.get_available_moves();
+    fn insert(db: &mut NodeMap<T>, game: &T, parent: Option<NodeId>) -> NodeId {
         let node = Node {
             visits: 0,
-            wins: 0,
+            value_sum: 0.,
             to_play: game.current_player(),
+            to_play_index: game.player_index(&game.current_player()),
             parent,
-            children: HashMap::new(),
-            unvisited_actions: available_moves,
+            children: Children::default(),
+            unvisited_actions: None,
+            chance_outcomes: game.chance_outcomes(),
             done: game.done(),
+            amaf: HashMap::new(),
+            proven: None,
         };
-        let node_id = NodeId::new();
-        db.insert(node_id, node);
-        node_id
+        db.insert(node)
+    }
+
+    /// An estimate of the memory the node takes: its entries in the tree and in the children
+    /// of its parent, and its lists of moves, whose capacity never changes once listed.
+    fn bytes(&self) -> usize {
+        mem::size_of::<(NodeId, Node<T>)>()
+            + mem::size_of::<(T::Action, NodeId)>()
+            + self.unvisited_actions.as_ref().map_or(0, Vec::capacity) * mem::size_of::<T::Action>()
+            + self.chance_outcomes.capacity() * mem::size_of::<(T::Action, f32)>()
+    }
+
+    /// Whether every move of the node has a child. A node that was never expanded has none.
+    fn fully_expanded(&self) -> bool {
+        self.unvisited_actions.as_ref().is_some_and(Vec::is_empty)
     }
 }
 
-type NodeMap<T> = HashMap<NodeId, Node<T>>;
+/// The nodes of a tree by id. Every tree numbers its own nodes, so that searches share
+/// nothing.
+struct NodeMap<T: Game> {
+    nodes: HashMap<NodeId, Node<T>>,
+    next_id: usize,
+    /// The sum of [`Node::bytes`] over the nodes.
+    bytes: usize,
+    /// The nodes added since the last search that grew the tree started, including any it
+    /// dropped.
+    searched: usize,
+}
 
-impl<T: Game> Mcts<T> {
-    pub(crate) fn new(num_simulations: usize) -> Self {
+impl<T: Game> NodeMap<T> {
+    fn new() -> Self {
         Self {
-            _phantom: std::marker::PhantomData,
-            num_simulations,
+            nodes: HashMap::new(),
+            next_id: 0,
+            bytes: 0,
+            searched: 0,
         }
     }
 
-    pub(crate) fn search(&self, game: &T) -> T::Action {
-        let mut db = NodeMap::new();
-        let root = Node::new(&mut db, game, None);
+    fn insert(&mut self, node: Node<T>) -> NodeId {
+        let node_id = NodeId(self.next_id);
+        self.next_id += 1;
+        self.searched += 1;
+        self.bytes += node.bytes();
+        self.nodes.insert(node_id, node);
+        node_id
+    }
 
-        for _ in 0..self.num_simulations {
-            let (path, leaf) = self.selection(&db, root);
-            let mut game = game.clone();
-            self.apply_actions(&mut game, path);
-            let expanded_node = self.expansion(&mut db, leaf, &mut game);
-            let winner = self.simulation(&mut game);
-            self.backpropagation(&mut db, expanded_node, winner);
+    /// Remove `node_id` and every node below it, leaving its parent as it is.
+    fn remove_subtree(&mut self, node_id: NodeId) {
+        let mut stack = vec![node_id];
+        while let Some(node_id) = stack.pop() {
+            let node = self
+                .nodes
+                .remove(&node_id)
+                .expect("children are in the tree");
+            self.bytes -= node.bytes();
+            stack.extend(node.children.values().copied());
         }
-        self.print_tree(&db, &root, 0);
-        self.best_action(&db, root)
     }
 
-    fn print_tree(&self, db: &NodeMap<T>, root: &NodeId, level: usize) {
-        let node = db.get(root).unwrap();
-        let indent = " ".repeat(level * 2);
-        if level == 0 {
-            debug!(
-                "{}{:?} {:?} {:?} {:?}",
-                indent, node.to_play, node.visits, node.wins, node.done
-            );
+    fn get(&self, node_id: &NodeId) -> Option<&Node<T>> {
+        self.nodes.get(node_id)
+    }
+
+    /// The unexpanded moves of `node_id`, whose state is `game`, listed on the first call.
+    fn unvisited_actions(&mut self, node_id: NodeId, game: &T) -> &mut Vec<T::Action> {
+        let node = self.nodes.get_mut(&node_id).unwrap();
+        if node.unvisited_actions.is_none() {
+            let moves = if node.chance_outcomes.is_empty() {
+                game.get_available_moves()
+            } else {
+                node.chance_outcomes
+                    .iter()
+                    .map(|(action, _)| action.clone())
+                    .collect()
+            };
+            self.bytes += moves.capacity() * mem::size_of::<T::Action>();
+            node.unvisited_actions = Some(moves);
         }
-        for (action, child_id) in node.children.iter() {
-            let child = db.get(child_id).unwrap();
-            debug!(
-                "{}{:?} {:?} {:?} {:?}",
-                indent, action, child.to_play, child.wins as f32/child.visits as f32, child.done
-            );
-            self.print_tree(db, child_id, level + 1);
+        node.unvisited_actions.as_mut().unwrap()
+    }
+
+    fn get_mut(&mut self, node_id: &NodeId) -> Option<&mut Node<T>> {
+        self.nodes.get_mut(node_id)
+    }
+
+    fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl<T: Game> std::ops::Index<&NodeId> for NodeMap<T> {
+    type Output = Node<T>;
+
+    fn index(&self, node_id: &NodeId) -> &Node<T> {
+        &self.nodes[node_id]
+    }
+}
+
+/// Children by their action, iterated in the same order in every run, so that ties between
+/// them are broken the same way and seeded searches repeat.
+type Children<A> = HashMap<A, NodeId, BuildHasherDefault<DefaultHasher>>;
+
+/// The player making the next move, or `None` if the next step is a chance event.
+fn mover<T: Game>(game: &T) -> Option<T::Player> {
+    if game.chance_outcomes().is_empty() {
+        Some(game.current_player())
+    } else {
+        None
+    }
+}
+
+/// Add `returns` to the running `sum` of the returns of several playouts.
+fn add_returns(sum: &mut Vec<f32>, returns: &[f32]) {
+    sum.resize(returns.len(), 0.);
+    for (sum, value) in sum.iter_mut().zip(returns) {
+        *sum += value;
+    }
+}
+
+/// The moves played during one simulation.
+struct Trajectory<T: Game, S> {
+    /// Every move in order, together with the player who made it.
+    moves: Vec<(Option<T::Player>, T::Action)>,
+    /// Sum of the rewards returned by [`Game::step`].
+    reward: f32,
+    stepper: S,
+}
+
+impl<T: Game, S: Stepper<T>> Trajectory<T, S> {
+    fn new(stepper: S) -> Self {
+        Self {
+            moves: vec![],
+            reward: 0.,
+            stepper,
         }
     }
 
-    fn selection(&self, db: &NodeMap<T>, root_id: NodeId) -> (Vec<T::Action>, NodeId) {
-        // Start from root R and select successive child nodes until a leaf node L is reached. 
-        // The root is the current game state and a leaf is any node that has a potential child from which no simulation (playout) has yet been initiated.
-        let mut node_id = root_id;
-        let mut path = vec![];
-        loop {
-            let node = db.get(&node_id).unwrap();
-            if node.done {
-                break;
-            }
-            if node.unvisited_actions.is_empty() {
-                let (action, child_id) = self.best_child(db, node_id);
-                path.push(action);
-                node_id = child_id;
-            } else {
-                break;
-            }
+    fn step(&mut self, game: &mut T, action: T::Action) {
+        self.moves.push((mover(game), action.clone()));
+        self.reward += self.stepper.step(game, action);
+    }
+
+    /// Bring `game` back to the root state for the next simulation.
+    fn reset(&mut self, game: &mut T) {
+        self.moves.clear();
+        self.reward = 0.;
+        self.stepper.reset(game);
+    }
+}
+
+/// Buffers reused by every simulation of a search, so that the loop doesn't allocate them
+/// again each time. The game itself is reused by the [`Stepper`].
+struct Scratch<T: Game> {
+    /// The actions selection followed from the root.
+    path: Vec<T::Action>,
+    /// The nodes from a leaf up to the root.
+    nodes: Vec<NodeId>,
+    /// The legal moves of the state a playout is in.
+    moves: Vec<T::Action>,
+}
+
+impl<T: Game> Scratch<T> {
+    fn new() -> Self {
+        Self {
+            path: vec![],
+            nodes: vec![],
+            moves: vec![],
         }
-        (path, node_id)
     }
+}
 
-    fn best_child(&self, db: &NodeMap<T>, node_id: NodeId) -> (T::Action, NodeId) {
-        // select the child node with the highest UCT value.
-        let node = db.get(&node_id).unwrap();
-        let mut best_action = None;
-        let mut best_node_id = None;
-        let mut best_value = 0.0;
-        for (action, child_id) in node.children.iter() {
-            let child = db.get(child_id).unwrap();
-            let win_rate_for_opponent = child.wins as f32 / child.visits as f32;
-            let win_rate = 1. - win_rate_for_opponent;
-            let value = win_rate + (2. * (node.visits as f32).ln() / child.visits as f32).sqrt();
-            if best_action.is_none() || value > best_value {
-                best_action = Some(action);
-                best_node_id = Some(child_id);
-                best_value = value;
-            }
+/// How simulations play moves on the game, and get back to the root state afterwards.
+trait Stepper<T: Game> {
+    fn step(&mut self, game: &mut T, action: T::Action) -> f32;
+
+    fn reset(&mut self, game: &mut T);
+}
+
+/// One of the playouts from an expanded node besides the last, see
+/// [`MctsConfig::rollouts_per_leaf`]: on a copy of the leaf state after the same moves down the
+/// tree, with a seed of its own so that it plays the same on any thread.
+struct ExtraPlayout<T: Game> {
+    game: T,
+    trajectory: Trajectory<T, CloneStepper<T>>,
+    seed: u64,
+    returns: Vec<f32>,
+}
+
+/// Plays on a copy of the root state.
+struct CloneStepper<T> {
+    root: T,
+}
+
+impl<T: Game> Stepper<T> for CloneStepper<T> {
+    fn step(&mut self, game: &mut T, action: T::Action) -> f32 {
+        game.step(action).unwrap()
+    }
+
+    fn reset(&mut self, game: &mut T) {
+        game.clone_from(&self.root);
+    }
+}
+
+/// Plays on the root state itself and undoes every move afterwards.
+struct UndoStepper<T: Undo> {
+    undo_stack: Vec<(T::Action, T::UndoToken)>,
+}
+
+impl<T: Undo> Stepper<T> for UndoStepper<T> {
+    fn step(&mut self, game: &mut T, action: T::Action) -> f32 {
+        let (reward, token) = game.step_with_undo(action.clone()).unwrap();
+        self.undo_stack.push((action, token));
+        reward
+    }
+
+    fn reset(&mut self, game: &mut T) {
+        while let Some((action, token)) = self.undo_stack.pop() {
+            game.undo(action, token);
         }
-        (best_action.unwrap().clone(), best_node_id.unwrap().clone())
     }
+}
 
-    fn apply_actions(&self, game: &mut T, actions: Vec<T::Action>) {
-        for action in actions {
-            game.step(action).unwrap();
+/// The range of returns, used to scale values for the UCT formula.
+struct ReturnBounds {
+    min: f32,
+    max: f32,
+}
+
+/// A tree as [`SearchTree::save`] writes it.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct SavedTree {
+    version: u32,
+    /// In hex, as JSON numbers only hold 53 bits exactly.
+    root_hash: String,
+    bounds: (f32, f32),
+    /// The nodes in depth-first order, every node followed by the subtrees of its children.
+    nodes: Vec<SavedNode>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct SavedNode {
+    /// The index of the action leading to the node, `None` for the root.
+    action: Option<usize>,
+    visits: usize,
+    value_sum: f32,
+    /// The number of children, whose subtrees follow the node.
+    children: usize,
+    /// The value and moves of the proof.
+    proven: Option<(f32, usize)>,
+    /// The visits and value sums of actions, by index.
+    amaf: Vec<(usize, (usize, f32))>,
+}
+
+/// A node of the tree [`Mcts::export_tree`] writes as JSON.
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+struct ExportedNode {
+    action: Option<String>,
+    visits: usize,
+    q: f32,
+    prior: f32,
+    proven: Option<f32>,
+    children: Vec<ExportedNode>,
+}
+
+impl ReturnBounds {
+    fn unknown() -> Self {
+        Self {
+            min: f32::MAX,
+            max: f32::MIN,
         }
     }
 
-    fn expansion(&self, db: &mut NodeMap<T>, node_id: NodeId, game: &mut T) -> NodeId {
-        // Unless L ends the game decisively (e.g. win/loss/draw) for either player,
-        // create a new child node N of L and move to it.
+    fn update(&mut self, returns: &[f32]) {
+        for &value in returns {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+    }
 
-        let node = db.get(&node_id).unwrap();
-        if node.done {
-            return node_id;
+    /// Scale `value` to [0, 2]: 0 for the worst return seen, 2 for the best.
+    fn win_rate(&self, value: f32) -> f32 {
+        if self.max > self.min {
+            2. * (value - self.min) / (self.max - self.min)
+        } else {
+            1.
         }
+    }
+}
 
-        let action = {
-            let node = db.get_mut(&node_id).unwrap();
-            // if !node.done, then node.unvisited_actions should not be empty
-            node.unvisited_actions.pop().unwrap()
+/// The version of the files [`SearchTree::save`] writes.
+#[cfg(feature = "serde")]
+pub const TREE_VERSION: u32 = 1;
+
+/// A search tree kept between searches, see [`Mcts::search_from`].
+///
+/// With the `serde` feature, it can be saved to a file and loaded again to go on searching
+/// later, e.g. to resume an analysis or to ship a tree grown from the opening. The file is
+/// JSON: the format version, the [`Game::state_hash`] of the root, the return bounds and the
+/// nodes in depth-first order, each with the index of its move, its statistics and its
+/// number of children.
+pub struct SearchTree<T: Game> {
+    db: NodeMap<T>,
+    root: NodeId,
+    bounds: ReturnBounds,
+}
+
+impl<T: Game> SearchTree<T> {
+    /// A tree of nothing but the root `game`.
+    pub fn new(game: &T) -> Self {
+        let mut db = NodeMap::new();
+        let root = Node::insert(&mut db, game, None);
+        let bounds = if game.num_players() == 1 {
+            ReturnBounds::unknown()
+        } else {
+            ReturnBounds { min: -1., max: 1. }
         };
+        Self { db, root, bounds }
+    }
 
-        game.step(action.clone()).unwrap();
-        let new_node_id = Node::new(db, game, Some(node_id));
-        let node = db.get_mut(&node_id).unwrap();
-        node.children.insert(action, new_node_id);
-        new_node_id
+    /// The subtree after `action` was played from the root, or `None` if the search never
+    /// tried it. The rest of the tree is dropped.
+    pub fn advance(mut self, action: &T::Action) -> Option<Self> {
+        let root = *self.db[&self.root].children.get(action)?;
+        // The subtree keeps its ids, and the tree goes on numbering after them.
+        let mut db = NodeMap {
+            nodes: HashMap::new(),
+            next_id: self.db.next_id,
+            bytes: 0,
+            searched: 0,
+        };
+        let mut stack = vec![root];
+        while let Some(node_id) = stack.pop() {
+            let node = self
+                .db
+                .nodes
+                .remove(&node_id)
+                .expect("children are in the tree");
+            stack.extend(node.children.values().copied());
+            db.bytes += node.bytes();
+            db.nodes.insert(node_id, node);
+        }
+        db.get_mut(&root).unwrap().parent = None;
+        Some(Self {
+            db,
+            root,
+            bounds: self.bounds,
+        })
     }
 
-    fn simulation(&self, game: &mut T) -> Option<T::Player> {
-        // Play a random playout from node N. This is typically done by selecting uniform random moves until the game is finished.
-        loop {
-            if let Some(winner) = game.check_winner() {
-                return Some(winner);
-            }
-            let available_moves = game.get_available_moves();
-            if available_moves.is_empty() {
-                return None;
-            }
-            let action = available_moves
+    /// The number of simulations through the root so far.
+    pub fn visits(&self) -> usize {
+        self.db[&self.root].visits
+    }
+
+    pub fn nodes(&self) -> usize {
+        self.db.len()
+    }
+
+    /// An estimate of the memory the tree takes, see [`MctsConfig::max_memory_bytes`].
+    pub fn memory_bytes(&self) -> usize {
+        self.db.bytes()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Game> SearchTree<T> {
+    /// Write the tree to `path`. `game` is its root, to number the moves.
+    pub fn save(&self, game: &T, path: &Path) -> Result<()> {
+        fs::write(path, self.to_json(game).to_string() + "\n")
+            .map_err(IoError::path("write", path))?;
+        Ok(())
+    }
+
+    /// Read a tree [`SearchTree::save`] wrote, which must have been searched from `game`.
+    pub fn load(path: &Path, game: &T) -> Result<Self> {
+        let text = fs::read_to_string(path).map_err(IoError::path("read", path))?;
+        serde_json::from_str(&text)
+            .map_err(Error::from)
+            .and_then(|json| Self::from_json(&json, game))
+            .with_context(|| format!("in {}", path.display()))
+    }
+
+    pub fn to_json(&self, game: &T) -> Value {
+        let mut nodes = vec![];
+        let mut stack = vec![(None, self.root)];
+        while let Some((action, node_id)) = stack.pop() {
+            let node = &self.db[&node_id];
+            let mut children: Vec<_> = node
+                .children
                 .iter()
-                .choose(&mut rand::thread_rng())
-                .unwrap();
-            game.step(action.clone()).unwrap();
+                .map(|(action, &child_id)| (game.action_to_index(action), child_id))
+                .collect();
+            // The first child on top of the stack, so that the nodes come in order.
+            children.sort_unstable_by_key(|&(index, _)| std::cmp::Reverse(index));
+            let mut amaf: Vec<_> = node
+                .amaf
+                .iter()
+                .map(|(action, stats)| (game.action_to_index(action), stats))
+                .collect();
+            amaf.sort_unstable_by_key(|&(index, _)| index);
+            nodes.push(SavedNode {
+                action,
+                visits: node.visits,
+                value_sum: node.value_sum,
+                children: children.len(),
+                proven: node.proven.map(|proof| (proof.value, proof.moves)),
+                amaf: amaf
+                    .into_iter()
+                    .map(|(index, stats)| (index, (stats.visits, stats.value_sum)))
+                    .collect(),
+            });
+            stack.extend(children.into_iter().map(|(index, id)| (Some(index), id)));
         }
+        let tree = SavedTree {
+            version: TREE_VERSION,
+            root_hash: format!("{:016x}", game.state_hash()),
+            bounds: (self.bounds.min, self.bounds.max),
+            nodes,
+        };
+        serde_json::to_value(tree).expect("a tree is valid JSON")
     }
 
-    fn backpropagation(&self, db: &mut NodeMap<T>, node_id: NodeId, winner: Option<T::Player>) {
-        // Update the current move sequence with the simulation result. 
-        // Backpropagate this result up the tree. This updates the win and visit count of each node.
-
-        let mut node_id = node_id;
-        loop {
-            let node = db.get_mut(&node_id).unwrap();
-            node.visits += 1;
-            if let Some(winner) = &winner {
-                if &node.to_play == winner {
-                    node.wins += 1;
-                } else {
-                    node.wins -= 1;
-                }
-            }
-            if let Some(parent_id) = node.parent {
-                node_id = parent_id;
-            } else {
-                break;
-            }
+    /// The tree [`SearchTree::to_json`] wrote, checking that its root is `game` and that
+    /// every move in it is legal.
+    pub fn from_json(json: &Value, game: &T) -> Result<Self> {
+        let tree = SavedTree::deserialize(json)?;
+        if tree.version != TREE_VERSION {
+            return Err(ParseError::Invalid(format!(
+                "tree format version {}, expected {}",
+                tree.version, TREE_VERSION
+            ))
+            .into());
+        }
+        if tree.root_hash != format!("{:016x}", game.state_hash()) {
+            return Err(ParseError::Invalid(
+                "the tree was searched from another position".to_string(),
+            )
+            .into());
+        }
+        let (min, max) = tree.bounds;
+        let mut nodes = tree.nodes.iter().peekable();
+        let mut db = NodeMap::new();
+        let root = Self::load_node(&mut db, &mut nodes, game, None)?;
+        if nodes.next().is_some() {
+            return Err(ParseError::Invalid("more nodes than the tree has".to_string()).into());
         }
+        db.searched = 0;
+        Ok(Self {
+            db,
+            root,
+            bounds: ReturnBounds { min, max },
+        })
     }
 
-    fn best_action(&self, db: &NodeMap<T>, node_id: NodeId) -> T::Action {
-        let node = db.get(&node_id).unwrap();
-        let mut best_action = None;
-        let mut best_value = 0.0;
-        for (action, child_id) in node.children.iter() {
-            let child = db.get(child_id).unwrap();
-            let win_rate_for_opponent = child.wins as f32 / child.visits as f32;
-            let win_rate = 1. - win_rate_for_opponent;
-            if best_action.is_none() || win_rate > best_value {
-                best_action = Some(action);
-                best_value = win_rate;
+    /// Add the next node of `nodes`, in the state `game`, and its subtree to `db`.
+    fn load_node(
+        db: &mut NodeMap<T>,
+        nodes: &mut Peekable<slice::Iter<SavedNode>>,
+        game: &T,
+        parent: Option<NodeId>,
+    ) -> Result<NodeId> {
+        let fewer = || ParseError::Invalid("fewer nodes than the tree has".to_string());
+        let saved = nodes.next().ok_or_else(fewer)?;
+        let node_id = Node::insert(db, game, parent);
+        let action = |index: usize| -> Result<T::Action> {
+            if index >= game.action_space_size() {
+                return Err(ParseError::Invalid(format!(
+                    "action {} is out of the action space",
+                    index
+                ))
+                .into());
             }
+            Ok(game.index_to_action(index))
+        };
+        let node = db.get_mut(&node_id).unwrap();
+        node.visits = saved.visits;
+        node.value_sum = saved.value_sum;
+        node.proven = saved.proven.map(|(value, moves)| Proof { value, moves });
+        for &(index, (visits, value_sum)) in &saved.amaf {
+            node.amaf
+                .insert(action(index)?, AmafStats { visits, value_sum });
         }
-        best_action.unwrap().clone()
+        for _ in 0..saved.children {
+            let child = nodes.peek().ok_or_else(fewer)?;
+            let index = child
+                .action
+                .ok_or_else(|| ParseError::Invalid("a child without an action".to_string()))?;
+            let action = action(index)?;
+            let unvisited = db.unvisited_actions(node_id, game);
+            let Some(i) = unvisited.iter().position(|a| *a == action) else {
+                return Err(
+                    ParseError::Invalid(format!("{:?} isn't a move of {}", action, game)).into(),
+                );
+            };
+            unvisited.swap_remove(i);
+            let mut child = game.clone();
+            child.step(action.clone())?;
+            let child_id = Self::load_node(db, nodes, &child, Some(node_id))?;
+            db.get_mut(&node_id)
+                .unwrap()
+                .children
+                .insert(action, child_id);
+        }
+        Ok(node_id)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::tic_tac_toe::TicTacToe;
+/// A search running on a background thread, made with [`Mcts::ponder`]. Dropping it stops the
+/// search and discards the tree.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Ponder<T: Game> {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<SearchTree<T>>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Game> Ponder<T> {
+    /// Stop searching and take the tree, to [`SearchTree::advance`] it by the moves actually
+    /// played.
+    pub fn stop(mut self) -> SearchTree<T> {
+        self.stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        let thread = self.thread.take().expect("only stopped once");
+        thread.join().expect("the pondering thread panicked")
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Game> Drop for Ponder<T> {
+    fn drop(&mut self) {
+        self.stop.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+/// Sample one of `outcomes` by its probability.
+pub fn sample_outcome<A: Clone>(outcomes: &[(A, f32)], rng: &mut impl Rng) -> A {
+    let dist = WeightedIndex::new(outcomes.iter().map(|(_, probability)| *probability))
+        .expect("chance outcomes must have positive total probability");
+    outcomes[dist.sample(rng)].0.clone()
+}
+
+impl<T: Game> Mcts<T> {
+    /// A search with `num_simulations` simulations and the default config.
+    pub fn new(num_simulations: usize) -> Self {
+        Self::with_config(MctsConfig {
+            num_simulations,
+            ..Default::default()
+        })
+    }
+
+    pub fn with_config(config: MctsConfig) -> Self {
+        Self {
+            _phantom: std::marker::PhantomData,
+            config,
+            rollout_policy: None,
+            extra_playouts: |mcts, playouts| {
+                for playout in playouts {
+                    mcts.extra_playout(playout);
+                }
+            },
+        }
+    }
+
+    /// Play the moves of the playouts with `policy` rather than [`MctsConfig::rollout`].
+    pub fn with_rollout_policy(mut self, policy: impl RolloutPolicy<T> + 'static) -> Self {
+        self.rollout_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Play the [`MctsConfig::rollouts_per_leaf`] playouts of every expanded node in parallel
+    /// on the rayon thread pool. A seeded search still gives the same result.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_parallel_playouts(mut self) -> Self
+    where
+        T: Send,
+        T::Action: Send,
+        T::Player: Send,
+    {
+        self.extra_playouts = |mcts, playouts| {
+            use rayon::prelude::*;
+            playouts
+                .par_iter_mut()
+                .for_each(|playout| mcts.extra_playout(playout));
+        };
+        self
+    }
+
+    /// Like [`Mcts::search`], but an error rather than a panic if there's no move to choose.
+    pub fn try_search(&self, game: &T) -> Result<SearchResult<T::Action>, SearchError> {
+        if game.done() {
+            return Err(SearchError::GameOver);
+        }
+        if !game.chance_outcomes().is_empty() {
+            return Err(SearchError::ChancePending);
+        }
+        if game.get_available_moves().is_empty() {
+            return Err(SearchError::NoLegalMoves);
+        }
+        if self.config.num_simulations == 0 && self.solve(game).is_none() {
+            return Err(SearchError::NoSimulations);
+        }
+        // The root and a child at least.
+        if self.config.max_nodes.is_some_and(|max| max < 2) {
+            return Err(SearchError::TreeTooSmall);
+        }
+        if let Some(result) = self.solved_result(game) {
+            return Ok(result);
+        }
+        let (db, root) = self.build_tree(game);
+        if db[&root].children.is_empty() {
+            return Err(SearchError::TreeTooSmall);
+        }
+        Ok(self.result(&db, root))
+    }
+
+    /// Search for the best action for the player to move in `game`, which must not be over.
+    /// Panics if there's no move to choose, see [`Mcts::try_search`].
+    pub fn search(&self, game: &T) -> SearchResult<T::Action> {
+        if let Some(result) = self.solved_result(game) {
+            return result;
+        }
+        let (db, root) = self.build_tree(game);
+        self.result(&db, root)
+    }
+
+    /// The result of the exact solver, if `game` can be solved within the solver budget.
+    fn solved_result(&self, game: &T) -> Option<SearchResult<T::Action>> {
+        let solution = self.solve(game)?;
+        let action = solution.action?;
+        Some(SearchResult {
+            action: action.clone(),
+            children: vec![],
+            max_depth: 0,
+            mean_depth: 0.,
+            nodes: 0,
+            allocated_nodes: 0,
+            principal_variation: vec![action],
+            proof: Some(Proof {
+                value: solution.value,
+                moves: solution.depth,
+            }),
+        })
+    }
+
+    fn result(&self, db: &NodeMap<T>, root: NodeId) -> SearchResult<T::Action> {
+        let node = &db[&root];
+        let children = Self::children_by_visits(db, node)
+            .into_iter()
+            .map(|(action, child_id, prior)| {
+                let child = &db[&child_id];
+                ChildStatistics {
+                    action: action.clone(),
+                    visits: child.visits,
+                    q: child.value_sum / child.visits.max(1) as f32,
+                    prior,
+                }
+            })
+            .collect();
+        let mut principal_variation = vec![];
+        let mut node_id = root;
+        while !db[&node_id].children.is_empty() {
+            let action = self.best_action(db, node_id);
+            node_id = db[&node_id].children[&action];
+            principal_variation.push(action);
+        }
+        let (depth_sum, nodes) = Self::depth_sum(db, root, 0);
+        SearchResult {
+            action: principal_variation
+                .first()
+                .expect("the tree limits leave no room to search a move")
+                .clone(),
+            children,
+            max_depth: Self::depth(db, root),
+            mean_depth: depth_sum as f32 / (nodes - 1).max(1) as f32,
+            nodes,
+            allocated_nodes: db.searched,
+            principal_variation,
+            proof: node.proven,
+        }
+    }
+
+    /// The sum of the depths of the nodes in the subtree of `node_id`, which is at `depth`,
+    /// and their number.
+    fn depth_sum(db: &NodeMap<T>, node_id: NodeId, depth: usize) -> (usize, usize) {
+        db[&node_id]
+            .children
+            .values()
+            .map(|&child_id| Self::depth_sum(db, child_id, depth + 1))
+            .fold((depth, 1), |(sum, nodes), (child_sum, child_nodes)| {
+                (sum + child_sum, nodes + child_nodes)
+            })
+    }
+
+    /// Like [`Mcts::search`], also returning the search tree in `format`: the visits `N` of
+    /// every node, its mean value `Q` for the player who moved into it, and its prior `P`,
+    /// uniform over the moves of the parent or the probability of a chance outcome.
+    pub fn export_tree(&self, game: &T, format: TreeFormat) -> (SearchResult<T::Action>, String) {
+        let (db, root) = self.build_tree(game);
+        let tree = match format {
+            TreeFormat::Dot => {
+                let mut dot = "digraph mcts {\n  node [shape=box];\n".to_string();
+                Self::write_dot(&db, root, "root".to_string(), 1., &mut 0, &mut dot);
+                dot.push_str("}\n");
+                dot
+            }
+            #[cfg(feature = "serde")]
+            TreeFormat::Json => serde_json::to_string(&Self::export_node(&db, root, None, 1.))
+                .expect("a tree is valid JSON"),
+        };
+        (self.result(&db, root), tree)
+    }
+
+    /// The children of `node`, the most visited first, with their priors.
+    fn children_by_visits<'a>(
+        db: &'a NodeMap<T>,
+        node: &'a Node<T>,
+    ) -> Vec<(&'a T::Action, NodeId, f32)> {
+        let moves = node.children.len() + node.unvisited_actions.as_ref().map_or(0, Vec::len);
+        let mut children: Vec<_> = node
+            .children
+            .iter()
+            .map(|(action, &child_id)| {
+                let prior = match node.chance_outcomes.iter().find(|(a, _)| a == action) {
+                    Some(&(_, probability)) => probability,
+                    None => 1. / moves as f32,
+                };
+                (action, child_id, prior)
+            })
+            .collect();
+        children.sort_by_key(|&(_, child_id, _)| std::cmp::Reverse(db[&child_id].visits));
+        children
+    }
+
+    /// Write the node `node_id` and its subtree as DOT nodes `n<id>`, numbering them from
+    /// `next_id`.
+    fn write_dot(
+        db: &NodeMap<T>,
+        node_id: NodeId,
+        name: String,
+        prior: f32,
+        next_id: &mut usize,
+        dot: &mut String,
+    ) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+        let node = &db[&node_id];
+        let mut label = format!(
+            "{}\\nN={} Q={:.3} P={:.3}",
+            name,
+            node.visits,
+            node.value_sum / node.visits.max(1) as f32,
+            prior
+        );
+        if let Some(proof) = node.proven {
+            label.push_str(&format!("\\n{}", proof));
+        }
+        let label = label.replace('"', "\\\"");
+        dot.push_str(&format!("  n{} [label=\"{}\"];\n", id, label));
+        for (action, child_id, prior) in Self::children_by_visits(db, node) {
+            let child = Self::write_dot(db, child_id, format!("{:?}", action), prior, next_id, dot);
+            dot.push_str(&format!("  n{} -> n{};\n", id, child));
+        }
+        id
+    }
+
+    #[cfg(feature = "serde")]
+    fn export_node(
+        db: &NodeMap<T>,
+        node_id: NodeId,
+        action: Option<&T::Action>,
+        prior: f32,
+    ) -> ExportedNode {
+        let node = &db[&node_id];
+        ExportedNode {
+            action: action.map(|action| format!("{:?}", action)),
+            visits: node.visits,
+            q: node.value_sum / node.visits.max(1) as f32,
+            prior,
+            proven: node.proven.map(|proof| proof.value),
+            children: Self::children_by_visits(db, node)
+                .into_iter()
+                .map(|(action, child_id, prior)| {
+                    Self::export_node(db, child_id, Some(action), prior)
+                })
+                .collect(),
+        }
+    }
+
+    /// The root visit counts over the action space and the root value of a search from
+    /// `game`, e.g. as training targets for self-play.
+    pub fn search_statistics(&self, game: &T) -> SearchStatistics {
+        if let Some(statistics) = self.solved_statistics(game) {
+            return statistics;
+        }
+        let (db, root) = self.build_tree(game);
+        self.statistics(&db, root, game)
+    }
+
+    /// Like [`Mcts::search_statistics`], also passing the statistics so far to `report` every
+    /// `interval` simulations, e.g. to show how a search is going.
+    pub fn search_statistics_with_progress(
+        &self,
+        game: &T,
+        interval: usize,
+        mut report: impl FnMut(&SearchStatistics),
+    ) -> SearchStatistics {
+        if let Some(statistics) = self.solved_statistics(game) {
+            report(&statistics);
+            return statistics;
+        }
+        let stepper = CloneStepper { root: game.clone() };
+        let (db, root) =
+            self.build_tree_with(&mut game.clone(), stepper, |db, root, simulations| {
+                if simulations % interval.max(1) == 0 {
+                    report(&self.statistics(db, root, game));
+                }
+            });
+        self.statistics(&db, root, game)
+    }
+
+    /// The exact solution of `game`, with a solver budget and if it's small enough.
+    fn solve(&self, game: &T) -> Option<Solution<T::Action>> {
+        solver::solve(game, self.config.solver_budget?)
+    }
+
+    /// Statistics putting every simulation on the proven best move, if `game` can be solved.
+    fn solved_statistics(&self, game: &T) -> Option<SearchStatistics> {
+        let solution = self.solve(game)?;
+        let mut visit_counts = vec![0; game.action_space_size()];
+        visit_counts[game.action_to_index(solution.action.as_ref()?)] = self.config.num_simulations;
+        Some(SearchStatistics {
+            root_value: solution.value,
+            visit_counts,
+            depth: solution.depth,
+            nodes: 0,
+            cache: None,
+        })
+    }
+
+    fn statistics(&self, db: &NodeMap<T>, root: NodeId, game: &T) -> SearchStatistics {
+        let node = db.get(&root).unwrap();
+        let mut visit_counts = vec![0; game.action_space_size()];
+        let (mut value_sum, mut visits) = (0., 0);
+        for (action, child_id) in node.children.iter() {
+            let child = db.get(child_id).unwrap();
+            visit_counts[game.action_to_index(action)] = child.visits;
+            value_sum += child.value_sum;
+            visits += child.visits;
+        }
+        SearchStatistics {
+            root_value: node
+                .proven
+                .map_or(value_sum / visits.max(1) as f32, |proof| proof.value),
+            visit_counts,
+            depth: Self::depth(db, root),
+            nodes: db.searched,
+            cache: None,
+        }
+    }
+
+    /// The number of moves on the longest path down from `node_id`.
+    fn depth(db: &NodeMap<T>, node_id: NodeId) -> usize {
+        let node = db.get(&node_id).unwrap();
+        node.children
+            .values()
+            .map(|&child_id| 1 + Self::depth(db, child_id))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Like [`Mcts::search`], but simulations play on `game` itself and undo their moves
+    /// instead of cloning the root state every time. `game` is left unchanged.
+    pub fn search_in_place(&self, game: &mut T) -> SearchResult<T::Action>
+    where
+        T: Undo,
+    {
+        if let Some(result) = self.solved_result(game) {
+            return result;
+        }
+        let stepper = UndoStepper { undo_stack: vec![] };
+        let (db, root) = self.build_tree_with(game, stepper, |_, _, _| {});
+        self.result(&db, root)
+    }
+
+    fn build_tree(&self, game: &T) -> (NodeMap<T>, NodeId) {
+        let stepper = CloneStepper { root: game.clone() };
+        self.build_tree_with(&mut game.clone(), stepper, |_, _, _| {})
+    }
+
+    /// Run the simulations, calling `on_simulation` with the tree and the number of
+    /// simulations so far after each one.
+    fn build_tree_with<S: Stepper<T>>(
+        &self,
+        game: &mut T,
+        stepper: S,
+        on_simulation: impl FnMut(&NodeMap<T>, NodeId, usize),
+    ) -> (NodeMap<T>, NodeId) {
+        let mut tree = SearchTree::new(game);
+        let simulations = self.config.num_simulations;
+        self.grow(&mut tree, game, stepper, simulations, None, on_simulation);
+        (tree.db, tree.root)
+    }
+
+    /// Add up to `simulations` simulations to `tree`, whose root must be `game`, stopping
+    /// early once `stop` is set.
+    fn grow<S: Stepper<T>>(
+        &self,
+        tree: &mut SearchTree<T>,
+        game: &mut T,
+        stepper: S,
+        simulations: usize,
+        stop: Option<&AtomicBool>,
+        mut on_simulation: impl FnMut(&NodeMap<T>, NodeId, usize),
+    ) {
+        let SearchTree { db, root, bounds } = tree;
+        let root = *root;
+        let prove = self.config.mcts_solver && game.num_players() == 2;
+        let _span = info_span!("search", simulations).entered();
+
+        let mut rng = match self.config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::seed_from_u64(random::rng().gen()),
+        };
+        let start = now();
+        db.searched = 0;
+        let mut trajectory = Trajectory::new(stepper);
+        let mut scratch = Scratch::<T>::new();
+        let mut completed = 0;
+        for simulation in 1..=simulations {
+            if stop.is_some_and(|stop| stop.load(std::sync::atomic::Ordering::Relaxed)) {
+                break;
+            }
+            if self.tree_full(db) {
+                match self.config.full_tree {
+                    FullTree::Stop => break,
+                    FullTree::Recycle => {
+                        if !self.recycle(db, root) {
+                            break;
+                        }
+                    }
+                }
+            }
+            let leaf = trace_span!("selection", simulation).in_scope(|| {
+                let leaf = self.selection(db, root, bounds, &mut rng, &mut scratch.path);
+                trace!("{:?} to {:?}", scratch.path, leaf);
+                leaf
+            });
+            self.apply_actions(game, &mut scratch.path, &mut trajectory);
+            let expanded_node = trace_span!("expansion").in_scope(|| {
+                let expanded_node = self.expansion(db, leaf, game, &mut trajectory, &mut rng);
+                trace!("{:?} to {:?}, {} nodes", leaf, expanded_node, db.len());
+                expanded_node
+            });
+            let span = trace_span!("simulation").entered();
+            let proof = if prove {
+                Self::prove_leaf(db, expanded_node, game)
+            } else {
+                None
+            };
+            let returns = if let Some(proof) = proof {
+                // No need to play out a proven node.
+                let mut returns = vec![-proof.value; 2];
+                returns[db[&expanded_node].to_play_index] = proof.value;
+                returns
+            } else {
+                // Every playout but the last plays on a copy of the leaf state, after the same
+                // moves down the tree.
+                let mut extras: Vec<_> = (1..self.config.rollouts_per_leaf)
+                    .map(|_| {
+                        let mut extra = Trajectory::new(CloneStepper { root: game.clone() });
+                        extra.moves.extend_from_slice(&trajectory.moves);
+                        extra.reward = trajectory.reward;
+                        ExtraPlayout {
+                            game: game.clone(),
+                            trajectory: extra,
+                            seed: rng.gen(),
+                            returns: vec![],
+                        }
+                    })
+                    .collect();
+                (self.extra_playouts)(self, &mut extras);
+                let mut sum = vec![];
+                for extra in &extras {
+                    if self.config.rave.is_some() {
+                        self.update_amaf(
+                            db,
+                            expanded_node,
+                            &extra.trajectory.moves,
+                            &extra.returns,
+                            &mut scratch.nodes,
+                        );
+                    }
+                    add_returns(&mut sum, &extra.returns);
+                }
+                let returns = self.playout(game, &mut trajectory, &mut rng, &mut scratch);
+                if self.config.rave.is_some() {
+                    self.update_amaf(
+                        db,
+                        expanded_node,
+                        &trajectory.moves,
+                        &returns,
+                        &mut scratch.nodes,
+                    );
+                }
+                add_returns(&mut sum, &returns);
+                let rollouts = self.config.rollouts_per_leaf.max(1) as f32;
+                sum.into_iter().map(|value| value / rollouts).collect()
+            };
+            trace!(
+                "returns {:?} after {} moves{}",
+                returns,
+                trajectory.moves.len(),
+                if proof.is_some() { ", proven" } else { "" }
+            );
+            drop(span);
+            trace_span!("backpropagation").in_scope(|| {
+                bounds.update(&returns);
+                self.backpropagation(db, expanded_node, &returns);
+                if proof.is_some() {
+                    Self::update_proofs(db, expanded_node);
+                }
+                trace!("{:?} up from {:?}", returns, expanded_node);
+            });
+            trajectory.reset(game);
+            on_simulation(db, root, simulation);
+            completed = simulation;
+        }
+        self.log_search(db, root, completed, start);
+    }
+
+    /// Log a summary of `simulations` simulations just run at info level, and the whole tree
+    /// at trace level.
+    fn log_search(
+        &self,
+        db: &NodeMap<T>,
+        root: NodeId,
+        simulations: usize,
+        start: Option<Instant>,
+    ) {
+        if tracing::enabled!(Level::INFO) && !db[&root].children.is_empty() {
+            let action = self.best_action(db, root);
+            let child = &db[&db[&root].children[&action]];
+            let time = start
+                .map(|start| format!(" in {:.1?}", start.elapsed()))
+                .unwrap_or_default();
+            info!(
+                "{} simulations{}: move {:?}, value {:.3}, {} nodes, depth {}",
+                simulations,
+                time,
+                action,
+                child.value_sum / child.visits.max(1) as f32,
+                db.len(),
+                Self::depth(db, root)
+            );
+        }
+        let _span = trace_span!("tree").entered();
+        if tracing::enabled!(Level::TRACE) {
+            self.print_tree(db, &root, 0);
+        }
+    }
+
+    /// Whether `db` has reached [`MctsConfig::max_nodes`] or [`MctsConfig::max_memory_bytes`].
+    fn tree_full(&self, db: &NodeMap<T>) -> bool {
+        self.config.max_nodes.is_some_and(|max| db.len() >= max)
+            || self
+                .config
+                .max_memory_bytes
+                .is_some_and(|max| db.bytes() >= max)
+    }
+
+    /// Drop the least visited subtrees below the children of the root, see
+    /// [`FullTree::Recycle`]. Proven nodes are kept. Returns whether anything was dropped.
+    fn recycle(&self, db: &mut NodeMap<T>, root: NodeId) -> bool {
+        let max_nodes = self.config.max_nodes.map(|max| max / 4 * 3);
+        let max_bytes = self.config.max_memory_bytes.map(|max| max / 4 * 3);
+        let root_children: HashSet<_> = db[&root].children.values().copied().collect();
+        // By visits and then by id, so that seeded searches repeat.
+        let mut candidates: Vec<_> = db
+            .nodes
+            .iter()
+            .filter(|(node_id, node)| {
+                node.parent.is_some() && !root_children.contains(node_id) && node.proven.is_none()
+            })
+            .map(|(&node_id, node)| (node.visits, node_id.0))
+            .collect();
+        candidates.sort_unstable();
+        let len = db.len();
+        for (_, id) in candidates {
+            let over = max_nodes.is_some_and(|max| db.len() > max)
+                || max_bytes.is_some_and(|max| db.bytes() > max);
+            if !over {
+                break;
+            }
+            let node_id = NodeId(id);
+            // Dropped with an ancestor already.
+            let Some(node) = db.get(&node_id) else {
+                continue;
+            };
+            let parent = db.get_mut(&node.parent.unwrap()).unwrap();
+            let action = parent
+                .children
+                .iter()
+                .find(|&(_, &child_id)| child_id == node_id)
+                .map(|(action, _)| action.clone())
+                .expect("a node is a child of its parent");
+            parent.children.remove(&action);
+            parent
+                .unvisited_actions
+                .as_mut()
+                .expect("a node with children listed its moves")
+                .push(action);
+            db.remove_subtree(node_id);
+        }
+        db.len() < len
+    }
+
+    /// Like [`Mcts::search`], continuing `tree` if there is one, e.g. the tree of a
+    /// [`Ponder`]. Its root must be `game`.
+    pub fn search_from(&self, game: &T, tree: Option<SearchTree<T>>) -> SearchResult<T::Action> {
+        let mut tree = tree.unwrap_or_else(|| SearchTree::new(game));
+        self.grow_tree(game, &mut tree)
+    }
+
+    /// Like [`Mcts::search_from`], leaving the grown tree in `tree`, e.g. to search on later
+    /// or to [`SearchTree::save`] it.
+    pub fn grow_tree(&self, game: &T, tree: &mut SearchTree<T>) -> SearchResult<T::Action> {
+        if let Some(result) = self.solved_result(game) {
+            return result;
+        }
+        let stepper = CloneStepper { root: game.clone() };
+        let simulations = self.config.num_simulations;
+        self.grow(
+            tree,
+            &mut game.clone(),
+            stepper,
+            simulations,
+            None,
+            |_, _, _| {},
+        );
+        self.result(&tree.db, tree.root)
+    }
+
+    /// Like [`Mcts::search`], but running simulations for `time` rather than a number of them.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn search_for(&self, game: &T, time: Duration) -> SearchResult<T::Action> {
+        if let Some(result) = self.solved_result(game) {
+            return result;
+        }
+        let stop = AtomicBool::new(false);
+        let mut tree = SearchTree::new(game);
+        let stepper = CloneStepper { root: game.clone() };
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(time);
+                stop.store(true, std::sync::atomic::Ordering::Relaxed);
+            });
+            self.grow(
+                &mut tree,
+                &mut game.clone(),
+                stepper,
+                usize::MAX,
+                Some(&stop),
+                |_, _, _| {},
+            );
+        });
+        self.result(&tree.db, tree.root)
+    }
+
+    /// Keep searching from `game` on a background thread, e.g. while the opponent thinks, for
+    /// at most `max_simulations` simulations or until [`Ponder::stop`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn ponder(&self, game: &T, max_simulations: usize) -> Ponder<T>
+    where
+        T: Send + 'static,
+        T::Action: Send,
+        T::Player: Send,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let mcts = self.clone();
+        let mut game = game.clone();
+        let thread = {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                let mut tree = SearchTree::new(&game);
+                let stepper = CloneStepper { root: game.clone() };
+                mcts.grow(
+                    &mut tree,
+                    &mut game,
+                    stepper,
+                    max_simulations,
+                    Some(&stop),
+                    |_, _, _| {},
+                );
+                tree
+            })
+        };
+        Ponder {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    fn print_tree(&self, db: &NodeMap<T>, root: &NodeId, level: usize) {
+        let node = db.get(root).unwrap();
+        let indent = " ".repeat(level * 2);
+        if level == 0 {
+            trace!(
+                "{}{:?} {:?} {:?} {:?}",
+                indent,
+                node.to_play,
+                node.visits,
+                node.value_sum,
+                node.done
+            );
+        }
+        for (action, child_id) in node.children.iter() {
+            let child = db.get(child_id).unwrap();
+            trace!(
+                "{}{:?} {:?} {:?} {:?}",
+                indent,
+                action,
+                child.to_play,
+                child.value_sum / child.visits as f32,
+                child.done
+            );
+            self.print_tree(db, child_id, level + 1);
+        }
+    }
+
+    fn selection(
+        &self,
+        db: &NodeMap<T>,
+        root_id: NodeId,
+        bounds: &ReturnBounds,
+        rng: &mut impl Rng,
+        path: &mut Vec<T::Action>,
+    ) -> NodeId {
+        // Start from root R and select successive child nodes until a leaf node L is reached.
+        // The root is the current game state and a leaf is any node that has a potential child from which no simulation (playout) has yet been initiated.
+        // The actions followed are left in `path`.
+        let mut node_id = root_id;
+        path.clear();
+        loop {
+            let node = db.get(&node_id).unwrap();
+            if node.done || node.proven.is_some() {
+                break;
+            }
+            if !node.chance_outcomes.is_empty() {
+                // Chance nodes follow the sampled outcome, and stop at it if it hasn't been expanded yet.
+                let action = sample_outcome(&node.chance_outcomes, rng);
+                match node.children.get(&action) {
+                    Some(child_id) => {
+                        path.push(action);
+                        node_id = *child_id;
+                        continue;
+                    }
+                    None => break,
+                }
+            }
+            if node.fully_expanded() || !self.can_widen(node) {
+                let (action, child_id, _) = self.best_child(db, node_id, bounds);
+                path.push(action);
+                node_id = child_id;
+                continue;
+            }
+            let Some(urgency) = self.urgency(db, node, bounds) else {
+                break;
+            };
+            let (action, child_id, score) = self.best_child(db, node_id, bounds);
+            if urgency > score {
+                break;
+            }
+            path.push(action);
+            node_id = child_id;
+        }
+        node_id
+    }
+
+    fn can_widen(&self, node: &Node<T>) -> bool {
+        match &self.config.progressive_widening {
+            Some(pw) => node.children.len() < pw.max_children(node.visits),
+            None => true,
+        }
+    }
+
+    /// The score of the unvisited moves of `node`, to compare with its visited children, or
+    /// `None` to expand one in any case.
+    fn urgency(&self, db: &NodeMap<T>, node: &Node<T>, bounds: &ReturnBounds) -> Option<f32> {
+        if node.children.is_empty() {
+            return None;
+        }
+        let value = match self.config.first_play_urgency {
+            FirstPlayUrgency::Infinite => return None,
+            FirstPlayUrgency::Lowest => return Some(0.),
+            FirstPlayUrgency::Parent { reduction } => {
+                // The children's values are from the perspective of the player to move here.
+                let (value_sum, visits) = node
+                    .children
+                    .values()
+                    .map(|child_id| &db[child_id])
+                    .fold((0., 0), |(sum, visits), child| {
+                        (sum + child.value_sum, visits + child.visits)
+                    });
+                value_sum / visits.max(1) as f32 - reduction
+            }
+            FirstPlayUrgency::Value(value) => value,
+        };
+        Some(bounds.win_rate(value))
+    }
+
+    /// The child with the highest UCT score, and that score.
+    fn best_child(
+        &self,
+        db: &NodeMap<T>,
+        node_id: NodeId,
+        bounds: &ReturnBounds,
+    ) -> (T::Action, NodeId, f32) {
+        // select the child node with the highest UCT value.
+        let node = db.get(&node_id).unwrap();
+        let lost = |child: &Node<T>| {
+            child
+                .proven
+                .is_some_and(|proof| proof.for_parent(node, child).value <= -1.)
+        };
+        let all_lost = node.children.values().all(|child_id| lost(&db[child_id]));
+        let mut best_action = None;
+        let mut best_node_id = None;
+        let mut best_value = 0.0;
+        for (action, child_id) in node.children.iter() {
+            let child = db.get(child_id).unwrap();
+            if lost(child) && !all_lost {
+                continue;
+            }
+            let mut win_rate = bounds.win_rate(child.value_sum / child.visits as f32);
+            if let Some(rave) = &self.config.rave {
+                if let Some(amaf) = node.amaf.get(action).filter(|amaf| amaf.visits > 0) {
+                    let amaf_win_rate = bounds.win_rate(amaf.value_sum / amaf.visits as f32);
+                    let beta = rave.beta(node.visits);
+                    win_rate = (1. - beta) * win_rate + beta * amaf_win_rate;
+                }
+            }
+            let value = win_rate
+                + self.config.exploration
+                    * ((node.visits as f32).ln() / child.visits as f32).sqrt();
+            if best_action.is_none() || value > best_value {
+                best_action = Some(action);
+                best_node_id = Some(child_id);
+                best_value = value;
+            }
+        }
+        (
+            best_action.unwrap().clone(),
+            *best_node_id.unwrap(),
+            best_value,
+        )
+    }
+
+    fn apply_actions<S: Stepper<T>>(
+        &self,
+        game: &mut T,
+        actions: &mut Vec<T::Action>,
+        trajectory: &mut Trajectory<T, S>,
+    ) {
+        for action in actions.drain(..) {
+            trajectory.step(game, action);
+        }
+    }
+
+    fn expansion<S: Stepper<T>>(
+        &self,
+        db: &mut NodeMap<T>,
+        node_id: NodeId,
+        game: &mut T,
+        trajectory: &mut Trajectory<T, S>,
+        rng: &mut impl Rng,
+    ) -> NodeId {
+        // Unless L ends the game decisively (e.g. win/loss/draw) for either player,
+        // create a new child node N of L and move to it.
+
+        let node = db.get(&node_id).unwrap();
+        if node.done || node.proven.is_some() {
+            return node_id;
+        }
+
+        let action = {
+            db.unvisited_actions(node_id, game);
+            let Node {
+                unvisited_actions: Some(unvisited),
+                chance_outcomes,
+                ..
+            } = db.get_mut(&node_id).unwrap()
+            else {
+                unreachable!("the moves were just listed");
+            };
+            // if !node.done, then the node should have unvisited actions.
+            let index = if !chance_outcomes.is_empty() {
+                // Selection stopped because it sampled an unexpanded outcome, so sample again
+                // from the unexpanded outcomes only, without collecting their probabilities.
+                let probability = |action: &T::Action| {
+                    chance_outcomes
+                        .iter()
+                        .find(|(outcome, _)| outcome == action)
+                        .map_or(0., |(_, probability)| *probability)
+                };
+                let total: f32 = unvisited.iter().map(probability).sum();
+                let mut target = rng.gen::<f32>() * total;
+                unvisited
+                    .iter()
+                    .position(|action| {
+                        target -= probability(action);
+                        target < 0.
+                    })
+                    .unwrap_or(0)
+            } else if self.config.progressive_widening.is_some() {
+                // Pick at random so that progressive widening doesn't favour the move order of the game.
+                rng.gen_range(0..unvisited.len())
+            } else {
+                // Every move gets expanded, so take them in the order of the game.
+                unvisited.len() - 1
+            };
+            unvisited.swap_remove(index)
+        };
+
+        trajectory.step(game, action.clone());
+        let new_node_id = Node::insert(db, game, Some(node_id));
+        let node = db.get_mut(&node_id).unwrap();
+        node.children.insert(action, new_node_id);
+        new_node_id
+    }
+
+    /// Play out the state `game` is in and return its values for every player, or the
+    /// reward collected since the root in single-player games.
+    fn playout<S: Stepper<T>>(
+        &self,
+        game: &mut T,
+        trajectory: &mut Trajectory<T, S>,
+        rng: &mut impl Rng,
+        scratch: &mut Scratch<T>,
+    ) -> Vec<f32> {
+        self.simulation(game, trajectory, rng, &mut scratch.moves);
+        if game.num_players() == 1 {
+            // Single-player games are scored by their cumulative reward. Rewards collected
+            // before the root are the same for every node, so they can be left out.
+            vec![trajectory.reward]
+        } else {
+            self.config.scoring.values(game)
+        }
+    }
+
+    fn extra_playout(&self, playout: &mut ExtraPlayout<T>) {
+        let mut rng = StdRng::seed_from_u64(playout.seed);
+        let ExtraPlayout {
+            game,
+            trajectory,
+            returns,
+            ..
+        } = playout;
+        *returns = self.playout(game, trajectory, &mut rng, &mut Scratch::new());
+    }
+
+    fn simulation<S: Stepper<T>>(
+        &self,
+        game: &mut T,
+        trajectory: &mut Trajectory<T, S>,
+        rng: &mut impl Rng,
+        moves: &mut Vec<T::Action>,
+    ) {
+        // Play a random playout from node N, by default by selecting uniform random moves until the game is finished.
+        let policy = self
+            .rollout_policy
+            .as_deref()
+            .unwrap_or(&self.config.rollout as &dyn RolloutPolicy<T>);
+        for _ in 0..self.config.max_rollout_depth.unwrap_or(usize::MAX) {
+            if game.done() {
+                return;
+            }
+            let chance_outcomes = game.chance_outcomes();
+            if !chance_outcomes.is_empty() {
+                trajectory.step(game, sample_outcome(&chance_outcomes, rng));
+                continue;
+            }
+            game.get_available_moves_into(moves);
+            if moves.is_empty() {
+                return;
+            }
+            let action = policy.choose(game, moves, rng);
+            trajectory.step(game, action);
+        }
+    }
+
+    fn update_amaf(
+        &self,
+        db: &mut NodeMap<T>,
+        node_id: NodeId,
+        moves: &[(Option<T::Player>, T::Action)],
+        returns: &[f32],
+        path: &mut Vec<NodeId>,
+    ) {
+        // A node at depth `d` was reached by the first `d` moves, so every later move made by
+        // the player to play at that node counts as if it had been played first from there.
+        path.clear();
+        path.push(node_id);
+        while let Some(parent) = db.get(path.last().unwrap()).unwrap().parent {
+            path.push(parent);
+        }
+        for (depth, node_id) in path.drain(..).rev().enumerate() {
+            let node = db.get_mut(&node_id).unwrap();
+            if !node.chance_outcomes.is_empty() {
+                continue;
+            }
+            let mut seen = HashSet::new();
+            for (player, action) in &moves[depth..] {
+                if player.as_ref() != Some(&node.to_play) || !seen.insert(action) {
+                    continue;
+                }
+                let stats = node.amaf.entry(action.clone()).or_default();
+                stats.visits += 1;
+                stats.value_sum += returns[node.to_play_index];
+            }
+        }
+    }
+
+    fn backpropagation(&self, db: &mut NodeMap<T>, node_id: NodeId, returns: &[f32]) {
+        // Update the current move sequence with the simulation result.
+        // Backpropagate this result up the tree. This updates the value and visit count of each node,
+        // each from the perspective of the player who moved into it.
+
+        let mut node_id = node_id;
+        loop {
+            let mover = db[&node_id]
+                .parent
+                .map(|parent_id| db[&parent_id].to_play_index);
+            let node = db.get_mut(&node_id).unwrap();
+            node.visits += 1;
+            if let Some(mover) = mover {
+                node.value_sum += returns[mover];
+            }
+            if let Some(parent_id) = node.parent {
+                node_id = parent_id;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// The child with the highest mean value, except that proven wins come first, the
+    /// quickest first, and proven losses last, the slowest first.
+    fn best_action(&self, db: &NodeMap<T>, node_id: NodeId) -> T::Action {
+        let node = db.get(&node_id).unwrap();
+        let mut best_action = None;
+        let mut best_value = (0, 0.);
+        for (action, child_id) in node.children.iter() {
+            let child = db.get(child_id).unwrap();
+            let value = match child.proven.map(|proof| proof.for_parent(node, child)) {
+                Some(proof) if proof.value >= 1. => (2, -(proof.moves as f32)),
+                Some(proof) if proof.value <= -1. => (0, proof.moves as f32),
+                _ => (1, 1. + child.value_sum / child.visits as f32),
+            };
+            if best_action.is_none() || value > best_value {
+                best_action = Some(action);
+                best_value = value;
+            }
+        }
+        best_action.unwrap().clone()
+    }
+
+    /// The proof of a leaf: its own if it was proven before, or its outcome if it's terminal.
+    fn prove_leaf(db: &mut NodeMap<T>, node_id: NodeId, game: &T) -> Option<Proof> {
+        let node = db.get_mut(&node_id).unwrap();
+        if node.proven.is_none() && node.done {
+            node.proven = Some(Proof {
+                value: game.returns()[node.to_play_index],
+                moves: 0,
+            });
+        }
+        node.proven
+    }
+
+    /// Prove the ancestors of the proven node `node_id` that its proof settles: a node is won
+    /// once one of its children is, and otherwise proven once all its moves are.
+    fn update_proofs(db: &mut NodeMap<T>, node_id: NodeId) {
+        let mut node_id = node_id;
+        while let Some(parent_id) = db[&node_id].parent {
+            let parent = &db[&parent_id];
+            if parent.proven.is_some() || !parent.chance_outcomes.is_empty() {
+                return;
+            }
+            let proofs: Vec<_> = parent
+                .children
+                .values()
+                .filter_map(|child_id| {
+                    let child = &db[child_id];
+                    child.proven.map(|proof| proof.for_parent(parent, child))
+                })
+                .collect();
+            let won = proofs.iter().filter(|proof| proof.value >= 1.);
+            let proof = if let Some(quickest) = won.min_by_key(|proof| proof.moves) {
+                *quickest
+            } else if parent.fully_expanded() && proofs.len() == parent.children.len() {
+                let value = proofs.iter().map(|proof| proof.value).fold(-1., f32::max);
+                let best = proofs.iter().filter(|proof| proof.value == value);
+                // Put off a loss or a draw as long as possible.
+                *best.max_by_key(|proof| proof.moves).unwrap()
+            } else {
+                return;
+            };
+            db.get_mut(&parent_id).unwrap().proven = Some(Proof {
+                value: proof.value,
+                moves: proof.moves + 1,
+            });
+            node_id = parent_id;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        error::GameError,
+        games::tic_tac_toe::{Player, TicTacToe},
+    };
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_mcts() {
+        let game = TicTacToe::new();
+        // Seeded, since 100 random playouts don't always tell the center from the corners.
+        let mcts = Mcts::<TicTacToe>::with_config(MctsConfig {
+            num_simulations: 100,
+            seed: Some(3),
+            ..Default::default()
+        });
+        let action = mcts.search(&game).action;
+        assert!(action == (1, 1))
+    }
+
+    #[test]
+    fn test_search_in_place() {
+        let mut game = TicTacToe::new();
+        game.step((0, 0)).unwrap();
+        game.step((1, 1)).unwrap();
+        game.step((0, 1)).unwrap();
+        let before = game.clone();
+        let mcts = Mcts::<TicTacToe>::new(1000);
+        // O has to block X's row.
+        assert_eq!(mcts.search_in_place(&mut game).action, (0, 2));
+        assert_eq!(game.to_string(), before.to_string());
+        assert_eq!(game.state_hash(), before.state_hash());
+    }
+
+    #[test]
+    fn test_search_statistics() {
+        let mut game = TicTacToe::new();
+        for action in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            game.step(action).unwrap();
+        }
+        let mcts = Mcts::<TicTacToe>::new(1000);
+        let stats = mcts.search_statistics(&game);
+        assert_eq!(stats.visit_counts.iter().sum::<usize>(), 1000);
+        assert_eq!(stats.visit_counts[game.action_to_index(&(1, 1))], 0);
+        // X wins at once.
+        assert_eq!(stats.select_action(0.), game.action_to_index(&(0, 2)));
+        assert!(stats.root_value > 0.5, "{}", stats.root_value);
+        // At most the five empty cells.
+        assert!((1..=5).contains(&stats.depth), "{}", stats.depth);
+
+        let mut reports = vec![];
+        let stats = mcts.search_statistics_with_progress(&game, 300, |stats| {
+            reports.push(stats.visit_counts.iter().sum::<usize>());
+        });
+        assert_eq!(reports, vec![300, 600, 900]);
+        assert_eq!(stats.visit_counts.iter().sum::<usize>(), 1000);
+    }
+
+    #[test]
+    fn test_solver_budget() {
+        // O has to block the top row.
+        let mut game = TicTacToe::new();
+        for action in [(0, 0), (1, 1), (0, 1)] {
+            game.step(action).unwrap();
+        }
+        let mcts = Mcts::<TicTacToe>::with_config(MctsConfig {
+            num_simulations: 1,
+            solver_budget: Some(100_000),
+            ..Default::default()
+        });
+        let result = mcts.search(&game);
+        assert_eq!(result.action, (0, 2));
+        assert!(result.nodes <= 50);
+        let stats = mcts.search_statistics(&game);
+        assert_eq!(stats.root_value, 0.);
+        assert_eq!(stats.visit_counts[2], 1);
+        // Too small a budget falls back to the search.
+        let mcts = Mcts::<TicTacToe>::with_config(MctsConfig {
+            num_simulations: 1,
+            solver_budget: Some(10),
+            ..Default::default()
+        });
+        assert_eq!(mcts.search_statistics(&game).depth, 1);
+    }
+
+    #[test]
+    fn test_mcts_solver() {
+        let play = |moves: &[(usize, usize)]| {
+            let mut game = TicTacToe::new();
+            for &action in moves {
+                game.step(action).unwrap();
+            }
+            game
+        };
+        let mcts = Mcts::<TicTacToe>::with_config(MctsConfig {
+            num_simulations: 200,
+            mcts_solver: true,
+            ..Default::default()
+        });
+        // X completes the top row.
+        let result = mcts.search(&play(&[(0, 0), (1, 0), (0, 1), (1, 1)]));
+        assert_eq!(result.action, (0, 2));
+        let proof = result.proof.unwrap();
+        assert_eq!(
+            proof,
+            Proof {
+                value: 1.,
+                moves: 1
+            }
+        );
+        assert_eq!(proof.to_string(), "forced win in 1");
+        let draw = Proof {
+            value: 0.,
+            moves: 4,
+        };
+        assert_eq!(draw.to_string(), "proven draw in 4");
+
+        // Every move of O but the block loses, which the search proves and then avoids.
+        let game = play(&[(0, 0), (1, 1), (0, 1)]);
+        let (db, root) = mcts.build_tree(&game);
+        let root_node = &db[&root];
+        let lost = root_node
+            .children
+            .values()
+            .filter(|child_id| {
+                let child = &db[child_id];
+                child.proven.is_some_and(|proof| proof.value >= 1.)
+            })
+            .count();
+        assert!(lost > 0);
+        assert_eq!(mcts.best_action(&db, root), (0, 2));
+    }
 
     #[test]
-    fn test_mcts() {
+    fn test_search_result() {
+        let mcts = Mcts::<TicTacToe>::new(200);
+        let result = mcts.search(&TicTacToe::new());
+        assert_eq!(result.children.len(), 9);
+        let visits: Vec<_> = result.children.iter().map(|child| child.visits).collect();
+        assert_eq!(visits.iter().sum::<usize>(), 200);
+        assert!(visits.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert!(result
+            .children
+            .iter()
+            .all(|child| (-1. ..=1.).contains(&child.q) && child.prior == 1. / 9.));
+        // Every simulation adds a node until the game ends.
+        assert_eq!(result.nodes, 201);
+        assert_eq!(result.principal_variation[0], result.action);
+        assert!(result.principal_variation.len() <= result.max_depth);
+        assert!(1. <= result.mean_depth && result.mean_depth <= result.max_depth as f32);
+        assert_eq!(result.proof, None);
+    }
+
+    #[test]
+    fn test_try_search() {
+        let mut game = TicTacToe::new();
+        assert!(Mcts::new(10).try_search(&game).is_ok());
+        assert_eq!(
+            Mcts::new(0).try_search(&game).unwrap_err(),
+            SearchError::NoSimulations
+        );
+        for action in [(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)] {
+            game.step(action).unwrap();
+        }
+        assert_eq!(
+            Mcts::new(10).try_search(&game).unwrap_err(),
+            SearchError::GameOver
+        );
+    }
+
+    #[test]
+    fn test_tiny_tree() {
+        let game = TicTacToe::new();
+        let search = |max_nodes, max_memory_bytes| {
+            Mcts::with_config(MctsConfig {
+                num_simulations: 10,
+                max_nodes,
+                max_memory_bytes,
+                ..Default::default()
+            })
+            .try_search(&game)
+        };
+        assert_eq!(
+            search(Some(1), None).unwrap_err(),
+            SearchError::TreeTooSmall
+        );
+        assert_eq!(search(Some(2), None).unwrap().nodes, 2);
+        // Full as soon as the root is expanded.
+        assert_eq!(
+            search(None, Some(1)).unwrap_err(),
+            SearchError::TreeTooSmall
+        );
+    }
+
+    #[test]
+    fn test_export_tree() {
+        let game = TicTacToe::new();
+        let mcts = Mcts::<TicTacToe>::new(50);
+        let (_, dot) = mcts.export_tree(&game, TreeFormat::Dot);
+        assert!(dot.starts_with("digraph mcts {"), "{}", dot);
+        assert!(
+            dot.contains("n0 [label=\"root\\nN=50 Q=0.000 P=1.000\"]"),
+            "{}",
+            dot
+        );
+        // One node per simulation besides the root, each with an edge from its parent.
+        assert_eq!(dot.matches("->").count(), 50);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_export_json() {
+        let game = TicTacToe::new();
+        let mcts = Mcts::<TicTacToe>::new(50);
+        let (_, json) = mcts.export_tree(&game, TreeFormat::Json);
+        let root: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(root["visits"], 50);
+        assert_eq!(root["action"], Value::Null);
+        let children = root["children"].as_array().unwrap();
+        assert_eq!(children.len(), 9);
+        let visits: Vec<u64> = children
+            .iter()
+            .map(|child| child["visits"].as_u64().unwrap())
+            .collect();
+        assert_eq!(visits.iter().sum::<u64>(), 50);
+        assert!(visits.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert_eq!(children[0]["prior"].as_f64().unwrap() as f32, 1. / 9.);
+    }
+
+    #[test]
+    fn test_progressive_widening() {
+        let game = TicTacToe::new();
+        let pw = ProgressiveWidening { c: 1.0, alpha: 0.5 };
+        let mcts = Mcts::<TicTacToe>::with_config(MctsConfig {
+            num_simulations: 16,
+            progressive_widening: Some(pw),
+            ..Default::default()
+        });
+        let (db, root) = mcts.build_tree(&game);
+        let root = db.get(&root).unwrap();
+        assert_eq!(root.visits, 16);
+        // The last child was added while the root had 15 visits.
+        assert!(root.children.len() <= pw.max_children(15));
+        assert!(root.children.len() < 9);
+    }
+
+    #[test]
+    fn test_expansion_order() {
+        let game = TicTacToe::new();
+        let mcts = Mcts::<TicTacToe>::new(3);
+        let (db, root) = mcts.build_tree(&game);
+        // Without widening, the moves are expanded last first, like popping them.
+        let mut expanded: Vec<_> = db[&root].children.keys().copied().collect();
+        expanded.sort();
+        assert_eq!(expanded, [(2, 0), (2, 1), (2, 2)]);
+        // Only the root was expanded, so the leaves keep no list of moves.
+        assert_eq!(db[&root].unvisited_actions.as_ref().unwrap().len(), 6);
+        for child_id in db[&root].children.values() {
+            assert!(db[child_id].unvisited_actions.is_none());
+        }
+    }
+
+    #[test]
+    fn test_rave() {
+        let game = TicTacToe::new();
+        let mcts = Mcts::<TicTacToe>::with_config(MctsConfig {
+            num_simulations: 200,
+            rave: Some(Rave { equivalence: 100. }),
+            ..Default::default()
+        });
+        let (db, root) = mcts.build_tree(&game);
+        let root = db.get(&root).unwrap();
+        // Every simulation from the empty board plays at least one move for X.
+        let amaf_visits: usize = root.amaf.values().map(|stats| stats.visits).sum();
+        assert!(amaf_visits >= 200);
+        assert_eq!(root.amaf.len(), 9);
+        for (action, child_id) in &root.children {
+            assert!(root.amaf[action].visits >= db[child_id].visits);
+        }
+    }
+
+    /// X bets on one of two coins, then a chance event decides whether X wins or O wins.
+    #[derive(Clone, Debug)]
+    struct Gamble {
+        bet: Option<usize>,
+        won: Option<bool>,
+    }
+
+    impl Gamble {
+        const WIN_PROBABILITIES: [f32; 2] = [0.3, 0.8];
+    }
+
+    impl std::fmt::Display for Gamble {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "{:?} {:?}", self.bet, self.won)
+        }
+    }
+
+    impl Game for Gamble {
+        type Action = usize;
+        type Player = Player;
+
+        fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+            match self.bet {
+                None => self.bet = Some(action),
+                Some(_) => self.won = Some(action == 1),
+            }
+            Ok(0.)
+        }
+
+        fn get_available_moves(&self) -> Vec<Self::Action> {
+            if self.done() {
+                vec![]
+            } else {
+                vec![0, 1]
+            }
+        }
+
+        fn action_space_size(&self) -> usize {
+            2
+        }
+
+        fn action_to_index(&self, action: &Self::Action) -> usize {
+            *action
+        }
+
+        fn index_to_action(&self, index: usize) -> Self::Action {
+            index
+        }
+
+        fn current_player(&self) -> Self::Player {
+            Player::X
+        }
+
+        fn player_index(&self, player: &Self::Player) -> usize {
+            *player as usize
+        }
+
+        fn observation_shape(&self) -> Vec<usize> {
+            vec![2]
+        }
+
+        fn observation(&self) -> Vec<f32> {
+            vec![
+                self.bet.map_or(0., |bet| bet as f32 + 1.),
+                self.won.map_or(0., |won| won as u8 as f32 + 1.),
+            ]
+        }
+
+        fn terminated(&self) -> bool {
+            self.won.is_some()
+        }
+
+        fn check_winner(&self) -> Option<Self::Player> {
+            self.won.map(|won| if won { Player::X } else { Player::O })
+        }
+
+        fn chance_outcomes(&self) -> Vec<(Self::Action, f32)> {
+            match (self.bet, self.won) {
+                (Some(bet), None) => {
+                    let p = Self::WIN_PROBABILITIES[bet];
+                    vec![(1, p), (0, 1. - p)]
+                }
+                _ => vec![],
+            }
+        }
+    }
+
+    #[test]
+    fn test_chance_nodes() {
+        let game = Gamble {
+            bet: None,
+            won: None,
+        };
+        let mcts = Mcts::<Gamble>::new(500);
+        let (db, root_id) = mcts.build_tree(&game);
+        let root = &db[&root_id];
+        assert_eq!(mcts.best_action(&db, root_id), 1);
+
+        // Outcomes of a chance node are visited in proportion to their probability, not their value.
+        let chance_node = &db[&root.children[&1]];
+        assert_eq!(chance_node.chance_outcomes.len(), 2);
+        let win_visits = db[&chance_node.children[&1]].visits as f32;
+        let win_frequency = win_visits / chance_node.visits as f32;
+        assert!((win_frequency - Gamble::WIN_PROBABILITIES[1]).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_rollouts_per_leaf() {
+        let game = Gamble {
+            bet: None,
+            won: None,
+        };
+        // Two simulations expand both bets, each valued by its playouts alone.
+        let config = MctsConfig {
+            num_simulations: 2,
+            rollouts_per_leaf: 2000,
+            seed: Some(0),
+            ..Default::default()
+        };
+        let result = Mcts::<Gamble>::with_config(config).search(&game);
+        assert_eq!(result.nodes, 3);
+        assert_eq!(result.action, 1);
+        for child in &result.children {
+            let expected = 2. * Gamble::WIN_PROBABILITIES[child.action] - 1.;
+            assert_eq!(child.visits, 1);
+            assert!((child.q - expected).abs() < 0.1, "{:?}", child);
+        }
+    }
+
+    #[test]
+    fn test_parallel_playouts() {
+        let config = MctsConfig {
+            num_simulations: 200,
+            rollouts_per_leaf: 8,
+            rave: Some(Rave { equivalence: 100. }),
+            seed: Some(3),
+            ..Default::default()
+        };
+        let statistics = |result: SearchResult<_>| -> Vec<_> {
+            result
+                .children
+                .into_iter()
+                .map(|child| (child.action, child.visits, child.q))
+                .collect()
+        };
+        let game = TicTacToe::new();
+        let sequential = Mcts::<TicTacToe>::with_config(config.clone()).search(&game);
+        let parallel = Mcts::with_config(config)
+            .with_parallel_playouts()
+            .search(&game);
+        assert_eq!(statistics(parallel), statistics(sequential));
+    }
+
+    /// Three players pick 0, 1 or 2 in turn. Player 0 wins by picking 1, otherwise player 2
+    /// wins by picking 2 and player 1 wins if they don't.
+    #[derive(Clone, Debug, Default)]
+    struct ThreeWay {
+        picks: Vec<usize>,
+    }
+
+    impl std::fmt::Display for ThreeWay {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "{:?}", self.picks)
+        }
+    }
+
+    impl Game for ThreeWay {
+        type Action = usize;
+        type Player = usize;
+
+        fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+            self.picks.push(action);
+            Ok(0.)
+        }
+
+        fn get_available_moves(&self) -> Vec<Self::Action> {
+            if self.done() {
+                vec![]
+            } else {
+                vec![0, 1, 2]
+            }
+        }
+
+        fn action_space_size(&self) -> usize {
+            3
+        }
+
+        fn action_to_index(&self, action: &Self::Action) -> usize {
+            *action
+        }
+
+        fn index_to_action(&self, index: usize) -> Self::Action {
+            index
+        }
+
+        fn current_player(&self) -> Self::Player {
+            self.picks.len() % 3
+        }
+
+        fn observation_shape(&self) -> Vec<usize> {
+            vec![3]
+        }
+
+        fn observation(&self) -> Vec<f32> {
+            let mut observation = vec![0.; 3];
+            for (i, &pick) in self.picks.iter().enumerate() {
+                observation[i] = pick as f32 + 1.;
+            }
+            observation
+        }
+
+        fn terminated(&self) -> bool {
+            self.picks.len() == 3
+        }
+
+        fn check_winner(&self) -> Option<Self::Player> {
+            match self.picks[..] {
+                [1, _, _] => Some(0),
+                [_, _, 2] => Some(2),
+                [_, _, _] => Some(1),
+                _ => None,
+            }
+        }
+
+        fn num_players(&self) -> usize {
+            3
+        }
+
+        fn player_index(&self, player: &Self::Player) -> usize {
+            *player
+        }
+    }
+
+    #[test]
+    fn test_three_players() {
+        let mcts = Mcts::<ThreeWay>::new(500);
+        assert_eq!(mcts.search(&ThreeWay::default()).action, 1);
+        let game = ThreeWay { picks: vec![0, 0] };
+        assert_eq!(mcts.search(&game).action, 2);
+        let game = ThreeWay {
+            picks: vec![0, 0, 2],
+        };
+        assert_eq!(game.returns(), vec![-1., -1., 1.]);
+    }
+
+    /// A single-player game: take a reward of 1 now, or pass it up to collect 5 on the next move.
+    #[derive(Clone, Debug, Default)]
+    struct Detour {
+        moves: Vec<usize>,
+    }
+
+    impl std::fmt::Display for Detour {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "{:?}", self.moves)
+        }
+    }
+
+    impl Game for Detour {
+        type Action = usize;
+        type Player = ();
+
+        fn step(&mut self, action: Self::Action) -> Result<f32, GameError> {
+            self.moves.push(action);
+            let reward = match self.moves[..] {
+                [1] => 1.,
+                [0, 1] => 5.,
+                _ => 0.,
+            };
+            Ok(reward)
+        }
+
+        fn get_available_moves(&self) -> Vec<Self::Action> {
+            if self.done() {
+                vec![]
+            } else {
+                vec![0, 1]
+            }
+        }
+
+        fn action_space_size(&self) -> usize {
+            2
+        }
+
+        fn action_to_index(&self, action: &Self::Action) -> usize {
+            *action
+        }
+
+        fn index_to_action(&self, index: usize) -> Self::Action {
+            index
+        }
+
+        fn current_player(&self) -> Self::Player {}
+
+        fn observation_shape(&self) -> Vec<usize> {
+            vec![2]
+        }
+
+        fn observation(&self) -> Vec<f32> {
+            let mut observation = vec![0.; 2];
+            for (i, &action) in self.moves.iter().enumerate() {
+                observation[i] = action as f32 + 1.;
+            }
+            observation
+        }
+
+        fn terminated(&self) -> bool {
+            self.moves.len() == 2
+        }
+
+        fn check_winner(&self) -> Option<Self::Player> {
+            None
+        }
+
+        fn num_players(&self) -> usize {
+            1
+        }
+
+        fn player_index(&self, _player: &Self::Player) -> usize {
+            0
+        }
+    }
+
+    #[test]
+    fn test_single_player() {
+        let mcts = Mcts::<Detour>::new(1000);
+        assert_eq!(mcts.search(&Detour::default()).action, 0);
+        let game = Detour { moves: vec![0] };
+        assert_eq!(mcts.search(&game).action, 1);
+    }
+
+    #[test]
+    fn test_max_rollout_depth() {
+        // Without playouts the detour is only found through the tree.
+        let mcts = Mcts::<Detour>::with_config(MctsConfig {
+            num_simulations: 1000,
+            max_rollout_depth: Some(0),
+            ..Default::default()
+        });
+        assert_eq!(mcts.search(&Detour::default()).action, 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_save_tree() {
+        let mut game = TicTacToe::new();
+        game.step((1, 1)).unwrap();
+        let mcts = Mcts::<TicTacToe>::with_config(MctsConfig {
+            num_simulations: 300,
+            rave: Some(Rave { equivalence: 100. }),
+            mcts_solver: true,
+            seed: Some(0),
+            ..Default::default()
+        });
+        let mut tree = SearchTree::new(&game);
+        let result = mcts.grow_tree(&game, &mut tree);
+        let path = std::env::temp_dir().join(format!("muzero-tree-{}", std::process::id()));
+        tree.save(&game, &path).unwrap();
+        let mut loaded = SearchTree::load(&path, &game).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.nodes(), tree.nodes());
+        assert_eq!(loaded.to_json(&game), tree.to_json(&game));
+        // Loading searches nothing.
+        let loaded_result = mcts.result(&loaded.db, loaded.root);
+        assert_eq!(loaded_result.allocated_nodes, 0);
+        let loaded_result = SearchResult {
+            allocated_nodes: result.allocated_nodes,
+            ..loaded_result
+        };
+        assert_eq!(loaded_result, result);
+
+        // The search goes on where it stopped.
+        mcts.grow_tree(&game, &mut loaded);
+        assert_eq!(loaded.visits(), 600);
+
+        assert!(SearchTree::from_json(&tree.to_json(&game), &TicTacToe::new()).is_err());
+        let json = tree.to_json(&game).to_string();
+        for (from, to) in [
+            ("\"version\":1", "\"version\":2"),
+            // The center is taken.
+            ("\"action\":0,", "\"action\":4,"),
+            ("\"children\":", "\"children\":1"),
+        ] {
+            let json: Value = serde_json::from_str(&json.replacen(from, to, 1)).unwrap();
+            assert!(SearchTree::from_json(&json, &game).is_err(), "{}", to);
+        }
+    }
+
+    #[test]
+    fn test_advance() {
+        let game = TicTacToe::new();
+        let mcts = Mcts::<TicTacToe>::new(500);
+        let mut tree = SearchTree::new(&game);
+        let stepper = CloneStepper { root: game.clone() };
+        mcts.grow(
+            &mut tree,
+            &mut game.clone(),
+            stepper,
+            500,
+            None,
+            |_, _, _| {},
+        );
+        assert_eq!(tree.visits(), 500);
+        let nodes = tree.nodes();
+        let center = &tree.db[&tree.root].children[&(1, 1)];
+        let visits = tree.db[center].visits;
+
+        let subtree = tree.advance(&(1, 1)).unwrap();
+        assert_eq!(subtree.visits(), visits);
+        assert!(subtree.nodes() < nodes);
+        assert!(subtree.db[&subtree.root].parent.is_none());
+        // Every node left is reachable from the new root.
+        let mut reachable = 0;
+        let mut stack = vec![subtree.root];
+        while let Some(node_id) = stack.pop() {
+            reachable += 1;
+            stack.extend(subtree.db[&node_id].children.values().copied());
+        }
+        assert_eq!(reachable, subtree.nodes());
+
+        // A move the search never tried leaves nothing to reuse.
+        let mut game = TicTacToe::new();
+        game.step((1, 1)).unwrap();
+        let mcts = Mcts::<TicTacToe>::new(1);
+        let mut tree = SearchTree::new(&game);
+        let stepper = CloneStepper { root: game.clone() };
+        mcts.grow(&mut tree, &mut game.clone(), stepper, 1, None, |_, _, _| {});
+        let tried = tree.db[&tree.root].children.keys().next().cloned().unwrap();
+        let untried = game
+            .get_available_moves()
+            .into_iter()
+            .find(|&action| action != tried)
+            .unwrap();
+        assert!(tree.advance(&untried).is_none());
+    }
+
+    #[test]
+    fn test_node_ids() {
+        let game = TicTacToe::new();
+        let mcts = Mcts::<TicTacToe>::new(10);
+        // Every tree numbers its nodes from 0, whatever other searches do.
+        for _ in 0..2 {
+            let mut tree = SearchTree::new(&game);
+            assert_eq!(tree.root, NodeId(0));
+            let stepper = CloneStepper { root: game.clone() };
+            mcts.grow(
+                &mut tree,
+                &mut game.clone(),
+                stepper,
+                10,
+                None,
+                |_, _, _| {},
+            );
+            let mut ids: Vec<_> = tree.db.nodes.keys().map(|id| id.0).collect();
+            ids.sort();
+            assert_eq!(ids, (0..=10).collect::<Vec<_>>());
+            assert_eq!(tree.db.searched, 10);
+
+            // Nodes added after advancing don't reuse the ids kept.
+            let mut tree = tree.advance(&(1, 1)).unwrap();
+            let mut game = game.clone();
+            game.step((1, 1)).unwrap();
+            let nodes = tree.nodes();
+            let stepper = CloneStepper { root: game.clone() };
+            mcts.grow(
+                &mut tree,
+                &mut game.clone(),
+                stepper,
+                10,
+                None,
+                |_, _, _| {},
+            );
+            assert_eq!(tree.nodes(), nodes + 10);
+            assert_eq!(tree.db.searched, 10);
+        }
+        let result = mcts.search(&game);
+        assert_eq!(result.allocated_nodes, 10);
+        assert_eq!(mcts.search_statistics(&game).nodes, 10);
+    }
+
+    #[test]
+    fn test_tree_limits() {
+        let game = TicTacToe::new();
+        let grow = |config: MctsConfig| {
+            let mut tree = SearchTree::new(&game);
+            let stepper = CloneStepper { root: game.clone() };
+            Mcts::with_config(config).grow(
+                &mut tree,
+                &mut game.clone(),
+                stepper,
+                2000,
+                None,
+                |_, _, _| {},
+            );
+            let bytes: usize = tree.db.nodes.values().map(Node::bytes).sum();
+            assert_eq!(tree.memory_bytes(), bytes);
+            tree
+        };
+        let config = MctsConfig {
+            max_nodes: Some(100),
+            seed: Some(0),
+            ..Default::default()
+        };
+
+        let tree = grow(config.clone());
+        assert_eq!(tree.nodes(), 100);
+        assert!(tree.visits() < 2000);
+
+        let tree = grow(MctsConfig {
+            full_tree: FullTree::Recycle,
+            ..config.clone()
+        });
+        assert_eq!(tree.visits(), 2000);
+        assert!(tree.nodes() <= 100);
+        assert_eq!(tree.db[&tree.root].children.len(), 9);
+        for (node_id, node) in &tree.db.nodes {
+            for child_id in node.children.values() {
+                assert_eq!(tree.db[child_id].parent, Some(*node_id));
+            }
+        }
+        let tree = tree.advance(&(1, 1)).unwrap();
+        let bytes: usize = tree.db.nodes.values().map(Node::bytes).sum();
+        assert_eq!(tree.memory_bytes(), bytes);
+
+        let tree = grow(MctsConfig {
+            max_nodes: None,
+            max_memory_bytes: Some(20_000),
+            ..config.clone()
+        });
+        let node = tree.db[&tree.root].bytes();
+        assert!((20_000..20_000 + node).contains(&tree.memory_bytes()));
+        assert!(tree.visits() < 2000);
+
+        // The moves of dropped subtrees are searched again, and the search still wins.
+        let mut game = TicTacToe::new();
+        for action in [(0, 0), (1, 1), (0, 1)] {
+            game.step(action).unwrap();
+        }
+        let mcts = Mcts::with_config(MctsConfig {
+            num_simulations: 2000,
+            max_nodes: Some(50),
+            full_tree: FullTree::Recycle,
+            ..config
+        });
+        let result = mcts.search(&game);
+        assert_eq!(result.action, (0, 2));
+        assert!(result.nodes <= 50);
+    }
+
+    #[test]
+    fn test_first_play_urgency() {
         let game = TicTacToe::new();
+        let grow = |first_play_urgency, simulations| {
+            let mut tree = SearchTree::new(&game);
+            let stepper = CloneStepper { root: game.clone() };
+            let config = MctsConfig {
+                first_play_urgency,
+                seed: Some(3),
+                ..Default::default()
+            };
+            Mcts::with_config(config).grow(
+                &mut tree,
+                &mut game.clone(),
+                stepper,
+                simulations,
+                None,
+                |_, _, _| {},
+            );
+            let root = &tree.db[&tree.root];
+            let mut visits: Vec<_> = root
+                .children
+                .values()
+                .map(|id| tree.db[id].visits)
+                .collect();
+            visits.sort_unstable();
+            visits
+        };
+        assert_eq!(grow(FirstPlayUrgency::Infinite, 9), [1; 9]);
+        assert_eq!(grow(FirstPlayUrgency::Lowest, 50), [50]);
+        // Anything worse than a loss is never tried before the visited moves.
+        let pessimistic = FirstPlayUrgency::Parent { reduction: 10. };
+        assert_eq!(grow(pessimistic, 50), [50]);
+        // Above the best possible return, every move is tried first, like `Infinite`.
+        assert_eq!(grow(FirstPlayUrgency::Value(100.), 9), [1; 9]);
+
+        let mut game = TicTacToe::new();
+        for action in [(0, 0), (1, 1), (0, 1)] {
+            game.step(action).unwrap();
+        }
+        // An optimistic urgency still finds the block.
+        let mcts = Mcts::with_config(MctsConfig {
+            num_simulations: 2000,
+            first_play_urgency: FirstPlayUrgency::Parent { reduction: -0.5 },
+            seed: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(mcts.search(&game).action, (0, 2));
+
+        for urgency in ["infinite", "lowest", "parent:0.25", "value:-1"] {
+            assert_eq!(
+                urgency.parse::<FirstPlayUrgency>().unwrap().to_string(),
+                urgency
+            );
+        }
+        assert_eq!(
+            "parent".parse::<FirstPlayUrgency>().unwrap(),
+            FirstPlayUrgency::Parent { reduction: 0. }
+        );
+        assert!("value".parse::<FirstPlayUrgency>().is_err());
+        assert!("parent:x".parse::<FirstPlayUrgency>().is_err());
+    }
+
+    #[test]
+    fn test_ponder() {
+        let mut game = TicTacToe::new();
         let mcts = Mcts::<TicTacToe>::new(100);
-        let action = mcts.search(&game);
-        assert!(action == (1, 1))
+        let ponder = mcts.ponder(&game, 300);
+        // Wait for the pondering to run out of simulations.
+        let tree = loop {
+            if ponder.thread.as_ref().unwrap().is_finished() {
+                break ponder.stop();
+            }
+            std::thread::yield_now();
+        };
+        assert_eq!(tree.visits(), 300);
+        game.step((1, 1)).unwrap();
+        let tree = tree.advance(&(1, 1)).unwrap();
+        let reused = tree.visits();
+        let result = mcts.search_from(&game, Some(tree));
+        let visits: usize = result.children.iter().map(|child| child.visits).sum();
+        // The root itself was expanded by an earlier simulation.
+        assert_eq!(visits, reused + 100 - 1);
+
+        // Stopping right away keeps whatever was searched.
+        let tree = mcts.ponder(&TicTacToe::new(), usize::MAX).stop();
+        assert!(tree.visits() < usize::MAX);
+    }
+
+    /// Plays the first move, counting its calls.
+    struct CountingPolicy(Arc<AtomicUsize>);
+
+    impl RolloutPolicy<TicTacToe> for CountingPolicy {
+        fn choose(
+            &self,
+            _game: &TicTacToe,
+            moves: &[(usize, usize)],
+            _rng: &mut dyn rand::RngCore,
+        ) -> (usize, usize) {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            moves[0]
+        }
+    }
+
+    #[test]
+    fn test_ponder_rollout_policy() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mcts = Mcts::<TicTacToe>::new(100).with_rollout_policy(CountingPolicy(calls.clone()));
+        let ponder = mcts.ponder(&TicTacToe::new(), 50);
+        while !ponder.thread.as_ref().unwrap().is_finished() {
+            std::thread::yield_now();
+        }
+        assert_eq!(ponder.stop().visits(), 50);
+        assert!(calls.load(std::sync::atomic::Ordering::Relaxed) > 0);
+    }
+
+    #[test]
+    fn test_seed() {
+        let mut game = TicTacToe::new();
+        game.step((0, 0)).unwrap();
+        let mcts = Mcts::<TicTacToe>::with_config(MctsConfig {
+            num_simulations: 300,
+            seed: Some(7),
+            ..Default::default()
+        });
+        let visits = |result: SearchResult<_>| -> Vec<_> {
+            result
+                .children
+                .into_iter()
+                .map(|child| (child.action, child.visits, child.q))
+                .collect()
+        };
+        assert_eq!(visits(mcts.search(&game)), visits(mcts.search(&game)));
+    }
+
+    #[test]
+    fn test_search_for() {
+        let mcts = Mcts::<TicTacToe>::new(1);
+        let start = std::time::Instant::now();
+        let result = mcts.search_for(&TicTacToe::new(), Duration::from_millis(50));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        let visits: usize = result.children.iter().map(|child| child.visits).sum();
+        assert!(visits > 1);
+    }
+
+    #[test]
+    fn test_scoring() {
+        // X's last move can only draw.
+        let mut game = TicTacToe::new();
+        for action in [
+            (0, 0),
+            (0, 1),
+            (0, 2),
+            (1, 1),
+            (1, 0),
+            (1, 2),
+            (2, 1),
+            (2, 0),
+        ] {
+            game.step(action).unwrap();
+        }
+        let search = |scoring| {
+            Mcts::with_config(MctsConfig {
+                num_simulations: 10,
+                scoring,
+                ..Default::default()
+            })
+            .search_statistics(&game)
+            .root_value
+        };
+        assert_eq!(search(ScoreConfig::default()), 0.);
+        let contempt = ScoreConfig {
+            draw_value: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(search(contempt), 0.5);
+    }
+
+    /// Keeps the `(spans, message)` of every event, the names of its spans from the outermost
+    /// joined by `:`.
+    struct Capture(Arc<std::sync::Mutex<Vec<(String, String)>>>);
+
+    impl<S> tracing_subscriber::Layer<S> for Capture
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let spans = ctx
+                .event_scope(event)
+                .map(|scope| {
+                    let names: Vec<_> = scope.from_root().map(|span| span.name()).collect();
+                    names.join(":")
+                })
+                .unwrap_or_default();
+            let mut message = String::new();
+            event.record(
+                &mut |field: &tracing::field::Field, value: &dyn fmt::Debug| {
+                    if field.name() == "message" {
+                        message = format!("{:?}", value);
+                    }
+                },
+            );
+            self.0.lock().unwrap().push((spans, message));
+        }
+    }
+
+    #[test]
+    fn test_search_logs() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let logged = Arc::default();
+        let subscriber = tracing_subscriber::registry().with(Capture(Arc::clone(&logged)));
+        let mcts = Mcts::<TicTacToe>::with_config(MctsConfig {
+            num_simulations: 10,
+            seed: Some(1),
+            ..Default::default()
+        });
+        tracing::subscriber::with_default(subscriber, || mcts.search(&TicTacToe::new()));
+        let logged = logged.lock().unwrap();
+        let count = |spans: &str| logged.iter().filter(|(s, _)| s == spans).count();
+        for phase in ["selection", "expansion", "simulation", "backpropagation"] {
+            assert_eq!(count(&format!("search:{}", phase)), 10, "{}", phase);
+        }
+        let summary: Vec<_> = logged
+            .iter()
+            .filter(|(spans, _)| spans == "search")
+            .collect();
+        assert_eq!(summary.len(), 1);
+        assert!(
+            summary[0].1.starts_with("10 simulations in "),
+            "{}",
+            summary[0].1
+        );
+        // A line per node: the root, its 9 children and one grandchild.
+        assert_eq!(count("search:tree"), 11);
     }
-}
\ No newline at end of file
+}
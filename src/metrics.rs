@@ -0,0 +1,295 @@
+//! Scalar metrics, like game lengths or evaluation Elo, written as TensorBoard event files or
+//! as CSV.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::error::{IoError, ParseError, Result};
+
+pub trait Metrics {
+    /// Record `value` of the scalar `tag` at `step`.
+    fn scalar(&mut self, tag: &str, step: u64, value: f64) -> Result<()>;
+
+    fn flush(&mut self) -> Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetricsFormat {
+    TensorBoard,
+    Csv,
+}
+
+impl FromStr for MetricsFormat {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        match s {
+            "tensorboard" => Ok(MetricsFormat::TensorBoard),
+            "csv" => Ok(MetricsFormat::Csv),
+            _ => Err(ParseError::Invalid(
+                "expected tensorboard or csv".to_string(),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricsConfig {
+    pub dir: PathBuf,
+    pub format: MetricsFormat,
+}
+
+impl MetricsConfig {
+    /// Start writing metrics into the directory, creating it.
+    pub fn open(&self) -> Result<Box<dyn Metrics>> {
+        fs::create_dir_all(&self.dir).map_err(IoError::path("create", &self.dir))?;
+        Ok(match self.format {
+            MetricsFormat::TensorBoard => Box::new(TensorBoardWriter::create(&self.dir)?),
+            MetricsFormat::Csv => Box::new(CsvWriter::open(&self.dir.join("metrics.csv"))?),
+        })
+    }
+}
+
+fn wall_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0., |time| time.as_secs_f64())
+}
+
+/// Appends `wall_time,step,tag,value` rows to a CSV file.
+pub struct CsvWriter {
+    writer: BufWriter<File>,
+}
+
+impl CsvWriter {
+    /// Append to the file at `path`, writing the header if it's new.
+    pub fn open(path: &Path) -> Result<Self> {
+        let is_new = !path.exists();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(IoError::path("open", path))?;
+        let mut writer = BufWriter::new(file);
+        if is_new {
+            writeln!(writer, "wall_time,step,tag,value")?;
+        }
+        Ok(Self { writer })
+    }
+}
+
+impl Metrics for CsvWriter {
+    fn scalar(&mut self, tag: &str, step: u64, value: f64) -> Result<()> {
+        writeln!(self.writer, "{:.3},{},{},{}", wall_time(), step, tag, value)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+/// Writes an `events.out.tfevents.*` file: TensorFlow `Event` protocol buffers, each framed
+/// as a TFRecord.
+pub struct TensorBoardWriter {
+    writer: BufWriter<File>,
+}
+
+impl TensorBoardWriter {
+    /// Start a new event file in `dir`.
+    pub fn create(dir: &Path) -> Result<Self> {
+        let time = wall_time();
+        let path = dir.join(format!(
+            "events.out.tfevents.{}.muzero-rs.{}",
+            time as u64,
+            std::process::id()
+        ));
+        let file = File::create(&path).map_err(IoError::path("create", path))?;
+        let mut writer = Self {
+            writer: BufWriter::new(file),
+        };
+        let mut event = vec![];
+        encode_double(&mut event, 1, time);
+        encode_bytes(&mut event, 3, b"brain.Event:2");
+        writer.write_record(&event)?;
+        Ok(writer)
+    }
+
+    fn write_record(&mut self, data: &[u8]) -> Result<()> {
+        let length = (data.len() as u64).to_le_bytes();
+        self.writer.write_all(&length)?;
+        self.writer
+            .write_all(&masked_crc32c(&length).to_le_bytes())?;
+        self.writer.write_all(data)?;
+        self.writer.write_all(&masked_crc32c(data).to_le_bytes())?;
+        Ok(())
+    }
+}
+
+impl Metrics for TensorBoardWriter {
+    fn scalar(&mut self, tag: &str, step: u64, value: f64) -> Result<()> {
+        let mut summary_value = vec![];
+        encode_bytes(&mut summary_value, 1, tag.as_bytes());
+        encode_key(&mut summary_value, 2, 5);
+        summary_value.extend((value as f32).to_le_bytes());
+        let mut summary = vec![];
+        encode_bytes(&mut summary, 1, &summary_value);
+        let mut event = vec![];
+        encode_double(&mut event, 1, wall_time());
+        encode_key(&mut event, 2, 0);
+        encode_varint(&mut event, step);
+        encode_bytes(&mut event, 5, &summary);
+        self.write_record(&event)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+fn encode_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn encode_key(buffer: &mut Vec<u8>, field: u64, wire_type: u64) {
+    encode_varint(buffer, field << 3 | wire_type);
+}
+
+fn encode_double(buffer: &mut Vec<u8>, field: u64, value: f64) {
+    encode_key(buffer, field, 1);
+    buffer.extend(value.to_le_bytes());
+}
+
+fn encode_bytes(buffer: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    encode_key(buffer, field, 2);
+    encode_varint(buffer, bytes.len() as u64);
+    buffer.extend(bytes);
+}
+
+/// CRC-32C (Castagnoli), bit by bit.
+fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                crc >> 1 ^ 0x82f6_3b78
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// The checksum of TFRecords, masked as they're stored next to the data they cover.
+fn masked_crc32c(data: &[u8]) -> u32 {
+    crc32c(data).rotate_right(15).wrapping_add(0xa282_ead8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("muzero-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// The data of the TFRecords in `bytes`, checking their framing.
+    fn records(mut bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut records = vec![];
+        while !bytes.is_empty() {
+            let length = u64::from_le_bytes(bytes[..8].try_into().unwrap()) as usize;
+            assert_eq!(
+                u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+                masked_crc32c(&bytes[..8])
+            );
+            let data = &bytes[12..12 + length];
+            let crc = &bytes[12 + length..16 + length];
+            assert_eq!(
+                u32::from_le_bytes(crc.try_into().unwrap()),
+                masked_crc32c(data)
+            );
+            records.push(data.to_vec());
+            bytes = &bytes[16 + length..];
+        }
+        records
+    }
+
+    #[test]
+    fn test_crc32c() {
+        assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+        assert_eq!(crc32c(b""), 0);
+    }
+
+    #[test]
+    fn test_varint() {
+        let mut buffer = vec![];
+        encode_varint(&mut buffer, 300);
+        assert_eq!(buffer, [0xac, 0x02]);
+    }
+
+    #[test]
+    fn test_tensorboard() {
+        let dir = temp_dir("tensorboard");
+        let config = MetricsConfig {
+            dir: dir.clone(),
+            format: MetricsFormat::TensorBoard,
+        };
+        let mut metrics = config.open().unwrap();
+        metrics.scalar("eval/elo", 300, 1.5).unwrap();
+        metrics.flush().unwrap();
+        let entries: Vec<_> = fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+        let path = entries[0].as_ref().unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("events.out.tfevents."), "{}", name);
+
+        let records = records(&fs::read(&path).unwrap());
+        assert_eq!(records.len(), 2);
+        assert!(records[0].ends_with(b"\x1a\x0dbrain.Event:2"));
+        // The wall time, then the step and the summary.
+        let mut expected = vec![0x10, 0xac, 0x02, 0x2a, 0x11, 0x0a, 0x0f, 0x0a, 0x08];
+        expected.extend(b"eval/elo");
+        expected.push(0x15);
+        expected.extend(1.5f32.to_le_bytes());
+        assert_eq!(records[1][0], 0x09);
+        assert_eq!(records[1][9..], expected);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_csv() {
+        let dir = temp_dir("csv");
+        let config = MetricsConfig {
+            dir: dir.clone(),
+            format: MetricsFormat::Csv,
+        };
+        for step in [1, 2] {
+            let mut metrics = config.open().unwrap();
+            metrics.scalar("selfplay/game_length", step, 9.).unwrap();
+            metrics.flush().unwrap();
+        }
+        let text = fs::read_to_string(dir.join("metrics.csv")).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "wall_time,step,tag,value");
+        assert!(
+            lines[2].ends_with(",2,selfplay/game_length,9"),
+            "{}",
+            lines[2]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,584 @@
+//! A [`Model`] in plain Rust that trains on the CPU with nothing to install, the backend of
+//! `muzero train` unless it's built with the `tch` feature.
+//!
+//! The model is the small fully connected network of the libtorch and burn models: the
+//! representation and the dynamics are a linear layer and a ReLU each, the dynamics taking
+//! the hidden state and the action one-hot, and the reward, policy and value are linear
+//! heads. Its weights have the names and shapes of the libtorch model's, so checkpoints move
+//! between the two, and its gradients are backpropagated by hand.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    checkpoint::{Tensor, Tensors},
+    error::{ConfigError, Result},
+    loss::Losses,
+    model::{Device, Model, Trainable},
+    network::softmax,
+    replay::Sample,
+};
+
+/// A fully connected layer, with its weight as `[outputs, inputs]` in row-major order.
+#[derive(Debug, Clone)]
+struct Linear {
+    weight: Vec<f32>,
+    bias: Vec<f32>,
+    inputs: usize,
+}
+
+impl Linear {
+    /// Initialized uniformly within ±1/√inputs, like libtorch's.
+    fn new(inputs: usize, outputs: usize, rng: &mut impl Rng) -> Self {
+        let bound = 1. / (inputs as f32).sqrt();
+        let mut uniform = |n| (0..n).map(|_| rng.gen_range(-bound..=bound)).collect();
+        Self {
+            weight: uniform(inputs * outputs),
+            bias: uniform(outputs),
+            inputs,
+        }
+    }
+
+    /// A layer of the same shape with every weight 0, to sum gradients in.
+    fn zeros(&self) -> Self {
+        Self {
+            weight: vec![0.; self.weight.len()],
+            bias: vec![0.; self.bias.len()],
+            inputs: self.inputs,
+        }
+    }
+
+    fn forward(&self, input: &[f32]) -> Vec<f32> {
+        debug_assert_eq!(input.len(), self.inputs);
+        self.weight
+            .chunks(self.inputs)
+            .zip(&self.bias)
+            .map(|(row, bias)| bias + row.iter().zip(input).map(|(w, x)| w * x).sum::<f32>())
+            .collect()
+    }
+
+    /// Add the gradients of the weight and the bias to `gradient`, given the input of a
+    /// forward pass and the gradient of its output. Returns the gradient of the input.
+    fn backward(&self, input: &[f32], output_gradient: &[f32], gradient: &mut Linear) -> Vec<f32> {
+        let mut input_gradient = vec![0.; self.inputs];
+        for (o, &g) in output_gradient.iter().enumerate() {
+            if g == 0. {
+                continue;
+            }
+            gradient.bias[o] += g;
+            let row = o * self.inputs..(o + 1) * self.inputs;
+            for (i, (w, w_gradient)) in self.weight[row.clone()]
+                .iter()
+                .zip(&mut gradient.weight[row])
+                .enumerate()
+            {
+                *w_gradient += g * input[i];
+                input_gradient[i] += g * w;
+            }
+        }
+        input_gradient
+    }
+}
+
+/// The layers of the model, or the gradients of their weights.
+#[derive(Debug, Clone)]
+struct Layers {
+    dynamics: Linear,
+    policy: Linear,
+    representation: Linear,
+    reward: Linear,
+    value: Linear,
+}
+
+impl Layers {
+    /// The layers by name, in the order of their tensors.
+    fn named(&self) -> [(&'static str, &Linear); 5] {
+        [
+            ("dynamics", &self.dynamics),
+            ("policy", &self.policy),
+            ("representation", &self.representation),
+            ("reward", &self.reward),
+            ("value", &self.value),
+        ]
+    }
+
+    fn named_mut(&mut self) -> [(&'static str, &mut Linear); 5] {
+        [
+            ("dynamics", &mut self.dynamics),
+            ("policy", &mut self.policy),
+            ("representation", &mut self.representation),
+            ("reward", &mut self.reward),
+            ("value", &mut self.value),
+        ]
+    }
+
+    fn zeros(&self) -> Self {
+        Self {
+            dynamics: self.dynamics.zeros(),
+            policy: self.policy.zeros(),
+            representation: self.representation.zeros(),
+            reward: self.reward.zeros(),
+            value: self.value.zeros(),
+        }
+    }
+
+    /// The weights, sorted by name.
+    fn tensors(&self) -> Tensors {
+        self.named()
+            .into_iter()
+            .flat_map(|(name, layer)| {
+                let outputs = layer.bias.len();
+                [
+                    (
+                        format!("{}.bias", name),
+                        Tensor::new(vec![outputs], layer.bias.clone()),
+                    ),
+                    (
+                        format!("{}.weight", name),
+                        Tensor::new(vec![outputs, layer.inputs], layer.weight.clone()),
+                    ),
+                ]
+            })
+            .map(|(name, tensor)| (name, tensor.expect("the values fill the shape")))
+            .collect()
+    }
+}
+
+fn relu(mut x: Vec<f32>) -> Vec<f32> {
+    for x in &mut x {
+        *x = x.max(0.);
+    }
+    x
+}
+
+fn add(sum: &mut [f32], x: &[f32]) {
+    for (sum, x) in sum.iter_mut().zip(x) {
+        *sum += x;
+    }
+}
+
+pub struct MlpModel {
+    layers: Layers,
+    action_space_size: usize,
+}
+
+impl MlpModel {
+    /// A model with its weights drawn from `rng`.
+    pub fn new(
+        observation_size: usize,
+        hidden_size: usize,
+        action_space_size: usize,
+        rng: &mut impl Rng,
+    ) -> Self {
+        Self {
+            layers: Layers {
+                dynamics: Linear::new(hidden_size + action_space_size, hidden_size, rng),
+                policy: Linear::new(hidden_size, action_space_size, rng),
+                representation: Linear::new(observation_size, hidden_size, rng),
+                reward: Linear::new(hidden_size, 1, rng),
+                value: Linear::new(hidden_size, 1, rng),
+            },
+            action_space_size,
+        }
+    }
+
+    /// A model with the weights of a checkpoint, sized after them.
+    pub fn from_tensors(tensors: &Tensors) -> Result<Self> {
+        let shape = |name: &str| {
+            tensors
+                .iter()
+                .find(|(other, _)| other == name)
+                .map(|(_, tensor)| tensor.shape.as_slice())
+        };
+        let (Some(&[hidden_size, observation_size]), Some(&[action_space_size, _])) =
+            (shape("representation.weight"), shape("policy.weight"))
+        else {
+            return Err(ConfigError::Invalid(
+                "the weights aren't of a fully connected model".to_string(),
+            )
+            .into());
+        };
+        let rng = &mut StdRng::seed_from_u64(0);
+        let mut model = Self::new(observation_size, hidden_size, action_space_size, rng);
+        model.load_tensors(tensors)?;
+        Ok(model)
+    }
+
+    /// The number of values of the observations the model takes.
+    pub fn observation_size(&self) -> usize {
+        self.layers.representation.inputs
+    }
+
+    /// The input of the dynamics: `hidden_state` and the one-hot of `action`.
+    fn dynamics_input(&self, hidden_state: &[f32], action: usize) -> Vec<f32> {
+        let mut input = hidden_state.to_vec();
+        input.resize(hidden_state.len() + self.action_space_size, 0.);
+        input[hidden_state.len() + action] = 1.;
+        input
+    }
+
+    /// Unroll the model along `sample`, adding its losses times `scale` to `losses` and
+    /// their gradients to `gradient`.
+    fn backpropagate(
+        &self,
+        sample: &Sample,
+        scale: f32,
+        gradient: &mut Layers,
+        losses: &mut Losses,
+    ) {
+        let layers = &self.layers;
+        // The input of the representation or the dynamics at every step, and their output.
+        let mut inputs = vec![sample.observation.clone()];
+        let mut states = vec![relu(layers.representation.forward(&sample.observation))];
+        for &action in &sample.actions {
+            let input = self.dynamics_input(states.last().unwrap(), action);
+            states.push(relu(layers.dynamics.forward(&input)));
+            inputs.push(input);
+        }
+        // From the last step back, with the gradient the step after passed to its state.
+        let mut state_gradient = vec![0.; layers.representation.bias.len()];
+        for (k, target) in sample.targets.iter().enumerate().rev() {
+            let state = &states[k];
+            let value = layers.value.forward(state)[0] - target.value;
+            losses.value += scale * value * value;
+            let g = layers
+                .value
+                .backward(state, &[scale * 2. * value], &mut gradient.value);
+            add(&mut state_gradient, &g);
+            // The reward of the first step is the one before the sampled position.
+            if k > 0 {
+                let reward = layers.reward.forward(state)[0] - target.reward;
+                losses.reward += scale * reward * reward;
+                let g = layers
+                    .reward
+                    .backward(state, &[scale * 2. * reward], &mut gradient.reward);
+                add(&mut state_gradient, &g);
+            }
+            // Masked absorbing states have no policy target.
+            if !target.policy.is_empty() {
+                let logits = layers.policy.forward(state);
+                let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                let log_sum = logits.iter().map(|l| (l - max).exp()).sum::<f32>().ln() + max;
+                let total: f32 = target.policy.iter().sum();
+                losses.policy -= scale
+                    * target
+                        .policy
+                        .iter()
+                        .zip(&logits)
+                        .map(|(p, l)| p * (l - log_sum))
+                        .sum::<f32>();
+                let logits_gradient: Vec<_> = softmax(&logits)
+                    .iter()
+                    .zip(&target.policy)
+                    .map(|(q, p)| scale * (q * total - p))
+                    .collect();
+                let g = layers
+                    .policy
+                    .backward(state, &logits_gradient, &mut gradient.policy);
+                add(&mut state_gradient, &g);
+            }
+            for (g, &x) in state_gradient.iter_mut().zip(state) {
+                if x <= 0. {
+                    *g = 0.;
+                }
+            }
+            if k > 0 {
+                let g =
+                    layers
+                        .dynamics
+                        .backward(&inputs[k], &state_gradient, &mut gradient.dynamics);
+                state_gradient = g[..state.len()].to_vec();
+            } else {
+                layers.representation.backward(
+                    &inputs[0],
+                    &state_gradient,
+                    &mut gradient.representation,
+                );
+            }
+        }
+    }
+}
+
+impl Model for MlpModel {
+    fn forward_representation(&self, observation: &[f32]) -> Vec<f32> {
+        relu(self.layers.representation.forward(observation))
+    }
+
+    fn forward_dynamics(&self, hidden_state: &[f32], action: usize) -> (Vec<f32>, f32) {
+        let input = self.dynamics_input(hidden_state, action);
+        let next = relu(self.layers.dynamics.forward(&input));
+        let reward = self.layers.reward.forward(&next)[0];
+        (next, reward)
+    }
+
+    fn forward_prediction(&self, hidden_state: &[f32]) -> (Vec<f32>, f32) {
+        (
+            self.layers.policy.forward(hidden_state),
+            self.layers.value.forward(hidden_state)[0],
+        )
+    }
+
+    fn tensors(&self) -> Tensors {
+        self.layers.tensors()
+    }
+
+    fn load_tensors(&mut self, tensors: &Tensors) -> Result<()> {
+        let expected = self.tensors();
+        if tensors.len() != expected.len() {
+            return Err(ConfigError::Invalid(format!(
+                "expected {} tensors, got {}",
+                expected.len(),
+                tensors.len()
+            ))
+            .into());
+        }
+        for (name, tensor) in tensors {
+            let (_, current) = expected
+                .iter()
+                .find(|(expected, _)| expected == name)
+                .ok_or_else(|| {
+                    ConfigError::Invalid(format!("the model has no tensor `{}`", name))
+                })?;
+            if tensor.shape != current.shape {
+                return Err(ConfigError::Invalid(format!(
+                    "tensor `{}` is {:?}, expected {:?}",
+                    name, tensor.shape, current.shape
+                ))
+                .into());
+            }
+        }
+        // As many tensors as the model's, each named after one of them, are all of them.
+        let find = |name: String| {
+            let (_, tensor) = tensors.iter().find(|(other, _)| *other == name).unwrap();
+            tensor.data.clone()
+        };
+        for (name, layer) in self.layers.named_mut() {
+            layer.weight = find(format!("{}.weight", name));
+            layer.bias = find(format!("{}.bias", name));
+        }
+        Ok(())
+    }
+
+    fn device(&self) -> Device {
+        Device::Cpu
+    }
+
+    fn to_device(&mut self, device: Device) -> Result<()> {
+        match device {
+            Device::Cpu => Ok(()),
+            device => Err(ConfigError::Unsupported(format!(
+                "the Rust model can't run on {}, only on the CPU",
+                device
+            ))
+            .into()),
+        }
+    }
+}
+
+impl Trainable for MlpModel {
+    fn gradients(&mut self, batch: &[Sample]) -> Result<(Losses, Tensors)> {
+        if batch.is_empty() {
+            return Err(ConfigError::Invalid("the batch is empty".to_string()).into());
+        }
+        let observation_size = self.observation_size();
+        for sample in batch {
+            if sample.observation.len() != observation_size {
+                return Err(ConfigError::Invalid(format!(
+                    "an observation of {} values for a model of {}",
+                    sample.observation.len(),
+                    observation_size
+                ))
+                .into());
+            }
+            if sample.targets.len() != sample.actions.len() + 1 {
+                return Err(ConfigError::Invalid(format!(
+                    "{} targets for {} actions",
+                    sample.targets.len(),
+                    sample.actions.len()
+                ))
+                .into());
+            }
+            let actions = self.action_space_size;
+            if let Some(action) = sample.actions.iter().find(|&&action| action >= actions) {
+                return Err(ConfigError::Invalid(format!(
+                    "action {} of {} actions",
+                    action, actions
+                ))
+                .into());
+            }
+            let policy = sample.targets.iter().map(|target| target.policy.len());
+            if let Some(len) = policy.filter(|&len| len != 0).find(|&len| len != actions) {
+                return Err(ConfigError::Invalid(format!(
+                    "a policy target of {} actions for {}",
+                    len, actions
+                ))
+                .into());
+            }
+        }
+        let mut gradient = self.layers.zeros();
+        let mut losses = Losses::default();
+        for sample in batch {
+            let scale = sample.weight / (sample.targets.len() * batch.len()) as f32;
+            self.backpropagate(sample, scale, &mut gradient, &mut losses);
+        }
+        Ok((losses, gradient.tensors()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        model::ModelNetwork,
+        network::Network,
+        optimizer::{Decay, LrSchedule, Optimizer, OptimizerConfig, OptimizerKind},
+        replay::Target,
+    };
+
+    fn target(value: f32, reward: f32, policy: &[f32]) -> Target {
+        Target {
+            value,
+            reward,
+            policy: policy.to_vec(),
+            absorbing: false,
+        }
+    }
+
+    fn batch() -> Vec<Sample> {
+        let sample = |observation: &[f32], actions: &[usize], weight| Sample {
+            observation: observation.to_vec(),
+            actions: actions.to_vec(),
+            targets: vec![
+                target(0.5, 0., &[0.2, 0.8, 0.]),
+                target(-1., 1., &[1., 0., 0.]),
+                // A masked absorbing state.
+                target(0., 0., &[]),
+            ],
+            next_observations: vec![],
+            game_id: 0,
+            index: 0,
+            weight,
+        };
+        vec![
+            sample(&[1., 0., -1., 2.], &[2, 0], 1.),
+            sample(&[0., 1., 0.5, 0.], &[1, 1], 0.5),
+        ]
+    }
+
+    #[test]
+    fn test_model() {
+        let mut model = MlpModel::new(4, 8, 3, &mut StdRng::seed_from_u64(0));
+        let tensors = model.tensors();
+        let names: Vec<_> = tensors.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "dynamics.bias",
+                "dynamics.weight",
+                "policy.bias",
+                "policy.weight",
+                "representation.bias",
+                "representation.weight",
+                "reward.bias",
+                "reward.weight",
+                "value.bias",
+                "value.weight"
+            ]
+        );
+        assert_eq!(tensors[1].1.shape, [8, 11]);
+        let zeros: Tensors = tensors
+            .iter()
+            .map(|(name, tensor)| {
+                let zeros = vec![0.; tensor.data.len()];
+                (
+                    name.clone(),
+                    Tensor::new(tensor.shape.clone(), zeros).unwrap(),
+                )
+            })
+            .collect();
+        model.load_tensors(&zeros).unwrap();
+        assert_eq!(model.tensors(), zeros);
+        let loaded = MlpModel::from_tensors(&zeros).unwrap();
+        assert_eq!(loaded.tensors(), zeros);
+        assert_eq!(loaded.observation_size(), 4);
+        assert!(MlpModel::from_tensors(&zeros[2..].to_vec()).is_err());
+        let network = ModelNetwork(model);
+        let output = network.initial_inference(&[1., 2., 3., 4.]);
+        assert_eq!(output.hidden_state, [0.; 8]);
+        assert_eq!(output.policy_logits, [0.; 3]);
+        assert_eq!(
+            network.recurrent_inference(&output.hidden_state, 2).reward,
+            0.
+        );
+
+        let mut model = network.0;
+        assert!(model.load_tensors(&zeros[1..].to_vec()).is_err());
+        let mut transposed = zeros.clone();
+        transposed[1].1 = Tensor::new(vec![11, 8], vec![0.; 88]).unwrap();
+        assert!(model.load_tensors(&transposed).is_err());
+        assert!(model.to_device(Device::Cuda(0)).is_err());
+        assert!(model.gradients(&[]).is_err());
+        let mut batch = batch();
+        batch[0].actions[0] = 3;
+        assert!(model.gradients(&batch).is_err());
+    }
+
+    #[test]
+    fn test_gradients() {
+        let mut model = MlpModel::new(4, 6, 3, &mut StdRng::seed_from_u64(1));
+        let batch = batch();
+        let (losses, gradients) = model.gradients(&batch).unwrap();
+        assert!(losses.value > 0. && losses.reward > 0. && losses.policy > 0.);
+        // Against central differences of the loss.
+        let weights = model.tensors();
+        let epsilon = 1e-2;
+        for (t, (name, gradient)) in gradients.iter().enumerate() {
+            for i in 0..gradient.data.len() {
+                let mut loss = |delta: f32| {
+                    let mut moved = weights.clone();
+                    moved[t].1.data[i] += delta;
+                    model.load_tensors(&moved).unwrap();
+                    model.gradients(&batch).unwrap().0.total()
+                };
+                let numeric = (loss(epsilon) - loss(-epsilon)) / (2. * epsilon);
+                let analytic = gradient.data[i];
+                assert!(
+                    (numeric - analytic).abs() < 2e-3 + 0.02 * analytic.abs(),
+                    "{}[{}]: {} by backpropagation, {} numerically",
+                    name,
+                    i,
+                    analytic,
+                    numeric
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_train_step() {
+        let mut model = MlpModel::new(4, 16, 3, &mut StdRng::seed_from_u64(2));
+        let mut optimizer = Optimizer::new(OptimizerConfig {
+            kind: OptimizerKind::Adam,
+            schedule: LrSchedule {
+                learning_rate: 0.01,
+                warmup_steps: 0,
+                decay: Decay::Constant,
+            },
+            weight_decay: 0.,
+            ..Default::default()
+        });
+        let batch = batch();
+        let (first, learning_rate) = model.train_step(&batch, &mut optimizer).unwrap();
+        assert_eq!(learning_rate, 0.01);
+        for _ in 0..300 {
+            model.train_step(&batch, &mut optimizer).unwrap();
+        }
+        let (last, _) = model.gradients(&batch).unwrap();
+        assert!(
+            last.total() < 0.5 * first.total(),
+            "the loss went from {:?} to {:?}",
+            first,
+            last
+        );
+        assert_eq!(optimizer.steps(), 301);
+    }
+}
@@ -0,0 +1,339 @@
+//! The interface deep learning backends implement: the three functions of MuZero as separate
+//! forward passes, weights to save and load, and the device they run on. [`ModelNetwork`]
+//! composes them into the [`Network`] the search evaluates with, so a backend never sees
+//! the search and the search never sees a backend. Backends that train implement
+//! [`Trainable`] as well.
+
+#[cfg(feature = "serde")]
+use std::path::Path;
+use std::{fmt, str::FromStr};
+
+#[cfg(feature = "serde")]
+use crate::checkpoint;
+use crate::{
+    checkpoint::Tensors,
+    error::{ConfigError, ParseError, Result},
+    loss::Losses,
+    network::{Network, NetworkOutput},
+    optimizer::Optimizer,
+    replay::Sample,
+};
+
+/// Where a model's tensors live and its forward passes run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Device {
+    Cpu,
+    /// The CUDA GPU with this index.
+    Cuda(usize),
+    Metal,
+    /// Any GPU through wgpu, i.e. Vulkan, Metal, DirectX 12 or WebGPU.
+    Wgpu,
+}
+
+impl fmt::Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Device::Cpu => write!(f, "cpu"),
+            Device::Cuda(index) => write!(f, "cuda:{}", index),
+            Device::Metal => write!(f, "metal"),
+            Device::Wgpu => write!(f, "wgpu"),
+        }
+    }
+}
+
+impl FromStr for Device {
+    type Err = ParseError;
+
+    /// `cpu`, `cuda` for the first GPU, `cuda:N`, `metal` or `wgpu`.
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        match s {
+            "cpu" => Ok(Device::Cpu),
+            "cuda" => Ok(Device::Cuda(0)),
+            "metal" => Ok(Device::Metal),
+            "wgpu" => Ok(Device::Wgpu),
+            _ => match s.strip_prefix("cuda:") {
+                Some(index) => Ok(Device::Cuda(index.parse().map_err(|_| {
+                    ParseError::Invalid(format!("invalid CUDA device `{}`", s))
+                })?)),
+                None => Err(ParseError::Invalid(format!(
+                    "unknown device `{}`, expected cpu, cuda:N, metal or wgpu",
+                    s
+                ))),
+            },
+        }
+    }
+}
+
+/// The floating point format of inference. Training keeps full precision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Precision {
+    #[default]
+    F32,
+    F16,
+    Bf16,
+}
+
+impl fmt::Display for Precision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Precision::F32 => write!(f, "f32"),
+            Precision::F16 => write!(f, "f16"),
+            Precision::Bf16 => write!(f, "bf16"),
+        }
+    }
+}
+
+impl FromStr for Precision {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        match s {
+            "f32" => Ok(Precision::F32),
+            "f16" => Ok(Precision::F16),
+            "bf16" => Ok(Precision::Bf16),
+            _ => Err(ParseError::Invalid(format!(
+                "unknown precision `{}`, expected f32, f16 or bf16",
+                s
+            ))),
+        }
+    }
+}
+
+/// Where and how a model evaluates positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvaluatorConfig {
+    pub device: Device,
+    pub precision: Precision,
+}
+
+impl Default for EvaluatorConfig {
+    fn default() -> Self {
+        Self {
+            device: Device::Cpu,
+            precision: Precision::F32,
+        }
+    }
+}
+
+impl EvaluatorConfig {
+    /// Move `model` to the configured device and precision, falling back to the CPU and to
+    /// f32 with a warning when they aren't available, so that one config runs everywhere.
+    /// Returns what the model ended up with.
+    pub fn apply<M: Model>(&self, model: &mut M) -> Result<EvaluatorConfig> {
+        let device = match model.to_device(self.device) {
+            Ok(()) => self.device,
+            Err(e) => {
+                tracing::warn!("{:#}, falling back to the CPU", e);
+                model.to_device(Device::Cpu)?;
+                Device::Cpu
+            }
+        };
+        let precision = match model.set_precision(self.precision) {
+            Ok(()) => self.precision,
+            Err(e) => {
+                tracing::warn!("{:#}, falling back to f32", e);
+                model.set_precision(Precision::F32)?;
+                Precision::F32
+            }
+        };
+        Ok(EvaluatorConfig { device, precision })
+    }
+}
+
+/// A MuZero model on some backend, on flat `f32` states like [`Network`].
+pub trait Model {
+    /// `h`: the hidden state of an observation.
+    fn forward_representation(&self, observation: &[f32]) -> Vec<f32>;
+
+    /// `g`: the hidden state after `action` and the reward for it.
+    fn forward_dynamics(&self, hidden_state: &[f32], action: usize) -> (Vec<f32>, f32);
+
+    /// `f`: the policy logits and the value of a hidden state.
+    fn forward_prediction(&self, hidden_state: &[f32]) -> (Vec<f32>, f32);
+
+    /// The weights, by name.
+    fn tensors(&self) -> Tensors;
+
+    /// Replace the weights with `tensors`, which must match the model's names and shapes.
+    fn load_tensors(&mut self, tensors: &Tensors) -> Result<()>;
+
+    fn device(&self) -> Device;
+
+    /// Move the weights to `device`, failing if the backend can't run there.
+    fn to_device(&mut self, device: Device) -> Result<()>;
+
+    /// Run inference in `precision`. Backends without half precision keep the default, which
+    /// only accepts f32.
+    fn set_precision(&mut self, precision: Precision) -> Result<()> {
+        match precision {
+            Precision::F32 => Ok(()),
+            precision => Err(ConfigError::Unsupported(format!(
+                "the backend can't run in {}",
+                precision
+            ))
+            .into()),
+        }
+    }
+
+    /// Write the weights to a safetensors file.
+    #[cfg(feature = "serde")]
+    fn save(&self, path: &Path) -> Result<()> {
+        checkpoint::write_safetensors(path, &self.tensors())
+    }
+
+    /// Read the weights from a safetensors file.
+    #[cfg(feature = "serde")]
+    fn load(&mut self, path: &Path) -> Result<()> {
+        self.load_tensors(&checkpoint::read_safetensors(path)?)
+    }
+}
+
+/// A [`Model`] that learns from batches of the replay buffer, unrolling the dynamics along
+/// the actions of every sample.
+pub trait Trainable: Model {
+    /// The losses of `batch` and their gradients, with the names and shapes of
+    /// [`Model::tensors`] and in their order.
+    fn gradients(&mut self, batch: &[Sample]) -> Result<(Losses, Tensors)>;
+
+    /// Take a step of `optimizer` on `batch`. Returns the losses before the step and the
+    /// learning rate of it.
+    fn train_step(&mut self, batch: &[Sample], optimizer: &mut Optimizer) -> Result<(Losses, f32)> {
+        let (losses, gradients) = self.gradients(batch)?;
+        let mut weights = self.tensors();
+        let learning_rate = optimizer.step(&mut weights, &gradients)?;
+        self.load_tensors(&weights)?;
+        Ok((losses, learning_rate))
+    }
+}
+
+/// A [`Model`] as the [`Network`] the search evaluates with.
+pub struct ModelNetwork<M>(pub M);
+
+impl<M: Model> Network for ModelNetwork<M> {
+    fn initial_inference(&self, observation: &[f32]) -> NetworkOutput {
+        let hidden_state = self.0.forward_representation(observation);
+        let (policy_logits, value) = self.0.forward_prediction(&hidden_state);
+        NetworkOutput {
+            value,
+            reward: 0.,
+            policy_logits,
+            hidden_state,
+        }
+    }
+
+    fn recurrent_inference(&self, hidden_state: &[f32], action: usize) -> NetworkOutput {
+        let (hidden_state, reward) = self.0.forward_dynamics(hidden_state, action);
+        let (policy_logits, value) = self.0.forward_prediction(&hidden_state);
+        NetworkOutput {
+            value,
+            reward,
+            policy_logits,
+            hidden_state,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::Tensor;
+
+    /// The hidden state is the observation scaled by a weight, each action adds its index.
+    struct ScaleModel {
+        weight: f32,
+    }
+
+    impl Model for ScaleModel {
+        fn forward_representation(&self, observation: &[f32]) -> Vec<f32> {
+            observation.iter().map(|x| x * self.weight).collect()
+        }
+
+        fn forward_dynamics(&self, hidden_state: &[f32], action: usize) -> (Vec<f32>, f32) {
+            let next = hidden_state.iter().map(|x| x + action as f32).collect();
+            (next, action as f32)
+        }
+
+        fn forward_prediction(&self, hidden_state: &[f32]) -> (Vec<f32>, f32) {
+            (hidden_state.to_vec(), hidden_state.iter().sum())
+        }
+
+        fn tensors(&self) -> Tensors {
+            vec![(
+                "weight".to_string(),
+                Tensor::new(vec![1], vec![self.weight]).unwrap(),
+            )]
+        }
+
+        fn load_tensors(&mut self, tensors: &Tensors) -> Result<()> {
+            match tensors.as_slice() {
+                [(name, tensor)] if name == "weight" && tensor.shape == [1] => {
+                    self.weight = tensor.data[0];
+                    Ok(())
+                }
+                _ => Err(ConfigError::Invalid("expected one weight".to_string()).into()),
+            }
+        }
+
+        fn device(&self) -> Device {
+            Device::Cpu
+        }
+
+        fn to_device(&mut self, device: Device) -> Result<()> {
+            match device {
+                Device::Cpu => Ok(()),
+                device => Err(ConfigError::Unsupported(format!("can't run on {}", device)).into()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_network() {
+        let network = ModelNetwork(ScaleModel { weight: 2. });
+        let output = network.initial_inference(&[1., 2.]);
+        assert_eq!(output.hidden_state, [2., 4.]);
+        assert_eq!(output.value, 6.);
+        let output = network.recurrent_inference(&output.hidden_state, 1);
+        assert_eq!(output.policy_logits, [3., 5.]);
+        assert_eq!(output.reward, 1.);
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut model = ScaleModel { weight: 1. };
+        #[cfg(feature = "serde")]
+        {
+            let path = std::env::temp_dir().join(format!("muzero-model-{}", std::process::id()));
+            ScaleModel { weight: 3. }.save(&path).unwrap();
+            model.load(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(model.weight, 3.);
+        }
+        assert!(model.load_tensors(&vec![]).is_err());
+        assert!(model.to_device(Device::Cuda(0)).is_err());
+        assert_eq!(Device::Cuda(1).to_string(), "cuda:1");
+    }
+
+    #[test]
+    fn test_evaluator_config() {
+        for device in ["cpu", "cuda:1", "metal", "wgpu"] {
+            assert_eq!(device.parse::<Device>().unwrap().to_string(), device);
+        }
+        assert_eq!("cuda".parse::<Device>().unwrap(), Device::Cuda(0));
+        for device in ["gpu", "cuda:", "cuda:x"] {
+            assert!(device.parse::<Device>().is_err(), "{}", device);
+        }
+        assert_eq!("bf16".parse::<Precision>().unwrap(), Precision::Bf16);
+        assert!("f64".parse::<Precision>().is_err());
+
+        // The model only runs on the CPU in f32.
+        let mut model = ScaleModel { weight: 1. };
+        let config = EvaluatorConfig {
+            device: Device::Cuda(0),
+            precision: Precision::F16,
+        };
+        assert_eq!(
+            config.apply(&mut model).unwrap(),
+            EvaluatorConfig::default()
+        );
+    }
+}
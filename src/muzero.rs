@@ -0,0 +1,1082 @@
+//! The MuZero tree search, which plans with a learned model behind [`Network`].
+
+use std::{collections::HashMap, iter};
+
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+
+use crate::{
+    cache::CacheStats,
+    mcts::FirstPlayUrgency,
+    network::{softmax, AfterstateOutput, Network, NetworkOutput, Pending, StochasticNetwork},
+    random,
+};
+
+/// Search hyperparameters for the MuZero tree search.
+#[derive(Debug, Clone)]
+pub struct MuZeroConfig {
+    pub action_space_size: usize,
+    /// 1 for single-player environments, 2 for alternating zero-sum games.
+    pub num_players: usize,
+    pub num_simulations: usize,
+    pub discount: f32,
+    pub root_dirichlet_alpha: f32,
+    pub root_exploration_fraction: f32,
+    pub pb_c_base: f32,
+    pub pb_c_init: f32,
+    pub known_bounds: Option<KnownBounds>,
+    /// Read the reward of the network as the value prefix of EfficientZero instead: the sum
+    /// of the rewards since its LSTM was last reset, which happens every this many steps of
+    /// the unroll. Meant for single-player environments.
+    pub value_prefix_horizon: Option<usize>,
+    /// Descend to this many leaves before evaluating them in one network batch. A virtual
+    /// loss on the paths already taken sends the descents of a batch apart. 1 evaluates every
+    /// leaf on its own.
+    pub leaf_batch_size: usize,
+    /// Descend to the next batch of leaves while the network evaluates the last one, for
+    /// networks which evaluate in the background like [`crate::inference::InferenceClient`].
+    pub overlap_evaluation: bool,
+    /// The value of unvisited children in the PUCT score, before their prior is added.
+    pub first_play_urgency: FirstPlayUrgency,
+}
+
+impl MuZeroConfig {
+    pub fn board_game(action_space_size: usize, dirichlet_alpha: f32) -> Self {
+        Self {
+            action_space_size,
+            num_players: 2,
+            num_simulations: 800,
+            discount: 1.,
+            root_dirichlet_alpha: dirichlet_alpha,
+            root_exploration_fraction: 0.25,
+            pb_c_base: 19652.,
+            pb_c_init: 1.25,
+            known_bounds: Some(KnownBounds { min: -1., max: 1. }),
+            value_prefix_horizon: None,
+            leaf_batch_size: 1,
+            overlap_evaluation: false,
+            first_play_urgency: FirstPlayUrgency::Lowest,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct KnownBounds {
+    pub min: f32,
+    pub max: f32,
+}
+
+/// The min-max values of the tree, used to normalize Q values into [0, 1].
+struct MinMaxStats {
+    minimum: f32,
+    maximum: f32,
+}
+
+impl MinMaxStats {
+    fn new(known_bounds: Option<KnownBounds>) -> Self {
+        match known_bounds {
+            Some(bounds) => Self {
+                minimum: bounds.min,
+                maximum: bounds.max,
+            },
+            None => Self {
+                minimum: f32::MAX,
+                maximum: f32::MIN,
+            },
+        }
+    }
+
+    fn update(&mut self, value: f32) {
+        self.maximum = self.maximum.max(value);
+        self.minimum = self.minimum.min(value);
+    }
+
+    fn normalize(&self, value: f32) -> f32 {
+        if self.maximum > self.minimum {
+            // We normalize only when we have set the maximum and minimum values.
+            (value - self.minimum) / (self.maximum - self.minimum)
+        } else {
+            value
+        }
+    }
+}
+
+/// The root statistics of a finished search.
+#[derive(Debug, Clone)]
+pub struct SearchStatistics {
+    /// The search value of the root, from the perspective of the player to play.
+    pub root_value: f32,
+    /// The visit count of every action index; illegal actions are never visited.
+    pub visit_counts: Vec<usize>,
+    /// The number of moves on the longest path of the search tree.
+    pub depth: usize,
+    /// The nodes the search allocated.
+    pub nodes: usize,
+    /// The lookups of the network's cache during the search, if it has one.
+    pub cache: Option<CacheStats>,
+}
+
+impl SearchStatistics {
+    /// The visit count distribution, used as the policy target during training.
+    pub fn policy(&self) -> Vec<f32> {
+        let total: usize = self.visit_counts.iter().sum();
+        self.visit_counts
+            .iter()
+            .map(|&visits| visits as f32 / total.max(1) as f32)
+            .collect()
+    }
+
+    /// Sample an action from the visit counts raised to `1 / temperature`; a temperature of
+    /// 0 picks the most visited action.
+    pub fn select_action(&self, temperature: f32) -> usize {
+        if temperature == 0. {
+            let (action, _) = self
+                .visit_counts
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, &visits)| visits)
+                .unwrap();
+            return action;
+        }
+        let weights = self
+            .visit_counts
+            .iter()
+            .map(|&visits| (visits as f32).powf(1. / temperature));
+        WeightedIndex::new(weights)
+            .unwrap()
+            .sample(&mut random::rng())
+    }
+}
+
+/// Run a MuZero search from `observation` with a deterministic model.
+pub fn run_mcts<N: Network>(
+    config: &MuZeroConfig,
+    network: &N,
+    observation: &[f32],
+    legal_actions: &[usize],
+    to_play: usize,
+) -> SearchStatistics {
+    Search::new(config, Deterministic(network)).run(observation, legal_actions, to_play)
+}
+
+/// Run a Stochastic MuZero search from `observation`.
+///
+/// Actions lead to chance nodes holding the afterstate, and chance codes lead from there to the
+/// next decision node. Chance nodes pick the code that is most under-visited relative to its
+/// predicted probability, so their visit counts follow the predicted distribution.
+pub fn run_stochastic_mcts<N: StochasticNetwork>(
+    config: &MuZeroConfig,
+    network: &N,
+    observation: &[f32],
+    legal_actions: &[usize],
+    to_play: usize,
+) -> SearchStatistics {
+    Search::new(config, Stochastic(network)).run(observation, legal_actions, to_play)
+}
+
+/// What expanding a child of a decision node produced.
+enum Expansion {
+    Decision(NetworkOutput),
+    Chance(AfterstateOutput),
+}
+
+/// An [`Expansion`] the model may still be computing.
+enum PendingExpansion {
+    Ready(Expansion),
+    Decision(Pending),
+}
+
+impl PendingExpansion {
+    fn wait(self) -> Expansion {
+        match self {
+            PendingExpansion::Ready(expansion) => expansion,
+            PendingExpansion::Decision(output) => Expansion::Decision(output.wait()),
+        }
+    }
+}
+
+/// The model as seen by the search.
+trait LatentModel {
+    fn initial_inference(&self, observation: &[f32]) -> NetworkOutput;
+
+    fn expand_action(&self, hidden_state: &[f32], action: usize) -> Expansion;
+
+    fn expand_chance_code(&self, afterstate: &[f32], chance_code: usize) -> NetworkOutput;
+
+    /// Start expanding many leaves at once, each given as the state of its parent, the action
+    /// or chance code leading to it, and whether the parent is a chance node.
+    fn submit_batch(&self, leaves: &[(&[f32], usize, bool)]) -> Vec<PendingExpansion> {
+        leaves
+            .iter()
+            .map(|&(state, action, chance)| {
+                PendingExpansion::Ready(if chance {
+                    Expansion::Decision(self.expand_chance_code(state, action))
+                } else {
+                    self.expand_action(state, action)
+                })
+            })
+            .collect()
+    }
+
+    fn is_absorbing(&self, _hidden_state: &[f32]) -> bool {
+        false
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        None
+    }
+}
+
+struct Deterministic<'a, N>(&'a N);
+
+impl<N: Network> LatentModel for Deterministic<'_, N> {
+    fn initial_inference(&self, observation: &[f32]) -> NetworkOutput {
+        self.0.initial_inference(observation)
+    }
+
+    fn expand_action(&self, hidden_state: &[f32], action: usize) -> Expansion {
+        Expansion::Decision(self.0.recurrent_inference(hidden_state, action))
+    }
+
+    fn expand_chance_code(&self, _afterstate: &[f32], _chance_code: usize) -> NetworkOutput {
+        unreachable!("a deterministic model never creates chance nodes")
+    }
+
+    fn submit_batch(&self, leaves: &[(&[f32], usize, bool)]) -> Vec<PendingExpansion> {
+        let inputs: Vec<_> = leaves
+            .iter()
+            .map(|&(hidden_state, action, _)| (hidden_state, action))
+            .collect();
+        self.0
+            .submit_recurrent_batch(&inputs)
+            .into_iter()
+            .map(PendingExpansion::Decision)
+            .collect()
+    }
+
+    fn is_absorbing(&self, hidden_state: &[f32]) -> bool {
+        self.0.is_absorbing(hidden_state)
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.0.cache_stats()
+    }
+}
+
+struct Stochastic<'a, N>(&'a N);
+
+impl<N: StochasticNetwork> LatentModel for Stochastic<'_, N> {
+    fn initial_inference(&self, observation: &[f32]) -> NetworkOutput {
+        self.0.initial_inference(observation)
+    }
+
+    fn expand_action(&self, hidden_state: &[f32], action: usize) -> Expansion {
+        Expansion::Chance(self.0.afterstate_inference(hidden_state, action))
+    }
+
+    fn expand_chance_code(&self, afterstate: &[f32], chance_code: usize) -> NetworkOutput {
+        self.0.chance_inference(afterstate, chance_code)
+    }
+}
+
+struct Node {
+    prior: f32,
+    /// The action, or chance code, on the edge from the parent.
+    action: usize,
+    to_play: usize,
+    visit_count: usize,
+    /// Visits of the descents waiting for their leaf to be evaluated.
+    virtual_visits: usize,
+    /// Sum of the values backed up through this node, from the perspective of `to_play`.
+    value_sum: f32,
+    /// The reward received by the parent's player when moving to this node.
+    reward: f32,
+    /// With value prefixes, the one predicted for this node.
+    value_prefix: f32,
+    /// The hidden state, or the afterstate for chance nodes.
+    state: Vec<f32>,
+    is_chance: bool,
+    /// Past the end of the episode: never expanded, and worth zero.
+    is_absorbing: bool,
+    children: Vec<usize>,
+}
+
+impl Node {
+    fn new(prior: f32, action: usize) -> Self {
+        Self {
+            prior,
+            action,
+            to_play: 0,
+            visit_count: 0,
+            virtual_visits: 0,
+            value_sum: 0.,
+            reward: 0.,
+            value_prefix: 0.,
+            state: vec![],
+            is_chance: false,
+            is_absorbing: false,
+            children: vec![],
+        }
+    }
+
+    fn expanded(&self) -> bool {
+        !self.children.is_empty()
+    }
+
+    fn value(&self) -> f32 {
+        if self.visit_count == 0 {
+            0.
+        } else {
+            self.value_sum / self.visit_count as f32
+        }
+    }
+}
+
+const ROOT: usize = 0;
+
+struct Search<'a, M> {
+    config: &'a MuZeroConfig,
+    model: M,
+    nodes: Vec<Node>,
+    min_max_stats: MinMaxStats,
+    /// The deepest leaf reached.
+    depth: usize,
+    /// The paths of the descents which reached a leaf already being evaluated, by leaf.
+    waiting: HashMap<usize, Vec<Vec<usize>>>,
+}
+
+/// Leaves descended to, whose expansions the model is working on.
+struct Round {
+    /// The paths from the root to the leaves.
+    paths: Vec<Vec<usize>>,
+    expansions: Vec<PendingExpansion>,
+}
+
+impl<'a, M: LatentModel> Search<'a, M> {
+    fn new(config: &'a MuZeroConfig, model: M) -> Self {
+        Self {
+            config,
+            model,
+            nodes: vec![],
+            min_max_stats: MinMaxStats::new(config.known_bounds),
+            depth: 0,
+            waiting: HashMap::new(),
+        }
+    }
+
+    fn run(
+        mut self,
+        observation: &[f32],
+        legal_actions: &[usize],
+        to_play: usize,
+    ) -> SearchStatistics {
+        // At the root of the search tree we use the representation function to
+        // obtain a hidden state given the current observation.
+        let cache = self.model.cache_stats();
+        self.nodes.push(Node::new(0., 0));
+        let output = self.model.initial_inference(observation);
+        let value = output.value;
+        self.expand_decision(ROOT, to_play, output, legal_actions);
+        self.backpropagate(&[ROOT], value, to_play);
+        self.add_exploration_noise(ROOT);
+
+        let mut simulations = 0;
+        let mut evaluating = None;
+        while simulations < self.config.num_simulations || evaluating.is_some() {
+            let mut round = None;
+            if simulations < self.config.num_simulations {
+                let batch_size = (self.config.num_simulations - simulations)
+                    .min(self.config.leaf_batch_size.max(1));
+                simulations += batch_size;
+                round = Some(self.descend(batch_size));
+            }
+            // Finish the last round only after descending to the next leaves, so that the
+            // descents overlapped with its evaluation.
+            if let Some(previous) = evaluating.take() {
+                self.finish(previous);
+            }
+            match round {
+                Some(round) if self.config.overlap_evaluation => evaluating = Some(round),
+                Some(round) => self.finish(round),
+                None => {}
+            }
+        }
+
+        let mut visit_counts = vec![0; self.config.action_space_size];
+        for &child in &self.nodes[ROOT].children {
+            visit_counts[self.nodes[child].action] = self.nodes[child].visit_count;
+        }
+        SearchStatistics {
+            root_value: self.nodes[ROOT].value(),
+            visit_counts,
+            depth: self.depth,
+            nodes: self.nodes.len(),
+            cache: cache
+                .zip(self.model.cache_stats())
+                .map(|(before, after)| after.since(&before)),
+        }
+    }
+
+    /// Descend to `count` leaves, with a virtual visit on each path until its leaf is
+    /// evaluated, and start evaluating those not being evaluated already.
+    fn descend(&mut self, count: usize) -> Round {
+        let mut paths = vec![];
+        for _ in 0..count {
+            let mut node = ROOT;
+            let mut search_path = vec![node];
+            while self.nodes[node].expanded() {
+                node = self.select_child(node);
+                search_path.push(node);
+            }
+            self.depth = self.depth.max(search_path.len() - 1);
+            if self.nodes[node].is_absorbing {
+                let to_play = self.nodes[node].to_play;
+                self.backpropagate(&search_path, 0., to_play);
+                continue;
+            }
+            let evaluating = self.nodes[node].virtual_visits > 0;
+            for &node in &search_path {
+                self.nodes[node].virtual_visits += 1;
+            }
+            if evaluating {
+                self.waiting.entry(node).or_default().push(search_path);
+            } else {
+                paths.push(search_path);
+            }
+        }
+
+        // Inside the search tree we use the dynamics function to obtain the next
+        // hidden state given an action and the previous hidden state.
+        let inputs: Vec<_> = paths
+            .iter()
+            .map(|path| {
+                let parent = &self.nodes[path[path.len() - 2]];
+                let action = self.nodes[path[path.len() - 1]].action;
+                (&parent.state[..], action, parent.is_chance)
+            })
+            .collect();
+        let expansions = self.model.submit_batch(&inputs);
+        Round { paths, expansions }
+    }
+
+    /// Expand the leaves of `round`, and back up their values along their paths and those of
+    /// the descents waiting for them.
+    fn finish(&mut self, round: Round) {
+        for (search_path, expansion) in round.paths.into_iter().zip(round.expansions) {
+            let parent_depth = search_path.len() - 2;
+            let node = search_path[parent_depth + 1];
+            let (value, to_play) = self.expand_leaf(
+                search_path[parent_depth],
+                node,
+                parent_depth,
+                expansion.wait(),
+            );
+            let waiting = self.waiting.remove(&node).unwrap_or_default();
+            for path in iter::once(search_path).chain(waiting) {
+                for &node in &path {
+                    self.nodes[node].virtual_visits -= 1;
+                }
+                self.backpropagate(&path, value, to_play);
+            }
+        }
+    }
+
+    fn next_player(&self, player: usize) -> usize {
+        (player + 1) % self.config.num_players
+    }
+
+    /// Express `value`, seen from `from`, from the perspective of `to`.
+    fn perspective(&self, value: f32, from: usize, to: usize) -> f32 {
+        if from == to {
+            value
+        } else {
+            -value
+        }
+    }
+
+    /// Expand `node`, the child of `parent` at `parent_depth`, with what the model predicted
+    /// for it, and return the value estimate and the player it belongs to.
+    fn expand_leaf(
+        &mut self,
+        parent: usize,
+        node: usize,
+        parent_depth: usize,
+        expansion: Expansion,
+    ) -> (f32, usize) {
+        let parent_to_play = self.nodes[parent].to_play;
+        let output = match expansion {
+            Expansion::Decision(output) => output,
+            Expansion::Chance(output) => {
+                // The afterstate belongs to the player who just acted.
+                let value = output.value;
+                self.expand_chance(node, parent_to_play, output);
+                self.nodes[node].value_prefix = self.nodes[parent].value_prefix;
+                return (value, parent_to_play);
+            }
+        };
+        let to_play = self.next_player(parent_to_play);
+        if self.model.is_absorbing(&output.hidden_state) {
+            // The reward of reaching the end is still real.
+            let node_ref = &mut self.nodes[node];
+            node_ref.to_play = to_play;
+            node_ref.reward = output.reward;
+            node_ref.is_absorbing = true;
+            return (0., to_play);
+        }
+        let value = output.value;
+        let all_actions: Vec<usize> = (0..self.config.action_space_size).collect();
+        self.expand_decision(node, to_play, output, &all_actions);
+        if let Some(horizon) = self.config.value_prefix_horizon {
+            // The reward of the step is what it added to the prefix, which starts over
+            // where the LSTM was reset.
+            let parent_prefix = if parent_depth.is_multiple_of(horizon) {
+                0.
+            } else {
+                self.nodes[parent].value_prefix
+            };
+            let node_ref = &mut self.nodes[node];
+            node_ref.value_prefix = node_ref.reward;
+            node_ref.reward -= parent_prefix;
+        }
+        (value, to_play)
+    }
+
+    /// We expand a node using the value, reward and policy prediction obtained from
+    /// the neural network.
+    fn expand_decision(
+        &mut self,
+        node: usize,
+        to_play: usize,
+        output: NetworkOutput,
+        actions: &[usize],
+    ) {
+        let logits: Vec<f32> = actions
+            .iter()
+            .map(|&action| output.policy_logits[action])
+            .collect();
+        let priors = softmax(&logits);
+        let node_ref = &mut self.nodes[node];
+        node_ref.to_play = to_play;
+        node_ref.state = output.hidden_state;
+        node_ref.reward = output.reward;
+        self.add_children(node, actions.iter().cloned().zip(priors));
+    }
+
+    fn expand_chance(&mut self, node: usize, to_play: usize, output: AfterstateOutput) {
+        let priors = softmax(&output.chance_logits);
+        let node_ref = &mut self.nodes[node];
+        node_ref.to_play = to_play;
+        node_ref.state = output.afterstate;
+        node_ref.is_chance = true;
+        self.add_children(node, priors.into_iter().enumerate());
+    }
+
+    fn add_children(&mut self, node: usize, children: impl Iterator<Item = (usize, f32)>) {
+        for (action, prior) in children {
+            let child = self.nodes.len();
+            self.nodes.push(Node::new(prior, action));
+            self.nodes[node].children.push(child);
+        }
+    }
+
+    fn select_child(&self, node: usize) -> usize {
+        let children = &self.nodes[node].children;
+        if self.nodes[node].is_chance {
+            let score = |&child: &usize| {
+                let child = &self.nodes[child];
+                child.prior / (child.visit_count + child.virtual_visits + 1) as f32
+            };
+            return *children
+                .iter()
+                .max_by(|a, b| score(a).total_cmp(&score(b)))
+                .unwrap();
+        }
+        let score = |&child: &usize| self.ucb_score(node, child);
+        *children
+            .iter()
+            .max_by(|a, b| score(a).total_cmp(&score(b)))
+            .unwrap()
+    }
+
+    /// The Q value of `child` from the perspective of the player to play at `parent`.
+    fn q_value(&self, parent: usize, child: usize) -> f32 {
+        let (parent, child) = (&self.nodes[parent], &self.nodes[child]);
+        let value = self.perspective(child.value(), child.to_play, parent.to_play);
+        child.reward + self.config.discount * value
+    }
+
+    /// The score for a node is based on its value, plus an exploration bonus based on the prior.
+    fn ucb_score(&self, parent: usize, child: usize) -> f32 {
+        let (parent_node, child_node) = (&self.nodes[parent], &self.nodes[child]);
+        let parent_visits = (parent_node.visit_count + parent_node.virtual_visits) as f32;
+        let visits = child_node.visit_count + child_node.virtual_visits;
+        let mut pb_c = ((parent_visits + self.config.pb_c_base + 1.) / self.config.pb_c_base).ln()
+            + self.config.pb_c_init;
+        pb_c *= parent_visits.sqrt() / (visits + 1) as f32;
+
+        let prior_score = pb_c * child_node.prior;
+        let value_score = if child_node.visit_count > 0 {
+            // Pending visits count as the worst value seen so far: the virtual loss.
+            self.min_max_stats.normalize(self.q_value(parent, child))
+                * child_node.visit_count as f32
+                / visits as f32
+        } else if child_node.virtual_visits > 0 {
+            0.
+        } else {
+            match self.config.first_play_urgency {
+                FirstPlayUrgency::Infinite => f32::INFINITY,
+                FirstPlayUrgency::Lowest => 0.,
+                FirstPlayUrgency::Parent { reduction } => self
+                    .min_max_stats
+                    .normalize(parent_node.value() - reduction),
+                FirstPlayUrgency::Value(value) => self.min_max_stats.normalize(value),
+            }
+        };
+        prior_score + value_score
+    }
+
+    /// At the end of a simulation, we propagate the evaluation all the way up the
+    /// tree to the root.
+    fn backpropagate(&mut self, search_path: &[usize], value: f32, to_play: usize) {
+        // `value` is always kept from the perspective of `to_play`.
+        let mut value = value;
+        for (depth, &node) in search_path.iter().enumerate().rev() {
+            let node_to_play = self.nodes[node].to_play;
+            let node_value = self.perspective(value, to_play, node_to_play);
+            self.nodes[node].value_sum += node_value;
+            self.nodes[node].visit_count += 1;
+
+            if depth == 0 {
+                break;
+            }
+            let parent = search_path[depth - 1];
+            let q_value = self.q_value(parent, node);
+            self.min_max_stats.update(q_value);
+
+            let parent_to_play = self.nodes[parent].to_play;
+            let reward = self.perspective(self.nodes[node].reward, parent_to_play, to_play);
+            value = reward + self.config.discount * value;
+        }
+    }
+
+    /// At the start of each search, we add dirichlet noise to the prior of the root
+    /// to encourage the search to explore new actions.
+    fn add_exploration_noise(&mut self, node: usize) {
+        let children = self.nodes[node].children.clone();
+        let noise = sample_dirichlet(self.config.root_dirichlet_alpha, children.len());
+        let frac = self.config.root_exploration_fraction;
+        for (child, noise) in children.into_iter().zip(noise) {
+            let prior = &mut self.nodes[child].prior;
+            *prior = *prior * (1. - frac) + noise * frac;
+        }
+    }
+}
+
+/// A symmetric Dirichlet sample, drawn as normalized Gamma(alpha, 1) samples.
+fn sample_dirichlet(alpha: f32, n: usize) -> Vec<f32> {
+    let mut rng = random::rng();
+    let samples: Vec<f32> = (0..n).map(|_| sample_gamma(alpha, &mut rng)).collect();
+    let sum: f32 = samples.iter().sum();
+    if sum > 0. {
+        samples.into_iter().map(|sample| sample / sum).collect()
+    } else {
+        vec![1. / n as f32; n]
+    }
+}
+
+/// Marsaglia and Tsang's method for Gamma(alpha, 1).
+fn sample_gamma(alpha: f32, rng: &mut impl Rng) -> f32 {
+    if alpha < 1. {
+        // Gamma(alpha) = Gamma(alpha + 1) * U^(1 / alpha)
+        let u: f32 = rng.gen();
+        return sample_gamma(alpha + 1., rng) * u.powf(1. / alpha);
+    }
+    let d = alpha - 1. / 3.;
+    let c = 1. / (9. * d).sqrt();
+    loop {
+        let x = sample_standard_normal(rng);
+        let v = (1. + c * x).powi(3);
+        if v <= 0. {
+            continue;
+        }
+        let u: f32 = rng.gen();
+        if u.ln() < 0.5 * x * x + d - d * v + d * v.ln() {
+            return d * v;
+        }
+    }
+}
+
+/// Box-Muller transform.
+fn sample_standard_normal(rng: &mut impl Rng) -> f32 {
+    let u1: f32 = rng.gen_range(f32::EPSILON..1.);
+    let u2: f32 = rng.gen();
+    (-2. * u1.ln()).sqrt() * (2. * std::f32::consts::PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::UniformNetwork;
+    use std::sync::Mutex;
+
+    /// A single-player model whose hidden state is the first action taken; only action 2 pays off.
+    struct FirstActionNetwork;
+
+    impl Network for FirstActionNetwork {
+        fn initial_inference(&self, _observation: &[f32]) -> NetworkOutput {
+            NetworkOutput {
+                value: 0.,
+                reward: 0.,
+                policy_logits: vec![0.; 4],
+                hidden_state: vec![],
+            }
+        }
+
+        fn recurrent_inference(&self, hidden_state: &[f32], action: usize) -> NetworkOutput {
+            let first_action = hidden_state.first().cloned().unwrap_or(action as f32);
+            NetworkOutput {
+                value: 0.,
+                reward: if hidden_state.is_empty() && action == 2 {
+                    1.
+                } else {
+                    0.
+                },
+                policy_logits: vec![0.; 4],
+                hidden_state: vec![first_action],
+            }
+        }
+    }
+
+    /// A two-player model where, if the first player opens with action 0, the second player
+    /// receives a reward of 1 with every reply.
+    struct PoisonedOpeningNetwork;
+
+    impl Network for PoisonedOpeningNetwork {
+        fn initial_inference(&self, _observation: &[f32]) -> NetworkOutput {
+            NetworkOutput {
+                value: 0.,
+                reward: 0.,
+                policy_logits: vec![0.; 2],
+                hidden_state: vec![],
+            }
+        }
+
+        fn recurrent_inference(&self, hidden_state: &[f32], action: usize) -> NetworkOutput {
+            let mut history = hidden_state.to_vec();
+            history.push(action as f32);
+            let reward = if history.len() == 2 && history[0] == 0. {
+                1.
+            } else {
+                0.
+            };
+            NetworkOutput {
+                value: 0.,
+                reward,
+                policy_logits: vec![0.; 2],
+                hidden_state: history,
+            }
+        }
+    }
+
+    /// A single-player model where the first action `a` wins 1 with probability
+    /// `WIN_PROBABILITIES[a]` and loses 1 otherwise. Chance code 0 is a win; later moves don't matter.
+    struct CoinNetwork;
+
+    impl CoinNetwork {
+        const WIN_PROBABILITIES: [f32; 2] = [0.5, 0.9];
+    }
+
+    impl StochasticNetwork for CoinNetwork {
+        fn num_chance_codes(&self) -> usize {
+            2
+        }
+
+        fn initial_inference(&self, _observation: &[f32]) -> NetworkOutput {
+            NetworkOutput {
+                value: 0.,
+                reward: 0.,
+                policy_logits: vec![0.; 2],
+                hidden_state: vec![],
+            }
+        }
+
+        fn afterstate_inference(&self, hidden_state: &[f32], action: usize) -> AfterstateOutput {
+            let p = Self::WIN_PROBABILITIES[action];
+            AfterstateOutput {
+                value: 0.,
+                chance_logits: vec![p.ln(), (1. - p).ln()],
+                afterstate: hidden_state.to_vec(),
+            }
+        }
+
+        fn chance_inference(&self, afterstate: &[f32], chance_code: usize) -> NetworkOutput {
+            let reward = match (afterstate.is_empty(), chance_code) {
+                (false, _) => 0.,
+                (true, 0) => 1.,
+                (true, _) => -1.,
+            };
+            NetworkOutput {
+                value: 0.,
+                reward,
+                policy_logits: vec![0.; 2],
+                hidden_state: vec![1.],
+            }
+        }
+
+        fn encode_chance(&self, observation: &[f32]) -> usize {
+            observation[0] as usize
+        }
+    }
+
+    fn single_player_config(action_space_size: usize, num_simulations: usize) -> MuZeroConfig {
+        MuZeroConfig {
+            num_players: 1,
+            num_simulations,
+            known_bounds: None,
+            ..MuZeroConfig::board_game(action_space_size, 0.3)
+        }
+    }
+
+    #[test]
+    fn test_uniform_network() {
+        let config = MuZeroConfig {
+            num_simulations: 50,
+            ..MuZeroConfig::board_game(9, 0.3)
+        };
+        let network = UniformNetwork {
+            action_space_size: 9,
+        };
+        let stats = run_mcts(&config, &network, &[], &[0, 4, 8], 0);
+        assert_eq!(stats.visit_counts.iter().sum::<usize>(), 50);
+        for (action, &visits) in stats.visit_counts.iter().enumerate() {
+            if ![0, 4, 8].contains(&action) {
+                assert_eq!(visits, 0);
+            }
+        }
+        assert!((stats.policy().iter().sum::<f32>() - 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_search_follows_rewards() {
+        let config = single_player_config(4, 100);
+        let stats = run_mcts(&config, &FirstActionNetwork, &[], &[0, 1, 2, 3], 0);
+        assert_eq!(stats.select_action(0.), 2);
+        assert!(stats.root_value > 0.5);
+    }
+
+    #[test]
+    fn test_two_player_perspective() {
+        let config = MuZeroConfig {
+            num_simulations: 100,
+            ..MuZeroConfig::board_game(2, 0.3)
+        };
+        let stats = run_mcts(&config, &PoisonedOpeningNetwork, &[], &[0, 1], 0);
+        assert_eq!(stats.select_action(0.), 1);
+    }
+
+    /// Predicts the value prefix of [`FirstActionNetwork`]: 1 once the first action was 2.
+    struct FirstActionPrefixNetwork;
+
+    impl Network for FirstActionPrefixNetwork {
+        fn initial_inference(&self, observation: &[f32]) -> NetworkOutput {
+            FirstActionNetwork.initial_inference(observation)
+        }
+
+        fn recurrent_inference(&self, hidden_state: &[f32], action: usize) -> NetworkOutput {
+            let output = FirstActionNetwork.recurrent_inference(hidden_state, action);
+            NetworkOutput {
+                reward: if output.hidden_state == [2.] { 1. } else { 0. },
+                ..output
+            }
+        }
+    }
+
+    #[test]
+    fn test_value_prefix() {
+        let config = MuZeroConfig {
+            value_prefix_horizon: Some(100),
+            ..single_player_config(4, 200)
+        };
+        let stats = run_mcts(&config, &FirstActionPrefixNetwork, &[], &[0, 1, 2, 3], 0);
+        assert_eq!(stats.select_action(0.), 2);
+        assert!(
+            stats.root_value > 0.5 && stats.root_value <= 1.,
+            "{}",
+            stats.root_value
+        );
+        // Resetting every step, the prefix counts as a reward again at every step.
+        let config = MuZeroConfig {
+            value_prefix_horizon: Some(1),
+            ..config
+        };
+        let stats = run_mcts(&config, &FirstActionPrefixNetwork, &[], &[0, 1, 2, 3], 0);
+        assert!(stats.root_value > 1.5, "{}", stats.root_value);
+    }
+
+    /// Action 0 ends the episode with a reward of 1, into a state it values at 5. Action 1
+    /// leads to a state worth 0.5.
+    struct EndingNetwork;
+
+    impl Network for EndingNetwork {
+        fn initial_inference(&self, _observation: &[f32]) -> NetworkOutput {
+            NetworkOutput {
+                value: 0.,
+                reward: 0.,
+                policy_logits: vec![0.; 2],
+                hidden_state: vec![0.],
+            }
+        }
+
+        fn recurrent_inference(&self, hidden_state: &[f32], action: usize) -> NetworkOutput {
+            let ended = hidden_state == [1.] || action == 0;
+            NetworkOutput {
+                value: if ended { 5. } else { 0.5 },
+                reward: if ended && hidden_state == [0.] {
+                    1.
+                } else {
+                    0.
+                },
+                policy_logits: vec![0.; 2],
+                hidden_state: vec![if ended { 1. } else { 2. }],
+            }
+        }
+
+        fn is_absorbing(&self, hidden_state: &[f32]) -> bool {
+            hidden_state == [1.]
+        }
+    }
+
+    #[test]
+    fn test_absorbing_states() {
+        let config = single_player_config(2, 100);
+        let stats = run_mcts(&config, &EndingNetwork, &[], &[0, 1], 0);
+        assert_eq!(stats.select_action(0.), 0);
+        assert!((stats.root_value - 1.).abs() < 0.1, "{}", stats.root_value);
+    }
+
+    /// [`FirstActionNetwork`], recording the sizes of the batches it evaluates.
+    struct BatchRecordingNetwork {
+        batches: Mutex<Vec<usize>>,
+    }
+
+    impl Network for BatchRecordingNetwork {
+        fn initial_inference(&self, observation: &[f32]) -> NetworkOutput {
+            FirstActionNetwork.initial_inference(observation)
+        }
+
+        fn recurrent_inference(&self, hidden_state: &[f32], action: usize) -> NetworkOutput {
+            FirstActionNetwork.recurrent_inference(hidden_state, action)
+        }
+
+        fn recurrent_inference_batch(&self, inputs: &[(&[f32], usize)]) -> Vec<NetworkOutput> {
+            self.batches.lock().unwrap().push(inputs.len());
+            FirstActionNetwork.recurrent_inference_batch(inputs)
+        }
+    }
+
+    #[test]
+    fn test_leaf_batches() {
+        let network = BatchRecordingNetwork {
+            batches: Mutex::new(vec![]),
+        };
+        let config = MuZeroConfig {
+            leaf_batch_size: 8,
+            ..single_player_config(4, 100)
+        };
+        let stats = run_mcts(&config, &network, &[], &[0, 1, 2, 3], 0);
+        assert_eq!(stats.visit_counts.iter().sum::<usize>(), 100);
+        assert_eq!(stats.select_action(0.), 2);
+        let batches = network.batches.lock().unwrap();
+        // 12 full batches and a last one of 4 simulations, with a leaf reached twice in a
+        // batch evaluated once.
+        assert_eq!(batches.len(), 13);
+        assert!(batches.iter().all(|&size| size > 0 && size <= 8));
+        assert!(batches[0] > 1, "{:?}", batches);
+        assert!(batches[12] <= 4);
+
+        // The virtual loss spreads the first batch over the root's children.
+        let config = MuZeroConfig {
+            leaf_batch_size: 4,
+            root_exploration_fraction: 0.,
+            ..single_player_config(4, 4)
+        };
+        let network = UniformNetwork {
+            action_space_size: 4,
+        };
+        let stats = run_mcts(&config, &network, &[], &[0, 1, 2, 3], 0);
+        assert_eq!(stats.visit_counts, [1; 4]);
+    }
+
+    #[test]
+    fn test_overlap_evaluation() {
+        for leaf_batch_size in [1, 4] {
+            let config = MuZeroConfig {
+                leaf_batch_size,
+                overlap_evaluation: true,
+                ..single_player_config(4, 100)
+            };
+            let stats = run_mcts(&config, &FirstActionNetwork, &[], &[0, 1, 2, 3], 0);
+            assert_eq!(stats.visit_counts.iter().sum::<usize>(), 100);
+            assert_eq!(stats.select_action(0.), 2);
+        }
+        let config = MuZeroConfig {
+            leaf_batch_size: 4,
+            overlap_evaluation: true,
+            ..single_player_config(2, 400)
+        };
+        let stats = run_stochastic_mcts(&config, &CoinNetwork, &[], &[0, 1], 0);
+        assert_eq!(stats.select_action(0.), 1);
+    }
+
+    #[test]
+    fn test_first_play_urgency() {
+        let network = UniformNetwork {
+            action_space_size: 4,
+        };
+        let config = MuZeroConfig {
+            first_play_urgency: FirstPlayUrgency::Infinite,
+            ..single_player_config(4, 4)
+        };
+        let stats = run_mcts(&config, &network, &[], &[0, 1, 2, 3], 0);
+        assert_eq!(stats.visit_counts, [1; 4]);
+
+        for first_play_urgency in [
+            FirstPlayUrgency::Parent { reduction: 0.2 },
+            FirstPlayUrgency::Value(1.),
+        ] {
+            let config = MuZeroConfig {
+                first_play_urgency,
+                ..single_player_config(4, 100)
+            };
+            let stats = run_mcts(&config, &FirstActionNetwork, &[], &[0, 1, 2, 3], 0);
+            assert_eq!(stats.select_action(0.), 2);
+        }
+    }
+
+    #[test]
+    fn test_batched_stochastic_search() {
+        let config = MuZeroConfig {
+            leaf_batch_size: 8,
+            ..single_player_config(2, 400)
+        };
+        let stats = run_stochastic_mcts(&config, &CoinNetwork, &[], &[0, 1], 0);
+        assert_eq!(stats.visit_counts.iter().sum::<usize>(), 400);
+        assert_eq!(stats.select_action(0.), 1);
+    }
+
+    #[test]
+    fn test_stochastic_search() {
+        let config = single_player_config(2, 400);
+        let stats = run_stochastic_mcts(&config, &CoinNetwork, &[], &[0, 1], 0);
+        assert_eq!(stats.select_action(0.), 1);
+        // Expected reward of the best action is 0.9 - 0.1.
+        assert!((stats.root_value - 0.8).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_dirichlet() {
+        let noise = sample_dirichlet(0.3, 9);
+        assert_eq!(noise.len(), 9);
+        assert!((noise.iter().sum::<f32>() - 1.).abs() < 1e-5);
+        assert!(noise.iter().all(|&x| x >= 0.));
+    }
+}
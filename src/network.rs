@@ -0,0 +1,267 @@
+//! The networks the MuZero search evaluates states with.
+
+use std::sync::mpsc::Receiver;
+
+use crate::cache::CacheStats;
+
+/// The output of a network inference, as in the MuZero pseudocode.
+#[derive(Debug, Clone)]
+pub struct NetworkOutput {
+    pub value: f32,
+    pub reward: f32,
+    /// One logit per action index of the game's action space.
+    pub policy_logits: Vec<f32>,
+    pub hidden_state: Vec<f32>,
+}
+
+/// The MuZero model: a representation function `h`, a dynamics function `g` and a
+/// prediction function `f`, all operating on flat `f32` states.
+pub trait Network {
+    /// `f(h(observation))`: the root of a search.
+    fn initial_inference(&self, observation: &[f32]) -> NetworkOutput;
+
+    /// `f(g(hidden_state, action))`: one step inside the search tree.
+    fn recurrent_inference(&self, hidden_state: &[f32], action: usize) -> NetworkOutput;
+
+    /// Whether `hidden_state` is an absorbing state past the end of the episode, e.g. as a
+    /// terminal head predicts. The search doesn't look past such states and values them at
+    /// zero, so the value predicted for them can't leak into the search.
+    fn is_absorbing(&self, _hidden_state: &[f32]) -> bool {
+        false
+    }
+
+    /// The lookups of the cache in front of the network, if there's one, see
+    /// [`crate::cache::CachedNetwork`].
+    fn cache_stats(&self) -> Option<CacheStats> {
+        None
+    }
+
+    /// `initial_inference` of many observations at once. Networks running on accelerators
+    /// override this with a single forward pass.
+    fn initial_inference_batch(&self, observations: &[&[f32]]) -> Vec<NetworkOutput> {
+        observations
+            .iter()
+            .map(|observation| self.initial_inference(observation))
+            .collect()
+    }
+
+    /// `recurrent_inference` of many `(hidden_state, action)` pairs at once.
+    fn recurrent_inference_batch(&self, inputs: &[(&[f32], usize)]) -> Vec<NetworkOutput> {
+        inputs
+            .iter()
+            .map(|&(hidden_state, action)| self.recurrent_inference(hidden_state, action))
+            .collect()
+    }
+
+    /// Start `recurrent_inference_batch` without waiting for its outputs, so that the caller
+    /// can go on while e.g. an inference server evaluates. Evaluates right away by default.
+    fn submit_recurrent_batch(&self, inputs: &[(&[f32], usize)]) -> Vec<Pending> {
+        self.recurrent_inference_batch(inputs)
+            .into_iter()
+            .map(Pending::Ready)
+            .collect()
+    }
+}
+
+/// The output of an evaluation which may still be running.
+pub enum Pending {
+    Ready(NetworkOutput),
+    /// Sent once the evaluation is done.
+    Receiving(Receiver<NetworkOutput>),
+}
+
+impl Pending {
+    /// Block until the output is there.
+    pub fn wait(self) -> NetworkOutput {
+        match self {
+            Pending::Ready(output) => output,
+            Pending::Receiving(receiver) => receiver.recv().expect("the evaluation was dropped"),
+        }
+    }
+}
+
+/// A network with the heads of the self-supervised consistency loss of EfficientZero (Ye et
+/// al., 2021), which pulls the hidden state the dynamics predicts towards the representation
+/// of the observation that actually followed.
+pub trait ConsistencyNetwork: Network {
+    /// The projector, mapping a hidden state to the space the loss compares in.
+    fn project(&self, hidden_state: &[f32]) -> Vec<f32>;
+
+    /// The predictor, applied to the projections of predicted hidden states only, as in
+    /// SimSiam.
+    fn predict_projection(&self, projection: &[f32]) -> Vec<f32>;
+}
+
+/// The output of the afterstate prediction function of Stochastic MuZero.
+#[derive(Debug, Clone)]
+pub struct AfterstateOutput {
+    /// The value of the afterstate, from the perspective of the player who just acted.
+    pub value: f32,
+    /// One logit per chance code.
+    pub chance_logits: Vec<f32>,
+    pub afterstate: Vec<f32>,
+}
+
+/// The Stochastic MuZero model (Antonoglou et al., 2022).
+///
+/// A transition is split into a deterministic part, from a state to an afterstate given the
+/// action, and a stochastic part, from an afterstate to the next state given a chance code.
+/// The chance codes are learned, so the environment's randomness never has to be modelled
+/// explicitly.
+pub trait StochasticNetwork {
+    fn num_chance_codes(&self) -> usize;
+
+    /// `f(h(observation))`: the root of a search.
+    fn initial_inference(&self, observation: &[f32]) -> NetworkOutput;
+
+    /// `ψ(φ(hidden_state, action))`: the afterstate dynamics followed by the afterstate prediction.
+    fn afterstate_inference(&self, hidden_state: &[f32], action: usize) -> AfterstateOutput;
+
+    /// `f(g(afterstate, chance_code))`: the dynamics from an afterstate followed by the prediction.
+    fn chance_inference(&self, afterstate: &[f32], chance_code: usize) -> NetworkOutput;
+
+    /// The encoder `e`: the chance code explaining the transition to `observation`,
+    /// used as the target of the chance distribution during training.
+    fn encode_chance(&self, observation: &[f32]) -> usize;
+}
+
+/// A network that knows nothing: uniform policy, zero value and reward.
+pub struct UniformNetwork {
+    pub action_space_size: usize,
+}
+
+impl UniformNetwork {
+    fn output(&self) -> NetworkOutput {
+        NetworkOutput {
+            value: 0.,
+            reward: 0.,
+            policy_logits: vec![0.; self.action_space_size],
+            hidden_state: vec![],
+        }
+    }
+}
+
+impl Network for UniformNetwork {
+    fn initial_inference(&self, _observation: &[f32]) -> NetworkOutput {
+        self.output()
+    }
+
+    fn recurrent_inference(&self, _hidden_state: &[f32], _action: usize) -> NetworkOutput {
+        self.output()
+    }
+}
+
+pub fn softmax(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let exps: Vec<f32> = logits.iter().map(|logit| (logit - max).exp()).collect();
+    let sum: f32 = exps.iter().sum();
+    exps.into_iter().map(|exp| exp / sum).collect()
+}
+
+/// The categorical representation of values and rewards of MuZero (Appendix F): scalars are
+/// squashed by `h(x) = sign(x)(sqrt(|x| + 1) - 1) + εx` and spread over the two neighbouring
+/// integers of the support `-size..=size`, so networks predict them with a softmax and are
+/// trained with a cross-entropy loss.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Support {
+    pub size: usize,
+}
+
+impl Support {
+    /// The ε of the transform, keeping it invertible and Lipschitz.
+    const EPSILON: f32 = 0.001;
+
+    /// The support of the paper for Atari: 601 integers from -300 to 300.
+    pub fn atari() -> Self {
+        Self { size: 300 }
+    }
+
+    /// The number of categories, so of logits of the heads.
+    pub fn num_categories(&self) -> usize {
+        2 * self.size + 1
+    }
+
+    pub fn transform(x: f32) -> f32 {
+        x.signum() * ((x.abs() + 1.).sqrt() - 1.) + Self::EPSILON * x
+    }
+
+    pub fn inverse_transform(y: f32) -> f32 {
+        let eps = Self::EPSILON;
+        let root = ((1. + 4. * eps * (y.abs() + 1. + eps)).sqrt() - 1.) / (2. * eps);
+        y.signum() * (root * root - 1.)
+    }
+
+    /// The target distribution of `x`: the two integers around `h(x)`, weighted so their
+    /// mean is `h(x)`. Values beyond the support are clamped to its ends.
+    pub fn encode(&self, x: f32) -> Vec<f32> {
+        let size = self.size as f32;
+        let y = Self::transform(x).clamp(-size, size);
+        let low = y.floor();
+        let high_weight = y - low;
+        let index = (low + size) as usize;
+        let mut probs = vec![0.; self.num_categories()];
+        probs[index] = 1. - high_weight;
+        if high_weight > 0. {
+            probs[index + 1] = high_weight;
+        }
+        probs
+    }
+
+    /// The scalar a distribution over the support stands for: `h⁻¹` of its mean.
+    pub fn decode(&self, probs: &[f32]) -> f32 {
+        let mean: f32 = probs
+            .iter()
+            .enumerate()
+            .map(|(i, p)| p * (i as f32 - self.size as f32))
+            .sum();
+        Self::inverse_transform(mean)
+    }
+
+    /// The scalar of a network's logits over the support.
+    pub fn decode_logits(&self, logits: &[f32]) -> f32 {
+        self.decode(&softmax(logits))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transform() {
+        assert_eq!(Support::transform(0.), 0.);
+        assert!((Support::transform(3.) - 1.003).abs() < 1e-6);
+        assert!((Support::transform(-3.) + 1.003).abs() < 1e-6);
+        for x in [-5000., -12.5, -1., 0., 0.3, 7., 1e5] {
+            let roundtrip = Support::inverse_transform(Support::transform(x));
+            assert!((roundtrip - x).abs() <= 1e-3 * x.abs().max(1.), "{}", x);
+        }
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let support = Support { size: 10 };
+        assert_eq!(support.num_categories(), 21);
+        assert_eq!(Support::atari().num_categories(), 601);
+        let probs = support.encode(3.);
+        // h(3) = 1.003
+        assert!((probs[11] - 0.997).abs() < 1e-4);
+        assert!((probs[12] - 0.003).abs() < 1e-4);
+        assert!((probs.iter().sum::<f32>() - 1.).abs() < 1e-6);
+        for x in [-50., -1., 0., 0.5, 8., 99.] {
+            assert!(
+                (support.decode(&support.encode(x)) - x).abs() < 1e-2,
+                "{}",
+                x
+            );
+        }
+        // Clamped to the end of the support.
+        assert_eq!(support.encode(1e6)[20], 1.);
+        assert_eq!(
+            support.decode(&support.encode(1e6)),
+            Support::inverse_transform(10.)
+        );
+        let logits: Vec<f32> = support.encode(5.).iter().map(|p| p.ln()).collect();
+        assert!((support.decode_logits(&logits) - 5.).abs() < 1e-2);
+    }
+}
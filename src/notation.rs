@@ -0,0 +1,117 @@
+//! A compact notation for board positions, like FEN in chess, for [`Game::to_notation`] and
+//! [`Game::from_notation`]: the rows from the top, separated by `/`, each cell the symbol of
+//! [`fmt::Display`](std::fmt::Display) or a number for a run of empty cells, then a space and
+//! the symbol of the player to move. `X1O/1X1/3 O` is a tic-tac-toe position with O to move.
+//!
+//! [`Game::to_notation`]: crate::Game::to_notation
+//! [`Game::from_notation`]: crate::Game::from_notation
+
+use crate::error::ParseError;
+
+/// The notation of `cells`, row by row, `'.'` for an empty one.
+pub fn write(cells: &[char], width: usize, to_play: char) -> String {
+    let rows: Vec<String> = cells
+        .chunks(width)
+        .map(|row| {
+            let mut text = String::new();
+            let mut empty = 0;
+            for &cell in row {
+                if cell == '.' {
+                    empty += 1;
+                    continue;
+                }
+                if empty > 0 {
+                    text += &empty.to_string();
+                    empty = 0;
+                }
+                text.push(cell);
+            }
+            if empty > 0 {
+                text += &empty.to_string();
+            }
+            text
+        })
+        .collect();
+    format!("{} {}", rows.join("/"), to_play)
+}
+
+/// The cells of a board of `height` rows of `width` from `notation`, row by row, `'.'` for an
+/// empty one, and the player to move. The symbols are left to the game to check.
+pub fn parse(notation: &str, height: usize, width: usize) -> Result<(Vec<char>, char), ParseError> {
+    let (board, to_play) = notation.trim().split_once(' ').ok_or_else(|| {
+        ParseError::Invalid(
+            "expected the rows and the player to move, separated by a space".to_string(),
+        )
+    })?;
+    let mut to_play = to_play.trim().chars();
+    let (Some(to_play), None) = (to_play.next(), to_play.next()) else {
+        return Err(ParseError::Invalid(
+            "expected one symbol for the player to move".to_string(),
+        ));
+    };
+    let rows: Vec<_> = board.split('/').collect();
+    if rows.len() != height {
+        return Err(ParseError::Invalid(format!(
+            "expected {} rows, found {}",
+            height,
+            rows.len()
+        )));
+    }
+    let mut cells = Vec::with_capacity(height * width);
+    for row in rows {
+        let start = cells.len();
+        let mut chars = row.chars().peekable();
+        while let Some(c) = chars.next() {
+            let Some(digit) = c.to_digit(10) else {
+                cells.push(c);
+                continue;
+            };
+            let mut empty = digit as usize;
+            while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                empty = empty * 10 + digit as usize;
+                chars.next();
+            }
+            if empty == 0 {
+                return Err(ParseError::Invalid(format!(
+                    "empty runs can't be 0 in `{}`",
+                    row
+                )));
+            }
+            cells.extend(std::iter::repeat_n('.', empty.min(width + 1)));
+        }
+        if cells.len() - start != width {
+            return Err(ParseError::Invalid(format!(
+                "expected {} cells in row `{}`",
+                width, row
+            )));
+        }
+    }
+    Ok((cells, to_play))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notation() {
+        let cells: Vec<char> = "X.O.X....".chars().collect();
+        let notation = write(&cells, 3, 'O');
+        assert_eq!(notation, "X1O/1X1/3 O");
+        assert_eq!(parse(&notation, 3, 3).unwrap(), (cells, 'O'));
+
+        let cells: Vec<char> = format!("{}X", ".".repeat(14)).chars().collect();
+        assert_eq!(write(&cells, 15, 'X'), "14X X");
+        assert_eq!(parse("14X X", 1, 15).unwrap().0, cells);
+
+        for notation in [
+            "X1O/1X1/3",
+            "X1O/1X1 O",
+            "X1O/1X1/4 O",
+            "X1O/1X1/3 OX",
+            "X0/3/3 O",
+        ] {
+            assert!(parse(notation, 3, 3).is_err(), "{}", notation);
+        }
+    }
+}
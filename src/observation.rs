@@ -0,0 +1,157 @@
+//! The input of the representation network: the last observations together with the actions
+//! that led to them, as MuZero stacks them for Atari and board games.
+
+use std::collections::VecDeque;
+
+use crate::history::GameHistory;
+
+/// How observations are stacked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameStacking {
+    /// The number of observations and actions stacked, K.
+    pub frames: usize,
+    /// The number of planes of an observation, its first dimension; the rest are spatial.
+    pub planes: usize,
+}
+
+impl FrameStacking {
+    /// The shape of the stacked input of observations of `observation_shape`:
+    /// `K * (planes + 1)` planes, as every frame gets a plane for its action.
+    pub fn stacked_shape(&self, observation_shape: &[usize]) -> Vec<usize> {
+        let mut shape = observation_shape.to_vec();
+        shape[0] = self.frames * (shape[0] + 1);
+        shape
+    }
+}
+
+/// Keeps the last K observations and actions of a game, and stacks them, oldest first: the
+/// planes of each observation followed by a plane holding its action. An action plane is
+/// filled with `(action + 1) / action_space_size`, and the planes of frames before the start
+/// of the game are zeros.
+#[derive(Debug, Clone)]
+pub struct ObservationStacker {
+    stacking: FrameStacking,
+    action_space_size: usize,
+    /// The last observations and the actions that led to them, `None` for the first.
+    frames: VecDeque<(Vec<f32>, Option<usize>)>,
+}
+
+impl ObservationStacker {
+    pub fn new(stacking: FrameStacking, action_space_size: usize) -> Self {
+        Self {
+            stacking,
+            action_space_size,
+            frames: VecDeque::with_capacity(stacking.frames),
+        }
+    }
+
+    /// Start a game at `observation`.
+    pub fn reset(&mut self, observation: Vec<f32>) {
+        self.frames.clear();
+        self.frames.push_back((observation, None));
+    }
+
+    /// Record that `action` led to `observation`.
+    pub fn push(&mut self, action: usize, observation: Vec<f32>) {
+        if self.frames.len() == self.stacking.frames {
+            self.frames.pop_front();
+        }
+        self.frames.push_back((observation, Some(action)));
+    }
+
+    /// The stacked input of the latest observation.
+    pub fn stacked(&self) -> Vec<f32> {
+        let Some((latest, _)) = self.frames.back() else {
+            return vec![];
+        };
+        let plane_len = latest.len() / self.stacking.planes.max(1);
+        let frame_len = latest.len() + plane_len;
+        let mut stacked = vec![0.; self.stacking.frames * frame_len];
+        let missing = self.stacking.frames - self.frames.len();
+        for (i, (observation, action)) in self.frames.iter().enumerate() {
+            let frame = &mut stacked[(missing + i) * frame_len..][..frame_len];
+            frame[..observation.len()].copy_from_slice(observation);
+            if let Some(action) = action {
+                let value = (action + 1) as f32 / self.action_space_size as f32;
+                frame[observation.len()..].fill(value);
+            }
+        }
+        stacked
+    }
+
+    /// The stacked input of position `index` of a recorded game.
+    pub fn from_history(
+        history: &GameHistory,
+        index: usize,
+        stacking: FrameStacking,
+        action_space_size: usize,
+    ) -> Vec<f32> {
+        let mut stacker = Self::new(stacking, action_space_size);
+        let first = (index + 1).saturating_sub(stacking.frames);
+        stacker.reset(history.observations[first].clone());
+        if first > 0 {
+            stacker.frames[0].1 = Some(history.actions[first - 1]);
+        }
+        for i in first + 1..=index {
+            stacker.push(history.actions[i - 1], history.observations[i].clone());
+        }
+        stacker.stacked()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stacker() {
+        let stacking = FrameStacking {
+            frames: 3,
+            planes: 1,
+        };
+        assert_eq!(stacking.stacked_shape(&[1, 1, 2]), [6, 1, 2]);
+        let mut stacker = ObservationStacker::new(stacking, 4);
+        stacker.reset(vec![1., 2.]);
+        assert_eq!(
+            stacker.stacked(),
+            [0., 0., 0., 0., 0., 0., 0., 0., 1., 2., 0., 0.]
+        );
+        stacker.push(1, vec![3., 4.]);
+        stacker.push(3, vec![5., 6.]);
+        stacker.push(0, vec![7., 8.]);
+        assert_eq!(
+            stacker.stacked(),
+            [3., 4., 0.5, 0.5, 5., 6., 1., 1., 7., 8., 0.25, 0.25]
+        );
+    }
+
+    #[test]
+    fn test_from_history() {
+        let history = GameHistory {
+            observations: vec![vec![1.], vec![2.], vec![3.]],
+            actions: vec![0, 1, 0],
+            ..Default::default()
+        };
+        let stacking = FrameStacking {
+            frames: 2,
+            planes: 1,
+        };
+        assert_eq!(
+            ObservationStacker::from_history(&history, 0, stacking, 2),
+            [0., 0., 1., 0.]
+        );
+        assert_eq!(
+            ObservationStacker::from_history(&history, 2, stacking, 2),
+            [2., 0.5, 3., 1.]
+        );
+        let stacking = FrameStacking {
+            frames: 1,
+            planes: 1,
+        };
+        // The action that led to the frame is kept even when the frame before isn't.
+        assert_eq!(
+            ObservationStacker::from_history(&history, 1, stacking, 2),
+            [2., 0.5]
+        );
+    }
+}
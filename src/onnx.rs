@@ -0,0 +1,327 @@
+//! Inference with models exported to ONNX, e.g. from PyTorch, behind the `onnx` feature, so
+//! that the search can play with them without Python. A model is a directory of the three
+//! functions of MuZero, run with tract:
+//!
+//! - `representation.onnx` takes the observation, shaped `[1, ...observation shape]`, to the
+//!   hidden state, shaped as the model likes.
+//! - `dynamics.onnx` takes the hidden state and the action, one-hot `[1, action space size]`,
+//!   to the next hidden state and the reward.
+//! - `prediction.onnx` takes the hidden state to the policy logits `[1, action space size]`
+//!   and the value.
+//!
+//! Values and rewards are scalars `[1, 1]`, or logits `[1, 2 * size + 1]` over a
+//! categorical [`Support`].
+
+use std::path::Path;
+use tract_onnx::prelude::{
+    Datum, Framework, InferenceFact, InferenceModel, InferenceModelExt, Tensor, TypedModel,
+    TypedRunnableModel,
+};
+
+use crate::{
+    error::{ConfigError, Context, Error, Result},
+    network::{Network, NetworkOutput, Support},
+};
+
+const REPRESENTATION: &str = "representation.onnx";
+const DYNAMICS: &str = "dynamics.onnx";
+const PREDICTION: &str = "prediction.onnx";
+
+type Plan = TypedRunnableModel<TypedModel>;
+
+/// A MuZero model exported to ONNX, as the [`Network`] the search evaluates with.
+pub struct OnnxEvaluator {
+    representation: Plan,
+    dynamics: Plan,
+    prediction: Plan,
+    observation_shape: Vec<usize>,
+    hidden_shape: Vec<usize>,
+    action_space_size: usize,
+}
+
+impl OnnxEvaluator {
+    /// Load the model in `dir` for a game with these observations and actions.
+    pub fn load(dir: &Path, observation_shape: &[usize], action_space_size: usize) -> Result<Self> {
+        let read = |name: &str| {
+            let path = dir.join(name);
+            tract_onnx::onnx()
+                .model_for_path(&path)
+                .map_err(Error::backend)
+                .with_context(|| format!("failed to load {}", path.display()))
+        };
+        Self::new(
+            read(REPRESENTATION)?,
+            read(DYNAMICS)?,
+            read(PREDICTION)?,
+            observation_shape,
+            action_space_size,
+        )
+    }
+
+    /// Optimize the three functions for a game with these observations and actions, checking
+    /// that their inputs and outputs fit together.
+    pub fn new(
+        representation: InferenceModel,
+        dynamics: InferenceModel,
+        prediction: InferenceModel,
+        observation_shape: &[usize],
+        action_space_size: usize,
+    ) -> Result<Self> {
+        let input: Vec<_> = [1].iter().chain(observation_shape).copied().collect();
+        let representation =
+            optimize(representation, &[&input], 1).context("in the representation")?;
+        let hidden_shape = output_shape(&representation, 0)?;
+        let action = [1, action_space_size];
+        let dynamics =
+            optimize(dynamics, &[&hidden_shape, &action], 2).context("in the dynamics")?;
+        if output_shape(&dynamics, 0)? != hidden_shape {
+            return Err(ConfigError::Invalid(
+                "the dynamics don't keep the shape of the hidden state".to_string(),
+            )
+            .into());
+        }
+        let prediction = optimize(prediction, &[&hidden_shape], 2).context("in the prediction")?;
+        if output_shape(&prediction, 0)? != action {
+            return Err(ConfigError::Invalid(format!(
+                "the policy must have {} logits",
+                action_space_size
+            ))
+            .into());
+        }
+        Ok(Self {
+            representation,
+            dynamics,
+            prediction,
+            observation_shape: observation_shape.to_vec(),
+            hidden_shape,
+            action_space_size,
+        })
+    }
+
+    fn predict(&self, hidden_state: Vec<f32>, reward: f32) -> NetworkOutput {
+        let outputs = run(&self.prediction, &[(&self.hidden_shape, &hidden_state)]);
+        NetworkOutput {
+            value: scalar(&outputs[1]),
+            reward,
+            policy_logits: outputs[0].clone(),
+            hidden_state,
+        }
+    }
+}
+
+impl Network for OnnxEvaluator {
+    fn initial_inference(&self, observation: &[f32]) -> NetworkOutput {
+        let input: Vec<_> = [1].iter().chain(&self.observation_shape).copied().collect();
+        let mut outputs = run(&self.representation, &[(&input, observation)]);
+        self.predict(outputs.swap_remove(0), 0.)
+    }
+
+    fn recurrent_inference(&self, hidden_state: &[f32], action: usize) -> NetworkOutput {
+        let mut one_hot = vec![0.; self.action_space_size];
+        one_hot[action] = 1.;
+        let mut outputs = run(
+            &self.dynamics,
+            &[
+                (&self.hidden_shape, hidden_state),
+                (&[1, self.action_space_size], &one_hot),
+            ],
+        );
+        let reward = scalar(&outputs[1]);
+        self.predict(outputs.swap_remove(0), reward)
+    }
+}
+
+/// `model` with inputs of these shapes, optimized to run, if it has `outputs` outputs.
+fn optimize(model: InferenceModel, inputs: &[&[usize]], outputs: usize) -> Result<Plan> {
+    let mut model = model;
+    if model.input_outlets().map_err(Error::backend)?.len() != inputs.len() {
+        return Err(ConfigError::Invalid(format!("expected {} inputs", inputs.len())).into());
+    }
+    if model.output_outlets().map_err(Error::backend)?.len() != outputs {
+        return Err(ConfigError::Invalid(format!("expected {} outputs", outputs)).into());
+    }
+    for (i, shape) in inputs.iter().enumerate() {
+        model = model
+            .with_input_fact(i, InferenceFact::dt_shape(f32::datum_type(), *shape))
+            .map_err(Error::backend)?;
+    }
+    model
+        .into_optimized()
+        .and_then(|model| model.into_runnable())
+        .map_err(Error::backend)
+}
+
+fn output_shape(plan: &Plan, output: usize) -> Result<Vec<usize>> {
+    let fact = plan.model().output_fact(output).map_err(Error::backend)?;
+    fact.shape
+        .as_concrete()
+        .map(<[usize]>::to_vec)
+        .ok_or_else(|| ConfigError::Invalid(format!("output {} has no fixed shape", output)).into())
+}
+
+/// The flat outputs of `plan` on flat inputs of the given shapes, which `new` checked.
+fn run(plan: &Plan, inputs: &[(&[usize], &[f32])]) -> Vec<Vec<f32>> {
+    let inputs = inputs
+        .iter()
+        .map(|(shape, data)| {
+            Tensor::from_shape(shape, data)
+                .expect("the input fits its shape")
+                .into()
+        })
+        .collect();
+    plan.run(inputs)
+        .expect("the ONNX model failed")
+        .iter()
+        .map(|output| output.as_slice::<f32>().expect("outputs are f32").to_vec())
+        .collect()
+}
+
+/// The scalar of a value or reward head, a single number or logits over a support.
+fn scalar(output: &[f32]) -> f32 {
+    match output {
+        [x] => *x,
+        logits => Support {
+            size: logits.len() / 2,
+        }
+        .decode_logits(logits),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::muzero::{run_mcts, MuZeroConfig};
+    use tract_onnx::pb::{
+        tensor_proto::DataType, type_proto, GraphProto, ModelProto, NodeProto, OperatorSetIdProto,
+        TensorProto, TypeProto, ValueInfoProto,
+    };
+
+    fn node(op_type: &str, input: &[&str], output: &str) -> NodeProto {
+        NodeProto {
+            op_type: op_type.to_string(),
+            input: input.iter().map(|name| name.to_string()).collect(),
+            output: vec![output.to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn constant(name: &str, dims: &[i64], values: &[f32]) -> TensorProto {
+        TensorProto {
+            name: name.to_string(),
+            dims: dims.to_vec(),
+            data_type: DataType::Float as i32,
+            float_data: values.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    /// A model of `nodes` with the inputs and outputs of these names, whose shapes are left to
+    /// the evaluator.
+    fn model(
+        inputs: &[&str],
+        outputs: &[&str],
+        nodes: Vec<NodeProto>,
+        initializer: Vec<TensorProto>,
+    ) -> InferenceModel {
+        let value = |name: &&str| ValueInfoProto {
+            name: name.to_string(),
+            r#type: Some(TypeProto {
+                value: Some(type_proto::Value::TensorType(type_proto::Tensor {
+                    elem_type: DataType::Float as i32,
+                    shape: None,
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let proto = ModelProto {
+            ir_version: 7,
+            opset_import: vec![OperatorSetIdProto {
+                domain: String::new(),
+                version: 13,
+            }],
+            graph: Some(GraphProto {
+                node: nodes,
+                initializer,
+                input: inputs.iter().map(value).collect(),
+                output: outputs.iter().map(value).collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        tract_onnx::onnx().model_for_proto_model(&proto).unwrap()
+    }
+
+    /// The representation, dynamics and prediction of a game with two actions: the hidden
+    /// state is twice the observation and each action adds to it, with a reward of its
+    /// index. The policy is the hidden state and the value its sum.
+    fn models() -> (InferenceModel, InferenceModel, InferenceModel) {
+        let representation = model(
+            &["observation"],
+            &["hidden"],
+            vec![node("Mul", &["observation", "two"], "hidden")],
+            vec![constant("two", &[1], &[2.])],
+        );
+        let dynamics = model(
+            &["hidden", "action"],
+            &["next", "reward"],
+            vec![
+                node("Add", &["hidden", "action"], "next"),
+                node("MatMul", &["action", "indices"], "reward"),
+            ],
+            vec![constant("indices", &[2, 1], &[0., 1.])],
+        );
+        let prediction = model(
+            &["hidden"],
+            &["policy", "value"],
+            vec![
+                node("Identity", &["hidden"], "policy"),
+                node("MatMul", &["hidden", "ones"], "value"),
+            ],
+            vec![constant("ones", &[2, 1], &[1., 1.])],
+        );
+        (representation, dynamics, prediction)
+    }
+
+    #[test]
+    fn test_inference() {
+        let (representation, dynamics, prediction) = models();
+        let network = OnnxEvaluator::new(representation, dynamics, prediction, &[2], 2).unwrap();
+        let output = network.initial_inference(&[1., 2.]);
+        assert_eq!(output.hidden_state, [2., 4.]);
+        assert_eq!(output.policy_logits, [2., 4.]);
+        assert_eq!(output.value, 6.);
+        let output = network.recurrent_inference(&output.hidden_state, 1);
+        assert_eq!(output.hidden_state, [2., 5.]);
+        assert_eq!(output.reward, 1.);
+        assert_eq!(output.value, 7.);
+
+        let stats = run_mcts(
+            &MuZeroConfig {
+                num_simulations: 20,
+                ..MuZeroConfig::board_game(2, 0.3)
+            },
+            &network,
+            &[1., 2.],
+            &[0, 1],
+            0,
+        );
+        assert_eq!(stats.visit_counts.iter().sum::<usize>(), 20);
+
+        assert!(OnnxEvaluator::load(Path::new("no-such-model"), &[2], 2).is_err());
+        let mut one_hot = Support { size: 2 }.encode(1.5);
+        for logit in &mut one_hot {
+            *logit = logit.ln();
+        }
+        assert!((scalar(&one_hot) - 1.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_mismatched() {
+        let (representation, dynamics, prediction) = models();
+        let Err(e) = OnnxEvaluator::new(representation, dynamics, prediction, &[2], 3) else {
+            panic!("the actions don't fit the dynamics");
+        };
+        assert!(format!("{:#}", e).contains("in the dynamics"), "{:#}", e);
+    }
+}
@@ -0,0 +1,510 @@
+//! Optimizers and learning rate schedules for training. The MuZero pseudocode uses SGD with
+//! momentum 0.9, weight decay 1e-4 and a learning rate decaying exponentially, by 0.1 every
+//! 350k to 400k steps; Adam and AdamW are the usual alternatives for smaller runs.
+
+use std::{f32::consts::PI, fmt, str::FromStr};
+
+use crate::{
+    checkpoint::{Tensor, Tensors},
+    error::{ConfigError, ParseError, Result},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptimizerKind {
+    /// SGD with momentum, with weight decay as an L2 penalty.
+    Sgd,
+    /// Adam (Kingma & Ba, 2015), with weight decay as an L2 penalty.
+    Adam,
+    /// AdamW (Loshchilov & Hutter, 2019): Adam with the weight decay applied to the weights
+    /// directly.
+    AdamW,
+}
+
+impl fmt::Display for OptimizerKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptimizerKind::Sgd => write!(f, "sgd"),
+            OptimizerKind::Adam => write!(f, "adam"),
+            OptimizerKind::AdamW => write!(f, "adamw"),
+        }
+    }
+}
+
+impl FromStr for OptimizerKind {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        match s {
+            "sgd" => Ok(OptimizerKind::Sgd),
+            "adam" => Ok(OptimizerKind::Adam),
+            "adamw" => Ok(OptimizerKind::AdamW),
+            _ => Err(ParseError::Invalid(format!(
+                "unknown optimizer `{}`, expected sgd, adam or adamw",
+                s
+            ))),
+        }
+    }
+}
+
+/// How the learning rate falls after the warmup, counting steps from its end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decay {
+    Constant,
+    /// Along half a cosine, to 0 after `steps` steps.
+    Cosine {
+        steps: usize,
+    },
+    /// By `rate` after every `steps` steps.
+    Step {
+        steps: usize,
+        rate: f32,
+    },
+    /// By `rate` every `steps` steps, continuously, as in the MuZero pseudocode.
+    Exponential {
+        steps: usize,
+        rate: f32,
+    },
+}
+
+impl fmt::Display for Decay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Decay::Constant => write!(f, "constant"),
+            Decay::Cosine { steps } => write!(f, "cosine:{}", steps),
+            Decay::Step { steps, rate } => write!(f, "step:{}:{}", steps, rate),
+            Decay::Exponential { steps, rate } => write!(f, "exponential:{}:{}", steps, rate),
+        }
+    }
+}
+
+impl FromStr for Decay {
+    type Err = ParseError;
+
+    /// `constant`, `cosine:<steps>`, `step:<steps>:<rate>` or `exponential:<steps>:<rate>`.
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        let parts: Vec<_> = s.split(':').collect();
+        let steps = |steps: &str| -> Result<usize, ParseError> {
+            match steps.parse() {
+                Ok(0) | Err(_) => Err(ParseError::Invalid(format!(
+                    "invalid number of steps `{}`",
+                    steps
+                ))),
+                Ok(steps) => Ok(steps),
+            }
+        };
+        let rate = |rate: &str| -> Result<f32, ParseError> {
+            rate.parse()
+                .map_err(|_| ParseError::Invalid(format!("invalid decay rate `{}`", rate)))
+        };
+        Ok(match parts[..] {
+            ["constant"] => Decay::Constant,
+            ["cosine", n] => Decay::Cosine { steps: steps(n)? },
+            ["step", n, r] => Decay::Step {
+                steps: steps(n)?,
+                rate: rate(r)?,
+            },
+            ["exponential", n, r] => Decay::Exponential {
+                steps: steps(n)?,
+                rate: rate(r)?,
+            },
+            _ => {
+                return Err(ParseError::Invalid(format!(
+                "unknown schedule `{}`, expected constant, cosine:<steps>, step:<steps>:<rate> \
+                 or exponential:<steps>:<rate>",
+                s
+            )))
+            }
+        })
+    }
+}
+
+/// A linear warmup from 0 to `learning_rate`, then a decay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LrSchedule {
+    pub learning_rate: f32,
+    pub warmup_steps: usize,
+    pub decay: Decay,
+}
+
+impl LrSchedule {
+    /// The learning rate of training step `step`, counting from 0.
+    pub fn at(&self, step: usize) -> f32 {
+        if step < self.warmup_steps {
+            return self.learning_rate * (step + 1) as f32 / self.warmup_steps as f32;
+        }
+        let step = step - self.warmup_steps;
+        self.learning_rate
+            * match self.decay {
+                Decay::Constant => 1.,
+                Decay::Cosine { steps } => {
+                    let progress = (step as f32 / steps as f32).min(1.);
+                    0.5 * (1. + (PI * progress).cos())
+                }
+                Decay::Step { steps, rate } => rate.powi((step / steps) as i32),
+                Decay::Exponential { steps, rate } => rate.powf(step as f32 / steps as f32),
+            }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OptimizerConfig {
+    pub kind: OptimizerKind,
+    pub schedule: LrSchedule,
+    /// The momentum of SGD.
+    pub momentum: f32,
+    /// The decay rates of Adam's moment estimates.
+    pub betas: (f32, f32),
+    pub epsilon: f32,
+    pub weight_decay: f32,
+    /// Scale the gradients down to at most this global L2 norm.
+    pub clip_grad_norm: Option<f32>,
+}
+
+impl Default for OptimizerConfig {
+    /// The optimizer of the MuZero pseudocode, with the learning rate of its Atari config.
+    fn default() -> Self {
+        Self {
+            kind: OptimizerKind::Sgd,
+            schedule: LrSchedule {
+                learning_rate: 0.05,
+                warmup_steps: 0,
+                decay: Decay::Exponential {
+                    steps: 350_000,
+                    rate: 0.1,
+                },
+            },
+            momentum: 0.9,
+            betas: (0.9, 0.999),
+            epsilon: 1e-8,
+            weight_decay: 1e-4,
+            clip_grad_norm: None,
+        }
+    }
+}
+
+/// Updates weights from their gradients, keeping the momentum or moment estimates of every
+/// weight between steps.
+pub struct Optimizer {
+    config: OptimizerConfig,
+    /// The steps taken.
+    step: usize,
+    /// SGD's velocity, or Adam's first and second moments, by weight.
+    state: Vec<(String, Vec<Vec<f32>>)>,
+}
+
+impl Optimizer {
+    pub fn new(config: OptimizerConfig) -> Self {
+        Self {
+            config,
+            step: 0,
+            state: vec![],
+        }
+    }
+
+    pub fn config(&self) -> &OptimizerConfig {
+        &self.config
+    }
+
+    /// The steps taken.
+    pub fn steps(&self) -> usize {
+        self.step
+    }
+
+    /// The learning rate of the next step.
+    pub fn learning_rate(&self) -> f32 {
+        self.config.schedule.at(self.step)
+    }
+
+    fn slots(&self) -> &'static [&'static str] {
+        match self.config.kind {
+            OptimizerKind::Sgd => &["velocity"],
+            OptimizerKind::Adam | OptimizerKind::AdamW => &["m", "v"],
+        }
+    }
+
+    /// Take a step against `gradients`, which must have the names and shapes of `weights`.
+    /// Returns the learning rate used.
+    pub fn step(&mut self, weights: &mut Tensors, gradients: &Tensors) -> Result<f32> {
+        if weights.len() != gradients.len() {
+            return Err(ConfigError::Invalid(format!(
+                "{} gradients for {} weights",
+                gradients.len(),
+                weights.len()
+            ))
+            .into());
+        }
+        for ((name, weight), (grad_name, gradient)) in weights.iter().zip(gradients) {
+            if name != grad_name || weight.shape != gradient.shape {
+                return Err(ConfigError::Invalid(format!(
+                    "the gradient {} {:?} doesn't match the weight {} {:?}",
+                    grad_name, gradient.shape, name, weight.shape
+                ))
+                .into());
+            }
+        }
+        if self.state.is_empty() {
+            let slots = self.slots().len();
+            self.state = weights
+                .iter()
+                .map(|(name, weight)| (name.clone(), vec![vec![0.; weight.data.len()]; slots]))
+                .collect();
+        }
+        if !(self.state.len() == weights.len()
+            && self.state.iter().zip(weights.iter()).all(
+                |((name, slots), (weight_name, weight))| {
+                    name == weight_name && slots[0].len() == weight.data.len()
+                },
+            ))
+        {
+            return Err(ConfigError::Invalid(
+                "the weights don't match the optimizer state".to_string(),
+            )
+            .into());
+        }
+        let OptimizerConfig {
+            kind,
+            momentum,
+            betas: (beta1, beta2),
+            epsilon,
+            weight_decay,
+            clip_grad_norm,
+            ..
+        } = self.config;
+        let scale = match clip_grad_norm {
+            Some(max_norm) => {
+                let norm = gradients
+                    .iter()
+                    .flat_map(|(_, gradient)| &gradient.data)
+                    .map(|g| g * g)
+                    .sum::<f32>()
+                    .sqrt();
+                if norm > max_norm {
+                    max_norm / norm
+                } else {
+                    1.
+                }
+            }
+            None => 1.,
+        };
+        let lr = self.learning_rate();
+        self.step += 1;
+        let t = self.step as i32;
+        let (correction1, correction2) = (1. - beta1.powi(t), 1. - beta2.powi(t));
+        for (((_, weight), (_, gradient)), (_, slots)) in
+            weights.iter_mut().zip(gradients).zip(&mut self.state)
+        {
+            for (i, (w, &g)) in weight.data.iter_mut().zip(&gradient.data).enumerate() {
+                let mut g = g * scale;
+                if kind != OptimizerKind::AdamW {
+                    g += weight_decay * *w;
+                }
+                match kind {
+                    OptimizerKind::Sgd => {
+                        let velocity = &mut slots[0][i];
+                        *velocity = momentum * *velocity + g;
+                        *w -= lr * *velocity;
+                    }
+                    OptimizerKind::Adam | OptimizerKind::AdamW => {
+                        let m = beta1 * slots[0][i] + (1. - beta1) * g;
+                        let v = beta2 * slots[1][i] + (1. - beta2) * g * g;
+                        (slots[0][i], slots[1][i]) = (m, v);
+                        if kind == OptimizerKind::AdamW {
+                            *w -= lr * weight_decay * *w;
+                        }
+                        *w -= lr * (m / correction1) / ((v / correction2).sqrt() + epsilon);
+                    }
+                }
+            }
+        }
+        Ok(lr)
+    }
+
+    /// The state to save in a checkpoint, as `<weight>.<slot>` tensors, e.g. `conv1.m`.
+    pub fn state(&self) -> Tensors {
+        self.state
+            .iter()
+            .flat_map(|(name, slots)| {
+                self.slots().iter().zip(slots).map(move |(slot, data)| {
+                    let tensor = Tensor::new(vec![data.len()], data.clone()).unwrap();
+                    (format!("{}.{}", name, slot), tensor)
+                })
+            })
+            .collect()
+    }
+
+    /// Resume after `step` steps from the state of a checkpoint. An empty state starts the
+    /// moments afresh.
+    pub fn load_state(&mut self, step: usize, state: &Tensors) -> Result<()> {
+        let slots = self.slots();
+        if !state.len().is_multiple_of(slots.len()) {
+            return Err(ConfigError::Invalid(format!(
+                "the optimizer state isn't from {}",
+                self.config.kind
+            ))
+            .into());
+        }
+        let mut loaded = vec![];
+        for chunk in state.chunks(slots.len()) {
+            let (first, _) = &chunk[0];
+            let name = first
+                .strip_suffix(&format!(".{}", slots[0]))
+                .ok_or_else(|| {
+                    ConfigError::Invalid(format!(
+                        "the optimizer state isn't from {}",
+                        self.config.kind
+                    ))
+                })?;
+            let mut data = vec![];
+            for ((tensor_name, tensor), slot) in chunk.iter().zip(slots) {
+                if *tensor_name != format!("{}.{}", name, slot) {
+                    return Err(ConfigError::Invalid(format!(
+                        "the optimizer state isn't from {}",
+                        self.config.kind
+                    ))
+                    .into());
+                }
+                data.push(tensor.data.clone());
+            }
+            loaded.push((name.to_string(), data));
+        }
+        self.state = loaded;
+        self.step = step;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weights(values: &[f32]) -> Tensors {
+        vec![(
+            "w".to_string(),
+            Tensor::new(vec![values.len()], values.to_vec()).unwrap(),
+        )]
+    }
+
+    fn config(kind: OptimizerKind) -> OptimizerConfig {
+        OptimizerConfig {
+            kind,
+            schedule: LrSchedule {
+                learning_rate: 0.1,
+                warmup_steps: 0,
+                decay: Decay::Constant,
+            },
+            weight_decay: 0.,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_schedule() {
+        let mut schedule = LrSchedule {
+            learning_rate: 1.,
+            warmup_steps: 4,
+            decay: Decay::Cosine { steps: 10 },
+        };
+        assert_eq!(schedule.at(0), 0.25);
+        assert_eq!(schedule.at(3), 1.);
+        assert_eq!(schedule.at(4), 1.);
+        assert!((schedule.at(9) - 0.5).abs() < 1e-6);
+        assert_eq!(schedule.at(100), 0.);
+        schedule.warmup_steps = 0;
+        schedule.decay = Decay::Step {
+            steps: 10,
+            rate: 0.5,
+        };
+        assert_eq!(schedule.at(9), 1.);
+        assert_eq!(schedule.at(25), 0.25);
+        schedule.decay = Decay::Exponential {
+            steps: 10,
+            rate: 0.5,
+        };
+        assert!((schedule.at(5) - 0.5f32.sqrt()).abs() < 1e-6);
+
+        for decay in [
+            "constant",
+            "cosine:100",
+            "step:10:0.5",
+            "exponential:350000:0.1",
+        ] {
+            assert_eq!(decay.parse::<Decay>().unwrap().to_string(), decay);
+        }
+        for decay in ["cosine", "cosine:0", "step:10", "linear"] {
+            assert!(decay.parse::<Decay>().is_err(), "{}", decay);
+        }
+    }
+
+    #[test]
+    fn test_sgd() {
+        let mut optimizer = Optimizer::new(OptimizerConfig {
+            weight_decay: 0.5,
+            ..config(OptimizerKind::Sgd)
+        });
+        let mut w = weights(&[1., -2.]);
+        let g = weights(&[1., 0.]);
+        optimizer.step(&mut w, &g).unwrap();
+        // The gradients with the L2 penalty are 1.5 and -1.
+        assert_eq!(w[0].1.data, [1. - 0.15, -2. + 0.1]);
+        optimizer.step(&mut w, &g).unwrap();
+        let velocity = 0.9 * 1.5 + (1. + 0.5 * 0.85);
+        assert!((w[0].1.data[0] - (0.85 - 0.1 * velocity)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_adam() {
+        // Adam's first step moves every weight by the learning rate, whatever the gradient.
+        for kind in [OptimizerKind::Adam, OptimizerKind::AdamW] {
+            let mut optimizer = Optimizer::new(config(kind));
+            let mut w = weights(&[1., 1.]);
+            optimizer.step(&mut w, &weights(&[100., -0.01])).unwrap();
+            assert!((w[0].1.data[0] - 0.9).abs() < 1e-5);
+            assert!((w[0].1.data[1] - 1.1).abs() < 1e-4);
+        }
+        // AdamW decays the weights directly, even without a gradient.
+        let mut optimizer = Optimizer::new(OptimizerConfig {
+            weight_decay: 0.5,
+            ..config(OptimizerKind::AdamW)
+        });
+        let mut w = weights(&[2.]);
+        optimizer.step(&mut w, &weights(&[0.])).unwrap();
+        assert!((w[0].1.data[0] - 1.9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_clipping() {
+        let mut optimizer = Optimizer::new(OptimizerConfig {
+            momentum: 0.,
+            clip_grad_norm: Some(1.),
+            ..config(OptimizerKind::Sgd)
+        });
+        let mut w = weights(&[0., 0.]);
+        optimizer.step(&mut w, &weights(&[3., 4.])).unwrap();
+        assert!((w[0].1.data[0] + 0.06).abs() < 1e-6);
+        assert!((w[0].1.data[1] + 0.08).abs() < 1e-6);
+        assert!(optimizer.step(&mut w, &weights(&[1.])).is_err());
+    }
+
+    #[test]
+    fn test_state() {
+        let mut optimizer = Optimizer::new(config(OptimizerKind::Adam));
+        let mut w = weights(&[1., 2.]);
+        let g = weights(&[0.5, -1.]);
+        optimizer.step(&mut w, &g).unwrap();
+        let state = optimizer.state();
+        assert_eq!(state[0].0, "w.m");
+        assert_eq!(state[1].0, "w.v");
+
+        let mut resumed = Optimizer::new(config(OptimizerKind::Adam));
+        resumed.load_state(1, &state).unwrap();
+        let mut w2 = w.clone();
+        optimizer.step(&mut w, &g).unwrap();
+        resumed.step(&mut w2, &g).unwrap();
+        assert_eq!(w, w2);
+        assert_eq!(resumed.steps(), 2);
+
+        let mut sgd = Optimizer::new(config(OptimizerKind::Sgd));
+        assert!(sgd.load_state(1, &state).is_err());
+        sgd.load_state(0, &vec![]).unwrap();
+    }
+}
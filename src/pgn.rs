@@ -0,0 +1,260 @@
+//! PGN, the Portable Game Notation, for chess games with the evaluation of the search after
+//! every move as a `[%eval]` comment, which chess GUIs graph.
+//!
+//! Moves are given in SAN, which needs legal move generation, so they're recorded from the
+//! positions of [`crate::games::chess::Chess`], behind the `chess` feature, rather than from
+//! game records.
+
+use std::fmt;
+
+use crate::games::chess::Color;
+#[cfg(feature = "chess")]
+use crate::{
+    error::GameError,
+    games::chess::{Chess, ChessMove},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    /// The game isn't over.
+    Unfinished,
+}
+
+impl Outcome {
+    /// The outcome of a finished game from its [`crate::game::Game::returns`], White first.
+    pub fn from_returns(returns: &[f32]) -> Self {
+        match returns {
+            [white, black] if white > black => Outcome::WhiteWins,
+            [white, black] if black > white => Outcome::BlackWins,
+            _ => Outcome::Draw,
+        }
+    }
+
+    fn token(self) -> &'static str {
+        match self {
+            Outcome::WhiteWins => "1-0",
+            Outcome::BlackWins => "0-1",
+            Outcome::Draw => "1/2-1/2",
+            Outcome::Unfinished => "*",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnMove {
+    /// The move in Standard Algebraic Notation, e.g. `Nf3` or `exd8=Q+`.
+    pub san: String,
+    /// The value of the search that chose the move for the side that played it, between -1
+    /// and 1, or `None` for moves of humans.
+    pub value: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnGame {
+    pub event: String,
+    pub site: String,
+    /// As `YYYY.MM.DD`, with `??` for unknown parts.
+    pub date: String,
+    pub white: String,
+    pub black: String,
+    pub moves: Vec<PgnMove>,
+    pub outcome: Outcome,
+}
+
+impl PgnGame {
+    pub fn new(white: &str, black: &str) -> Self {
+        Self {
+            event: "?".to_string(),
+            site: "?".to_string(),
+            date: "????.??.??".to_string(),
+            white: white.to_string(),
+            black: black.to_string(),
+            moves: vec![],
+            outcome: Outcome::Unfinished,
+        }
+    }
+}
+
+#[cfg(feature = "chess")]
+impl PgnGame {
+    /// Record `action`, about to be played in `game`, and the value of the search that chose
+    /// it.
+    pub fn push(
+        &mut self,
+        game: &Chess,
+        action: ChessMove,
+        value: Option<f32>,
+    ) -> Result<(), GameError> {
+        let san = game
+            .san(action)
+            .ok_or_else(|| GameError::illegal_move(&action, "not a legal move"))?;
+        self.moves.push(PgnMove { san, value });
+        Ok(())
+    }
+}
+
+/// A search value for `side` as an evaluation in pawns from White's point of view, with the
+/// mapping from expected score to centipawns Leela Chess Zero uses.
+pub fn eval_pawns(value: f32, side: Color) -> f32 {
+    let value = match side {
+        Color::White => value,
+        Color::Black => -value,
+    };
+    // tan grows without bound near +-1, so certain wins are capped at about 109 pawns.
+    let value = value.clamp(-0.999, 0.999);
+    1.117_146_4 * (1.562_068_8 * value).tan()
+}
+
+/// Escape a tag value for its quotes.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl fmt::Display for PgnGame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (tag, value) in [
+            ("Event", self.event.as_str()),
+            ("Site", &self.site),
+            ("Date", &self.date),
+            ("Round", "-"),
+            ("White", &self.white),
+            ("Black", &self.black),
+            ("Result", self.outcome.token()),
+        ] {
+            writeln!(f, "[{} \"{}\"]", tag, escape(value))?;
+        }
+        writeln!(f)?;
+        let mut tokens = vec![];
+        for (i, mv) in self.moves.iter().enumerate() {
+            let side = if i % 2 == 0 {
+                tokens.push(format!("{}.", i / 2 + 1));
+                Color::White
+            } else {
+                Color::Black
+            };
+            tokens.push(mv.san.clone());
+            if let Some(value) = mv.value {
+                tokens.push(format!("{{ [%eval {:.2}] }}", eval_pawns(value, side)));
+            }
+        }
+        tokens.push(self.outcome.token().to_string());
+        // Lines of at most 80 characters, as the export format asks.
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > 80 {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line += &token;
+        }
+        writeln!(f, "{}", line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(eval_pawns(0., Color::White), 0.);
+        assert!(eval_pawns(0.5, Color::White) > 1.);
+        assert_eq!(
+            eval_pawns(0.5, Color::Black),
+            -eval_pawns(0.5, Color::White)
+        );
+        assert!((100. ..110.).contains(&eval_pawns(1., Color::White)));
+    }
+
+    #[test]
+    fn test_display() {
+        let mut game = PgnGame::new("muzero \"mcts\"", "Human");
+        for (san, value) in [("e4", Some(0.)), ("e5", None), ("Qh5", Some(0.5))] {
+            game.moves.push(PgnMove {
+                san: san.to_string(),
+                value,
+            });
+        }
+        assert_eq!(
+            game.to_string(),
+            "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"-\"]\n\
+             [White \"muzero \\\"mcts\\\"\"]\n[Black \"Human\"]\n[Result \"*\"]\n\n\
+             1. e4 { [%eval 0.00] } e5 2. Qh5 { [%eval 1.11] } *\n"
+        );
+
+        game.outcome = Outcome::from_returns(&[-1., 1.]);
+        for _ in 0..20 {
+            game.moves.push(PgnMove {
+                san: "Nf3".to_string(),
+                value: Some(0.),
+            });
+        }
+        let text = game.to_string();
+        assert!(text.contains("[Result \"0-1\"]"));
+        assert!(text.trim_end().ends_with(" 0-1"));
+        assert!(text.lines().all(|line| line.len() <= 80));
+        assert_eq!(Outcome::from_returns(&[0., 0.]), Outcome::Draw);
+    }
+
+    #[cfg(feature = "chess")]
+    #[test]
+    fn test_chess_round_trip() {
+        use crate::game::Game;
+        use shakmaty::{san::SanPlus, Position};
+
+        let square = |name: &str| {
+            let name = name.as_bytes();
+            (name[0] - b'a') + 8 * (name[1] - b'1')
+        };
+        let mut game = Chess::new();
+        let mut pgn = PgnGame::new("muzero", "Human");
+        for (i, uci) in [
+            "e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "f8c5", "e1g1", "g8f6", "f3g5", "e8g8", "g5f7",
+            "f8f7", "c4f7", "g8f7", "d1h5", "f7g8",
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let action = ChessMove {
+                from: square(&uci[..2]),
+                to: square(&uci[2..]),
+                promotion: None,
+            };
+            let value = (i % 2 == 0).then_some(0.);
+            pgn.push(&game, action, value).unwrap();
+            game.step(action).unwrap();
+        }
+        assert!(pgn.push(&game, game.index_to_action(0), None).is_err());
+        let text = pgn.to_string();
+        let mut movetext = vec![];
+        let mut comment = false;
+        for token in text.split("\n\n").nth(1).unwrap().split_whitespace() {
+            match token {
+                "{" => comment = true,
+                "}" => comment = false,
+                _ if comment || token.ends_with('.') => {}
+                _ => movetext.push(token),
+            }
+        }
+        assert_eq!(
+            movetext.join(" "),
+            "e4 e5 Nf3 Nc6 Bc4 Bc5 O-O Nf6 Ng5 O-O Nxf7 Rxf7 Bxf7+ Kxf7 Qh5+ Kg8 *"
+        );
+
+        // Reading the moves back reaches the same position.
+        let mut position = shakmaty::Chess::default();
+        for san in &movetext[..movetext.len() - 1] {
+            let san = SanPlus::from_ascii(san.as_bytes()).unwrap();
+            let m = san.san.to_move(&position).unwrap();
+            position = position.play(m).unwrap();
+        }
+        assert_eq!(position.board(), game.position().board());
+        assert_eq!(position.turn(), game.position().turn());
+    }
+}
@@ -0,0 +1,234 @@
+//! A Prometheus endpoint for long-running self-play, behind the `prometheus` feature: counters
+//! and histograms served in the text format at `/metrics`. Rates like games per second are
+//! left to Prometheus, e.g. `rate(muzero_selfplay_games_total[5m])`.
+
+use std::{
+    fmt::Write as _,
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use crate::error::{Context, Result};
+
+#[derive(Debug, Default)]
+pub struct Counter {
+    value: AtomicU64,
+}
+
+impl Counter {
+    pub fn inc_by(&self, n: u64) {
+        self.value.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug)]
+pub struct Histogram {
+    /// The upper bounds of the buckets, in increasing order; a last bucket takes the rest.
+    bounds: Vec<f64>,
+    counts: Vec<AtomicU64>,
+    sum: Mutex<f64>,
+}
+
+impl Histogram {
+    pub fn new(bounds: Vec<f64>) -> Self {
+        Self {
+            counts: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            bounds,
+            sum: Mutex::new(0.),
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        let bucket = self.bounds.partition_point(|&bound| bound < value);
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        *self.sum.lock().unwrap() += value;
+    }
+
+    fn render(&self, out: &mut String, name: &str) {
+        let mut count = 0;
+        for (i, bucket) in self.counts.iter().enumerate() {
+            count += bucket.load(Ordering::Relaxed);
+            let le = match self.bounds.get(i) {
+                Some(bound) => bound.to_string(),
+                None => "+Inf".to_string(),
+            };
+            writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, count).unwrap();
+        }
+        writeln!(out, "{}_sum {}", name, self.sum.lock().unwrap()).unwrap();
+        writeln!(out, "{}_count {}", name, count).unwrap();
+    }
+}
+
+/// What self-play workers report.
+#[derive(Debug)]
+pub struct SelfPlayMetrics {
+    pub games: Counter,
+    pub moves: Counter,
+    pub simulations: Counter,
+    /// Nodes allocated by the searches.
+    pub nodes: Counter,
+    /// Seconds spent searching each move.
+    pub move_seconds: Histogram,
+    /// Moves per game.
+    pub game_length: Histogram,
+}
+
+impl Default for SelfPlayMetrics {
+    fn default() -> Self {
+        Self {
+            games: Counter::default(),
+            moves: Counter::default(),
+            simulations: Counter::default(),
+            nodes: Counter::default(),
+            move_seconds: Histogram::new(vec![0.001, 0.01, 0.1, 0.5, 1., 5., 30.]),
+            game_length: Histogram::new(vec![10., 20., 50., 100., 200., 500., 1000.]),
+        }
+    }
+}
+
+impl SelfPlayMetrics {
+    /// The metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let counters = [
+            (
+                "muzero_selfplay_games_total",
+                "Games played.",
+                self.games.get(),
+            ),
+            (
+                "muzero_selfplay_moves_total",
+                "Moves played.",
+                self.moves.get(),
+            ),
+            (
+                "muzero_search_simulations_total",
+                "Search simulations run.",
+                self.simulations.get(),
+            ),
+            (
+                "muzero_mcts_nodes_allocated_total",
+                "Nodes allocated by the searches.",
+                self.nodes.get(),
+            ),
+        ];
+        for (name, help, value) in counters {
+            writeln!(out, "# HELP {} {}", name, help).unwrap();
+            writeln!(out, "# TYPE {} counter", name).unwrap();
+            writeln!(out, "{} {}", name, value).unwrap();
+        }
+        let histograms = [
+            (
+                "muzero_search_move_seconds",
+                "Seconds spent searching a move.",
+                &self.move_seconds,
+            ),
+            (
+                "muzero_selfplay_game_length",
+                "Moves per game.",
+                &self.game_length,
+            ),
+        ];
+        for (name, help, histogram) in histograms {
+            writeln!(out, "# HELP {} {}", name, help).unwrap();
+            writeln!(out, "# TYPE {} histogram", name).unwrap();
+            histogram.render(&mut out, name);
+        }
+        out
+    }
+}
+
+/// Serve `metrics` at `http://<addr>/metrics` from a background thread. Returns the address
+/// listened on, which tells the port when `addr` asks for any.
+pub fn serve(addr: impl ToSocketAddrs, metrics: Arc<SelfPlayMetrics>) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(addr).context("failed to bind the metrics endpoint")?;
+    let local_addr = listener.local_addr()?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(e) = respond(stream, &metrics) {
+                tracing::warn!("metrics request failed: {}", e);
+            }
+        }
+    });
+    Ok(local_addr)
+}
+
+fn respond(stream: TcpStream, metrics: &SelfPlayMetrics) -> std::io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the headers.
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+    let (status, content_type, body) = match request_line.split_whitespace().nth(1) {
+        Some("/metrics") => ("200 OK", "text/plain; version=0.0.4", metrics.render()),
+        _ => ("404 Not Found", "text/plain", "not found\n".to_string()),
+    };
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_histogram() {
+        let histogram = Histogram::new(vec![1., 10.]);
+        for value in [0.5, 1., 3., 30.] {
+            histogram.observe(value);
+        }
+        let mut out = String::new();
+        histogram.render(&mut out, "h");
+        assert_eq!(
+            out,
+            "h_bucket{le=\"1\"} 2\nh_bucket{le=\"10\"} 3\nh_bucket{le=\"+Inf\"} 4\n\
+             h_sum 34.5\nh_count 4\n"
+        );
+    }
+
+    #[test]
+    fn test_serve() {
+        let metrics = Arc::new(SelfPlayMetrics::default());
+        let addr = serve("127.0.0.1:0", metrics.clone()).unwrap();
+        metrics.games.inc_by(3);
+        metrics.nodes.inc_by(40);
+        metrics.game_length.observe(9.);
+
+        let response = get(addr, "/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.contains("\nmuzero_selfplay_games_total 3\n"));
+        assert!(response.contains("\nmuzero_mcts_nodes_allocated_total 40\n"));
+        assert!(response.contains("# TYPE muzero_selfplay_game_length histogram\n"));
+        assert!(response.contains("\nmuzero_selfplay_game_length_bucket{le=\"10\"} 1\n"));
+        assert!(get(addr, "/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
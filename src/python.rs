@@ -0,0 +1,294 @@
+//! A Python module, behind the `python` feature, so that notebooks can script experiments
+//! while the search runs in Rust: the games of the registry, the search, and self-play
+//! writing the trajectory files that `muzero train` reads. Build it with maturin, e.g.
+//! `maturin develop --features python,pyo3/extension-module`:
+//!
+//! ```text
+//! import muzero_rs
+//!
+//! game = muzero_rs.Game("connect4")
+//! mcts = muzero_rs.Mcts(simulations=400, seed=1)
+//! result = mcts.search(game)
+//! game.step(result.action)
+//!
+//! games = [muzero_rs.self_play("tictactoe", simulations=200) for _ in range(10)]
+//! muzero_rs.write_games("tictactoe.traj", games)
+//! ```
+//!
+//! Actions are indices into the action space of the game. Errors are raised as
+//! `RuntimeError`.
+
+use pyo3::{exceptions::PyRuntimeError, prelude::*};
+use std::path::PathBuf;
+
+use crate::{
+    dyn_game::DynGame,
+    error::{Error, Result},
+    history::GameHistory,
+    mcts::{sample_outcome, Mcts, MctsConfig},
+    random,
+    registry::Registry,
+    trajectory::{TrajectoryReader, TrajectoryWriter},
+    Game,
+};
+
+impl From<Error> for PyErr {
+    fn from(error: Error) -> Self {
+        PyRuntimeError::new_err(format!("{:#}", error))
+    }
+}
+
+/// A game from the registry, like `gomoku:9`.
+#[pyclass(name = "Game", unsendable)]
+pub struct PyGame {
+    name: String,
+    game: Box<dyn DynGame>,
+}
+
+#[pymethods]
+impl PyGame {
+    #[new]
+    fn new(name: &str) -> Result<Self> {
+        Ok(Self {
+            name: name.to_string(),
+            game: Registry::default().create(name)?,
+        })
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn legal_actions(&self) -> Vec<usize> {
+        self.game.legal_actions()
+    }
+
+    fn action_space_size(&self) -> usize {
+        self.game.action_space_size()
+    }
+
+    fn action_name(&self, action: usize) -> String {
+        self.game.action_name(action)
+    }
+
+    /// Play the action at `action`, or the chance outcome, and return its reward.
+    fn step(&mut self, action: usize) -> Result<f32> {
+        Ok(self.game.step(action)?)
+    }
+
+    /// The pending chance outcomes and their probabilities, empty when a player is to move.
+    fn chance_outcomes(&self) -> Vec<(usize, f32)> {
+        self.game.chance_outcomes()
+    }
+
+    fn to_play(&self) -> usize {
+        self.game.to_play()
+    }
+
+    fn num_players(&self) -> usize {
+        self.game.num_players()
+    }
+
+    fn done(&self) -> bool {
+        self.game.done()
+    }
+
+    /// The returns of every player once the game is done.
+    fn returns(&self) -> Vec<f32> {
+        self.game.returns()
+    }
+
+    fn observation_shape(&self) -> Vec<usize> {
+        self.game.observation_shape()
+    }
+
+    /// The observation, flattened in row-major order.
+    fn observation(&self) -> Vec<f32> {
+        self.game.observation()
+    }
+
+    fn copy(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            game: self.game.clone_box(),
+        }
+    }
+
+    fn __str__(&self) -> String {
+        self.game.to_string()
+    }
+}
+
+/// The search with random playouts.
+#[pyclass(name = "Mcts", unsendable)]
+pub struct PyMcts {
+    mcts: Mcts<Box<dyn DynGame>>,
+}
+
+#[pymethods]
+impl PyMcts {
+    #[new]
+    #[pyo3(signature = (simulations = 1000, exploration = 1.414, seed = None))]
+    fn new(simulations: usize, exploration: f32, seed: Option<u64>) -> Self {
+        Self {
+            mcts: Mcts::with_config(MctsConfig {
+                num_simulations: simulations,
+                exploration,
+                seed,
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn search(&self, game: &PyGame) -> PySearchResult {
+        let result = self.mcts.search(&game.game);
+        PySearchResult {
+            action: result.action,
+            value: result.value(),
+            visits: result
+                .children
+                .iter()
+                .map(|child| (child.action, child.visits))
+                .collect(),
+        }
+    }
+}
+
+/// The best action of a search, the value of the position for the player to move, and the
+/// visits of the searched actions, the most visited first.
+#[pyclass(name = "SearchResult", frozen, get_all)]
+pub struct PySearchResult {
+    action: usize,
+    value: f32,
+    visits: Vec<(usize, usize)>,
+}
+
+/// A game played by self-play, as training reads it.
+#[pyclass(name = "GameHistory", frozen)]
+pub struct PyGameHistory(GameHistory);
+
+#[pymethods]
+impl PyGameHistory {
+    #[getter]
+    fn observations(&self) -> Vec<Vec<f32>> {
+        self.0.observations.clone()
+    }
+
+    #[getter]
+    fn to_play(&self) -> Vec<usize> {
+        self.0.to_play.clone()
+    }
+
+    #[getter]
+    fn actions(&self) -> Vec<usize> {
+        self.0.actions.clone()
+    }
+
+    #[getter]
+    fn rewards(&self) -> Vec<f32> {
+        self.0.rewards.clone()
+    }
+
+    /// The visit distribution of the search over the action space for every move.
+    #[getter]
+    fn child_visits(&self) -> Vec<Vec<f32>> {
+        self.0.child_visits.clone()
+    }
+
+    #[getter]
+    fn root_values(&self) -> Vec<f32> {
+        self.0.root_values.clone()
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Play a game of `game` against itself, sampling moves from the visits of the search raised
+/// to `1 / temperature`.
+#[pyfunction]
+#[pyo3(signature = (game, simulations = 1000, temperature = 1.))]
+fn self_play(game: &str, simulations: usize, temperature: f32) -> Result<PyGameHistory> {
+    let mut game = Registry::default().create(game)?;
+    let mcts = Mcts::<Box<dyn DynGame>>::new(simulations);
+    let mut history = GameHistory::default();
+    while !game.done() {
+        let outcomes = game.chance_outcomes();
+        if !outcomes.is_empty() {
+            game.step(sample_outcome(&outcomes, &mut random::rng()))?;
+            continue;
+        }
+        let stats = mcts.search_statistics(&game);
+        let action = game.index_to_action(stats.select_action(temperature));
+        history.apply(&mut game, action, &stats)?;
+    }
+    Ok(PyGameHistory(history))
+}
+
+/// Write `games` to a new trajectory file at `path`.
+#[pyfunction]
+fn write_games(path: PathBuf, games: Vec<PyRef<PyGameHistory>>) -> Result<()> {
+    let mut writer = TrajectoryWriter::create(&path)?;
+    for game in games {
+        writer.write(&game.0)?;
+    }
+    writer.flush()
+}
+
+/// The games of the trajectory file at `path`.
+#[pyfunction]
+fn read_games(path: PathBuf) -> Result<Vec<PyGameHistory>> {
+    let mut reader = TrajectoryReader::open(&path)?;
+    let mut games = vec![];
+    while let Some(game) = reader.read()? {
+        games.push(PyGameHistory(game));
+    }
+    Ok(games)
+}
+
+#[pymodule]
+fn muzero_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyGame>()?;
+    m.add_class::<PyMcts>()?;
+    m.add_class::<PySearchResult>()?;
+    m.add_class::<PyGameHistory>()?;
+    m.add_function(wrap_pyfunction!(self_play, m)?)?;
+    m.add_function(wrap_pyfunction!(write_games, m)?)?;
+    m.add_function(wrap_pyfunction!(read_games, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module() {
+        let mut game = PyGame::new("tictactoe").unwrap();
+        assert!(PyGame::new("shogi").is_err());
+        for action in [0, 3, 1, 4] {
+            game.step(action).unwrap();
+        }
+        let result = PyMcts::new(200, 1.414, Some(1)).search(&game);
+        // X completes the top row.
+        assert_eq!(result.action, 2);
+        assert_eq!(result.visits.len(), 5);
+        assert!(game.copy().step(4).is_err());
+
+        let history = self_play("tictactoe", 50, 1.).unwrap();
+        assert_eq!(history.actions().len(), history.__len__());
+        assert_eq!(history.child_visits()[0].len(), 9);
+        let path = std::env::temp_dir().join(format!("muzero-python-{}", std::process::id()));
+        Python::initialize();
+        Python::attach(|py| {
+            let history = Bound::new(py, history).unwrap();
+            write_games(path.clone(), vec![history.borrow(), history.borrow()]).unwrap();
+            let games = read_games(path.clone()).unwrap();
+            assert_eq!(games.len(), 2);
+            assert_eq!(games[1].actions(), history.get().actions());
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,154 @@
+//! The random numbers of the crate: chance events, random agents, move sampling, exploration
+//! noise and the seeds of unseeded searches all come from [`rng`]. By default it draws from
+//! the operating system; after [`set_seed`], every thread draws from a stream of its own
+//! derived from the seed, so that a run with a fixed number of threads repeats exactly.
+
+use rand::{rngs::StdRng, rngs::ThreadRng, RngCore, SeedableRng};
+use std::{
+    cell::RefCell,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
+
+static SEEDED: AtomicBool = AtomicBool::new(false);
+static SEED: AtomicU64 = AtomicU64::new(0);
+/// The stream of the next thread to draw.
+static NEXT_STREAM: AtomicU64 = AtomicU64::new(0);
+
+enum ThreadState {
+    Entropy(ThreadRng),
+    Seeded(Box<StdRng>),
+}
+
+thread_local! {
+    static STATE: RefCell<Option<ThreadState>> = const { RefCell::new(None) };
+}
+
+/// Make every later draw deterministic. Threads get their streams in the order they first
+/// draw, so call this before spawning any; the calling thread starts over at stream 0.
+pub fn set_seed(seed: u64) {
+    SEED.store(seed, Ordering::SeqCst);
+    SEEDED.store(true, Ordering::SeqCst);
+    NEXT_STREAM.store(1, Ordering::SeqCst);
+    let rng = StdRng::seed_from_u64(mix(seed, 0));
+    STATE.with(|state| *state.borrow_mut() = Some(ThreadState::Seeded(Box::new(rng))));
+}
+
+/// The seed given to [`set_seed`], if any.
+pub fn seed() -> Option<u64> {
+    SEEDED
+        .load(Ordering::SeqCst)
+        .then(|| SEED.load(Ordering::SeqCst))
+}
+
+/// Mix `seed` and `stream` into an unrelated seed, with SplitMix64.
+pub fn mix(seed: u64, stream: u64) -> u64 {
+    let mut z = seed.wrapping_add(stream.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn new_state() -> ThreadState {
+    match seed() {
+        Some(seed) => {
+            let stream = NEXT_STREAM.fetch_add(1, Ordering::SeqCst);
+            ThreadState::Seeded(Box::new(StdRng::seed_from_u64(mix(seed, stream))))
+        }
+        None => ThreadState::Entropy(rand::thread_rng()),
+    }
+}
+
+/// The random number generator of the current thread, like [`rand::thread_rng`].
+pub fn rng() -> Rng {
+    Rng(())
+}
+
+/// A handle to the generator of the current thread.
+#[derive(Debug)]
+pub struct Rng(());
+
+impl Rng {
+    fn with<T>(&mut self, f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            match state.get_or_insert_with(new_state) {
+                ThreadState::Entropy(rng) => f(rng),
+                ThreadState::Seeded(rng) => f(rng),
+            }
+        })
+    }
+}
+
+impl RngCore for Rng {
+    fn next_u32(&mut self) -> u32 {
+        self.with(|rng| rng.next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.with(|rng| rng.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.with(|rng| rng.fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.with(|rng| rng.try_fill_bytes(dest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agent::{Agent, RandomAgent},
+        games::tic_tac_toe::TicTacToe,
+        mcts::{Mcts, MctsConfig},
+        Game,
+    };
+    use rand::Rng as _;
+
+    const GOLDEN: [usize; 5] = [2, 3, 4, 1, 6];
+
+    #[test]
+    fn test_seed() {
+        let draw = || {
+            set_seed(42);
+            (0..4).map(|_| rng().gen()).collect::<Vec<u64>>()
+        };
+        let first = draw();
+        assert_eq!(draw(), first);
+        assert_eq!(seed(), Some(42));
+        // Another thread draws from another stream.
+        let other = std::thread::spawn(|| rng().gen::<u64>()).join().unwrap();
+        assert!(!first.contains(&other));
+        assert_ne!(mix(1, 0), mix(1, 1));
+    }
+
+    /// The moves of a game between unseeded searches and a random agent after `set_seed`.
+    fn seeded_game() -> Vec<usize> {
+        set_seed(2024);
+        let mut game = TicTacToe::new();
+        let mut mcts = Mcts::with_config(MctsConfig {
+            num_simulations: 50,
+            ..Default::default()
+        });
+        let mut moves = vec![];
+        while !game.done() {
+            let action = match moves.len() % 2 {
+                0 => mcts.select_action(&game).unwrap(),
+                _ => RandomAgent.select_action(&game).unwrap(),
+            };
+            moves.push(game.action_to_index(&action));
+            game.step(action).unwrap();
+        }
+        moves
+    }
+
+    #[test]
+    fn test_golden_game() {
+        // A change here means the search, or how it draws random numbers, behaves differently.
+        assert_eq!(seeded_game(), GOLDEN);
+        assert_eq!(seeded_game(), GOLDEN);
+    }
+}
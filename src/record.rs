@@ -0,0 +1,222 @@
+//! Records of whole games to watch again: every move including chance events, when it was
+//! played and the search that chose it. Record files hold one game per line, as JSON, which
+//! other tools can read and write for any game:
+//!
+//! ```text
+//! {"game": "tictactoe",
+//!  "moves": [{"action": 4, "player": 0, "timestamp": 1700000000000,
+//!             "search": {"value": 0.1, "visits": [[4, 90], [0, 10]]}},
+//!            {"action": 0, "player": 1, "timestamp": 1700000002000, "search": null}]}
+//! ```
+//!
+//! `game` is the game as the registry names it, actions are indices into its action space,
+//! `player` is `null` for chance events and `timestamp` is in milliseconds since the Unix
+//! epoch. Go, Gomoku and Othello records can also be written as SGF, see [`crate::sgf`].
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(feature = "serde")]
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+#[cfg(feature = "serde")]
+use crate::{error::IoError, sgf};
+use crate::{
+    error::{Context, ParseError, Result},
+    game::Game,
+    mcts::SearchResult,
+    muzero::SearchStatistics,
+};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GameRecord {
+    /// The game, as the registry names it.
+    pub game: String,
+    pub moves: Vec<MoveRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MoveRecord {
+    /// The index of the action.
+    pub action: usize,
+    /// The index of the player who moved, `None` for chance events.
+    pub player: Option<usize>,
+    /// When the move was played, in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The search that chose the move, `None` for humans and chance.
+    pub search: Option<MoveSearch>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MoveSearch {
+    /// The value of the position for the player to move.
+    pub value: f32,
+    /// The visit counts of the searched action indices, the most visited first.
+    pub visits: Vec<(usize, usize)>,
+}
+
+impl MoveSearch {
+    pub fn from_statistics(stats: &SearchStatistics) -> Self {
+        let mut visits: Vec<_> = stats
+            .visit_counts
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|&(_, visits)| visits > 0)
+            .collect();
+        visits.sort_by_key(|&(_, visits)| std::cmp::Reverse(visits));
+        Self {
+            value: stats.root_value,
+            visits,
+        }
+    }
+
+    pub fn from_result(result: &SearchResult<usize>) -> Self {
+        Self {
+            value: result.value(),
+            visits: result
+                .children
+                .iter()
+                .map(|child| (child.action, child.visits))
+                .collect(),
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_millis() as u64)
+}
+
+impl GameRecord {
+    pub fn new(game: &str) -> Self {
+        Self {
+            game: game.to_string(),
+            moves: vec![],
+        }
+    }
+
+    /// Record that `player` played `action` now.
+    pub fn push(&mut self, action: usize, player: Option<usize>, search: Option<MoveSearch>) {
+        self.moves.push(MoveRecord {
+            action,
+            player,
+            timestamp: now(),
+            search,
+        });
+    }
+
+    /// Every position of the game, from the start to after the last move.
+    pub fn positions<G: Game>(&self, mut game: G) -> Result<Vec<G>> {
+        let mut positions = vec![game.clone()];
+        for (i, record) in self.moves.iter().enumerate() {
+            if record.action >= game.action_space_size() {
+                return Err(ParseError::Invalid(format!(
+                    "move {}: invalid action {}",
+                    i + 1,
+                    record.action
+                ))
+                .into());
+            }
+            game.step(game.index_to_action(record.action))
+                .with_context(|| format!("move {}", i + 1))?;
+            positions.push(game.clone());
+        }
+        Ok(positions)
+    }
+
+    /// Add the record as a line to the end of the file at `path`, creating it.
+    #[cfg(feature = "serde")]
+    pub fn append(&self, path: &Path) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(IoError::path("open", path))?;
+        let line = serde_json::to_string(self)?;
+        writeln!(file, "{}", line).map_err(IoError::path("write", path))?;
+        Ok(())
+    }
+
+    /// Every game recorded in the file at `path`: an SGF collection if it ends in .sgf, and
+    /// JSON lines otherwise.
+    #[cfg(feature = "serde")]
+    pub fn load_all(path: &Path) -> Result<Vec<Self>> {
+        let text = fs::read_to_string(path).map_err(IoError::path("read", path))?;
+        if path.extension().is_some_and(|extension| extension == "sgf") {
+            return sgf::from_sgf(&text).with_context(|| format!("in {}", path.display()));
+        }
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line).with_context(|| format!("{}:{}", path.display(), i + 1))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::tic_tac_toe::TicTacToe;
+
+    #[test]
+    fn test_record() {
+        let mut record = GameRecord::new("tictactoe");
+        let stats = SearchStatistics {
+            root_value: 0.5,
+            visit_counts: vec![0, 0, 0, 0, 7, 0, 0, 0, 3],
+            depth: 2,
+            nodes: 3,
+            cache: None,
+        };
+        record.push(4, Some(0), Some(MoveSearch::from_statistics(&stats)));
+        record.push(0, Some(1), None);
+        assert_eq!(
+            record.moves[0].search,
+            Some(MoveSearch {
+                value: 0.5,
+                visits: vec![(4, 7), (8, 3)],
+            })
+        );
+        assert!(record.moves[0].timestamp <= record.moves[1].timestamp);
+
+        let positions = record.positions(TicTacToe::new()).unwrap();
+        assert_eq!(positions.len(), 3);
+        assert_eq!(positions[2].get_available_moves().len(), 7);
+
+        #[cfg(feature = "serde")]
+        {
+            let restored: GameRecord =
+                serde_json::from_str(&serde_json::to_string(&record).unwrap()).unwrap();
+            assert_eq!(restored, record);
+        }
+
+        record.push(4, Some(0), None);
+        assert!(record.positions(TicTacToe::new()).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_append() {
+        let path = std::env::temp_dir().join(format!("muzero-records-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut record = GameRecord::new("tictactoe");
+        record.append(&path).unwrap();
+        record.push(4, Some(0), None);
+        record.append(&path).unwrap();
+        let records = GameRecord::load_all(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1], record);
+    }
+}
@@ -0,0 +1,244 @@
+//! Games looked up by name at runtime, e.g. from the command line. A name may carry a
+//! parameter after a colon, like the board size in `gomoku:15`.
+
+#[cfg(feature = "atari")]
+use crate::games::atari::{Ale, AleConfig, Atari, AtariConfig};
+#[cfg(feature = "chess")]
+use crate::games::chess::Chess;
+#[cfg(feature = "gym")]
+use crate::games::gym::{self, GymConfig};
+use crate::{
+    dyn_game::{boxed, DynGame},
+    error::{Context, ParseError, Result},
+    games::{
+        cart_pole::CartPole,
+        checkers::Checkers,
+        connect_four::ConnectFour,
+        go::Go,
+        gomoku::{self, Gomoku},
+        gridworld::{Gridworld, GridworldConfig},
+        hex::{self, Hex},
+        nim::Nim,
+        othello::Othello,
+        tic_tac_toe::TicTacToe,
+        twenty_forty_eight::TwentyFortyEight,
+    },
+};
+
+/// Makes a new game from the parameter following the name, if any.
+pub type GameFactory = Box<dyn Fn(Option<&str>) -> Result<Box<dyn DynGame>>>;
+
+struct Entry {
+    name: String,
+    description: String,
+    factory: GameFactory,
+}
+
+pub struct Registry {
+    entries: Vec<Entry>,
+}
+
+impl Registry {
+    /// A registry without any game.
+    pub fn empty() -> Self {
+        Self { entries: vec![] }
+    }
+
+    /// Make `factory` available as `name`, replacing any game registered under it.
+    pub fn register(
+        &mut self,
+        name: &str,
+        description: &str,
+        factory: impl Fn(Option<&str>) -> Result<Box<dyn DynGame>> + 'static,
+    ) {
+        self.entries.retain(|entry| entry.name != name);
+        self.entries.push(Entry {
+            name: name.to_string(),
+            description: description.to_string(),
+            factory: Box::new(factory),
+        });
+    }
+
+    /// A new game from a name like `tictactoe` or `gomoku:15`.
+    pub fn create(&self, spec: &str) -> Result<Box<dyn DynGame>> {
+        let (name, parameter) = match spec.split_once(':') {
+            Some((name, parameter)) => (name, Some(parameter)),
+            None => (spec, None),
+        };
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.name == name)
+            .ok_or_else(|| ParseError::unknown("game", name))?;
+        (entry.factory)(parameter).with_context(|| format!("failed to make `{}`", spec))
+    }
+
+    /// The names and descriptions of the registered games.
+    pub fn games(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.description.as_str()))
+    }
+}
+
+fn no_parameter(parameter: Option<&str>) -> Result<()> {
+    match parameter {
+        Some(parameter) => {
+            Err(ParseError::Invalid(format!("unexpected parameter `{}`", parameter)).into())
+        }
+        None => Ok(()),
+    }
+}
+
+/// The largest board, whose points SGF can still name.
+const MAX_SIZE: usize = 26;
+
+/// The most actions of a game of Nim.
+const MAX_NIM_ACTIONS: usize = 1 << 16;
+
+/// The board size in `parameter`, or `default`.
+fn size(parameter: Option<&str>, default: usize) -> Result<usize> {
+    let size = parameter.map_or(Ok(default), |size| size.parse())?;
+    if size == 0 {
+        return Err(ParseError::Invalid("the board can't be empty".to_string()).into());
+    }
+    if size > MAX_SIZE {
+        return Err(
+            ParseError::Invalid(format!("boards are at most {}x{}", MAX_SIZE, MAX_SIZE)).into(),
+        );
+    }
+    Ok(size)
+}
+
+/// Every game of the crate.
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register("tictactoe", "Tic-tac-toe", |parameter| {
+            no_parameter(parameter)?;
+            Ok(boxed(TicTacToe::new()))
+        });
+        registry.register("connect4", "Connect Four", |parameter| {
+            no_parameter(parameter)?;
+            Ok(boxed(ConnectFour::new()))
+        });
+        registry.register("gomoku", "Gomoku, gomoku:<size> [15]", |parameter| {
+            Ok(boxed(Gomoku::new(size(parameter, gomoku::DEFAULT_SIZE)?)))
+        });
+        registry.register("othello", "Othello", |parameter| {
+            no_parameter(parameter)?;
+            Ok(boxed(Othello::new()))
+        });
+        registry.register("hex", "Hex, hex:<size> [11]", |parameter| {
+            Ok(boxed(Hex::new(size(parameter, hex::DEFAULT_SIZE)?)))
+        });
+        registry.register("checkers", "Checkers", |parameter| {
+            no_parameter(parameter)?;
+            Ok(boxed(Checkers::new()))
+        });
+        #[cfg(feature = "chess")]
+        registry.register("chess", "Chess", |parameter| {
+            no_parameter(parameter)?;
+            Ok(boxed(Chess::new()))
+        });
+        registry.register("go", "Go, go:<size> [9]", |parameter| {
+            Ok(boxed(Go::new(size(parameter, 9)?)))
+        });
+        registry.register("nim", "Nim, nim:<heap>,<heap>,... [3,4,5]", |parameter| {
+            let heaps: Vec<usize> = parameter
+                .unwrap_or("3,4,5")
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<_, _>>()?;
+            let max_heap = heaps.iter().copied().max().unwrap_or(0);
+            if heaps.len().saturating_mul(max_heap) > MAX_NIM_ACTIONS {
+                return Err(
+                    ParseError::Invalid(format!("more than {} actions", MAX_NIM_ACTIONS)).into(),
+                );
+            }
+            Ok(boxed(Nim::new(heaps)))
+        });
+        registry.register("2048", "2048", |parameter| {
+            no_parameter(parameter)?;
+            Ok(boxed(TwentyFortyEight::new()))
+        });
+        registry.register("cartpole", "CartPole", |parameter| {
+            no_parameter(parameter)?;
+            Ok(boxed(CartPole::new()))
+        });
+        registry.register("gridworld", "The 4x4 FrozenLake gridworld", |parameter| {
+            no_parameter(parameter)?;
+            Ok(boxed(Gridworld::four_by_four(GridworldConfig::default())))
+        });
+        #[cfg(feature = "gym")]
+        registry.register(
+            "gym",
+            "A Gymnasium environment, gym:<env id> [CartPole-v1]",
+            |parameter| {
+                let mut config = GymConfig::default();
+                if let Some(env_id) = parameter {
+                    config.env_id = env_id.to_string();
+                }
+                Ok(boxed(gym::make(&config)?))
+            },
+        );
+        #[cfg(feature = "atari")]
+        registry.register(
+            "atari",
+            "An Atari game of ale_py, atari:<rom> [breakout]",
+            |parameter| {
+                let mut config = AleConfig::default();
+                if let Some(rom) = parameter {
+                    config.rom = rom.to_string();
+                }
+                Ok(boxed(Atari::new(
+                    Ale::new(&config)?,
+                    AtariConfig::default(),
+                )?))
+            },
+        );
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create() {
+        let registry = Registry::default();
+        let game = registry.create("tictactoe").unwrap();
+        assert_eq!(game.action_space_size(), 9);
+        let game = registry.create("connect4").unwrap();
+        assert_eq!(game.action_space_size(), 7);
+        let game = registry.create("gomoku:9").unwrap();
+        assert_eq!(game.action_space_size(), 81);
+        let game = registry.create("nim:1,2").unwrap();
+        assert_eq!(game.legal_actions().len(), 3);
+
+        for spec in [
+            "shogi",
+            "gomoku:big",
+            "gomoku:0",
+            "tictactoe:4",
+            "nim:1,,2",
+            "go:27",
+            "nim:1,99999",
+        ] {
+            assert!(registry.create(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn test_register() {
+        let mut registry = Registry::empty();
+        assert!(registry.create("tictactoe").is_err());
+        registry.register("ttt", "Tic-tac-toe", |_| Ok(boxed(TicTacToe::new())));
+        assert!(registry.create("ttt").is_ok());
+        assert_eq!(
+            registry.games().collect::<Vec<_>>(),
+            vec![("ttt", "Tic-tac-toe")]
+        );
+    }
+}
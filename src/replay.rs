@@ -0,0 +1,1092 @@
+//! The replay buffer MuZero trains from, and Reanalyze (Schrittwieser et al., 2021), which
+//! searches stored games again with the latest network to refresh their policy and value
+//! targets.
+
+use rand::{seq::SliceRandom, Rng};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+#[cfg(unix)]
+use crate::storage::TrajectoryFile;
+use crate::{
+    error::{ConfigError, Result},
+    history::GameHistory,
+    muzero::{run_mcts, MuZeroConfig},
+    network::{Network, Support},
+    observation::{FrameStacking, ObservationStacker},
+    symmetry::Transform,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplayConfig {
+    /// The number of games kept; the oldest are dropped first.
+    pub window_size: usize,
+    pub batch_size: usize,
+    /// The number of actions the model unrolls from each sampled position.
+    pub num_unroll_steps: usize,
+    pub td_steps: usize,
+    pub discount: f32,
+    /// The fraction of every batch sampled from reanalyzed games, while there are any.
+    pub reanalyze_fraction: f32,
+    /// Sample positions by priority instead of uniformly.
+    pub priority: Option<Priority>,
+    pub absorbing_policy: AbsorbingPolicy,
+    /// Make value prefix targets, for a network whose LSTM is reset every this many steps
+    /// of the unroll, instead of per-step rewards.
+    pub value_prefix_horizon: Option<usize>,
+    /// Also sample the observations following the unrolled actions, the targets of the
+    /// consistency loss of EfficientZero.
+    pub consistency: bool,
+    /// Stack the observations sampled with the ones and the actions before them.
+    pub frame_stacking: Option<FrameStacking>,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self {
+            window_size: 1_000_000,
+            batch_size: 1024,
+            num_unroll_steps: 5,
+            td_steps: 10,
+            discount: 0.997,
+            reanalyze_fraction: 0.,
+            priority: None,
+            absorbing_policy: AbsorbingPolicy::Masked,
+            value_prefix_horizon: None,
+            consistency: false,
+            frame_stacking: None,
+        }
+    }
+}
+
+/// The training targets of one step of the unrolled model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub value: f32,
+    /// The reward of the action leading to the step, or with
+    /// [`ReplayConfig::value_prefix_horizon`], the value prefix: the rewards summed since the
+    /// last reset of the LSTM.
+    pub reward: f32,
+    /// Past the end of the game, as [`ReplayConfig::absorbing_policy`] says.
+    pub policy: Vec<f32>,
+    /// Whether the step is past the end of an episode that terminated, for training a
+    /// terminal head. Its value and reward are zero, except the reward of the last move.
+    pub absorbing: bool,
+}
+
+impl Target {
+    /// The value as a distribution over `support`, for a categorical value head.
+    pub fn value_probs(&self, support: &Support) -> Vec<f32> {
+        support.encode(self.value)
+    }
+
+    /// The reward as a distribution over `support`, for a categorical reward head.
+    pub fn reward_probs(&self, support: &Support) -> Vec<f32> {
+        support.encode(self.reward)
+    }
+}
+
+/// A position to train on: the observation, the actions to unroll, and a target for the
+/// position and for each step after it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub observation: Vec<f32>,
+    pub actions: Vec<usize>,
+    pub targets: Vec<Target>,
+    /// With [`ReplayConfig::consistency`], the observation after each of `actions`, as far
+    /// as the game recorded them.
+    pub next_observations: Vec<Vec<f32>>,
+    /// The game and the index of the position in it, to update its priority.
+    pub game_id: u64,
+    pub index: usize,
+    /// The importance-sampling weight of the sample's loss, correcting for prioritized
+    /// sampling; 1 without priorities.
+    pub weight: f32,
+}
+
+/// The policy target of absorbing states past the end of an episode.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum AbsorbingPolicy {
+    /// No target, so no policy loss.
+    #[default]
+    Masked,
+    /// Uniform over the action space.
+    Uniform,
+}
+
+/// Prioritized replay as in MuZero (Appendix G): position `i` is sampled with probability
+/// `P(i) = p_i^α / Σ p^α`, where `p_i` is how far its search value is from its n-step return,
+/// and its loss is scaled by `(N P(i))^-β`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Priority {
+    pub alpha: f32,
+    pub beta: f32,
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Self {
+            alpha: 1.,
+            beta: 1.,
+        }
+    }
+}
+
+/// Keeps positions whose value was predicted exactly sampleable.
+const MIN_PRIORITY: f32 = 1e-6;
+
+/// A binary tree whose inner nodes hold the sums of their children, to sample leaves in
+/// proportion to their values in O(log n).
+#[derive(Debug, Clone)]
+struct SumTree {
+    /// The root at 1, the children of node `i` at `2i` and `2i + 1`, and the leaves from
+    /// `capacity`.
+    nodes: Vec<f64>,
+}
+
+impl SumTree {
+    fn new(len: usize) -> Self {
+        Self {
+            nodes: vec![0.; 2 * len.next_power_of_two()],
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.nodes.len() / 2
+    }
+
+    fn total(&self) -> f64 {
+        self.nodes[1]
+    }
+
+    fn get(&self, leaf: usize) -> f64 {
+        self.nodes
+            .get(self.capacity() + leaf)
+            .copied()
+            .unwrap_or(0.)
+    }
+
+    fn set(&mut self, leaf: usize, value: f64) {
+        if leaf >= self.capacity() {
+            let mut grown = SumTree::new(leaf + 1);
+            for i in 0..self.capacity() {
+                grown.set(i, self.get(i));
+            }
+            *self = grown;
+        }
+        let mut node = self.capacity() + leaf;
+        self.nodes[node] = value;
+        while node > 1 {
+            node /= 2;
+            self.nodes[node] = self.nodes[2 * node] + self.nodes[2 * node + 1];
+        }
+    }
+
+    /// The leaf where the running sum of the leaves passes `mass`, in `0..total`.
+    fn find(&self, mut mass: f64) -> usize {
+        let mut node = 1;
+        while node < self.capacity() {
+            let left = 2 * node;
+            // Rounding may leave `mass` past the last nonzero leaf.
+            if mass < self.nodes[left] || self.nodes[left + 1] == 0. {
+                node = left;
+            } else {
+                mass -= self.nodes[left];
+                node = left + 1;
+            }
+        }
+        node - self.capacity()
+    }
+}
+
+/// Where the game of an entry is kept.
+enum Stored {
+    Memory(GameHistory),
+    /// A record of the buffer's trajectory file.
+    #[cfg(unix)]
+    Disk(usize),
+}
+
+struct Entry {
+    id: u64,
+    history: Stored,
+    /// The length of the game.
+    len: usize,
+    /// Whether the game was recorded with its legal actions, which reanalyzing it needs.
+    reanalyzable: bool,
+    /// How many times the game was reanalyzed.
+    reanalyzed: usize,
+    /// `p^α` of every position.
+    priorities: SumTree,
+}
+
+/// What a checkpoint keeps of a [`ReplayBuffer`], see [`ReplayBuffer::snapshot`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Snapshot {
+    /// The number of games ever added.
+    pub games: u64,
+    entries: Vec<SnapshotEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct SnapshotEntry {
+    id: u64,
+    reanalyzed: usize,
+    priorities: Vec<f64>,
+}
+
+pub struct ReplayBuffer {
+    config: ReplayConfig,
+    entries: VecDeque<Entry>,
+    next_id: u64,
+    /// The total `p^α` of every game, by slot `id % window_size`, for fresh and for
+    /// reanalyzed games.
+    fresh: SumTree,
+    reanalyzed: SumTree,
+    /// Every sample is put on the board under a random one of these.
+    symmetries: Vec<Transform>,
+    /// Where the games are kept instead of memory, if anywhere.
+    #[cfg(unix)]
+    storage: Option<TrajectoryFile>,
+}
+
+impl ReplayBuffer {
+    pub fn new(config: ReplayConfig) -> Self {
+        Self {
+            config,
+            entries: VecDeque::new(),
+            next_id: 0,
+            fresh: SumTree::new(1),
+            reanalyzed: SumTree::new(1),
+            symmetries: vec![],
+            #[cfg(unix)]
+            storage: None,
+        }
+    }
+
+    /// Augment the samples with the symmetries of the game, e.g. [`Symmetry::symmetries`].
+    ///
+    /// [`Symmetry::symmetries`]: crate::Symmetry::symmetries
+    pub fn with_symmetries(mut self, symmetries: Vec<Transform>) -> Self {
+        self.symmetries = symmetries;
+        self
+    }
+
+    /// Keep the games in `storage` instead of memory, starting with the last window of games
+    /// it already holds, e.g. from before a restart. Reanalyzed games are appended to it again.
+    #[cfg(unix)]
+    pub fn with_storage(mut self, storage: TrajectoryFile) -> Result<Self> {
+        let start = storage.len().saturating_sub(self.config.window_size);
+        let records = start..storage.len();
+        self.storage = Some(storage);
+        for record in records {
+            // Read every game once here, so that reading it again when sampling can't fail.
+            let history = self.storage.as_ref().unwrap().get(record)?;
+            self.push(
+                Stored::Disk(record),
+                history.len(),
+                history.legal_actions.len() == history.len(),
+            );
+        }
+        Ok(self)
+    }
+
+    /// Add a game, to the storage if there is one. If writing it fails, the game is kept in
+    /// memory instead.
+    pub fn save_game(&mut self, history: GameHistory) {
+        let (len, reanalyzable) = (history.len(), history.legal_actions.len() == history.len());
+        let history = self.store(history);
+        self.push(history, len, reanalyzable);
+    }
+
+    #[cfg(not(unix))]
+    fn store(&mut self, history: GameHistory) -> Stored {
+        Stored::Memory(history)
+    }
+
+    #[cfg(unix)]
+    fn store(&mut self, history: GameHistory) -> Stored {
+        let Some(storage) = &mut self.storage else {
+            return Stored::Memory(history);
+        };
+        match storage.append(&history) {
+            Ok(record) => Stored::Disk(record),
+            Err(e) => {
+                tracing::warn!("{:#}, keeping the game in memory", e);
+                Stored::Memory(history)
+            }
+        }
+    }
+
+    fn push(&mut self, history: Stored, len: usize, reanalyzable: bool) {
+        if self.entries.len() == self.config.window_size {
+            if let Some(entry) = self.entries.pop_front() {
+                let slot = self.slot(entry.id);
+                self.fresh.set(slot, 0.);
+                self.reanalyzed.set(slot, 0.);
+            }
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.push_back(Entry {
+            id,
+            history,
+            len,
+            reanalyzable,
+            reanalyzed: 0,
+            priorities: SumTree::new(1),
+        });
+        self.reprioritize(id);
+    }
+
+    /// The number of games.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn num_positions(&self) -> usize {
+        self.entries.iter().map(|entry| entry.len).sum()
+    }
+
+    /// The game of `entry`.
+    fn history<'a>(&'a self, entry: &'a Entry) -> Cow<'a, GameHistory> {
+        match &entry.history {
+            Stored::Memory(history) => Cow::Borrowed(history),
+            #[cfg(unix)]
+            Stored::Disk(record) => Cow::Owned(
+                self.storage
+                    .as_ref()
+                    .and_then(|storage| storage.get(*record).ok())
+                    .expect("stored games are readable"),
+            ),
+        }
+    }
+
+    fn slot(&self, id: u64) -> usize {
+        (id % self.config.window_size as u64) as usize
+    }
+
+    fn entry(&self, id: u64) -> Option<&Entry> {
+        let first = self.entries.front()?.id;
+        self.entries
+            .get(usize::try_from(id.checked_sub(first)?).ok()?)
+    }
+
+    fn entry_mut(&mut self, id: u64) -> Option<&mut Entry> {
+        let first = self.entries.front()?.id;
+        self.entries
+            .get_mut(usize::try_from(id.checked_sub(first)?).ok()?)
+    }
+
+    /// Set the priorities of game `id` from how far its search values are from its n-step
+    /// returns.
+    fn reprioritize(&mut self, id: u64) {
+        let ReplayConfig {
+            td_steps, discount, ..
+        } = self.config;
+        let Some(entry) = self.entry(id) else {
+            return;
+        };
+        let history = self.history(entry);
+        let priorities: Vec<_> = (0..history.len())
+            .map(|i| (history.root_values[i] - history.value_target(i, td_steps, discount)).abs())
+            .collect();
+        let updates: Vec<_> = priorities.into_iter().enumerate().collect();
+        self.update_priorities(id, &updates);
+    }
+
+    /// Set the priorities `p` of positions of game `id`, e.g. to how far the network's value
+    /// predictions were from the targets. Does nothing if the game left the window.
+    pub fn update_priorities(&mut self, id: u64, priorities: &[(usize, f32)]) {
+        let alpha = self.config.priority.unwrap_or_default().alpha;
+        let Some(entry) = self.entry_mut(id) else {
+            return;
+        };
+        for &(index, priority) in priorities {
+            let priority = priority.max(MIN_PRIORITY).powf(alpha);
+            entry.priorities.set(index, priority as f64);
+        }
+        self.update_pools(id);
+    }
+
+    /// Move the total priority of game `id` into the pool of its kind.
+    fn update_pools(&mut self, id: u64) {
+        let slot = self.slot(id);
+        let Some(entry) = self.entry(id) else {
+            return;
+        };
+        let (total, reanalyzed) = (entry.priorities.total(), entry.reanalyzed > 0);
+        let (pool, other) = if reanalyzed {
+            (&mut self.reanalyzed, &mut self.fresh)
+        } else {
+            (&mut self.fresh, &mut self.reanalyzed)
+        };
+        pool.set(slot, total);
+        other.set(slot, 0.);
+    }
+
+    /// What a checkpoint needs to restore the buffer after the same games are added again:
+    /// how many games were added, and the priorities and reanalyze counts of the window.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            games: self.next_id,
+            entries: self
+                .entries
+                .iter()
+                .map(|entry| SnapshotEntry {
+                    id: entry.id,
+                    reanalyzed: entry.reanalyzed,
+                    priorities: (0..entry.len).map(|i| entry.priorities.get(i)).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Restore the priorities and reanalyze counts of [`ReplayBuffer::snapshot`], which must
+    /// be of a buffer that had the same games added.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<()> {
+        if snapshot.games != self.next_id {
+            return Err(ConfigError::Invalid(format!(
+                "the snapshot is of {} games, the buffer has had {}",
+                snapshot.games, self.next_id
+            ))
+            .into());
+        }
+        if snapshot.entries.len() != self.entries.len() {
+            return Err(
+                ConfigError::Invalid("the snapshot has a different window".to_string()).into(),
+            );
+        }
+        for (saved, entry) in snapshot.entries.iter().zip(&mut self.entries) {
+            if saved.id != entry.id || saved.priorities.len() != entry.len {
+                return Err(
+                    ConfigError::Invalid("the snapshot is of different games".to_string()).into(),
+                );
+            }
+            entry.reanalyzed = saved.reanalyzed;
+            for (i, &priority) in saved.priorities.iter().enumerate() {
+                entry.priorities.set(i, priority);
+            }
+        }
+        let ids: Vec<_> = self.entries.iter().map(|entry| entry.id).collect();
+        for id in ids {
+            self.update_pools(id);
+        }
+        Ok(())
+    }
+
+    /// Sample a batch of positions: with priorities, in proportion to them, and otherwise
+    /// uniformly over games and then over their positions. `reanalyze_fraction` of them come
+    /// from reanalyzed games and the rest from fresh ones, unless one of the two kinds is
+    /// missing.
+    pub fn sample_batch(&self, rng: &mut impl Rng) -> Vec<Sample> {
+        let (reanalyzed, fresh): (Vec<_>, Vec<_>) = self
+            .entries
+            .iter()
+            .filter(|entry| entry.len > 0)
+            .partition(|entry| entry.reanalyzed > 0);
+        let batch_size = self.config.batch_size;
+        let num_reanalyzed = if reanalyzed.is_empty() {
+            0
+        } else if fresh.is_empty() {
+            batch_size
+        } else {
+            (self.config.reanalyze_fraction * batch_size as f32).round() as usize
+        };
+        let mut batch = Vec::with_capacity(batch_size);
+        for (pool, tree, count) in [
+            (reanalyzed, &self.reanalyzed, num_reanalyzed),
+            (fresh, &self.fresh, batch_size - num_reanalyzed),
+        ] {
+            if pool.is_empty() {
+                continue;
+            }
+            let num_positions: usize = pool.iter().map(|entry| entry.len).sum();
+            for _ in 0..count {
+                let (entry, index, weight) = match self.config.priority {
+                    None => {
+                        let entry = pool.choose(rng).unwrap();
+                        (*entry, rng.gen_range(0..entry.len), 1.)
+                    }
+                    Some(priority) => {
+                        let slot = tree.find(rng.gen::<f64>() * tree.total());
+                        let first = self.entries[0].id;
+                        let offset = (slot + self.config.window_size - self.slot(first))
+                            % self.config.window_size;
+                        let entry = &self.entries[offset];
+                        let priorities = &entry.priorities;
+                        let index = priorities.find(rng.gen::<f64>() * priorities.total());
+                        let probability = priorities.get(index) / tree.total();
+                        let weight =
+                            (num_positions as f64 * probability).powf(-priority.beta as f64);
+                        (entry, index, weight as f32)
+                    }
+                };
+                let sample = self.make_sample(entry, index, weight, rng);
+                batch.push(match self.symmetries.choose(rng) {
+                    Some(transform) => transform.sample(&sample),
+                    None => sample,
+                });
+            }
+        }
+        // Scale the weights so they only ever decrease the loss.
+        let max_weight = batch.iter().map(|sample| sample.weight).fold(0., f32::max);
+        for sample in &mut batch {
+            sample.weight /= max_weight;
+        }
+        batch
+    }
+
+    /// The sample at `index`, with the actions padded with random ones past the end of the
+    /// game.
+    fn make_sample(&self, entry: &Entry, index: usize, weight: f32, rng: &mut impl Rng) -> Sample {
+        let ReplayConfig {
+            num_unroll_steps,
+            td_steps,
+            discount,
+            value_prefix_horizon,
+            consistency,
+            absorbing_policy,
+            frame_stacking,
+            ..
+        } = self.config;
+        let history = &*self.history(entry);
+        let action_space_size = history.child_visits[0].len().max(1);
+        let actions = (index..index + num_unroll_steps)
+            .map(|i| match history.actions.get(i) {
+                Some(&action) => action,
+                None => rng.gen_range(0..action_space_size),
+            })
+            .collect();
+        let mut value_prefix = 0.;
+        let targets = (index..=index + num_unroll_steps)
+            .map(|i| {
+                let mut reward = match i {
+                    0 => 0.,
+                    i => history.rewards.get(i - 1).copied().unwrap_or(0.),
+                };
+                if let Some(horizon) = value_prefix_horizon {
+                    let step = i - index;
+                    value_prefix = match step {
+                        0 => 0.,
+                        _ if (step - 1).is_multiple_of(horizon) => reward,
+                        _ => value_prefix + reward,
+                    };
+                    reward = value_prefix;
+                }
+                if i < history.len() {
+                    Target {
+                        value: history.value_target(i, td_steps, discount),
+                        reward,
+                        policy: history.child_visits[i].clone(),
+                        absorbing: false,
+                    }
+                } else {
+                    Target {
+                        value: 0.,
+                        reward,
+                        policy: match absorbing_policy {
+                            AbsorbingPolicy::Masked => vec![],
+                            AbsorbingPolicy::Uniform => {
+                                vec![1. / action_space_size as f32; action_space_size]
+                            }
+                        },
+                        absorbing: history.final_value.is_none(),
+                    }
+                }
+            })
+            .collect();
+        let observation = |i: usize| match frame_stacking {
+            Some(stacking) => {
+                ObservationStacker::from_history(history, i, stacking, action_space_size)
+            }
+            None => history.observations[i].clone(),
+        };
+        let next_observations = match consistency {
+            true => (index + 1..history.observations.len())
+                .take(num_unroll_steps)
+                .map(observation)
+                .collect(),
+            false => vec![],
+        };
+        Sample {
+            observation: observation(index),
+            actions,
+            targets,
+            next_observations,
+            game_id: entry.id,
+            index,
+            weight,
+        }
+    }
+
+    /// The game reanalyzed the fewest times, oldest first, skipping games recorded without
+    /// their legal actions.
+    fn next_to_reanalyze(&self) -> Option<(u64, GameHistory)> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.reanalyzable)
+            .min_by_key(|entry| (entry.reanalyzed, entry.id))
+            .map(|entry| (entry.id, self.history(entry).into_owned()))
+    }
+
+    /// Replace the targets of game `id`, unless it left the window meanwhile.
+    fn update_reanalyzed(&mut self, id: u64, history: GameHistory) {
+        if self.entry(id).is_none() {
+            return;
+        }
+        let history = self.store(history);
+        if let Some(entry) = self.entry_mut(id) {
+            entry.history = history;
+            entry.reanalyzed += 1;
+            self.reprioritize(id);
+        }
+    }
+}
+
+/// Search every position of `history` again with `network`, replacing the visit
+/// distributions and root values the targets are made of.
+pub fn reanalyze<N: Network>(
+    history: &mut GameHistory,
+    config: &MuZeroConfig,
+    network: &N,
+) -> Result<()> {
+    if history.legal_actions.len() != history.len() {
+        return Err(ConfigError::Invalid(
+            "the game was recorded without its legal actions".to_string(),
+        )
+        .into());
+    }
+    for i in 0..history.len() {
+        let stats = run_mcts(
+            config,
+            network,
+            &history.observations[i],
+            &history.legal_actions[i],
+            history.to_play[i],
+        );
+        history.child_visits[i] = stats.policy();
+        history.root_values[i] = stats.root_value;
+    }
+    Ok(())
+}
+
+/// Reanalyzes the games of a replay buffer in the background, one at a time, while the
+/// learner samples from it.
+pub struct Reanalyzer {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Result<usize>>,
+}
+
+impl Reanalyzer {
+    /// Start reanalyzing with `network`, e.g. an
+    /// [`InferenceClient`](crate::inference::InferenceClient) of the latest weights.
+    pub fn spawn<N: Network + Send + 'static>(
+        buffer: Arc<Mutex<ReplayBuffer>>,
+        network: N,
+        config: MuZeroConfig,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let thread = thread::spawn(move || {
+            let mut games = 0;
+            while !stopped.load(Ordering::Relaxed) {
+                let next = buffer.lock().unwrap().next_to_reanalyze();
+                let Some((id, mut history)) = next else {
+                    thread::sleep(Duration::from_millis(10));
+                    continue;
+                };
+                reanalyze(&mut history, &config, &network)?;
+                buffer.lock().unwrap().update_reanalyzed(id, history);
+                games += 1;
+            }
+            Ok(games)
+        });
+        Self { stop, thread }
+    }
+
+    /// Stop after the game in progress. Returns the number of games reanalyzed.
+    pub fn stop(self) -> Result<usize> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.thread.join().unwrap_or_else(|_| {
+            Err(ConfigError::Invalid("the reanalyzer panicked".to_string()).into())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::UniformNetwork;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::time::Instant;
+
+    /// A single-player game of `len` moves whose observations are all `[marker]`.
+    fn history(len: usize, marker: f32) -> GameHistory {
+        GameHistory {
+            observations: vec![vec![marker]; len],
+            to_play: vec![0; len],
+            actions: vec![1; len],
+            rewards: (1..=len).map(|reward| reward as f32).collect(),
+            child_visits: vec![vec![0., 1., 0.]; len],
+            root_values: vec![5.; len],
+            final_value: None,
+            legal_actions: vec![vec![0, 1, 2]; len],
+        }
+    }
+
+    fn config() -> ReplayConfig {
+        ReplayConfig {
+            window_size: 3,
+            batch_size: 10,
+            num_unroll_steps: 2,
+            td_steps: 1,
+            discount: 1.,
+            reanalyze_fraction: 0.,
+            priority: None,
+            absorbing_policy: AbsorbingPolicy::Masked,
+            value_prefix_horizon: None,
+            consistency: false,
+            frame_stacking: None,
+        }
+    }
+
+    #[test]
+    fn test_sample() {
+        let mut buffer = ReplayBuffer::new(config());
+        buffer.save_game(history(2, 0.));
+        let sample = buffer.make_sample(&buffer.entries[0], 1, 1., &mut rand::thread_rng());
+        assert_eq!(sample.actions[0], 1);
+        assert_eq!(
+            sample.targets,
+            [
+                Target {
+                    value: 2.,
+                    reward: 1.,
+                    policy: vec![0., 1., 0.],
+                    absorbing: false,
+                },
+                Target {
+                    value: 0.,
+                    reward: 2.,
+                    policy: vec![],
+                    absorbing: true,
+                },
+                Target {
+                    value: 0.,
+                    reward: 0.,
+                    policy: vec![],
+                    absorbing: true,
+                },
+            ]
+        );
+        assert!(sample.next_observations.is_empty());
+        assert_eq!(buffer.sample_batch(&mut rand::thread_rng()).len(), 10);
+        let support = Support { size: 5 };
+        assert_eq!(sample.targets[1].reward_probs(&support), support.encode(2.));
+    }
+
+    #[test]
+    fn test_sum_tree() {
+        let mut tree = SumTree::new(3);
+        assert_eq!(tree.capacity(), 4);
+        for (leaf, value) in [(0, 1.), (1, 0.), (2, 3.)] {
+            tree.set(leaf, value);
+        }
+        assert_eq!(tree.total(), 4.);
+        assert_eq!(
+            [0., 0.99, 1., 3.99].map(|mass| tree.find(mass)),
+            [0, 0, 2, 2]
+        );
+        // Past the total, the last nonzero leaf.
+        assert_eq!(tree.find(4.5), 2);
+        tree.set(5, 4.);
+        assert_eq!(tree.capacity(), 8);
+        assert_eq!(tree.total(), 8.);
+        assert_eq!(tree.get(2), 3.);
+        assert_eq!(tree.find(5.), 5);
+    }
+
+    #[test]
+    fn test_absorbing_states() {
+        let mut buffer = ReplayBuffer::new(ReplayConfig {
+            absorbing_policy: AbsorbingPolicy::Uniform,
+            ..config()
+        });
+        buffer.save_game(history(1, 0.));
+        let mut truncated = history(1, 0.);
+        truncated.truncate(3.);
+        buffer.save_game(truncated);
+        let mut rng = rand::thread_rng();
+        let sample = buffer.make_sample(&buffer.entries[0], 0, 1., &mut rng);
+        let past_end = &sample.targets[1];
+        assert_eq!(past_end.policy, [1. / 3.; 3]);
+        assert!(past_end.absorbing);
+        assert_eq!((past_end.value, past_end.reward), (0., 1.));
+        assert_eq!(sample.targets[2].reward, 0.);
+        // A truncated episode didn't end, so nothing past it is absorbing.
+        let sample = buffer.make_sample(&buffer.entries[1], 0, 1., &mut rng);
+        assert!(sample.targets.iter().all(|target| !target.absorbing));
+    }
+
+    #[test]
+    fn test_value_prefix() {
+        let mut buffer = ReplayBuffer::new(ReplayConfig {
+            num_unroll_steps: 4,
+            value_prefix_horizon: Some(2),
+            ..config()
+        });
+        buffer.save_game(history(5, 0.));
+        let sample = buffer.make_sample(&buffer.entries[0], 0, 1., &mut rand::thread_rng());
+        let rewards: Vec<_> = sample.targets.iter().map(|target| target.reward).collect();
+        // The rewards are 1 to 5, and the prefix starts over after two steps.
+        assert_eq!(rewards, [0., 1., 1. + 2., 3., 3. + 4.]);
+    }
+
+    #[test]
+    fn test_next_observations() {
+        let mut buffer = ReplayBuffer::new(ReplayConfig {
+            consistency: true,
+            ..config()
+        });
+        buffer.save_game(GameHistory {
+            observations: vec![vec![0.], vec![1.], vec![2.]],
+            ..history(3, 0.)
+        });
+        let mut rng = rand::thread_rng();
+        let entry = &buffer.entries[0];
+        let sample = buffer.make_sample(entry, 0, 1., &mut rng);
+        assert_eq!(sample.next_observations, [[1.], [2.]]);
+        // The observation after the last move isn't recorded.
+        let sample = buffer.make_sample(entry, 2, 1., &mut rng);
+        assert!(sample.next_observations.is_empty());
+    }
+
+    #[test]
+    fn test_frame_stacking() {
+        let mut buffer = ReplayBuffer::new(ReplayConfig {
+            consistency: true,
+            frame_stacking: Some(FrameStacking {
+                frames: 2,
+                planes: 1,
+            }),
+            ..config()
+        });
+        buffer.save_game(GameHistory {
+            observations: vec![vec![0.], vec![1.], vec![2.]],
+            ..history(3, 0.)
+        });
+        let sample = buffer.make_sample(&buffer.entries[0], 1, 1., &mut rand::thread_rng());
+        // Every action of the game is 1, out of 3.
+        let plane = 2. / 3.;
+        assert_eq!(sample.observation, [0., 0., 1., plane]);
+        assert_eq!(sample.next_observations, [[1., plane, 2., plane]]);
+    }
+
+    #[test]
+    fn test_symmetries() {
+        let mut buffer = ReplayBuffer::new(config()).with_symmetries(Transform::dihedral(2, 4));
+        buffer.save_game(GameHistory {
+            observations: vec![vec![1., 0., 0., 0.]],
+            child_visits: vec![vec![1., 0., 0., 0.]],
+            ..history(1, 0.)
+        });
+        let mut corners = [0; 4];
+        for sample in buffer.sample_batch(&mut rand::thread_rng()) {
+            let corner = sample.observation.iter().position(|&x| x == 1.).unwrap();
+            // The policy moves with the board.
+            assert_eq!(sample.targets[0].policy[corner], 1.);
+            corners[corner] += 1;
+        }
+        assert_eq!(corners.iter().sum::<usize>(), 10);
+    }
+
+    fn count_in(batch: &[Sample], marker: f32) -> f32 {
+        let samples = batch.iter().filter(|sample| sample.observation == [marker]);
+        samples.count() as f32 / batch.len() as f32
+    }
+
+    #[test]
+    fn test_priorities() {
+        let mut buffer = ReplayBuffer::new(ReplayConfig {
+            batch_size: 1000,
+            priority: Some(Priority::default()),
+            ..config()
+        });
+        // Leaves the window.
+        buffer.save_game(history(2, 0.));
+        // The search values are 5 and the returns 1, so the priorities are 4.
+        for _ in 0..2 {
+            buffer.save_game(history(1, 1.));
+        }
+        // Predicted exactly.
+        buffer.save_game(GameHistory {
+            root_values: vec![1.],
+            ..history(1, 2.)
+        });
+        assert_eq!(buffer.len(), 3);
+        let mut rng = StdRng::seed_from_u64(0);
+        let batch = buffer.sample_batch(&mut rng);
+        assert_eq!(count_in(&batch, 1.), 1.);
+        assert!(batch.iter().all(|sample| sample.weight == 1.));
+
+        buffer.update_priorities(3, &[(0, 12.)]);
+        let batch = buffer.sample_batch(&mut rng);
+        assert!((count_in(&batch, 2.) - 0.6).abs() < 0.05);
+        for sample in &batch {
+            // The weights are inversely proportional to the priorities.
+            let expected = if sample.observation == [2.] {
+                1. / 3.
+            } else {
+                1.
+            };
+            assert!((sample.weight - expected).abs() < 1e-4, "{}", sample.weight);
+        }
+        buffer.update_priorities(0, &[(0, 100.)]);
+        assert_eq!(buffer.fresh.total(), 20.);
+    }
+
+    #[test]
+    fn test_window() {
+        let mut buffer = ReplayBuffer::new(config());
+        for len in 1..=4 {
+            buffer.save_game(history(len, 0.));
+        }
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.num_positions(), 2 + 3 + 4);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let config = ReplayConfig {
+            priority: Some(Priority::default()),
+            ..config()
+        };
+        let fill = || {
+            let mut buffer = ReplayBuffer::new(config);
+            for len in 1..=4 {
+                buffer.save_game(history(len, len as f32));
+            }
+            buffer
+        };
+        let mut buffer = fill();
+        buffer.update_priorities(2, &[(0, 100.), (2, 0.)]);
+        buffer.entries[0].reanalyzed = 1;
+        buffer.update_pools(1);
+        let snapshot = buffer.snapshot();
+        #[cfg(feature = "serde")]
+        let snapshot: Snapshot =
+            serde_json::from_str(&serde_json::to_string(&snapshot).unwrap()).unwrap();
+
+        let mut restored = fill();
+        restored.restore(&snapshot).unwrap();
+        let sample = |buffer: &ReplayBuffer| buffer.sample_batch(&mut StdRng::seed_from_u64(7));
+        assert_eq!(sample(&restored), sample(&buffer));
+        assert_eq!(restored.entries[0].reanalyzed, 1);
+
+        let mut fewer = ReplayBuffer::new(config);
+        fewer.save_game(history(1, 0.));
+        assert!(fewer.restore(&snapshot).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_storage() {
+        let path = std::env::temp_dir().join(format!("muzero-replay-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let open = || {
+            ReplayBuffer::new(config())
+                .with_storage(TrajectoryFile::open(&path).unwrap())
+                .unwrap()
+        };
+        let mut buffer = open();
+        for len in 1..=4 {
+            buffer.save_game(history(len, len as f32));
+        }
+        assert!(buffer
+            .entries
+            .iter()
+            .all(|entry| matches!(entry.history, Stored::Disk(_))));
+        let (id, mut reanalyzed) = buffer.next_to_reanalyze().unwrap();
+        reanalyzed.root_values = vec![0.; 2];
+        buffer.update_reanalyzed(id, reanalyzed);
+        drop(buffer);
+
+        // After a restart, the last window of games is back, the reanalyzed one included.
+        let buffer = open();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(buffer.num_positions(), 3 + 4 + 2);
+        assert_eq!(buffer.history(&buffer.entries[2]).root_values, [0.; 2]);
+        let batch = buffer.sample_batch(&mut StdRng::seed_from_u64(0));
+        assert!(batch.iter().all(|sample| sample.observation != [1.]));
+    }
+
+    #[test]
+    fn test_reanalyze() {
+        let mut history = history(2, 0.);
+        history.legal_actions[1] = vec![0, 2];
+        let mut config = MuZeroConfig::board_game(3, 0.3);
+        config.num_players = 1;
+        config.num_simulations = 30;
+        let network = UniformNetwork {
+            action_space_size: 3,
+        };
+        reanalyze(&mut history, &config, &network).unwrap();
+        assert_eq!(history.root_values, [0., 0.]);
+        assert!(history.child_visits[0].iter().all(|&p| p > 0.));
+        assert_eq!(history.child_visits[1][1], 0.);
+
+        history.legal_actions.clear();
+        assert!(reanalyze(&mut history, &config, &network).is_err());
+    }
+
+    #[test]
+    fn test_reanalyzer() {
+        let mut buffer = ReplayBuffer::new(ReplayConfig {
+            reanalyze_fraction: 0.3,
+            ..config()
+        });
+        // Recorded without legal actions, so it's never reanalyzed.
+        buffer.save_game(GameHistory {
+            legal_actions: vec![],
+            ..history(3, 1.)
+        });
+        buffer.save_game(history(3, 2.));
+        let buffer = Arc::new(Mutex::new(buffer));
+        let mut config = MuZeroConfig::board_game(3, 0.3);
+        config.num_simulations = 5;
+        let network = UniformNetwork {
+            action_space_size: 3,
+        };
+        let reanalyzer = Reanalyzer::spawn(buffer.clone(), network, config);
+        let start = Instant::now();
+        while buffer.lock().unwrap().entries[1].reanalyzed == 0 {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(reanalyzer.stop().unwrap() >= 1);
+
+        let buffer = buffer.lock().unwrap();
+        assert_eq!(buffer.entries[0].reanalyzed, 0);
+        assert_eq!(buffer.history(&buffer.entries[1]).root_values, [0.; 3]);
+        let batch = buffer.sample_batch(&mut StdRng::seed_from_u64(0));
+        let reanalyzed = batch.iter().filter(|sample| sample.observation == [2.]);
+        assert_eq!(reanalyzed.count(), 3);
+    }
+}
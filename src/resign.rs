@@ -0,0 +1,173 @@
+//! Resigning self-play games once they look lost, as in AlphaGo Zero, to save the moves of
+//! games whose outcome is settled. A fraction of the games which would resign are played to
+//! the end anyway, to measure how often resigning gives away a game that wasn't lost.
+
+use rand::Rng;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResignConfig {
+    /// Resign when the root value of the player to move is below this...
+    pub threshold: f32,
+    /// ...for this many of its moves in a row.
+    pub moves: usize,
+    /// The fraction of games played to the end even when a player would resign.
+    pub playthrough_fraction: f32,
+}
+
+impl Default for ResignConfig {
+    fn default() -> Self {
+        Self {
+            threshold: -0.9,
+            moves: 2,
+            playthrough_fraction: 0.1,
+        }
+    }
+}
+
+/// Decides when a player resigns one game.
+#[derive(Debug, Clone)]
+pub struct Resignation {
+    config: ResignConfig,
+    /// Whether the game is played to the end whatever the values.
+    playthrough: bool,
+    /// The moves in a row each player valued below the threshold.
+    streaks: Vec<usize>,
+    /// The first player who would have resigned.
+    would_resign: Option<usize>,
+    resigned: bool,
+}
+
+impl Resignation {
+    /// A game of `num_players`, played to the end with [`ResignConfig::playthrough_fraction`].
+    pub fn new(config: ResignConfig, num_players: usize, rng: &mut impl Rng) -> Self {
+        Self {
+            config,
+            playthrough: rng.gen::<f32>() < config.playthrough_fraction,
+            streaks: vec![0; num_players],
+            would_resign: None,
+            resigned: false,
+        }
+    }
+
+    /// Record the root value of a search for `player`, from its perspective, and return
+    /// whether it resigns now.
+    pub fn resigns(&mut self, player: usize, root_value: f32) -> bool {
+        let streak = &mut self.streaks[player];
+        *streak = if root_value < self.config.threshold {
+            *streak + 1
+        } else {
+            0
+        };
+        if *streak >= self.config.moves && self.would_resign.is_none() {
+            self.would_resign = Some(player);
+            self.resigned = !self.playthrough;
+        }
+        self.resigned
+    }
+
+    /// Whether the game is played to the end even if a player would resign.
+    pub fn playthrough(&self) -> bool {
+        self.playthrough
+    }
+
+    /// The first player who resigned, or would have if the game wasn't played through.
+    pub fn would_resign(&self) -> Option<usize> {
+        self.would_resign
+    }
+}
+
+/// How resigning went over many games.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ResignStats {
+    pub games: usize,
+    pub resigned: usize,
+    /// Games played to the end although a player would have resigned.
+    pub played_through: usize,
+    /// Those of the games played through which the player who would have resigned didn't
+    /// lose.
+    pub false_positives: usize,
+}
+
+impl ResignStats {
+    /// Count a game which is over, with `returns` by player if it was played to the end.
+    pub fn add(&mut self, resignation: &Resignation, returns: &[f32]) {
+        self.games += 1;
+        match resignation.would_resign {
+            Some(_) if resignation.resigned => self.resigned += 1,
+            Some(player) => {
+                self.played_through += 1;
+                if returns[player] >= 0. {
+                    self.false_positives += 1;
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// The fraction of the games played through which resigning would have given away.
+    pub fn false_positive_rate(&self) -> f64 {
+        self.false_positives as f64 / self.played_through.max(1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn new_resignation(playthrough_fraction: f32) -> Resignation {
+        let config = ResignConfig {
+            threshold: -0.8,
+            moves: 2,
+            playthrough_fraction,
+        };
+        Resignation::new(config, 2, &mut StdRng::seed_from_u64(0))
+    }
+
+    #[test]
+    fn test_resigns() {
+        let mut resignation = new_resignation(0.);
+        assert!(!resignation.resigns(0, -0.9));
+        // The other player's moves don't break the streak, a better value does.
+        assert!(!resignation.resigns(1, 0.9));
+        assert!(!resignation.resigns(0, -0.5));
+        assert!(!resignation.resigns(1, 0.9));
+        assert!(!resignation.resigns(0, -0.9));
+        assert!(!resignation.resigns(1, 0.9));
+        assert!(resignation.resigns(0, -0.95));
+        assert_eq!(resignation.would_resign(), Some(0));
+
+        let mut stats = ResignStats::default();
+        stats.add(&resignation, &[]);
+        assert_eq!(stats.resigned, 1);
+    }
+
+    #[test]
+    fn test_playthrough() {
+        let mut resignation = new_resignation(1.);
+        assert!(resignation.playthrough());
+        assert!(!resignation.resigns(1, -1.));
+        assert!(!resignation.resigns(1, -1.));
+        assert_eq!(resignation.would_resign(), Some(1));
+        // Only the first player who would have resigned counts.
+        assert!(!resignation.resigns(0, -1.));
+        assert!(!resignation.resigns(0, -1.));
+        assert_eq!(resignation.would_resign(), Some(1));
+
+        let mut stats = ResignStats::default();
+        // Player 1 came back to draw.
+        stats.add(&resignation, &[0., 0.]);
+        stats.add(&resignation, &[1., -1.]);
+        stats.add(&new_resignation(1.), &[1., -1.]);
+        assert_eq!(
+            stats,
+            ResignStats {
+                games: 3,
+                resigned: 0,
+                played_through: 2,
+                false_positives: 1,
+            }
+        );
+        assert_eq!(stats.false_positive_rate(), 0.5);
+    }
+}
@@ -0,0 +1,127 @@
+//! How random playouts choose their moves. Uniform playouts need nothing but the rules;
+//! playouts which take the wins and block the losses in sight, with [`Game::winning_moves`],
+//! value positions much better in the n-in-a-row games for the same number of simulations.
+
+use std::{fmt, str::FromStr};
+
+use rand::{seq::SliceRandom, RngCore};
+
+use crate::{error::ParseError, game::Game};
+
+/// Chooses the moves of the playouts of [`crate::Mcts`], see
+/// [`crate::Mcts::with_rollout_policy`] for heuristics of a game's own.
+pub trait RolloutPolicy<T: Game>: Send + Sync {
+    /// The move to play in `game`, one of its available `moves`, which aren't empty.
+    fn choose(&self, game: &T, moves: &[T::Action], rng: &mut dyn RngCore) -> T::Action;
+}
+
+/// The playouts every game can use, chosen by [`crate::MctsConfig::rollout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rollout {
+    /// A uniformly random move.
+    Uniform,
+    /// A move which wins at once if there is one, else one which stops the opponent from
+    /// winning at once, else a uniformly random move. Uniform for games which don't override
+    /// [`Game::winning_moves`].
+    Tactical,
+}
+
+impl fmt::Display for Rollout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rollout::Uniform => write!(f, "uniform"),
+            Rollout::Tactical => write!(f, "tactical"),
+        }
+    }
+}
+
+impl FromStr for Rollout {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        match s {
+            "uniform" => Ok(Rollout::Uniform),
+            "tactical" => Ok(Rollout::Tactical),
+            _ => Err(ParseError::Invalid(
+                "expected uniform or tactical".to_string(),
+            )),
+        }
+    }
+}
+
+impl<T: Game> RolloutPolicy<T> for Rollout {
+    fn choose(&self, game: &T, moves: &[T::Action], rng: &mut dyn RngCore) -> T::Action {
+        if *self == Rollout::Tactical {
+            for opponent in [false, true] {
+                let winning_moves = game.winning_moves(opponent);
+                if let Some(action) = winning_moves.choose(rng) {
+                    return action.clone();
+                }
+            }
+        }
+        moves.choose(rng).unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games::{connect_four::ConnectFour, gomoku::Gomoku, tic_tac_toe::TicTacToe},
+        mcts::{Mcts, MctsConfig},
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_tactical() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut game = TicTacToe::new();
+        for action in [(0, 0), (1, 1), (0, 1)] {
+            game.step(action).unwrap();
+        }
+        assert_eq!(game.winning_moves(false), []);
+        assert_eq!(game.winning_moves(true), [(0, 2)]);
+        let moves = game.get_available_moves();
+        for _ in 0..10 {
+            assert_eq!(Rollout::Tactical.choose(&game, &moves, &mut rng), (0, 2));
+        }
+        // X would rather take its own win than block O's.
+        let mut game = TicTacToe::new();
+        for action in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            game.step(action).unwrap();
+        }
+        assert_eq!(game.winning_moves(false), [(0, 2)]);
+        assert_eq!(game.winning_moves(true), [(1, 2)]);
+        let moves = game.get_available_moves();
+        assert_eq!(Rollout::Tactical.choose(&game, &moves, &mut rng), (0, 2));
+
+        let mut game = ConnectFour::new();
+        for col in [3, 0, 3, 0, 3] {
+            game.step(col).unwrap();
+        }
+        assert_eq!(game.winning_moves(true), [3]);
+
+        let mut game = Gomoku::new(9);
+        for action in [(4, 0), (0, 0), (4, 1), (0, 8), (4, 2), (8, 0), (4, 3)] {
+            game.step(action).unwrap();
+        }
+        assert_eq!(game.winning_moves(true), [(4, 4)]);
+        assert_eq!(game.winning_moves(false), []);
+    }
+
+    #[test]
+    fn test_tactical_search() {
+        // With tactical playouts, a few simulations are enough for O to find the block.
+        let mut game = TicTacToe::new();
+        for action in [(0, 0), (1, 1), (0, 1)] {
+            game.step(action).unwrap();
+        }
+        let config = MctsConfig {
+            num_simulations: 50,
+            rollout: Rollout::Tactical,
+            seed: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(Mcts::with_config(config).search(&game).action, (0, 2));
+    }
+}
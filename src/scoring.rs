@@ -0,0 +1,96 @@
+//! How a search values the end of a game: by its outcome, as [`Game::returns`] gives it, by
+//! the margin of victory of [`Game::scores`], or by a blend of both, and with draws worth
+//! more to one side than the other if need be.
+
+use crate::game::Game;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreConfig {
+    /// How much of a value is the margin of victory rather than the outcome, in `[0, 1]`.
+    pub margin_weight: f32,
+    /// The margin worth a full point, e.g. the area of the board in Go; larger margins count
+    /// as much.
+    pub margin_scale: f32,
+    /// The value of a draw for the first player, the others getting its opposite: e.g. a
+    /// contempt below 0 to make the first player avoid draws, or -1 for Armageddon games where
+    /// a draw is a win for the second player.
+    pub draw_value: f32,
+}
+
+impl Default for ScoreConfig {
+    fn default() -> Self {
+        Self {
+            margin_weight: 0.,
+            margin_scale: 1.,
+            draw_value: 0.,
+        }
+    }
+}
+
+impl ScoreConfig {
+    /// The value of the end of `game` for every player, indexed by [`Game::player_index`].
+    /// The default config gives [`Game::returns`]. Only games which terminated can be drawn,
+    /// truncated ones are worth their returns.
+    pub fn values<T: Game>(&self, game: &T) -> Vec<f32> {
+        let returns = game.returns();
+        if game.terminated() && returns.iter().all(|&value| value == 0.) {
+            return (0..returns.len())
+                .map(|player| match player {
+                    0 => self.draw_value,
+                    _ => -self.draw_value,
+                })
+                .collect();
+        }
+        if self.margin_weight == 0. {
+            return returns;
+        }
+        returns
+            .into_iter()
+            .zip(game.scores())
+            .map(|(outcome, score)| {
+                let margin = (score / self.margin_scale).clamp(-1., 1.);
+                (1. - self.margin_weight) * outcome + self.margin_weight * margin
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::{
+        go::{Go, Move},
+        tic_tac_toe::TicTacToe,
+    };
+
+    #[test]
+    fn test_values() {
+        let config = ScoreConfig::default();
+        let mut game = TicTacToe::new();
+        for action in [(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)] {
+            game.step(action).unwrap();
+        }
+        assert_eq!(config.values(&game), game.returns());
+
+        // Black passes first on an empty board, so White wins by komi.
+        let mut game = Go::with_komi(3, 4.5);
+        game.step(Move::Pass).unwrap();
+        game.step(Move::Pass).unwrap();
+        assert_eq!(game.scores(), [-4.5, 4.5]);
+        let config = ScoreConfig {
+            margin_weight: 0.5,
+            margin_scale: 9.,
+            draw_value: 0.,
+        };
+        assert_eq!(config.values(&game), [-0.75, 0.75]);
+
+        let mut game = Go::with_komi(3, 0.);
+        game.step(Move::Pass).unwrap();
+        game.step(Move::Pass).unwrap();
+        let config = ScoreConfig {
+            draw_value: -1.,
+            ..config
+        };
+        assert_eq!(config.values(&game), [-1., 1.]);
+    }
+}
@@ -0,0 +1,407 @@
+//! A WebSocket server, so that web pages can play against the search: it serves an example
+//! client at `/` and a JSON API at `/ws`, one game per connection.
+//!
+//! Every request is a text message with a `type`, answered by one message:
+//!
+//! ```text
+//! {"type": "new_game", "game": "tictactoe"}   start over, with the server's game if omitted
+//! {"type": "move", "action": 4}                play the action with that index
+//! {"type": "agent_move"}                       let the search play
+//! {"type": "analysis"}                         search without playing
+//! ```
+//!
+//! The first three are answered with the state of the game:
+//!
+//! ```text
+//! {"type": "state", "game": "tictactoe", "board": ". . .\n. X .\n. . .\n", "to_play": 1,
+//!  "legal_actions": [{"action": 0, "name": "(0, 0)"}, ...], "last_action": 4,
+//!  "done": false, "returns": null}
+//! ```
+//!
+//! where `returns` are those of every player once the game is done, and `analysis` with
+//!
+//! ```text
+//! {"type": "analysis", "value": 0.1, "proof": null, "principal_variation": ["(0, 0)", ...],
+//!  "moves": [{"action": 0, "name": "(0, 0)", "visits": 300, "value": 0.1}, ...]}
+//! ```
+//!
+//! with values for the player to move. Chance events are sampled as soon as they are pending.
+//! Failed requests are answered with `{"type": "error", "message": "..."}`.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+use tungstenite::{
+    error::ProtocolError,
+    handshake::derive_accept_key,
+    protocol::{Role, WebSocketConfig},
+    Message, WebSocket,
+};
+
+use crate::{
+    dyn_game::DynGame,
+    error::{Context, Error, GameError, Result, SearchError},
+    mcts::{sample_outcome, Mcts, MctsConfig},
+    random,
+    registry::Registry,
+    Game,
+};
+
+const CLIENT: &str = include_str!("../web/index.html");
+
+/// The longest message accepted, in bytes.
+const MAX_MESSAGE: usize = 1 << 20;
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    NewGame { game: Option<String> },
+    Move { action: usize },
+    AgentMove,
+    Analysis,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Response {
+    State {
+        game: String,
+        board: String,
+        to_play: usize,
+        legal_actions: Vec<NamedAction>,
+        last_action: Option<usize>,
+        done: bool,
+        returns: Option<Vec<f32>>,
+    },
+    Analysis {
+        value: f32,
+        proof: Option<String>,
+        principal_variation: Vec<String>,
+        moves: Vec<AnalyzedMove>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Serialize)]
+struct NamedAction {
+    action: usize,
+    name: String,
+}
+
+#[derive(Serialize)]
+struct AnalyzedMove {
+    action: usize,
+    name: String,
+    visits: usize,
+    value: f32,
+}
+
+/// Serve connections to `listener`, each on its own thread, until accepting one fails. Games
+/// start as `game`, a name in the registry.
+pub fn serve(listener: TcpListener, game: &str, config: MctsConfig) -> Result<()> {
+    Registry::default().create(game)?;
+    for stream in listener.incoming() {
+        let stream = stream.context("failed to accept a connection")?;
+        let (game, config) = (game.to_string(), config.clone());
+        thread::spawn(move || {
+            if let Err(e) = handle(stream, &game, config) {
+                tracing::warn!("connection failed: {:#}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Answer an HTTP request: the client, or a WebSocket session when it asks for an upgrade.
+fn handle(stream: TcpStream, game: &str, config: MctsConfig) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut key = None;
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+                key = Some(value.trim().to_string());
+            }
+        }
+        line.clear();
+    }
+    let mut stream = stream;
+    match (request_line.split_whitespace().nth(1), key) {
+        (Some("/ws"), Some(key)) => {
+            write!(
+                stream,
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                 Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                derive_accept_key(key.as_bytes())
+            )?;
+            let limits = WebSocketConfig::default()
+                .max_message_size(Some(MAX_MESSAGE))
+                .max_frame_size(Some(MAX_MESSAGE));
+            // The client may have sent messages right after its request.
+            let mut socket = WebSocket::from_partially_read(
+                stream,
+                reader.buffer().to_vec(),
+                Role::Server,
+                Some(limits),
+            );
+            Session::new(game, config)?.run(&mut socket)
+        }
+        (Some("/"), _) => {
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                CLIENT.len(),
+                CLIENT
+            )?;
+            Ok(())
+        }
+        _ => {
+            write!(
+                stream,
+                "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\n\
+                 Content-Length: 10\r\nConnection: close\r\n\r\nnot found\n"
+            )?;
+            Ok(())
+        }
+    }
+}
+
+/// The game of a connection and the search that plays it.
+struct Session {
+    registry: Registry,
+    name: String,
+    game: Box<dyn DynGame>,
+    mcts: Mcts<Box<dyn DynGame>>,
+}
+
+impl Session {
+    fn new(name: &str, config: MctsConfig) -> Result<Self> {
+        let registry = Registry::default();
+        let mut session = Self {
+            game: registry.create(name)?,
+            registry,
+            name: name.to_string(),
+            mcts: Mcts::with_config(config),
+        };
+        session.resolve_chance()?;
+        Ok(session)
+    }
+
+    /// Answer the messages of `socket` until the client closes the connection. Pings and
+    /// closes are answered by the socket itself.
+    fn run(&mut self, socket: &mut WebSocket<impl Read + Write>) -> Result<()> {
+        loop {
+            let message = match socket.read() {
+                Ok(message) => message,
+                Err(tungstenite::Error::ConnectionClosed)
+                | Err(tungstenite::Error::Protocol(ProtocolError::ResetWithoutClosingHandshake)) => {
+                    return Ok(())
+                }
+                Err(e) => return Err(Error::backend(e)),
+            };
+            // Binary messages are answered as text too.
+            let request = match &message {
+                Message::Text(text) => text.as_bytes(),
+                Message::Binary(data) => data,
+                _ => continue,
+            };
+            let response = match serde_json::from_slice(request)
+                .map_err(Error::from)
+                .and_then(|request| self.respond(request))
+            {
+                Ok(response) => response,
+                Err(e) => Response::Error {
+                    message: format!("{:#}", e),
+                },
+            };
+            socket
+                .send(Message::text(serde_json::to_string(&response)?))
+                .map_err(Error::backend)?;
+        }
+    }
+
+    fn respond(&mut self, request: Request) -> Result<Response> {
+        match request {
+            Request::NewGame { game } => {
+                let name = game.unwrap_or_else(|| self.name.clone());
+                self.game = self.registry.create(&name)?;
+                self.name = name;
+                self.resolve_chance()?;
+                Ok(self.state(None))
+            }
+            Request::Move { action } => {
+                if !self.game.legal_actions().contains(&action) {
+                    return Err(GameError::illegal_move(&action, "it isn't legal").into());
+                }
+                self.play(action)
+            }
+            Request::AgentMove => {
+                if self.game.done() {
+                    return Err(SearchError::GameOver.into());
+                }
+                let action = self.mcts.search(&self.game).action;
+                self.play(action)
+            }
+            Request::Analysis => {
+                if self.game.done() {
+                    return Err(SearchError::GameOver.into());
+                }
+                let result = self.mcts.search(&self.game);
+                Ok(Response::Analysis {
+                    value: result.value(),
+                    proof: result.proof.map(|proof| proof.to_string()),
+                    principal_variation: result
+                        .principal_variation
+                        .iter()
+                        .map(|&action| self.game.action_name(action))
+                        .collect(),
+                    moves: result
+                        .children
+                        .iter()
+                        .map(|child| AnalyzedMove {
+                            action: child.action,
+                            name: self.game.action_name(child.action),
+                            visits: child.visits,
+                            value: child.q,
+                        })
+                        .collect(),
+                })
+            }
+        }
+    }
+
+    fn play(&mut self, action: usize) -> Result<Response> {
+        self.game.step(action)?;
+        self.resolve_chance()?;
+        Ok(self.state(Some(action)))
+    }
+
+    fn resolve_chance(&mut self) -> Result<()> {
+        loop {
+            let outcomes = self.game.chance_outcomes();
+            if outcomes.is_empty() || self.game.done() {
+                return Ok(());
+            }
+            self.game
+                .step(sample_outcome(&outcomes, &mut random::rng()))?;
+        }
+    }
+
+    fn state(&self, last_action: Option<usize>) -> Response {
+        let done = self.game.done();
+        Response::State {
+            game: self.name.clone(),
+            board: self.game.to_string(),
+            to_play: self.game.to_play(),
+            legal_actions: self
+                .game
+                .legal_actions()
+                .into_iter()
+                .map(|action| NamedAction {
+                    action,
+                    name: self.game.action_name(action),
+                })
+                .collect(),
+            last_action,
+            done,
+            returns: done.then(|| self.game.returns()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn send(socket: &mut WebSocket<TcpStream>, text: &str) {
+        socket.send(Message::text(text)).unwrap();
+    }
+
+    fn receive(socket: &mut WebSocket<TcpStream>) -> Value {
+        let Message::Text(text) = socket.read().unwrap() else {
+            panic!("expected a text message");
+        };
+        serde_json::from_str(&text).unwrap()
+    }
+
+    #[test]
+    fn test_session() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config = MctsConfig {
+            num_simulations: 200,
+            ..Default::default()
+        };
+        thread::spawn(move || serve(listener, "tictactoe", config));
+
+        let stream = TcpStream::connect(addr).unwrap();
+        // The client checks the accept key of the handshake.
+        let (mut socket, response) = tungstenite::client("ws://localhost/ws", stream).unwrap();
+        assert_eq!(response.status(), 101);
+
+        send(&mut socket, r#"{"type": "new_game"}"#);
+        let state = receive(&mut socket);
+        assert_eq!(state["game"], "tictactoe");
+        assert_eq!(state["legal_actions"].as_array().unwrap().len(), 9);
+
+        for action in [0, 3, 1, 4] {
+            send(
+                &mut socket,
+                &format!(r#"{{"type": "move", "action": {}}}"#, action),
+            );
+            receive(&mut socket);
+        }
+        send(&mut socket, r#"{"type": "move", "action": 4}"#);
+        let error = receive(&mut socket);
+        assert_eq!(error["message"], "illegal move 4: it isn't legal");
+
+        send(&mut socket, r#"{"type": "analysis"}"#);
+        let analysis = receive(&mut socket);
+        assert_eq!(analysis["type"], "analysis");
+        assert_eq!(analysis["principal_variation"][0], "(0, 2)");
+
+        // X completes the top row.
+        send(&mut socket, r#"{"type": "agent_move"}"#);
+        let state = receive(&mut socket);
+        assert_eq!(state["last_action"], 2);
+        assert_eq!(state["done"], true);
+        assert_eq!(state["returns"], serde_json::json!([1., -1.]));
+
+        send(&mut socket, r#"{"type": "new_game", "game": "shogi"}"#);
+        assert_eq!(receive(&mut socket)["type"], "error");
+
+        socket.send(Message::Ping("ping".into())).unwrap();
+        assert_eq!(socket.read().unwrap(), Message::Pong("ping".into()));
+
+        // Close, and the server closes back.
+        socket.close(None).unwrap();
+        assert!(matches!(socket.read().unwrap(), Message::Close(_)));
+    }
+
+    #[test]
+    fn test_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || serve(listener, "tictactoe", MctsConfig::default()));
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let page = get("/");
+        assert!(page.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(page.contains("new WebSocket"));
+        assert!(get("/ws").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
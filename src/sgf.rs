@@ -0,0 +1,285 @@
+//! SGF, the Smart Game Format, for records of Go, Gomoku and Othello, whose actions are the
+//! points of a square board in row-major order, and for Go and Othello a pass after them.
+//!
+//! Only the main line of a file is read; variations, comments and setup stones are skipped.
+
+use std::{iter::Peekable, str::Chars};
+
+use crate::{
+    error::{Context, ParseError, Result},
+    record::{GameRecord, MoveRecord},
+};
+
+/// The registry names of the games SGF can hold, with their SGF game numbers.
+const GAMES: [(&str, u32); 3] = [("go", 1), ("othello", 2), ("gomoku", 4)];
+
+/// How deep variations may nest, so that parsing can't overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+/// Whether records of the game `spec`, e.g. `go:9`, can be written as SGF.
+pub fn supports(spec: &str) -> bool {
+    let name = spec.split(':').next().unwrap_or_default();
+    GAMES.iter().any(|&(game, _)| game == name)
+}
+
+/// `record` as SGF, for a game with `action_space_size` actions.
+pub fn to_sgf(record: &GameRecord, action_space_size: usize) -> Result<String> {
+    let name = record.game.split(':').next().unwrap_or_default();
+    let Some(&(_, number)) = GAMES.iter().find(|&&(game, _)| game == name) else {
+        return Err(ParseError::Invalid(format!("{} can't be written as SGF", record.game)).into());
+    };
+    let size = action_space_size.isqrt();
+    let mut sgf = format!("(;GM[{}]FF[4]CA[UTF-8]SZ[{}]", number, size);
+    for (i, record) in record.moves.iter().enumerate() {
+        let color = match record.player {
+            Some(0) => 'B',
+            Some(1) => 'W',
+            _ => {
+                return Err(ParseError::Invalid(format!(
+                    "move {} isn't by one of the two players",
+                    i + 1
+                ))
+                .into())
+            }
+        };
+        let point = if record.action == size * size {
+            String::new()
+        } else if record.action < size * size {
+            let (row, col) = (record.action / size, record.action % size);
+            [col, row]
+                .iter()
+                .map(|&x| (b'a' + x as u8) as char)
+                .collect()
+        } else {
+            return Err(ParseError::Invalid(format!(
+                "move {}: invalid action {}",
+                i + 1,
+                record.action
+            ))
+            .into());
+        };
+        sgf += &format!(";{}[{}]", color, point);
+    }
+    sgf += ")\n";
+    Ok(sgf)
+}
+
+/// The records of the games of the SGF collection `text`, each the main line of its tree. The
+/// moves have no timestamps or searches.
+pub fn from_sgf(text: &str) -> Result<Vec<GameRecord>> {
+    let mut chars = text.chars().peekable();
+    let mut records = vec![];
+    skip_whitespace(&mut chars);
+    while chars.peek().is_some() {
+        let nodes = parse_tree(&mut chars, 0)?;
+        records.push(game_record(&nodes).with_context(|| format!("game {}", records.len() + 1))?);
+        skip_whitespace(&mut chars);
+    }
+    if records.is_empty() {
+        return Err(ParseError::Invalid("no games".to_string()).into());
+    }
+    Ok(records)
+}
+
+fn game_record(nodes: &[Node]) -> Result<GameRecord> {
+    let property = |key: &str| {
+        nodes
+            .first()
+            .and_then(|node| node.iter().find(|(k, _)| k == key))
+            .map(|(_, value)| value.as_str())
+    };
+    let number: u32 = property("GM")
+        .unwrap_or("1")
+        .parse()
+        .context("invalid GM")?;
+    let Some(&(name, _)) = GAMES.iter().find(|&&(_, n)| n == number) else {
+        return Err(ParseError::Invalid(format!("unsupported SGF game {}", number)).into());
+    };
+    let size: usize = match property("SZ") {
+        Some(size) => size.parse().context("invalid SZ")?,
+        None if name == "go" => 19,
+        None if name == "gomoku" => 15,
+        None => 8,
+    };
+    if !(1..=26).contains(&size) {
+        return Err(ParseError::Invalid(format!("unsupported board size {}", size)).into());
+    }
+    let game = match name {
+        "othello" if size == 8 => name.to_string(),
+        "othello" => {
+            return Err(ParseError::Invalid("othello is only played on 8x8".to_string()).into())
+        }
+        _ => format!("{}:{}", name, size),
+    };
+    let mut record = GameRecord::new(&game);
+    for node in nodes {
+        for (key, value) in node {
+            let player = match key.as_str() {
+                "B" => 0,
+                "W" => 1,
+                _ => continue,
+            };
+            let action = match value.as_bytes() {
+                [] => size * size,
+                // The old way to pass on boards up to 19x19.
+                b"tt" if size <= 19 => size * size,
+                &[col, row]
+                    if (b'a'..b'a' + size as u8).contains(&col)
+                        && (b'a'..b'a' + size as u8).contains(&row) =>
+                {
+                    (row - b'a') as usize * size + (col - b'a') as usize
+                }
+                _ => return Err(ParseError::Invalid(format!("invalid point `{}`", value)).into()),
+            };
+            record.moves.push(MoveRecord {
+                action,
+                player: Some(player),
+                timestamp: 0,
+                search: None,
+            });
+        }
+    }
+    Ok(record)
+}
+
+type Node = Vec<(String, String)>;
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+/// The nodes of a game tree, following the first variation.
+/// The main line of the tree at `chars`, inside `depth` others.
+fn parse_tree(chars: &mut Peekable<Chars>, depth: usize) -> Result<Vec<Node>> {
+    if chars.next() != Some('(') {
+        return Err(ParseError::Invalid("expected `(`".to_string()).into());
+    }
+    if depth == MAX_DEPTH {
+        return Err(
+            ParseError::Invalid(format!("variations nested deeper than {}", MAX_DEPTH)).into(),
+        );
+    }
+    let mut nodes = vec![];
+    loop {
+        skip_whitespace(chars);
+        match chars.peek() {
+            Some(';') => {
+                chars.next();
+                nodes.push(parse_node(chars)?);
+            }
+            Some('(') => {
+                nodes.extend(parse_tree(chars, depth + 1)?);
+                skip_whitespace(chars);
+                while chars.peek() == Some(&'(') {
+                    parse_tree(chars, depth + 1)?;
+                    skip_whitespace(chars);
+                }
+            }
+            Some(')') => {
+                chars.next();
+                return Ok(nodes);
+            }
+            Some(c) => return Err(ParseError::Invalid(format!("unexpected `{}`", c)).into()),
+            None => {
+                return Err(ParseError::Invalid("unexpected end of the file".to_string()).into())
+            }
+        }
+    }
+}
+
+/// The properties of a node, the first value of each.
+fn parse_node(chars: &mut Peekable<Chars>) -> Result<Node> {
+    let mut node = vec![];
+    loop {
+        skip_whitespace(chars);
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(char::is_ascii_uppercase) {
+            key.push(c);
+        }
+        if key.is_empty() {
+            return Ok(node);
+        }
+        let mut values = vec![];
+        skip_whitespace(chars);
+        while chars.next_if_eq(&'[').is_some() {
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some(']') => break,
+                    Some('\\') => value.extend(chars.next()),
+                    Some(c) => value.push(c),
+                    None => {
+                        return Err(
+                            ParseError::Invalid(format!("unterminated value of {}", key)).into(),
+                        )
+                    }
+                }
+            }
+            values.push(value);
+            skip_whitespace(chars);
+        }
+        let Some(value) = values.into_iter().next() else {
+            return Err(ParseError::Invalid(format!("{} has no value", key)).into());
+        };
+        node.push((key, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games::{go::Go, othello::Othello},
+        record::MoveSearch,
+    };
+
+    #[test]
+    fn test_round_trip() {
+        let mut record = GameRecord::new("go:9");
+        let search = MoveSearch {
+            value: 0.,
+            visits: vec![],
+        };
+        record.push(40, Some(0), Some(search));
+        record.push(2, Some(1), None);
+        record.push(81, Some(0), None);
+        let sgf = to_sgf(&record, 82).unwrap();
+        assert_eq!(sgf, "(;GM[1]FF[4]CA[UTF-8]SZ[9];B[ee];W[ca];B[])\n");
+        let restored = from_sgf(&sgf).unwrap().remove(0);
+        assert_eq!(restored.game, "go:9");
+        let moves = |record: &GameRecord| -> Vec<_> {
+            record
+                .moves
+                .iter()
+                .map(|record| (record.action, record.player))
+                .collect()
+        };
+        assert_eq!(moves(&restored), moves(&record));
+        assert!(restored.positions(Go::new(9)).is_ok());
+
+        assert!(supports("gomoku:9"));
+        assert!(!supports("tictactoe"));
+        assert!(to_sgf(&GameRecord::new("tictactoe"), 9).is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        // Whitespace, escapes, pass as tt and a variation that isn't followed.
+        let sgf = "(;GM[2]SZ[8]C[a \\] comment]\n;B[dc];W[tt](;B[cc]C[main])(;B[ff]))";
+        let records = from_sgf(&format!("{}\n(;SZ[9];B[aa])", sgf)).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].game, "go:9");
+        let record = &records[0];
+        assert_eq!(record.game, "othello");
+        let actions: Vec<_> = record.moves.iter().map(|record| record.action).collect();
+        assert_eq!(actions, [2 * 8 + 3, 64, 2 * 8 + 2]);
+        assert_eq!(record.moves[1].player, Some(1));
+        assert_eq!(record.moves[1].timestamp, 0);
+        // The moves aren't legal Othello, which only replaying finds out.
+        assert!(record.positions(Othello::new()).is_err());
+
+        for sgf in ["", "(;GM[3])", "(;SZ[9];B[zz])", "(;B[aa]", "(;GM[2]SZ[9])"] {
+            assert!(from_sgf(sgf).is_err(), "{}", sgf);
+        }
+    }
+}
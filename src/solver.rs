@@ -0,0 +1,121 @@
+//! An exact endgame solver: negamax with alpha-beta pruning over the rest of the game tree,
+//! for two-player games without chance once few enough moves remain.
+
+use crate::game::Game;
+
+/// The proven outcome of a position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution<A> {
+    /// The outcome with perfect play, from the perspective of the player to move.
+    pub value: f32,
+    /// A move reaching `value`, `None` if the game is over.
+    pub action: Option<A>,
+    /// The number of moves until the end along the best line.
+    pub depth: usize,
+}
+
+/// Solve `game` by visiting at most `node_budget` states, or give up with `None` if the tree
+/// is larger, has chance events, or isn't a two-player game.
+pub fn solve<G: Game>(game: &G, node_budget: usize) -> Option<Solution<G::Action>> {
+    if game.num_players() != 2 {
+        return None;
+    }
+    let mut nodes = 0;
+    negamax(game, -1., 1., &mut nodes, node_budget)
+}
+
+/// The value of `game` for its player to move, exact if it lies within `(alpha, beta)`, and
+/// otherwise a bound on the side of the window it fell out of.
+fn negamax<G: Game>(
+    game: &G,
+    mut alpha: f32,
+    beta: f32,
+    nodes: &mut usize,
+    budget: usize,
+) -> Option<Solution<G::Action>> {
+    *nodes += 1;
+    if *nodes > budget || !game.chance_outcomes().is_empty() {
+        return None;
+    }
+    let player = game.player_index(&game.current_player());
+    if game.done() {
+        return Some(Solution {
+            value: game.returns()[player],
+            action: None,
+            depth: 0,
+        });
+    }
+    let mut best: Option<Solution<G::Action>> = None;
+    for action in game.get_available_moves() {
+        let mut child = game.clone();
+        child.step(action.clone()).ok()?;
+        // Games may let a player move twice in a row, e.g. after the opponent passes.
+        let (value, depth) = if child.done() {
+            (child.returns()[player], 0)
+        } else if child.player_index(&child.current_player()) == player {
+            let solution = negamax(&child, alpha, beta, nodes, budget)?;
+            (solution.value, solution.depth)
+        } else {
+            let solution = negamax(&child, -beta, -alpha, nodes, budget)?;
+            (-solution.value, solution.depth)
+        };
+        // Only strictly better moves replace the best one: a value equal to `alpha` may just
+        // be a bound.
+        if best.as_ref().is_none_or(|best| value > best.value) {
+            best = Some(Solution {
+                value,
+                action: Some(action),
+                depth: depth + 1,
+            });
+        }
+        alpha = alpha.max(value);
+        if alpha >= beta {
+            break;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::tic_tac_toe::TicTacToe;
+
+    fn play(moves: &[(usize, usize)]) -> TicTacToe {
+        let mut game = TicTacToe::new();
+        for &action in moves {
+            game.step(action).unwrap();
+        }
+        game
+    }
+
+    #[test]
+    fn test_solve() {
+        // X to move wins at once in the top right.
+        let game = play(&[(0, 0), (1, 0), (0, 1), (1, 1)]);
+        let solution = solve(&game, 100_000).unwrap();
+        assert_eq!(solution.value, 1.);
+        assert_eq!(solution.action, Some((0, 2)));
+        assert_eq!(solution.depth, 1);
+        // Once it's over, O to move has lost.
+        let game = play(&[(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)]);
+        assert_eq!(
+            solve(&game, 1),
+            Some(Solution {
+                value: -1.,
+                action: None,
+                depth: 0,
+            })
+        );
+
+        // O must block the top row, or lose.
+        let game = play(&[(0, 0), (1, 1), (0, 1)]);
+        let solution = solve(&game, 100_000).unwrap();
+        assert_eq!(solution.value, 0.);
+        assert_eq!(solution.action, Some((0, 2)));
+
+        // The empty board is a draw, but takes more than a few nodes to prove.
+        assert_eq!(solve(&TicTacToe::new(), 100).map(|s| s.value), None);
+        assert_eq!(solve(&TicTacToe::new(), 1_000_000).unwrap().value, 0.);
+    }
+}
@@ -0,0 +1,197 @@
+//! Games stored on disk, for replay buffers larger than memory that survive restarts: an
+//! append-only [trajectory file](crate::trajectory), memory-mapped for reading, and an index
+//! of its games kept in memory. A game cut off by a crash is dropped when the file is opened
+//! again.
+
+use memmap2::Mmap;
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{Context, IoError, ParseError, Result},
+    history::GameHistory,
+    trajectory::{self, HEADER_LEN},
+};
+
+/// A read-only mapping of the first `len` bytes of `file`.
+fn map(file: &File, len: usize) -> std::io::Result<Mmap> {
+    // Safety: the mapping is only of what was written before, and the lock that `open` takes
+    // keeps other `TrajectoryFile`s from truncating or rewriting it. Nothing else may change
+    // a trajectory file while it is open, as with every other memory-mapped file.
+    unsafe { memmap2::MmapOptions::new().len(len).map(file) }
+}
+
+pub struct TrajectoryFile {
+    path: PathBuf,
+    file: File,
+    /// The file up to `end`, mapped again when records are appended.
+    map: Mmap,
+    /// The offset and length of every record's game.
+    index: Vec<(usize, usize)>,
+    /// The end of the last complete record.
+    end: usize,
+}
+
+impl TrajectoryFile {
+    /// Open the file at `path`, creating it if it doesn't exist, and lock it for as long as it
+    /// is open.
+    pub fn open(path: &Path) -> Result<Self> {
+        let context = || format!("failed to open {}", path.display());
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .with_context(context)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let source = io::Error::new(io::ErrorKind::WouldBlock, "it is already open");
+                return Err(IoError::path("lock", path)(source).into());
+            }
+            Err(TryLockError::Error(e)) => return Err(e).with_context(context),
+        }
+        let mut len = file.metadata().with_context(context)?.len() as usize;
+        if len == 0 {
+            file.write_all(&trajectory::header())
+                .with_context(context)?;
+            len = HEADER_LEN;
+        }
+        let mut map = map(&file, len).with_context(context)?;
+        trajectory::check_header(&map[..len.min(HEADER_LEN)]).with_context(context)?;
+        let mut index = vec![];
+        let mut end = HEADER_LEN;
+        while end + 4 <= len {
+            let record_len = u32::from_le_bytes(map[end..end + 4].try_into().unwrap()) as usize;
+            if end + 4 + record_len > len {
+                break;
+            }
+            index.push((end + 4, record_len));
+            end += 4 + record_len;
+        }
+        if end < len {
+            tracing::warn!(
+                "dropping an incomplete record at the end of {}",
+                path.display()
+            );
+            // Unmap the end before cutting it off.
+            map = self::map(&file, end).with_context(context)?;
+            file.set_len(end as u64).with_context(context)?;
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            map,
+            index,
+            end,
+        })
+    }
+
+    /// The number of records.
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Append `history` as the last record and return its index.
+    pub fn append(&mut self, history: &GameHistory) -> Result<usize> {
+        let record = trajectory::record(history)?;
+        let end = self.end + record.len();
+        let written = self
+            .file
+            .write_all(&record)
+            .map_err(IoError::path("write", &self.path))
+            .and_then(|()| map(&self.file, end).map_err(IoError::path("map", &self.path)));
+        match written {
+            Ok(map) => self.map = map,
+            Err(e) => {
+                // Don't leave part of a record for the next one to follow.
+                let _ = self.file.set_len(self.end as u64);
+                return Err(e.into());
+            }
+        }
+        self.index.push((self.end + 4, record.len() - 4));
+        self.end = end;
+        Ok(self.index.len() - 1)
+    }
+
+    /// The game of record `record`.
+    pub fn get(&self, record: usize) -> Result<GameHistory> {
+        let &(offset, len) = self
+            .index
+            .get(record)
+            .ok_or_else(|| ParseError::Invalid(format!("no record {}", record)))?;
+        trajectory::decode(&self.map[offset..offset + len])
+            .with_context(|| format!("record {} of {}", record, self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn history(len: usize) -> GameHistory {
+        GameHistory {
+            observations: vec![vec![1.]; len],
+            to_play: vec![0; len],
+            actions: vec![0; len],
+            rewards: vec![0.5; len],
+            child_visits: vec![vec![1.]; len],
+            root_values: vec![0.; len],
+            legal_actions: vec![],
+            final_value: None,
+        }
+    }
+
+    #[test]
+    fn test_append_and_reopen() {
+        let path = std::env::temp_dir().join(format!("muzero-trajectories-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut file = TrajectoryFile::open(&path).unwrap();
+        assert!(file.is_empty());
+        for len in 1..=3 {
+            assert_eq!(file.append(&history(len)).unwrap(), len - 1);
+        }
+        assert_eq!(file.get(1).unwrap(), history(2));
+        assert!(file.get(3).is_err());
+        drop(file);
+
+        // A crash in the middle of a record.
+        let mut torn = OpenOptions::new().append(true).open(&path).unwrap();
+        torn.write_all(&100u32.to_le_bytes()).unwrap();
+        torn.write_all(&[0; 5]).unwrap();
+        let mut file = TrajectoryFile::open(&path).unwrap();
+        assert_eq!(file.len(), 3);
+        file.append(&history(4)).unwrap();
+        assert_eq!(file.get(3).unwrap(), history(4));
+        assert_eq!(file.get(2).unwrap(), history(3));
+        assert!(TrajectoryFile::open(&path).is_err());
+        drop(file);
+
+        fs::write(&path, b"{\"not\": \"trajectories\"}").unwrap();
+        assert!(TrajectoryFile::open(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_remap() {
+        let path = std::env::temp_dir().join(format!("muzero-remap-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut file = TrajectoryFile::open(&path).unwrap();
+        let long = history(5000);
+        // Past the first mapping.
+        for _ in 0..20 {
+            file.append(&long).unwrap();
+        }
+        assert!(file.end > 1 << 20);
+        assert_eq!(file.get(19).unwrap(), long);
+        fs::remove_file(&path).unwrap();
+    }
+}
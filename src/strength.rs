@@ -0,0 +1,138 @@
+//! Strength levels for playing against people: smaller searches that pick among the moves more
+//! loosely, and now and then blunder a random move.
+
+use rand::{seq::SliceRandom, Rng};
+use std::str::FromStr;
+
+use crate::{error::ParseError, mcts::MctsConfig, random};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strength {
+    Beginner,
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl FromStr for Strength {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        match s {
+            "beginner" => Ok(Strength::Beginner),
+            "easy" => Ok(Strength::Easy),
+            "medium" => Ok(Strength::Medium),
+            "hard" => Ok(Strength::Hard),
+            _ => Err(ParseError::Invalid(
+                "expected beginner, easy, medium or hard".to_string(),
+            )),
+        }
+    }
+}
+
+/// How a weaker agent searches and picks its moves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Handicap {
+    pub simulations: usize,
+    /// The temperature of the visit counts the move is sampled from, 0 for the most visited.
+    pub temperature: f32,
+    /// The probability of playing a uniformly random legal move instead.
+    pub blunder_probability: f32,
+}
+
+impl Strength {
+    pub fn handicap(self) -> Handicap {
+        let (simulations, temperature, blunder_probability) = match self {
+            Strength::Beginner => (20, 1., 0.25),
+            Strength::Easy => (100, 0.5, 0.1),
+            Strength::Medium => (400, 0.25, 0.03),
+            Strength::Hard => (2000, 0., 0.),
+        };
+        Handicap {
+            simulations,
+            temperature,
+            blunder_probability,
+        }
+    }
+}
+
+impl Handicap {
+    /// `config` with the simulations of the handicap, and without the exact solver, which
+    /// would play endgames perfectly at any level.
+    pub fn config(&self, config: MctsConfig) -> MctsConfig {
+        MctsConfig {
+            num_simulations: self.simulations,
+            solver_budget: None,
+            ..config
+        }
+    }
+
+    /// The move to play given the visit counts of the searched moves, or a random one of the
+    /// legal `moves` when blundering.
+    pub fn select<A: Clone>(&self, visits: &[(A, usize)], moves: &[A]) -> A {
+        let mut rng = random::rng();
+        if visits.is_empty() || rng.gen::<f32>() < self.blunder_probability {
+            return moves.choose(&mut rng).expect("a legal move").clone();
+        }
+        if self.temperature == 0. {
+            let (action, _) = visits.iter().max_by_key(|(_, visits)| visits).unwrap();
+            return action.clone();
+        }
+        visits
+            .choose_weighted(&mut rng, |(_, visits)| {
+                (*visits as f32).powf(1. / self.temperature)
+            })
+            .map(|(action, _)| action.clone())
+            .unwrap_or_else(|_| moves.choose(&mut rng).expect("a legal move").clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels() {
+        assert_eq!("easy".parse::<Strength>().unwrap(), Strength::Easy);
+        assert!("strongest".parse::<Strength>().is_err());
+        let levels = [
+            Strength::Beginner,
+            Strength::Easy,
+            Strength::Medium,
+            Strength::Hard,
+        ]
+        .map(Strength::handicap);
+        for pair in levels.windows(2) {
+            assert!(pair[0].simulations < pair[1].simulations);
+            assert!(pair[0].blunder_probability > pair[1].blunder_probability);
+        }
+        let config = levels[0].config(MctsConfig {
+            solver_budget: Some(1000),
+            ..Default::default()
+        });
+        assert_eq!(config.num_simulations, 20);
+        assert_eq!(config.solver_budget, None);
+    }
+
+    #[test]
+    fn test_select() {
+        let visits = [(0, 10), (1, 90), (2, 0)];
+        let moves = [0, 1, 2, 3];
+        assert_eq!(Strength::Hard.handicap().select(&visits, &moves), 1);
+        let blunders = Handicap {
+            simulations: 1,
+            temperature: 0.,
+            blunder_probability: 1.,
+        };
+        assert!((0..100).any(|_| blunders.select(&visits, &moves) == 3));
+        let loose = Handicap {
+            temperature: 1.,
+            blunder_probability: 0.,
+            ..blunders
+        };
+        for _ in 0..100 {
+            // Unvisited moves are never sampled.
+            assert!([0, 1].contains(&loose.select(&visits, &moves)));
+        }
+    }
+}
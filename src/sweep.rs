@@ -0,0 +1,535 @@
+//! Hyperparameter sweeps: a command run once for every combination of parameter values, and
+//! the final metrics of the runs side by side.
+//!
+//! A sweep is described by a TOML file:
+//!
+//! ```toml
+//! command = "eval"
+//! parallel = 4
+//!
+//! # The config file of every run.
+//! [base]
+//! game = "tictactoe"
+//! eval.games = 20
+//!
+//! # Every value of a list.
+//! [grid]
+//! mcts.simulations = [100, 400]
+//!
+//! # [start, end, count]: evenly spaced values, integers if both ends are.
+//! [range]
+//! mcts.exploration = [0.5, 2.0, 4]
+//!
+//! # [start, end, count]: evenly spaced on a log scale.
+//! [log_range]
+//! train.lr = [0.001, 0.1, 3]
+//! ```
+
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use toml::{Table, Value};
+
+use crate::error::{Context, IoError, ParseError, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    /// The config key, e.g. `mcts.exploration`.
+    pub key: String,
+    pub values: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepConfig {
+    /// The command of every run, like `eval`.
+    pub command: String,
+    /// Runs at a time.
+    pub parallel: usize,
+    /// The config keys every run shares.
+    pub base: Vec<(String, Value)>,
+    pub parameters: Vec<Parameter>,
+}
+
+/// One combination of parameter values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub name: String,
+    pub values: Vec<(String, Value)>,
+}
+
+/// A sweep file as written.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SweepFile {
+    command: String,
+    #[serde(default = "one")]
+    parallel: usize,
+    #[serde(default)]
+    base: Table,
+    #[serde(default)]
+    grid: Table,
+    #[serde(default)]
+    range: Table,
+    #[serde(default)]
+    log_range: Table,
+}
+
+/// The most runs a sweep may have, and so the most values of a parameter.
+const MAX_RUNS: usize = 1000;
+
+fn one() -> usize {
+    1
+}
+
+impl SweepConfig {
+    pub fn parse(text: &str) -> Result<Self> {
+        let file: SweepFile =
+            toml::from_str(text).map_err(|e| ParseError::Invalid(e.to_string()))?;
+        if file.parallel == 0 {
+            return Err(ParseError::Invalid("`parallel` must be at least 1".to_string()).into());
+        }
+        let mut parameters = vec![];
+        for (key, value) in flatten(&file.grid) {
+            let values = match value {
+                Value::Array(values) if !values.is_empty() => values,
+                _ => {
+                    return Err(ParseError::Invalid(format!(
+                        "`grid.{}` must be a list of values",
+                        key
+                    ))
+                    .into())
+                }
+            };
+            parameters.push(Parameter { key, values });
+        }
+        for (name, table, log) in [
+            ("range", &file.range, false),
+            ("log_range", &file.log_range, true),
+        ] {
+            for (key, value) in flatten(table) {
+                let values = range(&value, log).with_context(|| {
+                    format!("in `{}.{}`, expected [start, end, count]", name, key)
+                })?;
+                parameters.push(Parameter { key, values });
+            }
+        }
+        let runs = parameters.iter().try_fold(1usize, |runs, parameter| {
+            runs.checked_mul(parameter.values.len())
+                .filter(|&runs| runs <= MAX_RUNS)
+        });
+        if runs.is_none() {
+            return Err(
+                ParseError::Invalid(format!("a sweep may have at most {} runs", MAX_RUNS)).into(),
+            );
+        }
+        for (i, parameter) in parameters.iter().enumerate() {
+            if parameters[..i].iter().any(|p| p.key == parameter.key) {
+                return Err(
+                    ParseError::Invalid(format!("`{}` is swept twice", parameter.key)).into(),
+                );
+            }
+        }
+        Ok(Self {
+            command: file.command,
+            parallel: file.parallel,
+            base: flatten(&file.base),
+            parameters,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).map_err(IoError::path("read", path))?;
+        Self::parse(&text).with_context(|| format!("failed to parse {}", path.display()))
+    }
+
+    /// Every combination of the parameter values, the last parameter changing fastest.
+    pub fn runs(&self) -> Vec<Run> {
+        let mut combinations = vec![vec![]];
+        for parameter in &self.parameters {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|values: Vec<(String, Value)>| {
+                    parameter.values.iter().map(move |value| {
+                        let mut values = values.clone();
+                        values.push((parameter.key.clone(), value.clone()));
+                        values
+                    })
+                })
+                .collect();
+        }
+        combinations
+            .into_iter()
+            .enumerate()
+            .map(|(i, values)| Run {
+                name: format!("run-{:03}", i),
+                values,
+            })
+            .collect()
+    }
+
+    /// The config file of `run`: the base keys with the values of the run, one dotted key
+    /// per line.
+    pub fn config(&self, run: &Run) -> String {
+        let mut text = String::new();
+        for (key, value) in &self.base {
+            if !run.values.iter().any(|(k, _)| k == key) {
+                text += &format!("{} = {}\n", key, value);
+            }
+        }
+        for (key, value) in &run.values {
+            text += &format!("{} = {}\n", key, value);
+        }
+        text
+    }
+}
+
+/// The dotted keys of the values in `table`.
+fn flatten(table: &Table) -> Vec<(String, Value)> {
+    let mut values = vec![];
+    for (key, value) in table {
+        match value {
+            Value::Table(table) => values.extend(
+                flatten(table)
+                    .into_iter()
+                    .map(|(k, v)| (format!("{}.{}", key, k), v)),
+            ),
+            _ => values.push((key.clone(), value.clone())),
+        }
+    }
+    values
+}
+
+/// The values of `[start, end, count]`.
+fn range(value: &Value, log: bool) -> Result<Vec<Value>> {
+    let Value::Array(items) = value else {
+        return Err(ParseError::Invalid("not a list".to_string()).into());
+    };
+    let [start, end, Value::Integer(count)] = items.as_slice() else {
+        return Err(ParseError::Invalid("not three values".to_string()).into());
+    };
+    let number = |value: &Value| match *value {
+        Value::Integer(n) => Ok(n as f64),
+        Value::Float(x) => Ok(x),
+        _ => Err(ParseError::Invalid(format!("{} isn't a number", value))),
+    };
+    let (a, b, count) = (number(start)?, number(end)?, *count);
+    if count < 1 || count > MAX_RUNS as i64 || (count == 1 && a != b) {
+        return Err(ParseError::Invalid(format!(
+            "can't take {} values from {} to {}",
+            count, a, b
+        ))
+        .into());
+    }
+    if log && (a <= 0. || b <= 0.) {
+        return Err(ParseError::Invalid("a log scale needs positive ends".to_string()).into());
+    }
+    let integers = matches!((start, end), (Value::Integer(_), Value::Integer(_)));
+    let mut values: Vec<Value> = vec![];
+    for i in 0..count {
+        let t = if count == 1 {
+            0.
+        } else {
+            i as f64 / (count - 1) as f64
+        };
+        let x = if log {
+            (a.ln() + t * (b.ln() - a.ln())).exp()
+        } else {
+            a + t * (b - a)
+        };
+        let value = if integers {
+            Value::Integer(x.round() as i64)
+        } else {
+            Value::Float(x)
+        };
+        if !values.contains(&value) {
+            values.push(value);
+        }
+    }
+    Ok(values)
+}
+
+/// The last value of every tag in a CSV file of metrics.
+pub fn final_metrics(path: &Path) -> Result<BTreeMap<String, f64>> {
+    let file = fs::File::open(path).map_err(IoError::path("open", path))?;
+    let mut metrics = BTreeMap::new();
+    for (i, line) in BufReader::new(file).lines().enumerate().skip(1) {
+        let line = line?;
+        let fields: Vec<_> = line.split(',').collect();
+        let [_, _, tag, value] = fields.as_slice() else {
+            return Err(ParseError::Invalid(format!(
+                "line {} of {} isn't a metric",
+                i + 1,
+                path.display()
+            ))
+            .into());
+        };
+        let value = value
+            .parse()
+            .with_context(|| format!("line {} of {}", i + 1, path.display()))?;
+        metrics.insert(tag.to_string(), value);
+    }
+    Ok(metrics)
+}
+
+/// How a run ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Finished(BTreeMap<String, f64>),
+    Failed(String),
+}
+
+/// The runs of a sweep side by side: their parameter values, then their final metrics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub parameters: Vec<String>,
+    pub runs: Vec<(Run, Outcome)>,
+}
+
+impl Comparison {
+    /// Every metric of a finished run.
+    pub fn metrics(&self) -> Vec<String> {
+        let mut tags: Vec<_> = self
+            .runs
+            .iter()
+            .filter_map(|(_, outcome)| match outcome {
+                Outcome::Finished(metrics) => Some(metrics.keys().cloned()),
+                Outcome::Failed(_) => None,
+            })
+            .flatten()
+            .collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+
+    /// Order the runs by a metric, highest first or, like losses, lowest first, and those
+    /// without it last.
+    pub fn sort_by(&mut self, tag: &str, lowest_first: bool) {
+        let value = |outcome: &Outcome| match outcome {
+            Outcome::Finished(metrics) => metrics.get(tag).copied(),
+            Outcome::Failed(_) => None,
+        };
+        self.runs
+            .sort_by(|(_, a), (_, b)| match (value(a), value(b)) {
+                (Some(a), Some(b)) if lowest_first => a.total_cmp(&b),
+                (Some(a), Some(b)) => b.total_cmp(&a),
+                (a, b) => b.is_some().cmp(&a.is_some()),
+            });
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        let metrics = self.metrics();
+        let mut rows = vec![];
+        let mut header = vec!["run".to_string()];
+        header.extend(self.parameters.iter().cloned());
+        header.extend(metrics.iter().cloned());
+        rows.push(header);
+        for (run, outcome) in &self.runs {
+            let mut row = vec![run.name.clone()];
+            row.extend(run.values.iter().map(|(_, value)| match value {
+                Value::String(s) => s.clone(),
+                value => value.to_string(),
+            }));
+            match outcome {
+                Outcome::Finished(values) => row.extend(metrics.iter().map(|tag| {
+                    values
+                        .get(tag)
+                        .map_or_else(String::new, |value| format!("{:.4}", value))
+                })),
+                Outcome::Failed(error) => row.push(format!("failed: {}", error)),
+            }
+            rows.push(row);
+        }
+        rows
+    }
+
+    /// The table as CSV, failed runs with empty metrics.
+    pub fn to_csv(&self) -> String {
+        let columns = 1 + self.parameters.len() + self.metrics().len();
+        let mut text = String::new();
+        for mut row in self.rows() {
+            if row.last().is_some_and(|cell| cell.starts_with("failed: ")) {
+                row.pop();
+            }
+            row.resize(columns, String::new());
+            text += &row.join(",");
+            text += "\n";
+        }
+        text
+    }
+}
+
+/// An aligned text table.
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows = self.rows();
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let widths: Vec<_> = (0..columns)
+            .map(|column| {
+                rows.iter()
+                    .filter(|row| row.len() > column + 1)
+                    .map(|row| row[column].len())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        for row in &rows {
+            let cells: Vec<_> = row
+                .iter()
+                .enumerate()
+                .map(|(column, cell)| format!("{:<width$}", cell, width = widths[column]))
+                .collect();
+            writeln!(f, "{}", cells.join("  ").trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+command = "eval"
+parallel = 2
+
+[base]
+game = "tictactoe"
+mcts.simulations = 50
+
+[grid]
+mcts.simulations = [10, 20]
+
+[range]
+mcts.exploration = [0.5, 1.5, 3]
+eval.games = [2, 4, 3]
+
+[log_range]
+train.lr = [0.001, 0.1, 3]
+"#;
+
+    #[test]
+    fn test_sweep_config() {
+        let config = SweepConfig::parse(CONFIG).unwrap();
+        assert_eq!((config.command.as_str(), config.parallel), ("eval", 2));
+        let values: Vec<_> = config
+            .parameters
+            .iter()
+            .map(|p| {
+                (
+                    p.key.as_str(),
+                    p.values.iter().map(ToString::to_string).collect(),
+                )
+            })
+            .collect::<Vec<(_, Vec<_>)>>();
+        assert_eq!(
+            values[0],
+            ("mcts.simulations", vec!["10".into(), "20".into()])
+        );
+        assert_eq!(values[1].1, ["0.5", "1.0", "1.5"]);
+        assert_eq!(values[2].1, ["2", "3", "4"]);
+        let lr: Vec<f64> = config.parameters[3]
+            .values
+            .iter()
+            .map(|value| match value {
+                Value::Float(x) => *x,
+                _ => panic!("{:?}", value),
+            })
+            .collect();
+        assert_eq!(lr.len(), 3);
+        assert!((lr[1] - 0.01).abs() < 1e-9, "{:?}", lr);
+
+        let runs = config.runs();
+        assert_eq!(runs.len(), 2 * 3 * 3 * 3);
+        assert_eq!(runs[1].name, "run-001");
+        let text = config.config(&runs[1]);
+        assert_eq!(
+            text,
+            "game = \"tictactoe\"\nmcts.simulations = 10\nmcts.exploration = 0.5\n\
+             eval.games = 2\ntrain.lr = 0.01\n"
+                .replace("0.01", &config.parameters[3].values[1].to_string())
+        );
+        let table: Table = toml::from_str(&text).unwrap();
+        assert_eq!(table["mcts"]["simulations"], Value::Integer(10));
+
+        assert!(SweepConfig::parse("parallel = 2").is_err());
+        assert!(SweepConfig::parse("command = \"eval\"\nparallel = 0").is_err());
+        assert!(SweepConfig::parse("command = \"eval\"\n[grid]\nx = 1").is_err());
+        assert!(SweepConfig::parse("command = \"eval\"\n[range]\nx = [1, 2]").is_err());
+        assert!(SweepConfig::parse("command = \"eval\"\n[log_range]\nx = [0, 2, 3]").is_err());
+        assert!(SweepConfig::parse("command = \"eval\"\ngrids = 1").is_err());
+        assert!(SweepConfig::parse("command = \"eval\"\n[range]\nx = [1, 5000, 2000]").is_err());
+        assert!(SweepConfig::parse(
+            "command = \"eval\"\n[range]\nx = [1, 40, 40]\ny = [1, 40, 40]"
+        )
+        .is_err());
+        assert!(
+            SweepConfig::parse("command = \"eval\"\n[grid]\nx = [1]\n[range]\nx = [1, 2, 2]")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_comparison() {
+        let path = std::env::temp_dir().join(format!("muzero-sweep-{}.csv", std::process::id()));
+        fs::write(
+            &path,
+            "wall_time,step,tag,value\n1.0,1,eval/elo,10\n2.0,2,eval/elo,-5\n2.0,2,eval/score,0.4\n",
+        )
+        .unwrap();
+        let metrics = final_metrics(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(metrics.get("eval/elo"), Some(&-5.));
+
+        let config =
+            SweepConfig::parse("command = \"eval\"\n[grid]\nmcts.exploration = [1.0, 2.0, 3.0]")
+                .unwrap();
+        let runs = config.runs();
+        let mut better = metrics.clone();
+        better.insert("eval/elo".to_string(), 50.);
+        let mut comparison = Comparison {
+            parameters: vec!["mcts.exploration".to_string()],
+            runs: vec![
+                (runs[0].clone(), Outcome::Finished(metrics)),
+                (
+                    runs[1].clone(),
+                    Outcome::Failed("exit status: 1".to_string()),
+                ),
+                (runs[2].clone(), Outcome::Finished(better)),
+            ],
+        };
+        comparison.sort_by("eval/elo", false);
+        let names: Vec<_> = comparison
+            .runs
+            .iter()
+            .map(|(run, _)| run.name.as_str())
+            .collect();
+        assert_eq!(names, ["run-002", "run-000", "run-001"]);
+        assert_eq!(
+            comparison.to_csv(),
+            "run,mcts.exploration,eval/elo,eval/score\n\
+             run-002,3.0,50.0000,0.4000\n\
+             run-000,1.0,-5.0000,0.4000\n\
+             run-001,2.0,,\n"
+        );
+        comparison.sort_by("eval/elo", true);
+        assert_eq!(comparison.runs[0].0.name, "run-000");
+        assert_eq!(comparison.runs[2].0.name, "run-001");
+        let table = comparison.to_string();
+        assert!(
+            table.starts_with("run      mcts.exploration  eval/elo  eval/score\n"),
+            "{}",
+            table
+        );
+        assert!(
+            table.contains("run-001  2.0               failed: exit status: 1\n"),
+            "{}",
+            table
+        );
+    }
+}
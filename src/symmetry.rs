@@ -0,0 +1,267 @@
+//! Board symmetries: transforms under which the rules of a game don't change, used to augment
+//! training samples and to average the evaluations at the root of a search.
+
+use crate::{
+    cache::CacheStats,
+    network::{softmax, Network, NetworkOutput, Pending},
+    replay::Sample,
+};
+
+/// A symmetry of a board, mapping both the squares of observations and the actions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transform {
+    /// The square each square of a transformed observation plane comes from.
+    squares: Vec<usize>,
+    /// The action each action maps to.
+    actions: Vec<usize>,
+}
+
+impl Transform {
+    /// A transform moving the square `squares[i]` to `i` in every plane of an observation, and
+    /// the action `a` to `actions[a]`. Both must be permutations.
+    pub fn new(squares: Vec<usize>, actions: Vec<usize>) -> Self {
+        Self { squares, actions }
+    }
+
+    /// The 8 rotations and reflections of a `size` by `size` board, the identity first, for a
+    /// game whose actions are the squares in row-major order followed by actions like passing,
+    /// which don't move.
+    pub fn dihedral(size: usize, action_space_size: usize) -> Vec<Self> {
+        let area = size * size;
+        let last = size - 1;
+        (0..8)
+            .map(|symmetry| {
+                let mut squares = vec![0; area];
+                let mut actions: Vec<usize> = (0..action_space_size).collect();
+                for r in 0..size {
+                    for c in 0..size {
+                        let (row, col) = match symmetry {
+                            0 => (r, c),
+                            1 => (c, last - r),
+                            2 => (last - r, last - c),
+                            3 => (last - c, r),
+                            4 => (r, last - c),
+                            5 => (last - r, c),
+                            6 => (c, r),
+                            _ => (last - c, last - r),
+                        };
+                        squares[row * size + col] = r * size + c;
+                        actions[r * size + c] = row * size + col;
+                    }
+                }
+                Self::new(squares, actions)
+            })
+            .collect()
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.squares
+            .iter()
+            .enumerate()
+            .all(|(i, &square)| i == square)
+            && self
+                .actions
+                .iter()
+                .enumerate()
+                .all(|(i, &action)| i == action)
+    }
+
+    /// Transform every plane of `observation`, e.g. of stacked observations too.
+    pub fn observation(&self, observation: &[f32]) -> Vec<f32> {
+        observation
+            .chunks(self.squares.len())
+            .flat_map(|plane| self.squares.iter().map(|&square| plane[square]))
+            .collect()
+    }
+
+    pub fn action(&self, action: usize) -> usize {
+        self.actions[action]
+    }
+
+    /// Move the value of every action to the action it maps to. Empty policies stay empty.
+    pub fn policy(&self, policy: &[f32]) -> Vec<f32> {
+        let mut transformed = vec![0.; policy.len()];
+        for (action, &p) in policy.iter().enumerate() {
+            transformed[self.actions[action]] = p;
+        }
+        transformed
+    }
+
+    /// The inverse of [`Transform::policy`].
+    pub fn inverse_policy(&self, policy: &[f32]) -> Vec<f32> {
+        (0..policy.len())
+            .map(|action| policy[self.actions[action]])
+            .collect()
+    }
+
+    /// The same sample on the transformed board.
+    pub fn sample(&self, sample: &Sample) -> Sample {
+        let mut transformed = sample.clone();
+        transformed.observation = self.observation(&sample.observation);
+        for observation in &mut transformed.next_observations {
+            *observation = self.observation(observation);
+        }
+        for action in &mut transformed.actions {
+            *action = self.action(*action);
+        }
+        for target in &mut transformed.targets {
+            target.policy = self.policy(&target.policy);
+        }
+        transformed
+    }
+}
+
+/// Evaluates the root of a search as the average over the symmetries of the board, which
+/// smooths out the errors of the network. Other states are evaluated by the wrapped network
+/// as they are, and the root's hidden state is the one of the observation itself.
+pub struct SymmetricNetwork<N> {
+    pub network: N,
+    pub transforms: Vec<Transform>,
+}
+
+impl<N: Network> Network for SymmetricNetwork<N> {
+    fn initial_inference(&self, observation: &[f32]) -> NetworkOutput {
+        let transforms: Vec<_> = self
+            .transforms
+            .iter()
+            .filter(|transform| !transform.is_identity())
+            .collect();
+        let observations: Vec<_> = transforms
+            .iter()
+            .map(|transform| transform.observation(observation))
+            .collect();
+        let mut inputs = vec![observation];
+        inputs.extend(observations.iter().map(Vec::as_slice));
+        let mut outputs = self.network.initial_inference_batch(&inputs).into_iter();
+        let mut output = outputs.next().expect("one output per observation");
+        let mut policy = softmax(&output.policy_logits);
+        for (transform, other) in transforms.iter().zip(outputs) {
+            output.value += other.value;
+            output.reward += other.reward;
+            let other_policy = transform.inverse_policy(&softmax(&other.policy_logits));
+            for (p, other) in policy.iter_mut().zip(other_policy) {
+                *p += other;
+            }
+        }
+        let n = inputs.len() as f32;
+        output.value /= n;
+        output.reward /= n;
+        output.policy_logits = policy.iter().map(|p| (p / n).ln()).collect();
+        output
+    }
+
+    fn recurrent_inference(&self, hidden_state: &[f32], action: usize) -> NetworkOutput {
+        self.network.recurrent_inference(hidden_state, action)
+    }
+
+    fn is_absorbing(&self, hidden_state: &[f32]) -> bool {
+        self.network.is_absorbing(hidden_state)
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.network.cache_stats()
+    }
+
+    fn recurrent_inference_batch(&self, inputs: &[(&[f32], usize)]) -> Vec<NetworkOutput> {
+        self.network.recurrent_inference_batch(inputs)
+    }
+
+    fn submit_recurrent_batch(&self, inputs: &[(&[f32], usize)]) -> Vec<Pending> {
+        self.network.submit_recurrent_batch(inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::Target;
+
+    #[test]
+    fn test_dihedral() {
+        let transforms = Transform::dihedral(2, 5);
+        assert_eq!(transforms.len(), 8);
+        assert!(transforms[0].is_identity());
+        assert!(transforms[1..]
+            .iter()
+            .all(|transform| !transform.is_identity()));
+        // A quarter turn clockwise, on two planes: the top left goes to the top right.
+        let rotation = &transforms[1];
+        assert_eq!(
+            rotation.observation(&[1., 2., 3., 4., 5., 6., 7., 8.]),
+            [3., 1., 4., 2., 7., 5., 8., 6.]
+        );
+        assert_eq!(rotation.action(0), 1);
+        // The pass doesn't move.
+        assert_eq!(rotation.action(4), 4);
+        let policy = [0.1, 0.2, 0.3, 0.4, 0.];
+        assert_eq!(rotation.policy(&policy), [0.3, 0.1, 0.4, 0.2, 0.]);
+        assert_eq!(rotation.inverse_policy(&rotation.policy(&policy)), policy);
+    }
+
+    #[test]
+    fn test_sample() {
+        let transform = &Transform::dihedral(2, 4)[6];
+        let target = Target {
+            value: 1.,
+            reward: 0.,
+            policy: vec![0., 1., 0., 0.],
+            absorbing: false,
+        };
+        let sample = Sample {
+            observation: vec![1., 2., 3., 4.],
+            actions: vec![1],
+            targets: vec![
+                target.clone(),
+                Target {
+                    policy: vec![],
+                    ..target
+                },
+            ],
+            next_observations: vec![vec![0., 1., 0., 0.]],
+            game_id: 0,
+            index: 0,
+            weight: 1.,
+        };
+        // The transpose.
+        let transformed = transform.sample(&sample);
+        assert_eq!(transformed.observation, [1., 3., 2., 4.]);
+        assert_eq!(transformed.actions, [2]);
+        assert_eq!(transformed.targets[0].policy, [0., 0., 1., 0.]);
+        assert!(transformed.targets[1].policy.is_empty());
+        assert_eq!(transformed.next_observations, [[0., 0., 1., 0.]]);
+    }
+
+    /// Prefers the top left square, and values observations by their first entry.
+    struct CornerNetwork;
+
+    impl Network for CornerNetwork {
+        fn initial_inference(&self, observation: &[f32]) -> NetworkOutput {
+            NetworkOutput {
+                value: observation[0],
+                reward: 0.,
+                policy_logits: vec![2f32.ln(), 0., 0., 0.],
+                hidden_state: observation.to_vec(),
+            }
+        }
+
+        fn recurrent_inference(&self, _hidden_state: &[f32], _action: usize) -> NetworkOutput {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn test_symmetric_network() {
+        let network = SymmetricNetwork {
+            network: CornerNetwork,
+            transforms: Transform::dihedral(2, 4),
+        };
+        let output = network.initial_inference(&[1., 0., 0., 0.]);
+        assert_eq!(output.hidden_state, [1., 0., 0., 0.]);
+        // The corner holding the 1 goes to each corner twice.
+        assert!((output.value - 0.25).abs() < 1e-6);
+        let policy = softmax(&output.policy_logits);
+        for p in policy {
+            assert!((p - 0.25).abs() < 1e-6);
+        }
+    }
+}
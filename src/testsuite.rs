@@ -0,0 +1,208 @@
+//! Test suites: positions with known best moves, searched with a fixed budget to measure
+//! strength and catch regressions without playing whole games.
+//!
+//! A suite file lists one puzzle per line, like EPD in chess: the position in the notation of
+//! [`Game::to_notation`], then operations separated by `;`. `bm` lists the indices of the
+//! best moves, any of which solves the puzzle, `am` those of moves to avoid, and `id` names
+//! the puzzle. Blank lines and lines starting with `#` are skipped:
+//!
+//! ```text
+//! # TicTacToe: O must block the top row.
+//! XX1/1O1/3 O; bm 2; id block the top row
+//! ```
+
+use std::{fs, path::Path};
+
+use crate::{
+    error::{Context, IoError, ParseError, Result},
+    game::Game,
+    mcts::{Mcts, SearchResult},
+};
+
+/// A position of a suite and the moves which solve it.
+#[derive(Debug, Clone)]
+pub struct Puzzle<G> {
+    /// The `id` of the puzzle, or its line in the file.
+    pub id: String,
+    pub game: G,
+    pub best_moves: Vec<usize>,
+    pub avoid_moves: Vec<usize>,
+}
+
+impl<G> Puzzle<G> {
+    /// Whether playing the action with `index` solves the puzzle.
+    pub fn solved_by(&self, index: usize) -> bool {
+        (self.best_moves.is_empty() || self.best_moves.contains(&index))
+            && !self.avoid_moves.contains(&index)
+    }
+}
+
+pub fn load<G: Game>(
+    path: &Path,
+    parse_position: impl Fn(&str) -> Result<G, ParseError>,
+) -> Result<Vec<Puzzle<G>>> {
+    let text = fs::read_to_string(path).map_err(IoError::path("read", path))?;
+    parse(&text, parse_position).with_context(|| format!("in {}", path.display()))
+}
+
+/// The puzzles of the suite `text`, with `parse_position` reading the positions.
+pub fn parse<G: Game>(
+    text: &str,
+    parse_position: impl Fn(&str) -> Result<G, ParseError>,
+) -> Result<Vec<Puzzle<G>>> {
+    let mut puzzles = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let puzzle = parse_puzzle(line, i + 1, &parse_position)
+            .with_context(|| format!("on line {}", i + 1))?;
+        puzzles.push(puzzle);
+    }
+    Ok(puzzles)
+}
+
+fn parse_puzzle<G: Game>(
+    line: &str,
+    number: usize,
+    parse_position: impl Fn(&str) -> Result<G, ParseError>,
+) -> Result<Puzzle<G>> {
+    let mut fields = line.split(';');
+    let position = fields
+        .next()
+        .expect("split yields at least one field")
+        .trim();
+    let game =
+        parse_position(position).with_context(|| format!("invalid position `{}`", position))?;
+    if game.done() {
+        return Err(ParseError::Invalid("the game is over".to_string()).into());
+    }
+    if !game.chance_outcomes().is_empty() {
+        return Err(
+            ParseError::Invalid("a chance event is pending, not a move".to_string()).into(),
+        );
+    }
+    let mut puzzle = Puzzle {
+        id: format!("line {}", number),
+        game,
+        best_moves: vec![],
+        avoid_moves: vec![],
+    };
+    for field in fields {
+        let field = field.trim();
+        let (opcode, operand) = field.split_once(' ').unwrap_or((field, ""));
+        let operand = operand.trim();
+        match opcode {
+            "" => {}
+            "id" => puzzle.id = operand.to_string(),
+            "bm" | "am" => {
+                let moves = operand
+                    .split_whitespace()
+                    .map(str::parse)
+                    .collect::<Result<Vec<usize>, _>>()
+                    .with_context(|| format!("invalid moves `{}`", operand))?;
+                if moves.is_empty() {
+                    return Err(ParseError::Invalid(format!("`{}` lists no moves", opcode)).into());
+                }
+                let mask = puzzle.game.legal_action_mask();
+                if let Some(index) = moves
+                    .iter()
+                    .find(|&&i| !mask.get(i).copied().unwrap_or(false))
+                {
+                    return Err(
+                        ParseError::Invalid(format!("the move {} isn't legal", index)).into(),
+                    );
+                }
+                if opcode == "bm" {
+                    puzzle.best_moves.extend(moves);
+                } else {
+                    puzzle.avoid_moves.extend(moves);
+                }
+            }
+            _ => return Err(ParseError::Invalid(format!("unknown operation `{}`", opcode)).into()),
+        }
+    }
+    if puzzle.best_moves.is_empty() && puzzle.avoid_moves.is_empty() {
+        return Err(ParseError::Invalid("expected `bm` or `am` moves".to_string()).into());
+    }
+    Ok(puzzle)
+}
+
+/// Searches every puzzle with `mcts` and returns how many were solved, calling `report` with
+/// each puzzle, its search and whether the chosen move solved it.
+pub fn run<G: Game>(
+    puzzles: &[Puzzle<G>],
+    mcts: &Mcts<G>,
+    mut report: impl FnMut(&Puzzle<G>, &SearchResult<G::Action>, bool),
+) -> usize {
+    let mut solved = 0;
+    for puzzle in puzzles {
+        let result = mcts.search(&puzzle.game);
+        let ok = puzzle.solved_by(puzzle.game.action_to_index(&result.action));
+        solved += usize::from(ok);
+        report(puzzle, &result, ok);
+    }
+    solved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games::{connect_four::ConnectFour, tic_tac_toe::TicTacToe},
+        mcts::MctsConfig,
+    };
+
+    #[test]
+    fn test_parse() {
+        let text = "# comment\n\nXX1/1O1/3 O; bm 2; id block\nX2/1O1/2X O; am 2 6\n";
+        let puzzles = parse(text, TicTacToe::from_notation).unwrap();
+        assert_eq!(puzzles.len(), 2);
+        assert_eq!(puzzles[0].id, "block");
+        assert_eq!(puzzles[0].best_moves, [2]);
+        assert!(puzzles[0].solved_by(2) && !puzzles[0].solved_by(5));
+        assert_eq!(puzzles[1].id, "line 4");
+        assert!(puzzles[1].solved_by(1) && !puzzles[1].solved_by(6));
+
+        for text in [
+            "XX1/1O1/3 O",
+            "XX1/1O1/3 O; bm",
+            "XX1/1O1/3 O; bm 0",
+            "XX1/1O1/3 O; bm 9",
+            "XX1/1O1/3 O; xx 2",
+            "XX1/1O 0; bm 2",
+            "XXX/OO1/3 O; bm 5",
+        ] {
+            assert!(parse(text, TicTacToe::from_notation).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_shipped_suites() {
+        let puzzles = parse(
+            include_str!("../suites/tictactoe.txt"),
+            TicTacToe::from_notation,
+        )
+        .unwrap();
+        let mcts = Mcts::with_config(MctsConfig {
+            num_simulations: 400,
+            seed: Some(0),
+            ..Default::default()
+        });
+        let mut failed = vec![];
+        let solved = run(&puzzles, &mcts, |puzzle, _, ok| {
+            if !ok {
+                failed.push(puzzle.id.clone());
+            }
+        });
+        assert_eq!(solved, puzzles.len(), "failed {:?}", failed);
+
+        let puzzles = parse(
+            include_str!("../suites/connect4.txt"),
+            ConnectFour::from_notation,
+        )
+        .unwrap();
+        assert_eq!(puzzles.len(), 2);
+    }
+}
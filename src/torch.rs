@@ -0,0 +1,375 @@
+//! A [`Model`] on libtorch through tch-rs, behind the `tch` feature, for CUDA GPUs and half
+//! precision. Building it needs libtorch, see the tch-rs README; `--features tch,tch/doc-only`
+//! type-checks it without.
+//!
+//! The model is a small fully connected network on flat observations: the representation and
+//! the dynamics are a linear layer and a ReLU each, the dynamics taking the hidden state and
+//! the action one-hot, and the reward, policy and value are linear heads. It evaluates
+//! positions and saves and loads its weights; training it with libtorch's autograd isn't
+//! implemented yet.
+
+use tch::{
+    nn::{self, Module},
+    Kind, Tensor,
+};
+
+use crate::{
+    checkpoint::{self, Tensors},
+    error::{ConfigError, Result},
+    loss::Losses,
+    model::{Device, Model, Precision, Trainable},
+    replay::Sample,
+};
+
+pub struct TchModel {
+    vars: nn::VarStore,
+    representation: nn::Linear,
+    dynamics: nn::Linear,
+    reward: nn::Linear,
+    policy: nn::Linear,
+    value: nn::Linear,
+    action_space_size: usize,
+    device: Device,
+    kind: Kind,
+}
+
+impl TchModel {
+    /// A freshly initialized model on the CPU, in f32.
+    pub fn new(observation_size: usize, hidden_size: usize, action_space_size: usize) -> Self {
+        let vars = nn::VarStore::new(tch::Device::Cpu);
+        let root = vars.root();
+        let linear = |name: &str, inputs: usize, outputs: usize| {
+            nn::linear(
+                &root / name,
+                inputs as i64,
+                outputs as i64,
+                Default::default(),
+            )
+        };
+        Self {
+            representation: linear("representation", observation_size, hidden_size),
+            dynamics: linear("dynamics", hidden_size + action_space_size, hidden_size),
+            reward: linear("reward", hidden_size, 1),
+            policy: linear("policy", hidden_size, action_space_size),
+            value: linear("value", hidden_size, 1),
+            vars,
+            action_space_size,
+            device: Device::Cpu,
+            kind: Kind::Float,
+        }
+    }
+
+    /// `data` as a batch of one on the device of the model, in its precision.
+    fn input(&self, data: &[f32]) -> Tensor {
+        Tensor::from_slice(data)
+            .view([1, -1])
+            .to_device(self.vars.device())
+            .to_kind(self.kind)
+    }
+}
+
+/// The values of `values`, one row per sample, as a `[batch, -1]` tensor on `device`.
+fn batch_input(values: impl Iterator<Item = f32>, rows: usize, device: tch::Device) -> Tensor {
+    let values: Vec<f32> = values.collect();
+    Tensor::from_slice(&values)
+        .view([rows as i64, -1])
+        .to_device(device)
+}
+
+/// The values of `tensor`, flattened, as f32 on the CPU.
+fn output(tensor: &Tensor) -> Vec<f32> {
+    let tensor = tensor
+        .to_kind(Kind::Float)
+        .to_device(tch::Device::Cpu)
+        .view([-1]);
+    Vec::try_from(&tensor).expect("the tensor is f32")
+}
+
+impl Model for TchModel {
+    fn forward_representation(&self, observation: &[f32]) -> Vec<f32> {
+        tch::no_grad(|| output(&self.representation.forward(&self.input(observation)).relu()))
+    }
+
+    fn forward_dynamics(&self, hidden_state: &[f32], action: usize) -> (Vec<f32>, f32) {
+        let mut one_hot = vec![0.; self.action_space_size];
+        one_hot[action] = 1.;
+        tch::no_grad(|| {
+            let input = Tensor::cat(&[self.input(hidden_state), self.input(&one_hot)], 1);
+            let next = self.dynamics.forward(&input).relu();
+            (output(&next), output(&self.reward.forward(&next))[0])
+        })
+    }
+
+    fn forward_prediction(&self, hidden_state: &[f32]) -> (Vec<f32>, f32) {
+        tch::no_grad(|| {
+            let hidden_state = self.input(hidden_state);
+            (
+                output(&self.policy.forward(&hidden_state)),
+                output(&self.value.forward(&hidden_state))[0],
+            )
+        })
+    }
+
+    fn tensors(&self) -> Tensors {
+        let mut variables: Vec<_> = self.vars.variables().into_iter().collect();
+        variables.sort_by(|(a, _), (b, _)| a.cmp(b));
+        variables
+            .into_iter()
+            .map(|(name, tensor)| {
+                let shape = tensor.size().iter().map(|&dim| dim as usize).collect();
+                let tensor = checkpoint::Tensor::new(shape, output(&tensor))
+                    .expect("the values fill the shape");
+                (name, tensor)
+            })
+            .collect()
+    }
+
+    fn load_tensors(&mut self, tensors: &Tensors) -> Result<()> {
+        let mut variables = self.vars.variables();
+        if tensors.len() != variables.len() {
+            return Err(ConfigError::Invalid(format!(
+                "expected {} tensors, got {}",
+                variables.len(),
+                tensors.len()
+            ))
+            .into());
+        }
+        for (name, tensor) in tensors {
+            let variable = variables.get_mut(name).ok_or_else(|| {
+                ConfigError::Invalid(format!("the model has no tensor `{}`", name))
+            })?;
+            let shape: Vec<_> = tensor.shape.iter().map(|&dim| dim as i64).collect();
+            if variable.size() != shape {
+                return Err(ConfigError::Invalid(format!(
+                    "tensor `{}` is {:?}, expected {:?}",
+                    name,
+                    shape,
+                    variable.size()
+                ))
+                .into());
+            }
+            let values = Tensor::from_slice(&tensor.data)
+                .view(shape.as_slice())
+                .to_device(variable.device())
+                .to_kind(variable.kind());
+            tch::no_grad(|| variable.copy_(&values));
+        }
+        Ok(())
+    }
+
+    fn device(&self) -> Device {
+        self.device
+    }
+
+    fn to_device(&mut self, device: Device) -> Result<()> {
+        let target = match device {
+            Device::Cpu => tch::Device::Cpu,
+            Device::Cuda(index) if (index as i64) < tch::Cuda::device_count() => {
+                tch::Device::Cuda(index)
+            }
+            Device::Metal if tch::utils::has_mps() => tch::Device::Mps,
+            device => {
+                return Err(
+                    ConfigError::Unsupported(format!("libtorch can't run on {}", device)).into(),
+                )
+            }
+        };
+        self.vars.set_device(target);
+        self.device = device;
+        Ok(())
+    }
+
+    fn set_precision(&mut self, precision: Precision) -> Result<()> {
+        self.kind = match precision {
+            Precision::F32 => Kind::Float,
+            Precision::F16 => Kind::Half,
+            Precision::Bf16 => Kind::BFloat16,
+        };
+        self.vars.set_kind(self.kind);
+        Ok(())
+    }
+}
+
+impl Trainable for TchModel {
+    fn gradients(&mut self, batch: &[Sample]) -> Result<(Losses, Tensors)> {
+        if self.kind != Kind::Float {
+            return Err(ConfigError::Unsupported(format!(
+                "training runs in f32, not {:?}",
+                self.kind
+            ))
+            .into());
+        }
+        let Some(first) = batch.first() else {
+            return Err(ConfigError::Invalid("the batch is empty".to_string()).into());
+        };
+        let steps = first.targets.len();
+        let actions = self.action_space_size;
+        for sample in batch {
+            if sample.targets.len() != steps || sample.actions.len() + 1 != steps {
+                return Err(ConfigError::Invalid(
+                    "the samples of a batch unroll the same number of actions".to_string(),
+                )
+                .into());
+            }
+            if sample.actions.iter().any(|&action| action >= actions)
+                || sample
+                    .targets
+                    .iter()
+                    .any(|target| !target.policy.is_empty() && target.policy.len() != actions)
+            {
+                return Err(ConfigError::Invalid(format!(
+                    "the actions and policies of the samples aren't of {} actions",
+                    actions
+                ))
+                .into());
+            }
+        }
+        let device = self.vars.device();
+        let rows = batch.len();
+        let column =
+            |f: &dyn Fn(&Sample) -> f32| batch_input(batch.iter().map(f), rows, device).view([-1]);
+        // Every sample counts as much as its weight, its steps equally.
+        let scale = column(&|sample| sample.weight) / (steps * rows) as f64;
+        let observations = batch_input(
+            batch
+                .iter()
+                .flat_map(|sample| sample.observation.iter().copied()),
+            rows,
+            device,
+        );
+        let mut state = self.representation.forward(&observations).relu();
+        let zero = || Tensor::zeros([], (Kind::Float, device));
+        let (mut value_loss, mut reward_loss, mut policy_loss) = (zero(), zero(), zero());
+        for k in 0..steps {
+            if k > 0 {
+                let one_hot = batch_input(
+                    batch.iter().flat_map(|sample| {
+                        (0..actions).map(move |a| (a == sample.actions[k - 1]) as u8 as f32)
+                    }),
+                    rows,
+                    device,
+                );
+                state = self
+                    .dynamics
+                    .forward(&Tensor::cat(&[state, one_hot], 1))
+                    .relu();
+                let reward = self.reward.forward(&state).view([-1]);
+                let error = (reward - column(&|sample| sample.targets[k].reward)).square();
+                reward_loss += (error * &scale).sum(Kind::Float);
+            }
+            let value = self.value.forward(&state).view([-1]);
+            let error = (value - column(&|sample| sample.targets[k].value)).square();
+            value_loss += (error * &scale).sum(Kind::Float);
+            // Masked absorbing states have no policy target, so an all-zero one adds nothing.
+            let policy = batch_input(
+                batch
+                    .iter()
+                    .flat_map(|sample| match sample.targets[k].policy.as_slice() {
+                        [] => vec![0.; actions],
+                        policy => policy.to_vec(),
+                    }),
+                rows,
+                device,
+            );
+            let log_probs = self.policy.forward(&state).log_softmax(-1, Kind::Float);
+            let cross_entropy = -(policy * log_probs).sum_dim_intlist(-1, false, Kind::Float);
+            policy_loss += (cross_entropy * &scale).sum(Kind::Float);
+        }
+        for (_, mut variable) in self.vars.variables() {
+            variable.zero_grad();
+        }
+        (&value_loss + &reward_loss + &policy_loss).backward();
+        let mut variables: Vec<_> = self.vars.variables().into_iter().collect();
+        variables.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let gradients = variables
+            .into_iter()
+            .map(|(name, variable)| {
+                let shape = variable.size().iter().map(|&dim| dim as usize).collect();
+                let gradient = checkpoint::Tensor::new(shape, output(&variable.grad()))
+                    .expect("the values fill the shape");
+                (name, gradient)
+            })
+            .collect();
+        let losses = Losses {
+            value: value_loss.double_value(&[]) as f32,
+            reward: reward_loss.double_value(&[]) as f32,
+            policy: policy_loss.double_value(&[]) as f32,
+        };
+        Ok((losses, gradients))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mlp::MlpModel, model::ModelNetwork, network::Network, replay::Target};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_model() {
+        let mut model = TchModel::new(4, 8, 3);
+        let names: Vec<_> = model.tensors().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names[..2], ["dynamics.bias", "dynamics.weight"]);
+        let zeros: Tensors = model
+            .tensors()
+            .into_iter()
+            .map(|(name, tensor)| {
+                let zeros = vec![0.; tensor.data.len()];
+                (name, checkpoint::Tensor::new(tensor.shape, zeros).unwrap())
+            })
+            .collect();
+        model.load_tensors(&zeros).unwrap();
+        let network = ModelNetwork(model);
+        let output = network.initial_inference(&[1., 2., 3., 4.]);
+        assert_eq!(output.hidden_state, [0.; 8]);
+        assert_eq!(output.policy_logits, [0.; 3]);
+        let output = network.recurrent_inference(&output.hidden_state, 2);
+        assert_eq!(output.reward, 0.);
+
+        let mut model = network.0;
+        assert!(model.load_tensors(&zeros[1..].to_vec()).is_err());
+        assert!(model.to_device(Device::Wgpu).is_err());
+        model.set_precision(Precision::Bf16).unwrap();
+        assert_eq!(model.forward_representation(&[1., 2., 3., 4.]).len(), 8);
+    }
+
+    #[test]
+    fn test_gradients() {
+        let target = |value, reward, policy: &[f32]| Target {
+            value,
+            reward,
+            policy: policy.to_vec(),
+            absorbing: false,
+        };
+        let batch: Vec<_> = [([1., 0., -1., 2.], 2, 1.), ([0., 1., 0.5, 0.], 1, 0.5)]
+            .into_iter()
+            .map(|(observation, action, weight)| Sample {
+                observation: observation.to_vec(),
+                actions: vec![action],
+                targets: vec![target(0.5, 0., &[0.2, 0.8, 0.]), target(-1., 1., &[])],
+                next_observations: vec![],
+                game_id: 0,
+                index: 0,
+                weight,
+            })
+            .collect();
+        // The same weights have the gradients of the Rust model, backpropagated by hand.
+        let mut expected = MlpModel::new(4, 8, 3, &mut StdRng::seed_from_u64(0));
+        let mut model = TchModel::new(4, 8, 3);
+        model.load_tensors(&expected.tensors()).unwrap();
+        let (losses, gradients) = model.gradients(&batch).unwrap();
+        let (expected_losses, expected_gradients) = expected.gradients(&batch).unwrap();
+        assert!((losses.total() - expected_losses.total()).abs() < 1e-5);
+        for ((name, gradient), (expected_name, expected)) in
+            gradients.iter().zip(&expected_gradients)
+        {
+            assert_eq!(name, expected_name);
+            for (a, b) in gradient.data.iter().zip(&expected.data) {
+                assert!((a - b).abs() < 1e-5, "{}: {} and {}", name, a, b);
+            }
+        }
+        assert!(model.gradients(&[]).is_err());
+        model.set_precision(Precision::F16).unwrap();
+        assert!(model.gradients(&batch).is_err());
+    }
+}
@@ -0,0 +1,217 @@
+//! The training loop's steps, and the state of a run that checkpoints keep besides the
+//! weights, the optimizer and the replay buffer, so that a run resumed after a crash goes on
+//! as it would have.
+
+use rand::{rngs::StdRng, SeedableRng};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "serde")]
+use serde_json::Value;
+
+#[cfg(feature = "serde")]
+use crate::checkpoint::Checkpoint;
+use crate::{
+    checkpoint::Tensors,
+    error::{ConfigError, Result},
+    loss::Losses,
+    model::Trainable,
+    optimizer::{Optimizer, OptimizerConfig},
+    random,
+    replay::ReplayBuffer,
+};
+
+/// A model and its optimizer, learning from replay buffers one batch a step.
+pub struct Trainer<M> {
+    pub model: M,
+    pub optimizer: Optimizer,
+}
+
+impl<M: Trainable> Trainer<M> {
+    pub fn new(model: M, config: OptimizerConfig) -> Self {
+        Self {
+            model,
+            optimizer: Optimizer::new(config),
+        }
+    }
+
+    /// Go on from the weights and the optimizer state of a checkpoint taken after `steps`
+    /// steps of the trainer.
+    pub fn load(&mut self, weights: &Tensors, optimizer: &Tensors, steps: usize) -> Result<()> {
+        self.model.load_tensors(weights)?;
+        self.optimizer.load_state(steps, optimizer)
+    }
+
+    /// Take training step `step` of `run` on a batch of `buffer`, sampled with the random
+    /// numbers of the step. Returns the losses before the step and its learning rate.
+    pub fn train(
+        &mut self,
+        run: &RunState,
+        step: usize,
+        buffer: &ReplayBuffer,
+    ) -> Result<(Losses, f32)> {
+        if buffer.num_positions() == 0 {
+            return Err(
+                ConfigError::Invalid("the replay buffer has no positions".to_string()).into(),
+            );
+        }
+        let batch = buffer.sample_batch(&mut run.rng(step));
+        self.model.train_step(&batch, &mut self.optimizer)
+    }
+
+    /// A checkpoint of the trainer after `step` steps of `run`, keeping `replay_buffer`, e.g.
+    /// the [`ReplayBuffer::snapshot`] of the buffer it trains on.
+    #[cfg(feature = "serde")]
+    pub fn checkpoint(
+        &self,
+        run: &RunState,
+        step: usize,
+        replay_buffer: Value,
+    ) -> Result<Checkpoint> {
+        Ok(Checkpoint {
+            step,
+            weights: self.model.tensors(),
+            optimizer: self.optimizer.state(),
+            replay_buffer,
+            run: serde_json::to_value(run)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RunState {
+    /// The seed of every random number of the run. As a string, since JSON numbers lose the
+    /// low bits of large integers in most readers.
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "to_string", deserialize_with = "from_string")
+    )]
+    pub seed: u64,
+}
+
+impl RunState {
+    /// The random numbers of training step `step`, e.g. to sample its batch. Every step has
+    /// its own, so a resumed run draws the same ones as a run that never stopped.
+    pub fn rng(&self, step: usize) -> StdRng {
+        StdRng::seed_from_u64(random::mix(self.seed, step as u64))
+    }
+}
+
+#[cfg(feature = "serde")]
+fn to_string<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+#[cfg(feature = "serde")]
+fn from_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    value
+        .parse()
+        .map_err(|_| serde::de::Error::custom(format!("invalid seed `{}`", value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{history::GameHistory, mlp::MlpModel, replay::ReplayConfig};
+    use rand::Rng;
+
+    /// Three short games of two actions, with three values in every observation.
+    fn buffer() -> ReplayBuffer {
+        let mut buffer = ReplayBuffer::new(ReplayConfig {
+            batch_size: 4,
+            num_unroll_steps: 2,
+            ..ReplayConfig::default()
+        });
+        for len in [3, 5, 2] {
+            buffer.save_game(GameHistory {
+                observations: (0..len).map(|i| vec![i as f32, 1., -1.]).collect(),
+                to_play: vec![0; len],
+                actions: (0..len).map(|i| i % 2).collect(),
+                rewards: vec![1.; len],
+                child_visits: vec![vec![0.5, 0.5]; len],
+                root_values: vec![0.; len],
+                legal_actions: vec![vec![0, 1]; len],
+                final_value: None,
+            });
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_run_state() {
+        let state = RunState { seed: u64::MAX - 1 };
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&state).unwrap();
+            assert_eq!(json, format!(r#"{{"seed":"{}"}}"#, u64::MAX - 1));
+            assert_eq!(serde_json::from_str::<RunState>(&json).unwrap(), state);
+        }
+
+        let draw = |step| state.rng(step).gen::<u64>();
+        assert_eq!(draw(3), draw(3));
+        assert_ne!(draw(3), draw(4));
+        assert_ne!(draw(3), RunState { seed: 0 }.rng(3).gen::<u64>());
+    }
+
+    #[test]
+    fn test_trainer() {
+        let model = MlpModel::new(3, 8, 2, &mut StdRng::seed_from_u64(0));
+        let mut trainer = Trainer::new(model, OptimizerConfig::default());
+        let run = RunState { seed: 7 };
+        let empty = ReplayBuffer::new(ReplayConfig::default());
+        assert!(trainer.train(&run, 0, &empty).is_err());
+        let (losses, learning_rate) = trainer.train(&run, 0, &buffer()).unwrap();
+        assert!(losses.total() > 0.);
+        assert_eq!(learning_rate, 0.05);
+        assert_eq!(trainer.optimizer.steps(), 1);
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_resume() {
+        use crate::{model::Model, optimizer::OptimizerKind, replay::Snapshot};
+
+        let config = OptimizerConfig {
+            kind: OptimizerKind::Adam,
+            ..OptimizerConfig::default()
+        };
+        let model = |seed| MlpModel::new(3, 8, 2, &mut StdRng::seed_from_u64(seed));
+        let buffer = buffer();
+        let run = RunState { seed: 7 };
+        let mut uninterrupted = Trainer::new(model(0), config);
+        let mut losses = vec![];
+        for step in 0..6 {
+            losses.push(uninterrupted.train(&run, step, &buffer).unwrap().0);
+        }
+        assert!(losses.windows(2).all(|pair| pair[0] != pair[1]));
+
+        // Stop after 3 steps, then resume from the checkpoint with a new model and a buffer
+        // that had the same games added.
+        let mut trainer = Trainer::new(model(0), config);
+        for step in 0..3 {
+            trainer.train(&run, step, &buffer).unwrap();
+        }
+        let snapshot = serde_json::to_value(buffer.snapshot()).unwrap();
+        let dir = std::env::temp_dir().join(format!("muzero-resume-{}", std::process::id()));
+        trainer
+            .checkpoint(&run, 3, snapshot)
+            .unwrap()
+            .save(&dir)
+            .unwrap();
+        let checkpoint = Checkpoint::load(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let mut resumed = Trainer::new(model(1), config);
+        resumed
+            .load(&checkpoint.weights, &checkpoint.optimizer, checkpoint.step)
+            .unwrap();
+        let run = RunState::deserialize(&checkpoint.run).unwrap();
+        let mut restored = self::buffer();
+        let snapshot = Snapshot::deserialize(&checkpoint.replay_buffer).unwrap();
+        restored.restore(&snapshot).unwrap();
+        for (step, expected) in losses.iter().enumerate().skip(checkpoint.step) {
+            assert_eq!(&resumed.train(&run, step, &restored).unwrap().0, expected);
+        }
+        assert_eq!(resumed.model.tensors(), uninterrupted.model.tensors());
+        assert_eq!(resumed.optimizer.state(), uninterrupted.optimizer.state());
+    }
+}
@@ -0,0 +1,439 @@
+//! The binary format of trajectory files, the streams of [`GameHistory`] records self-play
+//! writes and training reads.
+//!
+//! A file starts with the magic bytes `MZTRAJ` and the format version as a little-endian u16.
+//! Every game follows as its length in bytes, a little-endian u32, and its fields in the order
+//! of [`GameHistory`]: each a little-endian u32 count of items followed by the items, f32s and
+//! u32s in little-endian and nested lists the same way. `final_value` has 0 or 1 items.
+//! Version 1 stored the games as JSON.
+
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    error::{ConfigError, Context, Error, IoError, ParseError, Result},
+    history::GameHistory,
+};
+
+pub const MAGIC: &[u8; 6] = b"MZTRAJ";
+pub const VERSION: u16 = 2;
+pub const HEADER_LEN: usize = MAGIC.len() + 2;
+
+pub fn header() -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[..MAGIC.len()].copy_from_slice(MAGIC);
+    header[MAGIC.len()..].copy_from_slice(&VERSION.to_le_bytes());
+    header
+}
+
+/// Fail unless `header` starts a trajectory file of this version.
+pub fn check_header(header: &[u8]) -> Result<()> {
+    if header.len() < HEADER_LEN || &header[..MAGIC.len()] != MAGIC {
+        return Err(ParseError::Invalid("not a trajectory file".to_string()).into());
+    }
+    let version = u16::from_le_bytes([header[MAGIC.len()], header[MAGIC.len() + 1]]);
+    if version != VERSION {
+        return Err(ParseError::Invalid(format!(
+            "format version {}, expected {}",
+            version, VERSION
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+fn put_len(bytes: &mut Vec<u8>, len: usize) -> Result<()> {
+    let len = u32::try_from(len)
+        .map_err(|_| ConfigError::Unsupported("too many items for the format".to_string()))?;
+    bytes.extend(len.to_le_bytes());
+    Ok(())
+}
+
+fn put_f32s(bytes: &mut Vec<u8>, values: &[f32]) -> Result<()> {
+    put_len(bytes, values.len())?;
+    for value in values {
+        bytes.extend(value.to_le_bytes());
+    }
+    Ok(())
+}
+
+fn put_indices(bytes: &mut Vec<u8>, values: &[usize]) -> Result<()> {
+    put_len(bytes, values.len())?;
+    for &value in values {
+        put_len(bytes, value)?;
+    }
+    Ok(())
+}
+
+/// The fields of a game, without the length in front.
+pub fn encode(history: &GameHistory) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    put_len(&mut bytes, history.observations.len())?;
+    for observation in &history.observations {
+        put_f32s(&mut bytes, observation)?;
+    }
+    put_indices(&mut bytes, &history.to_play)?;
+    put_indices(&mut bytes, &history.actions)?;
+    put_f32s(&mut bytes, &history.rewards)?;
+    put_len(&mut bytes, history.child_visits.len())?;
+    for visits in &history.child_visits {
+        put_f32s(&mut bytes, visits)?;
+    }
+    put_f32s(&mut bytes, &history.root_values)?;
+    put_len(&mut bytes, history.legal_actions.len())?;
+    for actions in &history.legal_actions {
+        put_indices(&mut bytes, actions)?;
+    }
+    put_f32s(&mut bytes, history.final_value.as_slice())?;
+    Ok(bytes)
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl Decoder<'_> {
+    fn word(&mut self) -> Result<[u8; 4]> {
+        if self.bytes.len() < 4 {
+            return Err(ParseError::Invalid("the game ends early".to_string()).into());
+        }
+        let (word, rest) = self.bytes.split_at(4);
+        self.bytes = rest;
+        Ok(word.try_into().unwrap())
+    }
+
+    fn index(&mut self) -> Result<usize> {
+        Ok(u32::from_le_bytes(self.word()?) as usize)
+    }
+
+    /// A count of items of at least `size` bytes each, checked against the bytes left so that
+    /// a corrupt count can't allocate more than the game.
+    fn len(&mut self, size: usize) -> Result<usize> {
+        let len = self.index()?;
+        if len * size > self.bytes.len() {
+            return Err(ParseError::Invalid("the game ends early".to_string()).into());
+        }
+        Ok(len)
+    }
+
+    fn f32s(&mut self) -> Result<Vec<f32>> {
+        (0..self.len(4)?)
+            .map(|_| Ok(f32::from_le_bytes(self.word()?)))
+            .collect()
+    }
+
+    fn indices(&mut self) -> Result<Vec<usize>> {
+        (0..self.len(4)?).map(|_| self.index()).collect()
+    }
+}
+
+/// The game [`encode`] made `bytes` of.
+pub fn decode(bytes: &[u8]) -> Result<GameHistory> {
+    let mut decoder = Decoder { bytes };
+    let d = &mut decoder;
+    let history = GameHistory {
+        observations: (0..d.len(4)?).map(|_| d.f32s()).collect::<Result<_, _>>()?,
+        to_play: d.indices()?,
+        actions: d.indices()?,
+        rewards: d.f32s()?,
+        child_visits: (0..d.len(4)?).map(|_| d.f32s()).collect::<Result<_, _>>()?,
+        root_values: d.f32s()?,
+        legal_actions: (0..d.len(4)?)
+            .map(|_| d.indices())
+            .collect::<Result<_, _>>()?,
+        final_value: match d.f32s()?[..] {
+            [] => None,
+            [value] => Some(value),
+            _ => return Err(ParseError::Invalid("more than one final value".to_string()).into()),
+        },
+    };
+    if !decoder.bytes.is_empty() {
+        return Err(ParseError::Invalid("trailing bytes after the game".to_string()).into());
+    }
+    Ok(history)
+}
+
+/// The record of a game: its length and its fields.
+pub fn record(history: &GameHistory) -> Result<Vec<u8>> {
+    let game = encode(history)?;
+    let mut record = Vec::with_capacity(4 + game.len());
+    put_len(&mut record, game.len())?;
+    record.extend(game);
+    Ok(record)
+}
+
+/// Writes games to a trajectory file, as self-play does.
+pub struct TrajectoryWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> TrajectoryWriter<W> {
+    /// A writer starting a new stream on `writer`.
+    pub fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(&header())?;
+        Ok(Self { writer })
+    }
+
+    pub fn write(&mut self, history: &GameHistory) -> Result<()> {
+        self.writer.write_all(&record(history)?)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+impl TrajectoryWriter<BufWriter<File>> {
+    /// A writer to a new file at `path`, replacing any file there.
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).map_err(IoError::path("create", path))?;
+        Self::new(BufWriter::new(file))
+    }
+
+    /// A writer adding games to the end of the file at `path`, creating it if it doesn't
+    /// exist.
+    pub fn append(path: &Path) -> Result<Self> {
+        let context = || format!("failed to open {}", path.display());
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .with_context(context)?;
+        if file.metadata().with_context(context)?.len() == 0 {
+            return Self::new(BufWriter::new(file));
+        }
+        let mut header = [0; HEADER_LEN];
+        file.read_exact(&mut header)
+            .map_err(Error::from)
+            .and_then(|()| check_header(&header))
+            .with_context(context)?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+        })
+    }
+}
+
+/// Reads the games of a trajectory file one at a time, as training does. A game cut off at
+/// the end of the file, e.g. by a crash of its writer, is skipped with a warning.
+pub struct TrajectoryReader<R: Read> {
+    reader: R,
+    /// Games read so far.
+    games: usize,
+}
+
+impl<R: Read> TrajectoryReader<R> {
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut header = [0; HEADER_LEN];
+        reader
+            .read_exact(&mut header)
+            .context("not a trajectory file")?;
+        check_header(&header)?;
+        Ok(Self { reader, games: 0 })
+    }
+
+    /// The next game, or `None` at the end of the stream.
+    pub fn read(&mut self) -> Result<Option<GameHistory>> {
+        let mut len = [0; 4];
+        match read_all(&mut self.reader, &mut len)? {
+            0 => return Ok(None),
+            4 => {}
+            _ => return Ok(self.torn()),
+        }
+        // Read what's there rather than trusting a corrupt length with the allocation.
+        let len = u32::from_le_bytes(len) as usize;
+        let mut game = vec![];
+        (&mut self.reader).take(len as u64).read_to_end(&mut game)?;
+        if game.len() < len {
+            return Ok(self.torn());
+        }
+        self.games += 1;
+        decode(&game)
+            .map(Some)
+            .with_context(|| format!("in game {}", self.games))
+    }
+
+    fn torn(&self) -> Option<GameHistory> {
+        tracing::warn!("skipping an incomplete game after game {}", self.games);
+        None
+    }
+}
+
+impl TrajectoryReader<BufReader<File>> {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(IoError::path("open", path))?;
+        Self::new(BufReader::new(file)).with_context(|| format!("in {}", path.display()))
+    }
+}
+
+impl<R: Read> Iterator for TrajectoryReader<R> {
+    type Item = Result<GameHistory>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+/// Fill as much of `buf` as the reader has left, returning how much that was.
+fn read_all(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// What a trajectory file holds, as `muzero inspect-data` shows it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub games: usize,
+    pub positions: usize,
+    pub min_length: usize,
+    pub max_length: usize,
+    /// Games cut off by a step limit rather than ended.
+    pub truncated: usize,
+    /// Games recorded with their legal actions, which can be reanalyzed.
+    pub reanalyzable: usize,
+    pub total_reward: f64,
+    /// The size of the first observation and of the action space.
+    pub observation_size: usize,
+    pub action_space_size: usize,
+    /// How many positions each player was to play.
+    pub players: Vec<usize>,
+}
+
+impl Summary {
+    pub fn add(&mut self, history: &GameHistory) {
+        if self.games == 0 {
+            self.min_length = history.len();
+            self.observation_size = history.observations.first().map_or(0, Vec::len);
+            self.action_space_size = history.child_visits.first().map_or(0, Vec::len);
+        }
+        self.games += 1;
+        self.positions += history.len();
+        self.min_length = self.min_length.min(history.len());
+        self.max_length = self.max_length.max(history.len());
+        self.truncated += history.final_value.is_some() as usize;
+        self.reanalyzable += (history.legal_actions.len() == history.len()) as usize;
+        self.total_reward += history.rewards.iter().map(|&r| r as f64).sum::<f64>();
+        for &player in &history.to_play {
+            if self.players.len() <= player {
+                self.players.resize(player + 1, 0);
+            }
+            self.players[player] += 1;
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let games = self.games.max(1) as f64;
+        writeln!(f, "games:             {}", self.games)?;
+        writeln!(f, "positions:         {}", self.positions)?;
+        writeln!(
+            f,
+            "game length:       {} to {}, {:.1} on average",
+            self.min_length,
+            self.max_length,
+            self.positions as f64 / games
+        )?;
+        writeln!(f, "truncated games:   {}", self.truncated)?;
+        writeln!(f, "reanalyzable:      {}", self.reanalyzable)?;
+        writeln!(f, "reward per game:   {:.3}", self.total_reward / games)?;
+        writeln!(f, "observation size:  {}", self.observation_size)?;
+        writeln!(f, "action space size: {}", self.action_space_size)?;
+        let players: Vec<_> = self.players.iter().map(|n| n.to_string()).collect();
+        write!(f, "positions by player to play: {}", players.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(len: usize) -> GameHistory {
+        GameHistory {
+            observations: vec![vec![1., -0.5]; len],
+            to_play: (0..len).map(|i| i % 2).collect(),
+            actions: vec![3; len],
+            rewards: vec![0.5; len],
+            child_visits: vec![vec![0.25; 4]; len],
+            root_values: vec![0.1; len],
+            legal_actions: vec![vec![0, 3]; len],
+            final_value: None,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let truncated = GameHistory {
+            final_value: Some(0.75),
+            legal_actions: vec![],
+            ..history(3)
+        };
+        for history in [history(0), history(2), truncated] {
+            assert_eq!(decode(&encode(&history).unwrap()).unwrap(), history);
+        }
+        let bytes = encode(&history(2)).unwrap();
+        assert!(decode(&bytes[..bytes.len() - 1]).is_err());
+        // A huge count doesn't allocate.
+        assert!(decode(&[0xff; 8]).is_err());
+    }
+
+    #[test]
+    fn test_stream() {
+        let mut writer = TrajectoryWriter::new(vec![]).unwrap();
+        for len in 1..=3 {
+            writer.write(&history(len)).unwrap();
+        }
+        let bytes = writer.writer;
+        let games: Vec<_> = TrajectoryReader::new(&bytes[..])
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(games, [history(1), history(2), history(3)]);
+
+        // A writer that crashed in the middle of the last game.
+        let games = TrajectoryReader::new(&bytes[..bytes.len() - 5]).unwrap();
+        assert_eq!(games.count(), 2);
+
+        assert!(TrajectoryReader::new(&b"{\"observations\": []}"[..]).is_err());
+        let mut old = header();
+        old[MAGIC.len()] = 1;
+        assert!(TrajectoryReader::new(&old[..]).is_err());
+    }
+
+    #[test]
+    fn test_append() {
+        let path = std::env::temp_dir().join(format!("muzero-append-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        for len in 1..=2 {
+            let mut writer = TrajectoryWriter::append(&path).unwrap();
+            writer.write(&history(len)).unwrap();
+            writer.flush().unwrap();
+        }
+        let mut summary = Summary::default();
+        for history in TrajectoryReader::open(&path).unwrap() {
+            summary.add(&history.unwrap());
+        }
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(summary.games, 2);
+        assert_eq!(summary.positions, 3);
+        assert_eq!((summary.min_length, summary.max_length), (1, 2));
+        assert_eq!(summary.reanalyzable, 2);
+        assert_eq!(summary.total_reward, 1.5);
+        assert_eq!(summary.action_space_size, 4);
+        assert_eq!(summary.players, [2, 1]);
+        assert!(summary.to_string().contains("1 to 2, 1.5 on average"));
+    }
+}
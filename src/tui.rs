@@ -0,0 +1,505 @@
+//! A full-screen terminal UI for playing against the search. Moves are picked with the cursor
+//! keys, the last move is highlighted and the visit counts of the agent's search are shown
+//! while it runs.
+//!
+//! Two-player games whose actions are the cells of their board, like Gomoku or Go, are drawn
+//! as a grid from their observation; other games are shown as text next to a list of moves.
+
+use anyhow::Context;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    style::Style,
+    text::{Line, Span},
+    widgets::Paragraph,
+    DefaultTerminal,
+};
+use std::io;
+
+use muzero_rs::{
+    dyn_game::DynGame,
+    mcts::{sample_outcome, MctsConfig},
+    random,
+    strength::Handicap,
+    Game, Mcts,
+};
+
+use crate::BoxedGame;
+
+const HELP: &str = "arrows/hjkl move, enter plays, p passes, u undoes, q quits";
+
+/// How many moves of the list, and of the search, are shown at once.
+const LIST_LENGTH: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Char(char),
+}
+
+/// The key of a key press, if the UI uses it.
+fn key(event: KeyEvent) -> Option<Key> {
+    if event.kind != KeyEventKind::Press {
+        return None;
+    }
+    Some(match event.code {
+        // Signals are off in raw mode.
+        KeyCode::Char('c') if event.modifiers.contains(KeyModifiers::CONTROL) => Key::Char('q'),
+        KeyCode::Enter | KeyCode::Char(' ') => Key::Enter,
+        KeyCode::Up | KeyCode::Char('k') => Key::Up,
+        KeyCode::Down | KeyCode::Char('j') => Key::Down,
+        KeyCode::Left | KeyCode::Char('h') => Key::Left,
+        KeyCode::Right | KeyCode::Char('l') => Key::Right,
+        KeyCode::Char(c) if c.is_ascii_graphic() => Key::Char(c),
+        _ => return None,
+    })
+}
+
+/// Keeps the terminal in raw mode on the alternate screen until dropped.
+struct Terminal(DefaultTerminal);
+
+impl Terminal {
+    fn new() -> anyhow::Result<Self> {
+        Ok(Self(
+            ratatui::try_init().context("the TUI needs a terminal")?,
+        ))
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        ratatui::restore();
+    }
+}
+
+/// The rows and columns of the board of a two-player game whose actions are its cells, in
+/// row-major order and maybe followed by a pass, and whose first two observation planes hold
+/// the pieces of each player.
+fn board_shape(game: &dyn DynGame) -> Option<(usize, usize)> {
+    match game.observation_shape()[..] {
+        [planes, rows, cols]
+            if planes >= 2
+                && game.num_players() == 2
+                && (rows * cols..=rows * cols + 1).contains(&game.action_space_size()) =>
+        {
+            Some((rows, cols))
+        }
+        _ => None,
+    }
+}
+
+/// Everything undoing a move restores.
+#[derive(Clone)]
+struct Position {
+    game: BoxedGame,
+    last_move: Option<usize>,
+    /// The rewards so far, the score of single-player games.
+    score: f32,
+}
+
+struct App {
+    position: Position,
+    /// Whether the human plays each player.
+    humans: Vec<bool>,
+    /// The positions before each move of the human.
+    history: Vec<Position>,
+    board: Option<(usize, usize)>,
+    /// The action under the cursor.
+    cursor: usize,
+    /// The visit counts of the agent's latest search.
+    visits: Vec<usize>,
+    status: String,
+}
+
+impl App {
+    fn new(game: BoxedGame, humans: Vec<bool>) -> Self {
+        let board = board_shape(game.as_ref());
+        let cursor = match board {
+            Some((rows, cols)) => rows / 2 * cols + cols / 2,
+            None => 0,
+        };
+        Self {
+            position: Position {
+                game,
+                last_move: None,
+                score: 0.,
+            },
+            humans,
+            history: vec![],
+            board,
+            cursor,
+            visits: vec![],
+            status: String::new(),
+        }
+    }
+
+    fn game(&self) -> &BoxedGame {
+        &self.position.game
+    }
+
+    fn play(&mut self, action: usize) -> anyhow::Result<()> {
+        self.position.score += self.position.game.step(action)?;
+        self.position.last_move = Some(action);
+        Ok(())
+    }
+
+    /// Play for the agent, drawing the search as it goes.
+    fn think(
+        &mut self,
+        terminal: &mut Terminal,
+        mcts: &Mcts<BoxedGame>,
+        simulations: usize,
+        handicap: Option<&Handicap>,
+    ) -> anyhow::Result<()> {
+        self.status = "The agent is thinking...".to_string();
+        let game = self.game().clone();
+        let mut drawn = Ok(());
+        let stats = mcts.search_statistics_with_progress(&game, simulations / 20, |stats| {
+            self.visits.clone_from(&stats.visit_counts);
+            if drawn.is_ok() {
+                drawn = self.draw(terminal);
+            }
+        });
+        drawn?;
+        self.visits = stats.visit_counts.clone();
+        let action = match handicap {
+            Some(handicap) => {
+                let visits: Vec<_> = stats.visit_counts.iter().copied().enumerate().collect();
+                handicap.select(&visits, &game.get_available_moves())
+            }
+            None => stats.select_action(0.),
+        };
+        self.status = format!("The agent played {}", game.action_name(action));
+        self.play(action)
+    }
+
+    /// Apply a key of the human, returning false to quit.
+    fn handle(&mut self, key: Key) -> anyhow::Result<bool> {
+        match key {
+            Key::Char('q') => return Ok(false),
+            Key::Char('u') => match self.history.pop() {
+                Some(position) => {
+                    self.position = position;
+                    self.status = "Undone".to_string();
+                }
+                None => self.status = "Nothing to undo".to_string(),
+            },
+            _ if self.game().done() => {}
+            Key::Enter => self.play_human(self.cursor)?,
+            Key::Char('p') => match self.board {
+                Some((rows, cols)) => self.play_human(rows * cols)?,
+                None => self.status = "There's no pass in this game".to_string(),
+            },
+            Key::Up | Key::Down | Key::Left | Key::Right => self.move_cursor(key),
+            Key::Char(_) => self.status = HELP.to_string(),
+        }
+        Ok(true)
+    }
+
+    fn play_human(&mut self, action: usize) -> anyhow::Result<()> {
+        if !self.game().legal_actions().contains(&action) {
+            self.status = format!("{} isn't legal", self.game().action_name(action));
+            return Ok(());
+        }
+        self.history.push(self.position.clone());
+        self.visits.clear();
+        self.status.clear();
+        self.play(action)
+    }
+
+    fn move_cursor(&mut self, key: Key) {
+        match self.board {
+            Some((rows, cols)) => {
+                let (mut row, mut col) = (self.cursor / cols, self.cursor % cols);
+                match key {
+                    Key::Up => row = row.saturating_sub(1),
+                    Key::Down => row = (row + 1).min(rows - 1),
+                    Key::Left => col = col.saturating_sub(1),
+                    Key::Right => col = (col + 1).min(cols - 1),
+                    _ => {}
+                }
+                self.cursor = row * cols + col;
+            }
+            None => {
+                let moves = self.game().legal_actions();
+                let i = moves.iter().position(|&action| action == self.cursor);
+                let i = match (key, i) {
+                    (Key::Up | Key::Left, Some(i)) => i.saturating_sub(1),
+                    (Key::Down | Key::Right, Some(i)) => (i + 1).min(moves.len() - 1),
+                    _ => 0,
+                };
+                if let Some(&action) = moves.get(i) {
+                    self.cursor = action;
+                }
+            }
+        }
+    }
+
+    /// Keep the cursor of the move list on a legal move.
+    fn snap_cursor(&mut self) {
+        let moves = self.game().legal_actions();
+        if self.board.is_none() && !moves.contains(&self.cursor) {
+            if let Some(&action) = moves.first() {
+                self.cursor = action;
+            }
+        }
+    }
+
+    fn draw(&self, terminal: &mut Terminal) -> io::Result<()> {
+        let lines = self.render();
+        terminal.0.draw(|frame| {
+            frame.render_widget(Paragraph::new(lines), frame.area());
+        })?;
+        Ok(())
+    }
+
+    fn render(&self) -> Vec<Line<'static>> {
+        let game = self.game();
+        let mut lines = vec![Line::from(self.title()), Line::default()];
+        match self.board {
+            Some((rows, cols)) => lines.extend(self.render_board(rows, cols)),
+            None => {
+                lines.extend(
+                    game.to_string()
+                        .lines()
+                        .map(|line| Line::from(line.to_string())),
+                );
+                if !game.done() {
+                    lines.push(Line::default());
+                    lines.extend(self.render_moves().into_iter().map(Line::from));
+                }
+            }
+        }
+        lines.push(Line::default());
+        lines.extend(self.render_search().into_iter().map(Line::from));
+        lines.push(Line::default());
+        lines.push(Line::from(self.status.clone()));
+        lines.push(Line::from(HELP));
+        lines
+    }
+
+    fn title(&self) -> String {
+        let game = self.game();
+        if !game.done() {
+            let player = game.to_play();
+            return match self.humans.iter().filter(|&&human| human).count() {
+                1 if self.humans[player] => "Your move".to_string(),
+                _ => format!("Player {} to move", player),
+            };
+        }
+        crate::outcome(game, &self.humans, self.position.score)
+    }
+
+    /// The cells as `X`, `O` or `.`, with the cursor in reverse video and the last move
+    /// in bold yellow.
+    fn render_board(&self, rows: usize, cols: usize) -> Vec<Line<'static>> {
+        let observation = self.game().observation();
+        let area = rows * cols;
+        (0..rows)
+            .map(|row| {
+                let mut line = Line::default();
+                for cell in row * cols..(row + 1) * cols {
+                    let piece = if observation[cell] > 0. {
+                        'X'
+                    } else if observation[area + cell] > 0. {
+                        'O'
+                    } else {
+                        '.'
+                    };
+                    let style = if cell == self.cursor && !self.game().done() {
+                        Style::new().reversed()
+                    } else if Some(cell) == self.position.last_move {
+                        Style::new().bold().yellow()
+                    } else {
+                        Style::new()
+                    };
+                    line.push_span(" ");
+                    line.push_span(Span::styled(piece.to_string(), style));
+                }
+                line
+            })
+            .collect()
+    }
+
+    /// The legal moves around the cursor.
+    fn render_moves(&self) -> Vec<String> {
+        let game = self.game();
+        let moves = game.legal_actions();
+        let i = moves
+            .iter()
+            .position(|&action| action == self.cursor)
+            .unwrap_or(0);
+        let start = i.saturating_sub(LIST_LENGTH / 2);
+        moves
+            .iter()
+            .skip(start)
+            .take(LIST_LENGTH)
+            .map(|&action| {
+                let marker = if action == self.cursor { '>' } else { ' ' };
+                format!("{} {}", marker, game.action_name(action))
+            })
+            .collect()
+    }
+
+    /// The most visited moves of the latest search.
+    fn render_search(&self) -> Vec<String> {
+        let total: usize = self.visits.iter().sum();
+        if total == 0 {
+            return vec![];
+        }
+        let mut actions: Vec<_> = (0..self.visits.len())
+            .filter(|&action| self.visits[action] > 0)
+            .collect();
+        actions.sort_by_key(|&action| std::cmp::Reverse(self.visits[action]));
+        let mut lines = vec![format!("Search, {} simulations:", total)];
+        for &action in actions.iter().take(LIST_LENGTH) {
+            let share = self.visits[action] as f32 / total as f32;
+            lines.push(format!(
+                "  {:<12} {:>6} {}",
+                self.game().action_name(action),
+                self.visits[action],
+                "#".repeat((share * 30.).round() as usize)
+            ));
+        }
+        lines
+    }
+}
+
+/// Play `game`, the human playing the players in `humans` and the search the others, until
+/// the human quits.
+pub(crate) fn run(
+    game: BoxedGame,
+    mcts: MctsConfig,
+    humans: Vec<bool>,
+    handicap: Option<Handicap>,
+) -> anyhow::Result<()> {
+    let simulations = mcts.num_simulations;
+    let mcts = Mcts::<BoxedGame>::with_config(mcts);
+    let mut app = App::new(game, humans);
+    let mut terminal = Terminal::new()?;
+    loop {
+        let game = app.game();
+        if !game.done() {
+            let outcomes = game.chance_outcomes();
+            if !outcomes.is_empty() {
+                app.play(sample_outcome(&outcomes, &mut random::rng()))?;
+                continue;
+            }
+            if !app.humans[game.to_play()] {
+                app.think(&mut terminal, &mcts, simulations, handicap.as_ref())?;
+                continue;
+            }
+        }
+        app.snap_cursor();
+        app.draw(&mut terminal)?;
+        // Any other event, like a resize, redraws.
+        if let Event::Key(event) = event::read()? {
+            match key(event) {
+                Some(key) if !app.handle(key)? => break,
+                _ => {}
+            }
+        }
+    }
+    drop(terminal);
+    println!("{}", app.game());
+    println!("{}", app.title());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use muzero_rs::{dyn_game::boxed, games::tic_tac_toe::TicTacToe};
+
+    fn text(lines: &[Line]) -> String {
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    #[test]
+    fn test_key() {
+        let keys: Vec<_> = [
+            KeyEvent::from(KeyCode::Up),
+            KeyEvent::from(KeyCode::Char('j')),
+            KeyEvent::from(KeyCode::Esc),
+            KeyEvent::from(KeyCode::Char('x')),
+            KeyEvent::from(KeyCode::Enter),
+            KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL),
+        ]
+        .into_iter()
+        .filter_map(key)
+        .collect();
+        assert_eq!(
+            keys,
+            [
+                Key::Up,
+                Key::Down,
+                Key::Char('x'),
+                Key::Enter,
+                Key::Char('q')
+            ]
+        );
+        let mut release = KeyEvent::from(KeyCode::Enter);
+        release.kind = KeyEventKind::Release;
+        assert_eq!(key(release), None);
+    }
+
+    #[test]
+    fn test_board_shape() {
+        let registry = muzero_rs::registry::Registry::default();
+        for (spec, shape) in [
+            ("tictactoe", Some((3, 3))),
+            ("go:5", Some((5, 5))),
+            ("checkers", None),
+            ("2048", None),
+            ("gridworld", None),
+        ] {
+            let game = registry.create(spec).unwrap();
+            assert_eq!(board_shape(game.as_ref()), shape, "{}", spec);
+        }
+    }
+
+    #[test]
+    fn test_play_and_undo() {
+        let mut app = App::new(boxed(TicTacToe::new()), vec![true, false]);
+        assert_eq!(app.cursor, 4);
+        app.handle(Key::Enter).unwrap();
+        assert_eq!(app.position.last_move, Some(4));
+        // Taken.
+        app.handle(Key::Enter).unwrap();
+        assert_eq!(app.status, "(1, 1) isn't legal");
+        app.handle(Key::Up).unwrap();
+        app.handle(Key::Left).unwrap();
+        app.handle(Key::Left).unwrap();
+        app.handle(Key::Enter).unwrap();
+        assert_eq!(app.position.last_move, Some(0));
+        app.handle(Key::Right).unwrap();
+        let lines = app.render();
+        let last_move = &lines[2].spans[1];
+        assert_eq!(last_move.content, "O");
+        assert_eq!(last_move.style, Style::new().bold().yellow());
+        assert_eq!(lines[2].spans[3].style, Style::new().reversed());
+
+        app.handle(Key::Char('u')).unwrap();
+        assert_eq!(app.position.last_move, Some(4));
+        assert_eq!(app.game().legal_actions().len(), 8);
+        assert!(app.handle(Key::Char('u')).unwrap());
+        assert!(app.handle(Key::Char('u')).unwrap());
+        assert_eq!(app.status, "Nothing to undo");
+        assert!(!app.handle(Key::Char('q')).unwrap());
+    }
+
+    #[test]
+    fn test_move_list() {
+        let registry = muzero_rs::registry::Registry::default();
+        let mut app = App::new(registry.create("nim:1,2").unwrap(), vec![true, true]);
+        app.snap_cursor();
+        let moves = app.game().legal_actions();
+        assert_eq!(app.cursor, moves[0]);
+        app.handle(Key::Down).unwrap();
+        assert_eq!(app.cursor, moves[1]);
+        assert!(text(&app.render()).contains(&format!("> {}", app.game().action_name(moves[1]))));
+    }
+}
@@ -0,0 +1,401 @@
+//! The Universal Chess Interface, so that chess GUIs and match runners like cutechess-cli can
+//! play against the search.
+//!
+//! The frontend works on any [`UciGame`]; [`Chess`](crate::games::chess::Chess), behind the
+//! `chess` feature, implements it through [`parse_move`] and [`format_move`].
+
+use std::{
+    io::{self, BufRead, Write},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::{ParseError, Result, SearchError},
+    game::Game,
+    games::chess::{ChessMove, Color, Role},
+    mcts::{Mcts, MctsConfig, SearchResult},
+    pgn::eval_pawns,
+};
+
+/// What the UCI frontend needs from a chess game. The first player is White.
+pub trait UciGame: Game {
+    fn start_position() -> Self;
+
+    fn from_fen(fen: &str) -> Result<Self, ParseError>;
+
+    /// The move named in UCI's long algebraic notation, like `e2e4` or `e7e8q`.
+    fn parse_move(&self, name: &str) -> Result<Self::Action, ParseError>;
+
+    fn format_move(&self, action: &Self::Action) -> String;
+}
+
+fn square_name(square: u8) -> String {
+    format!("{}{}", (b'a' + square % 8) as char, square / 8 + 1)
+}
+
+/// `mv` in UCI's notation. Castling is the king moving two squares, as UCI names it.
+pub fn format_move(mv: ChessMove) -> String {
+    let promotion = match mv.promotion {
+        Some(Role::Knight) => "n",
+        Some(Role::Bishop) => "b",
+        Some(Role::Rook) => "r",
+        Some(Role::Queen) => "q",
+        _ => "",
+    };
+    format!(
+        "{}{}{}",
+        square_name(mv.from),
+        square_name(mv.to),
+        promotion
+    )
+}
+
+pub fn parse_move(name: &str) -> Result<ChessMove, ParseError> {
+    let square = |name: &[u8]| match name {
+        &[file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Ok((rank - b'1') * 8 + (file - b'a')),
+        _ => Err(ParseError::Invalid(format!(
+            "invalid move `{}`",
+            String::from_utf8_lossy(name)
+        ))),
+    };
+    let bytes = name.as_bytes();
+    if !(4..=5).contains(&bytes.len()) {
+        return Err(ParseError::Invalid(format!("invalid move `{}`", name)));
+    }
+    let promotion = match bytes.get(4) {
+        None => None,
+        Some(b'n') => Some(Role::Knight),
+        Some(b'b') => Some(Role::Bishop),
+        Some(b'r') => Some(Role::Rook),
+        Some(b'q') => Some(Role::Queen),
+        Some(_) => {
+            return Err(ParseError::Invalid(format!(
+                "invalid promotion in `{}`",
+                name
+            )))
+        }
+    };
+    Ok(ChessMove {
+        from: square(&bytes[..2])?,
+        to: square(&bytes[2..4])?,
+        promotion,
+    })
+}
+
+#[cfg(feature = "chess")]
+impl UciGame for crate::games::chess::Chess {
+    fn start_position() -> Self {
+        Self::new()
+    }
+
+    fn from_fen(fen: &str) -> Result<Self, ParseError> {
+        Self::from_notation(fen)
+    }
+
+    fn parse_move(&self, name: &str) -> Result<ChessMove, ParseError> {
+        parse_move(name)
+    }
+
+    fn format_move(&self, action: &ChessMove) -> String {
+        format_move(*action)
+    }
+}
+
+/// How long to search, from the parameters of `go`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Limit {
+    Nodes(usize),
+    Time(Duration),
+}
+
+/// A UCI engine: the position set by the GUI and the search that plays from it.
+pub struct UciEngine<G: UciGame> {
+    game: G,
+    config: MctsConfig,
+}
+
+impl<G: UciGame> UciEngine<G> {
+    pub fn new(config: MctsConfig) -> Self {
+        Self {
+            game: G::start_position(),
+            config,
+        }
+    }
+
+    /// `position [startpos | fen <fen>] [moves <move>...]`
+    fn position(&mut self, args: &[&str]) -> Result<()> {
+        let moves = args.iter().position(|&arg| arg == "moves");
+        let (setup, moves) = match moves {
+            Some(i) => (&args[..i], &args[i + 1..]),
+            None => (args, &[][..]),
+        };
+        let mut game = match setup {
+            ["startpos"] => G::start_position(),
+            ["fen", fen @ ..] => G::from_fen(&fen.join(" "))?,
+            _ => return Err(ParseError::Invalid("expected startpos or fen".to_string()).into()),
+        };
+        for name in moves {
+            let action = game.parse_move(name)?;
+            game.step(action)?;
+        }
+        self.game = game;
+        Ok(())
+    }
+
+    /// The limit of `go` with `args`, sharing the clock out over about 30 more moves.
+    fn limit(&self, args: &[&str]) -> Result<Limit> {
+        let value = |name: &str| -> Result<Option<u64>> {
+            match args.iter().position(|&arg| arg == name) {
+                Some(i) => {
+                    let value = args
+                        .get(i + 1)
+                        .ok_or_else(|| ParseError::Invalid(format!("{} needs a value", name)))?;
+                    Ok(Some(value.parse()?))
+                }
+                None => Ok(None),
+            }
+        };
+        if let Some(nodes) = value("nodes")? {
+            return Ok(Limit::Nodes(nodes as usize));
+        }
+        if let Some(time) = value("movetime")? {
+            return Ok(Limit::Time(Duration::from_millis(time)));
+        }
+        let white = self.game.player_index(&self.game.current_player()) == 0;
+        let (time, increment) = if white {
+            (value("wtime")?, value("winc")?)
+        } else {
+            (value("btime")?, value("binc")?)
+        };
+        Ok(match time {
+            Some(time) => {
+                let increment = increment.unwrap_or(0);
+                Limit::Time(Duration::from_millis(time / 30 + increment / 2))
+            }
+            None => Limit::Nodes(self.config.num_simulations),
+        })
+    }
+
+    /// The `info` line of a finished search.
+    fn info(&self, result: &SearchResult<G::Action>, elapsed: Duration) -> String {
+        let score = match result.proof {
+            Some(proof) if proof.value != 0. => {
+                let moves = (proof.moves as i64 + 1) / 2;
+                format!("mate {}", if proof.value > 0. { moves } else { -moves })
+            }
+            _ => format!(
+                "cp {}",
+                (100. * eval_pawns(result.value(), Color::White)).round()
+            ),
+        };
+        let mut game = self.game.clone();
+        let mut pv = vec![];
+        for action in &result.principal_variation {
+            pv.push(game.format_move(action));
+            if game.step(action.clone()).is_err() {
+                break;
+            }
+        }
+        let millis = elapsed.as_millis().max(1);
+        format!(
+            "info depth {} nodes {} time {} nps {} score {} pv {}",
+            result.max_depth,
+            result.nodes,
+            millis,
+            result.nodes as u128 * 1000 / millis,
+            score,
+            pv.join(" ")
+        )
+    }
+
+    /// `go`, answered with an `info` line and the `bestmove`.
+    fn go(&mut self, args: &[&str]) -> Result<String> {
+        if self.game.done() {
+            return Err(SearchError::GameOver.into());
+        }
+        let start = Instant::now();
+        let result = match self.limit(args)? {
+            Limit::Nodes(simulations) => Mcts::with_config(MctsConfig {
+                num_simulations: simulations,
+                ..self.config.clone()
+            })
+            .search(&self.game),
+            Limit::Time(time) => {
+                Mcts::with_config(self.config.clone()).search_for(&self.game, time)
+            }
+        };
+        Ok(format!(
+            "{}\nbestmove {}",
+            self.info(&result, start.elapsed()),
+            self.game.format_move(&result.action)
+        ))
+    }
+
+    /// Answer the commands of `input` on `output` until `quit` or the end of the input.
+    /// Searches run to their limit, so `stop` has nothing to stop.
+    pub fn run(&mut self, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<()> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let words: Vec<_> = line.split_whitespace().collect();
+            let Some((&command, args)) = words.split_first() else {
+                continue;
+            };
+            let response = match command {
+                "uci" => Ok(format!(
+                    "id name muzero-rs {}\nid author the muzero-rs authors\nuciok",
+                    env!("CARGO_PKG_VERSION")
+                )),
+                "isready" => Ok("readyok".to_string()),
+                "ucinewgame" => {
+                    self.game = G::start_position();
+                    continue;
+                }
+                "position" => match self.position(args) {
+                    Ok(()) => continue,
+                    Err(e) => Err(e),
+                },
+                "go" => self.go(args),
+                "quit" => return Ok(()),
+                // Unknown commands, options and `stop` are ignored, as UCI asks.
+                _ => continue,
+            };
+            match response {
+                Ok(response) => writeln!(output, "{}", response)?,
+                Err(e) => writeln!(output, "info string error: {}", e)?,
+            }
+            output.flush()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::tic_tac_toe::TicTacToe;
+
+    /// TicTacToe with the squares named like the a1 to c3 corner of a chess board.
+    impl UciGame for TicTacToe {
+        fn start_position() -> Self {
+            TicTacToe::new()
+        }
+
+        fn from_fen(_fen: &str) -> Result<Self, ParseError> {
+            Err(ParseError::Invalid("no FEN for TicTacToe".to_string()))
+        }
+
+        fn parse_move(&self, name: &str) -> Result<Self::Action, ParseError> {
+            match name.as_bytes() {
+                &[file @ b'a'..=b'c', rank @ b'1'..=b'3'] => {
+                    Ok(((rank - b'1') as usize, (file - b'a') as usize))
+                }
+                _ => Err(ParseError::Invalid(format!("invalid move `{}`", name))),
+            }
+        }
+
+        fn format_move(&self, &(row, col): &Self::Action) -> String {
+            format!("{}{}", (b'a' + col as u8) as char, row + 1)
+        }
+    }
+
+    #[test]
+    fn test_moves() {
+        let mv = parse_move("e7e8q").unwrap();
+        assert_eq!(
+            mv,
+            ChessMove {
+                from: 52,
+                to: 60,
+                promotion: Some(Role::Queen),
+            }
+        );
+        assert_eq!(format_move(mv), "e7e8q");
+        assert_eq!(format_move(parse_move("e1g1").unwrap()), "e1g1");
+        for name in ["e2", "e2e9", "i2i4", "e7e8k", "e2e4e"] {
+            assert!(parse_move(name).is_err(), "{}", name);
+        }
+    }
+
+    fn session(engine: &mut UciEngine<TicTacToe>, commands: &str) -> Vec<String> {
+        let mut output = vec![];
+        engine.run(&mut commands.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_session() {
+        let mut engine = UciEngine::<TicTacToe>::new(MctsConfig::default());
+        let lines = session(
+            &mut engine,
+            "uci\nsetoption name Hash value 16\nisready\nposition startpos moves a1 b1 a2 b2\n\
+             go nodes 500\nquit\ngo\n",
+        );
+        assert!(lines[0].starts_with("id name muzero-rs"));
+        assert_eq!(lines[2..4], ["uciok", "readyok"]);
+        assert!(lines[4].starts_with("info depth "), "{}", lines[4]);
+        assert!(lines[4].contains(" pv a3"), "{}", lines[4]);
+        // Winning at once.
+        assert_eq!(lines[5], "bestmove a3");
+        assert_eq!(lines.len(), 6);
+
+        let lines = session(
+            &mut engine,
+            "position fen x\nposition startpos moves a1 a1\ngo movetime 20 nodes\n\
+             position startpos\ngo wtime 600 btime 600\n",
+        );
+        assert_eq!(lines[0], "info string error: no FEN for TicTacToe");
+        assert!(lines[1].starts_with("info string error: "));
+        assert_eq!(lines[2], "info string error: nodes needs a value");
+        assert!(lines[4].starts_with("bestmove "));
+    }
+
+    #[cfg(feature = "chess")]
+    #[test]
+    fn test_chess_session() {
+        use crate::games::chess::Chess;
+
+        let mut engine = UciEngine::<Chess>::new(MctsConfig {
+            max_rollout_depth: Some(10),
+            mcts_solver: true,
+            seed: Some(1),
+            ..Default::default()
+        });
+        let mut run = |commands: &str| {
+            let mut output = vec![];
+            engine.run(&mut commands.as_bytes(), &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let output = run("uci\nisready\nposition startpos moves e2e4 e7e5\ngo nodes 200\n");
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines[2..4], ["uciok", "readyok"]);
+        let bestmove = lines[5].strip_prefix("bestmove ").unwrap();
+        let mut game = Chess::new();
+        for name in ["e2e4", "e7e5"] {
+            game.step(parse_move(name).unwrap()).unwrap();
+        }
+        assert!(game
+            .get_available_moves()
+            .contains(&parse_move(bestmove).unwrap()));
+
+        // Scholar's mate.
+        let output = run(
+            "position fen r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5Q2/PPPP1PPP/RNB1K1NR w KQkq - 4 4\n\
+             go nodes 300\nposition startpos moves e2e5\n",
+        );
+        let lines: Vec<_> = output.lines().collect();
+        assert!(lines[0].contains(" score mate 1 "), "{}", lines[0]);
+        assert_eq!(lines[1], "bestmove f3f7");
+        assert!(
+            lines[2].starts_with("info string error: illegal move"),
+            "{}",
+            lines[2]
+        );
+    }
+}
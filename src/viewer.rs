@@ -0,0 +1,126 @@
+//! Stepping through a recorded game on the terminal, one line of input per command.
+
+use std::io::{self, BufRead, Write};
+
+use muzero_rs::record::{GameRecord, MoveRecord};
+
+use crate::BoxedGame;
+
+const HELP: &str = "enter or n steps forward, b back, s to the start, e to the end, a number \
+                    to that move, q quits";
+
+/// The move leading to position `index`, described with its search.
+fn describe(
+    record: &GameRecord,
+    positions: &[BoxedGame],
+    index: usize,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let MoveRecord {
+        action,
+        player,
+        timestamp,
+        search,
+    } = &record.moves[index - 1];
+    let before = &positions[index - 1];
+    let name = before.action_name(*action);
+    match player {
+        Some(player) => write!(output, "Player {} played {}", player, name)?,
+        None => write!(output, "Chance: {}", name)?,
+    }
+    match index.checked_sub(2).map(|previous| &record.moves[previous]) {
+        Some(previous) => {
+            let seconds = timestamp.saturating_sub(previous.timestamp) as f64 / 1000.;
+            writeln!(output, " after {:.1}s", seconds)?;
+        }
+        None => writeln!(output)?,
+    }
+    if let Some(search) = search {
+        writeln!(output, "  search value {:.2}", search.value)?;
+        for &(action, visits) in search.visits.iter().take(3) {
+            writeln!(
+                output,
+                "  {:>8} visits: {}",
+                visits,
+                before.action_name(action)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Show the positions of `record`, which are `positions`, moving between them by the commands
+/// read from `input` until it ends or the viewer quits.
+pub(crate) fn view(
+    record: &GameRecord,
+    positions: &[BoxedGame],
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let last = positions.len() - 1;
+    let mut index = 0;
+    writeln!(output, "{}", HELP)?;
+    loop {
+        writeln!(output, "Position {}/{} of {}", index, last, record.game)?;
+        if index > 0 {
+            describe(record, positions, index, output)?;
+        }
+        writeln!(output, "{}", positions[index])?;
+        write!(output, "> ")?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        match line.trim() {
+            "" | "n" => index = (index + 1).min(last),
+            "b" => index = index.saturating_sub(1),
+            "s" => index = 0,
+            "e" => index = last,
+            "q" => return Ok(()),
+            command => match command.parse::<usize>() {
+                Ok(to) if to <= last => index = to,
+                _ => writeln!(output, "{}", HELP)?,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use muzero_rs::{dyn_game::boxed, games::tic_tac_toe::TicTacToe, record::MoveSearch};
+
+    #[test]
+    fn test_view() {
+        let mut record = GameRecord::new("tictactoe");
+        let search = MoveSearch {
+            value: 0.25,
+            visits: vec![(4, 90), (0, 10)],
+        };
+        record.push(4, Some(0), Some(search));
+        record.push(0, Some(1), None);
+        let positions = record.positions(boxed(TicTacToe::new())).unwrap();
+        let mut input: &[u8] = b"\nx\ne\nb\nq\n0\n";
+        let mut output = vec![];
+        view(&record, &positions, &mut input, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let shown: Vec<_> = output
+            .lines()
+            .filter_map(|line| line.trim_start_matches("> ").strip_prefix("Position "))
+            .collect();
+        assert_eq!(
+            shown,
+            [
+                "0/2 of tictactoe",
+                "1/2 of tictactoe",
+                "1/2 of tictactoe",
+                "2/2 of tictactoe",
+                "1/2 of tictactoe"
+            ]
+        );
+        assert!(output.contains("Player 0 played (1, 1)\n  search value 0.25\n"));
+        assert!(output.contains("      90 visits: (1, 1)"));
+        assert!(output.contains("Player 1 played (0, 0) after "));
+    }
+}
@@ -0,0 +1,138 @@
+//! A JavaScript API for web pages to play against the search in the browser, behind the
+//! `wasm` feature:
+//!
+//! ```text
+//! import init, { new_game } from "./pkg/muzero_rs.js";
+//!
+//! await init();
+//! const game = new_game("tictactoe", 1000);
+//! game.play(4);
+//! const reply = game.agent_move();
+//! console.log(game.board(), game.legal_moves());
+//! ```
+//!
+//! Actions are indices into the action space of the game, as in the [server](crate::server)
+//! API, and chance events are sampled as soon as they are pending. Failing calls throw an
+//! `Error` with the message.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    dyn_game::DynGame,
+    error::Error,
+    mcts::{sample_outcome, Mcts, MctsConfig},
+    random,
+    registry::Registry,
+    Game,
+};
+
+/// A game from the registry and the search that plays it.
+#[wasm_bindgen]
+pub struct WasmGame {
+    game: Box<dyn DynGame>,
+    mcts: Mcts<Box<dyn DynGame>>,
+}
+
+/// Start `name`, a game in the registry like `gomoku:9`, against a search of `simulations`
+/// playouts per move, seeded so that it plays the same moves every time if `seed` is given.
+#[wasm_bindgen]
+pub fn new_game(name: &str, simulations: usize, seed: Option<u32>) -> Result<WasmGame, JsError> {
+    let mut game = WasmGame {
+        game: Registry::default().create(name).map_err(error)?,
+        mcts: Mcts::with_config(MctsConfig {
+            num_simulations: simulations,
+            seed: seed.map(u64::from),
+            ..Default::default()
+        }),
+    };
+    game.resolve_chance()?;
+    Ok(game)
+}
+
+#[wasm_bindgen]
+impl WasmGame {
+    /// The indices of the legal actions.
+    pub fn legal_moves(&self) -> Vec<usize> {
+        self.game.legal_actions()
+    }
+
+    /// The name of the action at `action`, like `(0, 2)`.
+    pub fn action_name(&self, action: usize) -> String {
+        self.game.action_name(action)
+    }
+
+    /// Play the action at `action`.
+    pub fn play(&mut self, action: usize) -> Result<(), JsError> {
+        if !self.game.legal_actions().contains(&action) {
+            return Err(JsError::new(&format!("illegal action {}", action)));
+        }
+        self.game.step(action).map_err(error)?;
+        self.resolve_chance()
+    }
+
+    /// Search, play the best action and return its index.
+    pub fn agent_move(&mut self) -> Result<usize, JsError> {
+        if self.game.done() {
+            return Err(JsError::new("the game is over"));
+        }
+        let action = self.mcts.search(&self.game).action;
+        self.play(action)?;
+        Ok(action)
+    }
+
+    /// The index of the player to move.
+    pub fn to_play(&self) -> usize {
+        self.game.to_play()
+    }
+
+    pub fn done(&self) -> bool {
+        self.game.done()
+    }
+
+    /// The returns of every player once the game is done.
+    pub fn returns(&self) -> Vec<f32> {
+        self.game.returns()
+    }
+
+    /// The board as text.
+    pub fn board(&self) -> String {
+        self.game.to_string()
+    }
+}
+
+impl WasmGame {
+    fn resolve_chance(&mut self) -> Result<(), JsError> {
+        loop {
+            let outcomes = self.game.chance_outcomes();
+            if outcomes.is_empty() || self.game.done() {
+                return Ok(());
+            }
+            let outcome = sample_outcome(&outcomes, &mut random::rng());
+            self.game.step(outcome).map_err(error)?;
+        }
+    }
+}
+
+fn error(e: impl Into<Error>) -> JsError {
+    JsError::new(&format!("{:#}", e.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_game() {
+        let mut game = new_game("tictactoe", 200, Some(1)).unwrap();
+        assert_eq!(game.legal_moves().len(), 9);
+        for action in [0, 3, 1, 4] {
+            game.play(action).unwrap();
+        }
+        assert_eq!(game.action_name(2), "(0, 2)");
+        // X completes the top row.
+        assert_eq!(game.agent_move().unwrap(), 2);
+        assert!(game.done());
+        assert_eq!(game.returns(), [1., -1.]);
+        assert!(game.legal_moves().is_empty());
+    }
+}
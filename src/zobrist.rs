@@ -0,0 +1,72 @@
+//! Zobrist hashing: a state is hashed as the XOR of one random key per (square, piece) on the
+//! board and one per player to move, so a move updates the hash with a couple of XORs.
+
+/// Random keys for every piece on every square, and for every player to move.
+#[derive(Debug, Clone)]
+pub struct ZobristTable {
+    num_pieces: usize,
+    piece_keys: Vec<u64>,
+    player_keys: Vec<u64>,
+}
+
+impl ZobristTable {
+    /// The keys are generated from `seed` with SplitMix64, so they are the same across runs,
+    /// platforms and versions of `rand`, and hashes can be stored in files.
+    pub fn new(num_squares: usize, num_pieces: usize, num_players: usize, seed: u64) -> Self {
+        let mut state = seed;
+        let mut next = || splitmix64(&mut state);
+        let piece_keys = (0..num_squares * num_pieces).map(|_| next()).collect();
+        let player_keys = (0..num_players).map(|_| next()).collect();
+        Self {
+            num_pieces,
+            piece_keys,
+            player_keys,
+        }
+    }
+
+    pub fn piece(&self, square: usize, piece: usize) -> u64 {
+        self.piece_keys[square * self.num_pieces + piece]
+    }
+
+    pub fn player(&self, player: usize) -> u64 {
+        self.player_keys[player]
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// 64-bit FNV-1a, a simple hash that is stable across runs, unlike `DefaultHasher`.
+pub fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_are_deterministic_and_distinct() {
+        let table = ZobristTable::new(9, 2, 2, 42);
+        let again = ZobristTable::new(9, 2, 2, 42);
+        assert_eq!(table.piece_keys, again.piece_keys);
+        assert_eq!(table.player_keys, again.player_keys);
+
+        let mut keys = table.piece_keys.clone();
+        keys.extend(&table.player_keys);
+        keys.sort();
+        keys.dedup();
+        assert_eq!(keys.len(), 9 * 2 + 2);
+        assert_ne!(
+            table.piece(0, 0),
+            ZobristTable::new(9, 2, 2, 43).piece(0, 0)
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! MCTS against a minimax oracle on Tic-tac-toe: whichever side it plays and whatever the
+//! opponent does, the search never loses from the empty board, never plays a move that
+//! changes the result with perfect play, and always blocks the opponent's wins. Plain UCT may
+//! prefer a fork to a win on the spot, as both win for sure; MCTS-Solver must take the win.
+
+use std::collections::HashMap;
+
+use muzero_rs::{
+    games::tic_tac_toe::TicTacToe,
+    mcts::{Mcts, MctsConfig},
+    solver, Game,
+};
+
+type Action = (usize, usize);
+
+const SIMULATIONS: usize = 2000;
+
+/// A search, remembering its moves by position as transpositions are reached many times.
+struct Player {
+    mcts: Mcts<TicTacToe>,
+    mcts_solver: bool,
+    moves: HashMap<String, Action>,
+}
+
+impl Player {
+    fn new(mcts_solver: bool) -> Self {
+        Self {
+            mcts: Mcts::with_config(MctsConfig {
+                num_simulations: SIMULATIONS,
+                mcts_solver,
+                seed: Some(0),
+                ..Default::default()
+            }),
+            mcts_solver,
+            moves: HashMap::new(),
+        }
+    }
+
+    fn select(&mut self, game: &TicTacToe) -> Action {
+        *self
+            .moves
+            .entry(game.to_string())
+            .or_insert_with(|| self.mcts.search(game).action)
+    }
+}
+
+/// The value of `game` with perfect play, for its player to move.
+fn value(game: &TicTacToe) -> f32 {
+    solver::solve(game, usize::MAX).unwrap().value
+}
+
+fn after(game: &TicTacToe, action: Action) -> TicTacToe {
+    let mut game = game.clone();
+    game.step(action).unwrap();
+    game
+}
+
+/// The moves of the player to move that win on the spot.
+fn winning_moves(game: &TicTacToe) -> Vec<Action> {
+    let player = game.player_index(&game.current_player());
+    game.get_available_moves()
+        .into_iter()
+        .filter(|&action| {
+            let next = after(game, action);
+            next.done() && next.returns()[player] > 0.
+        })
+        .collect()
+}
+
+/// Check the move of the search in `game` and in every position reachable after it, trying
+/// every reply of the opponent. Returns the number of positions checked.
+fn check(player: &mut Player, game: &TicTacToe, side: usize) -> usize {
+    if game.done() {
+        assert!(game.returns()[side] >= 0., "lost the game\n{}", game);
+        return 0;
+    }
+    if game.player_index(&game.current_player()) != side {
+        return game
+            .get_available_moves()
+            .into_iter()
+            .map(|action| check(player, &after(game, action), side))
+            .sum();
+    }
+    let action = player.select(game);
+    let next = after(game, action);
+    if !winning_moves(game).is_empty() {
+        if player.mcts_solver {
+            assert!(next.done(), "missed the win in\n{}", game);
+        }
+    } else {
+        // A mark can't make a line for the opponent, so a move that leaves them a win failed
+        // to block it, which matters if another move does.
+        let (blocks, misses): (Vec<_>, Vec<_>) = game
+            .get_available_moves()
+            .into_iter()
+            .partition(|&action| winning_moves(&after(game, action)).is_empty());
+        if !blocks.is_empty() && !misses.is_empty() {
+            assert!(blocks.contains(&action), "didn't block in\n{}", game);
+        }
+    }
+    assert!(
+        -value(&next) >= value(game),
+        "{:?} throws away the result of\n{}",
+        action,
+        game
+    );
+    1 + check(player, &next, side)
+}
+
+#[test]
+fn test_never_loses_as_first_player() {
+    for mcts_solver in [false, true] {
+        assert!(check(&mut Player::new(mcts_solver), &TicTacToe::new(), 0) > 0);
+    }
+}
+
+#[test]
+fn test_never_loses_as_second_player() {
+    for mcts_solver in [false, true] {
+        assert!(check(&mut Player::new(mcts_solver), &TicTacToe::new(), 1) > 0);
+    }
+}